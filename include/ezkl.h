@@ -0,0 +1,56 @@
+/* C ABI of ezkl, built as a shared library with `cargo build --release --features ffi`.
+ *
+ * Inputs are passed as (pointer, length) pairs of bytes, which are only read during the call: .onnx models, keys and KZG params as
+ * the files saved by ezkl, and settings, data and proofs as json. A NULL pointer is only accepted with a length of 0, and is an
+ * error otherwise. Outputs are written to buffers allocated by ezkl, which are
+ * released with ezkl_buffer_free. Functions return EZKL_ERROR on failure, after which ezkl_last_error describes the error. */
+
+#ifndef EZKL_H
+#define EZKL_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define EZKL_OK 0
+#define EZKL_REJECTED 1
+#define EZKL_ERROR -1
+
+typedef struct {
+    uint8_t *data;
+    size_t len;
+} EzklBuffer;
+
+/* The message of the last error raised on the calling thread, or NULL. Valid until the next call on the thread. */
+const char *ezkl_last_error(void);
+
+/* Releases a buffer written by ezkl. */
+void ezkl_buffer_free(EzklBuffer buffer);
+
+/* Writes the default circuit settings (those of the cli) as json to `out`. */
+int ezkl_default_settings(EzklBuffer *out);
+
+/* Generates the proving and verifying keys of the circuit of `model`, built with the json `settings`. */
+int ezkl_setup(const uint8_t *model, size_t model_len,
+               const uint8_t *settings, size_t settings_len,
+               const uint8_t *params, size_t params_len,
+               EzklBuffer *pk_out, EzklBuffer *vk_out);
+
+/* Proves `model` over the json `data`, writing the proof as json to `proof_out`. `transcript` is the name of the transcript,
+ * e.g "blake2b", or "keccak" for proofs verified on the EVM. */
+int ezkl_prove(const uint8_t *data, size_t data_len,
+               const uint8_t *model, size_t model_len,
+               const uint8_t *settings, size_t settings_len,
+               const uint8_t *pk, size_t pk_len,
+               const uint8_t *params, size_t params_len,
+               const uint8_t *transcript, size_t transcript_len,
+               EzklBuffer *proof_out);
+
+/* Verifies the json `proof`, returning EZKL_OK if it is accepted and EZKL_REJECTED if it isn't. */
+int ezkl_verify(const uint8_t *proof, size_t proof_len,
+                const uint8_t *model, size_t model_len,
+                const uint8_t *settings, size_t settings_len,
+                const uint8_t *vk, size_t vk_len,
+                const uint8_t *params, size_t params_len,
+                const uint8_t *transcript, size_t transcript_len);
+
+#endif
@@ -0,0 +1,442 @@
+use crate::commands::{CircuitSettings, TranscriptType};
+use crate::error::EzklError;
+use crate::execute::{create_kzg_proof, verify_kzg_batch, verify_kzg_proof};
+use crate::fieldutils::i32_to_felt;
+use crate::graph::{GraphError, Mode, Model, ModelCircuit, ModelSource, RunArgs};
+#[cfg(feature = "evm")]
+use crate::pfsys::evm::aggregation::gen_aggregation_evm_verifier;
+#[cfg(feature = "evm")]
+use crate::pfsys::evm::split::VerifierCode;
+use crate::pfsys::{
+    circuit_fingerprint, create_keys, load_pk, load_vk, prepare_circuit_and_public_input,
+    prepare_dummy_circuit, read_pk, read_vk, save_pk, save_vk, write_pk, write_vk, ArtifactHashes,
+    Fingerprint, ModelInput, PfsysError, Proof,
+};
+use crate::progress::{Phase, Task};
+use crate::tensor::Tensor;
+use halo2_proofs::dev::MockProver;
+use halo2_proofs::plonk::{ProvingKey, VerifyingKey};
+use halo2_proofs::poly::kzg::commitment::{KZGCommitmentScheme, ParamsKZG};
+use halo2_proofs::poly::kzg::multiopen::{ProverGWC, VerifierGWC};
+use halo2curves::bn256::{Bn256, Fr, G1Affine};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// The keys of the circuit of a model, as generated by [setup].
+#[derive(Debug)]
+pub struct Keys {
+    /// The proving key, which holds the verifying key.
+    pub pk: ProvingKey<G1Affine>,
+    /// The fingerprint of the circuit (model and settings) the keys were generated for, see [circuit_fingerprint].
+    pub fingerprint: Fingerprint,
+}
+
+impl Keys {
+    /// The verifying key, which is all verifiers need.
+    pub fn vk(&self) -> &VerifyingKey<G1Affine> {
+        self.pk.get_vk()
+    }
+
+    /// The proving key, encoded as it is saved by [save_keys] and read by [read_keys].
+    pub fn pk_bytes(&self) -> Result<Vec<u8>, EzklError> {
+        let mut bytes = vec![];
+        write_pk::<KZGCommitmentScheme<Bn256>, _>(&mut bytes, &self.pk, self.fingerprint)?;
+        Ok(bytes)
+    }
+
+    /// The verifying key, encoded as it is saved by [save_keys] and read by [read_verifying_key].
+    pub fn vk_bytes(&self) -> Result<Vec<u8>, EzklError> {
+        let mut bytes = vec![];
+        write_vk::<KZGCommitmentScheme<Bn256>, _>(&mut bytes, self.vk(), self.fingerprint)?;
+        Ok(bytes)
+    }
+}
+
+/// The run args the circuit of the model at `model` is built with.
+fn run_args(model: ModelSource, settings: &CircuitSettings) -> RunArgs {
+    RunArgs::new(model, settings.clone(), Mode::Prove)
+}
+
+/// Loads the .onnx model at `model`, quantized and restricted to a chunk as per `settings`.
+pub fn load_model(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+) -> Result<Model, EzklError> {
+    Ok(Model::from_run_args(&run_args(model.into(), settings))?)
+}
+
+/// Builds the circuit of the .onnx model at `model` over the inputs in `data`, returning it alongside its public inputs.
+pub fn circuit(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    data: &ModelInput,
+) -> Result<(ModelCircuit<Fr>, Vec<Tensor<i32>>), EzklError> {
+    Ok(prepare_circuit_and_public_input(
+        data,
+        &run_args(model.into(), settings),
+    )?)
+}
+
+/// Checks the circuit of the .onnx model at `model` is satisfied by the inputs in `data` with halo2's mock prover, as the `mock`
+/// command does, without generating keys or proofs.
+pub fn mock(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    data: &ModelInput,
+) -> Result<bool, EzklError> {
+    let args = run_args(model.into(), settings);
+    let (circuit, public_inputs) = prepare_circuit_and_public_input::<Fr>(data, &args)?;
+    let public_inputs = public_inputs
+        .into_iter()
+        .map(|i| i.into_iter().map(i32_to_felt::<Fr>).collect())
+        .collect();
+    let prover = args.scoped(|| MockProver::run(settings.logrows, &circuit, public_inputs))?;
+    Ok(prover.verify().is_ok())
+}
+
+/// Generates KZG params for circuits of `2^logrows` rows with a locally sampled (and as such unsafe) toxic waste, for testing
+/// only. See [crate::pfsys::srs::load_srs] to load params from a powers of tau ceremony instead.
+pub fn gen_srs(logrows: u32) -> ParamsKZG<Bn256> {
+    ParamsKZG::new(logrows)
+}
+
+/// Generates the keys of the circuit of the .onnx model at `model` with the KZG `params`.
+pub fn setup(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    params: &ParamsKZG<Bn256>,
+) -> Result<Keys, EzklError> {
+    let args = run_args(model.into(), settings);
+    let fingerprint = circuit_fingerprint(&args)?;
+    let circuit = prepare_dummy_circuit::<Fr>(&Model::from_run_args(&args)?)?;
+    // keygen configures the circuit, which loads its model from the scoped run args
+    let pk = args.scoped(|| create_keys::<KZGCommitmentScheme<Bn256>, Fr>(&circuit, params))?;
+    Ok(Keys { pk, fingerprint })
+}
+
+/// Saves `keys` to `pk_path` and `vk_path`, as the `setup` command does.
+pub fn save_keys(keys: &Keys, pk_path: &PathBuf, vk_path: &PathBuf) -> Result<(), EzklError> {
+    save_pk::<KZGCommitmentScheme<Bn256>>(pk_path, &keys.pk, keys.fingerprint)?;
+    save_vk::<KZGCommitmentScheme<Bn256>>(vk_path, keys.vk(), keys.fingerprint)?;
+    Ok(())
+}
+
+/// Loads the keys saved at `pk_path` by [save_keys] (or the `setup` command), checking they are those of the circuit of the
+/// .onnx model at `model`.
+pub fn load_keys(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    pk_path: &PathBuf,
+) -> Result<Keys, EzklError> {
+    let args = run_args(model.into(), settings);
+    let fingerprint = circuit_fingerprint(&args)?;
+    let pk =
+        args.scoped(|| load_pk::<KZGCommitmentScheme<Bn256>, Fr>(pk_path.clone(), fingerprint))?;
+    Ok(Keys { pk, fingerprint })
+}
+
+/// Loads the verifying key saved at `vk_path`, checking it is that of the circuit of the .onnx model at `model`.
+pub fn load_verifying_key(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    vk_path: &PathBuf,
+) -> Result<VerifyingKey<G1Affine>, EzklError> {
+    let args = run_args(model.into(), settings);
+    let fingerprint = circuit_fingerprint(&args)?;
+    Ok(args.scoped(|| load_vk::<KZGCommitmentScheme<Bn256>, Fr>(vk_path.clone(), fingerprint))?)
+}
+
+/// Reads the keys saved by [save_keys] from the contents `pk` of the proving key file, checking they are those of the circuit of
+/// the .onnx model at `model`.
+pub fn read_keys(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    pk: &[u8],
+) -> Result<Keys, EzklError> {
+    let args = run_args(model.into(), settings);
+    let fingerprint = circuit_fingerprint(&args)?;
+    let pk = args.scoped(|| read_pk::<KZGCommitmentScheme<Bn256>, Fr>(pk, fingerprint))?;
+    Ok(Keys { pk, fingerprint })
+}
+
+/// Reads a verifying key from the contents `vk` of the file saved by [save_keys], checking it is that of the circuit of the .onnx
+/// model at `model`.
+pub fn read_verifying_key(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    vk: &[u8],
+) -> Result<VerifyingKey<G1Affine>, EzklError> {
+    let args = run_args(model.into(), settings);
+    let fingerprint = circuit_fingerprint(&args)?;
+    Ok(args.scoped(|| read_vk::<KZGCommitmentScheme<Bn256>, Fr>(vk, fingerprint))?)
+}
+
+/// Proves the .onnx model at `model` over the inputs in `data`, with the KZG `params` and `keys` generated by [setup]. Proofs are
+/// created with the GWC multi-open scheme and a blake2b transcript, as is done by default by the `prove` command.
+pub fn prove(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    data: &ModelInput,
+    params: &ParamsKZG<Bn256>,
+    keys: &Keys,
+) -> Result<Proof, EzklError> {
+    prove_with_transcript(model, settings, data, params, keys, TranscriptType::Blake2b)
+}
+
+/// Proves the .onnx model at `model` as [prove] does, deriving challenges with `transcript`. Proofs created with a
+/// [TranscriptType::Keccak] transcript are those accepted by EVM verifiers.
+pub fn prove_with_transcript(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    data: &ModelInput,
+    params: &ParamsKZG<Bn256>,
+    keys: &Keys,
+    transcript: TranscriptType,
+) -> Result<Proof, EzklError> {
+    let hashes = artifact_hashes(params, keys)?;
+    prove_with_hashes(model, settings, data, params, keys, transcript, &hashes)
+}
+
+/// The hashes of the artifacts (circuit, verifying key and params) proofs are generated with, as recorded in proofs by [prove].
+pub fn artifact_hashes(
+    params: &ParamsKZG<Bn256>,
+    keys: &Keys,
+) -> Result<ArtifactHashes, EzklError> {
+    Ok(ArtifactHashes::new::<KZGCommitmentScheme<Bn256>>(
+        keys.fingerprint,
+        keys.vk(),
+        params,
+    )?)
+}
+
+/// Proves the .onnx model at `model` as [prove_with_transcript] does, recording the precomputed `hashes` of [artifact_hashes] in
+/// the proof rather than hashing the (possibly large) params and keys again, e.g. for provers generating many proofs.
+pub fn prove_with_hashes(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    data: &ModelInput,
+    params: &ParamsKZG<Bn256>,
+    keys: &Keys,
+    transcript: TranscriptType,
+    hashes: &ArtifactHashes,
+) -> Result<Proof, EzklError> {
+    let args = run_args(model.into(), settings);
+    prove_with_args(&args, data, params, keys, transcript, hashes)
+}
+
+/// Proves the model of `args` as [prove_with_hashes] does. When `args` hold a [crate::progress::Task], the proof reports its progress to it, and
+/// stops with [GraphError::Cancelled] when it is cancelled.
+pub fn prove_with_args(
+    args: &RunArgs,
+    data: &ModelInput,
+    params: &ParamsKZG<Bn256>,
+    keys: &Keys,
+    transcript: TranscriptType,
+    hashes: &ArtifactHashes,
+) -> Result<Proof, EzklError> {
+    let task = args.task.clone().unwrap_or_default();
+    task.report(Phase::Witness);
+    let (circuit, public_inputs) = prepare_circuit_and_public_input(data, args)?;
+    if task.is_cancelled() {
+        return Err(GraphError::Cancelled.into());
+    }
+    let proof = args.scoped(|| {
+        create_kzg_proof::<ProverGWC<_>>(&circuit, &public_inputs, params, &keys.pk, transcript)
+    });
+    // the layout of a cancelled proof fails, which halo2 reports as a synthesis error
+    if task.is_cancelled() {
+        return Err(GraphError::Cancelled.into());
+    }
+    let mut proof = proof?;
+    proof.hashes = Some(hashes.clone());
+    task.report(Phase::Done);
+    Ok(proof)
+}
+
+/// The circuit of a model prepared for proving many inputs, e.g. by a proving service: the model is loaded and quantized once,
+/// and the proving key (holding the configured constraint system and the fixed columns of the circuit), the params and the hashes
+/// proofs record are held alongside it, such that only the witness and the proof are computed for each input.
+#[derive(Debug)]
+pub struct PreparedCircuit {
+    args: RunArgs,
+    params: ParamsKZG<Bn256>,
+    keys: Keys,
+    hashes: ArtifactHashes,
+    transcript: TranscriptType,
+}
+
+impl PreparedCircuit {
+    /// Prepares the circuit of the .onnx model at `model` for proving with the KZG `params` and `keys` (as generated by [setup] or
+    /// loaded by [load_keys]), checking the keys are those of the circuit. Proofs derive their challenges with `transcript`.
+    pub fn new(
+        model: impl Into<ModelSource>,
+        settings: &CircuitSettings,
+        params: ParamsKZG<Bn256>,
+        keys: Keys,
+        transcript: TranscriptType,
+    ) -> Result<Self, EzklError> {
+        let mut args = run_args(model.into(), settings);
+        if keys.fingerprint != circuit_fingerprint(&args)? {
+            return Err(PfsysError::StaleKey.into());
+        }
+        args.prepared = Some(Arc::new(Model::from_run_args(&args)?));
+        let hashes = artifact_hashes(&params, &keys)?;
+        Ok(PreparedCircuit {
+            args,
+            params,
+            keys,
+            hashes,
+            transcript,
+        })
+    }
+
+    /// Prepares the circuit of the .onnx model at `model` as [PreparedCircuit::new] does, generating its keys with `params`.
+    pub fn setup(
+        model: impl Into<ModelSource>,
+        settings: &CircuitSettings,
+        params: ParamsKZG<Bn256>,
+        transcript: TranscriptType,
+    ) -> Result<Self, EzklError> {
+        let mut args = run_args(model.into(), settings);
+        let fingerprint = circuit_fingerprint(&args)?;
+        let model = Arc::new(Model::from_run_args(&args)?);
+        let circuit = prepare_dummy_circuit::<Fr>(&model)?;
+        args.prepared = Some(model);
+        let pk =
+            args.scoped(|| create_keys::<KZGCommitmentScheme<Bn256>, Fr>(&circuit, &params))?;
+        let keys = Keys { pk, fingerprint };
+        let hashes = artifact_hashes(&params, &keys)?;
+        Ok(PreparedCircuit {
+            args,
+            params,
+            keys,
+            hashes,
+            transcript,
+        })
+    }
+
+    /// The model of the circuit, as loaded and quantized when the circuit was prepared.
+    pub fn model(&self) -> &Model {
+        // always set when preparing the circuit
+        self.args
+            .prepared
+            .as_deref()
+            .expect("prepared circuits hold their model")
+    }
+
+    /// The keys of the circuit.
+    pub fn keys(&self) -> &Keys {
+        &self.keys
+    }
+
+    /// The KZG params proofs are created with.
+    pub fn params(&self) -> &ParamsKZG<Bn256> {
+        &self.params
+    }
+
+    /// The hashes of the artifacts proofs are created with, as recorded in each proof.
+    pub fn hashes(&self) -> &ArtifactHashes {
+        &self.hashes
+    }
+
+    /// Proves the model over the inputs in `data`.
+    pub fn prove(&self, data: &ModelInput) -> Result<Proof, EzklError> {
+        self.prove_task(data, &Task::default())
+    }
+
+    /// Proves the model over the inputs in `data`, reporting progress to `task` and stopping when it is cancelled.
+    pub fn prove_task(&self, data: &ModelInput, task: &Task) -> Result<Proof, EzklError> {
+        let mut args = self.args.clone();
+        args.task = Some(task.clone());
+        prove_with_args(
+            &args,
+            data,
+            &self.params,
+            &self.keys,
+            self.transcript,
+            &self.hashes,
+        )
+    }
+
+    /// Verifies a `proof` of the model, checking it was created with the artifacts of this circuit, and returning whether it is
+    /// accepted.
+    pub fn verify(&self, proof: &Proof) -> Result<bool, EzklError> {
+        proof.check_hashes(&self.hashes)?;
+        Ok(verify_with_transcript(
+            proof,
+            &self.params,
+            self.keys.vk(),
+            self.transcript,
+        ))
+    }
+
+    /// Verifies a `proof` of the model as [PreparedCircuit::verify] does, returning its public outputs dequantized at the scales
+    /// of the outputs of the model (see [Model::public_outputs]) when it is accepted, and `None` when it is rejected.
+    pub fn verify_outputs(&self, proof: &Proof) -> Result<Option<Vec<Vec<Vec<f32>>>>, EzklError> {
+        let outputs = self.model().public_outputs(&proof.public_inputs)?;
+        Ok(self.verify(proof)?.then_some(outputs))
+    }
+
+    /// Verifies `proofs` of the model as a batch, with a single pairing check for all of them (see
+    /// [crate::pfsys::verify_batch]), after checking the artifacts each proof was created with are those loaded. Returns whether
+    /// each proof is accepted.
+    pub fn verify_batch(&self, proofs: &[Proof]) -> Result<Vec<bool>, EzklError> {
+        for proof in proofs {
+            proof.check_hashes(&self.hashes)?;
+        }
+        Ok(verify_kzg_batch::<VerifierGWC<_>>(
+            proofs,
+            &self.params,
+            self.keys.vk(),
+            self.transcript,
+        ))
+    }
+}
+
+/// Verifies a `proof` created by [prove] against the verifying key `vk` and the KZG `params`, returning whether it is accepted.
+pub fn verify(proof: &Proof, params: &ParamsKZG<Bn256>, vk: &VerifyingKey<G1Affine>) -> bool {
+    verify_with_transcript(proof, params, vk, TranscriptType::Blake2b)
+}
+
+/// Verifies a `proof` created by [prove_with_transcript] with the given `transcript`, returning whether it is accepted.
+pub fn verify_with_transcript(
+    proof: &Proof,
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    transcript: TranscriptType,
+) -> bool {
+    verify_kzg_proof::<VerifierGWC<_>>(proof.clone(), params, vk, transcript).is_ok()
+}
+
+/// The public outputs of a `proof` of the .onnx model at `model`, dequantized at the scales of its outputs (see
+/// [Model::public_outputs]), such that verifiers don't interpret raw field elements. Verify the proof first.
+pub fn public_outputs(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    proof: &Proof,
+) -> Result<Vec<Vec<Vec<f32>>>, EzklError> {
+    Ok(load_model(model, settings)?.public_outputs(&proof.public_inputs)?)
+}
+
+/// Generates the bytecode of an EVM verifier for proofs of the .onnx model at `model`, created by [prove_with_transcript]
+/// with a [TranscriptType::Keccak] transcript. Verifiers exceeding the contract size limit are split into several contracts.
+#[cfg(feature = "evm")]
+pub fn create_evm_verifier(
+    model: impl Into<ModelSource>,
+    settings: &CircuitSettings,
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+) -> Result<VerifierCode, EzklError> {
+    let num_instance = load_model(model, settings)?
+        .instance_shapes()
+        .iter()
+        .map(|s| s.iter().product())
+        .collect();
+    // a proof which isn't an aggregate holds no accumulator
+    Ok(gen_aggregation_evm_verifier(
+        params,
+        vk,
+        num_instance,
+        vec![],
+    )?)
+}
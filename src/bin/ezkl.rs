@@ -1,19 +1,42 @@
-use ezkl::commands::Cli;
-use ezkl::execute::run;
+use ezkl::commands::{Cli, LogFormat};
+use ezkl::execute::{run, CommandReport};
 use log::{error, info};
 use rand::seq::SliceRandom;
 use std::error::Error;
+use std::time::Instant;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
 
 pub fn main() -> Result<(), Box<dyn Error>> {
     let args = Cli::create();
-    colog::init();
-    banner();
+    match args.log_format {
+        LogFormat::Text => {
+            colog::init();
+            banner();
+        }
+        // log records are forwarded to tracing, alongside its spans, whose durations are logged as they close
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_writer(std::io::stderr)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_env_filter(
+                EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+            )
+            .init(),
+    }
     info!("{}", &args.as_json()?);
+    let json = args.json;
+    let command = args.command.clone();
+    let start = Instant::now();
     let res = run(args);
     match &res {
         Ok(_) => info!("verify succeeded"),
         Err(e) => error!("verify failed: {}", e),
     };
+    if json {
+        let report = CommandReport::new(&command, start.elapsed(), &res);
+        println!("{}", serde_json::to_string(&report)?);
+    }
     res
 }
 
@@ -0,0 +1,229 @@
+use super::decomposed::RangeTable;
+use crate::fieldutils::i32_to_felt;
+use crate::tensor::{TensorType, ValTensor};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Layouter, Region, Value},
+    plonk::{Advice, Column, ConstraintSystem, Error as PlonkError, Fixed, Selector},
+    poly::Rotation,
+};
+use std::error::Error;
+use std::{cell::RefCell, rc::Rc};
+
+/// A side of a comparison: an element of a tensor (by its index in row major order), or a constant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    /// The element of the tensor at the given index.
+    Element(usize),
+    /// A constant, at the fixed point scale of the tensor.
+    Const(i32),
+}
+
+/// Configuration for comparisons `lhs >= rhs` between the elements of a tensor and constants, proven by looking up the
+/// difference of both sides in a [RangeTable] of `bits` bits. For values within the range of the lookup tables the difference
+/// lies in `[0, 2^bits)` exactly when `lhs >= rhs`.
+#[derive(Clone, Debug)]
+pub struct CompareConfig<F: FieldExt + TensorType> {
+    lhs: Column<Advice>,
+    rhs: Column<Advice>,
+    constant: Column<Fixed>,
+    /// Enables the comparison of `lhs` to `rhs`.
+    q_elements: Selector,
+    /// Enables the comparison of `lhs` to `constant`.
+    q_lower: Selector,
+    /// Enables the comparison of `constant` to `lhs`.
+    q_upper: Selector,
+    table: Rc<RefCell<RangeTable<F>>>,
+}
+
+impl<F: FieldExt + TensorType> CompareConfig<F> {
+    /// Configures the comparisons, looking up differences in `table`. Comparisons which aren't enabled look up 0, which the
+    /// table holds.
+    pub fn configure(cs: &mut ConstraintSystem<F>, table: &Rc<RefCell<RangeTable<F>>>) -> Self {
+        let lhs = cs.advice_column();
+        let rhs = cs.advice_column();
+        cs.enable_equality(lhs);
+        cs.enable_equality(rhs);
+        let config = CompareConfig {
+            lhs,
+            rhs,
+            constant: cs.fixed_column(),
+            q_elements: cs.complex_selector(),
+            q_lower: cs.complex_selector(),
+            q_upper: cs.complex_selector(),
+            table: table.clone(),
+        };
+        let column = table.borrow().column;
+
+        let _ = cs.lookup("compare elements", |cs| {
+            let q = cs.query_selector(config.q_elements);
+            let lhs = cs.query_advice(config.lhs, Rotation::cur());
+            let rhs = cs.query_advice(config.rhs, Rotation::cur());
+            vec![(q * (lhs - rhs), column)]
+        });
+        let _ = cs.lookup("compare to lower bound", |cs| {
+            let q = cs.query_selector(config.q_lower);
+            let lhs = cs.query_advice(config.lhs, Rotation::cur());
+            let constant = cs.query_fixed(config.constant, Rotation::cur());
+            vec![(q * (lhs - constant), column)]
+        });
+        let _ = cs.lookup("compare to upper bound", |cs| {
+            let q = cs.query_selector(config.q_upper);
+            let lhs = cs.query_advice(config.lhs, Rotation::cur());
+            let constant = cs.query_fixed(config.constant, Rotation::cur());
+            vec![(q * (constant - lhs), column)]
+        });
+
+        config
+    }
+
+    /// Lays out `comparisons` between the elements of `values` and constants, a row each, laying out the table on first use.
+    /// # Arguments
+    /// * `layouter` - A Halo2 Layouter.
+    /// * `values` - The tensor the elements compared are taken from, which are copied when previously assigned.
+    /// * `comparisons` - The pairs `(lhs, rhs)` proven to satisfy `lhs >= rhs`.
+    pub fn layout(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        values: &ValTensor<F>,
+        comparisons: &[(Operand, Operand)],
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.table.borrow().is_assigned {
+            self.table.borrow_mut().layout(layouter)?;
+        }
+        layouter.assign_region(
+            || "compare layout",
+            |mut region| {
+                for (row, comparison) in comparisons.iter().enumerate() {
+                    let constant = |region: &mut Region<'_, F>, c: i32| {
+                        region.assign_fixed(
+                            || "compared constant",
+                            self.constant,
+                            row,
+                            || Value::known(i32_to_felt::<F>(c)),
+                        )
+                    };
+                    match *comparison {
+                        (Operand::Element(a), Operand::Element(b)) => {
+                            assign_element(&mut region, self.lhs, row, values, a)?;
+                            assign_element(&mut region, self.rhs, row, values, b)?;
+                            self.q_elements.enable(&mut region, row)?;
+                        }
+                        (Operand::Element(a), Operand::Const(c)) => {
+                            assign_element(&mut region, self.lhs, row, values, a)?;
+                            constant(&mut region, c)?;
+                            self.q_lower.enable(&mut region, row)?;
+                        }
+                        (Operand::Const(c), Operand::Element(a)) => {
+                            assign_element(&mut region, self.lhs, row, values, a)?;
+                            constant(&mut region, c)?;
+                            self.q_upper.enable(&mut region, row)?;
+                        }
+                        // comparisons of constants are known ahead of the circuit
+                        (Operand::Const(_), Operand::Const(_)) => {
+                            return Err(PlonkError::Synthesis);
+                        }
+                    }
+                }
+                Ok(())
+            },
+        )?;
+        Ok(())
+    }
+}
+
+/// Assigns the element at `index` of `values` to `column` at `row`, copying it when it was previously assigned.
+fn assign_element<F: FieldExt + TensorType>(
+    region: &mut Region<'_, F>,
+    column: Column<Advice>,
+    row: usize,
+    values: &ValTensor<F>,
+    index: usize,
+) -> Result<AssignedCell<F, F>, PlonkError> {
+    if index >= values.dims().iter().product::<usize>() {
+        return Err(PlonkError::Synthesis);
+    }
+    match values {
+        ValTensor::PrevAssigned { inner, .. } => {
+            inner[index].copy_advice(|| "compared element", region, column, row)
+        }
+        ValTensor::Value { inner, .. } => {
+            region.assign_advice(|| "compared element", column, row, || inner[index])
+        }
+        ValTensor::AssignedValue { inner, .. } => region.assign_advice(
+            || "compared element",
+            column,
+            row,
+            || inner[index].evaluate(),
+        ),
+        ValTensor::Instance { inner, .. } => {
+            region.assign_advice_from_instance(|| "compared element", *inner, index, column, row)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::Tensor;
+    use halo2_proofs::{
+        circuit::SimpleFloorPlanner,
+        dev::MockProver,
+        plonk::{Circuit, ConstraintSystem},
+    };
+    use halo2curves::pasta::Fp;
+
+    #[derive(Clone)]
+    struct CompareCircuit<F: FieldExt + TensorType> {
+        values: ValTensor<F>,
+        comparisons: Vec<(Operand, Operand)>,
+    }
+
+    impl<F: FieldExt + TensorType> Circuit<F> for CompareCircuit<F> {
+        type Config = CompareConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            let table = Rc::new(RefCell::new(RangeTable::configure(cs, 4)));
+            CompareConfig::configure(cs, &table)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), PlonkError> {
+            config
+                .layout(&mut layouter, &self.values, &self.comparisons)
+                .map_err(|_| PlonkError::Synthesis)
+        }
+    }
+
+    #[test]
+    fn compares_elements_and_constants() {
+        let values = [-3, 5, 2].map(|v| Value::known(i32_to_felt::<Fp>(v)));
+        let prove = |comparisons: Vec<(Operand, Operand)>| {
+            let circuit = CompareCircuit::<Fp> {
+                values: ValTensor::from(Tensor::from(values.into_iter())),
+                comparisons,
+            };
+            MockProver::run(6, &circuit, vec![]).unwrap().verify()
+        };
+        use Operand::*;
+        // the second element is the largest, and lies within [2, 5]
+        assert!(prove(vec![
+            (Element(1), Element(0)),
+            (Element(1), Element(2)),
+            (Element(1), Const(2)),
+            (Const(5), Element(1)),
+        ])
+        .is_ok());
+        assert!(prove(vec![(Element(2), Element(1))]).is_err());
+        assert!(prove(vec![(Element(0), Const(-2))]).is_err());
+        assert!(prove(vec![(Const(4), Element(1))]).is_err());
+    }
+}
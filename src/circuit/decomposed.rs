@@ -0,0 +1,667 @@
+use super::lookup::{apply, Op};
+use super::*;
+use crate::fieldutils::{felt_to_i32_checked, i32_to_felt};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{Layouter, Value},
+    plonk::{
+        ConstraintSystem, Constraints, Error as PlonkError, Expression, Selector, TableColumn,
+        VirtualCells,
+    },
+    poly::Rotation,
+};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::{cell::RefCell, marker::PhantomData, rc::Rc};
+
+/// The values of a decomposed lookup over some inputs `x`, split into a high and a low limb.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Decomposition {
+    /// The high limbs, `x >> limb_bits`.
+    pub hi: Vec<i32>,
+    /// The low limbs, `x - hi * 2^limb_bits`, in `[0, 2^limb_bits)`.
+    pub lo: Vec<i32>,
+    /// The non-linearity at the start of the interval of each input, `f(hi * 2^limb_bits)`.
+    pub start: Vec<i32>,
+    /// The non-linearity at the end of the interval of each input, `f((hi + 1) * 2^limb_bits)`.
+    pub end: Vec<i32>,
+    /// The interpolated outputs.
+    pub output: Vec<i32>,
+    /// The remainders of rounding the interpolated outputs, in `[0, 2^limb_bits)`.
+    pub remainder: Vec<i32>,
+    /// The high limbs of the outputs, `output >> limb_bits`.
+    pub output_hi: Vec<i32>,
+    /// The low limbs of the outputs, in `[0, 2^limb_bits)`.
+    pub output_lo: Vec<i32>,
+}
+
+/// Decomposes `x` into limbs at `limb_bits`, and interpolates `nonlinearities` linearly between their values at the multiples of
+/// `2^limb_bits` surrounding each input, rounding to the nearest integer.
+pub fn decompose(nonlinearities: &[Op], x: &[i32], limb_bits: usize) -> Decomposition {
+    let step = 1i64 << limb_bits;
+    let hi: Vec<i32> = x.iter().map(|x| x >> limb_bits).collect();
+    let lo: Vec<i32> = x
+        .iter()
+        .zip(&hi)
+        .map(|(x, hi)| x - (hi << limb_bits))
+        .collect();
+    let start = apply(
+        nonlinearities,
+        hi.iter().map(|hi| (*hi as i64 * step) as i32).into(),
+    )
+    .to_vec();
+    let end = apply(
+        nonlinearities,
+        hi.iter().map(|hi| ((*hi as i64 + 1) * step) as i32).into(),
+    )
+    .to_vec();
+    let (output, remainder) = lo
+        .iter()
+        .zip(start.iter().zip(&end))
+        .map(|(lo, (a, b))| {
+            let numerator = *a as i64 * step + (*b as i64 - *a as i64) * *lo as i64 + step / 2;
+            let y = numerator.div_euclid(step);
+            (y as i32, (numerator - y * step) as i32)
+        })
+        .unzip();
+    let output_hi: Vec<i32> = output.iter().map(|y: &i32| y >> limb_bits).collect();
+    let output_lo = output
+        .iter()
+        .zip(&output_hi)
+        .map(|(y, hi)| y - (hi << limb_bits))
+        .collect();
+    Decomposition {
+        hi,
+        lo,
+        start,
+        end,
+        output,
+        remainder,
+        output_hi,
+        output_lo,
+    }
+}
+
+/// Evaluates `nonlinearities` over `x` as decomposed lookups at `limb_bits` do, see [decompose]. This is within one of the
+/// exact values for functions which are linear between multiples of `2^limb_bits` (e.g ReLU and Div), and approximates others
+/// (e.g Sigmoid) piecewise linearly.
+pub fn interpolate(nonlinearities: &[Op], x: &Tensor<i32>, limb_bits: usize) -> Tensor<i32> {
+    let mut output = Tensor::from(decompose(nonlinearities, x, limb_bits).output.into_iter());
+    output.reshape(x.dims());
+    output
+}
+
+/// A table of the values `0..2^bits`, range checking the low limbs and remainders of decomposed lookups.
+#[derive(Clone, Debug)]
+pub struct RangeTable<F: FieldExt> {
+    /// The values of the table.
+    pub column: TableColumn,
+    /// Number of bits of the values of the table.
+    pub bits: usize,
+    /// Flags if table has been previously assigned to.
+    pub is_assigned: bool,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> RangeTable<F> {
+    /// Configures the table.
+    pub fn configure(cs: &mut ConstraintSystem<F>, bits: usize) -> Self {
+        RangeTable {
+            column: cs.lookup_table_column(),
+            bits,
+            is_assigned: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Assigns values to the constraints generated when calling `configure`.
+    pub fn layout(&mut self, layouter: &mut impl Layouter<F>) -> Result<(), Box<dyn Error>> {
+        if self.is_assigned {
+            return Err(Box::new(CircuitError::TableAlreadyAssigned));
+        }
+        self.is_assigned = true;
+        layouter
+            .assign_table(
+                || "range table",
+                |mut table| {
+                    for row_offset in 0..1usize << self.bits {
+                        table.assign_cell(
+                            || format!("range_col row {}", row_offset),
+                            self.column,
+                            row_offset,
+                            || Value::known(F::from(row_offset as u64)),
+                        )?;
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(Box::<dyn Error>::from)
+    }
+}
+
+/// A table interpolating a composition of non-linearities over inputs of `bits` bits split at `limb_bits`: the row of each high
+/// limb `hi` holds the values of the non-linearities at `hi * 2^limb_bits` and `(hi + 1) * 2^limb_bits`.
+#[derive(Clone, Debug)]
+pub struct InterpolationTable<F: FieldExt> {
+    /// composition of operations to be represented by the table
+    pub nonlinearities: Vec<Op>,
+    /// The high limbs.
+    pub table_hi: TableColumn,
+    /// The values at the start of the interval of each high limb.
+    pub table_start: TableColumn,
+    /// The values at the end of the interval of each high limb.
+    pub table_end: TableColumn,
+    /// Flags if table has been previously assigned to.
+    pub is_assigned: bool,
+    /// Number of bits of the inputs.
+    pub bits: usize,
+    /// Number of bits of the low limb of the inputs.
+    pub limb_bits: usize,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> InterpolationTable<F> {
+    /// Configures the table.
+    pub fn configure(
+        cs: &mut ConstraintSystem<F>,
+        bits: usize,
+        limb_bits: usize,
+        nonlinearities: &[Op],
+    ) -> Self {
+        InterpolationTable {
+            nonlinearities: nonlinearities.to_vec(),
+            table_hi: cs.lookup_table_column(),
+            table_start: cs.lookup_table_column(),
+            table_end: cs.lookup_table_column(),
+            is_assigned: false,
+            bits,
+            limb_bits,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The row of the high limb `0`, which lookups that aren't enabled resolve to.
+    fn default_row(&self) -> (F, F) {
+        let d = decompose(&self.nonlinearities, &[0], self.limb_bits);
+        (i32_to_felt(d.start[0]), i32_to_felt(d.end[0]))
+    }
+
+    /// Assigns values to the constraints generated when calling `configure`.
+    pub fn layout(&mut self, layouter: &mut impl Layouter<F>) -> Result<(), Box<dyn Error>> {
+        if self.is_assigned {
+            return Err(Box::new(CircuitError::TableAlreadyAssigned));
+        }
+        let hi_bits = self.bits - self.limb_bits;
+        let inputs: Vec<i32> = (-(1 << (hi_bits - 1))..1 << (hi_bits - 1))
+            .map(|hi: i32| hi << self.limb_bits)
+            .collect();
+        let d = decompose(&self.nonlinearities, &inputs, self.limb_bits);
+        self.is_assigned = true;
+        layouter
+            .assign_table(
+                || "interpolation table",
+                |mut table| {
+                    for (row_offset, ((hi, start), end)) in
+                        d.hi.iter().zip(&d.start).zip(&d.end).enumerate()
+                    {
+                        for (column, value) in [
+                            (self.table_hi, hi),
+                            (self.table_start, start),
+                            (self.table_end, end),
+                        ] {
+                            table.assign_cell(
+                                || format!("interpolation row {}", row_offset),
+                                column,
+                                row_offset,
+                                || Value::known(i32_to_felt::<F>(*value)),
+                            )?;
+                        }
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(Box::<dyn Error>::from)
+    }
+}
+
+/// The tables shared by the decomposed lookups of a circuit: a single range table, and an interpolation table per composition
+/// of non-linearities.
+#[derive(Clone, Debug)]
+pub struct DecomposedTables<F: FieldExt> {
+    /// The range table of the low limbs and remainders.
+    pub range: Rc<RefCell<RangeTable<F>>>,
+    /// The interpolation tables, keyed by the non-linearities they interpolate.
+    pub interpolations: BTreeMap<Vec<Op>, Rc<RefCell<InterpolationTable<F>>>>,
+    /// Number of bits of the inputs.
+    pub bits: usize,
+    /// Number of bits of the low limb of the inputs.
+    pub limb_bits: usize,
+}
+
+impl<F: FieldExt> DecomposedTables<F> {
+    /// Configures the range table of lookups over inputs of `bits` bits split at `limb_bits`. Interpolation tables are
+    /// configured as they are first needed.
+    pub fn configure(cs: &mut ConstraintSystem<F>, bits: usize, limb_bits: usize) -> Self {
+        DecomposedTables {
+            range: Rc::new(RefCell::new(RangeTable::configure(cs, limb_bits))),
+            interpolations: BTreeMap::new(),
+            bits,
+            limb_bits,
+        }
+    }
+
+    /// The interpolation table of `nonlinearities`, configured if it doesn't exist yet.
+    pub fn interpolation(
+        &mut self,
+        cs: &mut ConstraintSystem<F>,
+        nonlinearities: &[Op],
+    ) -> Rc<RefCell<InterpolationTable<F>>> {
+        let (bits, limb_bits) = (self.bits, self.limb_bits);
+        self.interpolations
+            .entry(nonlinearities.to_vec())
+            .or_insert_with(|| {
+                Rc::new(RefCell::new(InterpolationTable::configure(
+                    cs,
+                    bits,
+                    limb_bits,
+                    nonlinearities,
+                )))
+            })
+            .clone()
+    }
+}
+
+/// The number of [VarTensor]s a decomposed lookup is laid out over.
+pub const DECOMPOSED_VARS: usize = 9;
+
+/// The number of lookups of each element of a decomposed lookup, see [DecomposedConfig::first_lookup].
+pub const DECOMPOSED_LOOKUPS: usize = 5;
+
+/// Queries the element `i` of `var`.
+fn query_element<F: FieldExt>(
+    cs: &mut VirtualCells<'_, F>,
+    var: &VarTensor,
+    i: usize,
+) -> Expression<F> {
+    let (x, y) = var.cartesian_coord(i);
+    match var {
+        VarTensor::Advice { inner, .. } => cs.query_advice(inner[x], Rotation(y as i32)),
+        VarTensor::Fixed { inner, .. } => cs.query_fixed(inner[x], Rotation(y as i32)),
+    }
+}
+
+/// Configuration of an element-wise non-linearity over inputs too wide for a lookup table to span. Each input `x` is split into
+/// a high limb `hi` and a low limb `lo` with `x = hi * 2^limb_bits + lo`, where `lo` is range checked by a table of `2^limb_bits`
+/// rows. The high limb looks up the values of the non-linearity at the ends of its interval in a table of `2^(bits - limb_bits)`
+/// rows, between which the output is interpolated: `y * 2^limb_bits + r = start * 2^limb_bits + (end - start) * lo +
+/// 2^(limb_bits - 1)`, with the remainder `r` range checked as well. As field elements, that equation holds for any `r` given some
+/// `y`, so the output is itself split into limbs as the input is, `y = y_hi * 2^limb_bits + y_lo`, with `y_lo` range checked and
+/// `y_hi` looked up among the high limbs of the interpolation table: `y` is then an integer of `bits` bits, and the only one
+/// (with its remainder) satisfying the equation. Outputs must hence fit in `bits` bits, as inputs do.
+#[derive(Clone, Debug)]
+pub struct DecomposedConfig<F: FieldExt + TensorType> {
+    /// the inputs to the lookup operations.
+    pub input: VarTensor,
+    /// the interpolated outputs.
+    pub output: VarTensor,
+    /// the high limbs of the inputs.
+    pub hi: VarTensor,
+    /// the low limbs of the inputs.
+    pub lo: VarTensor,
+    /// the values at the start of the interval of each input.
+    pub start: VarTensor,
+    /// the values at the end of the interval of each input.
+    pub end: VarTensor,
+    /// the remainders of rounding the outputs.
+    pub remainder: VarTensor,
+    /// the high limbs of the outputs.
+    pub output_hi: VarTensor,
+    /// the low limbs of the outputs.
+    pub output_lo: VarTensor,
+    /// [Selector] generated when configuring the layer.
+    pub qdecompose: Selector,
+    /// The range table of the low limbs and remainders.
+    pub range: Rc<RefCell<RangeTable<F>>>,
+    /// The interpolation table of the non-linearities.
+    pub table: Rc<RefCell<InterpolationTable<F>>>,
+    /// The index, within the constraint system, of the lookup argument for the first element of `input`. Each element has
+    /// [DECOMPOSED_LOOKUPS] lookups, of its low limb, its remainder, the low and high limbs of its output and its high limb, and
+    /// those of the remaining elements follow on in order.
+    pub first_lookup: usize,
+}
+
+impl<F: FieldExt + TensorType> DecomposedConfig<F> {
+    /// Configures an element-wise operation over `vars`, which are (in order) the input, output, high limbs, low limbs, interval
+    /// starts, interval ends, remainders, and high and low limbs of the output of the operation.
+    pub fn configure(
+        cs: &mut ConstraintSystem<F>,
+        vars: &[VarTensor],
+        tables: &mut DecomposedTables<F>,
+        nonlinearities: &[Op],
+    ) -> Result<Self, Box<dyn Error>> {
+        let [input, output, hi, lo, start, end, remainder, output_hi, output_lo]: [VarTensor;
+            DECOMPOSED_VARS] = vars.to_vec().try_into().map_err(|v: Vec<VarTensor>| {
+            CircuitError::DimMismatch(format!(
+                "decomposed lookups need {} vars, got {}",
+                DECOMPOSED_VARS,
+                v.len()
+            ))
+        })?;
+        let table = tables.interpolation(cs, nonlinearities);
+        let range = tables.range.clone();
+        let limb_bits = tables.limb_bits;
+        let qdecompose = cs.complex_selector();
+        let len = input.dims().iter().product::<usize>();
+
+        cs.create_gate("decomposed lookup", |cs| {
+            let q = cs.query_selector(qdecompose);
+            let step = Expression::Constant(F::from(1 << limb_bits));
+            let half = Expression::Constant(F::from(1 << (limb_bits - 1)));
+            let constraints = (0..len)
+                .flat_map(|i| {
+                    let [x, y, hi, lo, start, end, r, y_hi, y_lo] = [
+                        &input, &output, &hi, &lo, &start, &end, &remainder, &output_hi, &output_lo,
+                    ]
+                    .map(|var| query_element(cs, var, i));
+                    [
+                        x - hi * step.clone() - lo.clone(),
+                        y.clone() * step.clone() + r
+                            - start.clone() * step.clone()
+                            - (end - start) * lo
+                            - half.clone(),
+                        y - y_hi * step.clone() - y_lo,
+                    ]
+                })
+                .collect::<Vec<_>>();
+            Constraints::with_selector(q, constraints)
+        });
+
+        let first_lookup = cs.lookups().len();
+        let (default_start, default_end) = table.borrow().default_row();
+        for i in 0..len {
+            for var in [&lo, &remainder, &output_lo] {
+                cs.lookup("decomposed range", |cs| {
+                    let q = cs.query_selector(qdecompose);
+                    vec![(q * query_element(cs, var, i), range.borrow().column)]
+                });
+            }
+            cs.lookup("decomposed output", |cs| {
+                let q = cs.query_selector(qdecompose);
+                vec![(
+                    q * query_element(cs, &output_hi, i),
+                    table.borrow().table_hi,
+                )]
+            });
+            cs.lookup("decomposed interpolation", |cs| {
+                let q = cs.query_selector(qdecompose);
+                let not_q = Expression::Constant(F::one()) - q.clone();
+                let table = table.borrow();
+                vec![
+                    (q.clone() * query_element(cs, &hi, i), table.table_hi),
+                    (
+                        q.clone() * query_element(cs, &start, i)
+                            + not_q.clone() * Expression::Constant(default_start),
+                        table.table_start,
+                    ),
+                    (
+                        q * query_element(cs, &end, i) + not_q * Expression::Constant(default_end),
+                        table.table_end,
+                    ),
+                ]
+            });
+        }
+
+        Ok(Self {
+            input,
+            output,
+            hi,
+            lo,
+            start,
+            end,
+            remainder,
+            output_hi,
+            output_lo,
+            qdecompose,
+            range,
+            table,
+            first_lookup,
+        })
+    }
+
+    /// Assigns values to the variables created when calling `configure`.
+    pub fn layout(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        values: &ValTensor<F>,
+    ) -> Result<ValTensor<F>, Box<dyn Error>> {
+        if !self.range.borrow().is_assigned {
+            self.range.borrow_mut().layout(layouter)?
+        }
+        if !self.table.borrow().is_assigned {
+            self.table.borrow_mut().layout(layouter)?
+        }
+        let (nonlinearities, limb_bits) = {
+            let table = self.table.borrow();
+            (table.nonlinearities.clone(), table.limb_bits)
+        };
+        // inputs which don't fit 32 bits fail the region, and are reported rather than wrapping around
+        let mut overflow = None;
+        let region = layouter.assign_region(
+            || "Decomposed",
+            |mut region| {
+                self.qdecompose.enable(&mut region, 0)?;
+
+                let w = self.input.assign(&mut region, 0, values)?;
+
+                let mut x: Vec<i32> = vec![];
+                for cell in w.iter() {
+                    let _ = cell.value().map(|f| match felt_to_i32_checked(*f) {
+                        Ok(v) => x.push(v),
+                        Err(v) => overflow = Some(v),
+                    });
+                }
+                if overflow.is_some() {
+                    return Err(PlonkError::Synthesis);
+                }
+
+                // for key generation x will be empty and we need to assign a set of unassigned values
+                let witness = |values: &[i32]| -> ValTensor<F> {
+                    let t: Tensor<Value<F>> = match x.len() {
+                        0 => w.map(|_| Value::unknown()),
+                        _ => values.iter().map(|v| Value::known(i32_to_felt(*v))).into(),
+                    };
+                    ValTensor::from(t)
+                };
+                let d = decompose(&nonlinearities, &x, limb_bits);
+                for (var, values) in [
+                    (&self.hi, &d.hi),
+                    (&self.lo, &d.lo),
+                    (&self.start, &d.start),
+                    (&self.end, &d.end),
+                    (&self.remainder, &d.remainder),
+                    (&self.output_hi, &d.output_hi),
+                    (&self.output_lo, &d.output_lo),
+                ] {
+                    var.assign(&mut region, 0, &witness(values))?;
+                }
+                self.output.assign(&mut region, 0, &witness(&d.output))
+            },
+        );
+        let mut t = match (region, overflow) {
+            (Ok(t), _) => ValTensor::from(t),
+            (Err(_), Some(x)) => return Err(Box::new(CircuitError::WitnessOverflow(x))),
+            (Err(e), None) => return Err(Box::new(e)),
+        };
+        t.reshape(values.dims())?;
+        Ok(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner, Value},
+        dev::MockProver,
+        plonk::{Circuit, ConstraintSystem, Error},
+    };
+    use halo2curves::group::ff::Field;
+    use halo2curves::pasta::Fp as F;
+
+    #[test]
+    fn interpolates_between_limbs() {
+        let relu = [Op::ReLU { scale: 1 }];
+        let x = [-300, -1, 0, 5, 255, 256, 1000];
+        let d = decompose(&relu, &x, 4);
+        assert_eq!(d.output, vec![0, 0, 0, 5, 255, 256, 1000]);
+        for i in 0..x.len() {
+            assert_eq!(x[i], d.hi[i] * 16 + d.lo[i]);
+            assert_eq!(d.output[i], d.output_hi[i] * 16 + d.output_lo[i]);
+            assert!((0..16).contains(&d.lo[i]) && (0..16).contains(&d.remainder[i]));
+            assert!((0..16).contains(&d.output_lo[i]));
+        }
+        // division is within one of rounding the exact quotient
+        let div = [Op::Div { scale: 3 }];
+        let x: Vec<i32> = (-512..512).collect();
+        let exact = apply(&div, x.iter().cloned().into());
+        for (y, e) in decompose(&div, &x, 4).output.iter().zip(exact.iter()) {
+            assert!((y - e).abs() <= 1);
+        }
+    }
+
+    #[derive(Clone)]
+    struct DecomposedCircuit {
+        input: ValTensor<F>,
+    }
+
+    impl Circuit<F> for DecomposedCircuit {
+        type Config = DecomposedConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            let vars = (0..DECOMPOSED_VARS)
+                .map(|_| VarTensor::new_advice(cs, 7, 3, vec![3], true, 512))
+                .collect::<Vec<_>>();
+            // 12 bit inputs in a circuit of 2^7 rows
+            let mut tables = DecomposedTables::configure(cs, 12, 6);
+            DecomposedConfig::configure(cs, &vars, &mut tables, &[Op::ReLU { scale: 2 }]).unwrap()
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.layout(&mut layouter, &self.input).unwrap();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn decomposed_circuit() {
+        let input: Tensor<Value<F>> = [-2000, 3, 2047]
+            .iter()
+            .map(|x| Value::known(i32_to_felt(*x)))
+            .into();
+        let circuit = DecomposedCircuit {
+            input: ValTensor::from(input),
+        };
+        let prover = MockProver::run(7_u32, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+    }
+
+    /// Lays out the decomposition of `input` as [DecomposedConfig::layout] does, but with the remainders off by one when
+    /// `tamper` is set, and the outputs (no longer integers) which satisfy the interpolation with them.
+    #[derive(Clone)]
+    struct TamperedCircuit {
+        input: Vec<i32>,
+        tamper: bool,
+    }
+
+    impl Circuit<F> for TamperedCircuit {
+        type Config = DecomposedConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            DecomposedCircuit::configure(cs)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.range.borrow_mut().layout(&mut layouter).unwrap();
+            config.table.borrow_mut().layout(&mut layouter).unwrap();
+            let d = decompose(&[Op::ReLU { scale: 2 }], &self.input, 6);
+            let remainder: Vec<i32> = match self.tamper {
+                true => d.remainder.iter().map(|r| (r + 1) % 64).collect(),
+                false => d.remainder.clone(),
+            };
+            // y * step + r = y' * step + r', for y' = y + (r - r') / step
+            let step_inv = F::from(64).invert().unwrap();
+            let output: Vec<F> = d
+                .output
+                .iter()
+                .zip(d.remainder.iter().zip(&remainder))
+                .map(|(y, (r, tampered))| {
+                    i32_to_felt::<F>(*y)
+                        + (i32_to_felt::<F>(*r) - i32_to_felt::<F>(*tampered)) * step_inv
+                })
+                .collect();
+            let known = |values: Vec<F>| {
+                let t: Tensor<Value<F>> = values.into_iter().map(Value::known).into();
+                ValTensor::from(t)
+            };
+            let felts = |values: &[i32]| known(values.iter().map(|v| i32_to_felt(*v)).collect());
+            layouter.assign_region(
+                || "tampered",
+                |mut region| {
+                    config.qdecompose.enable(&mut region, 0)?;
+                    for (var, values) in [
+                        (&config.input, felts(&self.input)),
+                        (&config.output, known(output.clone())),
+                        (&config.hi, felts(&d.hi)),
+                        (&config.lo, felts(&d.lo)),
+                        (&config.start, felts(&d.start)),
+                        (&config.end, felts(&d.end)),
+                        (&config.remainder, felts(&remainder)),
+                        (&config.output_hi, felts(&d.output_hi)),
+                        (&config.output_lo, felts(&d.output_lo)),
+                    ] {
+                        var.assign(&mut region, 0, &values)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[test]
+    fn tampered_outputs_fail() {
+        let input = vec![-2000, 3, 2047];
+        let honest = TamperedCircuit {
+            input: input.clone(),
+            tamper: false,
+        };
+        MockProver::run(7_u32, &honest, vec![])
+            .unwrap()
+            .assert_satisfied();
+        let tampered = TamperedCircuit {
+            input,
+            tamper: true,
+        };
+        assert!(MockProver::run(7_u32, &tampered, vec![])
+            .unwrap()
+            .verify()
+            .is_err());
+    }
+}
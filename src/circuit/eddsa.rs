@@ -0,0 +1,566 @@
+use crate::circuit::poseidon::{poseidon_hash, PoseidonConfig};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Layouter, Region, Value},
+    plonk::{
+        Advice, Column, ConstraintSystem, Constraints, Error, Expression, Fixed, Instance, Selector,
+    },
+    poly::Rotation,
+};
+use num_bigint::BigUint;
+use rand::RngCore;
+use std::marker::PhantomData;
+
+/// The `a` coefficient of Baby Jubjub, the twisted Edwards curve `a x^2 + y^2 = 1 + d x^2 y^2` defined over the scalar field
+/// of BN254. Points on the curve are pairs of elements of the field circuits are over, so curve arithmetic is native to the circuit.
+pub const A: u64 = 168700;
+/// The `d` coefficient of Baby Jubjub.
+pub const D: u64 = 168696;
+/// The number of bits scalars are decomposed into when multiplying points in-circuit, enough for any element of the field.
+pub const SCALAR_BITS: usize = 254;
+
+/// The generator of the prime order subgroup of Baby Jubjub.
+const BASE_X: &str = "5299619240641551281634865583518297030282874472190772894086521144482721001553";
+const BASE_Y: &str =
+    "16950150798460657717958625567821834550301663161624707787222815936182638968203";
+/// The order of the prime order subgroup of Baby Jubjub.
+const ORDER: &str = "2736030358979909402780800718157159386076813972158567259200215660948447373041";
+
+/// A point on Baby Jubjub, as its `(x, y)` coordinates.
+pub type Point<F> = (F, F);
+
+/// A point on Baby Jubjub which was assigned in-circuit.
+type AssignedPoint<F> = (AssignedCell<F, F>, AssignedCell<F, F>);
+
+/// The generator of the prime order subgroup of Baby Jubjub, which keys and signatures are multiples of.
+pub fn base_point<F: FieldExt>() -> Point<F> {
+    (
+        F::from_str_vartime(BASE_X).expect("valid base point"),
+        F::from_str_vartime(BASE_Y).expect("valid base point"),
+    )
+}
+
+/// Checks whether `p` lies on Baby Jubjub.
+pub fn is_on_curve<F: FieldExt>(p: &Point<F>) -> bool {
+    let (x2, y2) = (p.0.square(), p.1.square());
+    F::from(A) * x2 + y2 == F::one() + F::from(D) * x2 * y2
+}
+
+/// Adds two points on Baby Jubjub. The addition law is complete, so this holds for any pair of points including the identity `(0, 1)`.
+pub fn add<F: FieldExt>(p: &Point<F>, q: &Point<F>) -> Point<F> {
+    let t = F::from(D) * p.0 * q.0 * p.1 * q.1;
+    let x = (p.0 * q.1 + p.1 * q.0) * (F::one() + t).invert().unwrap();
+    let y = (p.1 * q.1 - F::from(A) * p.0 * q.0) * (F::one() - t).invert().unwrap();
+    (x, y)
+}
+
+/// Multiplies `p` by `scalar`, by doubling and adding over the bits of `scalar` as is done in-circuit.
+pub fn mul<F: FieldExt>(p: &Point<F>, scalar: &F) -> Point<F> {
+    scalar_bits(scalar)
+        .into_iter()
+        .fold((F::zero(), F::one()), |acc, bit| {
+            let acc = add(&acc, &acc);
+            match bit {
+                true => add(&acc, p),
+                false => acc,
+            }
+        })
+}
+
+/// The [SCALAR_BITS] lowest bits of `scalar`, most significant first.
+fn scalar_bits<F: FieldExt>(scalar: &F) -> Vec<bool> {
+    // the representation is little endian
+    let mut bits = scalar
+        .to_repr()
+        .as_ref()
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1 == 1))
+        .take(SCALAR_BITS)
+        .collect::<Vec<bool>>();
+    bits.reverse();
+    bits
+}
+
+fn to_biguint<F: FieldExt>(f: &F) -> BigUint {
+    BigUint::from_bytes_le(f.to_repr().as_ref())
+}
+
+/// Converts `n`, which should be smaller than the modulus of the field, into a field element.
+fn from_biguint<F: FieldExt>(n: &BigUint) -> F {
+    let mut repr = F::Repr::default();
+    let bytes = n.to_bytes_le();
+    repr.as_mut()[..bytes.len()].copy_from_slice(&bytes);
+    F::from_repr(repr).unwrap()
+}
+
+fn order() -> BigUint {
+    ORDER.parse().expect("valid order")
+}
+
+/// Samples a scalar uniformly from `[0, l)`, where `l` is the order of the base point, e.g for use as a secret key.
+pub fn random_scalar<F: FieldExt>(rng: impl RngCore) -> F {
+    from_biguint(&(to_biguint(&F::random(rng)) % order()))
+}
+
+/// The public key of `secret_key`.
+pub fn public_key<F: FieldExt>(secret_key: &F) -> Point<F> {
+    mul(&base_point(), secret_key)
+}
+
+/// An EdDSA signature over Baby Jubjub, alongside the public key of its signer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature<F: FieldExt> {
+    /// The public key of the signer.
+    pub public_key: Point<F>,
+    /// The commitment to the nonce of the signature.
+    pub r: Point<F>,
+    /// The response of the signature, smaller than the order of the base point.
+    pub s: F,
+}
+
+/// The challenge a signature over `message` responds to, `H(r, public_key, message)` with H Poseidon.
+fn challenge<F: FieldExt>(r: &Point<F>, public_key: &Point<F>, message: F) -> F {
+    poseidon_hash(&[r.0, r.1, public_key.0, public_key.1, message])
+}
+
+/// Signs `message` with `secret_key`, such that `[s]B = R + [H(R, A, message)]A` for `B` the base point and `A` the public key.
+pub fn sign<F: FieldExt>(secret_key: &F, message: F, rng: impl RngCore) -> Signature<F> {
+    let public_key = public_key(secret_key);
+    let nonce = random_scalar::<F>(rng);
+    let r = mul(&base_point(), &nonce);
+    let h = challenge(&r, &public_key, message);
+    let s = (to_biguint(&nonce) + to_biguint(&h) * to_biguint(secret_key)) % order();
+    Signature {
+        public_key,
+        r,
+        s: from_biguint(&s),
+    }
+}
+
+/// Verifies `signature` over `message` natively, as is done in-circuit by [EddsaConfig::layout].
+pub fn verify<F: FieldExt>(signature: &Signature<F>, message: F) -> bool {
+    if !is_on_curve(&signature.public_key) || !is_on_curve(&signature.r) {
+        return false;
+    }
+    let h = challenge(&signature.r, &signature.public_key, message);
+    mul(&base_point(), &signature.s) == add(&signature.r, &mul(&signature.public_key, &h))
+}
+
+/// Configuration for verifying an EdDSA signature over Baby Jubjub (see [sign]) in-circuit, with the public key of the signer
+/// exposed as the limbs of its coordinates.
+///
+/// Points are multiplied by scalars over pairs of rows: the accumulator held by an even row is doubled into the next (odd) row, to
+/// which the point is added (or not) depending on the bit of the scalar held by that row. The bits are recomposed into the scalar
+/// alongside, in the `z` column of even rows.
+#[derive(Clone, Debug)]
+pub struct EddsaConfig<F: FieldExt> {
+    x: Column<Advice>,
+    y: Column<Advice>,
+    /// Holds the product of the coordinates of both points being added.
+    t: Column<Advice>,
+    bit: Column<Advice>,
+    px: Column<Advice>,
+    py: Column<Advice>,
+    z: Column<Advice>,
+    q_double: Selector,
+    q_add: Selector,
+    q_bits: Selector,
+    q_on_curve: Selector,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> EddsaConfig<F> {
+    /// Configures the gates doubling, adding and checking points, on their own columns.
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> Self {
+        let [x, y, t, bit, px, py, z] = [(); 7].map(|_| meta.advice_column());
+        for column in [x, y, bit, px, py, z] {
+            meta.enable_equality(column);
+        }
+        let constants: Column<Fixed> = meta.fixed_column();
+        meta.enable_constant(constants);
+        let q_double = meta.selector();
+        let q_add = meta.selector();
+        let q_bits = meta.selector();
+        let q_on_curve = meta.selector();
+
+        let a = Expression::Constant(F::from(A));
+        let d = Expression::Constant(F::from(D));
+        let one = Expression::Constant(F::one());
+
+        meta.create_gate("point double", |meta| {
+            let q = meta.query_selector(q_double);
+            let x1 = meta.query_advice(x, Rotation::cur());
+            let y1 = meta.query_advice(y, Rotation::cur());
+            let t = meta.query_advice(t, Rotation::cur());
+            let x3 = meta.query_advice(x, Rotation::next());
+            let y3 = meta.query_advice(y, Rotation::next());
+            Constraints::with_selector(
+                q,
+                vec![
+                    t.clone() - x1.clone() * x1.clone() * y1.clone() * y1.clone(),
+                    x3 * (one.clone() + d.clone() * t.clone())
+                        - Expression::Constant(F::from(2)) * x1.clone() * y1.clone(),
+                    y3 * (one.clone() - d.clone() * t)
+                        - (y1.clone() * y1 - a.clone() * x1.clone() * x1),
+                ],
+            )
+        });
+
+        // adds (px, py) if the bit is set, and the identity (0, 1) otherwise
+        meta.create_gate("point conditional add", |meta| {
+            let q = meta.query_selector(q_add);
+            let x1 = meta.query_advice(x, Rotation::cur());
+            let y1 = meta.query_advice(y, Rotation::cur());
+            let t = meta.query_advice(t, Rotation::cur());
+            let b = meta.query_advice(bit, Rotation::cur());
+            let px = meta.query_advice(px, Rotation::cur());
+            let py = meta.query_advice(py, Rotation::cur());
+            let x3 = meta.query_advice(x, Rotation::next());
+            let y3 = meta.query_advice(y, Rotation::next());
+            let qx = b.clone() * px.clone();
+            let qy = b.clone() * py.clone() + one.clone() - b.clone();
+            Constraints::with_selector(
+                q,
+                vec![
+                    b.clone() * (one.clone() - b.clone()),
+                    // x1 qx y1 qy, simplified given b is boolean
+                    t.clone() - x1.clone() * y1.clone() * b * px * py,
+                    x3 * (one.clone() + d.clone() * t.clone())
+                        - (x1.clone() * qy.clone() + y1.clone() * qx.clone()),
+                    y3 * (one.clone() - d.clone() * t) - (y1 * qy - a.clone() * x1 * qx),
+                ],
+            )
+        });
+
+        meta.create_gate("scalar bits", |meta| {
+            let q = meta.query_selector(q_bits);
+            let prev = meta.query_advice(z, Rotation::prev());
+            let b = meta.query_advice(bit, Rotation::cur());
+            let next = meta.query_advice(z, Rotation::next());
+            Constraints::with_selector(
+                q,
+                vec![next - (prev * Expression::Constant(F::from(2)) + b)],
+            )
+        });
+
+        meta.create_gate("point on curve", |meta| {
+            let q = meta.query_selector(q_on_curve);
+            let x = meta.query_advice(x, Rotation::cur());
+            let y = meta.query_advice(y, Rotation::cur());
+            let (x2, y2) = (x.clone() * x, y.clone() * y);
+            Constraints::with_selector(q, vec![a * x2.clone() + y2.clone() - one - d * x2 * y2])
+        });
+
+        EddsaConfig {
+            x,
+            y,
+            t,
+            bit,
+            px,
+            py,
+            z,
+            q_double,
+            q_add,
+            q_bits,
+            q_on_curve,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Verifies `signature` over `message` in-circuit, and constrains the public key of the signer to equal the limbs
+    /// (see [crate::circuit::poseidon::digest_to_limbs]) of its coordinates held by the first rows of `public_key`.
+    /// Verifiers should check the public key lies in the prime order subgroup, which isn't constrained in-circuit.
+    /// # Arguments
+    /// * `layouter` - A Halo2 Layouter.
+    /// * `poseidon` - The Poseidon config the challenge of the signature is hashed with.
+    /// * `message` - The (previously assigned) signed message, e.g a digest.
+    /// * `signature` - The signature, which is unknown when generating keys.
+    /// * `public_key` - The instance columns holding the limbs of the `x` and `y` coordinates of the public key.
+    pub fn layout(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        poseidon: &PoseidonConfig<F>,
+        message: &AssignedCell<F, F>,
+        signature: Value<&Signature<F>>,
+        public_key: [Column<Instance>; 2],
+    ) -> Result<(), Error> {
+        let a = self.assign_point(layouter, "public key", signature.map(|s| s.public_key))?;
+        poseidon.expose(layouter, &a.0, public_key[0])?;
+        poseidon.expose(layouter, &a.1, public_key[1])?;
+        let r = self.assign_point(layouter, "signature r", signature.map(|s| s.r))?;
+        let s = layouter.assign_region(
+            || "signature s",
+            |mut region| region.assign_advice(|| "s", self.z, 0, || signature.map(|s| s.s)),
+        )?;
+        let h = poseidon.hash(
+            layouter,
+            &[
+                r.0.clone(),
+                r.1.clone(),
+                a.0.clone(),
+                a.1.clone(),
+                message.clone(),
+            ],
+        )?;
+        let base = layouter.assign_region(
+            || "base point",
+            |mut region| {
+                let (x, y) = base_point::<F>();
+                Ok((
+                    region.assign_advice_from_constant(|| "x", self.x, 0, x)?,
+                    region.assign_advice_from_constant(|| "y", self.y, 0, y)?,
+                ))
+            },
+        )?;
+        let sb = self.mul(layouter, "[s]B", &base, &s)?;
+        let ha = self.mul(layouter, "[h]A", &a, &h)?;
+        self.constrain_sum(layouter, &r, &ha, &sb)
+    }
+
+    /// Assigns `p` and checks it lies on the curve.
+    fn assign_point(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        name: &str,
+        p: Value<Point<F>>,
+    ) -> Result<AssignedPoint<F>, Error> {
+        layouter.assign_region(
+            || name,
+            |mut region| {
+                self.q_on_curve.enable(&mut region, 0)?;
+                Ok((
+                    region.assign_advice(|| "x", self.x, 0, || p.map(|p| p.0))?,
+                    region.assign_advice(|| "y", self.y, 0, || p.map(|p| p.1))?,
+                ))
+            },
+        )
+    }
+
+    /// Assigns the (x, y) coordinates `p` in `row`.
+    fn assign_coords(
+        &self,
+        region: &mut Region<'_, F>,
+        row: usize,
+        p: Value<Point<F>>,
+    ) -> Result<AssignedPoint<F>, Error> {
+        Ok((
+            region.assign_advice(|| "x", self.x, row, || p.map(|p| p.0))?,
+            region.assign_advice(|| "y", self.y, row, || p.map(|p| p.1))?,
+        ))
+    }
+
+    /// Multiplies `p` by `scalar` in-circuit.
+    fn mul(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        name: &str,
+        p: &AssignedPoint<F>,
+        scalar: &AssignedCell<F, F>,
+    ) -> Result<AssignedPoint<F>, Error> {
+        layouter.assign_region(
+            || name,
+            |mut region| {
+                let bits = scalar.value().map(scalar_bits);
+                let p_value = p.0.value().copied().zip(p.1.value().copied());
+                let mut acc = (
+                    region.assign_advice_from_constant(|| "x", self.x, 0, F::zero())?,
+                    region.assign_advice_from_constant(|| "y", self.y, 0, F::one())?,
+                );
+                let mut z = region.assign_advice_from_constant(|| "z", self.z, 0, F::zero())?;
+                for i in 0..SCALAR_BITS {
+                    let row = 2 * i;
+                    let acc_value = acc.0.value().copied().zip(acc.1.value().copied());
+                    self.q_double.enable(&mut region, row)?;
+                    region.assign_advice(
+                        || "t",
+                        self.t,
+                        row,
+                        || acc_value.map(|(x, y)| x * x * y * y),
+                    )?;
+                    let double = acc_value.map(|a| add(&a, &a));
+                    self.assign_coords(&mut region, row + 1, double)?;
+
+                    let bit = bits.as_ref().map(|bits| bits[i]);
+                    self.q_add.enable(&mut region, row + 1)?;
+                    self.q_bits.enable(&mut region, row + 1)?;
+                    region.assign_advice(|| "bit", self.bit, row + 1, || bit.map(F::from))?;
+                    p.0.copy_advice(|| "px", &mut region, self.px, row + 1)?;
+                    p.1.copy_advice(|| "py", &mut region, self.py, row + 1)?;
+                    let sum = double.zip(p_value).zip(bit);
+                    region.assign_advice(
+                        || "t",
+                        self.t,
+                        row + 1,
+                        || {
+                            sum.map(|((d, p), bit)| match bit {
+                                true => d.0 * d.1 * p.0 * p.1,
+                                false => F::zero(),
+                            })
+                        },
+                    )?;
+                    acc = self.assign_coords(
+                        &mut region,
+                        row + 2,
+                        sum.map(|((d, p), bit)| match bit {
+                            true => add(&d, &p),
+                            false => d,
+                        }),
+                    )?;
+                    let z_value = z.value().copied() * Value::known(F::from(2)) + bit.map(F::from);
+                    z = region.assign_advice(|| "z", self.z, row + 2, || z_value)?;
+                }
+                region.constrain_equal(z.cell(), scalar.cell())?;
+                Ok(acc)
+            },
+        )
+    }
+
+    /// Constrains `p + q` to equal `expected`.
+    fn constrain_sum(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        p: &AssignedPoint<F>,
+        q: &AssignedPoint<F>,
+        expected: &AssignedPoint<F>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "signature check",
+            |mut region| {
+                self.q_add.enable(&mut region, 0)?;
+                p.0.copy_advice(|| "x", &mut region, self.x, 0)?;
+                p.1.copy_advice(|| "y", &mut region, self.y, 0)?;
+                region.assign_advice_from_constant(|| "bit", self.bit, 0, F::one())?;
+                q.0.copy_advice(|| "px", &mut region, self.px, 0)?;
+                q.1.copy_advice(|| "py", &mut region, self.py, 0)?;
+                let (p_value, q_value) = (
+                    p.0.value().copied().zip(p.1.value().copied()),
+                    q.0.value().copied().zip(q.1.value().copied()),
+                );
+                let pq = p_value.zip(q_value);
+                region.assign_advice(
+                    || "t",
+                    self.t,
+                    0,
+                    || pq.map(|(p, q)| p.0 * p.1 * q.0 * q.1),
+                )?;
+                let sum = self.assign_coords(&mut region, 1, pq.map(|(p, q)| add(&p, &q)))?;
+                region.constrain_equal(sum.0.cell(), expected.0.cell())?;
+                region.constrain_equal(sum.1.cell(), expected.1.cell())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::poseidon::digest_to_limbs;
+    use crate::fieldutils::i32_to_felt;
+    use halo2_proofs::{circuit::SimpleFloorPlanner, dev::MockProver, plonk::Circuit};
+    use halo2curves::bn256::Fr as F;
+    use rand::rngs::OsRng;
+
+    #[derive(Clone)]
+    struct SignatureCircuit {
+        message: F,
+        signature: Signature<F>,
+    }
+
+    impl Circuit<F> for SignatureCircuit {
+        type Config = (
+            PoseidonConfig<F>,
+            EddsaConfig<F>,
+            Column<Advice>,
+            [Column<Instance>; 2],
+        );
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            let advice = cs.advice_column();
+            cs.enable_equality(advice);
+            let instances = [(); 2].map(|_| {
+                let instance = cs.instance_column();
+                cs.enable_equality(instance);
+                instance
+            });
+            (
+                PoseidonConfig::configure(cs),
+                EddsaConfig::configure(cs),
+                advice,
+                instances,
+            )
+        }
+
+        fn synthesize(
+            &self,
+            (poseidon, eddsa, advice, instances): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let message = layouter.assign_region(
+                || "message",
+                |mut region| region.assign_advice(|| "m", advice, 0, || Value::known(self.message)),
+            )?;
+            eddsa.layout(
+                &mut layouter,
+                &poseidon,
+                &message,
+                Value::known(&self.signature),
+                instances,
+            )
+        }
+    }
+
+    fn public_key_limbs(public_key: &Point<F>) -> Vec<Vec<F>> {
+        [public_key.0, public_key.1]
+            .iter()
+            .map(|c| digest_to_limbs(*c).into_iter().map(i32_to_felt).collect())
+            .collect()
+    }
+
+    #[test]
+    fn base_point_has_prime_order() {
+        let base = base_point::<F>();
+        assert!(is_on_curve(&base));
+        let order = F::from_str_vartime(ORDER).unwrap();
+        assert_eq!(mul(&base, &order), (F::zero(), F::one()));
+    }
+
+    #[test]
+    fn signatures_verify_natively() {
+        let secret_key = random_scalar::<F>(OsRng);
+        let signature = sign(&secret_key, F::from(42), OsRng);
+        assert_eq!(signature.public_key, public_key(&secret_key));
+        assert!(verify(&signature, F::from(42)));
+        assert!(!verify(&signature, F::from(43)));
+    }
+
+    #[test]
+    fn signatures_verify_in_circuit() {
+        let secret_key = random_scalar::<F>(OsRng);
+        let signature = sign(&secret_key, F::from(42), OsRng);
+        let instances = public_key_limbs(&signature.public_key);
+
+        let circuit = SignatureCircuit {
+            message: F::from(42),
+            signature: signature.clone(),
+        };
+        let prover = MockProver::run(12, &circuit, instances.clone()).unwrap();
+        prover.assert_satisfied();
+
+        // signed by a different key
+        let other = public_key_limbs(&public_key(&random_scalar::<F>(OsRng)));
+        let prover = MockProver::run(12, &circuit, other).unwrap();
+        assert!(prover.verify().is_err());
+
+        let circuit = SignatureCircuit {
+            message: F::from(43),
+            signature,
+        };
+        let prover = MockProver::run(12, &circuit, instances).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
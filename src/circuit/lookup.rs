@@ -1,42 +1,100 @@
 use super::*;
+use crate::circuit::table_cache;
 use crate::tensor::ops::activations::*;
-use crate::{fieldutils::felt_to_i32, fieldutils::i32_to_felt};
+use crate::{fieldutils::felt_to_i32_checked, fieldutils::i32_to_felt};
 use halo2_proofs::{
     arithmetic::{Field, FieldExt},
     circuit::{Layouter, Value},
-    plonk::{ConstraintSystem, Expression, Selector, TableColumn},
+    plonk::{ConstraintSystem, Error as PlonkError, Expression, Selector, TableColumn},
     poly::Rotation,
 };
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::{max, min};
 use std::error::Error;
 use std::fmt;
+use std::path::PathBuf;
 use std::{cell::RefCell, marker::PhantomData, rc::Rc};
 
 #[allow(missing_docs)]
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Op {
     Div {
         scale: usize,
     },
+    /// Multiplies its input by `scale`, raising the fixed point scale of activations.
+    Mult {
+        scale: usize,
+    },
     ReLU {
         scale: usize,
     },
     LeakyReLU {
         scale: usize,
+        #[cfg_attr(feature = "serde", serde(with = "slopes"))]
         slope: eq_float::F32,
     },
     PReLU {
         scale: usize,
+        #[cfg_attr(feature = "serde", serde(with = "slopes"))]
         slopes: Vec<eq_float::F32>,
     },
     Sigmoid {
         scales: (usize, usize),
     },
+    /// The composition (in order) of ops chained elementwise, looked up in a single table.
+    Composed {
+        ops: Vec<Op>,
+    },
+}
+
+/// (De)serializes the slopes of ops as plain floats, which [eq_float::F32] wraps.
+#[cfg(feature = "serde")]
+mod slopes {
+    use eq_float::F32;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub trait Slopes: Sized {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error>;
+        fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error>;
+    }
+
+    impl Slopes for F32 {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            self.0.serialize(s)
+        }
+        fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            f32::deserialize(d).map(F32)
+        }
+    }
+
+    impl Slopes for Vec<F32> {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            self.iter()
+                .map(|slope| slope.0)
+                .collect::<Vec<_>>()
+                .serialize(s)
+        }
+        fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            Vec::<f32>::deserialize(d).map(|slopes| slopes.into_iter().map(F32).collect())
+        }
+    }
+
+    pub fn serialize<T: Slopes, S: Serializer>(slopes: &T, s: S) -> Result<S::Ok, S::Error> {
+        slopes.serialize(s)
+    }
+
+    pub fn deserialize<'de, T: Slopes, D: Deserializer<'de>>(d: D) -> Result<T, D::Error> {
+        T::deserialize(d)
+    }
 }
 
 impl fmt::Display for Op {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Op::Div { scale } => write!(f, "div  w/ scale: {}", scale),
+            Op::Mult { scale } => write!(f, "mult w/ scale: {}", scale),
             Op::ReLU { scale } => write!(f, "relu w/ scale: {}", scale),
             Op::LeakyReLU { scale, slope } => {
                 write!(f, "leaky-relu w/ scale: {}, slope: {}", scale, slope)
@@ -45,6 +103,14 @@ impl fmt::Display for Op {
                 write!(f, "leaky-relu w/ scale: {}, slopes: {:#?}", scale, slopes)
             }
             Op::Sigmoid { scales } => write!(f, "sigmoid  w/ scale: {}", scales.0),
+            Op::Composed { ops } => write!(
+                f,
+                "{}",
+                ops.iter()
+                    .map(|op| op.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            ),
         }
     }
 }
@@ -54,10 +120,30 @@ impl Op {
     pub fn f(&self, x: Tensor<i32>) -> Tensor<i32> {
         match &self {
             Op::Div { scale } => const_div(&x, *scale as i32),
+            // saturates rather than wrapping around, such that tables of too many bits hold the bounds of i32
+            Op::Mult { scale } => x.map(|x| {
+                (x as i128 * *scale as i128).clamp(i32::MIN as i128, i32::MAX as i128) as i32
+            }),
             Op::ReLU { scale } => leakyrelu(&x, *scale, 0_f32),
             Op::LeakyReLU { scale, slope } => leakyrelu(&x, *scale, slope.0),
             Op::PReLU { scale, slopes } => leakyrelu(&x, *scale, slopes[0].0),
             Op::Sigmoid { scales } => sigmoid(&x, scales.0, scales.1),
+            Op::Composed { ops } => apply(ops, x),
+        }
+    }
+
+    /// The op applying `ops` in order, flattening ops which are themselves compositions.
+    pub fn compose(ops: &[Op]) -> Op {
+        let ops = ops
+            .iter()
+            .flat_map(|op| match op {
+                Op::Composed { ops } => ops.clone(),
+                op => vec![op.clone()],
+            })
+            .collect::<Vec<_>>();
+        match ops.as_slice() {
+            [op] => op.clone(),
+            _ => Op::Composed { ops },
         }
     }
 
@@ -68,12 +154,28 @@ impl Op {
     }
 }
 
+/// Applies the composition of `nonlinearities` (in order) to `x`.
+pub fn apply(nonlinearities: &[Op], x: Tensor<i32>) -> Tensor<i32> {
+    nonlinearities.iter().fold(x, |x, nl| nl.f(x))
+}
+
+/// The inputs of a lookup table of `bits` bits, from `-2^(bits-1)` to `2^(bits-1) - 1`.
+pub fn table_inputs(bits: usize) -> Tensor<i32> {
+    let base = 2i32;
+    let smallest = -base.pow(bits as u32 - 1);
+    let largest = base.pow(bits as u32 - 1);
+    Tensor::from(smallest..largest)
+}
+
 /// Halo2 lookup table for element wise non-linearities.
 // Table that should be reused across all lookups (so no Clone)
 #[derive(Clone, Debug)]
 pub struct Table<F: FieldExt> {
-    /// composed operations represented by the table
-    pub nonlinearities: Vec<Op>,
+    /// The functions held by the table, each a composition of operations. A table holding several functions (a shared table) tags
+    /// the rows of each with its position in this list, plus one.
+    pub functions: Vec<Vec<Op>>,
+    /// (shared tables only) The tag of each row, telling which function it belongs to.
+    pub table_tag: Option<TableColumn>,
     /// Input to table.
     pub table_input: TableColumn,
     /// Output of table
@@ -82,6 +184,8 @@ pub struct Table<F: FieldExt> {
     pub is_assigned: bool,
     /// Number of bits used in lookup table.
     pub bits: usize,
+    /// The directory the outputs of the functions of the table are cached in, see [table_cache::evaluations].
+    pub cache: Option<PathBuf>,
     _marker: PhantomData<F>,
 }
 
@@ -89,53 +193,98 @@ impl<F: FieldExt> Table<F> {
     /// Configures the table.
     pub fn configure(cs: &mut ConstraintSystem<F>, bits: usize, nonlinearities: &[Op]) -> Table<F> {
         Table {
-            nonlinearities: nonlinearities.to_vec(),
+            functions: vec![nonlinearities.to_vec()],
+            table_tag: None,
+            table_input: cs.lookup_table_column(),
+            table_output: cs.lookup_table_column(),
+            is_assigned: false,
+            bits,
+            cache: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Configures a table shared by several `functions`, spanning `1 + functions.len() * 2^bits` rows over three columns rather
+    /// than two columns per function. Lookups into it are tagged with the function they look up, see [Config::configure_shared].
+    pub fn configure_shared(
+        cs: &mut ConstraintSystem<F>,
+        bits: usize,
+        functions: Vec<Vec<Op>>,
+    ) -> Table<F> {
+        Table {
+            functions,
+            table_tag: Some(cs.lookup_table_column()),
             table_input: cs.lookup_table_column(),
             table_output: cs.lookup_table_column(),
             is_assigned: false,
             bits,
+            cache: None,
             _marker: PhantomData,
         }
     }
+
+    /// Caches the outputs of the functions of the table in `dir` (if any), rather than computing them for every run.
+    pub fn cached(mut self, dir: Option<PathBuf>) -> Self {
+        self.cache = dir;
+        self
+    }
+
+    /// The tag of the rows of `function` in a shared table, if the table holds it.
+    pub fn tag(&self, function: &[Op]) -> Option<usize> {
+        self.functions
+            .iter()
+            .position(|f| f == function)
+            .map(|i| i + 1)
+    }
+
     /// Assigns values to the constraints generated when calling `configure`.
     pub fn layout(&mut self, layouter: &mut impl Layouter<F>) -> Result<(), Box<dyn Error>> {
         if self.is_assigned {
             return Err(Box::new(CircuitError::TableAlreadyAssigned));
         }
 
-        let base = 2i32;
-        let smallest = -base.pow(self.bits as u32 - 1);
-        let largest = base.pow(self.bits as u32 - 1);
-        let inputs = Tensor::from(smallest..largest);
-        let mut evals = inputs.clone();
-        for nl in self.nonlinearities.clone() {
-            evals = nl.f(inputs.clone());
+        let inputs = table_inputs(self.bits);
+        // shared tables start with an all zero row, which lookups that aren't enabled resolve to
+        let mut rows: Vec<(i32, i32, i32)> = match self.table_tag {
+            Some(_) => vec![(0, 0, 0)],
+            None => vec![],
+        };
+        for (i, function) in self.functions.iter().enumerate() {
+            let evals = table_cache::evaluations(function, self.bits, self.cache.as_deref());
+            rows.extend(
+                inputs
+                    .iter()
+                    .zip(evals.iter())
+                    .map(|(x, y)| (i as i32 + 1, *x, *y)),
+            );
         }
         self.is_assigned = true;
         layouter
             .assign_table(
                 || "nl table",
                 |mut table| {
-                    let _ = inputs
-                        .iter()
-                        .enumerate()
-                        .map(|(row_offset, input)| {
+                    for (row_offset, (tag, input, output)) in rows.iter().enumerate() {
+                        if let Some(table_tag) = self.table_tag {
                             table.assign_cell(
-                                || format!("nl_i_col row {}", row_offset),
-                                self.table_input,
+                                || format!("nl_t_col row {}", row_offset),
+                                table_tag,
                                 row_offset,
-                                || Value::known(i32_to_felt::<F>(*input)),
+                                || Value::known(i32_to_felt::<F>(*tag)),
                             )?;
-
-                            table.assign_cell(
-                                || format!("nl_o_col row {}", row_offset),
-                                self.table_output,
-                                row_offset,
-                                || Value::known(i32_to_felt::<F>(evals[row_offset])),
-                            )?;
-                            Ok(())
-                        })
-                        .collect::<Result<Vec<()>, halo2_proofs::plonk::Error>>()?;
+                        }
+                        table.assign_cell(
+                            || format!("nl_i_col row {}", row_offset),
+                            self.table_input,
+                            row_offset,
+                            || Value::known(i32_to_felt::<F>(*input)),
+                        )?;
+                        table.assign_cell(
+                            || format!("nl_o_col row {}", row_offset),
+                            self.table_output,
+                            row_offset,
+                            || Value::known(i32_to_felt::<F>(*output)),
+                        )?;
+                    }
                     Ok(())
                 },
             )
@@ -154,6 +303,16 @@ pub struct Config<F: FieldExt + TensorType> {
     pub qlookup: Selector,
     ///  table used to represent the non-linearity
     pub table: Rc<RefCell<Table<F>>>,
+    /// The operations applied (in order) to the input, which the table holds a function equal to over its domain.
+    pub nonlinearities: Vec<Op>,
+    /// The tag of the function looked up, in shared tables.
+    pub tag: Option<usize>,
+    /// The number of (input, output) column pairs the elements are spread over, each checked by a lookup argument of its own
+    /// over the rows of the region.
+    pub width: usize,
+    /// The index, within the constraint system, of the lookup argument of the first column pair.
+    /// The lookup arguments of the remaining column pairs follow on in order.
+    pub first_lookup: usize,
     _marker: PhantomData<F>,
 }
 
@@ -191,65 +350,134 @@ impl<F: FieldExt + TensorType> Config<F> {
         input: &VarTensor,
         output: &VarTensor,
         table: Rc<RefCell<Table<F>>>,
+    ) -> Self {
+        let nonlinearities = table.borrow().functions[0].clone();
+        let tag = table.borrow().table_tag.map(|_| 1);
+        Self::configure_lookups(cs, input, output, table, nonlinearities, tag)
+    }
+
+    /// Configures and creates an elementwise operation within a circuit looking up the function of `tag` in a shared `table`,
+    /// which equals the composition of `nonlinearities` over the domain of the table (see [Table::configure_shared]).
+    pub fn configure_shared(
+        cs: &mut ConstraintSystem<F>,
+        input: &VarTensor,
+        output: &VarTensor,
+        table: Rc<RefCell<Table<F>>>,
+        tag: usize,
+        nonlinearities: &[Op],
+    ) -> Result<Self, Box<dyn Error>> {
+        if table.borrow().table_tag.is_none() || tag == 0 || tag > table.borrow().functions.len() {
+            return Err(Box::new(CircuitError::LookupInstantiation));
+        }
+        Ok(Self::configure_lookups(
+            cs,
+            input,
+            output,
+            table,
+            nonlinearities.to_vec(),
+            Some(tag),
+        ))
+    }
+
+    fn configure_lookups(
+        cs: &mut ConstraintSystem<F>,
+        input: &VarTensor,
+        output: &VarTensor,
+        table: Rc<RefCell<Table<F>>>,
+        nonlinearities: Vec<Op>,
+        tag: Option<usize>,
     ) -> Self {
         let qlookup = cs.complex_selector();
+        let first_lookup = cs.lookups().len();
+        // lookups which aren't enabled resolve to a row which is always in the table: f(0) for tables of a single function, and
+        // the zero row of shared tables
+        let default_y: F = match tag {
+            Some(_) => F::zero(),
+            None => i32_to_felt(apply(&nonlinearities, vec![0_i32].into_iter().into())[0]),
+        };
 
-        let _ = (0..input.dims().iter().product::<usize>())
-            .map(|i| {
-                let _ = cs.lookup("lk", |cs| {
-                    let qlookup = cs.query_selector(qlookup);
-                    let not_qlookup = Expression::Constant(F::one()) - qlookup.clone();
-                    let default_x = <F as Field>::zero();
-                    let mut default_y = vec![0_i32].into_iter().into();
-                    for nl in table.borrow().nonlinearities.clone() {
-                        default_y = nl.f(default_y)
-                    }
-                    let default_y: F = i32_to_felt(default_y[0]);
-                    let (x, y) = input.cartesian_coord(i);
-                    vec![
-                        (
-                            match &input {
-                                VarTensor::Advice { inner: advices, .. } => {
-                                    qlookup.clone()
-                                        * cs.query_advice(advices[x], Rotation(y as i32))
-                                        + not_qlookup.clone() * default_x
-                                }
-                                VarTensor::Fixed { inner: fixed, .. } => {
-                                    qlookup.clone() * cs.query_fixed(fixed[x], Rotation(y as i32))
-                                        + not_qlookup.clone() * default_x
-                                }
-                            },
-                            table.borrow().table_input,
-                        ),
-                        (
-                            match &output {
-                                VarTensor::Advice { inner: advices, .. } => {
-                                    qlookup * cs.query_advice(advices[x], Rotation(y as i32))
-                                        + not_qlookup * default_y
-                                }
-                                VarTensor::Fixed { inner: fixed, .. } => {
-                                    qlookup * cs.query_fixed(fixed[x], Rotation(y as i32))
-                                        + not_qlookup * default_y
-                                }
-                            },
-                            table.borrow().table_output,
-                        ),
-                    ]
-                });
-            })
-            .collect::<Vec<_>>();
+        // wide tensors are spread over as many column pairs as they need, each looked up row by row, such that the number of
+        // lookup arguments grows with the columns rather than the elements, and the rows taken with the elements per column
+        let len = input.dims().iter().product::<usize>();
+        let cols = min(input.num_cols(), output.num_cols());
+        let width = max(
+            1,
+            min(cols, (len + input.col_size() - 1) / input.col_size()),
+        );
+
+        for x in 0..width {
+            let _ = cs.lookup("lk", |cs| {
+                let qlookup = cs.query_selector(qlookup);
+                let not_qlookup = Expression::Constant(F::one()) - qlookup.clone();
+                let default_x = <F as Field>::zero();
+                let mut lookups = vec![];
+                if let (Some(tag), Some(table_tag)) = (tag, table.borrow().table_tag) {
+                    lookups.push((
+                        qlookup.clone() * Expression::Constant(F::from(tag as u64)),
+                        table_tag,
+                    ));
+                }
+                lookups.push((
+                    match &input {
+                        VarTensor::Advice { inner: advices, .. } => {
+                            qlookup.clone() * cs.query_advice(advices[x], Rotation::cur())
+                                + not_qlookup.clone() * default_x
+                        }
+                        VarTensor::Fixed { inner: fixed, .. } => {
+                            qlookup.clone() * cs.query_fixed(fixed[x], Rotation::cur())
+                                + not_qlookup.clone() * default_x
+                        }
+                    },
+                    table.borrow().table_input,
+                ));
+                lookups.push((
+                    match &output {
+                        VarTensor::Advice { inner: advices, .. } => {
+                            qlookup * cs.query_advice(advices[x], Rotation::cur())
+                                + not_qlookup * default_y
+                        }
+                        VarTensor::Fixed { inner: fixed, .. } => {
+                            qlookup * cs.query_fixed(fixed[x], Rotation::cur())
+                                + not_qlookup * default_y
+                        }
+                    },
+                    table.borrow().table_output,
+                ));
+                lookups
+            });
+        }
 
         Self {
             input: input.clone(),
             output: output.clone(),
             table,
+            nonlinearities,
+            tag,
             qlookup,
+            width,
+            first_lookup,
             _marker: PhantomData,
         }
     }
 }
 
 impl<F: FieldExt + TensorType> Config<F> {
+    /// The rows taken by `len` elements spread over the column pairs of the lookup.
+    pub fn rows(&self, len: usize) -> usize {
+        max(1, (len + self.width - 1) / self.width)
+    }
+
+    /// The element of a tensor of `len` elements checked by the lookup argument of index `lookup_index` within the constraint
+    /// system, at `offset` rows into the region, if any.
+    pub fn element(&self, len: usize, lookup_index: usize, offset: usize) -> Option<usize> {
+        let x = lookup_index.checked_sub(self.first_lookup)?;
+        let i = x * self.rows(len) + offset;
+        match x < self.width && i < len {
+            true => Some(i),
+            false => None,
+        }
+    }
+
     /// Configures and creates an elementwise operation within a circuit.
     /// Variables are supplied as a single VarTensors.
     pub fn configure(
@@ -277,39 +505,62 @@ impl<F: FieldExt + TensorType> Config<F> {
         if !self.table.borrow().is_assigned {
             self.table.borrow_mut().layout(layouter)?
         }
+        // inputs which don't fit 32 bits fail the region, and are reported rather than wrapping around
+        let mut overflow = None;
         let mut t = ValTensor::from(
             match layouter.assign_region(
                 || "Elementwise", // the name of the region
                 |mut region| {
-                    self.qlookup.enable(&mut region, 0)?;
+                    let len = values.dims().iter().product::<usize>();
+                    let rows = self.rows(len);
+                    for row in 0..rows {
+                        self.qlookup.enable(&mut region, row)?;
+                    }
+                    let input_var = self.input.with_col_size(rows);
+                    let output_var = self.output.with_col_size(rows);
 
-                    let w = self.input.assign(&mut region, 0, values)?;
+                    let w = input_var.assign(&mut region, 0, values)?;
 
                     let mut res: Vec<i32> = vec![];
                     let _ = Tensor::from(w.iter().map(|acaf| (*acaf).value_field()).map(|vaf| {
-                        vaf.map(|f| {
-                            res.push(felt_to_i32(f.evaluate()));
+                        vaf.map(|f| match felt_to_i32_checked(f.evaluate()) {
+                            Ok(x) => res.push(x),
+                            Err(x) => overflow = Some(x),
                         })
                     }));
+                    if overflow.is_some() {
+                        return Err(PlonkError::Synthesis);
+                    }
 
                     // for key generation res will be empty and we need to return a set of unassigned values
                     let output: Tensor<Value<F>> = match res.len() {
                         0 => w.map(|_| Value::unknown()),
-                        _ => {
-                            let mut x = res.into_iter().into();
-                            for nl in self.table.borrow().nonlinearities.clone() {
-                                x = nl.f(x);
-                            }
-                            x.map(|elem| Value::known(i32_to_felt(elem)))
-                        }
+                        _ => apply(&self.nonlinearities, res.into_iter().into())
+                            .map(|elem| Value::known(i32_to_felt(elem))),
                     };
 
-                    self.output.assign(&mut region, 0, &ValTensor::from(output))
+                    let assigned = output_var.assign(&mut region, 0, &ValTensor::from(output))?;
+
+                    // the rows of the last column pair past the end of the tensor are looked up too, so they hold (0, f(0))
+                    let padding = rows * self.width - len;
+                    if padding > 0 {
+                        let zeros: Tensor<i32> = vec![0; padding].into_iter().into();
+                        let default_y = apply(&self.nonlinearities, zeros.clone());
+                        let to_values = |t: Tensor<i32>| {
+                            ValTensor::from(t.map(|elem| Value::known(i32_to_felt::<F>(elem))))
+                        };
+                        input_var.assign(&mut region, len, &to_values(zeros))?;
+                        output_var.assign(&mut region, len, &to_values(default_y))?;
+                    }
+                    Ok(assigned)
                 },
             ) {
                 Ok(a) => a,
                 Err(e) => {
-                    return Err(Box::new(e));
+                    return match overflow {
+                        Some(x) => Err(Box::new(CircuitError::WitnessOverflow(x))),
+                        None => Err(Box::new(e)),
+                    };
                 }
             },
         );
@@ -375,4 +626,169 @@ mod tests {
         let prover = MockProver::run(4_u32, &circuit, vec![]).unwrap();
         prover.assert_satisfied();
     }
+
+    #[derive(Clone)]
+    struct OverflowCircuit<F: FieldExt + TensorType> {
+        pub input: ValTensor<F>,
+    }
+
+    impl<F: FieldExt + TensorType> Circuit<F> for OverflowCircuit<F> {
+        type Config = Config<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            <ReLUCircuit<F> as Circuit<F>>::configure(cs)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let e = config.layout(&mut layouter, &self.input).unwrap_err();
+            assert!(matches!(
+                e.downcast_ref::<CircuitError>(),
+                Some(CircuitError::WitnessOverflow(x)) if *x == 2i128.pow(40)
+            ));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reports_inputs_overflowing_32_bits() {
+        let input: Tensor<Value<F>> =
+            Tensor::new(Some(&[Value::<F>::known(F::from(2u64.pow(40)))]), &[1]).unwrap();
+
+        let circuit = OverflowCircuit::<F> {
+            input: ValTensor::from(input),
+        };
+
+        MockProver::run(4_u32, &circuit, vec![]).unwrap();
+    }
+
+    #[derive(Clone)]
+    struct SharedTableCircuit<F: FieldExt + TensorType> {
+        pub input: ValTensor<F>,
+    }
+
+    impl<F: FieldExt + TensorType> Circuit<F> for SharedTableCircuit<F> {
+        type Config = [Config<F>; 2];
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            let advices = (0..2)
+                .map(|_| VarTensor::new_advice(cs, 5, 1, vec![1], true, 512))
+                .collect::<Vec<_>>();
+
+            let relu = Op::ReLU { scale: 1 };
+            let sigmoid = Op::Sigmoid { scales: (1, 1) };
+            let table = Rc::new(RefCell::new(Table::configure_shared(
+                cs,
+                2,
+                vec![vec![relu.clone()], vec![sigmoid.clone()]],
+            )));
+            [
+                Config::configure_shared(cs, &advices[0], &advices[1], table.clone(), 1, &[relu])
+                    .unwrap(),
+                Config::configure_shared(cs, &advices[0], &advices[1], table, 2, &[sigmoid])
+                    .unwrap(),
+            ]
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            for config in config.iter() {
+                config.layout(&mut layouter, &self.input).unwrap();
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn shares_tables_across_functions() {
+        let input: Tensor<Value<F>> =
+            Tensor::new(Some(&[Value::<F>::known(-F::from(2_u64))]), &[1]).unwrap();
+
+        let circuit = SharedTableCircuit::<F> {
+            input: ValTensor::from(input),
+        };
+
+        let prover = MockProver::run(5_u32, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+    }
+
+    #[derive(Clone)]
+    struct WideReLUCircuit<F: FieldExt + TensorType> {
+        pub input: ValTensor<F>,
+    }
+
+    impl<F: FieldExt + TensorType> Circuit<F> for WideReLUCircuit<F> {
+        type Config = Config<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            // columns of 4 rows, three of which hold the 10 elements
+            let advices = (0..2)
+                .map(|_| VarTensor::new_advice(cs, 5, 10, vec![10], true, 4))
+                .collect::<Vec<_>>();
+
+            Self::Config::configure(cs, &advices[0], &advices[1], 4, &[Op::ReLU { scale: 1 }])
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let output = config.layout(&mut layouter, &self.input).unwrap();
+            assert_eq!(output.dims(), vec![10]);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn spreads_lookups_over_column_pairs() {
+        let input: Tensor<Value<F>> = Tensor::new(
+            Some(
+                &(-5..5)
+                    .map(|i: i64| match i < 0 {
+                        true => Value::known(-F::from(-i as u64)),
+                        false => Value::known(F::from(i as u64)),
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            &[10],
+        )
+        .unwrap();
+
+        let mut cs = ConstraintSystem::<F>::default();
+        let config = WideReLUCircuit::<F>::configure(&mut cs);
+        // a lookup argument per column pair rather than per element, the elements spread over 4 rows, with the last two rows
+        // of the third column padded
+        assert_eq!((config.width, cs.lookups().len()), (3, 3));
+        assert_eq!(config.rows(10), 4);
+        assert_eq!(config.element(10, 2, 1), Some(9));
+        assert_eq!(config.element(10, 2, 2), None);
+
+        let circuit = WideReLUCircuit::<F> {
+            input: ValTensor::from(input),
+        };
+        let prover = MockProver::run(5_u32, &circuit, vec![]).unwrap();
+        prover.assert_satisfied();
+    }
 }
@@ -0,0 +1,282 @@
+use crate::circuit::poseidon::{poseidon_hash, PoseidonConfig};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Instance, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+/// The path from a leaf of a Merkle tree to its root, where each node is the Poseidon hash of its two children.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerklePath<F: FieldExt> {
+    /// The index of the leaf, whose bits (least significant first) tell whether the node at each level is a right child.
+    pub index: usize,
+    /// The siblings of the nodes on the path, from the leaf upwards.
+    pub siblings: Vec<F>,
+}
+
+impl<F: FieldExt> MerklePath<F> {
+    /// Computes the root of the tree from `leaf`, as is done in-circuit by [MerkleConfig::layout].
+    pub fn root(&self, leaf: F) -> F {
+        self.siblings
+            .iter()
+            .enumerate()
+            .fold(leaf, |node, (level, sibling)| {
+                match (self.index >> level) & 1 {
+                    1 => poseidon_hash(&[*sibling, node]),
+                    _ => poseidon_hash(&[node, *sibling]),
+                }
+            })
+    }
+}
+
+/// A Merkle tree of depth `depth` over `leaves`, padded with zero leaves.
+#[derive(Clone, Debug)]
+pub struct MerkleTree<F: FieldExt> {
+    /// The nodes of each level, from the leaves upwards.
+    levels: Vec<Vec<F>>,
+}
+
+impl<F: FieldExt> MerkleTree<F> {
+    /// Builds the tree, or returns `None` if there are more than `2^depth` leaves.
+    pub fn new(leaves: &[F], depth: usize) -> Option<Self> {
+        if leaves.len() > 1 << depth {
+            return None;
+        }
+        let mut level = leaves.to_vec();
+        level.resize(1 << depth, F::zero());
+        let mut levels = vec![level];
+        for _ in 0..depth {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(poseidon_hash)
+                .collect();
+            levels.push(next);
+        }
+        Some(MerkleTree { levels })
+    }
+
+    /// The root of the tree.
+    pub fn root(&self) -> F {
+        self.levels.last().unwrap()[0]
+    }
+
+    /// The path from the leaf at `index` to the root.
+    pub fn path(&self, index: usize) -> MerklePath<F> {
+        let depth = self.levels.len() - 1;
+        MerklePath {
+            index,
+            siblings: (0..depth)
+                .map(|level| self.levels[level][(index >> level) ^ 1])
+                .collect(),
+        }
+    }
+}
+
+/// Configuration for proving a (previously assigned) leaf is part of a Merkle tree, whose root is constrained to equal the limbs
+/// (see [crate::circuit::poseidon::digest_to_limbs]) held by an instance column.
+///
+/// At each level, the node and its sibling are swapped depending on the bit of the index held alongside, and then hashed together.
+#[derive(Clone, Debug)]
+pub struct MerkleConfig<F: FieldExt> {
+    node: Column<Advice>,
+    sibling: Column<Advice>,
+    bit: Column<Advice>,
+    left: Column<Advice>,
+    right: Column<Advice>,
+    q_swap: Selector,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> MerkleConfig<F> {
+    /// Configures the gate ordering the children at each level, on its own columns.
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> Self {
+        let [node, sibling, bit, left, right] = [(); 5].map(|_| meta.advice_column());
+        for column in [node, left, right] {
+            meta.enable_equality(column);
+        }
+        let q_swap = meta.selector();
+
+        meta.create_gate("merkle swap", |meta| {
+            let q = meta.query_selector(q_swap);
+            let node = meta.query_advice(node, Rotation::cur());
+            let sibling = meta.query_advice(sibling, Rotation::cur());
+            let bit = meta.query_advice(bit, Rotation::cur());
+            let left = meta.query_advice(left, Rotation::cur());
+            let right = meta.query_advice(right, Rotation::cur());
+            let one = Expression::Constant(F::one());
+            Constraints::with_selector(
+                q,
+                vec![
+                    bit.clone() * (one - bit.clone()),
+                    left - (node.clone() + bit.clone() * (sibling.clone() - node.clone())),
+                    right - (sibling.clone() + bit * (node - sibling)),
+                ],
+            )
+        });
+
+        MerkleConfig {
+            node,
+            sibling,
+            bit,
+            left,
+            right,
+            q_swap,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Constrains `leaf` to be part of the tree whose root is held by the first rows of `root`, as limbs.
+    /// # Arguments
+    /// * `layouter` - A Halo2 Layouter.
+    /// * `poseidon` - The Poseidon config nodes are hashed with.
+    /// * `leaf` - The (previously assigned) leaf.
+    /// * `path` - The path from the leaf to the root, unknown when generating keys.
+    /// * `depth` - The depth of the tree, which fixes the shape of the circuit.
+    /// * `root` - The instance column holding the limbs of the root.
+    pub fn layout(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        poseidon: &PoseidonConfig<F>,
+        leaf: &AssignedCell<F, F>,
+        path: Value<&MerklePath<F>>,
+        depth: usize,
+        root: Column<Instance>,
+    ) -> Result<(), Error> {
+        let mut node = leaf.clone();
+        for level in 0..depth {
+            let (left, right) = layouter.assign_region(
+                || format!("merkle level {}", level),
+                |mut region| {
+                    self.q_swap.enable(&mut region, 0)?;
+                    let node = node.copy_advice(|| "node", &mut region, self.node, 0)?;
+                    let sibling = region.assign_advice(
+                        || "sibling",
+                        self.sibling,
+                        0,
+                        || path.map(|p| p.siblings[level]),
+                    )?;
+                    let bit = path.map(|p| (p.index >> level) & 1 == 1);
+                    region.assign_advice(|| "bit", self.bit, 0, || bit.map(F::from))?;
+                    let pair = node.value().copied().zip(sibling.value().copied()).zip(bit);
+                    let left = region.assign_advice(
+                        || "left",
+                        self.left,
+                        0,
+                        || pair.map(|((n, s), bit)| if bit { s } else { n }),
+                    )?;
+                    let right = region.assign_advice(
+                        || "right",
+                        self.right,
+                        0,
+                        || pair.map(|((n, s), bit)| if bit { n } else { s }),
+                    )?;
+                    Ok((left, right))
+                },
+            )?;
+            node = poseidon.hash(layouter, &[left, right])?;
+        }
+        poseidon.expose(layouter, &node, root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::poseidon::digest_to_limbs;
+    use crate::fieldutils::i32_to_felt;
+    use halo2_proofs::{circuit::SimpleFloorPlanner, dev::MockProver, plonk::Circuit};
+    use halo2curves::pasta::Fp as F;
+
+    const DEPTH: usize = 3;
+
+    #[derive(Clone)]
+    struct InclusionCircuit {
+        leaf: F,
+        path: MerklePath<F>,
+    }
+
+    impl Circuit<F> for InclusionCircuit {
+        type Config = (
+            PoseidonConfig<F>,
+            MerkleConfig<F>,
+            Column<Advice>,
+            Column<Instance>,
+        );
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            let advice = cs.advice_column();
+            cs.enable_equality(advice);
+            let instance = cs.instance_column();
+            cs.enable_equality(instance);
+            (
+                PoseidonConfig::configure(cs),
+                MerkleConfig::configure(cs),
+                advice,
+                instance,
+            )
+        }
+
+        fn synthesize(
+            &self,
+            (poseidon, merkle, advice, instance): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let leaf = layouter.assign_region(
+                || "leaf",
+                |mut region| region.assign_advice(|| "leaf", advice, 0, || Value::known(self.leaf)),
+            )?;
+            merkle.layout(
+                &mut layouter,
+                &poseidon,
+                &leaf,
+                Value::known(&self.path),
+                DEPTH,
+                instance,
+            )
+        }
+    }
+
+    fn limbs(root: F) -> Vec<Vec<F>> {
+        vec![digest_to_limbs(root).into_iter().map(i32_to_felt).collect()]
+    }
+
+    #[test]
+    fn paths_lead_to_the_root() {
+        let leaves = (0..5).map(|i| F::from(i as u64 + 1)).collect::<Vec<_>>();
+        let tree = MerkleTree::new(&leaves, DEPTH).unwrap();
+        for (i, leaf) in leaves.iter().enumerate() {
+            assert_eq!(tree.path(i).root(*leaf), tree.root());
+        }
+        assert_ne!(tree.path(0).root(leaves[1]), tree.root());
+        assert!(MerkleTree::new(&vec![F::zero(); 9], DEPTH).is_none());
+    }
+
+    #[test]
+    fn inclusion_is_proven_in_circuit() {
+        let leaves = (0..8).map(|i| F::from(i as u64 * 3)).collect::<Vec<_>>();
+        let tree = MerkleTree::new(&leaves, DEPTH).unwrap();
+        let circuit = InclusionCircuit {
+            leaf: leaves[6],
+            path: tree.path(6),
+        };
+        let prover = MockProver::run(9, &circuit, limbs(tree.root())).unwrap();
+        prover.assert_satisfied();
+
+        // a leaf which isn't in the tree
+        let circuit = InclusionCircuit {
+            leaf: F::from(1),
+            path: tree.path(6),
+        };
+        let prover = MockProver::run(9, &circuit, limbs(tree.root())).unwrap();
+        assert!(prover.verify().is_err());
+    }
+}
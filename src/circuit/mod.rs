@@ -1,10 +1,22 @@
 use crate::tensor::*;
+/// Comparisons between the elements of tensors and constants, using lookups in a range table.
+pub mod compare;
+/// Element-wise operations over inputs too wide for a lookup table, decomposed into limbs looked up in smaller tables.
+pub mod decomposed;
+/// EdDSA signatures over Baby Jubjub, verified in-circuit.
+pub mod eddsa;
 /// Element-wise operations using lookup tables.
 pub mod lookup;
+/// Poseidon Merkle trees, with inclusion proven in-circuit.
+pub mod merkle;
 /// Structs and methods for configuring and assigning polynomial constraints to a gate within a Halo2 circuit.
 pub mod polynomial;
-/// A layer for range checks using polynomials.
+/// Poseidon hashing of previously assigned values, with the digest exposed in an instance column.
+pub mod poseidon;
+/// A layer for range checks using polynomials, or lookups in a range table.
 pub mod range;
+/// Lookup tables computed once and cached on disk, keyed by their function and number of bits.
+pub mod table_cache;
 /// Utility functions for building gates.
 pub mod utils;
 
@@ -22,6 +34,7 @@ pub enum CircuitError {
     /// A lookup table was was already assigned
     #[error("attempting to initialize an already instantiated lookup table")]
     TableAlreadyAssigned,
+    /// A value fed into a lookup doesn't fit the 32 bit integers lookups are evaluated over
+    #[error("lookup input {0} overflows 32 bit witness values: lower the scale or bits")]
+    WitnessOverflow(i128),
 }
-
-
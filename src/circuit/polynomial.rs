@@ -3,10 +3,17 @@ use crate::tensor::ops::*;
 use crate::tensor::{Tensor, TensorType};
 use halo2_proofs::{
     arithmetic::FieldExt,
-    circuit::Layouter,
-    plonk::{ConstraintSystem, Constraints, Expression, Selector},
+    circuit::{AssignedCell, Layouter, Region, Value},
+    plonk::{
+        Advice, Column, ConstraintSystem, Constraints, Error as PlonkError, Expression, Fixed,
+        Selector, VirtualCells,
+    },
+    poly::Rotation,
 };
 use itertools::Itertools;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::max;
 use std::error::Error;
 use std::fmt;
 use std::marker::PhantomData;
@@ -14,10 +21,12 @@ use std::marker::PhantomData;
 #[allow(missing_docs)]
 /// An enum representing the operations that can be merged into a single circuit gate.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Op {
     Identity,
     Reshape(Vec<usize>),
     Flatten(Vec<usize>),
+    Permute(Vec<usize>),
     Add,
     Sub,
     Sum,
@@ -50,6 +59,7 @@ impl fmt::Display for Op {
             Op::Identity => write!(f, "identity"),
             Op::Reshape(new_dims) => write!(f, "reshape to {:?}", new_dims),
             Op::Flatten(new_dims) => write!(f, "flatten to {:?}", new_dims),
+            Op::Permute(axes) => write!(f, "permute {:?}", axes),
             Op::Add => write!(f, "add"),
             Op::Sub => write!(f, "sub"),
             Op::Sum => write!(f, "sum"),
@@ -105,6 +115,8 @@ impl Op {
                 t.reshape(new_dims);
                 Ok(t)
             }
+            // a view over the input, such that fused ops consume it without the permuted values being laid out
+            Op::Permute(axes) => Ok(inputs[0].strided().permute(axes)?.to_tensor()),
             Op::Add => add(&inputs),
             Op::Sub => sub(&inputs),
             Op::Mult => mult(&inputs),
@@ -192,6 +204,19 @@ impl<F: FieldExt + TensorType> Config<F> {
         inputs: &[VarTensor],
         output: &VarTensor,
         nodes: &[Node],
+    ) -> Self {
+        Self::configure_sparse(meta, inputs, output, nodes, &vec![None; inputs.len()])
+    }
+
+    /// Configures the operations like [Config::configure], with the elements of each input flagged in `zeros` (in row major
+    /// order, if any) known to be zero, e.g pruned params. They aren't queried by the gate, and the terms they zero out are
+    /// pruned from its constraints.
+    pub fn configure_sparse(
+        meta: &mut ConstraintSystem<F>,
+        inputs: &[VarTensor],
+        output: &VarTensor,
+        nodes: &[Node],
+        zeros: &[Option<Vec<bool>>],
     ) -> Self {
         let mut config = Self {
             selector: meta.selector(),
@@ -206,7 +231,21 @@ impl<F: FieldExt + TensorType> Config<F> {
             let qis = config
                 .inputs
                 .iter()
-                .map(|input| input.query(meta, 0).expect("poly: input query failed"))
+                .zip(zeros)
+                .map(|(input, zeros)| {
+                    let query = input.query(meta, 0).expect("poly: input query failed");
+                    match zeros {
+                        Some(zeros) => query
+                            .enum_map::<_, _, CircuitError>(|i, q| {
+                                Ok(match zeros[i] {
+                                    true => Expression::Constant(F::zero()),
+                                    false => q,
+                                })
+                            })
+                            .expect("poly: input query failed"),
+                        None => query,
+                    }
+                })
                 .collect::<Vec<_>>();
 
             let mut config_outputs = vec![];
@@ -222,7 +261,9 @@ impl<F: FieldExt + TensorType> Config<F> {
                 .expect("poly: output query failed");
 
             let constraints = witnessed_output
-                .enum_map::<_, _, CircuitError>(|i, o| Ok(o - expected_output[i].clone()))
+                .enum_map::<_, _, CircuitError>(|i, o| {
+                    Ok(prune_zeros(o) - expected_output[i].clone())
+                })
                 .expect("poly: failed to create constraints");
 
             Constraints::with_selector(selector, constraints)
@@ -271,12 +312,16 @@ impl<F: FieldExt + TensorType> Config<F> {
                     Self::apply_op(node, &inputs, &mut layout_outputs)
                         .expect("poly: apply op failed");
                 }
-                let output: ValTensor<F> = match layout_outputs.last() {
+                let mut output: ValTensor<F> = match layout_outputs.last() {
                     Some(a) => a.clone().into(),
                     None => {
                         panic!("poly: empty outputs");
                     }
                 };
+                // broadcasting may leave leading unit dims (e.g a batch dim of a constant) the output doesn't have
+                output
+                    .reshape(&self.output.dims())
+                    .map_err(|_| PlonkError::Synthesis)?;
 
                 let output = self.output.assign(&mut region, offset, &output)?;
                 Ok(output)
@@ -310,6 +355,321 @@ impl<F: FieldExt + TensorType> Config<F> {
     }
 }
 
+fn is_zero<F: FieldExt>(expr: &Expression<F>) -> bool {
+    matches!(expr, Expression::Constant(c) if *c == F::zero())
+}
+
+/// Folds away the terms of `expr` multiplied by a constant zero (and the sums of such terms), as left by the queries of
+/// known zeros (see [Config::configure_sparse]).
+fn prune_zeros<F: FieldExt>(expr: Expression<F>) -> Expression<F> {
+    let zero = || Expression::Constant(F::zero());
+    match expr {
+        Expression::Sum(a, b) => match (prune_zeros(*a), prune_zeros(*b)) {
+            (a, b) if is_zero(&a) => b,
+            (a, b) if is_zero(&b) => a,
+            (a, b) => a + b,
+        },
+        Expression::Product(a, b) => match (prune_zeros(*a), prune_zeros(*b)) {
+            (a, b) if is_zero(&a) || is_zero(&b) => zero(),
+            (a, b) => a * b,
+        },
+        Expression::Scaled(a, c) => match prune_zeros(*a) {
+            a if is_zero(&a) || c == F::zero() => zero(),
+            a => a * c,
+        },
+        Expression::Negated(a) => match prune_zeros(*a) {
+            a if is_zero(&a) => zero(),
+            a => -a,
+        },
+        expr => expr,
+    }
+}
+
+/// The columns holding one side of the products of a [DotConfig]: advice columns, or fixed columns for public params.
+#[derive(Clone, Debug)]
+pub enum DotColumns {
+    /// Values assigned at proving time
+    Advice(Vec<Column<Advice>>),
+    /// Values assigned at circuit definition time
+    Fixed(Vec<Column<Fixed>>),
+}
+
+impl DotColumns {
+    fn new<F: FieldExt>(meta: &mut ConstraintSystem<F>, width: usize, fixed: bool) -> Self {
+        match fixed {
+            true => DotColumns::Fixed((0..width).map(|_| meta.fixed_column()).collect()),
+            false => DotColumns::Advice(
+                (0..width)
+                    .map(|_| {
+                        let col = meta.advice_column();
+                        meta.enable_equality(col);
+                        col
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    fn query<F: FieldExt>(&self, meta: &mut VirtualCells<'_, F>, k: usize) -> Expression<F> {
+        match self {
+            DotColumns::Advice(cols) => meta.query_advice(cols[k], Rotation::cur()),
+            DotColumns::Fixed(cols) => meta.query_fixed(cols[k], Rotation::cur()),
+        }
+    }
+
+    /// Assigns the element `i` of `values` to column `k` at `row`, returning its value.
+    fn assign<F: FieldExt + TensorType>(
+        &self,
+        region: &mut Region<'_, F>,
+        k: usize,
+        row: usize,
+        values: &ValTensor<F>,
+        i: usize,
+    ) -> Result<Value<F>, PlonkError> {
+        let value = match (self, values) {
+            (DotColumns::Advice(cols), ValTensor::Value { inner, .. }) => region
+                .assign_advice(|| "dot", cols[k], row, || inner[i])?
+                .value()
+                .copied(),
+            (DotColumns::Advice(cols), ValTensor::AssignedValue { inner, .. }) => region
+                .assign_advice(|| "dot", cols[k], row, || inner[i])?
+                .value_field()
+                .evaluate(),
+            (DotColumns::Advice(cols), ValTensor::PrevAssigned { inner, .. }) => inner[i]
+                .copy_advice(|| "dot", region, cols[k], row)?
+                .value()
+                .copied(),
+            (DotColumns::Advice(cols), ValTensor::Instance { inner, .. }) => region
+                .assign_advice_from_instance(|| "dot", *inner, i, cols[k], row)?
+                .value()
+                .copied(),
+            (DotColumns::Fixed(cols), ValTensor::Value { inner, .. }) => region
+                .assign_fixed(|| "dot", cols[k], row, || inner[i])?
+                .value()
+                .copied(),
+            (DotColumns::Fixed(cols), ValTensor::AssignedValue { inner, .. }) => region
+                .assign_fixed(|| "dot", cols[k], row, || inner[i])?
+                .value_field()
+                .evaluate(),
+            // values assigned at proving time can't be laid out in fixed columns
+            (DotColumns::Fixed(_), _) => return Err(PlonkError::Synthesis),
+        };
+        Ok(value)
+    }
+
+    /// Assigns a zero to column `k` at `row`, constrained to be zero through the `constants` of the config.
+    fn assign_zero<F: FieldExt>(
+        &self,
+        region: &mut Region<'_, F>,
+        k: usize,
+        row: usize,
+    ) -> Result<(), PlonkError> {
+        match self {
+            DotColumns::Advice(cols) => {
+                region.assign_advice_from_constant(|| "pad", cols[k], row, F::zero())?;
+            }
+            DotColumns::Fixed(cols) => {
+                region.assign_fixed(|| "pad", cols[k], row, || Value::known(F::zero()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Configuration for matrix products `lhs · rhs (+ bias)` laid out as dot products accumulated over consecutive rows, `width`
+/// products at a time. Unlike a fused [Config], whose gate spans the whole of its tensors, the columns and rotations of the
+/// gates don't depend on the size of the product, such that large fully connected layers needn't raise `max_rotations` nor
+/// the number of columns; they take rows instead. A single config can lay out any number of products.
+#[derive(Clone, Debug)]
+pub struct DotConfig<F: FieldExt + TensorType> {
+    /// The left hand side of the products of each row (e.g the kernel of an affine layer), and the bias of bias rows.
+    pub lhs: DotColumns,
+    /// The right hand side of the products of each row.
+    pub rhs: DotColumns,
+    /// The dot product accumulated up to each row.
+    pub acc: Column<Advice>,
+    /// The number of products of each row.
+    pub width: usize,
+    /// Starts the accumulation of a dot product: `acc = Σ lhs * rhs`.
+    pub q_first: Selector,
+    /// Accumulates a chunk of a dot product: `acc = acc[-1] + Σ lhs * rhs`.
+    pub q_next: Selector,
+    /// Adds the bias held by the first column of `lhs`: `acc = acc[-1] + lhs[0]`.
+    pub q_bias: Selector,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt + TensorType> DotConfig<F> {
+    /// Configures the gates of dot products of `width` products per row, with the left (right) hand side of the products in
+    /// fixed columns if `fixed.0` (`fixed.1`) is set, e.g for public params.
+    pub fn configure(meta: &mut ConstraintSystem<F>, width: usize, fixed: (bool, bool)) -> Self {
+        let constants = meta.fixed_column();
+        meta.enable_constant(constants);
+        let acc = meta.advice_column();
+        meta.enable_equality(acc);
+        let config = Self {
+            lhs: DotColumns::new(meta, width, fixed.0),
+            rhs: DotColumns::new(meta, width, fixed.1),
+            acc,
+            width,
+            q_first: meta.selector(),
+            q_next: meta.selector(),
+            q_bias: meta.selector(),
+            _marker: PhantomData,
+        };
+
+        let products = |meta: &mut VirtualCells<'_, F>| {
+            (0..width)
+                .map(|k| config.lhs.query(meta, k) * config.rhs.query(meta, k))
+                .fold(Expression::Constant(F::zero()), |sum, p| sum + p)
+        };
+        // the first row of a dot product doesn't query the previous row, so each step gets a gate of its own
+        meta.create_gate("dot first", |meta| {
+            let q_first = meta.query_selector(config.q_first);
+            let acc = meta.query_advice(config.acc, Rotation::cur());
+            Constraints::with_selector(q_first, vec![acc - products(meta)])
+        });
+        meta.create_gate("dot next", |meta| {
+            let q_next = meta.query_selector(config.q_next);
+            let acc = meta.query_advice(config.acc, Rotation::cur());
+            let prev = meta.query_advice(config.acc, Rotation::prev());
+            Constraints::with_selector(q_next, vec![acc - prev - products(meta)])
+        });
+        meta.create_gate("dot bias", |meta| {
+            let q_bias = meta.query_selector(config.q_bias);
+            let acc = meta.query_advice(config.acc, Rotation::cur());
+            let prev = meta.query_advice(config.acc, Rotation::prev());
+            let bias = config.lhs.query(meta, 0);
+            Constraints::with_selector(q_bias, vec![acc - prev - bias])
+        });
+
+        config
+    }
+
+    /// Lays out the matrix product of `lhs`, of dims `[m, n]`, and `rhs`, of dims `[n]` or `[n, p]`, plus the `bias` of each of
+    /// the `m` rows (if any). The output has dims `[m]` or `[m, p]`, following `rhs`. The products of the elements of `lhs`
+    /// flagged in `zeros` (in row major order, if any) are known to be zero and skipped, such that sparse rows take fewer rows
+    /// of the circuit.
+    pub fn layout(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        lhs: &ValTensor<F>,
+        rhs: &ValTensor<F>,
+        bias: Option<&ValTensor<F>>,
+        zeros: Option<&[bool]>,
+    ) -> Result<ValTensor<F>, Box<dyn Error>> {
+        let (m, n) = match lhs.dims() {
+            [m, n] => (*m, *n),
+            dims => {
+                return Err(Box::new(CircuitError::DimMismatch(format!(
+                    "dot lhs {:?}",
+                    dims
+                ))))
+            }
+        };
+        let p = match rhs.dims() {
+            [k] if *k == n => 1,
+            [k, p] if *k == n => *p,
+            dims => {
+                return Err(Box::new(CircuitError::DimMismatch(format!(
+                    "dot rhs {:?}",
+                    dims
+                ))))
+            }
+        };
+        if bias.map_or(false, |b| b.dims() != [m]) {
+            return Err(Box::new(CircuitError::DimMismatch("dot bias".to_string())));
+        }
+        if zeros.map_or(false, |z| z.len() != m * n) {
+            return Err(Box::new(CircuitError::DimMismatch("dot zeros".to_string())));
+        }
+        // the columns of each row of lhs whose products are laid out
+        let terms = (0..m)
+            .map(|i| {
+                (0..n)
+                    .filter(|col| !zeros.map_or(false, |z| z[i * n + col]))
+                    .collect_vec()
+            })
+            .collect_vec();
+
+        let outputs = layouter.assign_region(
+            || "chunked dot",
+            |mut region| {
+                let mut outputs = vec![];
+                let mut row = 0;
+                for (i, j) in (0..m).cartesian_product(0..p) {
+                    let mut acc: Option<AssignedCell<F, F>> = None;
+                    let chunks = max(1, (terms[i].len() + self.width - 1) / self.width);
+                    for chunk in 0..chunks {
+                        let mut sum = Value::known(F::zero());
+                        for k in 0..self.width {
+                            if let Some(&col) = terms[i].get(chunk * self.width + k) {
+                                let l = self.lhs.assign(&mut region, k, row, lhs, i * n + col)?;
+                                let r = self.rhs.assign(&mut region, k, row, rhs, col * p + j)?;
+                                sum = sum + l * r;
+                            } else {
+                                // padded products are zero, whatever the right hand side holds
+                                self.lhs.assign_zero(&mut region, k, row)?;
+                                self.rhs.assign_zero(&mut region, k, row)?;
+                            }
+                        }
+                        let value = match &acc {
+                            Some(prev) => {
+                                self.q_next.enable(&mut region, row)?;
+                                prev.value().copied() + sum
+                            }
+                            None => {
+                                self.q_first.enable(&mut region, row)?;
+                                sum
+                            }
+                        };
+                        acc = Some(region.assign_advice(|| "acc", self.acc, row, || value)?);
+                        row += 1;
+                    }
+                    if let Some(bias) = bias {
+                        self.q_bias.enable(&mut region, row)?;
+                        let b = self.lhs.assign(&mut region, 0, row, bias, i)?;
+                        let value = acc.as_ref().map(|a| a.value().copied() + b).unwrap();
+                        acc = Some(region.assign_advice(|| "acc", self.acc, row, || value)?);
+                        row += 1;
+                    }
+                    outputs.push(acc.unwrap());
+                }
+                Ok(outputs)
+            },
+        )?;
+
+        let mut output = Tensor::from(outputs.into_iter());
+        match rhs.dims().len() {
+            1 => output.reshape(&[m]),
+            _ => output.reshape(&[m, p]),
+        }
+        Ok(ValTensor::from(output))
+    }
+
+    /// Lays out an affine or matmul `op` over its `values`, as ordered by [Op::f], skipping the products of the elements of
+    /// the left hand side of the products (the kernel of an affine layer) flagged in `zeros`, see [DotConfig::layout].
+    pub fn layout_op(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        op: &Op,
+        values: &[ValTensor<F>],
+        zeros: Option<&[bool]>,
+    ) -> Result<ValTensor<F>, Box<dyn Error>> {
+        match (op, values) {
+            (Op::Affine, [input, kernel, bias]) => {
+                self.layout(layouter, kernel, input, Some(bias), zeros)
+            }
+            (Op::Matmul, [a, b]) => self.layout(layouter, a, b, None, zeros),
+            _ => Err(Box::new(CircuitError::DimMismatch(format!(
+                "chunked {} over {} inputs",
+                op,
+                values.len()
+            )))),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -396,4 +756,106 @@ mod tests {
         let prover = MockProver::run(K as u32, &circuit, vec![]).unwrap();
         prover.assert_satisfied();
     }
+
+    #[derive(Clone)]
+    struct DotCircuit<F: FieldExt + TensorType> {
+        kernel: ValTensor<F>,
+        input: ValTensor<F>,
+        bias: ValTensor<F>,
+        zeros: Option<Vec<bool>>,
+    }
+
+    impl<F: FieldExt + TensorType> Circuit<F> for DotCircuit<F> {
+        type Config = (DotConfig<F>, Column<halo2_proofs::plonk::Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            let instance = cs.instance_column();
+            cs.enable_equality(instance);
+            (DotConfig::configure(cs, 2, (true, false)), instance)
+        }
+
+        fn synthesize(
+            &self,
+            (config, instance): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let output = config
+                .layout_op(
+                    &mut layouter,
+                    &Op::Affine,
+                    &[self.input.clone(), self.kernel.clone(), self.bias.clone()],
+                    self.zeros.as_deref(),
+                )
+                .map_err(|_| Error::Synthesis)?;
+            match output {
+                ValTensor::PrevAssigned { inner, .. } => {
+                    for (i, cell) in inner.iter().enumerate() {
+                        layouter.constrain_instance(cell.cell(), instance, i)?;
+                    }
+                    Ok(())
+                }
+                _ => Err(Error::Synthesis),
+            }
+        }
+    }
+
+    fn check_dot_circuit(kernel: Tensor<i32>, sparse: bool) {
+        let input = Tensor::<i32>::new(Some(&[1, -2, 3, 0, 2]), &[5]).unwrap();
+        let bias = Tensor::<i32>::new(Some(&[1, 2, 3]), &[3]).unwrap();
+        let expected = affine(&vec![input.clone(), kernel.clone(), bias.clone()]).unwrap();
+        assert_eq!(expected.dims(), [3]);
+
+        let to_val = |t: Tensor<i32>| -> ValTensor<F> {
+            <Tensor<i32> as Into<Tensor<Value<F>>>>::into(t).into()
+        };
+        let circuit = DotCircuit::<F> {
+            zeros: sparse.then(|| kernel.iter().map(|v| *v == 0).collect()),
+            kernel: to_val(kernel),
+            input: to_val(input),
+            bias: to_val(bias),
+        };
+        let public = expected
+            .iter()
+            .map(|v| crate::fieldutils::i32_to_felt::<F>(*v))
+            .collect_vec();
+        let prover = MockProver::run(5, &circuit, vec![public.clone()]).unwrap();
+        prover.assert_satisfied();
+
+        let mut wrong = public;
+        wrong[1] += F::one();
+        let prover = MockProver::run(5, &circuit, vec![wrong]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn chunked_dot_circuit() {
+        // 5 products per output, accumulated 2 at a time over 3 rows with a padded product
+        let kernel = Tensor::<i32>::new(Some(&(0..15).collect_vec()), &[3, 5]).unwrap();
+        check_dot_circuit(kernel, false);
+    }
+
+    #[test]
+    fn sparse_dot_circuit() {
+        // the zero products are skipped, down to rows of the kernel without any product to lay out
+        let kernel = Tensor::<i32>::new(
+            Some(&[0, 3, 0, 0, -1, 0, 0, 0, 0, 0, 2, 0, 5, 1, 0]),
+            &[3, 5],
+        )
+        .unwrap();
+        check_dot_circuit(kernel, true);
+    }
+
+    #[test]
+    fn prunes_terms_zeroed_out() {
+        let c = |v: u64| Expression::<F>::Constant(F::from(v));
+        let expr = c(0) * c(3) + (c(2) + -(c(5) * c(0))) + c(4) * F::zero();
+        assert!(matches!(prune_zeros(expr), Expression::Constant(v) if v == F::from(2)));
+        let expr = c(2) * c(3) + c(1);
+        assert!(matches!(prune_zeros(expr), Expression::Sum(_, _)));
+    }
 }
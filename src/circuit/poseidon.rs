@@ -0,0 +1,376 @@
+use crate::fieldutils::i32_to_felt;
+use halo2_gadgets::poseidon::{
+    primitives::{Domain, Mds, Spec},
+    PaddedWord, Pow5Chip, Pow5Config, Sponge,
+};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::{Advice, Column, ConstraintSystem, Constraints, Error, Expression, Instance, Selector},
+    poly::Rotation,
+};
+use std::marker::PhantomData;
+
+/// The width of the Poseidon permutation.
+pub const WIDTH: usize = 3;
+/// The rate of the Poseidon sponge, i.e the number of elements absorbed per permutation.
+pub const RATE: usize = 2;
+/// The number of bits in each limb of a digest, as exposed in the instance column.
+pub const LIMB_BITS: usize = 16;
+/// The number of limbs a digest is split into, such that it fits into the `i32` public inputs of a circuit.
+pub const DIGEST_LIMBS: usize = 16;
+/// The number of [LIMB_BITS] bit limbs of the keys committed inputs are hashed with, for 128 bits of entropy.
+pub const KEY_LIMBS: usize = 8;
+
+/// Poseidon with an x^5 S-box over a width of 3, with the number of rounds used by halo2's `P128Pow5T3`.
+/// Round constants and the MDS matrix are generated for whichever field the circuit is over.
+#[derive(Clone, Copy, Debug)]
+pub struct PoseidonSpec;
+
+impl<F: FieldExt> Spec<F, WIDTH, RATE> for PoseidonSpec {
+    fn full_rounds() -> usize {
+        8
+    }
+
+    fn partial_rounds() -> usize {
+        56
+    }
+
+    fn sbox(val: F) -> F {
+        val.pow_vartime([5])
+    }
+
+    fn secure_mds() -> usize {
+        0
+    }
+}
+
+/// The [Domain] of hashes computed by ezkl. Messages have a length fixed by the circuit, and are zero-padded to a
+/// (non-zero) multiple of [RATE].
+#[derive(Clone, Copy, Debug)]
+pub struct FixedLength;
+
+impl<F: FieldExt> Domain<F, RATE> for FixedLength {
+    type Padding = Vec<F>;
+
+    fn name() -> String {
+        "FixedLength".to_string()
+    }
+
+    fn initial_capacity_element() -> F {
+        F::zero()
+    }
+
+    fn padding(input_len: usize) -> Self::Padding {
+        vec![F::zero(); padded_len(input_len) - input_len]
+    }
+}
+
+/// The length of a message of `len` elements once padded.
+fn padded_len(len: usize) -> usize {
+    std::cmp::max((len + RATE - 1) / RATE * RATE, RATE)
+}
+
+/// Applies the Poseidon permutation to `state`.
+fn permute<F: FieldExt>(
+    state: &mut [F; WIDTH],
+    mds: &Mds<F, WIDTH>,
+    round_constants: &[[F; WIDTH]],
+) {
+    let r_f = <PoseidonSpec as Spec<F, WIDTH, RATE>>::full_rounds() / 2;
+    let r_p = <PoseidonSpec as Spec<F, WIDTH, RATE>>::partial_rounds();
+    let sbox = <PoseidonSpec as Spec<F, WIDTH, RATE>>::sbox;
+    for (round, rcs) in round_constants.iter().enumerate() {
+        for (word, rc) in state.iter_mut().zip(rcs) {
+            *word += rc;
+        }
+        if round < r_f || round >= r_f + r_p {
+            for word in state.iter_mut() {
+                *word = sbox(*word);
+            }
+        } else {
+            state[0] = sbox(state[0]);
+        }
+        let mut new_state = [F::zero(); WIDTH];
+        for (i, new_word) in new_state.iter_mut().enumerate() {
+            for (j, word) in state.iter().enumerate() {
+                *new_word += mds[i][j] * word;
+            }
+        }
+        *state = new_state;
+    }
+}
+
+/// Hashes `message` with Poseidon, as is done in-circuit by [PoseidonConfig::layout].
+pub fn poseidon_hash<F: FieldExt>(message: &[F]) -> F {
+    let (round_constants, mds, _) = <PoseidonSpec as Spec<F, WIDTH, RATE>>::constants();
+    let mut padded = message.to_vec();
+    padded.extend(<FixedLength as Domain<F, RATE>>::padding(message.len()));
+    let mut state = [F::zero(); WIDTH];
+    state[RATE] = <FixedLength as Domain<F, RATE>>::initial_capacity_element();
+    for block in padded.chunks(RATE) {
+        for (word, m) in state.iter_mut().zip(block) {
+            *word += m;
+        }
+        permute(&mut state, &mds, &round_constants);
+    }
+    state[0]
+}
+
+/// Hashes quantized `values` with Poseidon (see [poseidon_hash]), returning the limbs of the digest (see [digest_to_limbs]).
+pub fn hash_to_limbs<F: FieldExt>(values: impl IntoIterator<Item = i32>) -> Vec<i32> {
+    let message = values.into_iter().map(i32_to_felt::<F>).collect::<Vec<F>>();
+    digest_to_limbs(poseidon_hash(&message))
+}
+
+/// Splits a digest into [DIGEST_LIMBS] limbs of [LIMB_BITS] bits, most significant first.
+pub fn digest_to_limbs<F: FieldExt>(digest: F) -> Vec<i32> {
+    let repr = digest.to_repr();
+    // the representation is little endian
+    let mut limbs = repr
+        .as_ref()
+        .chunks(LIMB_BITS / 8)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]) as i32)
+        .collect::<Vec<i32>>();
+    limbs.resize(DIGEST_LIMBS, 0);
+    limbs.reverse();
+    limbs
+}
+
+/// Recomposes a field element from its limbs, as output by [digest_to_limbs].
+pub fn limbs_to_digest<F: FieldExt>(limbs: &[i32]) -> F {
+    limbs.iter().fold(F::zero(), |acc, l| {
+        acc * F::from(1 << LIMB_BITS) + F::from(*l as u16 as u64)
+    })
+}
+
+/// Configuration for hashing a message with Poseidon, and constraining the digest to equal the limbs (as output by
+/// [digest_to_limbs]) held by an instance column.
+#[derive(Clone, Debug)]
+pub struct PoseidonConfig<F: FieldExt> {
+    pow5: Pow5Config<F, WIDTH, RATE>,
+    /// Accumulates the limbs of the digest, most significant first.
+    acc: Column<Advice>,
+    /// The limbs of the digest, copied from the instance column.
+    limb: Column<Advice>,
+    q_first: Selector,
+    q_step: Selector,
+    _marker: PhantomData<F>,
+}
+
+impl<F: FieldExt> PoseidonConfig<F> {
+    /// Configures the Poseidon chip and the gate recomposing the digest from its limbs, on their own columns.
+    pub fn configure(meta: &mut ConstraintSystem<F>) -> Self {
+        let state = [(); WIDTH].map(|_| meta.advice_column());
+        let partial_sbox = meta.advice_column();
+        let rc_a = [(); WIDTH].map(|_| meta.fixed_column());
+        let rc_b = [(); WIDTH].map(|_| meta.fixed_column());
+        // padding is assigned as constants
+        meta.enable_constant(rc_b[0]);
+        let pow5 = Pow5Chip::configure::<PoseidonSpec>(meta, state, partial_sbox, rc_a, rc_b);
+
+        let acc = meta.advice_column();
+        let limb = meta.advice_column();
+        meta.enable_equality(acc);
+        meta.enable_equality(limb);
+        let q_first = meta.selector();
+        let q_step = meta.selector();
+
+        meta.create_gate("digest first limb", |meta| {
+            let q = meta.query_selector(q_first);
+            let acc = meta.query_advice(acc, Rotation::cur());
+            let limb = meta.query_advice(limb, Rotation::cur());
+            Constraints::with_selector(q, vec![acc - limb])
+        });
+
+        meta.create_gate("digest limb", |meta| {
+            let q = meta.query_selector(q_step);
+            let prev = meta.query_advice(acc, Rotation::prev());
+            let acc = meta.query_advice(acc, Rotation::cur());
+            let limb = meta.query_advice(limb, Rotation::cur());
+            let base = Expression::Constant(F::from(1 << LIMB_BITS));
+            Constraints::with_selector(q, vec![acc - (prev * base + limb)])
+        });
+
+        PoseidonConfig {
+            pow5,
+            acc,
+            limb,
+            q_first,
+            q_step,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Hashes `message` in-circuit and constrains the digest to equal the limbs held by the first [DIGEST_LIMBS] rows of `instance`.
+    /// # Arguments
+    /// * `layouter` - A Halo2 Layouter.
+    /// * `message` - The (previously assigned) cells to hash.
+    /// * `instance` - The instance column holding the limbs of the expected digest.
+    pub fn layout(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        message: &[AssignedCell<F, F>],
+        instance: Column<Instance>,
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let digest = self.hash(layouter, message)?;
+        self.expose(layouter, &digest, instance)?;
+        Ok(digest)
+    }
+
+    /// Hashes `message` in-circuit, returning the digest without exposing it.
+    /// # Arguments
+    /// * `layouter` - A Halo2 Layouter.
+    /// * `message` - The (previously assigned) cells to hash.
+    pub fn hash(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        message: &[AssignedCell<F, F>],
+    ) -> Result<AssignedCell<F, F>, Error> {
+        let chip = Pow5Chip::construct(self.pow5.clone());
+        let mut sponge = Sponge::<F, _, PoseidonSpec, _, FixedLength, WIDTH, RATE>::new(
+            chip,
+            layouter.namespace(|| "poseidon init"),
+        )?;
+        let words = message.iter().cloned().map(PaddedWord::Message).chain(
+            <FixedLength as Domain<F, RATE>>::padding(message.len())
+                .into_iter()
+                .map(PaddedWord::Padding),
+        );
+        for (i, word) in words.enumerate() {
+            sponge.absorb(
+                layouter.namespace(|| format!("poseidon absorb {}", i)),
+                word,
+            )?;
+        }
+        let mut sponge = sponge.finish_absorbing(layouter.namespace(|| "poseidon finish"))?;
+        sponge.squeeze(layouter.namespace(|| "poseidon squeeze"))
+    }
+
+    /// Constrains `value` to equal the limbs (as output by [digest_to_limbs]) held by the first [DIGEST_LIMBS] rows of `instance`.
+    /// # Arguments
+    /// * `layouter` - A Halo2 Layouter.
+    /// * `value` - The (previously assigned) cell to expose, e.g a digest.
+    /// * `instance` - The instance column holding the limbs of `value`.
+    pub fn expose(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        value: &AssignedCell<F, F>,
+        instance: Column<Instance>,
+    ) -> Result<(), Error> {
+        layouter.assign_region(
+            || "digest limbs",
+            |mut region| {
+                let mut acc: Option<AssignedCell<F, F>> = None;
+                for row in 0..DIGEST_LIMBS {
+                    let limb = region.assign_advice_from_instance(
+                        || "limb",
+                        instance,
+                        row,
+                        self.limb,
+                        row,
+                    )?;
+                    let next = match &acc {
+                        None => {
+                            self.q_first.enable(&mut region, row)?;
+                            limb.value().copied()
+                        }
+                        Some(prev) => {
+                            self.q_step.enable(&mut region, row)?;
+                            prev.value().copied() * Value::known(F::from(1 << LIMB_BITS))
+                                + limb.value().copied()
+                        }
+                    };
+                    acc = Some(region.assign_advice(|| "acc", self.acc, row, || next)?);
+                }
+                // DIGEST_LIMBS is non-zero
+                region.constrain_equal(acc.unwrap().cell(), value.cell())
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{circuit::SimpleFloorPlanner, dev::MockProver, plonk::Circuit};
+    use halo2curves::pasta::Fp as F;
+
+    #[derive(Clone)]
+    struct HashCircuit {
+        message: Vec<F>,
+    }
+
+    impl Circuit<F> for HashCircuit {
+        type Config = (PoseidonConfig<F>, Column<Advice>, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            let advice = cs.advice_column();
+            cs.enable_equality(advice);
+            let instance = cs.instance_column();
+            cs.enable_equality(instance);
+            (PoseidonConfig::configure(cs), advice, instance)
+        }
+
+        fn synthesize(
+            &self,
+            (config, advice, instance): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let message = layouter.assign_region(
+                || "message",
+                |mut region| {
+                    self.message
+                        .iter()
+                        .enumerate()
+                        .map(|(i, m)| region.assign_advice(|| "m", advice, i, || Value::known(*m)))
+                        .collect::<Result<Vec<_>, Error>>()
+                },
+            )?;
+            config.layout(&mut layouter, &message, instance)?;
+            Ok(())
+        }
+    }
+
+    fn limbs(digest: F) -> Vec<F> {
+        digest_to_limbs(digest)
+            .into_iter()
+            .map(i32_to_felt)
+            .collect()
+    }
+
+    #[test]
+    fn digest_matches_native_hash() {
+        for len in [0, 1, 2, 5] {
+            let message = (0..len).map(|i| F::from(i as u64 + 7)).collect::<Vec<_>>();
+            let digest = poseidon_hash(&message);
+            let circuit = HashCircuit { message };
+            let prover = MockProver::run(8, &circuit, vec![limbs(digest)]).unwrap();
+            prover.assert_satisfied();
+        }
+    }
+
+    #[test]
+    fn wrong_digest_fails() {
+        let message = vec![F::from(1), F::from(2), F::from(3)];
+        let digest = poseidon_hash(&[F::from(1), F::from(2), F::from(4)]);
+        let circuit = HashCircuit { message };
+        let prover = MockProver::run(8, &circuit, vec![limbs(digest)]).unwrap();
+        assert!(prover.verify().is_err());
+    }
+
+    #[test]
+    fn limbs_recompose_digest() {
+        let digest = poseidon_hash(&[F::from(42)]);
+        let recomposed = digest_to_limbs(digest).iter().fold(F::zero(), |acc, l| {
+            acc * F::from(1 << LIMB_BITS) + F::from(*l as u64)
+        });
+        assert_eq!(recomposed, digest);
+        assert_eq!(limbs_to_digest::<F>(&digest_to_limbs(digest)), digest);
+    }
+}
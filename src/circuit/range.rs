@@ -1,20 +1,76 @@
+use super::decomposed::RangeTable;
 use super::CircuitError;
-use crate::fieldutils::i32_to_felt;
-use crate::tensor::{TensorType, ValTensor, VarTensor};
+use crate::fieldutils::{felt_to_i128, i32_to_felt};
+use crate::tensor::{Tensor, TensorType, ValTensor, VarTensor};
 use halo2_proofs::{
     arithmetic::FieldExt,
     circuit::Layouter,
     plonk::{ConstraintSystem, Constraints, Expression, Selector},
+    poly::Rotation,
 };
-use std::marker::PhantomData;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::{cell::RefCell, marker::PhantomData, rc::Rc};
 
-/// Configuration for a range check on the difference between `input` and `expected`.
+/// How the differences between an output and its expected value are bounded by the tolerance of a range check.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum ToleranceNorm {
+    /// Each difference is bounded by the tolerance on its own.
+    #[default]
+    Max,
+    /// The sum of the absolute differences is at most the tolerance, such that a single large error isn't hidden by exact
+    /// elements.
+    L1,
+    /// The sum of the squared differences is at most the tolerance.
+    L2,
+}
+
+impl fmt::Display for ToleranceNorm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToleranceNorm::Max => write!(f, "max"),
+            ToleranceNorm::L1 => write!(f, "l1"),
+            ToleranceNorm::L2 => write!(f, "l2"),
+        }
+    }
+}
+
+impl FromStr for ToleranceNorm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "max" => Ok(ToleranceNorm::Max),
+            "l1" => Ok(ToleranceNorm::L1),
+            "l2" => Ok(ToleranceNorm::L2),
+            _ => Err(format!(
+                "invalid tolerance norm `{}`, expected one of max, l1 or l2",
+                s
+            )),
+        }
+    }
+}
+
+/// Configuration for a range check on the difference between `input` and `expected`, or on `input` itself against a
+/// [RangeTable] (see [RangeCheckConfig::configure_table]).
 #[derive(Debug, Clone)]
 pub struct RangeCheckConfig<F: FieldExt + TensorType> {
     input: VarTensor,
-    /// The value we are expecting the output of the circuit to match (within a range)
-    pub expected: VarTensor,
-    selector: Selector,
+    /// The value we are expecting the output of the circuit to match (within a range), absent when checking against a table
+    pub expected: Option<VarTensor>,
+    /// The absolute differences between `input` and `expected`, witnessed when bounding their L1 norm.
+    abs: Option<VarTensor>,
+    /// The selector of the gate, or of the lookup of each column of `input` when checking against a table.
+    selectors: Vec<Selector>,
+    table: Option<Rc<RefCell<RangeTable<F>>>>,
     _marker: PhantomData<F>,
 }
 
@@ -33,8 +89,10 @@ impl<F: FieldExt + TensorType> RangeCheckConfig<F> {
     ) -> Self {
         let config = Self {
             input: input.clone(),
-            expected: expected.clone(),
-            selector: cs.selector(),
+            expected: Some(expected.clone()),
+            abs: None,
+            selectors: vec![cs.selector()],
+            table: None,
             _marker: PhantomData,
         };
 
@@ -43,7 +101,7 @@ impl<F: FieldExt + TensorType> RangeCheckConfig<F> {
             //       ------------------------------
             //          v       |         1
 
-            let q = cs.query_selector(config.selector);
+            let q = cs.query_selector(config.selectors[0]);
             let witnessed = input.query(cs, 0).expect("range: failed to query input");
 
             // Get output expressions for each input channel
@@ -70,6 +128,133 @@ impl<F: FieldExt + TensorType> RangeCheckConfig<F> {
         config
     }
 
+    /// Configures a range check on a norm of the difference between `input` and `expected` (see [ToleranceNorm]), which must
+    /// lie within `[0, tol]`.
+    /// # Arguments
+    /// * `input` - the input
+    /// * `expected` - the expected input we would have wanted to produce
+    /// * `abs` - the var the absolute differences are witnessed in, which only the L1 norm needs
+    /// * `norm` - the norm bounded by `tol`
+    /// * `tol` - our tolerance for error between `input` and `expected`
+    pub fn configure_norm(
+        cs: &mut ConstraintSystem<F>,
+        input: &VarTensor,
+        expected: &VarTensor,
+        abs: Option<&VarTensor>,
+        norm: ToleranceNorm,
+        tol: usize,
+    ) -> Self {
+        let abs = match norm {
+            ToleranceNorm::Max => return Self::configure(cs, input, expected, tol),
+            ToleranceNorm::L1 => Some(
+                abs.expect("range: the l1 norm needs a var for absolute differences")
+                    .clone(),
+            ),
+            ToleranceNorm::L2 => None,
+        };
+        let config = Self {
+            input: input.clone(),
+            expected: Some(expected.clone()),
+            abs,
+            selectors: vec![cs.selector()],
+            table: None,
+            _marker: PhantomData,
+        };
+
+        cs.create_gate("norm range check", |cs| {
+            let q = cs.query_selector(config.selectors[0]);
+            let witnessed = input.query(cs, 0).expect("range: failed to query input");
+            let expected = expected
+                .query(cs, 0)
+                .expect("range: failed to query expected value");
+            let differences = witnessed
+                .enum_map::<_, _, CircuitError>(|i, o| Ok(o - expected[i].clone()))
+                .expect("range: failed to create differences");
+
+            // Given a bound R and a value v, returns the expression v * (1 - v) * ... * (R - v), which is 0 for v in [0, R]
+            let non_negative = |bound: i32, value: Expression<F>| {
+                (1..=bound).fold(value.clone(), |expr, i| {
+                    expr * (Expression::Constant(i32_to_felt(i)) - value.clone())
+                })
+            };
+
+            let mut constraints = vec![];
+            let costs = match &config.abs {
+                Some(abs) => {
+                    let abs = abs.query(cs, 0).expect("range: failed to query abs");
+                    // each witness is non-negative and squares to the square of its difference, so is its absolute value
+                    for (d, a) in differences.iter().zip(abs.iter()) {
+                        constraints.push(a.clone() * a.clone() - d.clone() * d.clone());
+                        constraints.push(non_negative(tol as i32, a.clone()));
+                    }
+                    abs.iter().cloned().collect::<Vec<_>>()
+                }
+                None => {
+                    // the differences are integers within [-sqrt(tol), sqrt(tol)], such that the sum of their squares
+                    // can't wrap around the field
+                    let bound = (0..=tol).take_while(|r| r * r <= tol).last().unwrap_or(0) as i32;
+                    for d in differences.iter() {
+                        constraints.push((1..=bound).fold(d.clone(), |expr, i| {
+                            expr * (Expression::Constant(i32_to_felt(i * i))
+                                - d.clone() * d.clone())
+                        }));
+                    }
+                    differences.iter().map(|d| d.clone() * d.clone()).collect()
+                }
+            };
+            let norm = costs
+                .into_iter()
+                .fold(Expression::Constant(F::zero()), |sum, c| sum + c);
+            constraints.push(non_negative(tol as i32, norm));
+            Constraints::with_selector(q, constraints)
+        });
+
+        config
+    }
+
+    /// Configures a range check of `input` to lie within `[-2^(bits-1), 2^(bits-1))`, for ranges too wide to check with a
+    /// polynomial (such as that of the lookup tables): each value is shifted by `2^(bits-1)` and looked up in `table`, a
+    /// [RangeTable] of `bits` bits. The columns `input` spans are looked up row by row, as lookups are.
+    pub fn configure_table(
+        cs: &mut ConstraintSystem<F>,
+        input: &VarTensor,
+        table: &Rc<RefCell<RangeTable<F>>>,
+    ) -> Self {
+        let len = input.dims().iter().product::<usize>();
+        let width = (len + input.col_size() - 1) / input.col_size();
+        let selectors = (0..width.clamp(1, input.num_cols()))
+            .map(|_| cs.complex_selector())
+            .collect::<Vec<_>>();
+        let (column, bits) = {
+            let table = table.borrow();
+            (table.column, table.bits)
+        };
+        let shift = Expression::Constant(F::from(1u64 << (bits - 1)));
+        for (x, selector) in selectors.iter().enumerate() {
+            // lookups which aren't enabled resolve to 0, which the table holds
+            let _ = cs.lookup("range check table", |cs| {
+                let q = cs.query_selector(*selector);
+                let value = match input {
+                    VarTensor::Advice { inner: advices, .. } => {
+                        cs.query_advice(advices[x], Rotation::cur())
+                    }
+                    VarTensor::Fixed { inner: fixed, .. } => {
+                        cs.query_fixed(fixed[x], Rotation::cur())
+                    }
+                };
+                vec![(q * (value + shift.clone()), column)]
+            });
+        }
+        Self {
+            input: input.clone(),
+            expected: None,
+            abs: None,
+            selectors,
+            table: Some(table.clone()),
+            _marker: PhantomData,
+        }
+    }
+
     /// Assigns variables to the regions created when calling `configure`.
     /// # Arguments
     /// * `input` - The input values we want to express an error tolerance for
@@ -86,12 +271,26 @@ impl<F: FieldExt + TensorType> RangeCheckConfig<F> {
                 let offset = 0;
 
                 // Enable q_range_check
-                self.selector.enable(&mut region, offset)?;
+                self.selectors[0].enable(&mut region, offset)?;
 
                 // assigns the instance to the advice.
-                self.input.assign(&mut region, offset, &input)?;
+                let input = self.input.assign(&mut region, offset, &input)?;
 
-                self.expected.assign(&mut region, offset, &output)?;
+                if let Some(expected) = &self.expected {
+                    let expected = expected.assign(&mut region, offset, &output)?;
+                    if let Some(abs) = &self.abs {
+                        let values = input.iter().zip(expected.iter()).map(|(i, e)| {
+                            i.value().zip(e.value()).map(|(i, e)| {
+                                let d = *i - *e;
+                                match felt_to_i128(d) < 0 {
+                                    true => -d,
+                                    false => d,
+                                }
+                            })
+                        });
+                        abs.assign(&mut region, offset, &ValTensor::from(Tensor::from(values)))?;
+                    }
+                }
 
                 Ok(())
             },
@@ -100,6 +299,36 @@ impl<F: FieldExt + TensorType> RangeCheckConfig<F> {
             Err(e) => Err(e),
         }
     }
+
+    /// Assigns `input` to the lookups created when calling `configure_table`, laying out the table on first use.
+    /// # Arguments
+    /// * `layouter` - A Halo2 Layouter.
+    /// * `input` - The values to range check.
+    pub fn layout_table(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        input: &ValTensor<F>,
+    ) -> Result<(), Box<dyn Error>> {
+        let table = self
+            .table
+            .as_ref()
+            .ok_or(CircuitError::LookupInstantiation)?;
+        if !table.borrow().is_assigned {
+            table.borrow_mut().layout(layouter)?;
+        }
+        layouter.assign_region(
+            || "range check table layout",
+            |mut region| {
+                self.input.assign(&mut region, 0, input)?;
+                for i in 0..input.dims().iter().product::<usize>() {
+                    let (x, y) = self.input.cartesian_coord(i);
+                    self.selectors[x].enable(&mut region, y)?;
+                }
+                Ok(())
+            },
+        )?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -194,4 +423,129 @@ mod tests {
             }
         }
     }
+
+    #[derive(Clone)]
+    struct NormCircuit<F: FieldExt + TensorType, const L1: bool> {
+        input: ValTensor<F>,
+        output: ValTensor<F>,
+    }
+
+    impl<F: FieldExt + TensorType, const L1: bool> Circuit<F> for NormCircuit<F, L1> {
+        type Config = RangeCheckConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            let advices = (0..3)
+                .map(|_| VarTensor::new_advice(cs, 4, 1, vec![4], true, 512))
+                .collect_vec();
+            let norm = match L1 {
+                true => ToleranceNorm::L1,
+                false => ToleranceNorm::L2,
+            };
+            RangeCheckConfig::configure_norm(
+                cs,
+                &advices[0],
+                &advices[1],
+                Some(&advices[2]),
+                norm,
+                2,
+            )
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.layout(
+                layouter.namespace(|| "assign value"),
+                self.input.clone(),
+                self.output.clone(),
+            )
+        }
+    }
+
+    #[test]
+    fn range_checks_norms_of_differences() {
+        fn prove<const L1: bool>(differences: [i32; 4]) -> bool {
+            let expected = [3, 0, -2, 7];
+            let values = |v: &[i32]| {
+                ValTensor::from(Tensor::from(
+                    v.iter().map(|v| Value::known(i32_to_felt::<Fp>(*v))),
+                ))
+            };
+            let input = expected
+                .iter()
+                .zip(differences)
+                .map(|(e, d)| e + d)
+                .collect_vec();
+            let circuit = NormCircuit::<Fp, L1> {
+                input: values(&input),
+                output: values(&expected),
+            };
+            MockProver::run(4, &circuit, vec![])
+                .unwrap()
+                .verify()
+                .is_ok()
+        }
+        // a tolerance of 2 over the sum of absolute differences
+        assert!(prove::<true>([1, 0, 0, -1]));
+        assert!(prove::<true>([0, -2, 0, 0]));
+        assert!(!prove::<true>([2, 0, 0, 1]));
+        assert!(!prove::<true>([0, 0, -3, 0]));
+        // and over the sum of squared differences
+        assert!(prove::<false>([1, 0, 0, -1]));
+        assert!(!prove::<false>([1, 1, -1, 0]));
+        assert!(!prove::<false>([0, 2, 0, 0]));
+    }
+
+    #[derive(Clone)]
+    struct TableCircuit<F: FieldExt + TensorType> {
+        input: ValTensor<F>,
+    }
+
+    impl<F: FieldExt + TensorType> Circuit<F> for TableCircuit<F> {
+        type Config = RangeCheckConfig<F>;
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            // 6 values spread over two columns of 4 rows
+            let input = VarTensor::new_advice(cs, 6, 6, vec![6], true, 4);
+            let table = Rc::new(RefCell::new(RangeTable::configure(cs, 4)));
+            RangeCheckConfig::configure_table(cs, &input, &table)
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config
+                .layout_table(&mut layouter, &self.input)
+                .map_err(|_| Error::Synthesis)
+        }
+    }
+
+    #[test]
+    fn range_checks_against_a_table() {
+        let prove = |values: &[i32]| {
+            let values = values.iter().map(|v| Value::known(i32_to_felt::<Fp>(*v)));
+            let circuit = TableCircuit::<Fp> {
+                input: ValTensor::from(Tensor::from(values)),
+            };
+            MockProver::run(6, &circuit, vec![]).unwrap().verify()
+        };
+        // 4 bits hold [-8, 8)
+        assert!(prove(&[-8, -1, 0, 1, 5, 7]).is_ok());
+        assert!(prove(&[0, 0, 0, 0, 8, 0]).is_err());
+        assert!(prove(&[-9, 0, 0, 0, 0, 0]).is_err());
+    }
 }
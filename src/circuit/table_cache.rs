@@ -0,0 +1,147 @@
+use super::lookup::{apply, table_inputs, Op};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// The outputs of a function over the inputs of a lookup table of `bits` bits (see [table_inputs]), e.g as shipped with a
+/// compiled model.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CachedTable {
+    /// The function, a composition of operations, the table holds.
+    pub function: Vec<Op>,
+    /// The number of bits of the inputs of the table.
+    pub bits: usize,
+    /// The output of the function for each input of the table, in order.
+    pub outputs: Vec<i32>,
+}
+
+type Key = (Vec<Op>, usize);
+
+/// The tables computed or loaded by this process.
+static TABLES: Mutex<Option<HashMap<Key, Arc<Vec<i32>>>>> = Mutex::new(None);
+
+fn lock() -> MutexGuard<'static, Option<HashMap<Key, Arc<Vec<i32>>>>> {
+    TABLES.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The cache directory tables are saved to unless set otherwise: `$XDG_CACHE_HOME/ezkl/tables`, or `~/.cache/ezkl/tables`.
+pub fn default_dir() -> Option<PathBuf> {
+    let cache = match std::env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+    };
+    Some(cache.join("ezkl").join("tables"))
+}
+
+/// The file a table is cached in within `dir`, named by the hash of its function (whose ops hold their scales) and bits.
+fn file(dir: &Path, key: &Key) -> PathBuf {
+    let hash = blake3::hash(format!("{:?}", key).as_bytes());
+    dir.join(format!("{}.table", &hash.to_hex()[..32]))
+}
+
+fn read(path: &Path, bits: usize) -> Option<Vec<i32>> {
+    let bytes = std::fs::read(path).ok()?;
+    // tables which weren't fully written (e.g by a run that was interrupted) are computed again
+    if bytes.len() != 4 << bits {
+        return None;
+    }
+    Some(
+        bytes
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    )
+}
+
+fn write(path: &Path, outputs: &[i32]) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let bytes = outputs
+        .iter()
+        .flat_map(|y| y.to_le_bytes())
+        .collect::<Vec<_>>();
+    // written aside and renamed, such that concurrent runs never read a partial table
+    let partial = path.with_extension(format!("{}.partial", std::process::id()));
+    std::fs::write(&partial, bytes)?;
+    std::fs::rename(&partial, path)
+}
+
+/// The outputs of `function` over the inputs of a table of `bits` bits. Tables are computed once per process, and when `dir` is
+/// set, loaded from (or saved to) the cache directory `dir`, such that runs over the same functions don't compute them again.
+/// Failing to read or write the cache only costs computing the table.
+pub fn evaluations(function: &[Op], bits: usize, dir: Option<&Path>) -> Arc<Vec<i32>> {
+    let key = (function.to_vec(), bits);
+    if let Some(outputs) = lock().get_or_insert_with(HashMap::new).get(&key) {
+        return outputs.clone();
+    }
+    let path = dir.map(|dir| file(dir, &key));
+    let outputs = match path.as_deref().and_then(|p| read(p, bits)) {
+        Some(outputs) => outputs,
+        None => {
+            let outputs = apply(function, table_inputs(bits)).to_vec();
+            if let Some(path) = &path {
+                let _ = write(path, &outputs);
+            }
+            outputs
+        }
+    };
+    let outputs = Arc::new(outputs);
+    lock()
+        .get_or_insert_with(HashMap::new)
+        .insert(key, outputs.clone());
+    outputs
+}
+
+/// The table of `function` over `bits` bits, for shipping alongside a compiled model.
+pub fn table(function: &[Op], bits: usize, dir: Option<&Path>) -> CachedTable {
+    CachedTable {
+        function: function.to_vec(),
+        bits,
+        outputs: evaluations(function, bits, dir).to_vec(),
+    }
+}
+
+/// Makes `tables` (e.g those shipped with a compiled model) available to [evaluations], such that they aren't computed again.
+/// Tables which don't span their number of bits are ignored.
+pub fn preload(tables: Vec<CachedTable>) {
+    let mut cache = lock();
+    let cache = cache.get_or_insert_with(HashMap::new);
+    for table in tables {
+        if table.outputs.len() == 1 << table.bits {
+            cache.insert((table.function, table.bits), Arc::new(table.outputs));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tables_are_cached_on_disk() {
+        let dir = std::env::temp_dir().join(format!("ezkl_table_cache_{}", std::process::id()));
+        // a scale no other test uses, such that the table isn't already cached by this process
+        let function = vec![Op::Div { scale: 12345 }];
+        let outputs = evaluations(&function, 6, Some(&dir));
+        assert_eq!(*outputs, apply(&function, table_inputs(6)).to_vec());
+
+        let path = file(&dir, &(function.clone(), 6));
+        assert_eq!(read(&path, 6).as_ref(), Some(&*outputs));
+        // truncated tables are ignored
+        std::fs::write(&path, [0u8; 3]).unwrap();
+        assert_eq!(read(&path, 6), None);
+
+        let shipped = CachedTable {
+            function: vec![Op::ReLU { scale: 54321 }],
+            bits: 2,
+            outputs: vec![7, 7, 7, 7],
+        };
+        preload(vec![shipped.clone()]);
+        assert_eq!(table(&shipped.function, 2, None), shipped);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
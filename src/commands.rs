@@ -1,13 +1,23 @@
 //use crate::onnx::OnnxModel;
-use clap::{Parser, Subcommand, ValueEnum};
+use crate::circuit::range::ToleranceNorm;
+use crate::circuit::table_cache;
+use crate::graph::{Claims, CustomOps, GraphError, Mode, Model, RunArgs, Visibility};
+use crate::pfsys::artifact::{self, ArtifactHeader};
+#[cfg(feature = "evm")]
+use crate::pfsys::evm::solidity::DEFAULT_PRAGMA;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use log::info;
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::error::Error;
 use std::io::{stdin, stdout, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const EZKLCONF: &str = "EZKLCONF";
+/// The config file loaded when `--config` isn't passed.
+const DEFAULT_CONFIG: &str = "ezkl.toml";
 
 #[allow(missing_docs)]
 #[derive(Parser, Debug, Clone, Deserialize, Serialize)]
@@ -17,32 +27,623 @@ pub struct Cli {
     #[allow(missing_docs)]
     pub command: Commands,
     /// The tolerance for error on model outputs
-    #[arg(short = 'T', long, default_value = "0")]
+    #[arg(short = 'T', long, default_value = "0", env = "EZKL_TOLERANCE")]
     pub tolerance: usize,
     /// The denominator in the fixed point representation used when quantizing
-    #[arg(short = 'S', long, default_value = "7")]
+    #[arg(short = 'S', long, default_value = "7", env = "EZKL_SCALE")]
     pub scale: i32,
     /// The number of bits used in lookup tables
-    #[arg(short = 'B', long, default_value = "16")]
+    #[arg(short = 'B', long, default_value = "16", env = "EZKL_BITS")]
     pub bits: usize,
     /// The log_2 number of rows
-    #[arg(short = 'K', long, default_value = "17")]
+    #[arg(short = 'K', long, default_value = "17", env = "EZKL_LOGROWS")]
     pub logrows: u32,
     /// Flags whether inputs are public
-    #[arg(long, default_value = "false")]
+    #[arg(long, default_value = "false", env = "EZKL_PUBLIC_INPUTS")]
     pub public_inputs: bool,
     /// Flags whether outputs are public
-    #[arg(long, default_value = "true")]
+    #[arg(long, default_value = "true", env = "EZKL_PUBLIC_OUTPUTS")]
     pub public_outputs: bool,
     /// Flags whether params are public
-    #[arg(long, default_value = "false")]
+    #[arg(long, default_value = "false", env = "EZKL_PUBLIC_PARAMS")]
     pub public_params: bool,
-    /// Flags to set maximum rotations
-    #[arg(short = 'M', long, default_value = "512")]
-    pub max_rotations: usize,
+    /// Hashes the inputs in-circuit with Poseidon, such that only their hash is public (overrides `--public-inputs`)
+    #[arg(long, default_value = "false", env = "EZKL_HASHED_INPUTS")]
+    #[serde(default)]
+    pub hashed_inputs: bool,
+    /// Commits to the inputs in-circuit with a key shared by the data owner (the `input_key` of the data file), such that only
+    /// the Poseidon hash of the key and inputs is public (overrides `--hashed-inputs` and `--public-inputs`)
+    #[arg(long, default_value = "false", env = "EZKL_COMMITTED_INPUTS")]
+    #[serde(default)]
+    pub committed_inputs: bool,
+    /// Hashes the outputs in-circuit with Poseidon, such that only their hash is public (overrides `--public-outputs`)
+    #[arg(long, default_value = "false", env = "EZKL_HASHED_OUTPUTS")]
+    #[serde(default)]
+    pub hashed_outputs: bool,
+    /// Verifies in-circuit an EdDSA signature over the Poseidon hash of the inputs (the `input_signature` of the data file, see
+    /// the `sign` command), exposing the public key of the signer, such that verifiers know the inputs were attested by its holder
+    #[arg(long, default_value = "false", env = "EZKL_ATTESTED_INPUTS")]
+    #[serde(default)]
+    pub attested_inputs: bool,
+    /// Proves the inputs of each sample are a leaf of a Poseidon Merkle tree of the given depth (see the `merkle-path` command),
+    /// exposing only its root, such that verifiers know the inputs were drawn from a committed dataset without learning which record
+    #[arg(long, env = "EZKL_MERKLE_DEPTH")]
+    #[serde(default)]
+    pub merkle_depth: Option<usize>,
+    /// Hashes the (private) params of the model in-circuit with Poseidon and exposes the digest as a public input, such that
+    /// verifiers know which model produced the outputs without the params being revealed
+    #[arg(long, default_value = "false", env = "EZKL_COMMIT_PARAMS")]
+    #[serde(default)]
+    pub commit_params: bool,
+    /// The number of rotations (rows) each column of the model spans. If unset, it is computed from the largest node of the model,
+    /// as far as the circuit holds
+    #[arg(short = 'M', long, env = "EZKL_MAX_ROTATIONS")]
+    #[serde(default)]
+    pub max_rotations: Option<usize>,
+    /// Overrides the fixed point scale of a given node, specified as `<node index>=<scale>` (e.g `--layer-scale 5=10`).
+    /// Can be passed multiple times. The inputs of nodes at another scale to their producers are rescaled to the node's scale.
+    #[arg(long = "layer-scale", value_parser = parse_layer_scale, env = "EZKL_LAYER_SCALES")]
+    #[serde(default)]
+    pub layer_scales: Vec<(usize, i32)>,
+    /// Overrides the visibility of a given input, specified as `<input index>=<visibility>` (e.g `--input-visibility 0=public
+    /// --input-visibility 1=private`), where the visibility is one of private, public, hashed or committed. Inputs which aren't
+    /// overridden follow `--public-inputs`, `--hashed-inputs` and `--committed-inputs`
+    #[arg(long = "input-visibility", value_parser = parse_indexed_visibility, env = "EZKL_INPUT_VISIBILITY")]
+    #[serde(default)]
+    pub input_visibility: Vec<(usize, Visibility)>,
+    /// Overrides the visibility of a given output, specified as `<output index>=<visibility>`, where the visibility is one of
+    /// private, public or hashed. Outputs which aren't overridden follow `--public-outputs` and `--hashed-outputs`
+    #[arg(long = "output-visibility", value_parser = parse_indexed_visibility, env = "EZKL_OUTPUT_VISIBILITY")]
+    #[serde(default)]
+    pub output_visibility: Vec<(usize, Visibility)>,
+    /// Bounds a norm of the errors of a given public output by `--tolerance`, rather than each of its elements, specified as
+    /// `<output index>=<norm>` (e.g `--tolerance-norm 0=l1`), where the norm is one of max (each element, the default), l1
+    /// (the sum of absolute errors) or l2 (the sum of squared errors)
+    #[arg(long = "tolerance-norm", value_parser = parse_tolerance_norm, env = "EZKL_TOLERANCE_NORMS")]
+    #[serde(default)]
+    pub tolerance_norms: Vec<(usize, ToleranceNorm)>,
+    /// Splits the model before each of the given execution buckets (e.g `--cut-points 2,4` splits it into three chunks), such that
+    /// models too large for a single circuit can be proven one chunk at a time and the chunk proofs aggregated
+    #[arg(long, value_delimiter = ',', env = "EZKL_CUT_POINTS")]
+    #[serde(default)]
+    pub cut_points: Vec<usize>,
+    /// The chunk of the model, as split by `--cut-points`, to run commands on
+    #[arg(long, env = "EZKL_CHUNK")]
+    #[serde(default)]
+    pub chunk: Option<usize>,
+    /// The number of samples to prove in a single circuit
+    #[arg(long, default_value = "1", env = "EZKL_BATCH_SIZE")]
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Runs tract's `into_typed()` and `declutter()` over the model before converting it, resolving the shapes of its nodes and
+    /// folding its constant subgraphs (e.g shape computations) into constants, such that the ops feeding them needn't be supported
+    #[arg(long, default_value = "false", env = "EZKL_OPTIMIZE_GRAPH")]
+    #[serde(default)]
+    pub optimize_graph: bool,
+    /// Overrides the shape of a given input, specified as `<input index>=<shape>` with dimensions separated by `x` (e.g
+    /// `--input-shape 0=1x3x224x224`), such that models exported with symbolic dimensions (e.g a batch dimension `N`) can be
+    /// quantized and laid out. Can be passed once per input
+    #[arg(long = "input-shape", value_parser = parse_input_shape, env = "EZKL_INPUT_SHAPES")]
+    #[serde(default)]
+    pub input_shapes: Vec<(usize, Vec<usize>)>,
+    /// Cuts the model at the given named tensors (e.g `--input-nodes features`), which become the inputs of the circuit in place
+    /// of those of the model, such that only the part of the model downstream of them (e.g the head of a large network) is proven
+    #[arg(long, value_delimiter = ',', env = "EZKL_INPUT_NODES")]
+    #[serde(default)]
+    pub input_nodes: Vec<String>,
+    /// Cuts the model at the given named tensors, which become the outputs of the circuit in place of those of the model, such that
+    /// only the part of the model upstream of them is proven
+    #[arg(long, value_delimiter = ',', env = "EZKL_OUTPUT_NODES")]
+    #[serde(default)]
+    pub output_nodes: Vec<String>,
+    /// Lays out affine and matmul layers as dot products accumulated over consecutive rows, the given number of products per row,
+    /// rather than fusing them into a single gate spanning their whole tensors, such that large fully connected layers take rows
+    /// rather than columns and rotations
+    #[arg(long, env = "EZKL_DOT_CHUNK")]
+    #[serde(default)]
+    pub dot_chunk: Option<usize>,
+    /// Leaves the products with zero-valued params out of the circuit, shrinking the gates of pruned models and the rows of their
+    /// chunked dot products. With private params the positions of their zeros become part of the circuit
+    #[arg(long, default_value = "false", env = "EZKL_SPARSE_PARAMS")]
+    #[serde(default)]
+    pub sparse_params: bool,
+    /// Range checks the public inputs against a table of the range of the lookup tables (`[-2^(bits-1), 2^(bits-1))`), such that
+    /// proofs over instances outside of the quantization range fail to verify. The table spans 2^bits rows, so lookups can't be
+    /// decomposed
+    #[arg(long, default_value = "false", env = "EZKL_RANGE_CHECK_INPUTS")]
+    #[serde(default)]
+    pub range_check_inputs: bool,
+    /// The path to a json spec file of claims about the outputs of the model (e.g `[{"claim": "range", "output": 0, "index": 3,
+    /// "min": 0.8}, {"claim": "argmax", "output": 0, "index": 7}]`), proven in-circuit against constants such that the claims can
+    /// be made public in place of the outputs (see `--public-outputs`). Proving fails if a claim doesn't hold
+    #[arg(long, value_parser = parse_claims, env = "EZKL_CLAIMS")]
+    #[serde(default)]
+    pub claims: Option<Claims>,
+    /// The path to a structured reference string for KZG, either a .ptau ceremony file or params saved by ezkl. If the file doesn't exist
+    /// the Hermez powers of tau ceremony file for `logrows` is downloaded to it. If unset, an unsafe (locally generated) setup is used
+    #[arg(long, env = "EZKL_SRS_PATH")]
+    #[serde(default)]
+    pub srs_path: Option<PathBuf>,
+    /// The path to a safetensors file whose tensors replace the weights of the model of the same (initializer) name, such that
+    /// weights can be swapped without re-exporting the model
+    #[arg(long, env = "EZKL_WEIGHTS")]
+    #[serde(default)]
+    pub weights: Option<PathBuf>,
+    /// The path to a model compiled by `compile` with the same model and circuit settings, loaded in place of parsing and
+    /// quantizing the model file
+    #[arg(long, env = "EZKL_COMPILED_MODEL")]
+    #[serde(default)]
+    pub compiled_model: Option<PathBuf>,
+    /// The path to a settings file generated by `gen-settings`, whose circuit settings are used in place of the flags above.
+    /// Passing a flag which disagrees with the settings file is an error
+    #[arg(long, env = "EZKL_SETTINGS_PATH")]
+    #[serde(default)]
+    pub settings_path: Option<PathBuf>,
+    /// The path to a .toml config file setting defaults for the flags above (e.g `scale = 7`), which flags and `EZKL_*`
+    /// environment variables override. Defaults to `ezkl.toml` in the working directory, if it exists
+    #[arg(long, env = "EZKL_CONFIG")]
+    #[serde(default)]
+    pub config: Option<PathBuf>,
+    /// Prints the outcome of the command (whether it succeeded, how long it took, and any error) as a line of json on stdout,
+    /// for other programs to parse
+    #[arg(long, default_value = "false", env = "EZKL_JSON")]
+    #[serde(default)]
+    pub json: bool,
+    /// The format logs are printed in on stderr: coloured text, or a json object per line (e.g for log aggregation), in which
+    /// case the duration of each phase and of the layout of each node are logged as they complete
+    #[arg(long, default_value_t = LogFormat::Text, value_enum, env = "EZKL_LOG_FORMAT")]
+    #[serde(default = "default_log_format")]
+    pub log_format: LogFormat,
+    /// Shows the progress of long running phases (e.g graph loading, the layout of each node, keygen and proving) on stderr
+    #[arg(long, default_value = "false", env = "EZKL_PROGRESS")]
+    #[serde(default)]
+    pub progress: bool,
+    /// Trades time for memory: the original (float) model is released once converted, the quantized params are spilled to disk
+    /// and loaded back as the nodes consuming them are laid out, and the intermediate results of the layout are dropped after
+    /// their last use
+    #[arg(long, default_value = "false", env = "EZKL_LOW_MEMORY")]
+    #[serde(default)]
+    pub low_memory: bool,
+    /// The directory lookup tables are cached in, such that runs over the same functions and bits load them rather than
+    /// computing them again. Defaults to `$XDG_CACHE_HOME/ezkl/tables` (or `~/.cache/ezkl/tables`)
+    #[arg(long, value_name = "DIR", env = "EZKL_TABLE_CACHE")]
+    #[serde(default)]
+    pub table_cache: Option<PathBuf>,
+    /// Computes lookup tables for every run, rather than caching them on disk
+    #[arg(long, default_value = "false", env = "EZKL_NO_TABLE_CACHE")]
+    #[serde(default)]
+    pub no_table_cache: bool,
+}
+
+/// The log format of configurations which don't specify one.
+fn default_log_format() -> LogFormat {
+    LogFormat::Text
+}
+
+/// The batch size of configurations which don't specify one.
+fn default_batch_size() -> usize {
+    1
+}
+
+/// Parses a `<node index>=<scale>` pair passed to `--layer-scale`.
+fn parse_layer_scale(s: &str) -> Result<(usize, i32), String> {
+    let (idx, scale) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid layer scale `{}`, expected <node index>=<scale>", s))?;
+    let idx = idx.parse::<usize>().map_err(|e| e.to_string())?;
+    let scale = scale.parse::<i32>().map_err(|e| e.to_string())?;
+    Ok((idx, scale))
+}
+
+/// Parses a `<index>=<visibility>` pair passed to `--input-visibility` and `--output-visibility`.
+fn parse_indexed_visibility(s: &str) -> Result<(usize, Visibility), String> {
+    let (idx, visibility) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid visibility `{}`, expected <index>=<visibility>", s))?;
+    let idx = idx.parse::<usize>().map_err(|e| e.to_string())?;
+    Ok((idx, visibility.parse()?))
+}
+
+/// Parses a `<output index>=<norm>` pair passed to `--tolerance-norm`.
+fn parse_tolerance_norm(s: &str) -> Result<(usize, ToleranceNorm), String> {
+    let (idx, norm) = s.split_once('=').ok_or_else(|| {
+        format!(
+            "invalid tolerance norm `{}`, expected <output index>=<norm>",
+            s
+        )
+    })?;
+    let idx = idx.parse::<usize>().map_err(|e| e.to_string())?;
+    Ok((idx, norm.parse()?))
+}
+
+/// Parses a `<input index>=<shape>` pair passed to `--input-shape`, whose dimensions are separated by `x` (e.g `0=1x3x224x224`).
+fn parse_input_shape(s: &str) -> Result<(usize, Vec<usize>), String> {
+    let (idx, shape) = s.split_once('=').ok_or_else(|| {
+        format!(
+            "invalid input shape `{}`, expected <input index>=<shape>",
+            s
+        )
+    })?;
+    let idx = idx.parse::<usize>().map_err(|e| e.to_string())?;
+    let shape = shape
+        .split('x')
+        .map(|d| d.trim().parse::<usize>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((idx, shape))
+}
+
+/// Loads the spec file of claims passed to `--claims`.
+fn parse_claims(s: &str) -> Result<Claims, String> {
+    Claims::load(Path::new(s)).map_err(|e| e.to_string())
+}
+
+/// The settings which determine the shape of a circuit, such that `setup`, `prove` and `verify` agree on the circuit they use
+/// when passed the same settings file (see `gen-settings` and `--settings-path`).
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct CircuitSettings {
+    /// The tolerance for error on model outputs
+    pub tolerance: usize,
+    /// The denominator in the fixed point representation used when quantizing
+    pub scale: i32,
+    /// The number of bits used in lookup tables
+    pub bits: usize,
+    /// The log_2 number of rows
+    pub logrows: u32,
+    /// Flags whether inputs are public
+    pub public_inputs: bool,
+    /// Flags whether outputs are public
+    pub public_outputs: bool,
+    /// Flags whether params are public
+    pub public_params: bool,
+    /// Hashes the inputs in-circuit with Poseidon
+    pub hashed_inputs: bool,
+    /// Commits to the inputs in-circuit with a key shared by the data owner
+    pub committed_inputs: bool,
+    /// Hashes the outputs in-circuit with Poseidon
+    pub hashed_outputs: bool,
+    /// Verifies in-circuit an EdDSA signature over the Poseidon hash of the inputs
+    pub attested_inputs: bool,
+    /// The depth of the Merkle tree the inputs are proven to be a leaf of
+    pub merkle_depth: Option<usize>,
+    /// Hashes the (private) params of the model in-circuit with Poseidon
+    pub commit_params: bool,
+    /// The maximum number of rotations, computed from the model if unset
+    #[serde(default)]
+    pub max_rotations: Option<usize>,
+    /// Overrides of the fixed point scale of given nodes
+    pub layer_scales: Vec<(usize, i32)>,
+    /// Overrides of the visibility of given inputs
+    #[serde(default)]
+    pub input_visibility: Vec<(usize, Visibility)>,
+    /// Overrides of the visibility of given outputs
+    #[serde(default)]
+    pub output_visibility: Vec<(usize, Visibility)>,
+    /// The norms of the errors of given outputs bounded by the tolerance
+    #[serde(default)]
+    pub tolerance_norms: Vec<(usize, ToleranceNorm)>,
+    /// The execution buckets the model is split before
+    pub cut_points: Vec<usize>,
+    /// The chunk of the model, as split by `cut_points`
+    pub chunk: Option<usize>,
+    /// The number of samples proven in a single circuit
+    pub batch_size: usize,
+    /// Resolves shapes and folds constants with tract's typed (decluttered) model before the graph is converted
+    #[serde(default)]
+    pub optimize_graph: bool,
+    /// Overrides of the shape of given inputs, concretizing their symbolic dimensions
+    #[serde(default)]
+    pub input_shapes: Vec<(usize, Vec<usize>)>,
+    /// The named tensors the model is cut at, which become the inputs of the circuit
+    #[serde(default)]
+    pub input_nodes: Vec<String>,
+    /// The named tensors the model is cut at, which become the outputs of the circuit
+    #[serde(default)]
+    pub output_nodes: Vec<String>,
+    /// The number of products per row of affine and matmul layers laid out as chunked dot products
+    #[serde(default)]
+    pub dot_chunk: Option<usize>,
+    /// Whether products with zero-valued params are left out of the circuit
+    #[serde(default)]
+    pub sparse_params: bool,
+    /// Whether the public inputs are range checked against a table of the range of the lookup tables
+    #[serde(default)]
+    pub range_check_inputs: bool,
+    /// The claims about the outputs of the model proven in-circuit
+    #[serde(default)]
+    pub claims: Option<Claims>,
+    /// The fixed point scales of the outputs of the model, recorded by `gen-settings` when passed the model such that verifiers
+    /// can dequantize public outputs without it. They are derived from the model rather than shaping the circuit, so commands
+    /// loading the settings ignore them
+    #[serde(default)]
+    pub output_scales: Vec<i32>,
+}
+
+impl CircuitSettings {
+    /// The hex encoded (blake3) hash of the settings, recorded in the header of saved settings.
+    pub fn hash(&self) -> Result<String, Box<dyn Error>> {
+        let serialized = serde_json::to_vec(&self).map_err(Box::<dyn Error>::from)?;
+        Ok(blake3::hash(&serialized).to_hex().to_string())
+    }
+
+    /// Saves the settings to a specified `path` as json, with a leading [ArtifactHeader].
+    pub fn save(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        let serialized = artifact::to_json(self, &ArtifactHeader::new(self.hash()?), true)?;
+        std::fs::write(path, serialized).map_err(Box::<dyn Error>::from)
+    }
+
+    /// Loads settings saved as json from the provided path. Settings which can't be read and were saved by another version of
+    /// ezkl are reported as incompatible.
+    pub fn load(path: &PathBuf) -> Result<Self, Box<dyn Error>> {
+        let data = std::fs::read(path).map_err(Box::<dyn Error>::from)?;
+        let (_, settings) = artifact::from_json(&data, "settings")?;
+        Ok(settings)
+    }
+}
+
+impl Default for CircuitSettings {
+    /// The settings of the command line defaults.
+    fn default() -> Self {
+        CircuitSettings {
+            tolerance: 0,
+            scale: 7,
+            bits: 16,
+            logrows: 17,
+            public_inputs: false,
+            public_outputs: true,
+            public_params: false,
+            hashed_inputs: false,
+            committed_inputs: false,
+            hashed_outputs: false,
+            attested_inputs: false,
+            merkle_depth: None,
+            commit_params: false,
+            max_rotations: None,
+            layer_scales: vec![],
+            input_visibility: vec![],
+            output_visibility: vec![],
+            tolerance_norms: vec![],
+            cut_points: vec![],
+            chunk: None,
+            batch_size: 1,
+            optimize_graph: false,
+            input_shapes: vec![],
+            input_nodes: vec![],
+            output_nodes: vec![],
+            dot_chunk: None,
+            sparse_params: false,
+            range_check_inputs: false,
+            claims: None,
+            output_scales: vec![],
+        }
+    }
+}
+
+/// Whether the flag `id` was set on the command line or through its environment variable, rather than left to its default.
+fn explicitly_set(matches: &ArgMatches, id: &str) -> bool {
+    matches!(
+        matches.value_source(id),
+        Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable)
+    )
+}
+
+/// Overwrites `arg` with `setting`, unless `arg` was explicitly set (or set by the config file, as recorded in `configured`) to
+/// a different value.
+fn merge_setting<T: PartialEq + std::fmt::Debug>(
+    matches: &ArgMatches,
+    configured: &[String],
+    id: &str,
+    arg: &mut T,
+    setting: T,
+) -> Result<(), String> {
+    if *arg != setting {
+        if explicitly_set(matches, id) {
+            return Err(format!(
+                "--{} was set to {:?} but the settings file has {:?}",
+                id.replace('_', "-"),
+                arg,
+                setting
+            ));
+        }
+        if configured.iter().any(|key| key == id) {
+            return Err(format!(
+                "`{}` is {:?} in the config file but {:?} in the settings file",
+                id, arg, setting
+            ));
+        }
+    }
+    *arg = setting;
+    Ok(())
 }
 
 impl Cli {
+    /// A configuration running `command` with the circuit `settings`, leaving the other options to their defaults.
+    pub fn new(command: Commands, settings: CircuitSettings) -> Self {
+        Cli {
+            command,
+            tolerance: settings.tolerance,
+            scale: settings.scale,
+            bits: settings.bits,
+            logrows: settings.logrows,
+            public_inputs: settings.public_inputs,
+            public_outputs: settings.public_outputs,
+            public_params: settings.public_params,
+            hashed_inputs: settings.hashed_inputs,
+            committed_inputs: settings.committed_inputs,
+            hashed_outputs: settings.hashed_outputs,
+            attested_inputs: settings.attested_inputs,
+            merkle_depth: settings.merkle_depth,
+            commit_params: settings.commit_params,
+            max_rotations: settings.max_rotations,
+            layer_scales: settings.layer_scales,
+            input_visibility: settings.input_visibility,
+            output_visibility: settings.output_visibility,
+            tolerance_norms: settings.tolerance_norms,
+            cut_points: settings.cut_points,
+            chunk: settings.chunk,
+            batch_size: settings.batch_size,
+            optimize_graph: settings.optimize_graph,
+            input_shapes: settings.input_shapes,
+            input_nodes: settings.input_nodes,
+            output_nodes: settings.output_nodes,
+            dot_chunk: settings.dot_chunk,
+            sparse_params: settings.sparse_params,
+            range_check_inputs: settings.range_check_inputs,
+            claims: settings.claims,
+            srs_path: None,
+            weights: None,
+            compiled_model: None,
+            settings_path: None,
+            config: None,
+            json: false,
+            log_format: LogFormat::Text,
+            progress: false,
+            low_memory: false,
+            table_cache: None,
+            no_table_cache: false,
+        }
+    }
+    /// The circuit settings of this configuration.
+    pub fn settings(&self) -> CircuitSettings {
+        CircuitSettings {
+            tolerance: self.tolerance,
+            scale: self.scale,
+            bits: self.bits,
+            logrows: self.logrows,
+            public_inputs: self.public_inputs,
+            public_outputs: self.public_outputs,
+            public_params: self.public_params,
+            hashed_inputs: self.hashed_inputs,
+            committed_inputs: self.committed_inputs,
+            hashed_outputs: self.hashed_outputs,
+            attested_inputs: self.attested_inputs,
+            merkle_depth: self.merkle_depth,
+            commit_params: self.commit_params,
+            max_rotations: self.max_rotations,
+            layer_scales: self.layer_scales.clone(),
+            input_visibility: self.input_visibility.clone(),
+            output_visibility: self.output_visibility.clone(),
+            tolerance_norms: self.tolerance_norms.clone(),
+            cut_points: self.cut_points.clone(),
+            chunk: self.chunk,
+            batch_size: self.batch_size,
+            optimize_graph: self.optimize_graph,
+            input_shapes: self.input_shapes.clone(),
+            input_nodes: self.input_nodes.clone(),
+            output_nodes: self.output_nodes.clone(),
+            dot_chunk: self.dot_chunk,
+            sparse_params: self.sparse_params,
+            range_check_inputs: self.range_check_inputs,
+            claims: self.claims.clone(),
+            output_scales: vec![],
+        }
+    }
+    /// Replaces the circuit settings of this configuration with `settings`, erroring if any setting explicitly passed on the
+    /// command line or through the environment (as recorded by `matches`), or set by the config file (the `configured` keys
+    /// returned by [Cli::apply_config]) disagrees with them.
+    pub fn apply_settings(
+        &mut self,
+        settings: CircuitSettings,
+        matches: &ArgMatches,
+        configured: &[String],
+    ) -> Result<(), String> {
+        // settings are matched to their flags by the field name, which clap uses as the id of the flag
+        macro_rules! merge {
+            ($($field:ident),*) => {
+                $(merge_setting(matches, configured, stringify!($field), &mut self.$field, settings.$field)?;)*
+            };
+        }
+        merge!(
+            tolerance,
+            scale,
+            bits,
+            logrows,
+            public_inputs,
+            public_outputs,
+            public_params,
+            hashed_inputs,
+            committed_inputs,
+            hashed_outputs,
+            attested_inputs,
+            merkle_depth,
+            commit_params,
+            max_rotations,
+            layer_scales,
+            input_visibility,
+            output_visibility,
+            tolerance_norms,
+            cut_points,
+            chunk,
+            batch_size,
+            optimize_graph,
+            input_shapes,
+            input_nodes,
+            output_nodes,
+            dot_chunk,
+            sparse_params,
+            range_check_inputs,
+            claims
+        );
+        Ok(())
+    }
+    /// Sets the flags which weren't explicitly set (as recorded by `matches`) to their value in the toml `config`, whose keys
+    /// are the names of the fields of [Cli] (e.g `max_rotations`). Returns the keys of the flags it set.
+    pub fn apply_config(
+        &mut self,
+        config: toml::value::Table,
+        matches: &ArgMatches,
+    ) -> Result<Vec<String>, String> {
+        // flags are merged through their json encoding, such that config values are parsed as the serde encoding of their field
+        let mut json = serde_json::to_value(&self).map_err(|e| e.to_string())?;
+        let fields = json
+            .as_object_mut()
+            .expect("the configuration is a json object");
+        let mut configured = vec![];
+        for (key, value) in config {
+            if key == "command" || !fields.contains_key(&key) {
+                return Err(format!("unknown option `{}` in config file", key));
+            }
+            if !explicitly_set(matches, &key) {
+                let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+                fields.insert(key.clone(), value);
+                configured.push(key);
+            }
+        }
+        *self = serde_json::from_value(json).map_err(|e| format!("invalid config file: {}", e))?;
+        Ok(configured)
+    }
+    /// Parses an ezkl configuration from the command line, on top of the config file (see `--config`) and under the settings file
+    /// passed with `--settings-path`, if any. Exits with a usage error if either can't be loaded or disagrees with the flags passed.
+    pub fn from_command_line() -> Self {
+        let matches = Cli::command().get_matches();
+        let mut args = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        let config_path = args.config.clone().or_else(|| {
+            let default = PathBuf::from(DEFAULT_CONFIG);
+            default.exists().then_some(default)
+        });
+        let mut configured = vec![];
+        if let Some(path) = config_path {
+            let res = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|data| toml::from_str(&data).map_err(|e| e.to_string()))
+                .map_err(|e| format!("failed to load config from {:?}: {}", path, e))
+                .and_then(|config| args.apply_config(config, &matches));
+            match res {
+                Ok(keys) => configured = keys,
+                Err(msg) => Cli::command().error(ErrorKind::InvalidValue, msg).exit(),
+            }
+        }
+        if let Some(path) = args.settings_path.clone() {
+            let res = CircuitSettings::load(&path)
+                .map_err(|e| format!("failed to load settings from {:?}: {}", path, e))
+                .and_then(|settings| args.apply_settings(settings, &matches, &configured));
+            if let Err(msg) = res {
+                Cli::command()
+                    .error(ErrorKind::ArgumentConflict, msg)
+                    .exit();
+            }
+        }
+        args
+    }
     /// Export the ezkl configuration as json
     pub fn as_json(&self) -> Result<String, Box<dyn Error>> {
         let serialized = match serde_json::to_string(&self) {
@@ -57,11 +658,31 @@ impl Cli {
     pub fn from_json(arg_json: &str) -> Result<Self, serde_json::Error> {
         serde_json::from_str(arg_json)
     }
+    /// Translates the flags of a command running on a single model into [RunArgs], from which the model and its circuit are built.
+    pub fn run_args(&self) -> Result<RunArgs, GraphError> {
+        let (model, mode) = self.command.model().ok_or(GraphError::ModelLoad)?;
+        Ok(RunArgs {
+            model: model.into(),
+            weights: self.weights.clone(),
+            compiled: self.compiled_model.clone(),
+            settings: self.settings(),
+            mode,
+            progress: self.progress,
+            low_memory: self.low_memory,
+            table_cache: match self.no_table_cache {
+                true => None,
+                false => self.table_cache.clone().or_else(table_cache::default_dir),
+            },
+            prepared: None,
+            task: None,
+            custom_ops: CustomOps::default(),
+        })
+    }
     /// Create an ezkl configuration: if there is an EZKLCONF env variable, parse its value, else read it from the command line.
     pub fn create() -> Self {
         match env::var(EZKLCONF) {
             Ok(val) => Self::from_json(&val).unwrap(),
-            Err(_e) => Cli::parse(),
+            Err(_e) => Cli::from_command_line(),
         }
     }
 }
@@ -81,6 +702,126 @@ impl std::fmt::Display for ProofSystem {
     }
 }
 
+/// The multi-open scheme used to batch polynomial openings when proving with [ProofSystem::KZG].
+/// SHPLONK produces smaller proofs (and cheaper on-chain verification), whereas GWC is faster to prove.
+#[allow(missing_docs)]
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MultiOpenScheme {
+    GWC,
+    SHPLONK,
+}
+impl std::fmt::Display for MultiOpenScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+/// The transcript used to derive Fiat-Shamir challenges when creating and verifying proofs.
+/// Poseidon is cheap to verify within a circuit (e.g for aggregation) and Keccak is cheap to verify on the EVM:
+/// Keccak challenges are derived exactly as by the generated EVM verifiers, which therefore accept the same proof bytes as `verify`.
+#[allow(missing_docs)]
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TranscriptType {
+    Blake2b,
+    Keccak,
+    Poseidon,
+}
+impl std::fmt::Display for TranscriptType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+/// The verifier a proof is created for, which determines the transcript the proof must be created with.
+/// The EVM verifier of a single proof (see `create_evm_verifier`) derives its challenges with a keccak transcript, whereas
+/// `aggregate` verifies the proofs it aggregates with a poseidon transcript.
+#[allow(missing_docs)]
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum VerifierTarget {
+    Native,
+    Evm,
+    Aggregation,
+}
+impl VerifierTarget {
+    /// The transcript the verifier accepts proofs of, if it accepts any.
+    pub fn transcript(&self) -> Option<TranscriptType> {
+        match self {
+            VerifierTarget::Native => None,
+            VerifierTarget::Evm => Some(TranscriptType::Keccak),
+            VerifierTarget::Aggregation => Some(TranscriptType::Poseidon),
+        }
+    }
+}
+impl std::fmt::Display for VerifierTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+/// The format proofs are saved to and loaded from.
+/// Json holds the public inputs alongside the hex encoded proof, bincode is a compact binary encoding of the same,
+/// and hex is the raw calldata (public inputs as field elements followed by the proof) expected by EVM verifiers.
+#[allow(missing_docs)]
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ProofFormat {
+    Json,
+    Bincode,
+    Hex,
+}
+impl std::fmt::Display for ProofFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+/// The format logs are printed in.
+#[allow(missing_docs)]
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+impl std::fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
+/// The format `table` prints the model in.
+/// Table lists the nodes of the model and json lists them as a json array (e.g for other programs to parse), whereas dot
+/// (for Graphviz) and mermaid render the computational graph, with the nodes of each execution bucket grouped together.
+#[allow(missing_docs)]
+#[derive(ValueEnum, Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TableFormat {
+    Table,
+    Json,
+    Dot,
+    Mermaid,
+}
+impl std::fmt::Display for TableFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+            .fmt(f)
+    }
+}
+
 #[allow(missing_docs)]
 #[derive(Debug, Subcommand, Clone, Deserialize, Serialize)]
 pub enum Commands {
@@ -90,6 +831,103 @@ pub enum Commands {
         /// The path to the .onnx model file
         #[arg(short = 'M', long)]
         model: String,
+        /// The format to print the model in: a table or json array of its nodes, or its graph in the Graphviz DOT language or as a Mermaid flowchart
+        #[arg(long, default_value_t = TableFormat::Table, value_enum)]
+        format: TableFormat,
+    },
+
+    /// Runs a forward pass on the quantized model (without building a circuit) and saves the resulting outputs, for instance to populate the expected outputs of a .json data file
+    #[command(arg_required_else_help = true, alias = "gen-witness")]
+    Forward {
+        /// The path to the .json data file
+        #[arg(short = 'D', long)]
+        data: String,
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: String,
+        /// The path to the output .json data file, which holds the inputs in `data` alongside the computed outputs. For a path
+        /// ending in .csv, only the (dequantized) outputs are saved, with a row per sample
+        #[arg(short = 'O', long)]
+        output: PathBuf,
+        /// The path to (optionally) save the quantized values of every node in the model to
+        #[arg(long)]
+        witness_path: Option<PathBuf>,
+        /// The path to (optionally) save a .json data file for the next model of a chain (see `aggregate --chain`) to,
+        /// whose inputs are the computed outputs
+        #[arg(long)]
+        next_data: Option<PathBuf>,
+    },
+
+    /// Generates a .json data file of random (seeded) inputs with the shapes of the inputs of the model, alongside the outputs
+    /// of the quantized model, for smoke testing circuit settings and benchmarking without real data
+    #[command(arg_required_else_help = true)]
+    GenInput {
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: String,
+        /// The path to the output .json data file
+        #[arg(short = 'O', long)]
+        output: PathBuf,
+        /// The seed of the random number generator, such that the same inputs are generated across runs
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// The lower bound of the inputs, clamped to the smallest value representable at the scale and bits of the model
+        #[arg(long, default_value_t = -1.0, allow_negative_numbers = true)]
+        min: f32,
+        /// The upper bound of the inputs, clamped to the largest value representable at the scale and bits of the model
+        #[arg(long, default_value_t = 1.0, allow_negative_numbers = true)]
+        max: f32,
+    },
+
+    /// Parses and quantizes the model for the circuit settings, and saves the processed graph such that commands passed
+    /// `--compiled-model` skip loading the model
+    #[command(arg_required_else_help = true)]
+    Compile {
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: String,
+        /// The path to save the compiled model to
+        #[arg(short = 'O', long)]
+        output: PathBuf,
+        /// Ships the lookup tables of the model with the compiled model, such that they aren't computed when it is loaded
+        #[arg(long, default_value = "false")]
+        include_tables: bool,
+    },
+
+    /// Signs the inputs of a .json data file with an EdDSA key, for proving with `--attested-inputs`. The signature is over the inputs
+    /// as quantized for the model, so the same scales should be used when proving
+    #[command(arg_required_else_help = true)]
+    Sign {
+        /// The path to the .json data file
+        #[arg(short = 'D', long)]
+        data: String,
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: String,
+        /// The path to the secret key to sign with. If the file doesn't exist, a new key is generated and saved to it
+        #[arg(long)]
+        key_path: PathBuf,
+        /// The path to the output .json data file, which holds the inputs in `data` alongside their signature
+        #[arg(short = 'O', long)]
+        output: PathBuf,
+    },
+
+    /// Builds a Poseidon Merkle tree of depth `--merkle-depth` over the inputs of a dataset of .json data files (one leaf per sample),
+    /// prints its root, and saves a copy of the data file at `index` holding the paths of its samples to the root
+    #[command(arg_required_else_help = true)]
+    MerklePath {
+        /// The paths to the .json data files making up the dataset, in order
+        #[arg(short = 'D', long, num_args = 1..)]
+        data: Vec<String>,
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: String,
+        /// The index of the data file (in `data`) to save the paths of
+        #[arg(long)]
+        index: usize,
+        /// The path to the output .json data file
+        #[arg(short = 'O', long)]
+        output: PathBuf,
     },
 
     /// Loads model and input and runs mock prover (for testing)
@@ -101,6 +939,21 @@ pub enum Commands {
         /// The path to the .onnx model file
         #[arg(short = 'M', long)]
         model: String,
+        /// The path to (optionally) save the quantized values of every node in the model to, as computed from the inputs of the
+        /// circuit, with a witness per sample for batches
+        #[arg(long)]
+        witness_path: Option<PathBuf>,
+    },
+
+    /// Runs the original (floating point) model and the quantized model over one or more inputs and reports the error introduced by quantization
+    #[command(arg_required_else_help = true)]
+    Accuracy {
+        /// The path(s) to the .json data files, each holding a set of inputs to the model
+        #[arg(short = 'D', long, num_args = 1..)]
+        data: Vec<String>,
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: String,
     },
 
     /// Loads model and input and runs full prover (for testing)
@@ -120,6 +973,149 @@ pub enum Commands {
             value_enum
         )]
         pfsys: ProofSystem,
+        /// The [MultiOpenScheme] to use (KZG only)
+        #[arg(long, default_value_t = MultiOpenScheme::GWC, value_enum)]
+        multiopen: MultiOpenScheme,
+        /// The [TranscriptType] to use (blake2b only for IPA)
+        #[arg(long, default_value_t = TranscriptType::Blake2b, value_enum)]
+        transcript: TranscriptType,
+    },
+
+    /// Loads model and input, runs the full prover, and reports the time and peak memory of each phase and statistics of the circuit as json
+    #[command(arg_required_else_help = true)]
+    Bench {
+        /// The path to the .json data file
+        #[arg(short = 'D', long)]
+        data: String,
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: PathBuf,
+        /// The path to save the json report to, printed to stdout if unset
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Loads model and lays out its circuit without proving, reporting the rows used by each execution bucket, the number of columns,
+    /// the sizes of lookup tables, and the smallest `logrows` the circuit fits in as json
+    #[command(arg_required_else_help = true)]
+    Estimate {
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: PathBuf,
+        /// The path to save the json report to, printed to stdout if unset
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Loads model and renders the layout of its circuit (the columns and rows used by each region) to a .png image (requires the `dev-graph` feature)
+    #[cfg(feature = "dev-graph")]
+    #[command(arg_required_else_help = true)]
+    Render {
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: PathBuf,
+        /// The path to save the image to
+        #[arg(long)]
+        output: PathBuf,
+        /// The width of the image, in pixels
+        #[arg(long, default_value = "2048")]
+        width: u32,
+        /// The height of the image, in pixels
+        #[arg(long, default_value = "7680")]
+        height: u32,
+        /// Labels each region with its name, i.e the node of the graph it belongs to
+        #[arg(long, default_value = "false")]
+        labels: bool,
+    },
+
+    /// Loads model, the params at `--params-path`, and the proving key at `--pk-path` once, and serves proofs over HTTP: `POST /prove` takes the json
+    /// inputs of a data file and returns a json proof, `POST /verify` takes a json proof and returns whether it is accepted
+    #[cfg(not(target_arch = "wasm32"))]
+    #[command(arg_required_else_help = true)]
+    Serve {
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: PathBuf,
+        /// The path to the proving key generated by `setup`
+        #[arg(long)]
+        pk_path: PathBuf,
+        /// The path to the params used by `setup`
+        #[arg(long)]
+        params_path: PathBuf,
+        /// The address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+        /// The [TranscriptType] proofs are created and verified with
+        #[arg(long, default_value_t = TranscriptType::Blake2b, value_enum)]
+        transcript: TranscriptType,
+        /// The maximum size in bytes of the body of a request, larger requests being rejected with a 413
+        #[arg(long, default_value = "16777216")]
+        max_body_size: u64,
+    },
+
+    /// Converts a .ptau powers of tau ceremony file to KZG params for circuits of `logrows` rows
+    #[command(arg_required_else_help = true)]
+    ConvertPtau {
+        /// The path to the .ptau file
+        #[arg(long)]
+        ptau_path: PathBuf,
+        /// The path to output the params to
+        #[arg(long)]
+        params_path: PathBuf,
+    },
+
+    /// Trims the structured reference string at `--srs-path` (a .ptau file or params saved by ezkl) to support circuits of `logrows` rows, saving the result as KZG params
+    #[command(arg_required_else_help = true)]
+    TrimSrs {
+        /// The path to output the trimmed params to
+        #[arg(long)]
+        params_path: PathBuf,
+    },
+
+    /// Saves the circuit settings (e.g `--bits`, `-K`, `--scale`, `--tolerance` and the visibility flags) to a json file, such that
+    /// subsequent commands passed `--settings-path` are guaranteed to use the same circuit
+    #[command(arg_required_else_help = true)]
+    GenSettings {
+        /// The path to output the settings to
+        #[arg(long)]
+        settings_path: PathBuf,
+        /// The path to the .onnx model file, whose output scales are then recorded in the settings (optional)
+        #[arg(short = 'M', long)]
+        model: Option<PathBuf>,
+    },
+
+    /// Generates a dummy structured reference string for circuits of `logrows` rows. Not secure, for testing only
+    #[command(arg_required_else_help = true)]
+    GenSrs {
+        /// The path to output the params to
+        #[arg(long)]
+        params_path: PathBuf,
+    },
+
+    /// Loads model, generates the params, proving key, and verification key for its circuit, and saves them to disk for use by `prove` and `verify`
+    #[command(arg_required_else_help = true)]
+    Setup {
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: PathBuf,
+        /// The path to output the proving key to
+        #[arg(long)]
+        pk_path: PathBuf,
+        /// The path to output the verification key to
+        #[arg(long)]
+        vk_path: PathBuf,
+        /// The path to output the params to
+        #[arg(long)]
+        params_path: PathBuf,
+        /// The [ProofSystem] we'll be using.
+        #[arg(
+            long,
+            require_equals = true,
+            num_args = 0..=1,
+            default_value_t = ProofSystem::KZG,
+            value_enum
+        )]
+        pfsys: ProofSystem,
     },
 
     /// Loads model and data, prepares vk and pk, and creates proof, saving proof in --proof-path
@@ -134,12 +1130,18 @@ pub enum Commands {
         /// The path to the desired output file
         #[arg(long)]
         proof_path: PathBuf,
+        /// The [ProofFormat] to save the proof in
+        #[arg(long, default_value_t = ProofFormat::Json, value_enum)]
+        proof_format: ProofFormat,
         /// The path to output to the desired verfication key file (optional)
         #[arg(long)]
         vk_path: PathBuf,
         /// The path to output to the desired verfication key file (optional)
         #[arg(long)]
         params_path: PathBuf,
+        /// The path to a proving key generated by `setup`. If set, the proving key and the params at `params-path` are loaded rather than generated
+        #[arg(long)]
+        pk_path: Option<PathBuf>,
         /// The [ProofSystem] we'll be using.
         #[arg(
             long,
@@ -150,8 +1152,124 @@ pub enum Commands {
             value_enum
         )]
         pfsys: ProofSystem,
+        /// The [MultiOpenScheme] to use (KZG only)
+        #[arg(long, default_value_t = MultiOpenScheme::GWC, value_enum)]
+        multiopen: MultiOpenScheme,
+        /// The [TranscriptType] to use (blake2b only for IPA)
+        #[arg(long, default_value_t = TranscriptType::Blake2b, value_enum)]
+        transcript: TranscriptType,
+        /// The [VerifierTarget] the proof is created for, warning if the transcript isn't the one its verifier accepts
+        #[arg(long, default_value_t = VerifierTarget::Native, value_enum)]
+        target: VerifierTarget,
         // todo, optionally allow supplying proving key
     },
+    /// Aggregates proofs of one or more models (created by `prove` using the gwc multi-open scheme and a poseidon transcript) into a single proof,
+    /// and generates an EVM verifier for the aggregated proof. The proofs and the aggregated proof must use the structured reference string at `--srs-path`.
+    /// If `--cut-points` is set, the proofs are of each chunk of a single model (in order), and the activations passed between chunks are constrained to match.
+    /// If `--chain` is set, the proofs are of a pipeline of models (in order), and the outputs of each model are constrained to match the inputs of the next
+    #[cfg(feature = "evm")]
+    #[command(arg_required_else_help = true)]
+    Aggregate {
+        /// The paths to the .onnx model files, one per proof
+        #[arg(short = 'M', long = "model", num_args = 1..)]
+        models: Vec<PathBuf>,
+        /// The paths to the proofs to aggregate
+        #[arg(long = "proof-path", num_args = 1..)]
+        proof_paths: Vec<PathBuf>,
+        /// The [ProofFormat] the proofs were saved in
+        #[arg(long, default_value_t = ProofFormat::Json, value_enum)]
+        proof_format: ProofFormat,
+        /// The paths to the verification keys of each proof
+        #[arg(long = "vk-path", num_args = 1..)]
+        vk_paths: Vec<PathBuf>,
+        /// The path to output the aggregated proof to
+        #[arg(long)]
+        aggregation_proof_path: PathBuf,
+        /// The path to output the (hex encoded) deployment code of the EVM verifier of the aggregated proof to, one line per contract if
+        /// the verifier is split to fit the contract size limit
+        #[arg(long, visible_alias = "evm-verifier-path")]
+        deployment_code_path: PathBuf,
+        /// The log_2 number of rows of the aggregation circuit
+        #[arg(long, default_value = "23")]
+        aggregation_logrows: u32,
+        /// Links the proofs of a pipeline of models, where each model consumes the outputs of the previous one. The linked tensors
+        /// are held by the instances of both proofs, such that with `--hashed-inputs` and `--hashed-outputs` only their hash is public
+        #[arg(long)]
+        chain: bool,
+        /// Prints (as json) the gas used to deploy the verifier and to verify the aggregated proof, with the cost of verification
+        /// broken down into the base cost of the transaction, the cost of its calldata, and the cost of executing the verifier
+        #[arg(long)]
+        gas_report: bool,
+        /// The path to (optionally) save the Yul source of the EVM verifier to
+        #[arg(long)]
+        yul_path: Option<PathBuf>,
+        /// The path to (optionally) save a Solidity verifier to, which checks the instances of aggregated proofs and forwards
+        /// them to the EVM verifier (deployed from `--deployment-code-path`), behind a `verifyProof(bytes, uint256[])` interface
+        #[arg(long)]
+        sol_path: Option<PathBuf>,
+        /// The solc version requirement of the Solidity verifier
+        #[arg(long, default_value = DEFAULT_PRAGMA)]
+        solc_pragma: String,
+        /// The path to (optionally) save an attestation contract to, which verifies aggregated proofs with the Solidity verifier
+        /// (saved alongside it at `--sol-path`), and records and emits the params commitment, inputs, and outputs of each model
+        #[arg(long, requires = "sol_path")]
+        attestation_path: Option<PathBuf>,
+        /// The path to a .json file listing the calls (`address`, `calldata`, and `decimals` of the int256 returned) whose results
+        /// are the elements of the (public) inputs of the first model, in order, as checked by the data attestation contract
+        #[arg(long)]
+        data_calls_path: Option<PathBuf>,
+        /// The path to (optionally) save a data attestation contract to, which makes the calls at `--data-calls-path` at verification
+        /// time, checks their results match the inputs of the first model, and verifies aggregated proofs with the Solidity verifier
+        #[arg(long, requires_all = ["sol_path", "data_calls_path"])]
+        data_attestation_path: Option<PathBuf>,
+        /// The directory to (optionally) write a Foundry project to, holding the Solidity verifier, the deployment code of the
+        /// EVM verifier, a test verifying the aggregated proof, and a script deploying the verifiers
+        #[arg(long)]
+        scaffold: Option<PathBuf>,
+    },
+    /// Encodes the calldata verifying an aggregated proof (as generated by `aggregate`) with the Solidity verifier (see `--sol-path`)
+    #[cfg(feature = "evm")]
+    #[command(arg_required_else_help = true)]
+    EncodeEvmCalldata {
+        /// The path to the aggregated proof
+        #[arg(long)]
+        aggregation_proof_path: PathBuf,
+        /// The path to output the (hex encoded) calldata of a call to `verifyProof` of the Solidity verifier to
+        #[arg(long)]
+        calldata_path: PathBuf,
+        /// The path to (optionally) save a .json fixture for contract tests to, holding the proof, its instances, and the calldata
+        /// of calls to both the Solidity verifier and the verifier generated from Yul
+        #[arg(long)]
+        fixture_path: Option<PathBuf>,
+    },
+    /// Deploys an EVM verifier (as generated by `aggregate`) from the account of a private key, and prints its address
+    #[cfg(feature = "evm")]
+    #[command(arg_required_else_help = true)]
+    DeployVerifier {
+        /// The path to the (hex encoded) deployment code of the verifier, as saved by `aggregate`
+        #[arg(long)]
+        deployment_code_path: PathBuf,
+        /// The URL of the JSON-RPC endpoint of the Ethereum node to deploy through
+        #[arg(long)]
+        rpc_url: String,
+        /// The path to a file holding the (hex encoded) private key of the deploying account
+        #[arg(long)]
+        private_key_path: PathBuf,
+    },
+    /// Verifies an aggregated proof (as generated by `aggregate`) by calling a deployed EVM verifier, returning accept or reject
+    #[cfg(feature = "evm")]
+    #[command(arg_required_else_help = true)]
+    VerifyOnchain {
+        /// The path to the aggregated proof
+        #[arg(long)]
+        aggregation_proof_path: PathBuf,
+        /// The address of the deployed verifier
+        #[arg(long)]
+        addr: String,
+        /// The URL of the JSON-RPC endpoint of the Ethereum node to call the verifier through
+        #[arg(long)]
+        rpc_url: String,
+    },
     /// Verifies a proof, returning accept or reject
     #[command(arg_required_else_help = true)]
     Verify {
@@ -160,8 +1278,18 @@ pub enum Commands {
         model: PathBuf,
 
         /// The path to the proof file
+        #[arg(
+            long,
+            required_unless_present = "proofs_dir",
+            conflicts_with = "proofs_dir"
+        )]
+        proof_path: Option<PathBuf>,
+        /// A directory of proofs of the model to verify at once, which for KZG are batched into a single pairing check
         #[arg(long)]
-        proof_path: PathBuf,
+        proofs_dir: Option<PathBuf>,
+        /// The [ProofFormat] the proof was saved in
+        #[arg(long, default_value_t = ProofFormat::Json, value_enum)]
+        proof_format: ProofFormat,
         /// The path to output to the desired verfication key file (optional)
         #[arg(long)]
         vk_path: PathBuf,
@@ -178,9 +1306,89 @@ pub enum Commands {
             value_enum
         )]
         pfsys: ProofSystem,
+        /// The [MultiOpenScheme] the proof was created with (KZG only)
+        #[arg(long, default_value_t = MultiOpenScheme::GWC, value_enum)]
+        multiopen: MultiOpenScheme,
+        /// The [TranscriptType] the proof was created with (blake2b only for IPA)
+        #[arg(long, default_value_t = TranscriptType::Blake2b, value_enum)]
+        transcript: TranscriptType,
+        /// The path to save the public outputs of the proofs to as json, dequantized at the scales of the outputs of the model
+        /// (optional)
+        #[arg(long)]
+        outputs_path: Option<PathBuf>,
+    },
+
+    /// Prints the hashes of the circuit (model and settings), verification key, and params, which are recorded in proofs
+    #[command(arg_required_else_help = true)]
+    Hash {
+        /// The path to the .onnx model file
+        #[arg(short = 'M', long)]
+        model: PathBuf,
+        /// The path to the verification key file
+        #[arg(long)]
+        vk_path: PathBuf,
+        /// The path to the params file
+        #[arg(long)]
+        params_path: PathBuf,
+        /// The [ProofSystem] the keys were generated for
+        #[arg(
+            long,
+            require_equals = true,
+            num_args = 0..=1,
+            default_value_t = ProofSystem::KZG,
+            value_enum
+        )]
+        pfsys: ProofSystem,
+        /// The path to a proof, whose recorded hashes are checked against those of the artifacts (optional)
+        #[arg(long)]
+        proof_path: Option<PathBuf>,
+        /// The [ProofFormat] the proof was saved in
+        #[arg(long, default_value_t = ProofFormat::Json, value_enum)]
+        proof_format: ProofFormat,
     },
 }
 
+impl Commands {
+    /// The path to the .onnx model file the command runs on, and the [Mode] the model is used in, for commands which run on a
+    /// single model.
+    pub fn model(&self) -> Option<(PathBuf, Mode)> {
+        let (model, mode) = match self {
+            Commands::Table { model, .. } => (PathBuf::from(model), Mode::Table),
+            Commands::Mock { model, .. } => (PathBuf::from(model), Mode::Mock),
+            Commands::Fullprove { model, .. } => (PathBuf::from(model), Mode::FullProve),
+            Commands::Setup { model, .. } => (model.clone(), Mode::Setup),
+            Commands::Prove { model, .. } => (model.clone(), Mode::Prove),
+            Commands::Verify { model, .. } => (model.clone(), Mode::Verify),
+            Commands::Accuracy { model, .. } => (PathBuf::from(model), Mode::Accuracy),
+            Commands::Forward { model, .. } => (PathBuf::from(model), Mode::Forward),
+            Commands::GenInput { model, .. } => (PathBuf::from(model), Mode::GenInput),
+            Commands::Compile { model, .. } => (PathBuf::from(model), Mode::Compile),
+            Commands::Hash { model, .. } => (model.clone(), Mode::Hash),
+            Commands::Sign { model, .. } => (PathBuf::from(model), Mode::Sign),
+            Commands::MerklePath { model, .. } => (PathBuf::from(model), Mode::Merkle),
+            Commands::Bench { model, .. } => (model.clone(), Mode::Bench),
+            Commands::Estimate { model, .. } => (model.clone(), Mode::Estimate),
+            Commands::GenSettings {
+                model: Some(model), ..
+            } => (model.clone(), Mode::Settings),
+            #[cfg(feature = "dev-graph")]
+            Commands::Render { model, .. } => (model.clone(), Mode::Render),
+            #[cfg(not(target_arch = "wasm32"))]
+            Commands::Serve { model, .. } => (model.clone(), Mode::Serve),
+            // aggregation spans multiple models, each of which is loaded separately
+            _ => return None,
+        };
+        Some((model, mode))
+    }
+}
+
+impl Model {
+    /// Creates a `Model` from parsed CLI arguments
+    pub fn from_ezkl_conf(args: Cli) -> Result<Self, Box<dyn Error>> {
+        Model::from_run_args(&args.run_args()?)
+    }
+}
+
 /// Loads the path to a path `data` represented as a [String]. If empty queries the user for an input.
 pub fn data_path(data: String) -> PathBuf {
     let mut s = String::new();
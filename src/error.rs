@@ -0,0 +1,129 @@
+use crate::circuit::CircuitError;
+use crate::graph::input::InputError;
+use crate::graph::{BuilderError, GraphError};
+#[cfg(feature = "evm")]
+use crate::pfsys::evm::aggregation::AggregationError;
+use crate::pfsys::srs::SrsError;
+use crate::pfsys::PfsysError;
+use crate::tensor::TensorError;
+use halo2_proofs::plonk::Error as PlonkError;
+use std::error::Error;
+use std::io;
+use thiserror::Error;
+
+/// The errors of ezkl's public api, wrapping those of each module such that callers can match on what went wrong (e.g. a
+/// [PfsysError::StaleKey] to re-run setup) rather than on error messages.
+#[derive(Debug, Error)]
+pub enum EzklError {
+    /// Loading or laying out a model failed
+    #[error(transparent)]
+    Graph(#[from] GraphError),
+    /// Model settings are inconsistent
+    #[error(transparent)]
+    Builder(#[from] BuilderError),
+    /// Model inputs could not be loaded
+    #[error(transparent)]
+    Input(#[from] InputError),
+    /// Configuring or assigning a circuit failed
+    #[error(transparent)]
+    Circuit(#[from] CircuitError),
+    /// A tensor operation failed
+    #[error(transparent)]
+    Tensor(#[from] TensorError),
+    /// A proof system artifact (key, proof, input data) is invalid
+    #[error(transparent)]
+    Pfsys(#[from] PfsysError),
+    /// Structured reference strings could not be loaded
+    #[error(transparent)]
+    Srs(#[from] SrsError),
+    /// Aggregating proofs or generating EVM verifiers failed
+    #[cfg(feature = "evm")]
+    #[error(transparent)]
+    Aggregation(#[from] AggregationError),
+    /// halo2 failed to generate keys or proofs
+    #[error("halo2 error: {0}")]
+    Plonk(#[from] PlonkError),
+    /// Reading or writing a file failed
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    /// A json file or payload is malformed
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// Any other error, such as those of third party crates
+    #[error("{0}")]
+    Other(String),
+}
+
+/// Recovers the typed error behind a boxed one, as returned by the lower level functions of the crate.
+macro_rules! downcast {
+    ($e:expr, $($variant:ident($ty:ty)),*) => {{
+        let e = $e;
+        $(
+            let e = match e.downcast::<$ty>() {
+                Ok(e) => return EzklError::$variant(*e),
+                Err(e) => e,
+            };
+        )*
+        e
+    }};
+}
+
+impl From<Box<dyn Error>> for EzklError {
+    fn from(e: Box<dyn Error>) -> Self {
+        let e = match e.downcast::<EzklError>() {
+            Ok(e) => return *e,
+            Err(e) => e,
+        };
+        let e = downcast!(
+            e,
+            Graph(GraphError),
+            Builder(BuilderError),
+            Input(InputError),
+            Circuit(CircuitError),
+            Tensor(TensorError),
+            Pfsys(PfsysError),
+            Srs(SrsError),
+            Plonk(PlonkError),
+            Io(io::Error),
+            Json(serde_json::Error)
+        );
+        #[cfg(feature = "evm")]
+        let e = downcast!(e, Aggregation(AggregationError));
+        EzklError::Other(e.to_string())
+    }
+}
+
+impl From<String> for EzklError {
+    fn from(e: String) -> Self {
+        EzklError::Other(e)
+    }
+}
+
+impl From<&str> for EzklError {
+    fn from(e: &str) -> Self {
+        EzklError::Other(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovers_boxed_errors() {
+        let boxed: Box<dyn Error> = PfsysError::StaleKey.into();
+        assert!(matches!(
+            EzklError::from(boxed),
+            EzklError::Pfsys(PfsysError::StaleKey)
+        ));
+        let boxed: Box<dyn Error> = EzklError::Graph(GraphError::Cancelled).into();
+        assert!(matches!(
+            EzklError::from(boxed),
+            EzklError::Graph(GraphError::Cancelled)
+        ));
+        let boxed: Box<dyn Error> = io::Error::from(io::ErrorKind::NotFound).into();
+        assert!(matches!(EzklError::from(boxed), EzklError::Io(_)));
+        let boxed: Box<dyn Error> = "something else".into();
+        assert_eq!(EzklError::from(boxed).to_string(), "something else");
+    }
+}
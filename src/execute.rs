@@ -1,71 +1,497 @@
-use crate::commands::{Cli, Commands, ProofSystem};
+use crate::circuit::eddsa::{random_scalar, sign};
+use crate::circuit::merkle::MerkleTree;
+use crate::circuit::poseidon::{digest_to_limbs, limbs_to_digest};
+use crate::commands::{
+    Cli, Commands, MultiOpenScheme, ProofFormat, ProofSystem, TableFormat, TranscriptType,
+};
 use crate::fieldutils::i32_to_felt;
-use crate::graph::Model;
+#[cfg(feature = "evm")]
+use crate::graph::input::save_csv;
+use crate::graph::Port;
+use crate::graph::{scale_to_multiplier, CompiledModel, GraphError, Model, ModelCircuit, RunArgs};
+use crate::memory::{self, PhaseMemory};
+use crate::pfsys::bench::{circuit_stats, BenchReport};
 #[cfg(feature = "evm")]
 use crate::pfsys::evm::aggregation::{
-    evm_verify, gen_aggregation_evm_verifier, gen_application_snark, gen_kzg_proof, gen_pk,
-    gen_srs, AggregationCircuit,
+    evm_verify, gen_aggregation_evm_verifier, gen_aggregation_evm_verifier_yul,
+    gen_application_snark, gen_kzg_proof, gen_pk, link_instances, AggregatedProof,
+    AggregationCircuit, AggregationError, Snark,
+};
+#[cfg(feature = "evm")]
+use crate::pfsys::evm::deploy::{deploy_verifier, verify_onchain};
+#[cfg(feature = "evm")]
+use crate::pfsys::evm::scaffold::scaffold_foundry;
+#[cfg(feature = "evm")]
+use crate::pfsys::evm::solidity::{
+    attested_ranges, data_attestation_inputs, gen_attestation_contract,
+    gen_data_attestation_contract, gen_solidity_verifier, CalldataFixture, DataCall,
 };
-use crate::pfsys::{create_keys, load_params, load_vk, Proof};
+#[cfg(feature = "evm")]
+use crate::pfsys::evm::split::VerifierCode;
+use crate::pfsys::srs::{load_srs, load_trimmed_params, read_ptau, verify_srs};
 use crate::pfsys::{
-    create_proof_model, prepare_circuit_and_public_input, prepare_data, save_params, save_vk,
-    verify_proof_model,
+    circuit_fingerprint, create_keys, input_leaves, input_message, load_params, load_pk, load_vk,
+    ArtifactHashes, InputMerklePath, InputSignature, ModelInput, ModelWitness, PoseidonTranscript,
+    Proof,
 };
-use halo2_proofs::dev::VerifyFailure;
+use crate::pfsys::{
+    create_proof_model, prepare_circuit, prepare_circuit_and_public_input, prepare_data,
+    prepare_dummy_circuit, save_params, save_pk, save_vk, verify_batch, verify_proof_model,
+};
+use crate::progress::with_spinner;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::serve;
+use crate::tensor::Tensor;
+#[cfg(feature = "dev-graph")]
+use halo2_proofs::dev::CircuitLayout;
+use halo2_proofs::plonk::{ConstraintSystem, ProvingKey, VerifyingKey};
 #[cfg(feature = "evm")]
 use halo2_proofs::poly::commitment::Params;
+use halo2_proofs::poly::commitment::{Prover, Verifier};
+use halo2_proofs::poly::ipa::{
+    commitment::{IPACommitmentScheme, ParamsIPA},
+    multiopen::{ProverIPA, VerifierIPA},
+    strategy::SingleStrategy as IPASingleStrategy,
+};
 use halo2_proofs::poly::kzg::commitment::KZGCommitmentScheme;
-use halo2_proofs::poly::kzg::multiopen::ProverGWC;
+use halo2_proofs::poly::kzg::multiopen::{ProverGWC, ProverSHPLONK, VerifierSHPLONK};
 use halo2_proofs::poly::kzg::{
-    commitment::ParamsKZG, multiopen::VerifierGWC, strategy::SingleStrategy as KZGSingleStrategy,
+    commitment::ParamsKZG,
+    multiopen::VerifierGWC,
+    strategy::{
+        AccumulatorStrategy as KZGAccumulatorStrategy, SingleStrategy as KZGSingleStrategy,
+    },
 };
+use halo2_proofs::poly::VerificationStrategy;
+use halo2_proofs::transcript::{Blake2bRead, Blake2bWrite, Challenge255};
 use halo2_proofs::{dev::MockProver, poly::commitment::ParamsProver};
+use halo2curves::bn256::{Bn256, Fr, G1Affine};
+use halo2curves::pasta::{EqAffine, Fp};
+use log::{error, info, trace, warn};
+#[cfg(feature = "dev-graph")]
+use plotters::prelude::{BitMapBackend, IntoDrawingArea, WHITE};
+use rand::rngs::{OsRng, StdRng};
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
 #[cfg(feature = "evm")]
-use halo2curves::bn256::G1Affine;
-use halo2curves::bn256::{Bn256, Fr};
-use log::{info, trace};
-#[cfg(feature = "evm")]
+use snark_verifier::loader::evm::encode_calldata;
+use snark_verifier::loader::native::NativeLoader;
 use snark_verifier::system::halo2::transcript::evm::EvmTranscript;
+use std::collections::BTreeMap;
 use std::error::Error;
 #[cfg(feature = "evm")]
+use std::path::PathBuf;
+use std::time::Duration;
+#[cfg(feature = "evm")]
 use std::time::Instant;
-use tabled::Table;
+use tabled::{Table, Tabled};
 use thiserror::Error;
 /// A wrapper for tensor related errors.
 #[derive(Debug, Error)]
 pub enum ExecutionError {
-    /// Shape mismatch in a operation
+    /// The mock prover found the circuit unsatisfied, with a diagnosis of each failure
     #[error("verification failed")]
-    VerifyError(Vec<VerifyFailure>),
+    VerifyError(Vec<String>),
+    /// The proof was rejected by the verifier
+    #[error("the proof was rejected")]
+    ProofRejected,
+    /// Some of the proofs verified as a batch (the count of which is given first) were rejected
+    #[error("{0} of the {1} proofs were rejected")]
+    ProofsRejected(usize, usize),
+    /// The directory of proofs to verify holds no proofs
+    #[error("no proofs were found in {0:?}")]
+    NoProofs(PathBuf),
+    /// The requested transcript is not supported by the proof system
+    #[error("{0} does not support the {1} transcript")]
+    UnsupportedTranscript(ProofSystem, TranscriptType),
+    /// The range inputs are generated in is empty, once clamped to the values representable by the model
+    #[error("no inputs can be generated in [{0}, {1}]")]
+    EmptyRange(f32, f32),
+    /// A command requiring a structured reference string was run without `--srs-path`
+    #[error("no structured reference string was provided, set --srs-path")]
+    MissingSrs,
+}
+
+/// Error statistics for a single output of a model, comparing the (dequantized) outputs of the quantized model
+/// to those of the original floating point model.
+#[derive(Debug, Tabled)]
+pub struct AccuracyReport {
+    /// Index of the model output
+    pub output: usize,
+    /// Fixed point scale of the output
+    pub scale: i32,
+    /// Mean absolute error
+    pub mean_abs_error: f32,
+    /// Maximum absolute error
+    pub max_abs_error: f32,
+    /// Root mean squared error
+    pub rmse: f32,
+}
+
+impl AccuracyReport {
+    /// Computes error statistics over a set of (float output, dequantized output) pairs.
+    pub fn new(output: usize, scale: i32, pairs: &[(f32, f32)]) -> Self {
+        let errors = pairs
+            .iter()
+            .map(|(f, q)| (f - q).abs())
+            .collect::<Vec<f32>>();
+        let n = errors.len().max(1) as f32;
+        AccuracyReport {
+            output,
+            scale,
+            mean_abs_error: errors.iter().sum::<f32>() / n,
+            max_abs_error: errors.iter().cloned().fold(0.0, f32::max),
+            rmse: (errors.iter().map(|e| e * e).sum::<f32>() / n).sqrt(),
+        }
+    }
+}
+
+/// The outcome of a command, printed as json when running with `--json`.
+#[derive(Debug, Serialize)]
+pub struct CommandReport {
+    /// The name of the command, e.g `prove`
+    pub command: String,
+    /// Whether the command succeeded (e.g for `verify`, whether the proof was accepted)
+    pub success: bool,
+    /// The time taken to run the command, in seconds
+    pub elapsed_secs: f64,
+    /// The error the command failed with, if any
+    pub error: Option<String>,
+    /// The diagnosis of each failure found by the mock prover, if any
+    pub failures: Vec<String>,
+    /// The peak memory of each phase of the command (e.g graph loading, keygen and proving), on platforms where it is tracked
+    pub phases: Vec<PhaseMemory>,
+}
+
+impl CommandReport {
+    /// Reports the result `res` of running `command` in `elapsed` time.
+    pub fn new(command: &Commands, elapsed: Duration, res: &Result<(), Box<dyn Error>>) -> Self {
+        // the (PascalCase) variant name, as encoded by serde, converted to the kebab-case name of the subcommand
+        let variant = match serde_json::to_value(command) {
+            Ok(serde_json::Value::Object(map)) => map.keys().next().cloned().unwrap_or_default(),
+            Ok(serde_json::Value::String(name)) => name,
+            _ => String::new(),
+        };
+        let command = variant
+            .chars()
+            .enumerate()
+            .fold(String::new(), |mut name, (i, c)| {
+                if c.is_uppercase() && i > 0 {
+                    name.push('-');
+                }
+                name.push(c.to_ascii_lowercase());
+                name
+            });
+        let failures = match res {
+            Err(e) => match e.downcast_ref::<ExecutionError>() {
+                Some(ExecutionError::VerifyError(diagnoses)) => diagnoses.clone(),
+                _ => vec![],
+            },
+            Ok(_) => vec![],
+        };
+        CommandReport {
+            command,
+            success: res.is_ok(),
+            elapsed_secs: elapsed.as_secs_f64(),
+            error: res.as_ref().err().map(|e| e.to_string()),
+            failures,
+            phases: memory::take_phases(),
+        }
+    }
 }
 
 /// Run an ezkl command with given args
 pub fn run(args: Cli) -> Result<(), Box<dyn Error>> {
+    // circuits configured by commands running on a single model are those of the model
+    match args.run_args() {
+        Ok(run_args) => run_args.scoped(|| run_command(args)),
+        Err(_) => run_command(args),
+    }
+}
+
+fn run_command(args: Cli) -> Result<(), Box<dyn Error>> {
     match args.command {
-        Commands::Table { model: _ } => {
+        Commands::Table { model: _, format } => {
             let om = Model::from_ezkl_conf(args)?;
-            println!("{}", Table::new(om.nodes.flatten()));
+            match format {
+                TableFormat::Table => println!("{}", Table::new(om.nodes.flatten())),
+                TableFormat::Json => println!("{}", om.nodes.to_json()?),
+                TableFormat::Dot => print!("{}", om.nodes.to_dot()),
+                TableFormat::Mermaid => print!("{}", om.nodes.to_mermaid()),
+            }
+        }
+        Commands::Accuracy { ref data, model: _ } => {
+            let model = Model::from_ezkl_conf(args.clone())?;
+            let in_scales = model.get_input_scales();
+            let out_scales = model.get_output_scales();
+            let mut pairs: Vec<Vec<(f32, f32)>> = vec![vec![]; model.num_outputs()];
+            for d in data {
+                let data = prepare_data(d.to_string())?
+                    .order_inputs(&model.input_names(), &model.unused_inputs)?;
+                let float_outputs = model.float_forward(&data.input_data, &data.input_shapes)?;
+                let inputs = prepare_circuit::<Fr>(&data, &in_scales)?.inputs;
+                let outputs = model.forward(&inputs)?;
+                for (i, (f, q)) in float_outputs.iter().zip(outputs).enumerate() {
+                    let mult = scale_to_multiplier(out_scales[i]);
+                    pairs[i].extend(f.iter().zip(q.iter()).map(|(f, q)| (*f, *q as f32 / mult)));
+                }
+            }
+            let reports = pairs
+                .iter()
+                .enumerate()
+                .map(|(i, p)| AccuracyReport::new(i, out_scales[i], p))
+                .collect::<Vec<_>>();
+            println!("{}", Table::new(reports));
+        }
+        Commands::Forward {
+            ref data,
+            model: _,
+            ref output,
+            ref witness_path,
+            ref next_data,
+        } => {
+            let model = Model::from_ezkl_conf(args.clone())?;
+            let mut data = prepare_data(data.to_string())?
+                .order_inputs(&model.input_names(), &model.unused_inputs)?;
+            let inputs = prepare_circuit::<Fr>(&data, &model.get_input_scales())?.inputs;
+            let results = model.forward_all(&inputs)?;
+
+            data.output_data = model
+                .outputs
+                .iter()
+                .zip(model.get_output_scales())
+                .map(|(o, scale)| {
+                    let mult = scale_to_multiplier(scale);
+                    results[&o.node].iter().map(|x| *x as f32 / mult).collect()
+                })
+                .collect();
+            info!("saving forward pass outputs to {:?}", output);
+            match output.extension().map_or(false, |e| e == "csv") {
+                true => save_csv(
+                    output,
+                    &model.output_names(),
+                    &data.output_data,
+                    model.batch_size,
+                )?,
+                false => data.save(output)?,
+            }
+
+            if let Some(path) = witness_path {
+                info!("saving intermediate witnesses to {:?}", path);
+                ModelWitness::new(&model, &results).save(path)?;
+            }
+
+            if let Some(path) = next_data {
+                let next = ModelInput {
+                    input_data: data.output_data.clone(),
+                    input_shapes: model
+                        .output_shapes()
+                        .into_iter()
+                        .map(|shape| match model.batch_size {
+                            1 => shape,
+                            batch_size => [vec![batch_size], shape].concat(),
+                        })
+                        .collect(),
+                    input_names: vec![],
+                    output_data: vec![],
+                    input_key: None,
+                    input_signature: None,
+                    input_merkle_paths: vec![],
+                };
+                info!("saving the inputs of the next model to {:?}", path);
+                next.save(path)?;
+            }
+        }
+        Commands::GenInput {
+            model: _,
+            ref output,
+            seed,
+            min,
+            max,
+        } => {
+            let model = Model::from_ezkl_conf(args.clone())?;
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut data = ModelInput {
+                input_data: vec![],
+                input_shapes: vec![],
+                input_names: vec![],
+                output_data: vec![],
+                input_key: None,
+                input_signature: None,
+                input_merkle_paths: vec![],
+            };
+            for (shape, scale) in model
+                .input_shapes()
+                .into_iter()
+                .zip(model.get_input_scales())
+            {
+                // inputs are clamped such that once quantized they fit in the range of the lookups
+                let bound = ((1i64 << (args.bits - 1)) - 1) as f32 / scale_to_multiplier(scale);
+                let (lo, hi) = (min.max(-bound), max.min(bound));
+                if lo > hi {
+                    return Err(Box::new(ExecutionError::EmptyRange(lo, hi)));
+                }
+                let len = shape.iter().product::<usize>() * model.batch_size;
+                data.input_data
+                    .push((0..len).map(|_| rng.gen_range(lo..=hi)).collect());
+                data.input_shapes.push(match model.batch_size {
+                    1 => shape,
+                    batch_size => [vec![batch_size], shape].concat(),
+                });
+            }
+            let inputs = prepare_circuit::<Fr>(&data, &model.get_input_scales())?.inputs;
+            data.output_data = model
+                .forward(&inputs)?
+                .iter()
+                .zip(model.get_output_scales())
+                .map(|(o, scale)| {
+                    let mult = scale_to_multiplier(scale);
+                    o.iter().map(|x| *x as f32 / mult).collect()
+                })
+                .collect();
+            info!("saving random inputs (seed {}) to {:?}", seed, output);
+            data.save(output)?;
+        }
+        Commands::Compile {
+            model: _,
+            ref output,
+            include_tables,
+        } => {
+            // the model is always compiled from its model file
+            let run_args = RunArgs {
+                compiled: None,
+                ..args.run_args()?
+            };
+            CompiledModel::compile(&run_args, include_tables)?.save(output)?;
+        }
+        Commands::Sign {
+            ref data,
+            model: _,
+            ref key_path,
+            ref output,
+        } => {
+            let model = Model::from_ezkl_conf(args.clone())?;
+            let mut data = prepare_data(data.to_string())?
+                .order_inputs(&model.input_names(), &model.unused_inputs)?;
+            let secret_key = match key_path.exists() {
+                true => {
+                    let limbs: Vec<i32> =
+                        serde_json::from_str(&std::fs::read_to_string(key_path)?)?;
+                    limbs_to_digest::<Fr>(&limbs)
+                }
+                false => {
+                    info!("generating a new signing key at {:?}", key_path);
+                    let secret_key = random_scalar::<Fr>(OsRng);
+                    std::fs::write(
+                        key_path,
+                        serde_json::to_string(&digest_to_limbs(secret_key))?,
+                    )?;
+                    secret_key
+                }
+            };
+            let signature = sign(&secret_key, input_message::<Fr>(&data, &model)?, OsRng);
+            data.input_signature = Some(InputSignature::new(&signature));
+            info!(
+                "public key: {:?}",
+                data.input_signature.as_ref().map(|s| &s.public_key)
+            );
+            info!("saving signed data to {:?}", output);
+            data.save(output)?;
         }
-        Commands::Mock { ref data, model: _ } => {
+        Commands::MerklePath {
+            ref data,
+            model: _,
+            index,
+            ref output,
+        } => {
+            let model = Model::from_ezkl_conf(args.clone())?;
+            let depth = args.merkle_depth.ok_or(GraphError::InvalidMerklePath(0))?;
+            let dataset = data
+                .iter()
+                .map(|d| {
+                    Ok(prepare_data(d.to_string())?
+                        .order_inputs(&model.input_names(), &model.unused_inputs)?)
+                })
+                .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+            // each sample of each data file is a leaf, in order
+            let mut leaves = vec![];
+            let mut ranges = vec![];
+            for d in dataset.iter() {
+                let start = leaves.len();
+                leaves.extend(input_leaves::<Fr>(d, &model)?);
+                ranges.push(start..leaves.len());
+            }
+            let tree =
+                MerkleTree::new(&leaves, depth).ok_or(GraphError::InvalidMerklePath(depth))?;
+            let (mut record, range) = dataset
+                .into_iter()
+                .zip(ranges)
+                .nth(index)
+                .ok_or(GraphError::InvalidMerklePath(depth))?;
+            record.input_merkle_paths = range
+                .map(|leaf| InputMerklePath::new(&tree.path(leaf)))
+                .collect();
+            println!("{:?}", digest_to_limbs(tree.root()));
+            info!(
+                "saving data file {} with its merkle paths to {:?}",
+                index, output
+            );
+            record.save(output)?;
+        }
+        Commands::Mock {
+            ref data,
+            model: _,
+            ref witness_path,
+        } => {
             let data = prepare_data(data.to_string())?;
-            let (circuit, public_inputs) = prepare_circuit_and_public_input(&data, &args)?;
+            let (circuit, public_inputs) = with_spinner(args.progress, "graph loading", || {
+                prepare_circuit_and_public_input(&data, &args.run_args()?)
+            })?;
+            // saved before proving, such that the witness of an unsatisfied circuit can be inspected
+            if let Some(path) = witness_path {
+                let model = Model::from_ezkl_conf(args.clone())?;
+                let witnesses = circuit
+                    .inputs
+                    .chunks(model.num_inputs())
+                    .map(|inputs| Ok(ModelWitness::new(&model, &model.forward_all(inputs)?)))
+                    .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+                info!("saving intermediate witnesses to {:?}", path);
+                match witnesses.as_slice() {
+                    [witness] => witness.save(path)?,
+                    _ => std::fs::write(path, serde_json::to_string(&witnesses)?)?,
+                }
+            }
             info!("Mock proof");
             let pi: Vec<Vec<Fr>> = public_inputs
                 .into_iter()
                 .map(|i| i.into_iter().map(i32_to_felt::<Fr>).collect())
                 .collect();
 
-            let prover =
-                MockProver::run(args.logrows, &circuit, pi).map_err(Box::<dyn Error>::from)?;
-            prover
-                .verify()
-                .map_err(|e| Box::<dyn Error>::from(ExecutionError::VerifyError(e)))?;
+            let prover = with_spinner(args.progress, "mock proving", || {
+                MockProver::run(args.logrows, &circuit, pi)
+            })
+            .map_err(Box::<dyn Error>::from)?;
+            if let Err(failures) = prover.verify() {
+                // trace each failure back to the node of the graph it originates from
+                let mut cs = ConstraintSystem::<Fr>::default();
+                let config = ModelCircuit::<Fr>::configure_with_args(&mut cs, &args.run_args()?)?;
+                let diagnoses = failures
+                    .iter()
+                    .map(|failure| config.model.diagnose(&config, failure))
+                    .collect::<Vec<_>>();
+                for diagnosis in diagnoses.iter() {
+                    error!("{}", diagnosis);
+                }
+                return Err(Box::new(ExecutionError::VerifyError(diagnoses)));
+            }
         }
 
         Commands::Fullprove {
             ref data,
             model: _,
             pfsys,
+            multiopen,
+            transcript,
         } => {
             // A direct proof
 
@@ -73,56 +499,123 @@ pub fn run(args: Cli) -> Result<(), Box<dyn Error>> {
 
             match pfsys {
                 ProofSystem::IPA => {
-                    unimplemented!()
+                    if transcript != TranscriptType::Blake2b {
+                        return Err(Box::new(ExecutionError::UnsupportedTranscript(
+                            pfsys, transcript,
+                        )));
+                    }
+                    // A direct proof
+                    let (circuit, public_inputs) =
+                        prepare_circuit_and_public_input::<Fp>(&data, &args.run_args()?)?;
+                    let params: ParamsIPA<EqAffine> = ParamsIPA::new(args.logrows);
+                    let pk = create_keys::<IPACommitmentScheme<_>, Fp>(&circuit, &params)
+                        .map_err(Box::<dyn Error>::from)?;
+                    let strategy = IPASingleStrategy::new(&params);
+                    trace!("params computed");
+
+                    let (proof, _dims) =
+                        create_proof_model::<
+                            IPACommitmentScheme<_>,
+                            Fp,
+                            ProverIPA<_>,
+                            _,
+                            Blake2bWrite<_, _, Challenge255<_>>,
+                        >(&circuit, &public_inputs, &params, &pk)
+                        .map_err(Box::<dyn Error>::from)?;
+
+                    verify_proof_model::<
+                        _,
+                        VerifierIPA<'_, EqAffine>,
+                        _,
+                        _,
+                        _,
+                        Blake2bRead<_, _, Challenge255<_>>,
+                    >(proof, &params, pk.get_vk(), strategy)?;
                 }
                 #[cfg(not(feature = "evm"))]
                 ProofSystem::KZG => {
                     // A direct proof
                     let (circuit, public_inputs) =
-                        prepare_circuit_and_public_input::<Fr>(&data, &args)?;
-                    let params: ParamsKZG<Bn256> = ParamsKZG::new(args.logrows);
+                        prepare_circuit_and_public_input::<Fr>(&data, &args.run_args()?)?;
+                    let params = kzg_params(&args, args.logrows)?;
                     let pk = create_keys::<KZGCommitmentScheme<_>, Fr>(&circuit, &params)
                         .map_err(Box::<dyn Error>::from)?;
-                    let strategy = KZGSingleStrategy::new(&params);
                     trace!("params computed");
 
-                    let (proof, _dims) = create_proof_model::<
-                        KZGCommitmentScheme<_>,
-                        Fr,
-                        ProverGWC<_>,
-                    >(
-                        &circuit, &public_inputs, &params, &pk
-                    )
-                    .map_err(Box::<dyn Error>::from)?;
-
-                    verify_proof_model::<_, VerifierGWC<'_, Bn256>, _, _>(
-                        proof,
-                        &params,
-                        pk.get_vk(),
-                        strategy,
-                    )?;
+                    info!(
+                        "proof with {} using {} and a {} transcript",
+                        pfsys, multiopen, transcript
+                    );
+                    match multiopen {
+                        MultiOpenScheme::GWC => {
+                            let proof = create_kzg_proof::<ProverGWC<_>>(
+                                &circuit,
+                                &public_inputs,
+                                &params,
+                                &pk,
+                                transcript,
+                            )?;
+                            verify_kzg_proof::<VerifierGWC<_>>(
+                                proof,
+                                &params,
+                                pk.get_vk(),
+                                transcript,
+                            )?;
+                        }
+                        MultiOpenScheme::SHPLONK => {
+                            let proof = create_kzg_proof::<ProverSHPLONK<_>>(
+                                &circuit,
+                                &public_inputs,
+                                &params,
+                                &pk,
+                                transcript,
+                            )?;
+                            verify_kzg_proof::<VerifierSHPLONK<_>>(
+                                proof,
+                                &params,
+                                pk.get_vk(),
+                                transcript,
+                            )?;
+                        }
+                    }
                 }
                 #[cfg(feature = "evm")]
                 ProofSystem::KZG => {
+                    // the aggregation circuit accumulates GWC openings of the application proofs
+                    if multiopen != MultiOpenScheme::GWC {
+                        return Err(Box::new(AggregationError::UnsupportedMultiOpen(multiopen)));
+                    }
+                    // application proofs always use a poseidon transcript (so they can be verified in the aggregation circuit)
+                    // and the aggregated proof always uses a keccak transcript (so it can be verified on the EVM).
+                    if transcript != TranscriptType::Blake2b {
+                        warn!(
+                            "ignoring {} transcript, aggregated proofs use poseidon and keccak transcripts",
+                            transcript
+                        );
+                    }
                     // We will need aggregator k > application k > bits
                     //		    let application_logrows = args.logrows; //bits + 1;
                     let aggregation_logrows = args.logrows + 6;
 
-                    let params = gen_srs(aggregation_logrows);
+                    let params = kzg_params(&args, aggregation_logrows)?;
                     let params_app = {
                         let mut params = params.clone();
                         params.downsize(args.logrows);
                         params
                     };
                     let now = Instant::now();
-                    let snarks = [gen_application_snark(&params_app, &data, &args)?];
+                    let snarks = [gen_application_snark(
+                        &params_app,
+                        &data,
+                        &args.run_args()?,
+                    )?];
                     info!("Application proof took {}", now.elapsed().as_secs());
                     let agg_circuit = AggregationCircuit::new(&params, snarks)?;
                     let pk = gen_pk(&params, &agg_circuit)?;
-                    let deployment_code = gen_aggregation_evm_verifier(
+                    let verifier = gen_aggregation_evm_verifier(
                         &params,
                         pk.get_vk(),
-                        AggregationCircuit::num_instance(),
+                        agg_circuit.num_instance(),
                         AggregationCircuit::accumulator_indices(),
                     )?;
                     let now = Instant::now();
@@ -136,74 +629,921 @@ pub fn run(args: Cli) -> Result<(), Box<dyn Error>> {
                     )?;
                     info!("Aggregation proof took {}", now.elapsed().as_secs());
                     let now = Instant::now();
-                    evm_verify(deployment_code, agg_circuit.instances(), proof)?;
+                    evm_verify(&verifier, agg_circuit.instances(), proof)?;
                     info!("verify took {}", now.elapsed().as_secs());
                 }
             }
         }
+        Commands::ConvertPtau {
+            ref ptau_path,
+            ref params_path,
+        } => {
+            let params = read_ptau(ptau_path, args.logrows)?;
+            info!("verifying SRS");
+            verify_srs(&params)?;
+            save_params::<KZGCommitmentScheme<Bn256>>(params_path, &params)?;
+        }
+        Commands::TrimSrs { ref params_path } => {
+            let srs_path = args.srs_path.as_ref().ok_or(ExecutionError::MissingSrs)?;
+            let params = load_srs(srs_path, args.logrows)?;
+            save_params::<KZGCommitmentScheme<Bn256>>(params_path, &params)?;
+        }
+        Commands::GenSettings {
+            ref settings_path,
+            ref model,
+        } => {
+            let mut settings = args.settings();
+            if model.is_some() {
+                settings.output_scales = Model::from_ezkl_conf(args.clone())?.get_output_scales();
+            }
+            settings.save(settings_path)?;
+        }
+        Commands::GenSrs { ref params_path } => {
+            warn!("generating an unsafe structured reference string, which should not be used in production");
+            let params = ParamsKZG::<Bn256>::new(args.logrows);
+            save_params::<KZGCommitmentScheme<Bn256>>(params_path, &params)?;
+        }
+        Commands::Setup {
+            model: _,
+            ref pk_path,
+            ref vk_path,
+            ref params_path,
+            pfsys,
+        } => {
+            let fingerprint = circuit_fingerprint(&args.run_args()?)?;
+            let om = with_spinner(args.progress, "graph loading", || {
+                Model::from_ezkl_conf(args.clone())
+            })?;
+            info!("setup with {}", pfsys);
+            match pfsys {
+                ProofSystem::IPA => {
+                    let circuit = prepare_dummy_circuit::<Fp>(&om)?;
+                    let params: ParamsIPA<EqAffine> = ParamsIPA::new(args.logrows);
+                    let pk = with_spinner(args.progress, "keygen", || {
+                        create_keys::<IPACommitmentScheme<EqAffine>, Fp>(&circuit, &params)
+                    })
+                    .map_err(Box::<dyn Error>::from)?;
+                    save_params::<IPACommitmentScheme<EqAffine>>(params_path, &params)?;
+                    save_pk::<IPACommitmentScheme<EqAffine>>(pk_path, &pk, fingerprint)?;
+                    save_vk::<IPACommitmentScheme<EqAffine>>(vk_path, pk.get_vk(), fingerprint)?;
+                }
+                ProofSystem::KZG => {
+                    let circuit = prepare_dummy_circuit::<Fr>(&om)?;
+                    let params = kzg_params(&args, args.logrows)?;
+                    let pk = with_spinner(args.progress, "keygen", || {
+                        create_keys::<KZGCommitmentScheme<Bn256>, Fr>(&circuit, &params)
+                    })
+                    .map_err(Box::<dyn Error>::from)?;
+                    save_params::<KZGCommitmentScheme<Bn256>>(params_path, &params)?;
+                    save_pk::<KZGCommitmentScheme<Bn256>>(pk_path, &pk, fingerprint)?;
+                    save_vk::<KZGCommitmentScheme<Bn256>>(vk_path, pk.get_vk(), fingerprint)?;
+                }
+            };
+        }
         Commands::Prove {
             ref data,
             model: _,
             ref proof_path,
+            proof_format,
             ref vk_path,
             ref params_path,
+            ref pk_path,
             pfsys,
+            multiopen,
+            transcript,
+            target,
         } => {
             let data = prepare_data(data.to_string())?;
+            let fingerprint = circuit_fingerprint(&args.run_args()?)?;
 
             match pfsys {
                 ProofSystem::IPA => {
-                    unimplemented!()
-                }
-                ProofSystem::KZG => {
+                    if transcript != TranscriptType::Blake2b {
+                        return Err(Box::new(ExecutionError::UnsupportedTranscript(
+                            pfsys, transcript,
+                        )));
+                    }
                     info!("proof with {}", pfsys);
-                    let (circuit, public_inputs) = prepare_circuit_and_public_input(&data, &args)?;
-                    let params: ParamsKZG<Bn256> = ParamsKZG::new(args.logrows);
-                    let pk = create_keys::<KZGCommitmentScheme<Bn256>, Fr>(&circuit, &params)
-                        .map_err(Box::<dyn Error>::from)?;
+                    let (circuit, public_inputs) =
+                        with_spinner(args.progress, "graph loading", || {
+                            prepare_circuit_and_public_input(&data, &args.run_args()?)
+                        })?;
+                    let (params, pk) = match pk_path {
+                        Some(pk_path) => {
+                            let params: ParamsIPA<EqAffine> =
+                                load_params::<IPACommitmentScheme<EqAffine>>(params_path.clone())?;
+                            let pk = load_pk::<IPACommitmentScheme<EqAffine>, Fp>(
+                                pk_path.clone(),
+                                fingerprint,
+                            )?;
+                            (params, pk)
+                        }
+                        None => {
+                            let params: ParamsIPA<EqAffine> = ParamsIPA::new(args.logrows);
+                            let pk = with_spinner(args.progress, "keygen", || {
+                                create_keys::<IPACommitmentScheme<EqAffine>, Fp>(&circuit, &params)
+                            })
+                            .map_err(Box::<dyn Error>::from)?;
+                            (params, pk)
+                        }
+                    };
                     trace!("params computed");
 
-                    let (proof, _input_dims) = create_proof_model::<
-                        KZGCommitmentScheme<Bn256>,
-                        Fr,
-                        ProverGWC<'_, Bn256>,
-                    >(
-                        &circuit, &public_inputs, &params, &pk
-                    )
+                    let (mut proof, _input_dims) = with_spinner(args.progress, "proving", || {
+                        create_proof_model::<
+                            IPACommitmentScheme<EqAffine>,
+                            Fp,
+                            ProverIPA<'_, EqAffine>,
+                            _,
+                            Blake2bWrite<_, _, Challenge255<_>>,
+                        >(&circuit, &public_inputs, &params, &pk)
+                    })
                     .map_err(Box::<dyn Error>::from)?;
 
-                    proof.save(proof_path)?;
-                    save_params::<KZGCommitmentScheme<Bn256>>(params_path, &params)?;
-                    save_vk::<KZGCommitmentScheme<Bn256>>(vk_path, pk.get_vk())?;
+                    proof.hashes = Some(ArtifactHashes::new::<IPACommitmentScheme<EqAffine>>(
+                        fingerprint,
+                        pk.get_vk(),
+                        &params,
+                    )?);
+                    proof.save::<Fp>(proof_path, proof_format)?;
+                    if pk_path.is_none() {
+                        save_params::<IPACommitmentScheme<EqAffine>>(params_path, &params)?;
+                        save_vk::<IPACommitmentScheme<EqAffine>>(
+                            vk_path,
+                            pk.get_vk(),
+                            fingerprint,
+                        )?;
+                    }
+                }
+                ProofSystem::KZG => {
+                    info!(
+                        "proof with {} using {} and a {} transcript",
+                        pfsys, multiopen, transcript
+                    );
+                    match target.transcript() {
+                        Some(expected) if expected != transcript => warn!(
+                            "a {} transcript is incompatible with the {} verifier, which only accepts proofs created with a {} transcript",
+                            transcript, target, expected
+                        ),
+                        _ => {}
+                    }
+                    let (circuit, public_inputs) =
+                        with_spinner(args.progress, "graph loading", || {
+                            prepare_circuit_and_public_input(&data, &args.run_args()?)
+                        })?;
+                    let (params, pk) = match pk_path {
+                        Some(pk_path) => {
+                            let params: ParamsKZG<Bn256> =
+                                load_trimmed_params(params_path, args.logrows)?;
+                            let pk = load_pk::<KZGCommitmentScheme<Bn256>, Fr>(
+                                pk_path.clone(),
+                                fingerprint,
+                            )?;
+                            (params, pk)
+                        }
+                        None => {
+                            let params = kzg_params(&args, args.logrows)?;
+                            let pk = with_spinner(args.progress, "keygen", || {
+                                create_keys::<KZGCommitmentScheme<Bn256>, Fr>(&circuit, &params)
+                            })
+                            .map_err(Box::<dyn Error>::from)?;
+                            (params, pk)
+                        }
+                    };
+                    trace!("params computed");
+
+                    let mut proof = with_spinner(args.progress, "proving", || match multiopen {
+                        MultiOpenScheme::GWC => create_kzg_proof::<ProverGWC<_>>(
+                            &circuit,
+                            &public_inputs,
+                            &params,
+                            &pk,
+                            transcript,
+                        ),
+                        MultiOpenScheme::SHPLONK => create_kzg_proof::<ProverSHPLONK<_>>(
+                            &circuit,
+                            &public_inputs,
+                            &params,
+                            &pk,
+                            transcript,
+                        ),
+                    })?;
+
+                    proof.hashes = Some(ArtifactHashes::new::<KZGCommitmentScheme<Bn256>>(
+                        fingerprint,
+                        pk.get_vk(),
+                        &params,
+                    )?);
+                    proof.save::<Fr>(proof_path, proof_format)?;
+                    if pk_path.is_none() {
+                        save_params::<KZGCommitmentScheme<Bn256>>(params_path, &params)?;
+                        save_vk::<KZGCommitmentScheme<Bn256>>(vk_path, pk.get_vk(), fingerprint)?;
+                    }
                 }
             };
         }
+        #[cfg(feature = "evm")]
+        Commands::Aggregate {
+            ref models,
+            ref proof_paths,
+            proof_format,
+            ref vk_paths,
+            ref aggregation_proof_path,
+            ref deployment_code_path,
+            aggregation_logrows,
+            chain,
+            gas_report,
+            ref yul_path,
+            ref sol_path,
+            ref solc_pragma,
+            ref attestation_path,
+            ref data_calls_path,
+            ref data_attestation_path,
+            ref scaffold,
+        } => {
+            // each proof is of a (chunk of a) model, and its verifying key is configured using said model
+            let chunked = !args.cut_points.is_empty();
+            let model_args: Vec<(&PathBuf, Cli)> = if chunked {
+                let num_chunks = args.cut_points.len() + 1;
+                if models.len() != 1
+                    || proof_paths.len() != num_chunks
+                    || vk_paths.len() != num_chunks
+                {
+                    return Err(Box::new(AggregationError::ChunkArtifacts(num_chunks)));
+                }
+                (0..num_chunks)
+                    .map(|chunk| {
+                        let model_args = Cli {
+                            command: Commands::Table {
+                                model: models[0].to_string_lossy().to_string(),
+                                format: TableFormat::Table,
+                            },
+                            chunk: Some(chunk),
+                            ..args.clone()
+                        };
+                        (&models[0], model_args)
+                    })
+                    .collect()
+            } else {
+                if models.len() != proof_paths.len() || models.len() != vk_paths.len() {
+                    return Err(Box::new(AggregationError::SnarkArtifacts(
+                        models.len(),
+                        proof_paths.len(),
+                        vk_paths.len(),
+                    )));
+                }
+                models
+                    .iter()
+                    .map(|model| {
+                        let model_args = Cli {
+                            command: Commands::Table {
+                                model: model.to_string_lossy().to_string(),
+                                format: TableFormat::Table,
+                            },
+                            chunk: None,
+                            ..args.clone()
+                        };
+                        (model, model_args)
+                    })
+                    .collect()
+            };
+            // the accumulated openings are checked against the aggregation params, so the application proofs must share their SRS
+            let srs_path = args.srs_path.as_ref().ok_or(ExecutionError::MissingSrs)?;
+            let params = load_srs(srs_path, aggregation_logrows)?;
+            let params_app = {
+                let mut params = params.clone();
+                params.downsize(args.logrows);
+                params
+            };
+
+            let mut snarks = vec![];
+            let mut instance_nodes = vec![];
+            let mut instance_ports = vec![];
+            let mut loaded_models = vec![];
+            for (((model, model_args), proof_path), vk_path) in
+                model_args.iter().zip(proof_paths).zip(vk_paths)
+            {
+                info!("loading proof {:?} of model {:?}", proof_path, model);
+                let run_args = model_args.run_args()?;
+                let fingerprint = circuit_fingerprint(&run_args)?;
+                let om = Model::from_run_args(&run_args)?;
+                let num_instances: Vec<usize> = match proof_format {
+                    ProofFormat::Hex => om
+                        .instance_shapes()
+                        .iter()
+                        .map(|s| s.iter().product())
+                        .collect(),
+                    _ => vec![],
+                };
+                instance_nodes.push(om.instance_nodes());
+                instance_ports.push((om.num_inputs(), om.num_outputs(), om.instance_ports()));
+                let proof = Proof::load::<Fr>(proof_path, proof_format, &num_instances)?;
+                let vk = run_args.scoped(|| {
+                    load_vk::<KZGCommitmentScheme<Bn256>, Fr>(vk_path.clone(), fingerprint)
+                })?;
+                proof.check_hashes(&ArtifactHashes::new::<KZGCommitmentScheme<Bn256>>(
+                    fingerprint,
+                    &vk,
+                    &params_app,
+                )?)?;
+                snarks.push(Snark::from_proof(&params_app, &vk, proof));
+                loaded_models.push(om);
+            }
+            // activations passed between chunks are held by instances of each chunk's proof, tagged by the node they are the output of
+            let links = if chunked {
+                link_instances(&instance_nodes)
+            } else if chain {
+                link_instances(&chain_tags(&instance_ports, &args)?)
+            } else {
+                vec![]
+            };
+
+            info!("aggregating {} proofs", snarks.len());
+            let agg_circuit = AggregationCircuit::new_linked(&params, snarks, links)?;
+            let pk = gen_pk(&params, &agg_circuit)?;
+            let yul_code = gen_aggregation_evm_verifier_yul(
+                &params,
+                pk.get_vk(),
+                agg_circuit.num_instance(),
+                AggregationCircuit::accumulator_indices(),
+            )?;
+            let verifier = VerifierCode::new(&yul_code)?;
+            if verifier.is_split() {
+                info!(
+                    "verifier exceeds the contract size limit, split into {} contracts and a dispatcher",
+                    verifier.chunks.len()
+                );
+            }
+            if let Some(path) = yul_path {
+                info!("saving verifier yul to {:?}", path);
+                std::fs::write(path, &yul_code)?;
+            }
+            if let Some(path) = sol_path {
+                info!("saving solidity verifier to {:?}", path);
+                let num_instances = agg_circuit.num_instance().iter().sum();
+                std::fs::write(path, gen_solidity_verifier(num_instances, solc_pragma))?;
+            }
+            // the verifier is imported relative to the contracts wrapping it, which are expected to be saved alongside it
+            let verifier_import = sol_path.as_ref().map(|path| {
+                format!(
+                    "./{}",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                )
+            });
+            if let (Some(path), Some(verifier_import)) = (attestation_path, &verifier_import) {
+                info!("saving attestation contract to {:?}", path);
+                let contract = gen_attestation_contract(
+                    verifier_import,
+                    AggregationCircuit::accumulator_indices().len(),
+                    loaded_models.len(),
+                    &attested_ranges(&loaded_models),
+                    solc_pragma,
+                );
+                std::fs::write(path, contract)?;
+            }
+            if let (Some(path), Some(calls_path), Some(verifier_import)) =
+                (data_attestation_path, data_calls_path, &verifier_import)
+            {
+                let accumulator_limbs = AggregationCircuit::accumulator_indices().len();
+                let inputs = data_attestation_inputs(
+                    &loaded_models[0],
+                    accumulator_limbs,
+                    &attested_ranges(&loaded_models),
+                );
+                let calls: Vec<DataCall> =
+                    serde_json::from_str(&std::fs::read_to_string(calls_path)?)?;
+                let model = &loaded_models[0];
+                let public_inputs =
+                    (0..model.num_inputs()).all(|i| model.visibility.input_at(i).is_public());
+                if !public_inputs || calls.len() != inputs.len() {
+                    return Err(Box::new(AggregationError::InvalidDataCalls(inputs.len())));
+                }
+                info!("saving data attestation contract to {:?}", path);
+                let calls = calls.into_iter().zip(inputs).collect::<Vec<_>>();
+                std::fs::write(
+                    path,
+                    gen_data_attestation_contract(verifier_import, &calls, solc_pragma)?,
+                )?;
+            }
+            let now = Instant::now();
+            let proof = gen_kzg_proof::<
+                _,
+                _,
+                EvmTranscript<G1Affine, _, _, _>,
+                EvmTranscript<G1Affine, _, _, _>,
+            >(&params, &pk, agg_circuit.clone(), agg_circuit.instances())?;
+            info!("Aggregation proof took {}", now.elapsed().as_secs());
+            let (accepted, gas) = evm_verify(&verifier, agg_circuit.instances(), proof.clone())?;
+            if !accepted {
+                return Err(Box::new(AggregationError::ProofVerify));
+            }
+            if gas_report {
+                println!("{}", serde_json::to_string_pretty(&gas)?);
+            }
+
+            if let Some(dir) = scaffold {
+                info!("writing foundry project to {:?}", dir);
+                let num_instances = agg_circuit.num_instance().iter().sum();
+                scaffold_foundry(
+                    dir,
+                    &gen_solidity_verifier(num_instances, solc_pragma),
+                    &verifier,
+                    &CalldataFixture::new(&agg_circuit.instances(), &proof),
+                    solc_pragma,
+                )?;
+            }
+
+            AggregatedProof::new(&agg_circuit.instances(), proof).save(aggregation_proof_path)?;
+            info!(
+                "saving verifier deployment code to {:?}",
+                deployment_code_path
+            );
+            std::fs::write(deployment_code_path, verifier.to_hex())?;
+        }
+        #[cfg(feature = "evm")]
+        Commands::EncodeEvmCalldata {
+            ref aggregation_proof_path,
+            ref calldata_path,
+            ref fixture_path,
+        } => {
+            let proof = AggregatedProof::load(aggregation_proof_path)?;
+            let fixture = CalldataFixture::new(&proof.instances()?, &proof.proof);
+            info!("saving calldata to {:?}", calldata_path);
+            std::fs::write(calldata_path, &fixture.calldata)?;
+            if let Some(path) = fixture_path {
+                info!("saving calldata fixture to {:?}", path);
+                std::fs::write(path, serde_json::to_string_pretty(&fixture)?)?;
+            }
+        }
+        #[cfg(feature = "evm")]
+        Commands::DeployVerifier {
+            ref deployment_code_path,
+            ref rpc_url,
+            ref private_key_path,
+        } => {
+            let verifier = VerifierCode::from_hex(&std::fs::read_to_string(deployment_code_path)?)?;
+            let private_key = std::fs::read_to_string(private_key_path)?;
+            // the chunks of a split verifier are deployed first, the dispatcher being constructed with their addresses
+            let address = verifier.deploy(|code| deploy_verifier(rpc_url, &private_key, code))?;
+            info!("verifier deployed at {:?}", address);
+            println!("{:?}", address);
+        }
+        #[cfg(feature = "evm")]
+        Commands::VerifyOnchain {
+            ref aggregation_proof_path,
+            ref addr,
+            ref rpc_url,
+        } => {
+            let proof = AggregatedProof::load(aggregation_proof_path)?;
+            let calldata = encode_calldata(&proof.instances()?, &proof.proof);
+            let result = verify_onchain(rpc_url, addr.parse()?, calldata)?;
+            info!("verified: {}", result);
+            assert!(result);
+        }
         Commands::Verify {
             model: _,
-            proof_path,
-            vk_path,
-            params_path,
+            ref proof_path,
+            ref proofs_dir,
+            proof_format,
+            ref vk_path,
+            ref params_path,
             pfsys,
+            multiopen,
+            transcript,
+            ref outputs_path,
         } => {
-            let proof = Proof::load(&proof_path)?;
-            match pfsys {
+            let fingerprint = circuit_fingerprint(&args.run_args()?)?;
+            // the model recovers how hex calldata splits the public inputs, and which of them are outputs
+            let model = Model::from_ezkl_conf(args.clone())?;
+            let num_instances: Vec<usize> = match proof_format {
+                ProofFormat::Hex => model
+                    .instance_shapes()
+                    .iter()
+                    .map(|s| s.iter().product())
+                    .collect(),
+                _ => vec![],
+            };
+            let paths = match (proof_path, proofs_dir) {
+                (_, Some(dir)) => proofs_in(dir)?,
+                (Some(path), None) => vec![path.clone()],
+                (None, None) => unreachable!("clap requires --proof-path or --proofs-dir"),
+            };
+            // the public outputs of each proof, dequantized, which are only reported for the proofs accepted. A proof
+            // whose public inputs can't be read back as outputs is logged and left out of the report
+            let mut outputs = vec![];
+            let public_outputs =
+                |path: &PathBuf, proof: &Proof| match model.public_outputs(&proof.public_inputs) {
+                    Ok(outputs) => Some(outputs),
+                    Err(e) => {
+                        error!("could not read the public outputs of {:?}: {}", path, e);
+                        None
+                    }
+                };
+            let results = match pfsys {
                 ProofSystem::IPA => {
-                    unimplemented!()
+                    if transcript != TranscriptType::Blake2b {
+                        return Err(Box::new(ExecutionError::UnsupportedTranscript(
+                            pfsys, transcript,
+                        )));
+                    }
+                    let params: ParamsIPA<EqAffine> =
+                        load_params::<IPACommitmentScheme<EqAffine>>(params_path.clone())?;
+                    let vk =
+                        load_vk::<IPACommitmentScheme<EqAffine>, Fp>(vk_path.clone(), fingerprint)?;
+                    let hashes = ArtifactHashes::new::<IPACommitmentScheme<EqAffine>>(
+                        fingerprint,
+                        &vk,
+                        &params,
+                    )?;
+                    let mut results = vec![];
+                    for path in &paths {
+                        let proof = Proof::load::<Fp>(path, proof_format, &num_instances)?;
+                        proof.check_hashes(&hashes)?;
+                        outputs.push(public_outputs(path, &proof));
+                        let strategy = IPASingleStrategy::new(&params);
+                        results.push(
+                            verify_proof_model::<
+                                _,
+                                VerifierIPA<'_, EqAffine>,
+                                _,
+                                _,
+                                _,
+                                Blake2bRead<_, _, Challenge255<_>>,
+                            >(proof, &params, &vk, strategy)
+                            .is_ok(),
+                        );
+                    }
+                    results
                 }
                 ProofSystem::KZG => {
-                    let params: ParamsKZG<Bn256> =
-                        load_params::<KZGCommitmentScheme<Bn256>>(params_path)?;
-                    let strategy = KZGSingleStrategy::new(&params);
-                    let vk = load_vk::<KZGCommitmentScheme<Bn256>, Fr>(vk_path)?;
-                    let result = verify_proof_model::<_, VerifierGWC<'_, Bn256>, _, _>(
-                        proof, &params, &vk, strategy,
-                    )
-                    .is_ok();
-                    info!("verified: {}", result);
-                    assert!(result);
+                    let params: ParamsKZG<Bn256> = load_trimmed_params(params_path, args.logrows)?;
+                    let vk =
+                        load_vk::<KZGCommitmentScheme<Bn256>, Fr>(vk_path.clone(), fingerprint)?;
+                    let hashes = ArtifactHashes::new::<KZGCommitmentScheme<Bn256>>(
+                        fingerprint,
+                        &vk,
+                        &params,
+                    )?;
+                    let proofs = paths
+                        .iter()
+                        .map(|path| {
+                            let proof = Proof::load::<Fr>(path, proof_format, &num_instances)?;
+                            proof.check_hashes(&hashes)?;
+                            Ok(proof)
+                        })
+                        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+                    for (path, proof) in paths.iter().zip(&proofs) {
+                        outputs.push(public_outputs(path, proof));
+                    }
+                    match multiopen {
+                        MultiOpenScheme::GWC => {
+                            verify_kzg_batch::<VerifierGWC<_>>(&proofs, &params, &vk, transcript)
+                        }
+                        MultiOpenScheme::SHPLONK => verify_kzg_batch::<VerifierSHPLONK<_>>(
+                            &proofs, &params, &vk, transcript,
+                        ),
+                    }
+                }
+            };
+            let mut accepted_outputs = BTreeMap::new();
+            for ((path, accepted), outputs) in paths.iter().zip(&results).zip(outputs) {
+                match (accepted, outputs) {
+                    (true, Some(outputs)) => {
+                        info!("public outputs of {:?}: {:?}", path, outputs);
+                        accepted_outputs.insert(path.display().to_string(), outputs);
+                    }
+                    (true, None) => {}
+                    (false, _) => error!("{:?} was rejected", path),
+                }
+            }
+            if let Some(outputs_path) = outputs_path {
+                std::fs::write(
+                    outputs_path,
+                    serde_json::to_string_pretty(&accepted_outputs)?,
+                )?;
+            }
+            let rejected = results.iter().filter(|accepted| !**accepted).count();
+            info!("verified: {}", rejected == 0);
+            match (rejected, proofs_dir) {
+                (0, _) => {}
+                (_, None) => return Err(Box::new(ExecutionError::ProofRejected)),
+                (_, Some(_)) => {
+                    return Err(Box::new(ExecutionError::ProofsRejected(
+                        rejected,
+                        results.len(),
+                    )))
+                }
+            }
+        }
+        Commands::Estimate {
+            ref model,
+            ref output,
+        } => {
+            let om = Model::from_ezkl_conf(args.clone())?;
+            let circuit = prepare_dummy_circuit::<Fr>(&om)?;
+            let stats = circuit_stats(&circuit, args.logrows)?;
+            if stats.min_logrows > args.logrows {
+                warn!(
+                    "{:?} needs at least 2^{} rows, but -K is {}",
+                    model, stats.min_logrows, args.logrows
+                );
+            }
+            let json = serde_json::to_string_pretty(&stats)?;
+            match output {
+                Some(path) => std::fs::write(path, json)?,
+                None => println!("{}", json),
+            }
+        }
+        #[cfg(feature = "dev-graph")]
+        Commands::Render {
+            ref model,
+            ref output,
+            width,
+            height,
+            labels,
+        } => {
+            let om = Model::from_ezkl_conf(args.clone())?;
+            let circuit = prepare_dummy_circuit::<Fr>(&om)?;
+            let root = BitMapBackend::new(output, (width, height)).into_drawing_area();
+            root.fill(&WHITE).map_err(Box::<dyn Error>::from)?;
+            let root = root
+                .titled(&model.to_string_lossy(), ("sans-serif", 60))
+                .map_err(Box::<dyn Error>::from)?;
+            CircuitLayout::default()
+                .show_labels(labels)
+                .render(args.logrows, &circuit, &root)
+                .map_err(Box::<dyn Error>::from)?;
+            info!("rendered the circuit layout to {:?}", output);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        Commands::Serve {
+            ref model,
+            ref pk_path,
+            ref params_path,
+            ref addr,
+            transcript,
+            max_body_size,
+        } => {
+            let prover = serve::Prover::load(
+                model.clone(),
+                args.settings(),
+                pk_path,
+                params_path,
+                transcript,
+            )?;
+            serve::serve(&prover, addr, max_body_size)?;
+        }
+        Commands::Bench {
+            ref data,
+            ref model,
+            ref output,
+        } => {
+            let data = prepare_data(data.to_string())?;
+            let mut report = BenchReport::default();
+            let om = report.measure("graph load", || Model::from_ezkl_conf(args.clone()))?;
+            let (circuit, public_inputs) = report.measure("quantization", || {
+                prepare_circuit_and_public_input::<Fr>(&data, &args.run_args()?)
+            })?;
+            report.measure("witness generation", || {
+                circuit
+                    .inputs
+                    .chunks(om.num_inputs())
+                    .map(|sample| om.forward_all(sample).map(|_| ()))
+                    .collect::<Result<Vec<()>, _>>()
+            })?;
+            let params = report.measure("srs", || kzg_params(&args, args.logrows))?;
+            let pk = report.measure("keygen", || {
+                create_keys::<KZGCommitmentScheme<Bn256>, Fr>(&circuit, &params)
+            })?;
+            let proof = report.measure("proving", || {
+                create_kzg_proof::<ProverGWC<_>>(
+                    &circuit,
+                    &public_inputs,
+                    &params,
+                    &pk,
+                    TranscriptType::Blake2b,
+                )
+            })?;
+            report.measure("verification", || {
+                verify_kzg_proof::<VerifierGWC<_>>(
+                    proof,
+                    &params,
+                    pk.get_vk(),
+                    TranscriptType::Blake2b,
+                )
+            })?;
+            report.circuit = circuit_stats(&circuit, args.logrows)?;
+
+            let json = serde_json::to_string_pretty(&report)?;
+            match output {
+                Some(path) => std::fs::write(path, json)?,
+                None => println!("{}", json),
+            }
+        }
+        Commands::Hash {
+            model: _,
+            ref vk_path,
+            ref params_path,
+            pfsys,
+            ref proof_path,
+            proof_format,
+        } => {
+            let fingerprint = circuit_fingerprint(&args.run_args()?)?;
+            let hashes = match pfsys {
+                ProofSystem::IPA => {
+                    let params: ParamsIPA<EqAffine> =
+                        load_params::<IPACommitmentScheme<EqAffine>>(params_path.clone())?;
+                    let vk =
+                        load_vk::<IPACommitmentScheme<EqAffine>, Fp>(vk_path.clone(), fingerprint)?;
+                    ArtifactHashes::new::<IPACommitmentScheme<EqAffine>>(fingerprint, &vk, &params)?
+                }
+                ProofSystem::KZG => {
+                    let params: ParamsKZG<Bn256> = load_trimmed_params(params_path, args.logrows)?;
+                    let vk =
+                        load_vk::<KZGCommitmentScheme<Bn256>, Fr>(vk_path.clone(), fingerprint)?;
+                    ArtifactHashes::new::<KZGCommitmentScheme<Bn256>>(fingerprint, &vk, &params)?
+                }
+            };
+            println!("{}", serde_json::to_string_pretty(&hashes)?);
+            if let Some(proof_path) = proof_path {
+                let proof = match proof_format {
+                    // hex calldata doesn't record artifact hashes
+                    ProofFormat::Hex => None,
+                    _ => Some(Proof::load::<Fr>(proof_path, proof_format, &[])?),
+                };
+                match proof {
+                    Some(proof) if proof.hashes.is_some() => {
+                        proof.check_hashes(&hashes)?;
+                        info!("proof {:?} was generated with these artifacts", proof_path);
+                    }
+                    _ => warn!("proof {:?} doesn't record artifact hashes", proof_path),
                 }
             }
         }
     }
     Ok(())
 }
+
+/// Tags the instances of each model of a chain, given as its number of inputs and outputs and its [Model::instance_ports],
+/// such that the outputs of each model share their tags with the inputs of the next model (see [link_instances]).
+#[cfg(feature = "evm")]
+#[allow(clippy::type_complexity)]
+fn chain_tags(
+    models: &[(usize, usize, Vec<(usize, Port)>)],
+    args: &Cli,
+) -> Result<Vec<Vec<(Option<usize>, usize, usize)>>, AggregationError> {
+    if models.len() < 2 {
+        return Err(AggregationError::UnchainedModels(0, 1));
+    }
+    let exposes = |ports: &[(usize, Port)], port: Port| ports.iter().any(|(_, p)| *p == port);
+    for (k, pair) in models.windows(2).enumerate() {
+        let ((_, num_outputs, ports), (num_inputs, _, next_ports)) = (&pair[0], &pair[1]);
+        // committed inputs are hashed with a key, so their digest never matches that of the outputs they consume
+        if args.committed_inputs
+            || num_outputs != num_inputs
+            || !exposes(ports, Port::Output(0))
+            || !exposes(next_ports, Port::Input(0))
+        {
+            return Err(AggregationError::UnchainedModels(k, k + 1));
+        }
+    }
+    Ok(models
+        .iter()
+        .enumerate()
+        .map(|(k, (_, _, ports))| {
+            ports
+                .iter()
+                .map(|(sample, port)| match port {
+                    // the inputs of the first model have no previous model to be linked to
+                    Port::Input(i) => (k.checked_sub(1), *sample, *i),
+                    Port::Output(i) => (Some(k), *sample, *i),
+                })
+                .collect()
+        })
+        .collect())
+}
+
+/// Loads the KZG params for circuits of `2^k` rows from `--srs-path` if set, else falls back to an unsafe (locally generated) setup.
+fn kzg_params(args: &Cli, k: u32) -> Result<ParamsKZG<Bn256>, Box<dyn Error>> {
+    match &args.srs_path {
+        Some(path) => load_srs(path, k),
+        None => {
+            warn!(
+                "no --srs-path set, using an unsafe setup which should not be used in production"
+            );
+            Ok(ParamsKZG::new(k))
+        }
+    }
+}
+
+/// Creates a KZG proof for `circuit` using the multi-open prover `P`, deriving challenges with the requested `transcript`.
+pub(crate) fn create_kzg_proof<'params, P: Prover<'params, KZGCommitmentScheme<Bn256>>>(
+    circuit: &ModelCircuit<Fr>,
+    public_inputs: &[Tensor<i32>],
+    params: &'params ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    transcript: TranscriptType,
+) -> Result<Proof, Box<dyn Error>> {
+    let (proof, _dims) = match transcript {
+        TranscriptType::Blake2b => create_proof_model::<
+            KZGCommitmentScheme<Bn256>,
+            Fr,
+            P,
+            _,
+            Blake2bWrite<_, _, Challenge255<_>>,
+        >(circuit, public_inputs, params, pk),
+        TranscriptType::Keccak => create_proof_model::<
+            KZGCommitmentScheme<Bn256>,
+            Fr,
+            P,
+            _,
+            EvmTranscript<G1Affine, NativeLoader, _, _>,
+        >(circuit, public_inputs, params, pk),
+        TranscriptType::Poseidon => create_proof_model::<
+            KZGCommitmentScheme<Bn256>,
+            Fr,
+            P,
+            _,
+            PoseidonTranscript<NativeLoader, _>,
+        >(circuit, public_inputs, params, pk),
+    }
+    .map_err(Box::<dyn Error>::from)?;
+    Ok(proof)
+}
+
+/// Verifies a KZG `proof` using the multi-open verifier `V`, deriving challenges with the requested `transcript`.
+pub(crate) fn verify_kzg_proof<'params, V: Verifier<'params, KZGCommitmentScheme<Bn256>>>(
+    proof: Proof,
+    params: &'params ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    transcript: TranscriptType,
+) -> Result<(), Box<dyn Error>>
+where
+    KZGSingleStrategy<'params, Bn256>: VerificationStrategy<'params, KZGCommitmentScheme<Bn256>, V>,
+{
+    let strategy = KZGSingleStrategy::new(params);
+    match transcript {
+        TranscriptType::Blake2b => {
+            verify_proof_model::<Fr, V, _, _, _, Blake2bRead<_, _, Challenge255<_>>>(
+                proof, params, vk, strategy,
+            )
+            .map(|_| ())
+        }
+        TranscriptType::Keccak => {
+            verify_proof_model::<Fr, V, _, _, _, EvmTranscript<G1Affine, NativeLoader, _, _>>(
+                proof, params, vk, strategy,
+            )
+            .map(|_| ())
+        }
+        TranscriptType::Poseidon => {
+            verify_proof_model::<Fr, V, _, _, _, PoseidonTranscript<NativeLoader, _>>(
+                proof, params, vk, strategy,
+            )
+            .map(|_| ())
+        }
+    }
+    .map_err(Box::<dyn Error>::from)
+}
+
+/// Verifies KZG `proofs` sharing the verifying key `vk` as a batch (see [crate::pfsys::verify_batch]) using the multi-open
+/// verifier `V`, deriving challenges with the requested `transcript`. Returns whether each proof was accepted.
+pub(crate) fn verify_kzg_batch<'params, V: Verifier<'params, KZGCommitmentScheme<Bn256>>>(
+    proofs: &[Proof],
+    params: &'params ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    transcript: TranscriptType,
+) -> Vec<bool>
+where
+    KZGAccumulatorStrategy<'params, Bn256>: VerificationStrategy<
+        'params,
+        KZGCommitmentScheme<Bn256>,
+        V,
+        Output = KZGAccumulatorStrategy<'params, Bn256>,
+    >,
+    KZGSingleStrategy<'params, Bn256>: VerificationStrategy<'params, KZGCommitmentScheme<Bn256>, V>,
+{
+    match transcript {
+        TranscriptType::Blake2b => {
+            verify_batch::<V, _, Blake2bRead<_, _, Challenge255<_>>>(proofs, params, vk)
+        }
+        TranscriptType::Keccak => {
+            verify_batch::<V, _, EvmTranscript<G1Affine, NativeLoader, _, _>>(proofs, params, vk)
+        }
+        TranscriptType::Poseidon => {
+            verify_batch::<V, _, PoseidonTranscript<NativeLoader, _>>(proofs, params, vk)
+        }
+    }
+}
+
+/// The proofs in `dir`, i.e the files it holds, in order of their names.
+fn proofs_in(dir: &PathBuf) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    paths.retain(|path| path.is_file());
+    paths.sort();
+    if paths.is_empty() {
+        return Err(Box::new(ExecutionError::NoProofs(dir.clone())));
+    }
+    Ok(paths)
+}
@@ -0,0 +1,238 @@
+// exporting unmangled functions over raw pointers is inherently unsafe, each dereference is commented on below
+#![allow(unsafe_code)]
+
+use crate::api;
+use crate::commands::{CircuitSettings, TranscriptType};
+use crate::graph::ModelSource;
+use crate::pfsys::{ModelInput, Proof};
+use clap::ValueEnum;
+use halo2_proofs::poly::commitment::Params;
+use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+use halo2curves::bn256::Bn256;
+use std::cell::RefCell;
+use std::error::Error;
+use std::ffi::{c_char, c_int, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// Returned by all functions on success, and by [ezkl_verify] when the proof is accepted.
+pub const EZKL_OK: c_int = 0;
+/// Returned by [ezkl_verify] when the proof is rejected.
+pub const EZKL_REJECTED: c_int = 1;
+/// Returned by all functions on failure, see [ezkl_last_error].
+pub const EZKL_ERROR: c_int = -1;
+
+thread_local! {
+    /// The message of the last error raised on this thread, see [ezkl_last_error].
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// A buffer of bytes allocated by ezkl, to be released with [ezkl_buffer_free].
+#[repr(C)]
+#[derive(Debug)]
+pub struct EzklBuffer {
+    /// The bytes of the buffer.
+    pub data: *mut u8,
+    /// The number of bytes in the buffer.
+    pub len: usize,
+}
+
+impl EzklBuffer {
+    fn new(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        EzklBuffer { data, len }
+    }
+}
+
+/// Borrows the `len` bytes at `data`, which callers guarantee are valid for the duration of the call.
+fn input<'a>(data: *const u8, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+    match len {
+        0 => Ok(&[]),
+        _ if data.is_null() => Err(format!("null input of {} bytes", len).into()),
+        // SAFETY: callers pass a pointer to `len` initialized bytes, which they don't mutate until the call returns, and checked
+        // to be non-null above
+        _ => Ok(unsafe { std::slice::from_raw_parts(data, len) }),
+    }
+}
+
+/// Borrows the `len` bytes at `data` as utf-8 text.
+fn input_str<'a>(data: *const u8, len: usize) -> Result<&'a str, Box<dyn Error>> {
+    Ok(std::str::from_utf8(input(data, len)?)?)
+}
+
+fn settings(data: *const u8, len: usize) -> Result<CircuitSettings, Box<dyn Error>> {
+    Ok(serde_json::from_str(input_str(data, len)?)?)
+}
+
+fn params(data: *const u8, len: usize) -> Result<ParamsKZG<Bn256>, Box<dyn Error>> {
+    Ok(ParamsKZG::<Bn256>::read(&mut input(data, len)?)?)
+}
+
+fn transcript(data: *const u8, len: usize) -> Result<TranscriptType, Box<dyn Error>> {
+    Ok(TranscriptType::from_str(input_str(data, len)?, true)?)
+}
+
+/// Writes `bytes` to the caller's `out` buffer.
+fn output(out: *mut EzklBuffer, bytes: Vec<u8>) -> Result<(), Box<dyn Error>> {
+    if out.is_null() {
+        return Err("null output buffer".into());
+    }
+    // SAFETY: callers pass a pointer to an `EzklBuffer` they own, and checked to be non-null above
+    unsafe { *out = EzklBuffer::new(bytes) };
+    Ok(())
+}
+
+/// Runs `f`, recording its error (or panic) as the last error of the thread and returning [EZKL_ERROR] if it fails.
+fn run(f: impl FnOnce() -> Result<c_int, Box<dyn Error>>) -> c_int {
+    let msg = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(status)) => return status,
+        Ok(Err(e)) => e.to_string(),
+        Err(_) => "ezkl panicked".to_string(),
+    };
+    let msg = CString::new(msg.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+    EZKL_ERROR
+}
+
+/// The message of the last error raised on the calling thread, or null if there was none. The string is owned by ezkl and valid
+/// until the next call on the thread.
+#[no_mangle]
+pub extern "C" fn ezkl_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |msg| msg.as_ptr()))
+}
+
+/// Releases a buffer returned by ezkl.
+#[no_mangle]
+pub extern "C" fn ezkl_buffer_free(buffer: EzklBuffer) {
+    if buffer.data.is_null() {
+        return;
+    }
+    // SAFETY: buffers are only created by `EzklBuffer::new`, from a boxed slice of `len` bytes which is reclaimed exactly once
+    drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)) });
+}
+
+/// Writes the default circuit settings (those of the cli) as json to `out`.
+#[no_mangle]
+pub extern "C" fn ezkl_default_settings(out: *mut EzklBuffer) -> c_int {
+    run(|| {
+        output(out, serde_json::to_vec(&CircuitSettings::default())?)?;
+        Ok(EZKL_OK)
+    })
+}
+
+/// Generates the keys of the circuit of the .onnx model `model` built with the json `settings` and the KZG `params` saved by
+/// ezkl, writing the proving and verifying keys (as saved by the `setup` command) to `pk_out` and `vk_out`.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn ezkl_setup(
+    model: *const u8,
+    model_len: usize,
+    settings_json: *const u8,
+    settings_len: usize,
+    params_data: *const u8,
+    params_len: usize,
+    pk_out: *mut EzklBuffer,
+    vk_out: *mut EzklBuffer,
+) -> c_int {
+    run(|| {
+        let settings = settings(settings_json, settings_len)?;
+        let params = params(params_data, params_len)?;
+        let model = input(model, model_len)?.to_vec();
+        let keys = api::setup(model, &settings, &params)?;
+        output(pk_out, keys.pk_bytes()?)?;
+        output(vk_out, keys.vk_bytes()?)?;
+        Ok(EZKL_OK)
+    })
+}
+
+/// Proves the .onnx model `model` over the json `data` (as accepted by the cli) with the proving key `pk`, deriving challenges
+/// with the named `transcript` (e.g. "blake2b", or "keccak" for EVM verifiers). The proof is written as json to `proof_out`.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn ezkl_prove(
+    data: *const u8,
+    data_len: usize,
+    model: *const u8,
+    model_len: usize,
+    settings_json: *const u8,
+    settings_len: usize,
+    pk: *const u8,
+    pk_len: usize,
+    params_data: *const u8,
+    params_len: usize,
+    transcript_name: *const u8,
+    transcript_len: usize,
+    proof_out: *mut EzklBuffer,
+) -> c_int {
+    run(|| {
+        let data: ModelInput = serde_json::from_str(input_str(data, data_len)?)?;
+        let settings = settings(settings_json, settings_len)?;
+        let params = params(params_data, params_len)?;
+        let transcript = transcript(transcript_name, transcript_len)?;
+        let model = ModelSource::from(input(model, model_len)?.to_vec());
+        let keys = api::read_keys(model.clone(), &settings, input(pk, pk_len)?)?;
+        let proof =
+            api::prove_with_transcript(model, &settings, &data, &params, &keys, transcript)?;
+        output(proof_out, serde_json::to_vec(&proof)?)?;
+        Ok(EZKL_OK)
+    })
+}
+
+/// Verifies the json `proof` created by [ezkl_prove] against the verifying key `vk`, returning [EZKL_OK] if it is accepted and
+/// [EZKL_REJECTED] if it isn't.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn ezkl_verify(
+    proof: *const u8,
+    proof_len: usize,
+    model: *const u8,
+    model_len: usize,
+    settings_json: *const u8,
+    settings_len: usize,
+    vk: *const u8,
+    vk_len: usize,
+    params_data: *const u8,
+    params_len: usize,
+    transcript_name: *const u8,
+    transcript_len: usize,
+) -> c_int {
+    run(|| {
+        let proof: Proof = serde_json::from_str(input_str(proof, proof_len)?)?;
+        let settings = settings(settings_json, settings_len)?;
+        let params = params(params_data, params_len)?;
+        let transcript = transcript(transcript_name, transcript_len)?;
+        let model = input(model, model_len)?.to_vec();
+        let vk = api::read_verifying_key(model, &settings, input(vk, vk_len)?)?;
+        Ok(
+            match api::verify_with_transcript(&proof, &params, &vk, transcript) {
+                true => EZKL_OK,
+                false => EZKL_REJECTED,
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn null_inputs_are_errors() {
+        assert!(input(ptr::null(), 0).unwrap().is_empty());
+        assert!(input(ptr::null(), 4).is_err());
+        assert!(input_str(ptr::null(), 4).is_err());
+
+        let mut buffer = EzklBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let out: *mut EzklBuffer = &mut buffer;
+        // the settings are read first
+        let status = ezkl_setup(ptr::null(), 0, ptr::null(), 16, ptr::null(), 0, out, out);
+        assert_eq!(status, EZKL_ERROR);
+        // SAFETY: the last error is set by the failed call above, and valid until the next call on the thread
+        let error = unsafe { std::ffi::CStr::from_ptr(ezkl_last_error()) };
+        assert_eq!(error.to_str().unwrap(), "null input of 16 bytes");
+    }
+}
@@ -6,20 +6,28 @@ pub fn i32_to_felt<F: FieldExt>(x: i32) -> F {
     if x >= 0 {
         F::from(x as u64)
     } else {
-        -F::from((-x) as u64)
+        -F::from(x.unsigned_abs() as u64)
     }
 }
 
-fn felt_to_u32<F: FieldExt>(x: F) -> u32 {
-    x.get_lower_32()
+/// Converts a Field element to an i32. Values which don't fit an i32 wrap around, see [felt_to_i32_checked].
+pub fn felt_to_i32<F: FieldExt>(x: F) -> i32 {
+    felt_to_i128(x) as i32
 }
 
-/// Converts a Field element to an i32.
-pub fn felt_to_i32<F: FieldExt>(x: F) -> i32 {
-    if x > F::from(65536) {
-        -(felt_to_u32(-x) as i32)
+/// Converts a Field element to an i32, if it fits one.
+pub fn felt_to_i32_checked<F: FieldExt>(x: F) -> Result<i32, i128> {
+    let x = felt_to_i128(x);
+    i32::try_from(x).map_err(|_| x)
+}
+
+/// Converts a Field element to an i128. Unlike [felt_to_i32] this correctly recovers any value
+/// whose magnitude fits in 127 bits, which makes it suitable for range and overflow checks.
+pub fn felt_to_i128<F: FieldExt>(x: F) -> i128 {
+    if x > F::from_u128(i128::MAX as u128) {
+        -((-x).get_lower_128() as i128)
     } else {
-        felt_to_u32(x) as i32
+        x.get_lower_128() as i128
     }
 }
 
@@ -43,4 +51,43 @@ mod test {
             assert_eq!(x, xf);
         }
     }
+
+    #[test]
+    fn felttoi32_past_16_bits() {
+        for x in [
+            i32::MIN,
+            -(2i32.pow(20)),
+            -65537,
+            65537,
+            2i32.pow(20),
+            i32::MAX,
+        ] {
+            let fieldx: F = i32_to_felt::<F>(x);
+            assert_eq!(felt_to_i32::<F>(fieldx), x);
+            assert_eq!(felt_to_i32_checked::<F>(fieldx), Ok(x));
+        }
+        let overflow = F::from(2u64.pow(40));
+        assert_eq!(felt_to_i32_checked::<F>(overflow), Err(2i128.pow(40)));
+        assert_eq!(felt_to_i32_checked::<F>(-overflow), Err(-(2i128.pow(40))));
+    }
+
+    #[test]
+    fn felttoi128() {
+        for x in [
+            -(2i128.pow(100)),
+            -(2i128.pow(40)),
+            -1,
+            0,
+            1,
+            2i128.pow(17),
+            2i128.pow(100),
+        ] {
+            let fieldx: F = if x >= 0 {
+                F::from_u128(x as u128)
+            } else {
+                -F::from_u128((-x) as u128)
+            };
+            assert_eq!(x, felt_to_i128::<F>(fieldx));
+        }
+    }
 }
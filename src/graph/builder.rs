@@ -0,0 +1,529 @@
+use super::{Claim, CustomOps, GraphError, Mode, Model, ModelSource, VarVisibility, Visibility};
+use crate::circuit::range::ToleranceNorm;
+use crate::commands::CircuitSettings;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// The largest `logrows` of a circuit, that of the largest powers of tau ceremony.
+const MAX_LOGROWS: u32 = 28;
+
+/// The largest `bits` of lookups, such that the ends of the intervals decomposed lookups interpolate between fit in an i32.
+const MAX_BITS: usize = 31;
+
+/// Errors building a [Model] with a [ModelBuilder].
+#[derive(Debug, Error)]
+pub enum BuilderError {
+    /// Lookup tables don't fit in the circuit, even decomposed into two limbs
+    #[error(
+        "lookup tables of {0} bits need more than 2^{1} rows even when decomposed (bits must be at most {MAX_BITS} and 2 * (logrows - 1)), increase logrows or decrease bits"
+    )]
+    BitsExceedRows(usize, u32),
+    /// Lookup tables are empty
+    #[error("bits must be non-zero")]
+    ZeroBits,
+    /// The circuit is larger than any available SRS
+    #[error("logrows must be at most {MAX_LOGROWS}, got {0}")]
+    InvalidLogrows(u32),
+    /// Values of the given scale overflow the lookup tables
+    #[error("scale {0} must be non-negative and less than bits ({1})")]
+    InvalidScale(i32, usize),
+    /// No samples are proven
+    #[error("batch size must be non-zero")]
+    ZeroBatchSize,
+    /// The circuit can't be laid out without rotations
+    #[error("max rotations must be non-zero")]
+    ZeroRotations,
+    /// Chunked dot products hold no products per row
+    #[error("dot chunk must be non-zero")]
+    ZeroDotChunk,
+    /// The range table of input range checks and claims doesn't fit in the circuit, as it can't be decomposed
+    #[error(
+        "range checking inputs or proving claims takes a table of 2^{0} rows, which needs logrows of at least {0} + 1 (got {1}), increase logrows or decrease bits"
+    )]
+    RangeTableExceedsRows(usize, u32),
+    /// Sparse params are left out of the circuit where they are zero, which neither commitments nor private params allow
+    #[error(
+        "sparse params must be public and not committed, as the positions of their zeros are part of the circuit"
+    )]
+    SparsePrivateParams,
+    /// The visibility of some variable isn't supported
+    #[error("{0} can't be {1}")]
+    UnsupportedVisibility(&'static str, Visibility),
+    /// The settings of the model are inconsistent
+    #[error(transparent)]
+    Graph(#[from] GraphError),
+    /// The model couldn't be loaded or converted to a graph of nodes
+    #[error("failed to load model: {0}")]
+    Load(Box<dyn Error>),
+}
+
+/// Optimization passes applied to the graph of a model as it is loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pass {
+    /// When a node runs at a lower scale than the nodes feeding into it, folds the rescaling of its inputs into the lookups
+    /// producing them, rather than feeding them through rescaling nodes (between nodes configured at different scales, see
+    /// [ModelBuilder::layer_scales]) or leaving them at their higher scale.
+    FoldRescales,
+    /// Evaluates the nodes whose inputs are all constants when the model is loaded, and replaces them with constants, such that
+    /// subgraphs computing constants (e.g shapes) take no rows and needn't be supported by ezkl.
+    FoldConstants,
+    /// Drops the nodes which don't contribute to the outputs of the model (e.g training-only branches computing auxiliary losses)
+    /// before they are converted, such that they are neither configured nor laid out.
+    PruneDeadNodes,
+    /// Fuses chains of lookup nodes (e.g a sigmoid followed by a rescaling division) into a single lookup of their composition,
+    /// when the intermediate results aren't consumed by any other node, such that each chain takes a single table and region.
+    FuseLookups,
+    /// Converts the model into tract's typed model and declutters it, resolving the shapes of the nodes of the graph and folding
+    /// its constant subgraphs into constants before they are converted. Not applied by default.
+    OptimizeGraph,
+}
+
+impl Pass {
+    /// The passes applied by default.
+    pub fn all() -> Vec<Pass> {
+        vec![
+            Pass::FoldConstants,
+            Pass::PruneDeadNodes,
+            Pass::FoldRescales,
+            Pass::FuseLookups,
+        ]
+    }
+
+    /// The default passes, and those enabled by the circuit `settings`.
+    pub fn from_settings(settings: &CircuitSettings) -> Vec<Pass> {
+        let mut passes = Pass::all();
+        if settings.optimize_graph {
+            passes.push(Pass::OptimizeGraph);
+        }
+        passes
+    }
+}
+
+/// Builds a [Model] from an .onnx file, starting from the defaults of the CLI (see [CircuitSettings::default]). Settings are
+/// validated when building, such that inconsistent settings are reported before the model is loaded.
+///
+/// ```no_run
+/// use ezkl::graph::ModelBuilder;
+///
+/// let model = ModelBuilder::new("network.onnx").scale(4).bits(12).logrows(14).build().unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ModelBuilder {
+    pub(super) source: ModelSource,
+    pub(super) weights: Option<PathBuf>,
+    pub(super) scale: i32,
+    pub(super) layer_scales: BTreeMap<usize, i32>,
+    pub(super) input_shapes: BTreeMap<usize, Vec<usize>>,
+    pub(super) input_nodes: Vec<String>,
+    pub(super) output_nodes: Vec<String>,
+    pub(super) bits: usize,
+    pub(super) logrows: u32,
+    pub(super) max_rotations: Option<usize>,
+    pub(super) tolerance: usize,
+    pub(super) tolerance_norms: BTreeMap<usize, ToleranceNorm>,
+    pub(super) mode: Mode,
+    pub(super) visibility: VarVisibility,
+    pub(super) batch_size: usize,
+    pub(super) commit_params: bool,
+    pub(super) attested_inputs: bool,
+    pub(super) merkle_depth: Option<usize>,
+    pub(super) dot_chunk: Option<usize>,
+    pub(super) sparse_params: bool,
+    pub(super) range_check_inputs: bool,
+    pub(super) claims: Vec<Claim>,
+    pub(super) passes: Vec<Pass>,
+    pub(super) custom_ops: CustomOps,
+}
+
+impl ModelBuilder {
+    /// Starts building the model at `path` (or held in memory), with private inputs and params and public outputs.
+    pub fn new(path: impl Into<ModelSource>) -> Self {
+        let defaults = CircuitSettings::default();
+        ModelBuilder {
+            source: path.into(),
+            weights: None,
+            scale: defaults.scale,
+            layer_scales: BTreeMap::new(),
+            input_shapes: BTreeMap::new(),
+            input_nodes: vec![],
+            output_nodes: vec![],
+            bits: defaults.bits,
+            logrows: defaults.logrows,
+            max_rotations: defaults.max_rotations,
+            tolerance: defaults.tolerance,
+            tolerance_norms: BTreeMap::new(),
+            mode: Mode::Mock,
+            visibility: VarVisibility::new(
+                Visibility::Private,
+                Visibility::Private,
+                Visibility::Public,
+            ),
+            batch_size: defaults.batch_size,
+            commit_params: false,
+            attested_inputs: false,
+            merkle_depth: None,
+            dot_chunk: defaults.dot_chunk,
+            sparse_params: defaults.sparse_params,
+            range_check_inputs: defaults.range_check_inputs,
+            claims: vec![],
+            passes: Pass::all(),
+            custom_ops: CustomOps::default(),
+        }
+    }
+
+    /// Starts building the model at `path` with circuit `settings`, ignoring the chunk they restrict the model to (see
+    /// [Model::from_settings]).
+    pub fn from_settings(
+        path: impl Into<ModelSource>,
+        settings: &CircuitSettings,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(ModelBuilder::new(path)
+            .scale(settings.scale)
+            .layer_scales(settings.layer_scales.iter().cloned().collect())
+            .input_shapes(settings.input_shapes.iter().cloned().collect())
+            .input_nodes(settings.input_nodes.clone())
+            .output_nodes(settings.output_nodes.clone())
+            .bits(settings.bits)
+            .logrows(settings.logrows)
+            .max_rotations(settings.max_rotations)
+            .tolerance(settings.tolerance)
+            .tolerance_norms(settings.tolerance_norms.iter().cloned().collect())
+            .visibility(VarVisibility::from_settings(settings)?)
+            .batch_size(settings.batch_size)
+            .commit_params(settings.commit_params)
+            .attested_inputs(settings.attested_inputs)
+            .merkle_depth(settings.merkle_depth)
+            .dot_chunk(settings.dot_chunk)
+            .sparse_params(settings.sparse_params)
+            .range_check_inputs(settings.range_check_inputs)
+            .claims(settings.claims.clone().map(|c| c.0).unwrap_or_default())
+            .passes(&Pass::from_settings(settings)))
+    }
+
+    /// A safetensors file whose tensors replace the initializers (and `Constant` nodes) of the model of the same name, such
+    /// that weights can be swapped without re-exporting the model. Rebound tensors must keep their shape.
+    pub fn weights(mut self, weights: Option<PathBuf>) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// The denominator (as a power of 2) used for fixed point arithmetic.
+    pub fn scale(mut self, scale: i32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Per-node overrides of the scale, keyed by node index.
+    pub fn layer_scales(mut self, layer_scales: BTreeMap<usize, i32>) -> Self {
+        self.layer_scales = layer_scales;
+        self
+    }
+
+    /// Overrides of the shape of inputs, keyed by input index, concretizing the symbolic dimensions (e.g a batch dimension `N`)
+    /// the model was exported with before its nodes are quantized.
+    pub fn input_shapes(mut self, input_shapes: BTreeMap<usize, Vec<usize>>) -> Self {
+        self.input_shapes = input_shapes;
+        self
+    }
+
+    /// Cuts the model at the given named tensors (or nodes), which become the inputs of the model in place of its own, in order.
+    /// Nodes upstream of them are pruned.
+    pub fn input_nodes(mut self, input_nodes: Vec<String>) -> Self {
+        self.input_nodes = input_nodes;
+        self
+    }
+
+    /// Cuts the model at the given named tensors (or nodes), which become the outputs of the model in place of its own, in order.
+    pub fn output_nodes(mut self, output_nodes: Vec<String>) -> Self {
+        self.output_nodes = output_nodes;
+        self
+    }
+
+    /// The number of bits of the lookup tables.
+    pub fn bits(mut self, bits: usize) -> Self {
+        self.bits = bits;
+        self
+    }
+
+    /// The log of the number of rows of the circuit.
+    pub fn logrows(mut self, logrows: u32) -> Self {
+        self.logrows = logrows;
+        self
+    }
+
+    /// The maximum number of rotations a single operation is laid out over. If unset, the rotations are computed from the
+    /// largest node of the model (see [Model::auto_rotations]).
+    pub fn max_rotations(mut self, max_rotations: impl Into<Option<usize>>) -> Self {
+        self.max_rotations = max_rotations.into();
+        self
+    }
+
+    /// How much each quantized output is allowed to be off by.
+    pub fn tolerance(mut self, tolerance: usize) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Per-output overrides of the norm of the errors bounded by the tolerance, keyed by output index. Outputs which aren't
+    /// overridden bound each of their errors on its own.
+    pub fn tolerance_norms(mut self, tolerance_norms: BTreeMap<usize, ToleranceNorm>) -> Self {
+        self.tolerance_norms = tolerance_norms;
+        self
+    }
+
+    /// The [Mode] the model is used in.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Which of the inputs, params, and outputs of the model are public.
+    pub fn visibility(mut self, visibility: VarVisibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// The number of samples proven in a single circuit.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Whether the params of the model are committed to.
+    pub fn commit_params(mut self, commit_params: bool) -> Self {
+        self.commit_params = commit_params;
+        self
+    }
+
+    /// Whether a signature over the inputs is verified in-circuit.
+    pub fn attested_inputs(mut self, attested_inputs: bool) -> Self {
+        self.attested_inputs = attested_inputs;
+        self
+    }
+
+    /// The depth of the Merkle tree the inputs are proven to be a leaf of, if any.
+    pub fn merkle_depth(mut self, merkle_depth: Option<usize>) -> Self {
+        self.merkle_depth = merkle_depth;
+        self
+    }
+
+    /// Lays out affine and matmul layers as dot products accumulated over consecutive rows, `dot_chunk` products per row (see
+    /// [crate::circuit::polynomial::DotConfig]), rather than fusing them into a single gate.
+    pub fn dot_chunk(mut self, dot_chunk: Option<usize>) -> Self {
+        self.dot_chunk = dot_chunk;
+        self
+    }
+
+    /// Leaves the products with zero-valued params out of the circuit: zero params are left out of the gates of fused
+    /// layers, and their products skipped by chunked dot products. The positions of the zeros then become part of the
+    /// circuit, such that params must be public and not committed.
+    pub fn sparse_params(mut self, sparse_params: bool) -> Self {
+        self.sparse_params = sparse_params;
+        self
+    }
+
+    /// Range checks the public inputs of the model to lie within the range of the lookup tables, against a table of 2^bits rows.
+    pub fn range_check_inputs(mut self, range_check_inputs: bool) -> Self {
+        self.range_check_inputs = range_check_inputs;
+        self
+    }
+
+    /// Claims about the outputs of the model, proven in-circuit by comparing the outputs to constants against a range table of
+    /// 2^bits rows, such that the claims can be made public in place of the outputs.
+    pub fn claims(mut self, claims: Vec<Claim>) -> Self {
+        self.claims = claims;
+        self
+    }
+
+    /// The optimization passes applied as the model is loaded, all of them by default.
+    pub fn passes(mut self, passes: &[Pass]) -> Self {
+        self.passes = passes.to_vec();
+        self
+    }
+
+    /// The custom ops implementing ONNX ops ezkl doesn't support, see [CustomOps].
+    pub fn custom_ops(mut self, custom_ops: CustomOps) -> Self {
+        self.custom_ops = custom_ops;
+        self
+    }
+
+    /// Checks the settings are consistent, without loading the model.
+    pub fn validate(&self) -> Result<(), BuilderError> {
+        if self.bits == 0 {
+            return Err(BuilderError::ZeroBits);
+        }
+        if self.logrows > MAX_LOGROWS {
+            return Err(BuilderError::InvalidLogrows(self.logrows));
+        }
+        // tables span 2^bits rows, on top of which halo2 reserves a few blinding rows. Wider lookups are decomposed into two
+        // limbs, each looked up in tables of at most 2^(logrows - 1) rows
+        if self.bits > MAX_BITS || self.bits > 2 * (self.logrows as usize).saturating_sub(1) {
+            return Err(BuilderError::BitsExceedRows(self.bits, self.logrows));
+        }
+        for scale in std::iter::once(&self.scale).chain(self.layer_scales.values()) {
+            if *scale < 0 || *scale as usize >= self.bits {
+                return Err(BuilderError::InvalidScale(*scale, self.bits));
+            }
+        }
+        if self.batch_size == 0 {
+            return Err(BuilderError::ZeroBatchSize);
+        }
+        if self.max_rotations == Some(0) {
+            return Err(BuilderError::ZeroRotations);
+        }
+        if self.dot_chunk == Some(0) {
+            return Err(BuilderError::ZeroDotChunk);
+        }
+        if (self.range_check_inputs || !self.claims.is_empty())
+            && self.bits >= self.logrows as usize
+        {
+            return Err(BuilderError::RangeTableExceedsRows(self.bits, self.logrows));
+        }
+        let VarVisibility {
+            input,
+            params,
+            output,
+            input_overrides,
+            output_overrides,
+        } = &self.visibility;
+        if params.is_hashed() || params.is_committed() {
+            return Err(BuilderError::UnsupportedVisibility(
+                "params",
+                params.clone(),
+            ));
+        }
+        if let Some(output) = std::iter::once(output)
+            .chain(output_overrides.values())
+            .find(|v| v.is_committed())
+        {
+            return Err(BuilderError::UnsupportedVisibility(
+                "outputs",
+                output.clone(),
+            ));
+        }
+        if self.commit_params && params.is_public() {
+            return Err(GraphError::CommitPublicParams.into());
+        }
+        // the gates of sparse params don't query their zeros, such that committed params wouldn't be bound to the zeros the
+        // circuit applies, and the verifying key would reveal where the zeros of private params are
+        if self.sparse_params && (self.commit_params || !params.is_public()) {
+            return Err(BuilderError::SparsePrivateParams);
+        }
+        let any_public = input.is_public()
+            || input.is_hashed()
+            || input.is_committed()
+            || params.is_public()
+            || output.is_public()
+            || output.is_hashed()
+            || input_overrides
+                .values()
+                .chain(output_overrides.values())
+                .any(|v| !v.is_private())
+            || self.commit_params
+            || self.attested_inputs
+            || self.merkle_depth.is_some()
+            || !self.claims.is_empty();
+        if !any_public {
+            return Err(GraphError::Visibility.into());
+        }
+        Ok(())
+    }
+
+    /// Validates the settings and loads the model.
+    pub fn build(&self) -> Result<Model, BuilderError> {
+        self.validate()?;
+        Model::from_builder(self).map_err(BuilderError::Load)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_settings() {
+        let builder = ModelBuilder::new("network.onnx");
+        assert!(builder.validate().is_ok());
+        assert!(matches!(
+            builder.clone().bits(33).validate(),
+            Err(BuilderError::BitsExceedRows(33, 17))
+        ));
+        // 24 bit lookups are decomposed to fit in 2^17 rows
+        assert!(builder.clone().bits(24).validate().is_ok());
+        assert!(matches!(
+            builder.clone().scale(16).validate(),
+            Err(BuilderError::InvalidScale(16, 16))
+        ));
+        // the range table of input range checks isn't decomposed
+        assert!(matches!(
+            builder.clone().bits(17).range_check_inputs(true).validate(),
+            Err(BuilderError::RangeTableExceedsRows(17, 17))
+        ));
+        assert!(builder.clone().range_check_inputs(true).validate().is_ok());
+        assert!(matches!(
+            builder.clone().batch_size(0).validate(),
+            Err(BuilderError::ZeroBatchSize)
+        ));
+        // the zeros of sparse params are part of the circuit, so they can only be public
+        assert!(matches!(
+            builder.clone().sparse_params(true).validate(),
+            Err(BuilderError::SparsePrivateParams)
+        ));
+        let public_params =
+            VarVisibility::new(Visibility::Private, Visibility::Public, Visibility::Public);
+        assert!(builder
+            .clone()
+            .visibility(public_params)
+            .sparse_params(true)
+            .validate()
+            .is_ok());
+        assert!(matches!(
+            builder
+                .clone()
+                .commit_params(true)
+                .sparse_params(true)
+                .validate(),
+            Err(BuilderError::SparsePrivateParams)
+        ));
+        let private = VarVisibility::new(
+            Visibility::Private,
+            Visibility::Private,
+            Visibility::Private,
+        );
+        assert!(matches!(
+            builder.clone().visibility(private.clone()).validate(),
+            Err(BuilderError::Graph(GraphError::Visibility))
+        ));
+        assert!(builder
+            .clone()
+            .visibility(private.clone())
+            .attested_inputs(true)
+            .validate()
+            .is_ok());
+        // claims are public through the circuit, in place of the outputs
+        let argmax = Claim::Argmax {
+            output: 0,
+            index: 1,
+        };
+        assert!(builder
+            .clone()
+            .visibility(private.clone())
+            .claims(vec![argmax])
+            .validate()
+            .is_ok());
+        // a single public input is enough
+        let mut one_public = private.clone();
+        one_public.input_overrides.insert(1, Visibility::Public);
+        assert!(builder.clone().visibility(one_public).validate().is_ok());
+        let mut committed_output = private;
+        committed_output
+            .output_overrides
+            .insert(0, Visibility::Committed);
+        assert!(matches!(
+            builder.visibility(committed_output).validate(),
+            Err(BuilderError::UnsupportedVisibility("outputs", _))
+        ));
+    }
+}
@@ -0,0 +1,184 @@
+use super::{scale_to_multiplier, GraphError};
+use crate::circuit::compare::Operand;
+use crate::tensor::Tensor;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// A statement about an output of a model, proven in-circuit by comparing the output to constants, such that the claim can be
+/// made public in place of the output. Outputs are compared at their fixed point scale, to which the bounds of the claim are
+/// quantized.
+///
+/// In json, claims are tagged by their kind, e.g `{"claim": "range", "output": 0, "index": 3, "min": 0.8}` or
+/// `{"claim": "argmax", "output": 0, "index": 7}`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "claim", rename_all = "snake_case")]
+pub enum Claim {
+    /// The element of an output lies within `[min, max]`. Either bound can be left out, for a threshold.
+    Range {
+        /// The index of the output among the outputs of the model.
+        output: usize,
+        /// The index of the element, in row major order.
+        index: usize,
+        /// The lower bound of the element, if any.
+        #[serde(default)]
+        min: Option<f32>,
+        /// The upper bound of the element, if any.
+        #[serde(default)]
+        max: Option<f32>,
+    },
+    /// The element of an output is its largest, such that it is at least as large as every other element.
+    Argmax {
+        /// The index of the output among the outputs of the model.
+        output: usize,
+        /// The index of the element, in row major order.
+        index: usize,
+    },
+}
+
+impl fmt::Display for Claim {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Claim::Range {
+                output,
+                index,
+                min,
+                max,
+            } => {
+                if let Some(min) = min {
+                    write!(f, "{} <= ", min)?;
+                }
+                write!(f, "output {}[{}]", output, index)?;
+                match max {
+                    Some(max) => write!(f, " <= {}", max),
+                    None => Ok(()),
+                }
+            }
+            Claim::Argmax { output, index } => write!(f, "argmax(output {}) == {}", output, index),
+        }
+    }
+}
+
+impl Claim {
+    /// The index of the output the claim is about.
+    pub fn output(&self) -> usize {
+        match self {
+            Claim::Range { output, .. } | Claim::Argmax { output, .. } => *output,
+        }
+    }
+
+    /// The comparisons `lhs >= rhs` proving the claim, over an output of `len` elements at fixed point `scale`.
+    pub fn comparisons(
+        &self,
+        len: usize,
+        scale: i32,
+    ) -> Result<Vec<(Operand, Operand)>, GraphError> {
+        let quantize = |v: f32| Operand::Const((v * scale_to_multiplier(scale)).round() as i32);
+        let index = match self {
+            Claim::Range { index, .. } | Claim::Argmax { index, .. } => *index,
+        };
+        if index >= len {
+            return Err(GraphError::InvalidClaim(format!(
+                "{}: the output has {} elements",
+                self, len
+            )));
+        }
+        let element = Operand::Element(index);
+        match self {
+            Claim::Range { min, max, .. } => {
+                let comparisons = min
+                    .map(|min| (element, quantize(min)))
+                    .into_iter()
+                    .chain(max.map(|max| (quantize(max), element)))
+                    .collect::<Vec<_>>();
+                if comparisons.is_empty() {
+                    return Err(GraphError::InvalidClaim(format!(
+                        "{}: a range claim needs a min or a max",
+                        self
+                    )));
+                }
+                Ok(comparisons)
+            }
+            Claim::Argmax { .. } => Ok((0..len)
+                .filter(|j| *j != index)
+                .map(|j| (element, Operand::Element(j)))
+                .collect()),
+        }
+    }
+
+    /// Whether the claim holds for `output`, the quantized output it is about, at fixed point `scale`.
+    pub fn holds(&self, output: &Tensor<i32>, scale: i32) -> Result<bool, GraphError> {
+        let value = |o: &Operand| match o {
+            Operand::Element(i) => output[*i],
+            Operand::Const(c) => *c,
+        };
+        Ok(self
+            .comparisons(output.len(), scale)?
+            .iter()
+            .all(|(lhs, rhs)| value(lhs) >= value(rhs)))
+    }
+}
+
+// the bounds of claims are parsed from json, which has no NaN, so that they compare as totally as circuit settings need them to
+impl Eq for Claim {}
+
+/// The claims of a spec file (a json array of [Claim]s), as passed to `--claims`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Claims(pub Vec<Claim>);
+
+impl Claims {
+    /// Loads the claims of the json spec file at `path`.
+    pub fn load(path: &Path) -> Result<Self, GraphError> {
+        let data = std::fs::read(path)
+            .map_err(|e| GraphError::InvalidClaim(format!("{:?}: {}", path, e)))?;
+        serde_json::from_slice(&data)
+            .map_err(|e| GraphError::InvalidClaim(format!("{:?}: {}", path, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn claims_compare_outputs_to_constants() {
+        let claims: Claims = serde_json::from_str(
+            r#"[{"claim": "range", "output": 0, "index": 1, "min": 0.5}, {"claim": "argmax", "output": 0, "index": 1}]"#,
+        )
+        .unwrap();
+        let (threshold, argmax) = (&claims.0[0], &claims.0[1]);
+        assert_eq!(threshold.to_string(), "0.5 <= output 0[1]");
+        // at scale 2, 0.5 is quantized to 2
+        assert_eq!(
+            threshold.comparisons(3, 2).unwrap(),
+            vec![(Operand::Element(1), Operand::Const(2))]
+        );
+        assert_eq!(argmax.comparisons(3, 2).unwrap().len(), 2);
+
+        let output = Tensor::new(Some(&[1, 3, -2]), &[3]).unwrap();
+        assert!(threshold.holds(&output, 2).unwrap());
+        assert!(argmax.holds(&output, 2).unwrap());
+        assert!(!threshold.holds(&output, 3).unwrap());
+        let interval = Claim::Range {
+            output: 0,
+            index: 2,
+            min: Some(-1.0),
+            max: Some(0.0),
+        };
+        assert!(interval.holds(&output, 1).unwrap());
+        assert!(!interval.holds(&output, 0).unwrap());
+
+        assert!(matches!(
+            argmax.comparisons(1, 2),
+            Err(GraphError::InvalidClaim(_))
+        ));
+        let unbounded = Claim::Range {
+            output: 0,
+            index: 0,
+            min: None,
+            max: None,
+        };
+        assert!(unbounded.comparisons(3, 2).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use super::node::{deserialize_outlets, serialize_outlets};
+use super::{GraphError, Model, NodeGraph, RunArgs};
+use crate::circuit::table_cache::CachedTable;
+use crate::pfsys::artifact::{self, ArtifactHeader};
+use crate::pfsys::{circuit_fingerprint, Fingerprint, PfsysError};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::path::Path;
+use tract_onnx::prelude::OutletId;
+
+/// The processed node graph of a model (its quantized constants, shapes and execution buckets), as built for the circuit
+/// settings of some [RunArgs]. `compile` saves it such that commands passed `--compiled-model` load the model without
+/// parsing and quantizing its .onnx file again. The graph is built before the model is restricted to a chunk, so a compiled
+/// model serves every chunk of the settings it was compiled with.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CompiledModel {
+    /// The fingerprint of the model and circuit settings the graph was built from, see [circuit_fingerprint].
+    pub fingerprint: Fingerprint,
+    /// The nodes of the model, bucketed for execution.
+    pub nodes: NodeGraph,
+    /// The outlets of the graph's inputs.
+    #[serde(serialize_with = "serialize_outlets")]
+    #[serde(deserialize_with = "deserialize_outlets")]
+    pub inputs: Vec<OutletId>,
+    /// The declared inputs of the graph the model doesn't use, see [Model::unused_inputs].
+    pub unused_inputs: Vec<(usize, String)>,
+    /// The outlets of the graph's outputs.
+    #[serde(serialize_with = "serialize_outlets")]
+    #[serde(deserialize_with = "deserialize_outlets")]
+    pub outputs: Vec<OutletId>,
+    /// The lookup tables of the model, if shipped with it (see `compile --include-tables`), which are loaded rather than
+    /// computed when the compiled model is.
+    pub tables: Vec<CachedTable>,
+}
+
+impl CompiledModel {
+    /// Loads and processes the model of `args`, alongside its lookup tables when `include_tables` is set.
+    pub fn compile(args: &RunArgs, include_tables: bool) -> Result<Self, Box<dyn Error>> {
+        let mut model = Model::builder(args)?.build()?;
+        model.table_cache = args.table_cache.clone();
+        let tables = match include_tables {
+            true => model.lookup_tables(),
+            false => vec![],
+        };
+        Ok(CompiledModel {
+            fingerprint: circuit_fingerprint(args)?,
+            nodes: model.nodes,
+            inputs: model.inputs,
+            unused_inputs: model.unused_inputs,
+            outputs: model.outputs,
+            tables,
+        })
+    }
+
+    /// Saves the compiled model to `path`, bincode encoded and prefixed by an [ArtifactHeader].
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let header = ArtifactHeader::new(hex::encode(self.fingerprint));
+        std::fs::write(path, artifact::to_bincode(self, &header)?)?;
+        info!("saved compiled model to {:?}", path);
+        Ok(())
+    }
+
+    /// Loads the compiled model at `path`, checking it was compiled from the model and circuit settings of `args`.
+    pub fn load(path: &Path, args: &RunArgs) -> Result<Self, Box<dyn Error>> {
+        let invalid = |msg: String| GraphError::InvalidCompiledModel(path.to_path_buf(), msg);
+        let bytes = std::fs::read(path).map_err(|e| invalid(e.to_string()))?;
+        // headers of other versions are reported as such, and other failures as an invalid compiled model
+        let (header, compiled) = artifact::from_bincode::<CompiledModel>(&bytes, "compiled model")
+            .map_err(|e| match e.downcast::<PfsysError>() {
+                Ok(e) => e as Box<dyn Error>,
+                Err(e) => Box::new(invalid(e.to_string())) as Box<dyn Error>,
+            })?;
+        let fingerprint = circuit_fingerprint(args)?;
+        if compiled.fingerprint != fingerprint {
+            if let Some(header) = header {
+                header.check("compiled model", &hex::encode(fingerprint))?;
+            }
+            return Err(Box::new(invalid(
+                "compiled from a different model or circuit settings".to_string(),
+            )));
+        }
+        Ok(compiled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::CircuitSettings;
+    use crate::graph::{GraphBuilder, Mode};
+    use crate::tensor::Tensor;
+
+    #[test]
+    fn compiled_models_match_the_models_they_are_compiled_from() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 2]);
+        let w = graph.constant(&[0.5, -1.0], &[2]).unwrap();
+        let y = graph.add(&x, &w);
+        let y = graph.relu(&y);
+        graph.output(&y);
+
+        let compiled_path = std::env::temp_dir().join("ezkl_compiled_model_test.compiled");
+        let mut args = RunArgs::new(graph.to_onnx(), CircuitSettings::default(), Mode::Forward);
+        let compiled = CompiledModel::compile(&args, true).unwrap();
+        // the relu's table ships with the compiled model
+        assert_eq!(compiled.tables.len(), 1);
+        assert_eq!(compiled.tables[0].outputs.len(), 1 << args.settings.bits);
+        compiled.save(&compiled_path).unwrap();
+        assert!(std::fs::read(&compiled_path)
+            .unwrap()
+            .starts_with(artifact::MAGIC));
+        let model = Model::from_run_args(&args).unwrap();
+        args.compiled = Some(compiled_path.clone());
+        let loaded = Model::from_run_args(&args).unwrap();
+        assert!(loaded.model.is_none());
+        assert_eq!(loaded.inputs, model.inputs);
+        assert_eq!(loaded.input_names(), model.input_names());
+        let input = Tensor::new(Some(&[128, -256]), &[2]).unwrap();
+        assert_eq!(
+            loaded.forward(&[input.clone()]).unwrap(),
+            model.forward(&[input]).unwrap()
+        );
+
+        // compiled models are only loaded for the settings they were compiled with
+        args.settings.scale += 1;
+        assert!(Model::from_run_args(&args).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use crate::pfsys::srs::mmap_file;
+use crate::tensor::Tensor;
+#[cfg(not(target_arch = "wasm32"))]
+use memmap2::Mmap;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(not(target_arch = "wasm32"))]
+type Bytes = Mmap;
+#[cfg(target_arch = "wasm32")]
+type Bytes = Vec<u8>;
+
+/// Distinguishes the spill files of the models loaded by this process.
+static SPILLS: AtomicUsize = AtomicUsize::new(0);
+
+/// The quantized constants of a model, spilled to a memory mapped file (see `--low-memory`) such that they are paged in as the
+/// nodes consuming them are laid out, and dropped afterwards, rather than all held in memory until proving ends. The pages of
+/// the file are backed by the file itself, so the kernel reclaims them under memory pressure without swapping.
+#[derive(Debug)]
+pub struct ConstStore {
+    bytes: Bytes,
+    /// The offset (in elements) and dims of the constant of each node, keyed by node index.
+    entries: BTreeMap<usize, (usize, Vec<usize>)>,
+}
+
+impl ConstStore {
+    /// Writes `consts` (keyed by node index) to a file in the temporary directory, which is memory mapped and then removed,
+    /// such that it doesn't outlive the process.
+    pub fn spill(consts: &BTreeMap<usize, Tensor<i32>>) -> Result<Self, Box<dyn Error>> {
+        let path = std::env::temp_dir().join(format!(
+            "ezkl_consts_{}_{}.bin",
+            std::process::id(),
+            SPILLS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut entries = BTreeMap::new();
+        let mut offset = 0;
+        let mut file = BufWriter::new(File::create(&path)?);
+        for (idx, value) in consts {
+            for v in value.iter() {
+                file.write_all(&v.to_le_bytes())?;
+            }
+            entries.insert(*idx, (offset, value.dims().to_vec()));
+            offset += value.len();
+        }
+        file.flush()?;
+        drop(file);
+        let bytes = mmap_file(&path)?;
+        // the map stays valid once the file is unlinked (where the platform allows removing mapped files)
+        let _ = std::fs::remove_file(&path);
+        Ok(ConstStore { bytes, entries })
+    }
+
+    /// Loads the constant of the node at `idx`, if it was spilled.
+    pub fn get(&self, idx: usize) -> Option<Tensor<i32>> {
+        let (offset, dims) = self.entries.get(&idx)?;
+        let len = dims.iter().product::<usize>();
+        let bytes = self.bytes.get(offset * 4..(offset + len) * 4)?;
+        let values = bytes
+            .chunks_exact(4)
+            .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect::<Vec<_>>();
+        Tensor::new(Some(&values), dims).ok()
+    }
+
+    /// The size of the spilled constants, in bytes.
+    pub fn size(&self) -> usize {
+        self.bytes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spilled_consts_are_loaded_back() {
+        let mut consts = BTreeMap::new();
+        consts.insert(
+            3,
+            Tensor::new(Some(&[1, -2, 3, -4, 5, -6]), &[2, 3]).unwrap(),
+        );
+        consts.insert(7, Tensor::new(Some(&[i32::MAX, i32::MIN]), &[2]).unwrap());
+        let store = ConstStore::spill(&consts).unwrap();
+        assert_eq!(store.size(), 8 * 4);
+        assert_eq!(store.get(3), consts.get(&3).cloned());
+        assert_eq!(store.get(7), consts.get(&7).cloned());
+        assert_eq!(store.get(5), None);
+    }
+}
@@ -0,0 +1,231 @@
+use super::GraphError;
+use crate::tensor::{Tensor, TensorType, ValTensor, VarTensor};
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::Region,
+    plonk::{ConstraintSystem, Error as PlonkError},
+};
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+/// The shape, scale and bound of the output of a [CustomOp].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomQuantization {
+    /// Dimensions of the output.
+    pub out_dims: Vec<usize>,
+    /// The denominator in the fixed point representation of the output.
+    pub out_scale: i32,
+    /// The maximum value that can appear in the output.
+    pub output_max: f32,
+}
+
+/// An ONNX op ezkl doesn't support, implemented by library users for circuits over the field `F`. Register implementations in
+/// [CustomOps], which models consult as they are loaded: nodes running a registered op are quantized, evaluated, and laid out by
+/// it rather than being rejected as unknown.
+pub trait CustomOp<F: FieldExt + TensorType>: fmt::Debug + Send + Sync {
+    /// The output of the op, given the dimensions and scales of its inputs (in order) and the `scale` the node runs at.
+    fn quantize(
+        &self,
+        in_dims: &[Vec<usize>],
+        in_scales: &[i32],
+        scale: i32,
+    ) -> Result<CustomQuantization, Box<dyn Error>>;
+
+    /// Evaluates the op over quantized inputs, e.g. for forward passes and to compute the outputs proofs are checked against.
+    fn f(&self, inputs: &[Tensor<i32>]) -> Result<Tensor<i32>, Box<dyn Error>>;
+
+    /// Configures the gates constraining the op, over the columns its `inputs` and `output` are assigned to.
+    fn configure(
+        &self,
+        meta: &mut ConstraintSystem<F>,
+        inputs: &[VarTensor],
+        output: &VarTensor,
+    ) -> Arc<dyn CustomConfig<F>>;
+}
+
+/// The gates of a [CustomOp], as configured by [CustomOp::configure].
+pub trait CustomConfig<F: FieldExt + TensorType>: fmt::Debug {
+    /// Assigns the op over `inputs` (one per input of the node) in `region`, returning its output.
+    fn layout(
+        &self,
+        region: &mut Region<'_, F>,
+        inputs: &[ValTensor<F>],
+    ) -> Result<ValTensor<F>, PlonkError>;
+}
+
+/// A [CustomOp] with its field erased, such that ops can be looked up while loading models, before circuits (and their field)
+/// come into play.
+trait ErasedOp: fmt::Debug + Send + Sync {
+    fn quantize(
+        &self,
+        in_dims: &[Vec<usize>],
+        in_scales: &[i32],
+        scale: i32,
+    ) -> Result<CustomQuantization, Box<dyn Error>>;
+
+    fn f(&self, inputs: &[Tensor<i32>]) -> Result<Tensor<i32>, Box<dyn Error>>;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+#[derive(Debug)]
+struct Erased<F: FieldExt + TensorType>(Arc<dyn CustomOp<F>>);
+
+impl<F: FieldExt + TensorType> ErasedOp for Erased<F> {
+    fn quantize(
+        &self,
+        in_dims: &[Vec<usize>],
+        in_scales: &[i32],
+        scale: i32,
+    ) -> Result<CustomQuantization, Box<dyn Error>> {
+        self.0.quantize(in_dims, in_scales, scale)
+    }
+
+    fn f(&self, inputs: &[Tensor<i32>]) -> Result<Tensor<i32>, Box<dyn Error>> {
+        self.0.f(inputs)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Custom ops keyed by the name of the ONNX op they implement (as named by tract, e.g. `Erf`).
+///
+/// ```ignore
+/// let mut ops = CustomOps::default();
+/// ops.register::<Fr>("Erf", MyErf);
+/// let model = ModelBuilder::new("network.onnx").custom_ops(ops).build()?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CustomOps(BTreeMap<String, Arc<dyn ErasedOp>>);
+
+impl CustomOps {
+    /// Registers `op` as the implementation of the ONNX op `name` in circuits over the field `F`, replacing any op previously
+    /// registered under that name.
+    pub fn register<F: FieldExt + TensorType>(
+        &mut self,
+        name: impl Into<String>,
+        op: impl CustomOp<F> + 'static,
+    ) {
+        self.0
+            .insert(name.into(), Arc::new(Erased::<F>(Arc::new(op))));
+    }
+
+    /// Whether an op is registered under `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    fn get(&self, name: &str) -> Result<&Arc<dyn ErasedOp>, GraphError> {
+        self.0
+            .get(name)
+            .ok_or_else(|| GraphError::UnregisteredCustomOp(name.to_string()))
+    }
+
+    /// The output of the op registered under `name`, see [CustomOp::quantize].
+    pub fn quantize(
+        &self,
+        name: &str,
+        in_dims: &[Vec<usize>],
+        in_scales: &[i32],
+        scale: i32,
+    ) -> Result<CustomQuantization, Box<dyn Error>> {
+        self.get(name)?.quantize(in_dims, in_scales, scale)
+    }
+
+    /// Evaluates the op registered under `name`, see [CustomOp::f].
+    pub fn f(&self, name: &str, inputs: &[Tensor<i32>]) -> Result<Tensor<i32>, Box<dyn Error>> {
+        self.get(name)?.f(inputs)
+    }
+
+    /// The op registered under `name`, if it was registered for circuits over the field `F`.
+    pub fn op<F: FieldExt + TensorType>(
+        &self,
+        name: &str,
+    ) -> Result<Arc<dyn CustomOp<F>>, GraphError> {
+        self.get(name)?
+            .as_any()
+            .downcast_ref::<Erased<F>>()
+            .map(|op| op.0.clone())
+            .ok_or_else(|| GraphError::UnregisteredCustomOp(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tensor::ops::add;
+    use halo2curves::bn256::Fr;
+    use halo2curves::pasta::Fp;
+
+    /// Doubles its input, without constraining anything.
+    #[derive(Debug)]
+    struct Double;
+
+    #[derive(Debug)]
+    struct DoubleConfig;
+
+    impl<F: FieldExt + TensorType> CustomConfig<F> for DoubleConfig {
+        fn layout(
+            &self,
+            _region: &mut Region<'_, F>,
+            inputs: &[ValTensor<F>],
+        ) -> Result<ValTensor<F>, PlonkError> {
+            Ok(inputs[0].clone())
+        }
+    }
+
+    impl<F: FieldExt + TensorType> CustomOp<F> for Double {
+        fn quantize(
+            &self,
+            in_dims: &[Vec<usize>],
+            in_scales: &[i32],
+            _scale: i32,
+        ) -> Result<CustomQuantization, Box<dyn Error>> {
+            Ok(CustomQuantization {
+                out_dims: in_dims[0].clone(),
+                out_scale: in_scales[0],
+                output_max: 512.0,
+            })
+        }
+
+        fn f(&self, inputs: &[Tensor<i32>]) -> Result<Tensor<i32>, Box<dyn Error>> {
+            Ok(add(&vec![inputs[0].clone(), inputs[0].clone()])?)
+        }
+
+        fn configure(
+            &self,
+            _meta: &mut ConstraintSystem<F>,
+            _inputs: &[VarTensor],
+            _output: &VarTensor,
+        ) -> Arc<dyn CustomConfig<F>> {
+            Arc::new(DoubleConfig)
+        }
+    }
+
+    #[test]
+    fn looks_up_registered_ops() {
+        let mut ops = CustomOps::default();
+        ops.register::<Fr>("Double", Double);
+        assert!(ops.contains("Double"));
+        assert!(!ops.contains("Erf"));
+
+        let q = ops.quantize("Double", &[vec![2, 3]], &[7], 7).unwrap();
+        assert_eq!(q.out_dims, vec![2, 3]);
+        let input = Tensor::new(Some(&[1, 2, 3]), &[3]).unwrap();
+        let output = ops.f("Double", &[input]).unwrap();
+        assert_eq!(output, Tensor::new(Some(&[2, 4, 6]), &[3]).unwrap());
+
+        // ops are only laid out in circuits over the field they were registered for
+        assert!(ops.op::<Fr>("Double").is_ok());
+        assert!(matches!(
+            ops.op::<Fp>("Double"),
+            Err(GraphError::UnregisteredCustomOp(_))
+        ));
+        assert!(ops.f("Erf", &[]).is_err());
+    }
+}
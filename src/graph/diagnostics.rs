@@ -0,0 +1,217 @@
+use super::{Model, ModelConfig, NodeConfig};
+use crate::circuit::decomposed::DECOMPOSED_LOOKUPS;
+use crate::tensor::TensorType;
+use halo2_proofs::{
+    arithmetic::FieldExt,
+    circuit::{Cell, Layouter, Region, Table, Value},
+    dev::{FailureLocation, VerifyFailure},
+    plonk::{Challenge, Column, Error, Instance},
+};
+use std::marker::PhantomData;
+
+/// A [Layouter] which prefixes the name of each region it assigns with the graph node being laid out, e.g
+/// `node 17 (Conv, bucket 2): assign inputs`. Failures reported by the mock prover then carry the node they
+/// originate from, which [Model::diagnose] maps back to the graph.
+pub struct NodeLayouter<'a, F: FieldExt, L: Layouter<F>> {
+    inner: &'a mut L,
+    node: Option<String>,
+    _marker: PhantomData<F>,
+}
+
+impl<'a, F: FieldExt, L: Layouter<F>> NodeLayouter<'a, F, L> {
+    /// Wraps `inner`, initially without any node set.
+    pub fn new(inner: &'a mut L) -> Self {
+        NodeLayouter {
+            inner,
+            node: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Sets the label of the node whose regions are assigned next, or `None` for regions belonging to no node.
+    pub fn set_node(&mut self, node: Option<String>) {
+        self.node = node;
+    }
+}
+
+impl<'a, F: FieldExt, L: Layouter<F>> Layouter<F> for NodeLayouter<'a, F, L> {
+    type Root = Self;
+
+    fn assign_region<A, AR, N, NR>(&mut self, name: N, assignment: A) -> Result<AR, Error>
+    where
+        A: FnMut(Region<'_, F>) -> Result<AR, Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        let node = self.node.clone();
+        self.inner.assign_region(
+            move || match &node {
+                Some(n) => format!("{}: {}", n, name().into()),
+                None => name().into(),
+            },
+            assignment,
+        )
+    }
+
+    // tables are shared between nodes, so they are left as is
+    fn assign_table<A, N, NR>(&mut self, name: N, assignment: A) -> Result<(), Error>
+    where
+        A: FnMut(Table<'_, F>) -> Result<(), Error>,
+        N: Fn() -> NR,
+        NR: Into<String>,
+    {
+        self.inner.assign_table(name, assignment)
+    }
+
+    fn constrain_instance(
+        &mut self,
+        cell: Cell,
+        column: Column<Instance>,
+        row: usize,
+    ) -> Result<(), Error> {
+        self.inner.constrain_instance(cell, column, row)
+    }
+
+    fn get_challenge(&self, challenge: Challenge) -> Value<F> {
+        self.inner.get_challenge(challenge)
+    }
+
+    fn get_root(&mut self) -> &mut Self::Root {
+        self
+    }
+
+    fn push_namespace<NR, N>(&mut self, name_fn: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.inner.push_namespace(name_fn)
+    }
+
+    fn pop_namespace(&mut self, gadget_name: Option<String>) {
+        self.inner.pop_namespace(gadget_name)
+    }
+}
+
+/// Parses the number directly following the first occurrence of `prefix` in `s`.
+fn number_after(s: &str, prefix: &str) -> Option<usize> {
+    let rest = &s[s.find(prefix)? + prefix.len()..];
+    let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+    digits.parse().ok()
+}
+
+/// Converts a linear index into a tensor of shape `dims` into its coordinates.
+fn coordinates(mut linear: usize, dims: &[usize]) -> Vec<usize> {
+    let mut coord = vec![0; dims.len()];
+    for (c, d) in coord.iter_mut().zip(dims).rev() {
+        if *d == 0 {
+            continue;
+        }
+        *c = linear % d;
+        linear /= d;
+    }
+    coord
+}
+
+impl Model {
+    /// A human readable label for the node at `idx`, e.g `node 17 (Conv, bucket 2)`.
+    pub fn node_label(&self, idx: usize) -> String {
+        let node = self.nodes.filter(idx);
+        format!(
+            "node {} ({}, bucket {})",
+            idx,
+            node.opkind,
+            node.bucket.map_or("-".to_string(), |b| b.to_string())
+        )
+    }
+
+    /// Describes a failure of the mock prover in terms of the graph, i.e which node (and which element of the
+    /// node's output) is responsible for it, for instance
+    /// `node 17 (Conv, bucket 2): constraint 5 failed at element [0, 1, 2]`.
+    /// Falls back on the failure as reported by halo2 when it can't be traced back to a node.
+    /// # Arguments
+    ///
+    /// * `config` - The [ModelConfig] the circuit was configured with.
+    /// * `failure` - The failure reported by the mock prover.
+    pub fn diagnose<F: FieldExt + TensorType>(
+        &self,
+        config: &ModelConfig<F>,
+        failure: &VerifyFailure,
+    ) -> String {
+        let location = match failure {
+            VerifyFailure::ConstraintNotSatisfied { location, .. }
+            | VerifyFailure::Lookup { location, .. }
+            | VerifyFailure::Permutation { location, .. } => location,
+            _ => return failure.to_string(),
+        };
+        let (region, offset) = match location {
+            FailureLocation::InRegion { region, offset } => (region.to_string(), *offset),
+            FailureLocation::OutsideRegion { .. } => return failure.to_string(),
+        };
+        let idx = match number_after(&region, "'node ") {
+            Some(idx) => idx,
+            None => return failure.to_string(),
+        };
+        let node = self.nodes.filter(idx);
+        let label = self.node_label(idx);
+        let element = match (failure, config.configs.get(&idx)) {
+            (VerifyFailure::ConstraintNotSatisfied { constraint, .. }, _) => {
+                // each constraint of a node's gate constrains one element of the node's output, in order
+                number_after(&constraint.to_string(), "Constraint ")
+                    .map(|i| (format!("constraint {}", i), coordinates(i, &node.out_dims)))
+            }
+            // lookups check a column of the node's output each, row by row
+            (VerifyFailure::Lookup { lookup_index, .. }, Some(NodeConfig::Lookup(conf, _))) => {
+                let len = node.out_dims.iter().product();
+                conf.element(len, *lookup_index, offset).map(|i| {
+                    (
+                        format!("lookup {}", lookup_index),
+                        coordinates(i, &node.out_dims),
+                    )
+                })
+            }
+            // decomposed lookups have DECOMPOSED_LOOKUPS lookups per element
+            (VerifyFailure::Lookup { lookup_index, .. }, Some(NodeConfig::Decomposed(conf, _))) => {
+                lookup_index.checked_sub(conf.first_lookup).map(|i| {
+                    (
+                        format!("lookup {}", lookup_index),
+                        coordinates(i / DECOMPOSED_LOOKUPS, &node.out_dims),
+                    )
+                })
+            }
+            _ => None,
+        };
+        match element {
+            Some((what, coord)) => {
+                format!(
+                    "{}: {} failed at element {:?} ({})",
+                    label, what, coord, failure
+                )
+            }
+            None => format!("{}: {}", label, failure),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_are_parsed_from_region_names() {
+        let region = "Region 3 ('node 17 (Conv, bucket 2): assign inputs')";
+        assert_eq!(number_after(region, "'node "), Some(17));
+        assert_eq!(number_after("Region 0 ('Elementwise')", "'node "), None);
+        assert_eq!(
+            number_after("Constraint 12 in gate 0 ('basic_op')", "Constraint "),
+            Some(12)
+        );
+    }
+
+    #[test]
+    fn linear_indices_map_to_coordinates() {
+        assert_eq!(coordinates(0, &[2, 3, 4]), vec![0, 0, 0]);
+        assert_eq!(coordinates(23, &[2, 3, 4]), vec![1, 2, 3]);
+        assert_eq!(coordinates(6, &[2, 3, 4]), vec![0, 1, 2]);
+    }
+}
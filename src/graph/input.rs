@@ -0,0 +1,175 @@
+use crate::pfsys::ModelInput;
+use std::path::Path;
+use thiserror::Error;
+
+/// Separates the name of an input from the name of a feature in the header of a CSV column, as in `features:age`.
+const SEPARATOR: char = ':';
+
+/// Errors reading and writing CSV data files.
+#[derive(Debug, Error)]
+pub enum InputError {
+    /// The file couldn't be read, written, or parsed as CSV
+    #[error("invalid csv file {0}: {1}")]
+    Csv(String, csv::Error),
+    /// A cell doesn't hold a number
+    #[error("invalid value {0:?} in column {1} of row {2}")]
+    InvalidValue(String, String, usize),
+    /// Some, but not all, columns are mapped to an input by name
+    #[error("column {0} isn't mapped to an input, expected a header of the form `input:feature`")]
+    UnmappedColumn(String),
+    /// The file holds no rows
+    #[error("no rows found in {0}")]
+    NoRows(String),
+}
+
+/// Loads the inputs of a model from a CSV file, each row of which is a sample (such that files of several rows hold a batch,
+/// to be proven with a `--batch-size` of as many rows). Columns are either all features of the single input of the model, in
+/// order, or mapped to the inputs of the model by name with headers of the form `input:feature`, the columns of each input
+/// being concatenated in order. Outputs are left empty.
+pub fn load_csv(path: &Path) -> Result<ModelInput, InputError> {
+    let name = path.display().to_string();
+    let csv_err = |e| InputError::Csv(name.clone(), e);
+    let mut reader = csv::Reader::from_path(path).map_err(csv_err)?;
+    let headers = reader
+        .headers()
+        .map_err(csv_err)?
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect::<Vec<_>>();
+
+    // the input each column belongs to, all columns belonging to a single unnamed input when no header names an input
+    let named = headers.iter().any(|h| h.contains(SEPARATOR));
+    let mut input_names: Vec<String> = vec![];
+    let mut column_inputs = vec![];
+    for header in &headers {
+        let input = match (named, header.split_once(SEPARATOR)) {
+            (true, Some((input, _))) => input.to_string(),
+            (true, None) => return Err(InputError::UnmappedColumn(header.clone())),
+            (false, _) => String::new(),
+        };
+        let idx = match input_names.iter().position(|n| *n == input) {
+            Some(idx) => idx,
+            None => {
+                input_names.push(input);
+                input_names.len() - 1
+            }
+        };
+        column_inputs.push(idx);
+    }
+
+    let mut input_data = vec![vec![]; input_names.len()];
+    let mut rows = 0;
+    for record in reader.records() {
+        let record = record.map_err(csv_err)?;
+        for (column, cell) in record.iter().enumerate() {
+            let value = cell.trim().parse::<f32>().map_err(|_| {
+                InputError::InvalidValue(cell.to_string(), headers[column].clone(), rows)
+            })?;
+            input_data[column_inputs[column]].push(value);
+        }
+        rows += 1;
+    }
+    if rows == 0 {
+        return Err(InputError::NoRows(name));
+    }
+
+    let input_shapes = input_data
+        .iter()
+        .map(|d| match rows {
+            1 => vec![d.len()],
+            _ => vec![rows, d.len() / rows],
+        })
+        .collect();
+    Ok(ModelInput {
+        input_data,
+        input_shapes,
+        input_names: match named {
+            true => input_names,
+            false => vec![],
+        },
+        output_data: vec![],
+        input_key: None,
+        input_signature: None,
+        input_merkle_paths: vec![],
+    })
+}
+
+/// Saves the (dequantized) outputs of a model to a CSV file with a row per sample of a batch of `rows` samples. The columns of
+/// each output are named `output:index`, after the names of the outputs in `names`.
+pub fn save_csv(
+    path: &Path,
+    names: &[String],
+    outputs: &[Vec<f32>],
+    rows: usize,
+) -> Result<(), InputError> {
+    let csv_err = |e| InputError::Csv(path.display().to_string(), e);
+    let mut writer = csv::Writer::from_path(path).map_err(csv_err)?;
+    let widths = outputs
+        .iter()
+        .map(|o| o.len() / rows.max(1))
+        .collect::<Vec<_>>();
+    let headers = names
+        .iter()
+        .zip(&widths)
+        .flat_map(|(name, width)| (0..*width).map(move |i| format!("{}{}{}", name, SEPARATOR, i)));
+    writer.write_record(headers).map_err(csv_err)?;
+    for row in 0..rows {
+        let record = outputs.iter().zip(&widths).flat_map(|(o, width)| {
+            o[row * width..(row + 1) * width]
+                .iter()
+                .map(|v| v.to_string())
+        });
+        writer.write_record(record).map_err(csv_err)?;
+    }
+    writer.flush().map_err(|e| csv_err(e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, contents: &str) -> Result<ModelInput, InputError> {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        load_csv(&path)
+    }
+
+    #[test]
+    fn maps_columns_to_inputs() {
+        let data = load("ezkl_unnamed.csv", "age,income\n31,5.5\n").unwrap();
+        assert_eq!(data.input_data, vec![vec![31.0, 5.5]]);
+        assert_eq!(data.input_shapes, vec![vec![2]]);
+        assert!(data.input_names.is_empty());
+
+        let data = load(
+            "ezkl_named.csv",
+            "x:age,mask:0,x:income\n31,1,5.5\n40,0,2.5\n",
+        )
+        .unwrap();
+        assert_eq!(data.input_names, vec!["x", "mask"]);
+        assert_eq!(
+            data.input_data,
+            vec![vec![31.0, 5.5, 40.0, 2.5], vec![1.0, 0.0]]
+        );
+        assert_eq!(data.input_shapes, vec![vec![2, 2], vec![2, 1]]);
+
+        assert!(matches!(
+            load("ezkl_unmapped.csv", "x:age,income\n31,5.5\n"),
+            Err(InputError::UnmappedColumn(_))
+        ));
+        assert!(matches!(
+            load("ezkl_invalid.csv", "age,income\n31,n/a\n"),
+            Err(InputError::InvalidValue(..))
+        ));
+    }
+
+    #[test]
+    fn saves_outputs() {
+        let path = std::env::temp_dir().join("ezkl_outputs.csv");
+        save_csv(&path, &["score".to_string()], &[vec![0.5, 0.25]], 2).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "score:0\n0.5\n0.25\n"
+        );
+    }
+}
@@ -1,26 +1,62 @@
 /// Helper functions
 pub mod utilities;
 pub use utilities::*;
+/// Building models with validated settings.
+pub mod builder;
+/// Claims about the outputs of models, proven in-circuit in place of exposing the outputs.
+pub mod claims;
+/// Caching the processed node graph of models on disk.
+pub mod compiled;
+/// Spilling the quantized constants of models to disk, loaded back as they are laid out.
+pub mod consts;
+/// Custom operators, implementing ONNX ops ezkl doesn't support out of the box.
+pub mod custom;
+/// Tracing of circuit failures back to the nodes of the graph.
+pub mod diagnostics;
+/// Loading model inputs from, and saving model outputs to, CSV files.
+pub mod input;
 /// Crate for defining a computational graph and building a ZK-circuit from it.
 pub mod model;
+/// Converting NNEF models into ONNX ones.
+#[cfg(feature = "nnef")]
+pub mod nnef;
 /// Inner elements of a computational graph that represent a single operation / constraints.
 pub mod node;
+/// Building models programmatically, without ONNX.
+pub mod program;
+/// Converting TensorFlow Lite models into ONNX ones.
+#[cfg(feature = "tflite")]
+pub mod tflite;
 /// Representations of a computational graph's variables.
 pub mod vars;
+/// Loading the weights of models from external data and safetensors files.
+pub mod weights;
 
+use crate::circuit::eddsa::Signature;
+use crate::circuit::merkle::MerklePath;
+use crate::commands::CircuitSettings;
+use crate::progress::Task;
 use crate::tensor::TensorType;
 use crate::tensor::{Tensor, ValTensor};
 use anyhow::Result;
+pub use builder::*;
+pub use claims::*;
+pub use compiled::*;
+pub use custom::*;
 use halo2_proofs::{
     arithmetic::FieldExt,
     circuit::{Layouter, SimpleFloorPlanner, Value},
     plonk::{Circuit, ConstraintSystem, Error as PlonkError},
 };
-use log::{info, trace};
+use log::{error, info, trace};
 pub use model::*;
 pub use node::*;
-use std::cmp::max;
+pub use program::*;
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use thiserror::Error;
 pub use vars::*;
 
@@ -51,6 +87,18 @@ pub enum GraphError {
     /// Error in the configuration of the visibility of variables
     #[error("there should be at least one set of public variables")]
     Visibility,
+    /// Inputs are committed to, but no key was provided to commit to them with
+    #[error("committing to inputs requires an input_key of {0} values")]
+    MissingInputKey(usize),
+    /// Inputs are attested, but no valid signature over them was provided
+    #[error("attesting inputs requires a valid input_signature over them, see the sign command")]
+    InvalidInputSignature,
+    /// Inclusion of the inputs in a Merkle tree is proven, but the paths provided don't lead to a common root
+    #[error("proving inclusion in a merkle tree requires an input_merkle_paths entry of depth {0} for each sample, see the merkle-path command")]
+    InvalidMerklePath(usize),
+    /// Params were both made public and committed to
+    #[error("params can only be committed to when they are private")]
+    CommitPublicParams,
     /// Ezkl only supports divisions by constants
     #[error("ezkl currently only supports division by constants")]
     NonConstantDiv,
@@ -63,54 +111,209 @@ pub enum GraphError {
     /// Error when attempting to load a model
     #[error("failed to load model")]
     ModelLoad,
+    /// A witness value falls outside of the range covered by the lookup tables
+    #[error("value {2} fed into node {0} ({1}) overflows the lookup range")]
+    Overflow(usize, OpKind, i128),
+    /// The requested chunk does not exist or holds no operations
+    #[error("invalid chunk {0} for the given cut points")]
+    InvalidChunk(usize),
+    /// The task laying out the model was cancelled
+    #[error("the task was cancelled")]
+    Cancelled,
+    /// A node runs a custom op which isn't registered (for the field of the circuit)
+    #[error("no custom op {0} is registered for the field of the circuit")]
+    UnregisteredCustomOp(String),
+    /// The values of a constant don't fill its shape
+    #[error("{0} values can't fill a constant of shape {1:?}")]
+    ConstantShape(usize, Vec<usize>),
+    /// A TFLite model is malformed or uses features the TFLite frontend doesn't support
+    #[error("invalid or unsupported tflite model: {0}")]
+    InvalidTflite(String),
+    /// The NNEF model could not be parsed, or uses fragments which can't be converted
+    #[error("invalid or unsupported nnef model: {0}")]
+    InvalidNnef(String),
+    /// The external data or weights file of a model could not be read, or doesn't match the model
+    #[error("invalid weights: {0}")]
+    InvalidWeights(String),
+    /// The shape an input is overridden with doesn't match the one declared by the model
+    #[error("input {0} can't be given shape {1:?}, the model declares {2}")]
+    InputShape(usize, Vec<usize>, String),
+    /// No tensor or node of the model has the given name
+    #[error("no tensor or node named {0} in the model")]
+    MissingTensor(String),
+    /// The operation needs the original model, which models loaded from a compiled graph (or with `--low-memory`) don't hold
+    #[error("the original model is required, which compiled models (and models loaded with --low-memory) don't hold")]
+    CompiledModel,
+    /// The compiled graph of a model couldn't be read, or was compiled from a different model or circuit settings
+    #[error("invalid compiled model {0:?}: {1}")]
+    InvalidCompiledModel(PathBuf, String),
+    /// The (quantized) output of a node doesn't fit the 32 bit integers witnesses are computed over
+    #[error("the output of node {0} ({1}) reaches {2}, which overflows 32 bit witness values: lower the scale or bits")]
+    WitnessOverflow(usize, OpKind, i128),
+    /// A claim of the spec file can't be read, or isn't about an element of the outputs of the model
+    #[error("invalid claim: {0}")]
+    InvalidClaim(String),
+    /// A claim doesn't hold for the outputs the model computes, such that it can't be proven
+    #[error("claim {0} doesn't hold for the outputs of the model")]
+    FalseClaim(String),
+    /// The public inputs of a proof don't match the instances of the circuit
+    #[error("the proof holds {0} public inputs, the circuit {1}")]
+    InstanceMismatch(usize, usize),
+}
+
+/// Where the .onnx file of a model is loaded from.
+#[derive(Clone, Debug)]
+pub enum ModelSource {
+    /// A file on disk.
+    Path(PathBuf),
+    /// The contents of a file, e.g. for environments without a filesystem such as the browser.
+    Bytes(Arc<Vec<u8>>),
+}
+
+impl ModelSource {
+    /// The contents of the .onnx file.
+    pub fn read(&self) -> std::io::Result<Cow<'_, [u8]>> {
+        match self {
+            ModelSource::Path(path) => std::fs::read(path).map(Cow::Owned),
+            ModelSource::Bytes(bytes) => Ok(Cow::Borrowed(bytes.as_slice())),
+        }
+    }
+}
+
+impl From<PathBuf> for ModelSource {
+    fn from(path: PathBuf) -> Self {
+        ModelSource::Path(path)
+    }
+}
+
+impl From<&Path> for ModelSource {
+    fn from(path: &Path) -> Self {
+        ModelSource::Path(path.to_path_buf())
+    }
+}
+
+impl From<&PathBuf> for ModelSource {
+    fn from(path: &PathBuf) -> Self {
+        ModelSource::Path(path.clone())
+    }
+}
+
+impl From<&str> for ModelSource {
+    fn from(path: &str) -> Self {
+        ModelSource::Path(PathBuf::from(path))
+    }
+}
+
+impl From<Vec<u8>> for ModelSource {
+    fn from(bytes: Vec<u8>) -> Self {
+        ModelSource::Bytes(Arc::new(bytes))
+    }
+}
+
+/// Everything needed to load a model and build its circuit: the model file, the settings of the circuit, and the [Mode] the model
+/// is used in. Library code builds these directly, and the CLI translates its flags into one.
+#[derive(Clone, Debug)]
+pub struct RunArgs {
+    /// The .onnx model file.
+    pub model: ModelSource,
+    /// A safetensors file whose tensors replace the initializers of the model of the same name, see [ModelBuilder::weights].
+    pub weights: Option<PathBuf>,
+    /// The compiled graph of the model (see [CompiledModel]), loaded in place of the model file when set.
+    pub compiled: Option<PathBuf>,
+    /// The settings the circuit is built with.
+    pub settings: CircuitSettings,
+    /// The mode the model is used in.
+    pub mode: Mode,
+    /// Whether progress bars are shown when quantizing and laying out the model.
+    pub progress: bool,
+    /// Whether memory is traded for time: the tract graph of the model is released once converted, its constants are spilled to
+    /// disk (see [consts::ConstStore]), and the results of the layout are dropped after their last use.
+    pub low_memory: bool,
+    /// The directory lookup tables are cached in, if any, see [crate::circuit::table_cache]. Tables are only cached on disk
+    /// when set.
+    pub table_cache: Option<PathBuf>,
+    /// The model of these args, already loaded (see [crate::api::PreparedCircuit]), which circuits configured and witnesses
+    /// prepared from these args share rather than parsing and quantizing it again (see [Model::shared_from_run_args]).
+    pub prepared: Option<Arc<Model>>,
+    /// The proof (if any) the layout of the model reports its progress to, and stops laying out the model when it is cancelled.
+    pub task: Option<Task>,
+    /// The custom ops nodes of the model are implemented with, see [CustomOps].
+    pub custom_ops: CustomOps,
+}
+
+thread_local! {
+    /// The run args circuits configured on this thread load their model with, see [RunArgs::scoped].
+    static RUN_ARGS: RefCell<Option<RunArgs>> = RefCell::new(None);
+}
+
+impl RunArgs {
+    /// Creates run args for the model at `model` with the given `settings`.
+    pub fn new(model: impl Into<ModelSource>, settings: CircuitSettings, mode: Mode) -> Self {
+        RunArgs {
+            model: model.into(),
+            weights: None,
+            compiled: None,
+            settings,
+            mode,
+            progress: false,
+            low_memory: false,
+            table_cache: None,
+            prepared: None,
+            task: None,
+            custom_ops: CustomOps::default(),
+        }
+    }
+
+    /// Runs `f` with these run args in scope. halo2 configures circuits statically (without access to the circuit instance), so
+    /// [ModelCircuit]s load the model they are configured for from the run args in scope: any circuit configured within `f` (e.g.
+    /// when generating keys or proofs) is that of the model these args describe.
+    pub fn scoped<T>(&self, f: impl FnOnce() -> T) -> T {
+        let prev = RUN_ARGS.with(|args| args.replace(Some(self.clone())));
+        let res = f();
+        RUN_ARGS.with(|args| *args.borrow_mut() = prev);
+        res
+    }
+
+    /// The run args currently in scope, see [RunArgs::scoped].
+    pub fn current() -> Option<RunArgs> {
+        RUN_ARGS.with(|args| args.borrow().clone())
+    }
 }
 
 /// Defines the circuit for a computational graph / model loaded from a `.onnx` file.
+///
+/// halo2 configures circuits statically, so the model a circuit is configured for is that of the [RunArgs] in scope (see
+/// [RunArgs::scoped]) on the configuring thread: keys and proofs must be generated, and keys read, within [RunArgs::scoped]
+/// (including on any other thread, e.g. of `spawn_blocking`). A circuit configured out of scope fails to synthesize with
+/// [PlonkError::Synthesis], as does one whose model fails to load. [ModelCircuit::configure_with_args] configures the circuit
+/// of explicit args instead.
 #[derive(Clone, Debug)]
 pub struct ModelCircuit<F: FieldExt> {
     /// Vector of input tensors to the model / graph of computations.
     pub inputs: Vec<Tensor<i32>>,
+    /// The key the inputs are committed to with, when inputs are committed to.
+    pub input_key: Option<Tensor<i32>>,
+    /// The signature over the inputs, when inputs are attested.
+    pub input_signature: Option<Signature<F>>,
+    /// The path from the inputs of each sample to the root of the dataset they are drawn from, when proving their inclusion.
+    pub input_merkle_paths: Vec<MerklePath<F>>,
     /// Represents the Field we are using.
     pub _marker: PhantomData<F>,
 }
 
-impl<F: FieldExt + TensorType> Circuit<F> for ModelCircuit<F> {
-    type Config = ModelConfig<F>;
-    type FloorPlanner = SimpleFloorPlanner;
-
-    fn without_witnesses(&self) -> Self {
-        self.clone()
-    }
-
-    fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
-        let model = Model::from_arg().expect("model should load from args");
-        let mut num_fixed = 0;
+impl<F: FieldExt + TensorType> ModelCircuit<F> {
+    /// Configures the circuit of the model described by `args`.
+    pub fn configure_with_args(
+        cs: &mut ConstraintSystem<F>,
+        args: &RunArgs,
+    ) -> Result<ModelConfig<F>, Box<dyn std::error::Error>> {
+        let model = Model::shared_from_run_args(args)?;
         let row_cap = model.max_node_size();
-
-        // TODO: extract max number of params in a given fused layer
-        let num_advice: usize = if model.visibility.params.is_public() {
-            num_fixed += model.max_node_params();
-            // this is the maximum of variables in non-fused layer, and the maximum of variables (non-params) in fused layers
-            max(model.max_node_vars_non_fused(), model.max_node_vars_fused())
-        } else {
-            // this is the maximum of variables in non-fused layer, and the maximum of variables (non-params) in fused layers
-            //  + the max number of params in a fused layer
-            max(
-                model.max_node_vars_non_fused(),
-                model.max_node_params() + model.max_node_vars_fused(),
-            )
-        };
+        // the vars are shared by the regions of every bucket, so there are as many as the widest region takes
+        let (num_advice, num_fixed) = model.num_vars();
         // for now the number of instances corresponds to the number of graph / model outputs
-        let mut num_instances = 0;
-        let mut instance_shapes = vec![];
-        if model.visibility.input.is_public() {
-            num_instances += model.num_inputs();
-            instance_shapes.extend(model.input_shapes());
-        }
-        if model.visibility.output.is_public() {
-            num_instances += model.num_outputs();
-            instance_shapes.extend(model.output_shapes());
-        }
+        let instance_shapes = model.instance_shapes();
+        let num_instances = instance_shapes.len();
         let mut vars = ModelVars::new(
             cs,
             model.logrows as usize,
@@ -129,7 +332,23 @@ impl<F: FieldExt + TensorType> Circuit<F> for ModelCircuit<F> {
             vars.fixed.iter().map(|a| a.num_cols()).sum::<usize>()
         );
         info!("number of instances used: {:?}", num_instances);
-        model.configure(cs, &mut vars).unwrap()
+        model.configure(cs, &mut vars, args.task.clone())
+    }
+}
+
+impl<F: FieldExt + TensorType> Circuit<F> for ModelCircuit<F> {
+    /// Failing to configure the circuit is reported when it is synthesized, as halo2 can't fail configuration.
+    type Config = Result<ModelConfig<F>, String>;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+        let args = RunArgs::current()
+            .ok_or_else(|| "circuits must be configured within RunArgs::scoped".to_string())?;
+        Self::configure_with_args(cs, &args).map_err(|e| e.to_string())
     }
 
     fn synthesize(
@@ -137,6 +356,10 @@ impl<F: FieldExt + TensorType> Circuit<F> for ModelCircuit<F> {
         config: Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), PlonkError> {
+        let config = config.map_err(|e| {
+            error!("failed to configure the circuit: {}", e);
+            PlonkError::Synthesis
+        })?;
         trace!("Setting input in synthesize");
         let inputs = self
             .inputs
@@ -144,11 +367,66 @@ impl<F: FieldExt + TensorType> Circuit<F> for ModelCircuit<F> {
             .map(|i| ValTensor::from(<Tensor<i32> as Into<Tensor<Value<F>>>>::into(i.clone())))
             .collect::<Vec<ValTensor<F>>>();
         trace!("Setting output in synthesize");
+        let input_key = self
+            .input_key
+            .clone()
+            .map(|k| ValTensor::from(<Tensor<i32> as Into<Tensor<Value<F>>>>::into(k)));
         config
             .model
-            .layout(config.clone(), &mut layouter, &inputs, &config.vars)
-            .unwrap();
+            .layout(
+                config.clone(),
+                &mut layouter,
+                &inputs,
+                input_key.as_ref(),
+                self.input_signature.as_ref(),
+                &self.input_merkle_paths,
+                &config.vars,
+            )
+            .map_err(|e| {
+                error!("failed to lay out the model: {}", e);
+                PlonkError::Synthesis
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::dev::MockProver;
+    use halo2curves::pasta::Fp;
+
+    #[test]
+    fn circuits_configured_out_of_scope_fail_to_synthesize() {
+        assert!(RunArgs::current().is_none());
+        let circuit = ModelCircuit::<Fp> {
+            inputs: vec![],
+            input_key: None,
+            input_signature: None,
+            input_merkle_paths: vec![],
+            _marker: PhantomData,
+        };
+        assert!(matches!(
+            MockProver::run(4, &circuit, vec![]),
+            Err(PlonkError::Synthesis)
+        ));
+    }
+
+    #[test]
+    fn configs_share_the_prepared_model() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 2]);
+        let y = graph.relu(&x);
+        graph.output(&y);
 
-        Ok(())
+        let mut args = RunArgs::new(graph.to_onnx(), CircuitSettings::default(), Mode::Prove);
+        let model = Arc::new(Model::from_run_args(&args).unwrap());
+        args.prepared = Some(model.clone());
+        assert!(Arc::ptr_eq(
+            &Model::shared_from_run_args(&args).unwrap(),
+            &model
+        ));
+        let mut cs = ConstraintSystem::<Fp>::default();
+        let config = ModelCircuit::<Fp>::configure_with_args(&mut cs, &args).unwrap();
+        assert!(Arc::ptr_eq(&config.model, &model));
     }
 }
@@ -1,39 +1,409 @@
+use super::consts::ConstStore;
+use super::diagnostics::NodeLayouter;
 use super::node::*;
 use super::vars::*;
-use super::GraphError;
+use super::{
+    scale_to_multiplier, Claim, CompiledModel, CustomOps, GraphError, ModelBuilder, ModelSource,
+    Pass, RunArgs,
+};
+use crate::circuit::decomposed::{
+    interpolate, DecomposedConfig, DecomposedTables, RangeTable, DECOMPOSED_VARS,
+};
 use crate::circuit::lookup::Config as LookupConfig;
 use crate::circuit::lookup::Op as LookupOp;
 use crate::circuit::lookup::Table as LookupTable;
 use crate::circuit::polynomial::Config as PolyConfig;
+use crate::circuit::polynomial::DotConfig;
 use crate::circuit::polynomial::InputType as PolyInputType;
 use crate::circuit::polynomial::Node as PolyNode;
 use crate::circuit::polynomial::Op as PolyOp;
+use crate::circuit::table_cache::{self, CachedTable};
 
 // use crate::circuit::polynomial::InputType as PolyInputType;
 
+use crate::circuit::compare::CompareConfig;
+use crate::circuit::eddsa::{EddsaConfig, Signature};
+use crate::circuit::merkle::{MerkleConfig, MerklePath};
+use crate::circuit::poseidon::{hash_to_limbs, PoseidonConfig, DIGEST_LIMBS, KEY_LIMBS};
 use crate::circuit::range::*;
-use crate::commands::{Cli, Commands};
+use crate::commands::CircuitSettings;
+use crate::fieldutils::felt_to_i128;
+use crate::memory;
+use crate::progress::{self, Phase, Task};
 use crate::tensor::TensorType;
 use crate::tensor::{Tensor, ValTensor, VarTensor};
 //use clap::Parser;
 use anyhow::{Context, Error as AnyError};
 use halo2_proofs::{
     arithmetic::FieldExt,
-    circuit::{Layouter, Value},
-    plonk::ConstraintSystem,
+    circuit::{AssignedCell, Layouter, Value},
+    plonk::{Column, ConstraintSystem, Instance},
 };
+use indicatif::ProgressBar;
 use itertools::Itertools;
-use log::{debug, info, trace};
+use log::{debug, error, info, trace, warn};
 use std::cell::RefCell;
-use std::cmp::max;
-use std::collections::{BTreeMap, HashSet};
+use std::cmp::{max, min};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::error::Error;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::sync::Arc;
 use tabled::Table;
+use tracing::info_span;
 use tract_onnx;
-use tract_onnx::prelude::{Framework, Graph, InferenceFact, Node as OnnxNode, OutletId};
-use tract_onnx::tract_hir::internal::InferenceOp;
+use tract_onnx::prelude::{
+    Datum, Framework, Graph, InferenceFact, InferenceModelExt, Node as OnnxNode, OutletId, TVec,
+    Tensor as TractTensor,
+};
+use tract_onnx::tract_hir::infer::{Factoid, ShapeFactoid};
+use tract_onnx::tract_hir::internal::{DatumType, EvalOp, InferenceOp};
+use tract_onnx::tract_hir::tract_core::ops::konst::Const;
+/// Parses the model of `source` into a tract graph, converting models of other frontends (e.g TFLite, NNEF) into ONNX first.
+/// The weights of ONNX models are read from their external data files, and rebound to those of the safetensors file at
+/// `weights` if any.
+fn load_graph(
+    source: &ModelSource,
+    weights: Option<&Path>,
+) -> Result<Graph<InferenceFact, Box<dyn InferenceOp>>, Box<dyn Error>> {
+    let frontend = |onnx: Vec<u8>| -> Result<_, Box<dyn Error>> {
+        if weights.is_some() {
+            return Err(GraphError::InvalidWeights(
+                "weights can only be rebound in onnx models".to_string(),
+            )
+            .into());
+        }
+        Ok(tract_onnx::onnx()
+            .model_for_read(&mut onnx.as_slice())
+            .map_err(|_| GraphError::ModelLoad)?)
+    };
+    #[cfg(feature = "tflite")]
+    if source.is_tflite() {
+        return frontend(super::tflite::to_onnx(&source.read()?)?);
+    }
+    #[cfg(feature = "nnef")]
+    if source.is_nnef() {
+        return frontend(super::nnef::to_onnx(source)?);
+    }
+    Ok(match (super::weights::resolve(source, weights)?, source) {
+        (Some(onnx), _) => tract_onnx::onnx().model_for_read(&mut onnx.as_slice()),
+        (None, ModelSource::Path(path)) => tract_onnx::onnx().model_for_path(path),
+        (None, ModelSource::Bytes(bytes)) => {
+            tract_onnx::onnx().model_for_read(&mut bytes.as_slice())
+        }
+    }
+    .map_err(|_| GraphError::ModelLoad)?)
+}
+
+/// Gives the inputs of `model` the concrete shapes of `input_shapes` (keyed by input index), and re-runs the analysis of the
+/// graph such that the facts of the nodes downstream of them (which were symbolic, or unknown) are concrete too. The dimensions
+/// the model declares concretely must be kept.
+fn concretize_inputs(
+    mut model: Graph<InferenceFact, Box<dyn InferenceOp>>,
+    input_shapes: &BTreeMap<usize, Vec<usize>>,
+) -> Result<Graph<InferenceFact, Box<dyn InferenceOp>>, Box<dyn Error>> {
+    if input_shapes.is_empty() {
+        return Ok(model);
+    }
+    let inputs: HashSet<usize> = model.inputs.iter().map(|o| o.node).collect();
+    for (i, shape) in input_shapes {
+        let declared = model.input_fact(*i)?.shape.clone();
+        let mismatch = || GraphError::InputShape(*i, shape.clone(), format!("{:?}", declared));
+        if let Some(rank) = declared.rank().concretize() {
+            if rank as usize != shape.len() {
+                return Err(Box::new(mismatch()));
+            }
+        }
+        for (dim, d) in declared.dims().zip(shape) {
+            if let Some(Ok(dim)) = dim.concretize().map(|dim| dim.to_usize()) {
+                if dim != *d {
+                    return Err(Box::new(mismatch()));
+                }
+            }
+        }
+        model = model.with_input_fact(*i, InferenceFact::dt_shape(f32::datum_type(), shape))?;
+    }
+    // facts left symbolic by the export are forgotten, and inferred again from the concrete inputs
+    for node in model.nodes.iter_mut().filter(|n| !inputs.contains(&n.id)) {
+        for output in node.outputs.iter_mut() {
+            if !matches!(output.fact.shape.as_concrete_finite(), Ok(Some(_))) {
+                output.fact.shape = ShapeFactoid::default();
+            }
+        }
+    }
+    model.analyse(false)?;
+    Ok(model)
+}
+
+/// Evaluates the nodes of `model` whose inputs are all constants (such as the `Shape`, `Gather` and `Mul` chains computing the
+/// shape fed to a reshape, or precomputed masks), and replaces each of them with a constant holding its output. Nodes are
+/// visited in evaluation order, so whole constant subgraphs fold down to their last node. The folded values are stored as the
+/// f32 or i64 constants nodes are converted from. Returns the number of folded nodes.
+fn fold_constants(
+    model: &mut Graph<InferenceFact, Box<dyn InferenceOp>>,
+) -> Result<usize, Box<dyn Error>> {
+    let mut folded = 0;
+    for id in model.eval_order()? {
+        let node = &model.nodes[id];
+        if node.inputs.is_empty() || node.outputs.len() != 1 || !node.op.is_stateless() {
+            continue;
+        }
+        let values = node
+            .inputs
+            .iter()
+            .map(|i| {
+                model.nodes[i.node]
+                    .op_as::<Const>()
+                    .map(|konst| konst.0.clone())
+            })
+            .collect::<Option<TVec<_>>>();
+        let values = match values {
+            Some(values) => values,
+            None => continue,
+        };
+        let value = match node.op.eval(values) {
+            Ok(mut outputs) => outputs.remove(0),
+            Err(e) => {
+                warn!("failed to fold node {} ({}): {}", id, node.name, e);
+                continue;
+            }
+        };
+        let value = match value.datum_type() {
+            DatumType::F32 | DatumType::I64 => value,
+            dt if dt.is_float() => value.cast_to::<f32>()?.into_owned().into(),
+            _ => value.cast_to::<i64>()?.into_owned().into(),
+        };
+        debug!("folding node {} ({}) into a constant", id, node.name);
+        let node = &mut model.nodes[id];
+        node.outputs[0].fact = InferenceFact::from(value.clone());
+        node.op = Box::new(Const(value));
+        node.inputs.clear();
+        folded += 1;
+    }
+    Ok(folded)
+}
+
+/// The outlet of `model` labelled `name` (as ONNX tensors are), or else the first output of the node named `name`.
+fn outlet_by_name(
+    model: &Graph<InferenceFact, Box<dyn InferenceOp>>,
+    name: &str,
+) -> Result<OutletId, GraphError> {
+    model
+        .outlet_labels
+        .iter()
+        .find(|(_, label)| label.as_str() == name)
+        .map(|(outlet, _)| *outlet)
+        .or_else(|| {
+            model
+                .nodes
+                .iter()
+                .find(|n| n.name == name)
+                .map(|n| OutletId::new(n.id, 0))
+        })
+        .ok_or_else(|| GraphError::MissingTensor(name.to_string()))
+}
+
+/// Restricts `model` to the subgraph between the tensors named `input_nodes` and `output_nodes` (either of which can be empty to
+/// keep the inputs or outputs of the model). Each cut input is replaced by a new source, of the same fact, feeding the consumers of
+/// the tensor, and the new sources become the inputs of the model. Nodes upstream of the cut are left disconnected from the
+/// outputs, to be pruned when the graph is converted. Returns whether the inputs of the model were cut.
+fn slice_graph(
+    model: &mut Graph<InferenceFact, Box<dyn InferenceOp>>,
+    input_nodes: &[String],
+    output_nodes: &[String],
+) -> Result<bool, Box<dyn Error>> {
+    if !output_nodes.is_empty() {
+        model.outputs = output_nodes
+            .iter()
+            .map(|name| outlet_by_name(model, name))
+            .collect::<Result<_, _>>()?;
+    }
+    if input_nodes.is_empty() {
+        return Ok(false);
+    }
+    let mut inputs = vec![];
+    for name in input_nodes {
+        let outlet = outlet_by_name(model, name)?;
+        let fact = model.outlet_fact(outlet)?.clone();
+        let consumers = model.outlet_successors(outlet).to_vec();
+        let source = model.add_source(format!("{}.cut", name), fact)?;
+        for inlet in consumers {
+            model.add_edge(source, inlet)?;
+        }
+        // the cut tensor may itself be an output of the (sliced) model
+        for output in model.outputs.iter_mut().filter(|o| **o == outlet) {
+            *output = source;
+        }
+        inputs.push(source);
+    }
+    model.inputs = inputs;
+    Ok(true)
+}
+
+/// The nodes of `model` contributing to its outputs, found by walking back from the outputs through the inputs of each node.
+/// The inputs of the model are always kept, as data files provide a value for each of them.
+fn live_nodes(model: &Graph<InferenceFact, Box<dyn InferenceOp>>) -> HashSet<usize> {
+    let mut live: HashSet<usize> = model.inputs.iter().map(|o| o.node).collect();
+    let mut stack: Vec<usize> = model.outputs.iter().map(|o| o.node).collect();
+    while let Some(id) = stack.pop() {
+        if live.insert(id) {
+            stack.extend(
+                model.nodes[id]
+                    .inputs
+                    .iter()
+                    .map(|i| i.node)
+                    .filter(|n| !live.contains(n)),
+            );
+        }
+    }
+    live
+}
+
+/// Splits the inputs of `model` into those its (converted) `nodes` use, and those they don't: inputs nothing consumes, and inputs
+/// an initializer overrides (which aren't converted as inputs). The nodes of the unused inputs are removed from `nodes`, and the
+/// unused inputs are returned by their position among the inputs of `model` and name.
+fn prune_inputs(
+    nodes: &mut BTreeMap<usize, Node>,
+    model: &Graph<InferenceFact, Box<dyn InferenceOp>>,
+) -> (Vec<OutletId>, Vec<(usize, String)>) {
+    let consumed: HashSet<usize> = nodes
+        .values()
+        .flat_map(|n| n.inputs.iter().map(|i| i.node))
+        .chain(model.outputs.iter().map(|o| o.node))
+        .collect();
+    let mut inputs = vec![];
+    let mut unused = vec![];
+    for (i, input) in model.inputs.iter().enumerate() {
+        let is_input = nodes
+            .get(&input.node)
+            .map_or(false, |n| n.opkind.is_input());
+        if is_input && consumed.contains(&input.node) {
+            inputs.push(*input);
+            continue;
+        }
+        let name = model.nodes[input.node].name.clone();
+        warn!(
+            "input {} ({}) is unused, and pruned from the model",
+            i, name
+        );
+        if is_input {
+            nodes.remove(&input.node);
+        }
+        unused.push((i, name));
+    }
+    (inputs, unused)
+}
+
+/// Fuses each lookup node fed by a lookup node which nothing else consumes (e.g a sigmoid followed by a rescaling division)
+/// into a single lookup of their composition, such that chains of elementwise lookups take a single table and region.
+/// Returns the number of nodes fused away.
+fn fuse_lookups(nodes: &mut BTreeMap<usize, Node>, outputs: &[OutletId]) -> usize {
+    let mut consumers = HashMap::<usize, usize>::new();
+    for node in nodes.values() {
+        for input in &node.inputs {
+            *consumers.entry(input.node).or_default() += 1;
+        }
+    }
+    for output in outputs {
+        *consumers.entry(output.node).or_default() += 1;
+    }
+
+    let mut fused = 0;
+    for idx in execution_order(nodes.values()) {
+        let input = match nodes.get(&idx) {
+            Some(Node {
+                opkind: OpKind::Lookup(_),
+                inputs,
+                ..
+            }) if inputs.len() == 1 => inputs[0].node,
+            _ => continue,
+        };
+        let fusable = nodes.get(&input).map_or(false, |n| n.opkind.is_lookup());
+        if !fusable || consumers.get(&input) != Some(&1) {
+            continue;
+        }
+        let producer = nodes.remove(&input).unwrap();
+        let node = nodes.get_mut(&idx).unwrap();
+        if let (OpKind::Lookup(first), OpKind::Lookup(second)) = (&producer.opkind, &node.opkind) {
+            debug!(
+                "fusing lookup node {} ({}) into node {} ({})",
+                producer.idx, first, idx, second
+            );
+            node.opkind = OpKind::Lookup(LookupOp::compose(&[first.clone(), second.clone()]));
+        }
+        node.inputs = producer.inputs;
+        node.in_dims = producer.in_dims;
+        node.in_scale = producer.in_scale;
+        fused += 1;
+    }
+    fused
+}
+
+/// The rescaling nodes inserted between nodes running at different scales as a model is converted (see [Node::rescale]), keyed by
+/// the outlet they rescale and the scale they rescale it to, such that the consumers of an outlet at the same scale share a node.
+struct Rescales {
+    next_idx: usize,
+    nodes: BTreeMap<(OutletId, i32), usize>,
+}
+
+impl Rescales {
+    /// No rescaling nodes, the first of which is to be indexed `first_idx`.
+    fn new(first_idx: usize) -> Self {
+        Rescales {
+            next_idx: first_idx,
+            nodes: BTreeMap::new(),
+        }
+    }
+
+    /// The number of rescaling nodes inserted.
+    fn inserted(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// The outlet of the node rescaling `outlet` to `scale`, which is inserted into `nodes` unless it already was.
+    fn get(&mut self, nodes: &mut BTreeMap<usize, Node>, outlet: OutletId, scale: i32) -> OutletId {
+        let idx = match self.nodes.get(&(outlet, scale)) {
+            Some(idx) => *idx,
+            None => {
+                let idx = self.next_idx;
+                self.next_idx += 1;
+                let node = Node::rescale(&nodes[&outlet.node], outlet, scale, idx);
+                nodes.insert(idx, node);
+                self.nodes.insert((outlet, scale), idx);
+                idx
+            }
+        };
+        OutletId::new(idx, 0)
+    }
+}
+
+/// Runs tract's `into_typed()` and `declutter()` over `model`, and carries what they resolve back onto its nodes: the (concrete)
+/// output facts of each node, and the constants its constant subgraphs fold into. Nodes computed from constants alone (e.g the
+/// shape fed to a reshape) become constants themselves, such that ezkl doesn't need to support the ops computing them. Nodes are
+/// matched with those of the typed model by name, and nodes fused away by decluttering are left as they are.
+fn optimize_graph(
+    mut model: Graph<InferenceFact, Box<dyn InferenceOp>>,
+) -> Result<Graph<InferenceFact, Box<dyn InferenceOp>>, Box<dyn Error>> {
+    let typed = model.clone().into_typed()?.into_decluttered()?;
+    for node in model.nodes.iter_mut() {
+        let typed_node = match typed.node_by_name(&node.name) {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+        if let Some(konst) = typed_node.op_as::<Const>() {
+            if !node.inputs.is_empty() {
+                debug!("folding node {} ({}) into a constant", node.id, node.name);
+                node.op = Box::new(konst.clone());
+                node.inputs.clear();
+            }
+        }
+        for (output, typed_output) in node.outputs.iter_mut().zip(typed_node.outputs.iter()) {
+            output.fact = InferenceFact::from(typed_output.fact.clone());
+        }
+    }
+    Ok(model)
+}
+
 /// Mode we're using the model in.
 #[derive(Clone, Debug)]
 pub enum Mode {
@@ -41,31 +411,87 @@ pub enum Mode {
     Table,
     /// Initialize the model and generate a mock proof
     Mock,
+    /// Initialize the model and generate the keys for its circuit
+    Setup,
     /// Initialize the model and generate a proof
     Prove,
     /// Initialize the model, generate a proof, and verify
     FullProve,
     /// Initialize the model and verify an already generated proof
     Verify,
+    /// Initialize the model and compare the outputs of the quantized model to those of the original model
+    Accuracy,
+    /// Initialize the model and run a forward pass on the quantized model
+    Forward,
+    /// Initialize the model and generate random inputs for it
+    GenInput,
+    /// Initialize the model and save its processed graph, see [CompiledModel]
+    Compile,
+    /// Initialize the model and hash the artifacts of its circuit
+    Hash,
+    /// Initialize the model and sign the inputs of a data file, as quantized for the model
+    Sign,
+    /// Initialize the model and build a Merkle tree over the inputs of a dataset, as quantized for the model
+    Merkle,
+    /// Initialize the model and benchmark proof generation
+    Bench,
+    /// Initialize the model and estimate the cost of its circuit, without proving
+    Estimate,
+    /// Initialize the model and render the layout of its circuit
+    Render,
+    /// Initialize the model and serve proofs over HTTP
+    Serve,
+    /// Initialize the model and record its output scales in saved settings
+    Settings,
+}
+
+/// An input or output of a model, by its position among the model's inputs or outputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Port {
+    /// The input at the given position
+    Input(usize),
+    /// The output at the given position
+    Output(usize),
 }
 
 /// A circuit configuration for the entirety of a model loaded from an Onnx file.
 #[derive(Clone, Debug)]
 pub struct ModelConfig<F: FieldExt + TensorType> {
-    configs: BTreeMap<usize, NodeConfig<F>>,
-    /// The model struct
-    pub model: Model,
+    /// The configs of the nodes of the graph, keyed by the index of the (last) node they lay out
+    pub configs: BTreeMap<usize, NodeConfig<F>>,
+    /// The model struct, shared rather than cloned such that the weights and graph of the model aren't duplicated by each
+    /// (clone of the) config
+    pub model: Arc<Model>,
     /// (optional) range checked outputs of the model graph
     pub public_outputs: Vec<RangeCheckConfig<F>>,
+    /// (optional) range checked public inputs of the model graph, when inputs are range checked
+    pub public_inputs: Vec<RangeCheckConfig<F>>,
+    /// (optional) compares the outputs of the model to constants, when claims about the outputs are proven
+    pub claims: Option<CompareConfig<F>>,
     /// A wrapper for holding all columns that will be assigned to by the model
     pub vars: ModelVars<F>,
+    /// (optional) hashes the params, inputs, or outputs of the model, when committing to params or when inputs or outputs are hashed
+    pub poseidon: Option<PoseidonConfig<F>>,
+    /// (optional) verifies the signature over the inputs of the model, when inputs are attested
+    pub eddsa: Option<EddsaConfig<F>>,
+    /// (optional) proves the inputs of each sample are part of a Merkle tree, when proving their inclusion
+    pub merkle: Option<MerkleConfig<F>>,
+    /// The proof (if any) the layout of the model reports its progress to, and stops laying out the model when it is cancelled
+    pub task: Option<Task>,
 }
 
 /// A struct for loading from an Onnx file and converting a computational graph to a circuit.
 #[derive(Clone, Debug)]
 pub struct Model {
-    /// The raw tract [Graph] data structure.
-    pub model: Graph<InferenceFact, Box<dyn InferenceOp>>,
+    /// The raw tract [Graph] data structure, absent for models loaded from a compiled graph (see [CompiledModel]).
+    pub model: Option<Graph<InferenceFact, Box<dyn InferenceOp>>>,
+    /// The outlets of the graph's inputs.
+    pub inputs: Vec<OutletId>,
+    /// The declared inputs of the graph which nothing consumes (or which an initializer overrides), by their position among the
+    /// declared inputs and name. They are pruned from `inputs`, and dropped from the data files that give them.
+    pub unused_inputs: Vec<(usize, String)>,
+    /// The outlets of the graph's outputs.
+    pub outputs: Vec<OutletId>,
     /// Graph of nodes we are loading from Onnx.
     pub nodes: NodeGraph, // Wrapped nodes with additional methods and data (e.g. inferred shape, quantization)
     /// bits used in lookup tables
@@ -76,21 +502,52 @@ pub struct Model {
     pub max_rotations: usize,
     /// Exponent used in the fixed point representation.
     pub scale: i32,
+    /// Per-node overrides of `scale`, keyed by node index.
+    pub layer_scales: BTreeMap<usize, i32>,
     /// The divergence from the expected output (if using public outputs) we can tolerate. This is in absolute value across each dimension.
     /// eg. for a tolerance of 1 and for a 2D output we could tolerate at most off by 1 errors for each of the 2 outputs.
     pub tolerance: usize,
+    /// Per-output overrides of the norm of the errors bounded by `tolerance`, see [ModelBuilder::tolerance_norms].
+    pub tolerance_norms: BTreeMap<usize, ToleranceNorm>,
     /// The [Mode] we're using the model in.
     pub mode: Mode,
     /// Defines which inputs to the model are public and private (params, inputs, outputs) using [VarVisibility].
     pub visibility: VarVisibility,
+    /// The number of samples proven in a single circuit, the model is laid out once per sample.
+    pub batch_size: usize,
+    /// Whether the params of the model are committed to, by exposing their Poseidon hash as a public input.
+    pub commit_params: bool,
+    /// Whether an EdDSA signature over the inputs is verified in-circuit, with the public key of the signer as a public input.
+    pub attested_inputs: bool,
+    /// The depth of the Merkle tree the inputs of each sample are proven to be a leaf of, with its root as a public input.
+    pub merkle_depth: Option<usize>,
+    /// The number of products per row of affine and matmul layers laid out as chunked dot products (see [DotConfig]), if any.
+    pub dot_chunk: Option<usize>,
+    /// Whether products with zero-valued params are left out of the circuit, see [ModelBuilder::sparse_params].
+    pub sparse_params: bool,
+    /// Whether the public inputs are range checked against a table, see [ModelBuilder::range_check_inputs].
+    pub range_check_inputs: bool,
+    /// The claims about the outputs of the model proven in-circuit, see [ModelBuilder::claims].
+    pub claims: Vec<Claim>,
+    /// Whether to show the progress of laying out the model (see `--progress`).
+    pub progress: bool,
+    /// Whether the results of the layout are dropped after their last use (see `--low-memory`).
+    pub low_memory: bool,
+    /// The directory lookup tables are cached in (see `--table-cache`), if any.
+    pub table_cache: Option<PathBuf>,
+    /// The quantized constants spilled to disk (see `--low-memory`), which nodes no longer hold.
+    pub consts: Option<Arc<ConstStore>>,
+    /// The custom ops nodes of the model are implemented with, see [CustomOps].
+    pub custom_ops: CustomOps,
 }
 
 impl Model {
-    /// Creates an `Model` from a specified path to an Onnx file.
+    /// Creates an `Model` from a specified path to an Onnx file. See [ModelBuilder] to only set some of these, with validation.
     /// # Arguments
     ///
     /// * `path` - A path to an Onnx file.
     /// * `scale` - The denominator used for fixed point arithmetic (relevant for quantizing input data and model parameters).
+    /// * `layer_scales` - Per-node overrides of `scale`, keyed by node index.
     /// * `bits` - Number of bits to use.
     /// * `logrows` -  Log rows available in circuit.
     /// * `max_rotations` - Maximum number of permitted rotations.
@@ -101,6 +558,7 @@ impl Model {
     pub fn new(
         path: impl AsRef<Path>,
         scale: i32,
+        layer_scales: BTreeMap<usize, i32>,
         bits: usize,
         logrows: u32,
         max_rotations: usize,
@@ -108,94 +566,411 @@ impl Model {
         mode: Mode,
         visibility: VarVisibility,
     ) -> Result<Self, Box<dyn Error>> {
-        let model = tract_onnx::onnx()
-            .model_for_path(path)
-            .map_err(|_| GraphError::ModelLoad)?;
-        info!("visibility: {}", visibility);
+        let model = ModelBuilder::new(path.as_ref())
+            .scale(scale)
+            .layer_scales(layer_scales)
+            .bits(bits)
+            .logrows(logrows)
+            .max_rotations(max_rotations)
+            .tolerance(tolerance)
+            .mode(mode)
+            .visibility(visibility)
+            .build()?;
+        Ok(model)
+    }
+
+    /// Loads the model of a (validated) [ModelBuilder], see [ModelBuilder::build].
+    pub(super) fn from_builder(builder: &ModelBuilder) -> Result<Self, Box<dyn Error>> {
+        let mut model = load_graph(&builder.source, builder.weights.as_deref())?;
+        let sliced = slice_graph(&mut model, &builder.input_nodes, &builder.output_nodes)?;
+        model = concretize_inputs(model, &builder.input_shapes)?;
+        if builder.passes.contains(&Pass::OptimizeGraph) {
+            model = optimize_graph(model)?;
+        }
+        if builder.passes.contains(&Pass::FoldConstants) {
+            let folded = fold_constants(&mut model)?;
+            info!("folded {} nodes into constants", folded);
+        }
+        info!("visibility: {}", builder.visibility);
+
+        // the nodes upstream of a cut are always pruned, as they aren't fed by the inputs of the model anymore
+        let live =
+            (sliced || builder.passes.contains(&Pass::PruneDeadNodes)).then(|| live_nodes(&model));
+        if let Some(live) = &live {
+            info!(
+                "pruned {} nodes not contributing to the outputs",
+                model.nodes.len() - live.len()
+            );
+        }
 
+        let fold_rescales = builder.passes.contains(&Pass::FoldRescales);
+        let scales = |i: usize| *builder.layer_scales.get(&i).unwrap_or(&builder.scale);
         let mut nodes = BTreeMap::<usize, Node>::new();
+        // rescaling nodes are indexed past the nodes of the model
+        let mut rescales = Rescales::new(model.nodes.len());
         for (i, n) in model.nodes.iter().enumerate() {
-            let n = Node::new(n.clone(), &mut nodes, scale, i)?;
+            if live.as_ref().map_or(false, |live| !live.contains(&i)) {
+                continue;
+            }
+            let n = Self::rescale_inputs(n, &mut nodes, &scales, fold_rescales, &mut rescales);
+            let n = Node::new(n, &mut nodes, scales(i), i, &builder.custom_ops)?;
             nodes.insert(i, n);
         }
-        let om = Model {
-            model: model.clone(),
-            scale,
-            tolerance,
-            nodes: Self::assign_execution_buckets(nodes)?,
-            bits,
-            logrows,
-            max_rotations,
-            mode,
-            visibility,
-        };
+        if rescales.inserted() > 0 {
+            info!(
+                "inserted {} nodes rescaling between layers of different scales",
+                rescales.inserted()
+            );
+        }
+        if builder.passes.contains(&Pass::FuseLookups) {
+            let fused = fuse_lookups(&mut nodes, &model.outputs);
+            info!("fused {} lookup nodes into the lookups they feed", fused);
+        }
+        let (inputs, unused_inputs) = prune_inputs(&mut nodes, &model);
+        let om = Self::assemble(
+            builder,
+            Self::assign_execution_buckets(nodes)?,
+            inputs,
+            unused_inputs,
+            model.outputs.clone(),
+            Some(model),
+        );
 
         debug!("{}", Table::new(om.nodes.flatten()).to_string());
 
         Ok(om)
     }
 
-    /// Creates a `Model` from parsed CLI arguments
-    pub fn from_ezkl_conf(args: Cli) -> Result<Self, Box<dyn Error>> {
-        let visibility = VarVisibility::from_args(args.clone())?;
-        match args.command {
-            Commands::Table { model } => Model::new(
-                model,
-                args.scale,
-                args.bits,
-                args.logrows,
-                args.max_rotations,
-                args.tolerance,
-                Mode::Table,
-                visibility,
-            ),
-            Commands::Mock { model, .. } => Model::new(
-                model,
-                args.scale,
-                args.bits,
-                args.logrows,
-                args.max_rotations,
-                args.tolerance,
-                Mode::Mock,
-                visibility,
-            ),
-            Commands::Fullprove { model, .. } => Model::new(
-                model,
-                args.scale,
-                args.bits,
-                args.logrows,
-                args.max_rotations,
-                args.tolerance,
-                Mode::FullProve,
-                visibility,
-            ),
-            Commands::Prove { model, .. } => Model::new(
-                model,
-                args.scale,
-                args.bits,
-                args.logrows,
-                args.max_rotations,
-                args.tolerance,
-                Mode::Prove,
-                visibility,
-            ),
-            Commands::Verify { model, .. } => Model::new(
-                model,
-                args.scale,
-                args.bits,
-                args.logrows,
-                args.max_rotations,
-                args.tolerance,
-                Mode::Verify,
-                visibility,
+    /// Loads the model of a (validated) [ModelBuilder] from its `compiled` graph, in place of the model file of the builder.
+    pub(super) fn from_compiled(builder: &ModelBuilder, compiled: CompiledModel) -> Self {
+        Self::assemble(
+            builder,
+            compiled.nodes,
+            compiled.inputs,
+            compiled.unused_inputs,
+            compiled.outputs,
+            None,
+        )
+    }
+
+    /// Assembles a model from its processed `nodes` and the settings of `builder`.
+    fn assemble(
+        builder: &ModelBuilder,
+        nodes: NodeGraph,
+        inputs: Vec<OutletId>,
+        unused_inputs: Vec<(usize, String)>,
+        outputs: Vec<OutletId>,
+        model: Option<Graph<InferenceFact, Box<dyn InferenceOp>>>,
+    ) -> Self {
+        let mut om = Model {
+            model,
+            inputs,
+            unused_inputs,
+            outputs,
+            scale: builder.scale,
+            layer_scales: builder.layer_scales.clone(),
+            tolerance: builder.tolerance,
+            tolerance_norms: builder.tolerance_norms.clone(),
+            nodes,
+            bits: builder.bits,
+            logrows: builder.logrows,
+            max_rotations: builder.max_rotations.unwrap_or(0),
+            mode: builder.mode.clone(),
+            visibility: builder.visibility.clone(),
+            batch_size: builder.batch_size,
+            commit_params: builder.commit_params,
+            attested_inputs: builder.attested_inputs,
+            merkle_depth: builder.merkle_depth,
+            dot_chunk: builder.dot_chunk,
+            sparse_params: builder.sparse_params,
+            range_check_inputs: builder.range_check_inputs,
+            claims: builder.claims.clone(),
+            progress: false,
+            low_memory: false,
+            table_cache: None,
+            consts: None,
+            custom_ops: builder.custom_ops.clone(),
+        };
+        match builder.max_rotations {
+            None => {
+                om.max_rotations = om.auto_rotations();
+                info!("laying out nodes over {} rotations", om.max_rotations);
+            }
+            Some(rotations) if rotations > om.rotation_bound() => warn!(
+                "columns of {} rotations don't fit in 2^{} rows alongside their blinding rows and lookup tables, at most {} do",
+                rotations,
+                om.logrows,
+                om.rotation_bound()
             ),
+            Some(_) => {}
+        }
+        om
+    }
+
+    /// Rescales the inputs of a node to the fixed point scale it runs at, returning the node fed by the rescaled inputs.
+    /// When the node runs at a lower scale than a lookup based producer (which has yet to be consumed), and `fold` is set, the
+    /// producer is re-targeted to output directly at the node's scale, so the rescale is folded into the existing lookup. Between
+    /// nodes configured at different scales (see [ModelBuilder::layer_scales]), other inputs are fed through a rescaling lookup
+    /// node (see [Node::rescale]), shared by the consumers of the input at the same scale: any input of a polynomial node at
+    /// another scale, and the inputs of lookup nodes at a lower scale, as lookups otherwise only rescale their inputs down.
+    /// # Arguments
+    ///
+    /// * `node` - The tract node about to be converted.
+    /// * `nodes` - Previously converted nodes, which the rescaling nodes are inserted into.
+    /// * `scales` - The fixed point scale each node is configured to run at, by index.
+    /// * `fold` - Whether to fold rescales into the lookups producing the inputs, see [Pass::FoldRescales].
+    /// * `rescales` - The rescaling nodes inserted so far.
+    fn rescale_inputs(
+        node: &OnnxNode<InferenceFact, Box<dyn InferenceOp>>,
+        nodes: &mut BTreeMap<usize, Node>,
+        scales: &impl Fn(usize) -> i32,
+        fold: bool,
+        rescales: &mut Rescales,
+    ) -> OnnxNode<InferenceFact, Box<dyn InferenceOp>> {
+        let mut node = node.clone();
+        let scale = scales(node.id);
+        let opkind = OpKind::new(node.op().name().as_ref());
+        for input in node.inputs.iter_mut() {
+            let already_consumed = nodes
+                .values()
+                .any(|n| n.inputs.iter().any(|i| i.node == input.node));
+            let producer = match nodes.get_mut(&input.node) {
+                Some(producer) if !producer.opkind.is_const() => producer,
+                _ => continue,
+            };
+            let mixed = scales(input.node) != scale;
+            let rescale = match &opkind {
+                OpKind::Poly(_) if mixed => producer.out_scale != scale,
+                OpKind::Poly(_) => producer.out_scale > scale,
+                // sigmoids take inputs at any scale
+                OpKind::Lookup(LookupOp::Sigmoid { .. }) => false,
+                OpKind::Lookup(_) => mixed && producer.out_scale < scale,
+                _ => false,
+            };
+            if !rescale {
+                continue;
+            }
+            if fold
+                && opkind.is_poly()
+                && producer.opkind.is_lookup()
+                && producer.out_scale > scale
+                && !already_consumed
+            {
+                producer.rescale_output(scale);
+                if producer.out_scale == scale {
+                    continue;
+                }
+            }
+            if !mixed {
+                if fold {
+                    warn!(
+                        "node {} ({}) outputs at scale {} but cannot be rescaled to {}",
+                        producer.idx, producer.opkind, producer.out_scale, scale
+                    );
+                }
+                continue;
+            }
+            *input = rescales.get(nodes, *input, scale);
+        }
+        node
+    }
+
+    /// Loads the model at `path` and restricts it to the chunk (if any) of its circuit `settings`.
+    /// # Arguments
+    ///
+    /// * `path` - A path to an Onnx file.
+    /// * `settings` - The settings which determine the shape of the model's circuit.
+    /// * `mode` - The [Mode] we're using the model in.
+    pub fn from_settings(
+        path: impl Into<ModelSource>,
+        settings: &CircuitSettings,
+        mode: Mode,
+    ) -> Result<Self, Box<dyn Error>> {
+        let builder = ModelBuilder::from_settings(path, settings)?.mode(mode);
+        Self::from_settings_builder(&builder, settings)
+    }
+
+    /// Builds the model of `builder`, started from circuit `settings`, and restricts it to the chunk (if any) of the settings.
+    fn from_settings_builder(
+        builder: &ModelBuilder,
+        settings: &CircuitSettings,
+    ) -> Result<Self, Box<dyn Error>> {
+        Self::for_settings(builder.build()?, settings)
+    }
+
+    /// Restricts a built `model` to the chunk (if any) of its circuit `settings`.
+    fn for_settings(mut model: Model, settings: &CircuitSettings) -> Result<Self, Box<dyn Error>> {
+        let chunk = settings.chunk;
+        // only the first chunk of a split model is fed the (signed) inputs of the model
+        model.attested_inputs = settings.attested_inputs && chunk.unwrap_or(0) == 0;
+        model.merkle_depth = settings.merkle_depth.filter(|_| chunk.unwrap_or(0) == 0);
+        match chunk {
+            Some(chunk) => model.chunk(&settings.cut_points, chunk),
+            None => Ok(model),
+        }
+    }
+
+    /// The builder of the model of [RunArgs].
+    pub(super) fn builder(args: &RunArgs) -> Result<ModelBuilder, Box<dyn Error>> {
+        Ok(
+            ModelBuilder::from_settings(args.model.clone(), &args.settings)?
+                .weights(args.weights.clone())
+                .mode(args.mode.clone())
+                .custom_ops(args.custom_ops.clone()),
+        )
+    }
+
+    /// Creates a `Model` from explicit [RunArgs], loading its compiled graph (see [CompiledModel]) rather than its model file
+    /// when `args.compiled` is set, or cloning the model already loaded when `args.prepared` is. See [Model::shared_from_run_args]
+    /// to share the model already loaded rather than cloning it.
+    pub fn from_run_args(args: &RunArgs) -> Result<Self, Box<dyn Error>> {
+        if let Some(prepared) = &args.prepared {
+            return Ok(Model::clone(prepared));
         }
+        let builder = Self::builder(args)?;
+        let mut model = match &args.compiled {
+            Some(path) => {
+                builder.validate()?;
+                let mut compiled = CompiledModel::load(path, args)?;
+                table_cache::preload(std::mem::take(&mut compiled.tables));
+                Self::for_settings(Self::from_compiled(&builder, compiled), &args.settings)?
+            }
+            None => Self::from_settings_builder(&builder, &args.settings)?,
+        };
+        model.progress = args.progress;
+        model.low_memory = args.low_memory;
+        model.table_cache = args.table_cache.clone();
+        if args.low_memory {
+            // the float model (and its weights) is only needed to compare against the quantized model
+            model.model = None;
+            model.spill_consts()?;
+        }
+        Ok(model)
+    }
+
+    /// Creates a `Model` from [RunArgs] as [Model::from_run_args] does, sharing the model already loaded when `args.prepared` is
+    /// set, such that configuring circuits and preparing their witnesses doesn't copy its nodes, weights and graph.
+    pub fn shared_from_run_args(args: &RunArgs) -> Result<Arc<Self>, Box<dyn Error>> {
+        match &args.prepared {
+            Some(prepared) => Ok(Arc::clone(prepared)),
+            None => Ok(Arc::new(Self::from_run_args(args)?)),
+        }
+    }
+
+    /// Moves the quantized constants of the model to a [ConstStore], from which they are loaded as the nodes consuming them are
+    /// laid out. The float constants the quantized ones were computed from are dropped.
+    fn spill_consts(&mut self) -> Result<(), Box<dyn Error>> {
+        let mut consts = BTreeMap::new();
+        for node in self.nodes.0.values_mut().flat_map(|b| b.values_mut()) {
+            if node.opkind.is_const() {
+                node.raw_const_value = None;
+                if let Some(value) = node.const_value.take() {
+                    consts.insert(node.idx, value);
+                }
+            }
+        }
+        if consts.is_empty() {
+            return Ok(());
+        }
+        let store = ConstStore::spill(&consts)?;
+        info!(
+            "spilled the constants of {} nodes ({}) to disk",
+            consts.len(),
+            memory::human(store.size() as u64)
+        );
+        self.consts = Some(Arc::new(store));
+        Ok(())
     }
 
-    /// Creates a `Model` based on CLI arguments
-    pub fn from_arg() -> Result<Self, Box<dyn Error>> {
-        let args = Cli::create();
-        Self::from_ezkl_conf(args)
+    /// Restricts the model to a single chunk of its execution buckets, such that models too large for a single circuit can be proven
+    /// one chunk at a time. The graph is split before each bucket in `cut_points`, so fused operations are never split between chunks.
+    /// The activations flowing into the chunk from earlier chunks become the inputs of the chunk, and the activations consumed by later
+    /// chunks (or which are outputs of the model) become its outputs. Both are made public, such that the proofs of each chunk can be linked
+    /// when aggregated.
+    /// # Arguments
+    ///
+    /// * `cut_points` - The (ascending) execution buckets to split the model before.
+    /// * `chunk` - The index of the chunk to restrict the model to.
+    pub fn chunk(&self, cut_points: &[usize], chunk: usize) -> Result<Self, Box<dyn Error>> {
+        if chunk > cut_points.len() || cut_points.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(Box::new(GraphError::InvalidChunk(chunk)));
+        }
+        let start = if chunk == 0 { 0 } else { cut_points[chunk - 1] };
+        let end = cut_points.get(chunk).cloned().unwrap_or(usize::MAX);
+
+        let all_nodes = self.nodes.flatten();
+        let chunk_nodes = all_nodes
+            .iter()
+            .filter(|n| !n.opkind.is_input())
+            .filter(|n| n.bucket.map_or(false, |b| b >= start && b < end))
+            .collect_vec();
+        if chunk_nodes.is_empty() {
+            return Err(Box::new(GraphError::InvalidChunk(chunk)));
+        }
+        let in_chunk: HashSet<usize> = chunk_nodes.iter().map(|n| n.idx).collect();
+
+        let consumed = chunk_nodes
+            .iter()
+            .flat_map(|n| n.inputs.iter().map(|i| i.node))
+            .filter(|i| !in_chunk.contains(i))
+            .unique()
+            .sorted()
+            .collect_vec();
+        let model_outputs: HashSet<usize> = self.outputs.iter().map(|o| o.node).collect();
+        let outputs = chunk_nodes
+            .iter()
+            .filter(|n| {
+                model_outputs.contains(&n.idx)
+                    || all_nodes.iter().any(|m| {
+                        !in_chunk.contains(&m.idx) && m.inputs.iter().any(|i| i.node == n.idx)
+                    })
+            })
+            .map(|n| n.idx)
+            .collect_vec();
+
+        let mut nodes = NodeGraph::new();
+        let mut inputs = vec![];
+        for idx in consumed {
+            let node = self.nodes.filter(idx);
+            if node.opkind.is_const() {
+                nodes.insert(None, idx, node);
+                continue;
+            }
+            // activations from earlier chunks are fed into this chunk as inputs
+            let input = Node {
+                opkind: OpKind::Input,
+                name: node.name.clone(),
+                in_dims: vec![node.out_dims.clone()],
+                out_dims: node.out_dims.clone(),
+                in_scale: node.out_scale,
+                out_scale: node.out_scale,
+                output_max: node.output_max,
+                idx,
+                bucket: Some(start),
+                ..Default::default()
+            };
+            nodes.insert(input.bucket, idx, input);
+            inputs.push(OutletId::new(idx, 0));
+        }
+        for node in chunk_nodes {
+            nodes.insert(node.bucket, node.idx, node.clone());
+        }
+        info!(
+            "chunk {} has inputs {:?} and outputs {:?}",
+            chunk,
+            inputs.iter().map(|i| i.node).collect_vec(),
+            outputs
+        );
+
+        let mut model = self.clone();
+        model.inputs = inputs;
+        model.outputs = outputs.iter().map(|o| OutletId::new(*o, 0)).collect();
+        model.nodes = nodes;
+        model.visibility.input = Visibility::Public;
+        model.visibility.output = Visibility::Public;
+        model.visibility.input_overrides.clear();
+        model.visibility.output_overrides.clear();
+        Ok(model)
     }
 
     /// Configures an `Model`. Does so one execution `bucket` at a time. Each bucket holds either:
@@ -205,14 +980,25 @@ impl Model {
     ///
     /// * `meta` - Halo2 ConstraintSystem.
     /// * `advices` - A `VarTensor` holding columns of advices. Must be sufficiently large to configure all the nodes loaded in `self.nodes`.
+    /// * `task` - The proof (if any) the layout of the model reports its progress to, see [RunArgs::task].
     pub fn configure<F: FieldExt + TensorType>(
-        &self,
+        self: Arc<Self>,
         meta: &mut ConstraintSystem<F>,
         vars: &mut ModelVars<F>,
+        task: Option<Task>,
     ) -> Result<ModelConfig<F>, Box<dyn Error>> {
         info!("configuring model");
         let mut results = BTreeMap::new();
-        let mut tables = BTreeMap::new();
+        let mut decomposed = self
+            .limb_bits()
+            .map(|limb_bits| DecomposedTables::configure(meta, self.bits, limb_bits));
+        let tables = match decomposed {
+            Some(_) => BTreeMap::new(),
+            None => self.conf_tables(meta),
+        };
+        let offsets = self.pack_regions(vars);
+        // a single config lays out all the chunked dot products
+        let mut dot = None;
 
         for (bucket, bucket_nodes) in self.nodes.0.iter() {
             trace!("configuring bucket: {:?}", bucket);
@@ -234,18 +1020,44 @@ impl Model {
 
             if !lookup_ops.is_empty() {
                 for (i, node) in lookup_ops {
-                    let config = self.conf_table(node, meta, vars, &mut tables)?;
+                    let vars = &mut vars.skip_cols(offsets[*i]);
+                    let config = self.conf_table(node, meta, vars, &tables, decomposed.as_mut())?;
                     results.insert(*i, config);
                 }
             }
 
-            // preserves ordering
-            let poly_ops: BTreeMap<&usize, &Node> = bucket_nodes
+            let custom_ops: BTreeMap<&usize, &Node> = bucket_nodes
                 .iter()
-                .filter(|(_, n)| n.opkind.is_poly())
+                .filter(|(_, n)| n.opkind.is_custom())
                 .collect();
+            for (i, node) in custom_ops {
+                let vars = &mut vars.skip_cols(offsets[*i]);
+                let config = self.conf_custom(node, meta, vars)?;
+                results.insert(*i, config);
+            }
+
+            let dot_ops: BTreeMap<&usize, &Node> = bucket_nodes
+                .iter()
+                .filter(|(_, n)| self.is_chunked(n))
+                .collect();
+            for (i, node) in dot_ops {
+                let config = dot.get_or_insert_with(|| {
+                    DotConfig::configure(
+                        meta,
+                        self.dot_chunk.unwrap(),
+                        (self.visibility.params.is_public(), false),
+                    )
+                });
+                let op = match &node.opkind {
+                    OpKind::Poly(op) => op.clone(),
+                    c => return Err(Box::new(GraphError::WrongMethod(node.idx, c.clone()))),
+                };
+                results.insert(*i, NodeConfig::Dot(config.clone(), op, node.inputs.clone()));
+            }
+
             // preserves ordering
-            if !poly_ops.is_empty() {
+            for poly_ops in self.fused_runs(bucket_nodes) {
+                let vars = &mut vars.skip_cols(offsets[*poly_ops.keys().max().unwrap()]);
                 let config = self.conf_poly_ops(&poly_ops, meta, vars)?;
                 results.insert(**poly_ops.keys().max().unwrap(), config);
 
@@ -258,46 +1070,222 @@ impl Model {
             }
         }
 
-        let mut public_outputs = vec![];
-        if self.visibility.output.is_public() {
-            public_outputs = self.range_check_outputs(meta, vars)
+        let public_outputs = self.range_check_outputs(meta, vars);
+        // input range checks and claims share a range table, as it spans 2^bits rows
+        let mut range_table = None;
+        let public_inputs = self.range_check_inputs(meta, vars, &mut range_table);
+        let claims = match self.claims.is_empty() {
+            true => None,
+            false => {
+                let table = range_table.get_or_insert_with(|| {
+                    Rc::new(RefCell::new(RangeTable::configure(meta, self.bits)))
+                });
+                Some(CompareConfig::configure(meta, table))
+            }
+        };
+
+        let poseidon = match self.commit_params
+            || self
+                .visibility
+                .any_hashed(self.num_inputs(), self.num_outputs())
+            || self.attested_inputs
+            || self.merkle_depth.is_some()
+        {
+            true => Some(PoseidonConfig::configure(meta)),
+            false => None,
         };
+        let eddsa = match self.attested_inputs {
+            true => Some(EddsaConfig::configure(meta)),
+            false => None,
+        };
+        let merkle = self.merkle_depth.map(|_| MerkleConfig::configure(meta));
 
         Ok(ModelConfig {
             configs: results,
-            model: self.clone(),
+            model: self,
             public_outputs,
+            public_inputs,
+            claims,
             vars: vars.clone(),
+            poseidon,
+            eddsa,
+            merkle,
+            task,
         })
     }
 
+    /// Whether `node` is laid out as chunked dot products (see [DotConfig]) rather than fused with the poly nodes of its
+    /// bucket: affine layers over matrix kernels and products of matrices, when `dot_chunk` is set. As public params are laid
+    /// out in the fixed columns of the left hand side of the products, the kernel (and bias) of chunked layers must then be
+    /// params, and their other side not.
+    fn is_chunked(&self, node: &Node) -> bool {
+        let is_const = |i: usize| {
+            node.inputs
+                .get(i)
+                .map_or(false, |o| self.nodes.filter(o.node).opkind.is_const())
+        };
+        let (lhs, rhs): (&[usize], usize) = match (&node.opkind, node.in_dims.as_slice()) {
+            (OpKind::Poly(PolyOp::Affine), [input, kernel, _])
+                if kernel.len() == 2 && (input.len() == 1 || input.len() == 2) =>
+            {
+                (&[1, 2], 0)
+            }
+            (OpKind::Poly(PolyOp::Matmul), [a, b]) if a.len() == 2 && b.len() == 2 => (&[0], 1),
+            _ => return false,
+        };
+        self.dot_chunk.is_some()
+            && (!self.visibility.params.is_public()
+                || (lhs.iter().all(|i| is_const(*i)) && !is_const(rhs)))
+    }
+
+    /// The poly nodes of `bucket_nodes` fused into a single gate each: the runs of poly nodes between those laid out as
+    /// chunked dot products (see [Model::is_chunked]).
+    fn fused_runs<'a>(
+        &self,
+        bucket_nodes: &'a BTreeMap<usize, Node>,
+    ) -> Vec<BTreeMap<&'a usize, &'a Node>> {
+        let mut runs = vec![BTreeMap::new()];
+        for (idx, node) in bucket_nodes.iter().filter(|(_, n)| n.opkind.is_poly()) {
+            match self.is_chunked(node) {
+                true => runs.push(BTreeMap::new()),
+                false => {
+                    runs.last_mut().unwrap().insert(idx, node);
+                }
+            }
+        }
+        runs.retain(|run| !run.is_empty());
+        runs
+    }
+
+    /// Assigns the region of each node (or fused run of poly nodes, keyed by its last node) a column offset within the
+    /// [ModelVars], such that regions spanning fewer columns than the vars hold are packed into the same rows across
+    /// unused columns, rather than each taking rows of their own. Regions are placed in the order they are laid out, each at
+    /// the offset where they can start at the earliest row, mirroring how the floor planner places regions over their
+    /// columns.
+    fn pack_regions<F: FieldExt + TensorType>(
+        &self,
+        vars: &ModelVars<F>,
+    ) -> BTreeMap<usize, usize> {
+        let (col_size, num_cols) = match vars.advices.first() {
+            Some(v) => (max(v.col_size(), 1), v.num_cols()),
+            None => (1, 1),
+        };
+        let size = |node: &Node| {
+            node.in_dims
+                .iter()
+                .chain(std::iter::once(&node.out_dims))
+                .map(|d| d.iter().product::<usize>())
+                .max()
+                .unwrap_or(0)
+        };
+        let mut regions = BTreeMap::new();
+        for bucket_nodes in self.nodes.0.values() {
+            for (idx, node) in bucket_nodes {
+                if node.opkind.is_lookup() || node.opkind.is_custom() {
+                    let spread = node.opkind.is_lookup() && self.limb_bits().is_none();
+                    regions.insert(*idx, (size(node), spread));
+                }
+            }
+            // chunked dot products are laid out in columns of their own
+            for run in self.fused_runs(bucket_nodes) {
+                let run_size = run.values().map(|n| size(n)).max().unwrap_or(0);
+                regions.insert(**run.keys().max().unwrap(), (run_size, false));
+            }
+        }
+
+        // the row each column is used up to
+        let mut ends = vec![0; num_cols];
+        let mut offsets = BTreeMap::new();
+        for (idx, (size, spread)) in self
+            .nodes
+            .execution_order()
+            .into_iter()
+            .filter_map(|idx| regions.get(&idx).map(|region| (idx, *region)))
+        {
+            let cols = min(max(1, (size + col_size - 1) / col_size), num_cols);
+            // lookups (unless decomposed) spread their elements evenly over the columns they take
+            let rows = match spread {
+                true => (size + cols - 1) / cols,
+                false => min(size, col_size),
+            };
+            let (offset, start) = (0..=num_cols - cols)
+                .map(|o| (o, ends[o..o + cols].iter().copied().max().unwrap_or(0)))
+                .min_by_key(|(_, start)| *start)
+                .unwrap_or((0, 0));
+            for end in &mut ends[offset..offset + cols] {
+                *end = start + rows;
+            }
+            offsets.insert(idx, offset);
+        }
+        debug!(
+            "packed regions span {} rows",
+            ends.iter().max().unwrap_or(&0)
+        );
+        offsets
+    }
+
     fn range_check_outputs<F: FieldExt + TensorType>(
         &self,
         meta: &mut ConstraintSystem<F>,
         vars: &mut ModelVars<F>,
     ) -> Vec<RangeCheckConfig<F>> {
         let mut configs = vec![];
-        let output_nodes = self.model.outputs.clone();
-        let output_shapes = output_nodes
-            .iter()
-            .map(|o| self.nodes.filter(o.node).out_dims)
+        // only public outputs are range checked against the instances
+        let output_shapes = self
+            .output_shapes()
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| self.visibility.output_at(*i).is_public())
             .collect_vec();
 
         info!("output_shapes {:?}", output_shapes);
 
-        for s in &output_shapes {
+        for (i, s) in &output_shapes {
             let input = vars.advices[0].reshape(s);
             let output = vars.advices[1].reshape(s);
+            // absolute errors are witnessed in the third var, which `num_vars` accounts for
+            let abs = vars.advices.get(2).map(|a| a.reshape(s));
 
-            configs.push(RangeCheckConfig::configure(
+            configs.push(RangeCheckConfig::configure_norm(
                 meta,
                 &input,
                 &output,
+                abs.as_ref(),
+                self.tolerance_norm(*i),
                 self.tolerance,
             ));
         }
         configs
     }
+
+    /// Configures a range check of each public input against a table of the range of the lookup tables, when inputs are range
+    /// checked, such that instances outside of it fail to verify rather than being fed to the model. The table is configured
+    /// into `range_table` on first use.
+    fn range_check_inputs<F: FieldExt + TensorType>(
+        &self,
+        meta: &mut ConstraintSystem<F>,
+        vars: &ModelVars<F>,
+        range_table: &mut Option<Rc<RefCell<RangeTable<F>>>>,
+    ) -> Vec<RangeCheckConfig<F>> {
+        let input_shapes = self
+            .input_shapes()
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| self.visibility.input_at(*i).is_public())
+            .map(|(_, s)| s)
+            .collect_vec();
+        if !self.range_check_inputs || input_shapes.is_empty() {
+            return vec![];
+        }
+        let table = range_table
+            .get_or_insert_with(|| Rc::new(RefCell::new(RangeTable::configure(meta, self.bits))))
+            .clone();
+        input_shapes
+            .iter()
+            .map(|s| RangeCheckConfig::configure_table(meta, &vars.advices[0].reshape(s), &table))
+            .collect()
+    }
+
     /// Configures non op related nodes (eg. representing an input or const value)
     pub fn conf_non_op_node<F: FieldExt + TensorType>(
         &self,
@@ -335,7 +1323,7 @@ impl Model {
         meta: &mut ConstraintSystem<F>,
         vars: &mut ModelVars<F>,
     ) -> Result<NodeConfig<F>, Box<dyn Error>> {
-        let mut input_nodes: BTreeMap<(&usize, &PolyOp), Vec<Node>> = BTreeMap::new();
+        let mut input_nodes: BTreeMap<(&usize, &PolyOp), Vec<(OutletId, Node)>> = BTreeMap::new();
 
         for (i, e) in nodes.iter() {
             let key = (
@@ -350,7 +1338,7 @@ impl Model {
             let value = e
                 .inputs
                 .iter()
-                .map(|i| self.nodes.filter(i.node))
+                .map(|i| (*i, self.nodes.filter(i.node)))
                 .collect_vec();
             input_nodes.insert(key, value);
         }
@@ -362,19 +1350,19 @@ impl Model {
         let mut advice_idx = 0;
         let mut fixed_idx = 0;
         // impose an execution order here
-        let inputs_to_layer: Vec<(usize, VarTensor)> = input_nodes
+        let inputs_to_layer: Vec<(OutletId, VarTensor)> = input_nodes
             .iter()
             .flat_map(|x| {
                 x.1.iter()
-                    .filter(|i| !nodes.contains_key(&i.idx) && seen.insert(i.idx))
-                    .map(|f| {
-                        let s = f.out_dims.clone();
+                    .filter(|(o, i)| !nodes.contains_key(&i.idx) && seen.insert(*o))
+                    .map(|(o, f)| {
+                        let s = f.outlet_dims(o.slot).unwrap_or(&f.out_dims).to_vec();
                         if f.opkind.is_const() && self.visibility.params.is_public() {
-                            let vars = (f.idx, vars.fixed[fixed_idx].reshape(&s));
+                            let vars = (*o, vars.fixed[fixed_idx].reshape(&s));
                             fixed_idx += 1;
                             vars
                         } else {
-                            let vars = (f.idx, vars.advices[advice_idx].reshape(&s));
+                            let vars = (*o, vars.advices[advice_idx].reshape(&s));
                             advice_idx += 1;
                             vars
                         }
@@ -393,10 +1381,10 @@ impl Model {
             .map(|(op, e)| {
                 let order = e
                     .iter()
-                    .map(|n| {
+                    .map(|(o, n)| {
                         if !nodes.contains_key(&n.idx) {
                             PolyInputType::Input(
-                                inputs_to_layer.iter().position(|r| r.0 == n.idx).unwrap(),
+                                inputs_to_layer.iter().position(|r| r.0 == *o).unwrap(),
                             )
                         } else {
                             inter_counter += 1;
@@ -412,19 +1400,89 @@ impl Model {
             .collect_vec();
 
         let inputs = inputs_to_layer.iter();
+        let zeros = inputs
+            .clone()
+            .map(|x| self.zero_params(x.0.node))
+            .collect_vec();
 
         let config = NodeConfig::Poly(
-            PolyConfig::configure(
+            PolyConfig::configure_sparse(
                 meta,
                 &inputs.clone().map(|x| x.1.clone()).collect_vec(),
                 output,
                 &fused_nodes,
+                &zeros,
             ),
             inputs.map(|x| x.0).collect_vec(),
         );
         Ok(config)
     }
 
+    /// Configures the lookup tables of the lookup nodes of the model, returning the table each node looks up (keyed by node
+    /// index), along with the tag of its function within the table if the table is shared. Ops which compute the same function
+    /// over the domain of the tables (e.g a ReLU and a LeakyReLU of zero slope) look up the same rows, and distinct functions are packed
+    /// into shared tables holding as many functions as fit within the rows of the circuit.
+    /// # Arguments
+    ///
+    /// * `meta` - Halo2 ConstraintSystem.
+    fn conf_tables<F: FieldExt + TensorType>(
+        &self,
+        meta: &mut ConstraintSystem<F>,
+    ) -> BTreeMap<usize, (Rc<RefCell<LookupTable<F>>>, Option<usize>)> {
+        // the distinct functions, keyed by their evaluations over the domain, and the function each node computes
+        let mut functions: Vec<(Arc<Vec<i32>>, Vec<LookupOp>)> = vec![];
+        let mut node_functions = BTreeMap::new();
+        for node in self.nodes.flatten() {
+            let op = match &node.opkind {
+                OpKind::Lookup(op) => vec![op.clone()],
+                _ => continue,
+            };
+            let evals = table_cache::evaluations(&op, self.bits, self.table_cache.as_deref());
+            let i = match functions.iter().position(|(e, _)| *e == evals) {
+                Some(i) => i,
+                None => {
+                    functions.push((evals, op));
+                    functions.len() - 1
+                }
+            };
+            node_functions.insert(node.idx, i);
+        }
+
+        // halo2 reserves the last rows of the circuit for blinding factors, which grow with the number of queries of an advice
+        // column (at most `max_rotations`), and shared tables start with a zero row
+        let blinding_rows = max(3, self.max_rotations) + 3;
+        let rows = (1usize << self.logrows).saturating_sub(blinding_rows + 1);
+        let capacity = max(1, rows >> self.bits);
+        let mut tables = vec![];
+        for group in functions.chunks(capacity) {
+            let table = match group {
+                [(_, op)] => LookupTable::configure(meta, self.bits, op),
+                _ => LookupTable::configure_shared(
+                    meta,
+                    self.bits,
+                    group.iter().map(|(_, op)| op.clone()).collect(),
+                ),
+            }
+            .cached(self.table_cache.clone());
+            let shared = group.len() > 1;
+            let table = Rc::new(RefCell::new(table));
+            for i in 0..group.len() {
+                tables.push((table.clone(), if shared { Some(i + 1) } else { None }));
+            }
+        }
+        debug!(
+            "{} lookup nodes share {} distinct functions over {} tables",
+            node_functions.len(),
+            functions.len(),
+            functions.chunks(capacity).len()
+        );
+
+        node_functions
+            .into_iter()
+            .map(|(idx, i)| (idx, tables[i].clone()))
+            .collect()
+    }
+
     /// Configures a lookup table based operation. These correspond to operations that are represented in
     /// the `circuit::eltwise` module.
     /// # Arguments
@@ -432,17 +1490,20 @@ impl Model {
     /// * `node` - The [Node] must represent a lookup based op.
     /// * `meta` - Halo2 ConstraintSystem.
     /// * `advices` - A `VarTensor` holding columns of advices. Must be sufficiently large to configure the passed `node`.
+    /// * `tables` - The table each lookup node looks up, see [Model::conf_tables].
+    /// * `decomposed` - The tables of decomposed lookups, when the lookup tables don't fit in the circuit (see [Model::limb_bits]).
     fn conf_table<F: FieldExt + TensorType>(
         &self,
         node: &Node,
         meta: &mut ConstraintSystem<F>,
         vars: &mut ModelVars<F>,
-        tables: &mut BTreeMap<Vec<LookupOp>, Rc<RefCell<LookupTable<F>>>>,
+        tables: &BTreeMap<usize, (Rc<RefCell<LookupTable<F>>>, Option<usize>)>,
+        decomposed: Option<&mut DecomposedTables<F>>,
     ) -> Result<NodeConfig<F>, Box<dyn Error>> {
         let input_len = node.in_dims[0].iter().product();
         let input = &vars.advices[0].reshape(&[input_len]);
         let output = &vars.advices[1].reshape(&[input_len]);
-        let node_inputs = node.inputs.iter().map(|e| e.node).collect();
+        let node_inputs = node.inputs.clone();
 
         let op = match &node.opkind {
             OpKind::Lookup(l) => l,
@@ -451,19 +1512,67 @@ impl Model {
             }
         };
 
-        let config =
-            if let std::collections::btree_map::Entry::Vacant(e) = tables.entry(vec![op.clone()]) {
-                let conf: LookupConfig<F> =
-                    LookupConfig::configure(meta, input, output, self.bits, &[op.clone()]);
-                e.insert(conf.table.clone());
-                NodeConfig::Lookup(conf, node_inputs)
-            } else {
-                let table = tables.get(&vec![op.clone()]).unwrap();
-                let conf: LookupConfig<F> =
+        if let Some(decomposed) = decomposed {
+            let vars = vars.advices[..DECOMPOSED_VARS]
+                .iter()
+                .map(|v| v.reshape(&[input_len]))
+                .collect_vec();
+            let conf = DecomposedConfig::configure(meta, &vars, decomposed, &[op.clone()])?;
+            return Ok(NodeConfig::Decomposed(conf, node_inputs));
+        }
+
+        let conf: LookupConfig<F> = match tables.get(&node.idx) {
+            Some((table, Some(tag))) => LookupConfig::configure_shared(
+                meta,
+                input,
+                output,
+                table.clone(),
+                *tag,
+                &[op.clone()],
+            )?,
+            // the table may hold an equal function computed by different ops, so the node's own ops compute its outputs
+            Some((table, None)) => {
+                let mut conf =
                     LookupConfig::configure_with_table(meta, input, output, table.clone());
-                NodeConfig::Lookup(conf, node_inputs)
-            };
-        Ok(config)
+                conf.nonlinearities = vec![op.clone()];
+                conf
+            }
+            None => LookupConfig::configure(meta, input, output, self.bits, &[op.clone()]),
+        };
+        Ok(NodeConfig::Lookup(conf, node_inputs))
+    }
+
+    /// Configures a custom op, with the [CustomOp] registered for the field of the circuit. Each input of the node is assigned
+    /// to its own column of advices, followed by the output.
+    /// # Arguments
+    ///
+    /// * `node` - The [Node] must represent a custom op.
+    /// * `meta` - Halo2 ConstraintSystem.
+    /// * `vars` - A `VarTensor` holding columns of advices. Must be sufficiently large to configure the passed `node`.
+    fn conf_custom<F: FieldExt + TensorType>(
+        &self,
+        node: &Node,
+        meta: &mut ConstraintSystem<F>,
+        vars: &mut ModelVars<F>,
+    ) -> Result<NodeConfig<F>, Box<dyn Error>> {
+        let op = match &node.opkind {
+            OpKind::Custom(name) => self.custom_ops.op::<F>(name)?,
+            c => {
+                return Err(Box::new(GraphError::WrongMethod(node.idx, c.clone())));
+            }
+        };
+        let inputs = node
+            .in_dims
+            .iter()
+            .enumerate()
+            .map(|(i, dims)| vars.advices[i].reshape(dims))
+            .collect_vec();
+        let output = vars.advices[inputs.len()].reshape(&node.out_dims);
+        let node_inputs = node.inputs.clone();
+        Ok(NodeConfig::Custom(
+            op.configure(meta, &inputs, &output),
+            node_inputs,
+        ))
     }
 
     /// Assigns values to the regions created when calling `configure`.
@@ -471,60 +1580,459 @@ impl Model {
     ///
     /// * `config` - [ModelConfig] holding all node configs.
     /// * `layouter` - Halo2 Layouter.
-    /// * `inputs` - The values to feed into the circuit.
+    /// * `inputs` - The values to feed into the circuit, for each sample of the batch in turn.
+    /// * `input_key` - The key the inputs are committed to with, when inputs are committed to.
+    /// * `input_signature` - The signature over the inputs, when inputs are attested. Unknown when generating keys.
+    /// * `input_merkle_paths` - The Merkle path of the inputs of each sample, when proving their inclusion. Empty when generating keys.
+    /// * `vars` - The columns assigned to by the model.
+    #[allow(clippy::too_many_arguments)]
     pub fn layout<F: FieldExt + TensorType>(
         &self,
         config: ModelConfig<F>,
         layouter: &mut impl Layouter<F>,
         inputs: &[ValTensor<F>],
+        input_key: Option<&ValTensor<F>>,
+        input_signature: Option<&Signature<F>>,
+        input_merkle_paths: &[MerklePath<F>],
         vars: &ModelVars<F>,
     ) -> Result<(), Box<dyn Error>> {
         info!("model layout");
-        let mut results = BTreeMap::<usize, ValTensor<F>>::new();
-        for i in inputs.iter().enumerate() {
-            if self.visibility.input.is_public() {
-                results.insert(i.0, vars.instances[i.0].clone());
-            } else {
-                results.insert(i.0, i.1.clone());
-            }
-        }
-        for (idx, config) in config.configs.iter() {
-            if let Some(vt) = self.layout_config(layouter, &mut results, config)? {
-                // we get the max as for fused nodes this corresponds to the node output
-                results.insert(*idx, vt);
+        // the params are assigned (and hashed) once, and copied into the nodes of each sample
+        let params = match self.commit_params {
+            true => self.layout_params_commitment(&config, layouter, vars)?,
+            false => BTreeMap::new(),
+        };
+        // each sample in a batch is laid out separately, with its own inputs and instances
+        let num_instances = self.sample_instance_shapes().len();
+        let mut attested = vec![];
+        let progress = progress::bar(
+            self.progress,
+            "model layout",
+            (config.configs.len() * self.batch_size) as u64,
+        );
+        for (sample, inputs) in inputs.chunks(self.num_inputs()).enumerate() {
+            trace!("laying out sample {}", sample);
+            let instances = &vars.instances[sample * num_instances..(sample + 1) * num_instances];
+            let cells = self.layout_sample(
+                &config, layouter, inputs, input_key, instances, &params, &progress,
+            )?;
+            if let Some(depth) = self.merkle_depth {
+                let path = input_merkle_paths.get(sample);
+                self.layout_inclusion(&config, layouter, &cells, path, depth, vars)?;
+            }
+            attested.extend(cells);
+        }
+        progress.finish_and_clear();
+        if self.attested_inputs {
+            self.layout_attestation(&config, layouter, &attested, input_signature, vars)?;
+        }
+        if let Some(task) = &config.task {
+            task.report(Phase::Proving);
+        }
+        info!("computing...");
+        Ok(())
+    }
+
+    /// Assigns the params of the model, and hashes them with Poseidon such that the digest is constrained to equal the last instance.
+    /// Returns the assigned params keyed by node index, for nodes to copy from.
+    /// # Arguments
+    ///
+    /// * `config` - [ModelConfig] holding the Poseidon config.
+    /// * `layouter` - Halo2 Layouter.
+    /// * `vars` - The columns assigned to by the model.
+    fn layout_params_commitment<F: FieldExt + TensorType>(
+        &self,
+        config: &ModelConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        vars: &ModelVars<F>,
+    ) -> Result<BTreeMap<usize, ValTensor<F>>, Box<dyn Error>> {
+        let nodes = self.const_nodes();
+        let values = nodes
+            .iter()
+            .map(|idx| {
+                let value: Tensor<Value<F>> = self.const_value(*idx)?.into();
+                Ok((self.node_label(*idx), value.into()))
+            })
+            .collect::<Result<Vec<(String, ValTensor<F>)>, GraphError>>()?;
+        // the commitment follows on from the instances of every sample
+        let instance = vars
+            .instances
+            .get(self.batch_size * self.sample_instance_shapes().len())
+            .ok_or_else(|| GraphError::MissingParams("params commitment instance".to_string()))?;
+        let assigned = self.layout_hashed(config, layouter, &values, instance)?;
+        Ok(nodes.into_iter().zip(assigned).collect())
+    }
+
+    /// Assigns `values` in turn, and hashes them with Poseidon such that the digest is constrained to equal the limbs held by `instance`.
+    /// Values which were previously assigned are copied. Returns the assigned values, for nodes to copy from.
+    /// # Arguments
+    ///
+    /// * `config` - [ModelConfig] holding the Poseidon config.
+    /// * `layouter` - Halo2 Layouter.
+    /// * `values` - The values to hash, each labelled by the node they are the output of.
+    /// * `instance` - The instance holding the limbs of the digest.
+    fn layout_hashed<F: FieldExt + TensorType>(
+        &self,
+        config: &ModelConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        values: &[(String, ValTensor<F>)],
+        instance: &ValTensor<F>,
+    ) -> Result<Vec<ValTensor<F>>, Box<dyn Error>> {
+        let poseidon = config
+            .poseidon
+            .as_ref()
+            .expect("poseidon is configured when hashing");
+        let instance = instance_column(instance)?;
+        let (assigned, cells) = self.assign_values(config, layouter, values, "hashed")?;
+        poseidon.layout(layouter, &cells, instance)?;
+        Ok(assigned)
+    }
+
+    /// Assigns `values` in turn, in regions named after their label and `purpose`. Values which were previously assigned are copied.
+    /// Returns the assigned values, alongside all of their cells in order.
+    fn assign_values<F: FieldExt + TensorType>(
+        &self,
+        config: &ModelConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        values: &[(String, ValTensor<F>)],
+        purpose: &str,
+    ) -> Result<(Vec<ValTensor<F>>, Vec<AssignedCell<F, F>>), Box<dyn Error>> {
+        let mut assigned = vec![];
+        let mut cells = vec![];
+        for (label, value) in values {
+            let var = config.vars.advices[0].reshape(value.dims());
+            let t = layouter.assign_region(
+                || format!("{}: {}", label, purpose),
+                |mut region| var.assign(&mut region, 0, value),
+            )?;
+            cells.extend(t.iter().cloned());
+            assigned.push(ValTensor::from(t));
+        }
+        Ok((assigned, cells))
+    }
+
+    /// Hashes the (previously assigned) inputs of a sample with Poseidon, and proves the digest is a leaf of the Merkle tree whose root
+    /// is constrained to equal the limbs held by the instance following on from the params commitment.
+    /// # Arguments
+    ///
+    /// * `config` - [ModelConfig] holding the Poseidon and Merkle configs.
+    /// * `layouter` - Halo2 Layouter.
+    /// * `inputs` - The cells of the inputs of the sample, in order.
+    /// * `path` - The path from the leaf to the root, unknown when generating keys.
+    /// * `depth` - The depth of the tree.
+    /// * `vars` - The columns assigned to by the model.
+    fn layout_inclusion<F: FieldExt + TensorType>(
+        &self,
+        config: &ModelConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        inputs: &[AssignedCell<F, F>],
+        path: Option<&MerklePath<F>>,
+        depth: usize,
+        vars: &ModelVars<F>,
+    ) -> Result<(), Box<dyn Error>> {
+        let (poseidon, merkle) = match (&config.poseidon, &config.merkle) {
+            (Some(poseidon), Some(merkle)) => (poseidon, merkle),
+            _ => panic!("poseidon and merkle are configured when proving inclusion"),
+        };
+        let index =
+            self.batch_size * self.sample_instance_shapes().len() + self.commit_params as usize;
+        let root = vars
+            .instances
+            .get(index)
+            .ok_or_else(|| GraphError::MissingParams("merkle root instance".to_string()))?;
+        let leaf = poseidon.hash(layouter, inputs)?;
+        let path = match path {
+            Some(path) => Value::known(path),
+            None => Value::unknown(),
+        };
+        merkle.layout(
+            layouter,
+            poseidon,
+            &leaf,
+            path,
+            depth,
+            instance_column(root)?,
+        )?;
+        Ok(())
+    }
+
+    /// Hashes the (previously assigned) inputs of every sample with Poseidon, and verifies the signature over the digest.
+    /// The public key of the signer is constrained to equal the limbs held by the last two instances.
+    /// # Arguments
+    ///
+    /// * `config` - [ModelConfig] holding the Poseidon and EdDSA configs.
+    /// * `layouter` - Halo2 Layouter.
+    /// * `inputs` - The cells of the inputs of every sample, in order.
+    /// * `signature` - The signature over the inputs, unknown when generating keys.
+    /// * `vars` - The columns assigned to by the model.
+    fn layout_attestation<F: FieldExt + TensorType>(
+        &self,
+        config: &ModelConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        inputs: &[AssignedCell<F, F>],
+        signature: Option<&Signature<F>>,
+        vars: &ModelVars<F>,
+    ) -> Result<(), Box<dyn Error>> {
+        let (poseidon, eddsa) = match (&config.poseidon, &config.eddsa) {
+            (Some(poseidon), Some(eddsa)) => (poseidon, eddsa),
+            _ => panic!("poseidon and eddsa are configured when inputs are attested"),
+        };
+        let public_key = match &vars.instances[vars.instances.len().saturating_sub(2)..] {
+            [x, y] => [instance_column(x)?, instance_column(y)?],
+            _ => {
+                return Err(Box::new(GraphError::MissingParams(
+                    "public key instances".to_string(),
+                )))
+            }
+        };
+        let message = poseidon.hash(layouter, inputs)?;
+        let signature = match signature {
+            Some(signature) => Value::known(signature),
+            None => Value::unknown(),
+        };
+        eddsa.layout(layouter, poseidon, &message, signature, public_key)?;
+        Ok(())
+    }
+
+    /// Assigns the values of a single sample to the regions created when calling `configure`.
+    /// # Arguments
+    ///
+    /// * `config` - [ModelConfig] holding all node configs.
+    /// * `layouter` - Halo2 Layouter.
+    /// * `inputs` - The values to feed into the circuit.
+    /// * `input_key` - The key the inputs are committed to with, when inputs are committed to.
+    /// * `instances` - The instances holding the public inputs and outputs of the sample.
+    /// * `params` - Previously assigned params, keyed by node index, which are copied rather than assigned anew.
+    /// * `progress` - The bar advanced as each node is laid out.
+    ///
+    /// Returns the cells of the inputs when they are hashed after the fact (when attested or proven to be part of a Merkle tree),
+    /// and nothing otherwise.
+    #[allow(clippy::too_many_arguments)]
+    fn layout_sample<F: FieldExt + TensorType>(
+        &self,
+        config: &ModelConfig<F>,
+        layouter: &mut impl Layouter<F>,
+        inputs: &[ValTensor<F>],
+        input_key: Option<&ValTensor<F>>,
+        instances: &[ValTensor<F>],
+        params: &BTreeMap<usize, ValTensor<F>>,
+        progress: &ProgressBar,
+    ) -> Result<Vec<AssignedCell<F, F>>, Box<dyn Error>> {
+        // region names are prefixed with the node they belong to, such that failures can be traced back to the graph
+        let mut layouter = NodeLayouter::new(layouter);
+        // params are constants, which have a single output
+        let mut results: BTreeMap<OutletId, ValTensor<F>> = params
+            .iter()
+            .map(|(idx, param)| (OutletId::new(*idx, 0), param.clone()))
+            .collect();
+        let last_uses = match self.low_memory {
+            true => self.last_uses(config),
+            false => BTreeMap::new(),
+        };
+        let mut attested = vec![];
+        // instances are held by the inputs and then the outputs which aren't private, in order
+        let mut instance = 0;
+        // range checks are configured for the public inputs only, in order
+        let mut range_checks = config.public_inputs.iter();
+        for (i, (input, outlet)) in inputs.iter().zip(self.inputs.iter()).enumerate() {
+            let visibility = self.visibility.input_at(i);
+            let input = match visibility {
+                Visibility::Public => {
+                    if let Some(range_check) = range_checks.next() {
+                        range_check.layout_table(&mut layouter, &instances[instance])?;
+                    }
+                    instances[instance].clone()
+                }
+                Visibility::Hashed => {
+                    let values = [(self.node_label(outlet.node), input.clone())];
+                    self.layout_hashed(config, &mut layouter, &values, &instances[instance])?
+                        .remove(0)
+                }
+                // the key is absorbed ahead of the input, such that the digest can't be brute forced
+                Visibility::Committed => {
+                    let key = input_key.ok_or(GraphError::MissingInputKey(KEY_LIMBS))?;
+                    let values = [
+                        ("input key".to_string(), key.clone()),
+                        (self.node_label(outlet.node), input.clone()),
+                    ];
+                    self.layout_hashed(config, &mut layouter, &values, &instances[instance])?
+                        .remove(1)
+                }
+                Visibility::Private => input.clone(),
+            };
+            if !visibility.is_private() {
+                instance += 1;
+            }
+            // the inputs are hashed as fed into the nodes, so they are assigned ahead of them
+            let input = match self.attested_inputs || self.merkle_depth.is_some() {
+                true => {
+                    let values = [(self.node_label(outlet.node), input)];
+                    let (mut assigned, cells) =
+                        self.assign_values(config, &mut layouter, &values, "leaf")?;
+                    attested.extend(cells);
+                    assigned.remove(0)
+                }
+                false => input,
+            };
+            results.insert(*outlet, input);
+        }
+        let task = config.task.as_ref();
+        for (idx, config) in self.layout_order(config) {
+            if task.map_or(false, |task| task.is_cancelled()) {
+                return Err(GraphError::Cancelled.into());
+            }
+            if let NodeConfig::Lookup(_, node_inputs) | NodeConfig::Decomposed(_, node_inputs) =
+                config
+            {
+                if let Some(input) = node_inputs.first().and_then(|i| results.get(i)) {
+                    self.check_lookup_range(*idx, input)?;
+                }
+            }
+            let node = self.nodes.filter(*idx);
+            // spans are keyed by the node, such that the time spent laying out each node can be profiled (see `--log-format`)
+            let _span =
+                info_span!("node", idx = *idx, op = %node.opkind, bucket = ?node.bucket).entered();
+            let label = self.node_label(*idx);
+            progress.set_message(label.clone());
+            layouter.set_node(Some(label));
+            // for fused nodes, the outputs are those of the last node of the run, which the config is keyed by
+            for (slot, vt) in self
+                .layout_config(&mut layouter, &mut results, config)?
+                .into_iter()
+                .enumerate()
+            {
                 //only use with mock prover
                 if matches!(self.mode, Mode::Mock) {
-                    trace!("------------ output {:?}", results.get(idx).unwrap().show());
+                    trace!("------------ output {} {:?}", slot, vt.show());
                 }
+                results.insert(OutletId::new(*idx, slot), vt);
+            }
+            for done in last_uses.get(idx).into_iter().flatten() {
+                results.remove(done);
+            }
+            progress.inc(1);
+            if let Some(task) = task {
+                task.report(Phase::Layout {
+                    done: progress.position(),
+                    total: progress.length().unwrap_or_default(),
+                });
             }
         }
 
-        let output_nodes = self.model.outputs.iter();
+        layouter.set_node(None);
+
+        let output_nodes = self.outputs.iter();
         info!(
             "model outputs are nodes: {:?}",
             output_nodes.clone().map(|o| o.node).collect_vec()
         );
         let outputs = output_nodes
-            .map(|o| results.get(&o.node).unwrap().clone())
-            .collect_vec();
-        let _ = config
-            .public_outputs
-            .iter()
-            .zip(outputs)
-            .enumerate()
-            .map(|(i, (range_check, output))| {
-                let mut offset = 0;
-                if self.visibility.input.is_public() {
-                    offset += inputs.len();
-                };
-                range_check.layout(
-                    layouter.namespace(|| "range check outputs"),
-                    output,
-                    vars.instances[offset + i].clone(),
-                )
+            .clone()
+            .map(|o| {
+                results
+                    .get(o)
+                    .cloned()
+                    .ok_or(GraphError::MissingNode(o.node))
             })
-            .collect_vec();
-        info!("computing...");
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(compare) = &config.claims {
+            let scales = self.get_output_scales();
+            for claim in self.claims.iter() {
+                let output = outputs.get(claim.output()).ok_or_else(|| {
+                    GraphError::InvalidClaim(format!("{}: no such output", claim))
+                })?;
+                let len = output.dims().iter().product();
+                compare.layout(
+                    &mut layouter,
+                    output,
+                    &claim.comparisons(len, scales[claim.output()])?,
+                )?;
+            }
+        }
+        // the instances of the outputs follow on from those of the inputs
+        let mut range_checks = config.public_outputs.iter();
+        for (i, (output, outlet)) in outputs.into_iter().zip(output_nodes).enumerate() {
+            match self.visibility.output_at(i) {
+                Visibility::Hashed => {
+                    let values = [(self.node_label(outlet.node), output)];
+                    self.layout_hashed(config, &mut layouter, &values, &instances[instance])?;
+                }
+                Visibility::Public => {
+                    // range checks are configured for the public outputs only, in order
+                    if let Some(range_check) = range_checks.next() {
+                        let _ = range_check.layout(
+                            layouter.namespace(|| "range check outputs"),
+                            output,
+                            instances[instance].clone(),
+                        );
+                    }
+                }
+                _ => continue,
+            }
+            instance += 1;
+        }
+        Ok(attested)
+    }
+
+    /// The configs of the nodes (or fused runs of poly nodes, keyed by their last node) of `config`, in the order the nodes are
+    /// executed in (see [NodeGraph::execution_order]).
+    fn layout_order<'a, F: FieldExt + TensorType>(
+        &self,
+        config: &'a ModelConfig<F>,
+    ) -> Vec<(&'a usize, &'a NodeConfig<F>)> {
+        self.nodes
+            .execution_order()
+            .into_iter()
+            .filter_map(|idx| config.configs.get_key_value(&idx))
+            .collect()
+    }
+
+    /// The results of the layout which can be dropped once each node is laid out, keyed by the node: those the node is the last
+    /// to be fed (in the order of the configs), other than the outputs of the model.
+    fn last_uses<F: FieldExt + TensorType>(
+        &self,
+        config: &ModelConfig<F>,
+    ) -> BTreeMap<usize, Vec<OutletId>> {
+        let mut last = BTreeMap::new();
+        for (idx, node_config) in self.layout_order(config) {
+            for input in node_config.inputs() {
+                last.insert(*input, *idx);
+            }
+        }
+        let mut last_uses: BTreeMap<usize, Vec<OutletId>> = BTreeMap::new();
+        for (input, idx) in last {
+            if !self.outputs.contains(&input) {
+                last_uses.entry(idx).or_default().push(input);
+            }
+        }
+        last_uses
+    }
+
+    /// Checks that the witness values fed into a lookup node lie within the range covered by its lookup table,
+    /// i.e within `[-2^(bits-1), 2^(bits-1))`. Values outside this range would otherwise only surface as an
+    /// unsatisfied lookup constraint.
+    /// # Arguments
+    ///
+    /// * `idx` - The index of the lookup node.
+    /// * `input` - The values being fed into the node.
+    fn check_lookup_range<F: FieldExt + TensorType>(
+        &self,
+        idx: usize,
+        input: &ValTensor<F>,
+    ) -> Result<(), Box<dyn Error>> {
+        // instances are not witnessed in the circuit, so there are no values to check
+        if matches!(input, ValTensor::Instance { .. }) {
+            return Ok(());
+        }
+        let bound = 2i128.pow(self.bits as u32 - 1);
+        for v in input.get_felt_evals()?.iter() {
+            let v = felt_to_i128(*v);
+            if v < -bound || v >= bound {
+                let opkind = self.nodes.filter(idx).opkind;
+                error!("node {} ({}) overflows with value {}", idx, opkind, v);
+                return Err(Box::new(GraphError::Overflow(idx, opkind, v)));
+            }
+        }
         Ok(())
     }
 
@@ -534,44 +2042,50 @@ impl Model {
     /// * `config` - [NodeConfig] the single region we will layout.
     /// * `layouter` - Halo2 Layouter.
     /// * `inputs` - `BTreeMap` of values to feed into the NodeConfig, can also include previous intermediate results, i.e the output of other nodes.
+    ///
+    /// Returns the outputs of the region, in order of slot, which for inputs and constants (laid out as they are fed) are none.
     fn layout_config<F: FieldExt + TensorType>(
         &self,
         layouter: &mut impl Layouter<F>,
-        inputs: &mut BTreeMap<usize, ValTensor<F>>,
+        inputs: &mut BTreeMap<OutletId, ValTensor<F>>,
         config: &NodeConfig<F>,
-    ) -> Result<Option<ValTensor<F>>, Box<dyn Error>> {
+    ) -> Result<Vec<ValTensor<F>>, Box<dyn Error>> {
         // The node kind and the config should be the same.
         let res = match config.clone() {
             NodeConfig::Poly(mut ac, idx) => {
-                let values: Vec<ValTensor<F>> = idx
-                    .iter()
-                    .map(|i| {
-                        let node = &self.nodes.filter(*i);
-                        match node.opkind {
-                            OpKind::Const => {
-                                let val = node
-                                    .const_value
-                                    .clone()
-                                    .context("Tensor<i32> should already be loaded")
-                                    .unwrap();
-                                <Tensor<i32> as Into<Tensor<Value<F>>>>::into(val).into()
-                            }
-                            _ => inputs.get(i).unwrap().clone(),
-                        }
-                    })
-                    .collect_vec();
-
-                Some(ac.layout(layouter, &values)?)
+                let values = self.node_values(&idx, inputs);
+                vec![ac.layout(layouter, &values)?]
+            }
+            NodeConfig::Dot(dc, op, idx) => {
+                let values = self.node_values(&idx, inputs);
+                // the left hand side of the products: the kernel of affine layers
+                let lhs = match op {
+                    PolyOp::Affine => idx.get(1),
+                    _ => idx.first(),
+                };
+                let zeros = lhs.and_then(|i| self.zero_params(i.node));
+                vec![dc.layout_op(layouter, &op, &values, zeros.as_deref())?]
+            }
+            NodeConfig::Custom(cc, idx) => {
+                let values = self.node_values(&idx, inputs);
+                vec![layouter
+                    .assign_region(|| "custom op", |mut region| cc.layout(&mut region, &values))?]
             }
             NodeConfig::Lookup(rc, idx) => {
                 if idx.len() != 1 {
                     return Err(Box::new(GraphError::InvalidLookupInputs));
                 }
                 // For activations and elementwise operations, the dimensions are sometimes only in one or the other of input and output.
-                Some(rc.layout(layouter, inputs.get(&idx[0]).unwrap())?)
+                vec![rc.layout(layouter, inputs.get(&idx[0]).unwrap())?]
             }
-            NodeConfig::Input => None,
-            NodeConfig::Const => None,
+            NodeConfig::Decomposed(dc, idx) => {
+                if idx.len() != 1 {
+                    return Err(Box::new(GraphError::InvalidLookupInputs));
+                }
+                vec![dc.layout(layouter, inputs.get(&idx[0]).unwrap())?]
+            }
+            NodeConfig::Input => vec![],
+            NodeConfig::Const => vec![],
             _ => {
                 return Err(Box::new(GraphError::UnsupportedOp));
             }
@@ -579,6 +2093,166 @@ impl Model {
         Ok(res)
     }
 
+    /// The values of the outlets `idx`, as fed into a node: previous results, or constants loaded from the model.
+    fn node_values<F: FieldExt + TensorType>(
+        &self,
+        idx: &[OutletId],
+        inputs: &BTreeMap<OutletId, ValTensor<F>>,
+    ) -> Vec<ValTensor<F>> {
+        idx.iter()
+            .map(|i| {
+                let node = &self.nodes.filter(i.node);
+                match (&node.opkind, inputs.get(i)) {
+                    // committed params have already been assigned
+                    (OpKind::Const, Some(assigned)) => assigned.clone(),
+                    (OpKind::Const, None) => {
+                        let val = self
+                            .const_value(i.node)
+                            .context("Tensor<i32> should already be loaded")
+                            .unwrap();
+                        <Tensor<i32> as Into<Tensor<Value<F>>>>::into(val).into()
+                    }
+                    _ => inputs.get(i).unwrap().clone(),
+                }
+            })
+            .collect_vec()
+    }
+
+    /// Runs a forward pass on the quantized computational graph in plain Rust, i.e without constructing a circuit.
+    /// Returns the quantized outputs of the graph.
+    /// # Arguments
+    ///
+    /// * `model_inputs` - The quantized inputs to the graph.
+    pub fn forward(
+        &self,
+        model_inputs: &[Tensor<i32>],
+    ) -> Result<Vec<Tensor<i32>>, Box<dyn Error>> {
+        let results = self.forward_outlets(model_inputs)?;
+        let mut outputs = vec![];
+        for o in self.outputs.iter() {
+            match results.get(o) {
+                Some(t) => outputs.push(t.clone()),
+                None => return Err(Box::new(GraphError::MissingNode(o.node))),
+            }
+        }
+        Ok(outputs)
+    }
+
+    /// Runs a forward pass on the quantized computational graph in plain Rust, i.e without constructing a circuit.
+    /// Returns the quantized value of every node in the graph (including inputs and constants), keyed by node index. For
+    /// nodes with several outputs, this is their first output (see [Model::forward_outlets]).
+    /// # Arguments
+    ///
+    /// * `model_inputs` - The quantized inputs to the graph.
+    pub fn forward_all(
+        &self,
+        model_inputs: &[Tensor<i32>],
+    ) -> Result<BTreeMap<usize, Tensor<i32>>, Box<dyn Error>> {
+        Ok(self
+            .forward_outlets(model_inputs)?
+            .into_iter()
+            .filter(|(o, _)| o.slot == 0)
+            .map(|(o, t)| (o.node, t))
+            .collect())
+    }
+
+    /// Runs a forward pass on the quantized computational graph in plain Rust, i.e without constructing a circuit.
+    /// Returns the quantized value of every output of every node in the graph, keyed by outlet (node index and output slot).
+    /// # Arguments
+    ///
+    /// * `model_inputs` - The quantized inputs to the graph.
+    pub fn forward_outlets(
+        &self,
+        model_inputs: &[Tensor<i32>],
+    ) -> Result<BTreeMap<OutletId, Tensor<i32>>, Box<dyn Error>> {
+        let mut results = BTreeMap::<OutletId, Tensor<i32>>::new();
+        for (input, value) in self.inputs.iter().zip(model_inputs) {
+            results.insert(*input, value.clone());
+        }
+        for node in self.nodes.flatten() {
+            let mut inputs = vec![];
+            for i in node.inputs.iter() {
+                match results.get(i) {
+                    Some(t) => inputs.push(t.clone()),
+                    None => return Err(Box::new(GraphError::MissingNode(i.node))),
+                }
+            }
+            let res = match &node.opkind {
+                OpKind::Input => continue,
+                OpKind::Const => self.const_value(node.idx)?,
+                OpKind::Lookup(op) => {
+                    if inputs.len() != 1 {
+                        return Err(Box::new(GraphError::InvalidLookupInputs));
+                    }
+                    match self.limb_bits() {
+                        Some(limb_bits) => interpolate(&[op.clone()], &inputs[0], limb_bits),
+                        None => op.f(inputs[0].clone()),
+                    }
+                }
+                OpKind::Poly(op) => Self::poly_forward(&node, op, &inputs)?,
+                OpKind::Custom(name) => self.custom_ops.f(name, &inputs)?,
+                c => return Err(Box::new(GraphError::WrongMethod(node.idx, c.clone()))),
+            };
+            results.insert(OutletId::new(node.idx, 0), res);
+        }
+        Ok(results)
+    }
+
+    /// Evaluates the poly op of `node` over `inputs`, accumulating in 128 bit integers: the products and sums of dot products
+    /// over values of 16 bits or more overflow 32 bits long before the field elements the circuit computes over do, which
+    /// would leave witnesses disagreeing with the circuit. Outputs which don't fit 32 bits are reported as such.
+    fn poly_forward(
+        node: &Node,
+        op: &PolyOp,
+        inputs: &[Tensor<i32>],
+    ) -> Result<Tensor<i32>, Box<dyn Error>> {
+        let wide = inputs.iter().map(|t| t.map(|x| x as i128)).collect_vec();
+        let res = op.f(wide)?;
+        if let Some(x) = res.iter().find(|x| i32::try_from(**x).is_err()) {
+            return Err(Box::new(GraphError::WitnessOverflow(
+                node.idx,
+                node.opkind.clone(),
+                *x,
+            )));
+        }
+        Ok(res.map(|x| x as i32))
+    }
+
+    /// Runs a forward pass on the original (floating point) model using tract, for instance to measure the
+    /// error introduced by quantization. Returns the flattened outputs of the graph.
+    /// # Arguments
+    ///
+    /// * `model_inputs` - The (flattened) floating point inputs to the graph.
+    /// * `input_shapes` - The shapes of said inputs, used if the model does not specify concrete input shapes.
+    pub fn float_forward(
+        &self,
+        model_inputs: &[Vec<f32>],
+        input_shapes: &[Vec<usize>],
+    ) -> Result<Vec<Vec<f32>>, Box<dyn Error>> {
+        let mut model = self.tract()?.clone();
+        let mut inputs: TVec<TractTensor> = TVec::new();
+        for (i, (data, shape)) in model_inputs.iter().zip(input_shapes).enumerate() {
+            // prefer the shape declared by the model (which usually includes a batch dimension)
+            let fact_shape = model
+                .input_fact(i)?
+                .shape
+                .as_concrete_finite()?
+                .map(|s| s.to_vec());
+            let shape = match fact_shape {
+                Some(s) if s.iter().product::<usize>() == data.len() => s,
+                _ => shape.clone(),
+            };
+            model = model.with_input_fact(i, InferenceFact::dt_shape(f32::datum_type(), &shape))?;
+            inputs.push(TractTensor::from_shape(&shape, data)?);
+        }
+        let runnable = model.into_optimized()?.into_runnable()?;
+        let mut outputs = vec![];
+        for o in runnable.run(inputs)?.iter() {
+            outputs.push(o.to_array_view::<f32>()?.iter().cloned().collect());
+        }
+        Ok(outputs)
+    }
+
     /// Iterates over Nodes and assigns execution buckets to them.  Each bucket holds either:
     /// a) independent lookup operations (i.e operations that don't feed into one another so can be processed in parallel).
     /// b) operations that can be fused together, i.e the output of one op might feed into another.
@@ -595,27 +2269,28 @@ impl Model {
 
         let mut bucketed_nodes = NodeGraph(BTreeMap::<Option<usize>, BTreeMap<usize, Node>>::new());
 
-        for (_, node) in nodes.iter_mut() {
+        for idx in execution_order(nodes.values()) {
+            let node = nodes.get_mut(&idx).unwrap();
             let mut prev_buckets = vec![];
-            for n in node
-                .inputs
-                .iter()
-                .filter(|n| !bucketed_nodes.filter(n.node).opkind.is_const())
-            {
-                match bucketed_nodes.filter(n.node).bucket {
-                    Some(b) => prev_buckets.push(b),
-                    None => {
-                        return Err(GraphError::MissingNode(n.node));
-                    }
+            for n in node.inputs.iter() {
+                let input = bucketed_nodes
+                    .get(n.node)
+                    .ok_or(GraphError::MissingNode(n.node))?;
+                match (&input.opkind, input.bucket) {
+                    (OpKind::Const, _) => {}
+                    (_, Some(b)) => prev_buckets.push(b),
+                    (_, None) => return Err(GraphError::MissingNode(n.node)),
                 }
             }
-            let prev_bucket: Option<&usize> = prev_buckets.iter().max();
+            // nodes fed by constants alone are laid out alongside the inputs
+            let prev_bucket = prev_buckets.into_iter().max().unwrap_or(0);
 
             match &node.opkind {
                 OpKind::Input => node.bucket = Some(0),
                 OpKind::Const => node.bucket = None,
-                OpKind::Poly(_) => node.bucket = Some(*prev_bucket.unwrap()),
-                OpKind::Lookup(_) => node.bucket = Some(prev_bucket.unwrap() + 1),
+                OpKind::Poly(_) => node.bucket = Some(prev_bucket),
+                // custom ops are laid out in their own regions, like lookups
+                OpKind::Lookup(_) | OpKind::Custom(_) => node.bucket = Some(prev_bucket + 1),
                 op => {
                     return Err(GraphError::WrongMethod(node.idx, op.clone()));
                 }
@@ -631,95 +2306,364 @@ impl Model {
     /// interchange the order of evaluation of fixed parameters.   For example weight could have id 1 on one load,
     /// and bias id 2, and vice versa on the next load of the same file. The ids are also not stable.
     pub fn eval_order(&self) -> Result<Vec<usize>, AnyError> {
-        self.model.eval_order()
+        self.tract()?.eval_order()
     }
 
-    /// Note that this order is not stable.
+    /// Note that this order is not stable. Empty for models loaded from a compiled graph.
     pub fn nodes(&self) -> Vec<OnnxNode<InferenceFact, Box<dyn InferenceOp>>> {
-        self.model.nodes().to_vec()
+        self.model
+            .as_ref()
+            .map_or(vec![], |model| model.nodes().to_vec())
+    }
+
+    /// The tract graph of the model, which models loaded from a compiled graph (see [CompiledModel]) don't hold.
+    fn tract(&self) -> Result<&Graph<InferenceFact, Box<dyn InferenceOp>>, GraphError> {
+        self.model.as_ref().ok_or(GraphError::CompiledModel)
     }
 
     /// Returns the ID of the computational graph's inputs
     pub fn input_outlets(&self) -> Result<Vec<OutletId>, Box<dyn Error>> {
-        Ok(self.model.input_outlets()?.to_vec())
+        Ok(self.inputs.clone())
     }
 
     /// Returns the ID of the computational graph's outputs
     pub fn output_outlets(&self) -> Result<Vec<OutletId>, Box<dyn Error>> {
-        Ok(self.model.output_outlets()?.to_vec())
+        Ok(self.outputs.clone())
     }
 
     /// Returns the number of the computational graph's inputs
     pub fn num_inputs(&self) -> usize {
-        let input_nodes = self.model.inputs.iter();
+        let input_nodes = self.inputs.iter();
         input_nodes.len()
     }
 
+    /// Returns the names of the computational graph's inputs, as given in the onnx file
+    pub fn input_names(&self) -> Vec<String> {
+        self.inputs
+            .iter()
+            .map(|o| self.nodes.filter(o.node).name)
+            .collect_vec()
+    }
+
+    /// Returns the names of the computational graph's outputs, as given in the onnx file
+    pub fn output_names(&self) -> Vec<String> {
+        self.outputs
+            .iter()
+            .map(|o| self.nodes.filter(o.node).name)
+            .collect_vec()
+    }
+
     ///  Returns shapes of the computational graph's inputs
     pub fn input_shapes(&self) -> Vec<Vec<usize>> {
-        self.model
-            .inputs
+        self.inputs
             .iter()
             .map(|o| self.nodes.filter(o.node).out_dims)
             .collect_vec()
     }
 
+    /// Returns the shapes of the circuit's instances (public inputs), inputs first and then outputs, as configured by the model's visibility.
+    /// These are repeated for each sample in a batch, and followed by the limbs of the params commitment when committing to params,
+    /// of the Merkle root when proving inclusion of the inputs, then of the `x` and `y` coordinates of the signer's public key when
+    /// inputs are attested.
+    pub fn instance_shapes(&self) -> Vec<Vec<usize>> {
+        let mut instance_shapes = self.sample_instance_shapes().repeat(self.batch_size);
+        if self.commit_params {
+            instance_shapes.push(vec![DIGEST_LIMBS]);
+        }
+        if self.merkle_depth.is_some() {
+            instance_shapes.push(vec![DIGEST_LIMBS]);
+        }
+        if self.attested_inputs {
+            instance_shapes.extend(vec![vec![DIGEST_LIMBS]; 2]);
+        }
+        instance_shapes
+    }
+
+    /// The public outputs held by the `public_inputs` of a proof of the model, dequantized at the scales of the outputs (see
+    /// [Model::get_output_scales]) such that verifiers needn't know them. Returns the public outputs of each sample of the batch,
+    /// in order, as flat vectors; hashed and private outputs are left out.
+    pub fn public_outputs(
+        &self,
+        public_inputs: &[Vec<i32>],
+    ) -> Result<Vec<Vec<Vec<f32>>>, GraphError> {
+        let shapes = self.instance_shapes();
+        if public_inputs.len() != shapes.len() {
+            return Err(GraphError::InstanceMismatch(
+                public_inputs.len(),
+                shapes.len(),
+            ));
+        }
+        let scales = self.get_output_scales();
+        // the instances of the outputs of each sample follow on from those of its inputs
+        let offset = (0..self.num_inputs())
+            .filter(|i| !self.visibility.input_at(*i).is_private())
+            .count();
+        let per_sample = self.sample_instance_shapes().len();
+        let outputs = (0..self.num_outputs())
+            .filter(|i| !self.visibility.output_at(*i).is_private())
+            .enumerate()
+            .filter(|(_, i)| self.visibility.output_at(*i).is_public())
+            .collect_vec();
+        Ok(public_inputs
+            .chunks(per_sample.max(1))
+            .take(self.batch_size)
+            .map(|sample| {
+                outputs
+                    .iter()
+                    .map(|(instance, i)| {
+                        let multiplier = scale_to_multiplier(scales[*i]);
+                        sample[offset + instance]
+                            .iter()
+                            .map(|v| *v as f32 / multiplier)
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Returns the shapes of the instances of a single sample of a batch, see [Model::instance_shapes].
+    /// Hashed (or committed) inputs and outputs are each held as the limbs of their digest.
+    fn sample_instance_shapes(&self) -> Vec<Vec<usize>> {
+        let mut instance_shapes = vec![];
+        let inputs = self.input_shapes().into_iter().enumerate();
+        for (i, shape) in inputs {
+            match self.visibility.input_at(i) {
+                Visibility::Public => instance_shapes.push(shape),
+                Visibility::Hashed | Visibility::Committed => {
+                    instance_shapes.push(vec![DIGEST_LIMBS])
+                }
+                Visibility::Private => {}
+            }
+        }
+        for (i, shape) in self.output_shapes().into_iter().enumerate() {
+            match self.visibility.output_at(i) {
+                Visibility::Public => instance_shapes.push(shape),
+                Visibility::Hashed | Visibility::Committed => {
+                    instance_shapes.push(vec![DIGEST_LIMBS])
+                }
+                Visibility::Private => {}
+            }
+        }
+        instance_shapes
+    }
+
+    /// Returns the indices of the model's constant nodes (i.e its params), in ascending order.
+    pub fn const_nodes(&self) -> Vec<usize> {
+        self.nodes
+            .flatten()
+            .iter()
+            .filter(|n| n.opkind.is_const())
+            .map(|n| n.idx)
+            .sorted()
+            .collect()
+    }
+
+    /// Returns the quantized value of the constant node at `idx`, loading it back if it was spilled to disk.
+    fn const_value(&self, idx: usize) -> Result<Tensor<i32>, GraphError> {
+        self.nodes
+            .filter(idx)
+            .const_value
+            .or_else(|| self.consts.as_ref().and_then(|c| c.get(idx)))
+            .ok_or_else(|| GraphError::MissingParams(format!("value of node {}", idx)))
+    }
+
+    /// Flags the zero elements (in row major order) of the constant node at `idx` when `sparse_params` is set, such that the
+    /// products over them are left out of the circuit. Returns `None` for other nodes, or when none of its elements are zero.
+    fn zero_params(&self, idx: usize) -> Option<Vec<bool>> {
+        if !self.sparse_params || !self.nodes.filter(idx).opkind.is_const() {
+            return None;
+        }
+        let value = self.const_value(idx).ok()?;
+        value
+            .iter()
+            .any(|v| *v == 0)
+            .then(|| value.iter().map(|v| *v == 0).collect())
+    }
+
+    /// Computes the commitment to the model's params, as constrained in-circuit when committing to params: the Poseidon hash
+    /// of the params of each constant node (see [Model::const_nodes]) in turn, as the limbs exposed in the instance column.
+    pub fn params_commitment<F: FieldExt>(&self) -> Result<Vec<i32>, Box<dyn Error>> {
+        let mut params = vec![];
+        for idx in self.const_nodes() {
+            params.extend(self.const_value(idx)?.iter().copied());
+        }
+        Ok(hash_to_limbs::<F>(params))
+    }
+
+    /// Returns the (sample, node index) pairs whose values are held by each of the circuit's instances, ordered as in [Model::instance_shapes].
+    /// Hashed inputs and outputs are held as their digest. The params commitment, the Merkle root, and the public key of the signer
+    /// of attested inputs, which are held by no node, are left out.
+    pub fn instance_nodes(&self) -> Vec<(usize, usize)> {
+        let instance_nodes = self
+            .instance_ports()
+            .into_iter()
+            .take_while(|(sample, _)| *sample == 0)
+            .map(|(_, port)| match port {
+                Port::Input(i) => self.inputs[i].node,
+                Port::Output(i) => self.outputs[i].node,
+            })
+            .collect_vec();
+        (0..self.batch_size)
+            .flat_map(|sample| instance_nodes.iter().map(move |n| (sample, *n)))
+            .collect()
+    }
+
+    /// Returns the (sample, [Port]) pairs whose values are held by each of the circuit's instances, in the same order as
+    /// [Model::instance_nodes], such that the outputs of one model can be linked to the inputs of another.
+    pub fn instance_ports(&self) -> Vec<(usize, Port)> {
+        let mut ports = vec![];
+        ports.extend(
+            (0..self.num_inputs())
+                .filter(|i| !self.visibility.input_at(*i).is_private())
+                .map(Port::Input),
+        );
+        ports.extend(
+            (0..self.num_outputs())
+                .filter(|i| !self.visibility.output_at(*i).is_private())
+                .map(Port::Output),
+        );
+        (0..self.batch_size)
+            .flat_map(|sample| ports.iter().map(move |p| (sample, *p)))
+            .collect()
+    }
+
     /// Returns the number of the computational graph's outputs
     pub fn num_outputs(&self) -> usize {
-        let output_nodes = self.model.outputs.iter();
+        let output_nodes = self.outputs.iter();
         output_nodes.len()
     }
 
     /// Returns shapes of the computational graph's outputs
     pub fn output_shapes(&self) -> Vec<Vec<usize>> {
-        self.model
-            .outputs
+        self.outputs
             .iter()
             .map(|o| self.nodes.filter(o.node).out_dims)
             .collect_vec()
     }
 
+    /// Returns the fixed point scale of the computational graph's inputs
+    pub fn get_input_scales(&self) -> Vec<i32> {
+        let input_nodes = self.inputs.iter();
+        input_nodes
+            .map(|o| self.nodes.filter(o.node).out_scale)
+            .collect_vec()
+    }
+
     /// Returns the fixed point scale of the computational graph's outputs
     pub fn get_output_scales(&self) -> Vec<i32> {
-        let output_nodes = self.model.outputs.iter();
+        let output_nodes = self.outputs.iter();
         output_nodes
             .map(|o| self.nodes.filter(o.node).out_scale)
             .collect_vec()
     }
 
+    /// The norm of the errors of the output `idx` bounded by the tolerance, see [ModelBuilder::tolerance_norms].
+    pub fn tolerance_norm(&self, idx: usize) -> ToleranceNorm {
+        self.tolerance_norms.get(&idx).copied().unwrap_or_default()
+    }
+
+    /// Checks that the claims about the outputs of the model hold for the (quantized) `outputs` of a sample, such that a claim
+    /// which doesn't hold is reported as such rather than as an unsatisfied lookup of the circuit.
+    pub fn check_claims(&self, outputs: &[Tensor<i32>]) -> Result<(), GraphError> {
+        let scales = self.get_output_scales();
+        for claim in self.claims.iter() {
+            let output = outputs
+                .get(claim.output())
+                .ok_or_else(|| GraphError::InvalidClaim(format!("{}: no such output", claim)))?;
+            if !claim.holds(output, scales[claim.output()])? {
+                return Err(GraphError::FalseClaim(claim.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// The bits of the low limb lookups are split at when tables of `bits` bits don't fit in the circuit, in which case lookups
+    /// are decomposed (see [DecomposedConfig]). The high limb is given the largest table which fits, `logrows - 1` bits, such
+    /// that non-linearities are interpolated over the shortest intervals.
+    pub fn limb_bits(&self) -> Option<usize> {
+        let hi_bits = self.logrows as usize - 1;
+        (self.bits > hi_bits).then(|| self.bits - hi_bits)
+    }
+
+    /// The lookup tables of the model's lookup nodes, for shipping with its compiled graph (see [CompiledModel]) such that they
+    /// aren't computed again when it is loaded. Decomposed lookups (see [Model::limb_bits]) take tables of their own, which
+    /// aren't included.
+    pub fn lookup_tables(&self) -> Vec<CachedTable> {
+        if self.limb_bits().is_some() {
+            return vec![];
+        }
+        self.nodes
+            .flatten()
+            .iter()
+            .filter_map(|n| match &n.opkind {
+                OpKind::Lookup(op) => Some(vec![op.clone()]),
+                _ => None,
+            })
+            .unique()
+            .map(|function| table_cache::table(&function, self.bits, self.table_cache.as_deref()))
+            .collect()
+    }
+
+    /// The number of rotations the columns of the model span unless set explicitly (see [ModelBuilder::max_rotations]):
+    /// enough for the largest node to be laid out within a single column, within [Model::rotation_bound].
+    pub fn auto_rotations(&self) -> usize {
+        max(1, min(self.max_node_size(), self.rotation_bound()))
+    }
+
+    /// The most rotations the columns of the model can span in the circuit. A column spanning `r` rows is queried at `r`
+    /// rotations, which halo2 offsets with as many blinding rows at the end of the circuit, so columns span at most half of its
+    /// rows, and must leave room for the lookup tables.
+    pub fn rotation_bound(&self) -> usize {
+        let rows = 1usize << self.logrows;
+        let lookups = self.nodes.flatten().iter().any(|n| n.opkind.is_lookup())
+            || self.range_check_inputs
+            || !self.claims.is_empty();
+        let table_rows = match (lookups, self.limb_bits()) {
+            (false, _) => 0,
+            (true, None) => 1 << self.bits,
+            // decomposed lookups look up limbs in tables of at most `logrows - 1` bits
+            (true, Some(_)) => 1 << (self.logrows - 1),
+        };
+        // the blinding rows of `r` rotations take max(3, r) + 3 rows, plus the unusable last row (or zero row of shared tables)
+        let fit_tables = rows.saturating_sub(table_rows + 4);
+        let fit_columns = rows.saturating_sub(4) / 2;
+        min(fit_tables, fit_columns)
+    }
+
     /// Max number of inlets or outlets to a node
     pub fn max_node_size(&self) -> usize {
+        // chunked dot products take rows rather than columns, whatever their size
+        let nodes = self
+            .nodes
+            .flatten()
+            .into_iter()
+            .filter(|n| !self.is_chunked(n))
+            .collect_vec();
         max(
-            self.nodes
-                .flatten()
+            nodes
                 .iter()
                 .map(|e| {
                     e.in_dims
                         .iter()
                         .map(|dims| dims.iter().product::<usize>())
                         .max()
-                        .unwrap()
+                        .unwrap_or(0)
                 })
                 .max()
-                .unwrap(),
-            self.nodes
-                .flatten()
+                .unwrap_or(0),
+            nodes
                 .iter()
                 .map(|e| e.out_dims.iter().product())
                 .max()
-                .unwrap(),
+                .unwrap_or(0),
         )
     }
 
     /// Max number of parameters (i.e trainable weights) across the computational graph
     pub fn max_node_params(&self) -> usize {
         let mut maximum_number_inputs = 0;
-        for (_, bucket_nodes) in self.nodes.0.iter() {
-            let fused_ops: BTreeMap<&usize, &Node> = bucket_nodes
-                .iter()
-                .filter(|(_, n)| n.opkind.is_poly())
-                .collect();
-
+        for fused_ops in self.nodes.0.values().flat_map(|b| self.fused_runs(b)) {
             let params = fused_ops
                 .iter()
                 .flat_map(|(_, n)| n.inputs.iter().map(|o| o.node).collect_vec())
@@ -738,12 +2682,7 @@ impl Model {
     /// Maximum number of input variables in fused layers
     pub fn max_node_vars_fused(&self) -> usize {
         let mut maximum_number_inputs = 0;
-        for (_, bucket_nodes) in self.nodes.0.iter() {
-            let fused_ops: BTreeMap<&usize, &Node> = bucket_nodes
-                .iter()
-                .filter(|(_, n)| n.opkind.is_poly())
-                .collect();
-
+        for fused_ops in self.nodes.0.values().flat_map(|b| self.fused_runs(b)) {
             let fused_inputs = fused_ops
                 .iter()
                 .flat_map(|(_, n)| n.inputs.iter().map(|o| o.node).collect_vec())
@@ -759,6 +2698,51 @@ impl Model {
         maximum_number_inputs + 1
     }
 
+    /// The number of advice and fixed [VarTensor]s of the [ModelVars] the model is laid out in. Each region (of a lookup or
+    /// custom node, or a fused run of poly nodes) assigns its inputs and output to the vars from the first on, and the vars are
+    /// shared by the regions of every bucket, which are laid out at distinct rows. The model hence needs as many vars as its
+    /// widest region, rather than as many as the most params of any region on top of the most inputs of any other.
+    pub fn num_vars(&self) -> (usize, usize) {
+        let mut regions = vec![];
+        for (bucket, bucket_nodes) in self.nodes.0.iter() {
+            for node in bucket_nodes.values().filter(|n| !n.opkind.is_poly()) {
+                let inputs = match node.opkind.is_lookup() && self.limb_bits().is_some() {
+                    // decomposed lookups also lay out the limbs of their input and output, interval ends and remainders
+                    true => DECOMPOSED_VARS - 1,
+                    false => node.inputs.len(),
+                };
+                regions.push((inputs + 1, 0));
+            }
+            for run in self.fused_runs(bucket_nodes) {
+                let (params, inputs): (Vec<usize>, Vec<usize>) = run
+                    .values()
+                    .flat_map(|n| n.inputs.iter().map(|o| o.node))
+                    .filter(|id| !run.contains_key(id))
+                    .unique()
+                    .partition(|id| self.nodes.filter(*id).opkind.is_const());
+                // public params are laid out in fixed columns
+                let region = match self.visibility.params.is_public() {
+                    true => (inputs.len() + 1, params.len()),
+                    false => (inputs.len() + params.len() + 1, 0),
+                };
+                trace!("fused run of bucket {:?} takes {:?} vars", bucket, region);
+                regions.push(region);
+            }
+        }
+        // public outputs are range checked against their instances in the first two vars, and the absolute errors of those
+        // whose L1 norm is bounded in the third
+        for i in (0..self.num_outputs()).filter(|i| self.visibility.output_at(*i).is_public()) {
+            match self.tolerance_norm(i) {
+                ToleranceNorm::L1 => regions.push((3, 0)),
+                _ => regions.push((2, 0)),
+            }
+        }
+        (
+            regions.iter().map(|r| r.0).max().unwrap_or(1),
+            regions.iter().map(|r| r.1).max().unwrap_or(0),
+        )
+    }
+
     /// Maximum number of input variables in non-fused layers
     pub fn max_node_vars_non_fused(&self) -> usize {
         let mut maximum_number_inputs = 0;
@@ -766,7 +2750,13 @@ impl Model {
             let non_fused_ops = bucket_nodes
                 .iter()
                 .filter(|(_, n)| !n.opkind.is_poly())
-                .map(|(_, n)| n.inputs.len())
+                .map(
+                    |(_, n)| match n.opkind.is_lookup() && self.limb_bits().is_some() {
+                        // decomposed lookups also lay out the limbs of their input and output, interval ends and remainders
+                        true => DECOMPOSED_VARS - 1,
+                        false => n.inputs.len(),
+                    },
+                )
                 .max()
                 .unwrap_or(0);
 
@@ -776,3 +2766,487 @@ impl Model {
         maximum_number_inputs + 1
     }
 }
+
+/// The instance column backing `instance`.
+fn instance_column<F: FieldExt + TensorType>(
+    instance: &ValTensor<F>,
+) -> Result<Column<Instance>, GraphError> {
+    match instance {
+        ValTensor::Instance { inner, .. } => Ok(*inner),
+        _ => Err(GraphError::MissingParams("digest instance".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{GraphBuilder, OpKind};
+    use crate::pfsys::ModelInput;
+    use crate::tensor::Tensor;
+
+    #[test]
+    fn folds_constant_subgraphs() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 2]);
+        let a = graph.constant(&[1.0, 0.0], &[2]).unwrap();
+        let b = graph.constant(&[0.0, 1.0], &[2]).unwrap();
+        let c = graph.add(&a, &b);
+        let y = graph.add(&x, &c);
+        graph.output(&y);
+
+        let count = |model: &Model, kind: OpKind| {
+            model
+                .nodes
+                .flatten()
+                .iter()
+                .filter(|n| n.opkind == kind)
+                .count()
+        };
+        let unfolded = ModelBuilder::new(&graph).passes(&[]).build().unwrap();
+        assert_eq!(count(&unfolded, OpKind::Const), 2);
+        let model = ModelBuilder::new(&graph).build().unwrap();
+        assert_eq!(count(&model, OpKind::Const), 3);
+        assert_eq!(count(&model, OpKind::Poly(PolyOp::Add)), 1);
+
+        // inputs of 1, 2 at the default scale of 7
+        let input = Tensor::new(Some(&[128, 256]), &[2]).unwrap();
+        let outputs = model.forward(&[input]).unwrap();
+        assert_eq!(outputs[0], Tensor::new(Some(&[256, 384]), &[2]).unwrap());
+    }
+
+    #[test]
+    fn witnesses_accumulate_without_overflowing() {
+        let node = Node {
+            idx: 3,
+            opkind: OpKind::Poly(PolyOp::Matmul),
+            ..Default::default()
+        };
+        let a = Tensor::new(Some(&[30000; 5]), &[1, 5]).unwrap();
+        // the partial sums of the products reach 2.7e9, past i32::MAX, though the dot product doesn't
+        let b = Tensor::new(Some(&[30000, 30000, 30000, -30000, -30000]), &[5, 1]).unwrap();
+        let res = Model::poly_forward(&node, &PolyOp::Matmul, &[a.clone(), b]).unwrap();
+        assert_eq!(res, Tensor::new(Some(&[900_000_000]), &[1, 1]).unwrap());
+
+        // dot products which don't fit the witness values are reported rather than wrapping around
+        let b = Tensor::new(Some(&[30000; 5]), &[5, 1]).unwrap();
+        match Model::poly_forward(&node, &PolyOp::Matmul, &[a, b]) {
+            Err(e) => match e.downcast_ref::<GraphError>() {
+                Some(GraphError::WitnessOverflow(3, _, x)) => assert_eq!(*x, 4_500_000_000),
+                _ => panic!("expected the overflow to be reported, got {}", e),
+            },
+            Ok(_) => panic!("expected the overflow to be reported"),
+        }
+    }
+
+    #[test]
+    fn results_are_keyed_by_outlet() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 2]);
+        let y = graph.relu(&x);
+        graph.output(&y);
+        let model = ModelBuilder::new(&graph).build().unwrap();
+
+        let input = Tensor::new(Some(&[-128, 256]), &[2]).unwrap();
+        let outlets = model.forward_outlets(&[input.clone()]).unwrap();
+        assert!(outlets.keys().all(|o| o.slot == 0));
+        let output = &outlets[&model.outputs[0]];
+        assert_eq!(output[0], 0);
+        assert_eq!(
+            model.forward(&[input.clone()]).unwrap(),
+            vec![output.clone()]
+        );
+        assert_eq!(
+            model.forward_all(&[input]).unwrap()[&model.outputs[0].node],
+            *output
+        );
+
+        let node = Node {
+            out_dims: vec![2, 3],
+            slot_dims: vec![vec![4], vec![1, 1]],
+            ..Default::default()
+        };
+        assert_eq!(node.num_outputs(), 3);
+        assert_eq!(node.outlet_dims(0), Some(&[2, 3][..]));
+        assert_eq!(node.outlet_dims(2), Some(&[1, 1][..]));
+        assert_eq!(node.outlet_dims(3), None);
+    }
+
+    #[test]
+    fn unused_inputs_are_pruned() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 2]);
+        let _unused = graph.input("z", &[1, 3]);
+        let y = graph.relu(&x);
+        graph.output(&y);
+        let model = ModelBuilder::new(&graph).build().unwrap();
+        assert_eq!(model.num_inputs(), 1);
+        assert_eq!(model.input_names(), vec!["x".to_string()]);
+        assert_eq!(model.unused_inputs, vec![(1, "z".to_string())]);
+        assert!(model.nodes.flatten().iter().all(|n| n.name != "z"));
+
+        // data files may give the unused input, or leave it out
+        let data = ModelInput {
+            input_data: vec![vec![1.0, -1.0], vec![0.0; 3]],
+            input_shapes: vec![vec![1, 2], vec![1, 3]],
+            input_names: vec![],
+            output_data: vec![],
+            input_key: None,
+            input_signature: None,
+            input_merkle_paths: vec![],
+        };
+        let names = model.input_names();
+        let ordered = data.order_inputs(&names, &model.unused_inputs).unwrap();
+        assert_eq!(ordered.input_shapes, vec![vec![1, 2]]);
+        let omitted = ModelInput {
+            input_data: vec![vec![1.0, -1.0]],
+            input_shapes: vec![vec![1, 2]],
+            ..data
+        };
+        let ordered = omitted.order_inputs(&names, &model.unused_inputs).unwrap();
+        assert_eq!(ordered.input_data, vec![vec![1.0, -1.0]]);
+    }
+
+    #[test]
+    fn l1_tolerances_take_a_var_for_absolute_errors() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 2]);
+        let y = graph.add(&x, &x);
+        graph.output(&y);
+        let builder = ModelBuilder::new(&graph).tolerance(2);
+        // the sum and its operand take two vars, as do the output and its instance
+        assert_eq!(builder.build().unwrap().num_vars().0, 2);
+        let model = builder
+            .tolerance_norms(BTreeMap::from([(0, ToleranceNorm::L1)]))
+            .build()
+            .unwrap();
+        assert_eq!(model.tolerance_norm(0), ToleranceNorm::L1);
+        assert_eq!(model.tolerance_norm(1), ToleranceNorm::Max);
+        assert_eq!(model.num_vars().0, 3);
+    }
+
+    #[test]
+    fn public_outputs_are_dequantized() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 2]);
+        let y = graph.relu(&x);
+        let z = graph.add(&x, &x);
+        graph.output(&y);
+        graph.output(&z);
+        let mut visibility =
+            VarVisibility::new(Visibility::Public, Visibility::Private, Visibility::Public);
+        visibility.output_overrides.insert(0, Visibility::Hashed);
+        let model = ModelBuilder::new(&graph)
+            .scale(2)
+            .visibility(visibility)
+            .batch_size(2)
+            .build()
+            .unwrap();
+        // the input, the digest of the first output, then the second output of each sample
+        let sample = |v: i32| vec![vec![v, v], vec![0; DIGEST_LIMBS], vec![v, 2 * v]];
+        let public_inputs = [sample(1), sample(-4)].concat();
+        let m = scale_to_multiplier(model.get_output_scales()[1]);
+        assert_eq!(
+            model.public_outputs(&public_inputs).unwrap(),
+            vec![vec![vec![1.0 / m, 2.0 / m]], vec![vec![-4.0 / m, -8.0 / m]]]
+        );
+        assert!(matches!(
+            model.public_outputs(&public_inputs[1..]),
+            Err(GraphError::InstanceMismatch(5, 6))
+        ));
+    }
+
+    #[test]
+    fn claims_are_checked_against_the_outputs() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 3]);
+        let y = graph.relu(&x);
+        graph.output(&y);
+        let argmax = Claim::Argmax {
+            output: 0,
+            index: 2,
+        };
+        let model = ModelBuilder::new(&graph)
+            .claims(vec![argmax])
+            .build()
+            .unwrap();
+        let scale = model.get_input_scales()[0];
+        let inputs = [Tensor::new(Some(&[-4, 2, 3].map(|v| v << scale)), &[1, 3]).unwrap()];
+        let outputs = model.forward(&inputs).unwrap();
+        assert!(model.check_claims(&outputs).is_ok());
+
+        let model = ModelBuilder::new(&graph)
+            .claims(vec![Claim::Range {
+                output: 0,
+                index: 0,
+                min: Some(1.0),
+                max: None,
+            }])
+            .build()
+            .unwrap();
+        assert!(matches!(
+            model.check_claims(&outputs),
+            Err(GraphError::FalseClaim(_))
+        ));
+        let missing = Claim::Argmax {
+            output: 1,
+            index: 0,
+        };
+        let model = ModelBuilder::new(&graph)
+            .claims(vec![missing])
+            .build()
+            .unwrap();
+        assert!(matches!(
+            model.check_claims(&outputs),
+            Err(GraphError::InvalidClaim(_))
+        ));
+    }
+
+    #[test]
+    fn packs_small_regions_into_unused_columns() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 64]);
+        let z = graph.input("z", &[1, 4]);
+        let wide = graph.relu(&x);
+        let small = graph.sigmoid(&z);
+        let smaller = graph.relu(&z);
+        for t in [&wide, &small, &smaller] {
+            graph.output(t);
+        }
+        let model = ModelBuilder::new(&graph).max_rotations(16).build().unwrap();
+
+        let mut cs = ConstraintSystem::<halo2curves::pasta::Fp>::default();
+        let row_cap = model.max_node_size();
+        let vars = ModelVars::new(&mut cs, 17, 16, (2, row_cap), (0, row_cap), (0, vec![]));
+        // the relu of x spans 4 of the 5 columns, so both lookups of z are laid out in the last one
+        let offsets = model.pack_regions(&vars);
+        assert_eq!(offsets.into_values().collect_vec(), vec![0, 4, 4]);
+    }
+
+    #[test]
+    fn shares_vars_between_regions() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 3]);
+        let w = graph.constant(&[0.5; 6], &[2, 3]).unwrap();
+        let b = graph.constant(&[1.0, -1.0], &[2]).unwrap();
+        let y = graph.affine(&x, &w, &b);
+        let y = graph.relu(&y);
+        graph.output(&y);
+
+        // the affine layer takes its input, kernel, bias and output, the relu its input and output
+        let model = ModelBuilder::new(&graph).build().unwrap();
+        assert_eq!(model.num_vars(), (4, 0));
+        assert!(model.num_vars().0 < model.max_node_params() + model.max_node_vars_fused());
+
+        let public_params =
+            VarVisibility::new(Visibility::Private, Visibility::Public, Visibility::Public);
+        let model = ModelBuilder::new(&graph)
+            .visibility(public_params)
+            .build()
+            .unwrap();
+        assert_eq!(model.num_vars(), (2, 2));
+    }
+
+    #[test]
+    fn computes_rotations_from_the_largest_node() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 64]);
+        let y = graph.relu(&x);
+        graph.output(&y);
+        let model = ModelBuilder::new(&graph).build().unwrap();
+        assert_eq!(model.max_rotations, 64);
+        let model = ModelBuilder::new(&graph).max_rotations(16).build().unwrap();
+        assert_eq!(model.max_rotations, 16);
+
+        // in 2^10 rows, columns of 510 rows leave 514 for their blinding rows and the unusable last row
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 2000]);
+        let y = graph.relu(&x);
+        graph.output(&y);
+        let model = ModelBuilder::new(&graph)
+            .logrows(10)
+            .bits(8)
+            .scale(4)
+            .build()
+            .unwrap();
+        assert_eq!(model.max_rotations, 510);
+    }
+
+    #[test]
+    fn prunes_nodes_not_reaching_outputs() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 2]);
+        let y = graph.relu(&x);
+        // an auxiliary branch which isn't an output of the model
+        let _aux = graph.sigmoid(&x);
+        graph.output(&y);
+
+        let pruned = ModelBuilder::new(&graph).build().unwrap();
+        assert_eq!(pruned.nodes.flatten().len(), 2);
+        let unpruned = ModelBuilder::new(&graph)
+            .passes(&[Pass::FoldRescales])
+            .build()
+            .unwrap();
+        assert_eq!(unpruned.nodes.flatten().len(), 3);
+    }
+
+    #[test]
+    fn spills_consts_in_low_memory_mode() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 3]);
+        let w = graph
+            .constant(&[0.5, -1.0, 0.0, 2.0, 1.5, -0.5], &[2, 3])
+            .unwrap();
+        let b = graph.constant(&[1.0, -1.0], &[2]).unwrap();
+        let y = graph.affine(&x, &w, &b);
+        graph.output(&y);
+
+        let mut args = RunArgs::new(graph.to_onnx(), CircuitSettings::default(), Mode::Forward);
+        let model = Model::from_run_args(&args).unwrap();
+        args.low_memory = true;
+        let spilled = Model::from_run_args(&args).unwrap();
+        assert!(spilled
+            .nodes
+            .flatten()
+            .iter()
+            .all(|n| n.const_value.is_none() && n.raw_const_value.is_none()));
+        assert_eq!(spilled.consts.as_ref().unwrap().size(), 8 * 4);
+        for idx in model.const_nodes() {
+            assert_eq!(
+                spilled.const_value(idx).unwrap(),
+                model.const_value(idx).unwrap()
+            );
+        }
+        let input = Tensor::new(Some(&[128, -256, 64]), &[3]).unwrap();
+        assert_eq!(
+            spilled.forward(&[input.clone()]).unwrap(),
+            model.forward(&[input]).unwrap()
+        );
+    }
+
+    #[test]
+    fn fuses_chains_of_lookups() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 4]);
+        let y = graph.sigmoid(&x);
+        let y = graph.relu(&y);
+        graph.output(&y);
+
+        let fused = ModelBuilder::new(&graph).build().unwrap();
+        let lookups = fused
+            .nodes
+            .flatten()
+            .into_iter()
+            .filter(|n| n.opkind.is_lookup())
+            .collect_vec();
+        assert_eq!(lookups.len(), 1);
+        assert!(matches!(
+            &lookups[0].opkind,
+            OpKind::Lookup(LookupOp::Composed { ops }) if ops.len() == 2
+        ));
+
+        let unfused = ModelBuilder::new(&graph)
+            .passes(&[Pass::FoldRescales])
+            .build()
+            .unwrap();
+        assert_eq!(unfused.nodes.flatten().len(), 3);
+        let input = Tensor::new(Some(&[-256, -1, 0, 300]), &[4]).unwrap();
+        assert_eq!(
+            fused.forward(&[input.clone()]).unwrap(),
+            unfused.forward(&[input]).unwrap()
+        );
+
+        // intermediate results consumed elsewhere are kept
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 4]);
+        let hidden = graph.sigmoid(&x);
+        let y = graph.relu(&hidden);
+        graph.output(&hidden);
+        graph.output(&y);
+        let model = ModelBuilder::new(&graph).build().unwrap();
+        assert_eq!(model.nodes.flatten().len(), 3);
+    }
+
+    #[test]
+    fn rescales_between_layers_of_different_scales() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 4]);
+        let y = graph.relu(&x);
+        let z = graph.add(&y, &x);
+        graph.output(&z);
+        let model = ModelBuilder::new(&graph).build().unwrap();
+        let nodes = model.nodes.flatten();
+        let relu = nodes.iter().find(|n| n.opkind.is_lookup()).unwrap().idx;
+        let add = nodes.iter().find(|n| n.opkind.is_poly()).unwrap().idx;
+        // inputs of 1, -2, 0.5, 0 at the default scale of 7
+        let input = Tensor::new(Some(&[128, -256, 64, 0]), &[4]).unwrap();
+
+        // the sum runs at a scale of 4: the relu is re-targeted to output at that scale (unless rescales aren't folded), and the
+        // input is divided down to it
+        let down = BTreeMap::from([(add, 4)]);
+        let folded = ModelBuilder::new(&graph)
+            .layer_scales(down.clone())
+            .build()
+            .unwrap();
+        assert_eq!(folded.nodes.flatten().len(), 4);
+        let unfolded = ModelBuilder::new(&graph)
+            .layer_scales(down)
+            .passes(&[])
+            .build()
+            .unwrap();
+        assert_eq!(unfolded.nodes.flatten().len(), 5);
+        for model in [&folded, &unfolded] {
+            assert_eq!(model.get_output_scales(), vec![4]);
+            assert_eq!(
+                model.forward(&[input.clone()]).unwrap()[0],
+                Tensor::new(Some(&[32, -32, 16, 0]), &[4]).unwrap()
+            );
+        }
+
+        // the relu runs at a scale of 4, and its output is multiplied back up to the scale of the sum
+        let up = ModelBuilder::new(&graph)
+            .layer_scales(BTreeMap::from([(relu, 4)]))
+            .passes(&[])
+            .build()
+            .unwrap();
+        let nodes = up.nodes.flatten();
+        assert_eq!(nodes.len(), 4);
+        assert!(nodes
+            .iter()
+            .any(|n| n.idx > add && n.opkind == OpKind::Lookup(LookupOp::Mult { scale: 8 })));
+        assert_eq!(up.get_output_scales(), vec![7]);
+        assert_eq!(
+            up.forward(&[input]).unwrap()[0],
+            Tensor::new(Some(&[256, -256, 128, 0]), &[4]).unwrap()
+        );
+    }
+
+    #[test]
+    fn slices_the_graph_between_named_tensors() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 2]);
+        let hidden = graph.relu(&x);
+        let y = graph.sigmoid(&hidden);
+        graph.output(&y);
+
+        let head = ModelBuilder::new(&graph)
+            .input_nodes(vec![hidden.name().to_string()])
+            .build()
+            .unwrap();
+        assert_eq!(head.nodes.flatten().len(), 2);
+        assert_eq!(head.inputs.len(), 1);
+        assert!(head
+            .nodes
+            .flatten()
+            .iter()
+            .all(|n| !matches!(n.opkind, OpKind::Lookup(LookupOp::ReLU { .. }))));
+
+        let body = ModelBuilder::new(&graph)
+            .output_nodes(vec![hidden.name().to_string()])
+            .build()
+            .unwrap();
+        assert_eq!(body.nodes.flatten().len(), 2);
+
+        let missing = ModelBuilder::new(&graph).input_nodes(vec!["nope".to_string()]);
+        assert!(missing.build().is_err());
+    }
+}
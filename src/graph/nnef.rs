@@ -0,0 +1,341 @@
+use super::{GraphBuilder, GraphError, GraphTensor, ModelSource};
+use std::collections::HashMap;
+use tract_nnef::ast::{Argument, Invocation, LValue, Literal, RValue};
+use tract_nnef::framework::ProtoModel;
+
+/// The magic bytes opening gzip streams, as `.nnef.tgz` archives are.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+/// The magic of (ustar) tar archives, at offset 257 of their first header.
+const TAR_MAGIC: &[u8; 5] = b"ustar";
+
+fn invalid(msg: impl Into<String>) -> GraphError {
+    GraphError::InvalidNnef(msg.into())
+}
+
+/// Whether `bytes` are those of a (possibly gzipped) tar archive, as NNEF models are distributed.
+pub fn is_nnef(bytes: &[u8]) -> bool {
+    bytes.starts_with(&GZIP_MAGIC) || bytes.get(257..262) == Some(TAR_MAGIC)
+}
+
+impl ModelSource {
+    /// Whether the model is an NNEF archive (or directory) rather than an .onnx file, as told by its name or contents.
+    pub fn is_nnef(&self) -> bool {
+        match self {
+            ModelSource::Path(path) => {
+                let name = path.file_name().map(|n| n.to_string_lossy().to_lowercase());
+                path.is_dir()
+                    || name.map_or(false, |n| {
+                        [".nnef", ".nnef.tar", ".nnef.tgz", ".nnef.tar.gz"]
+                            .iter()
+                            .any(|ext| n.ends_with(ext))
+                    })
+            }
+            ModelSource::Bytes(bytes) => is_nnef(bytes),
+        }
+    }
+}
+
+/// Parses the NNEF archive (or directory) of `source` and converts its graph into an .onnx model, such that it is quantized
+/// and laid out as exported ONNX models are. The graph is read with `tract_nnef` and translated fragment by fragment, which
+/// sidesteps the typed (optimized) models tract builds from NNEF: ezkl lays out the operators of the original graph.
+pub fn to_onnx(source: &ModelSource) -> Result<Vec<u8>, GraphError> {
+    let nnef = tract_nnef::nnef();
+    let proto = match source {
+        ModelSource::Path(path) => nnef.proto_model_for_path(path),
+        ModelSource::Bytes(bytes) => nnef.proto_model_for_read(&mut bytes.as_slice()),
+    }
+    .map_err(|e| invalid(e.to_string()))?;
+    convert(&proto)
+}
+
+fn convert(proto: &ProtoModel) -> Result<Vec<u8>, GraphError> {
+    let graph_def = &proto.doc.graph_def;
+    let mut graph = GraphBuilder::new();
+    let mut values: HashMap<String, GraphTensor> = HashMap::new();
+
+    for assignment in &graph_def.body {
+        let name = match &assignment.left {
+            LValue::Identifier(name) => name.to_string(),
+            _ => return Err(invalid("fragments with several outputs")),
+        };
+        let invocation = match &assignment.right {
+            RValue::Invocation(invocation) => invocation,
+            // aliases of other values, e.g `y = x;`
+            rvalue => {
+                let value = operand(&mut graph, &values, rvalue)?;
+                values.insert(name, value);
+                continue;
+            }
+        };
+        let output = match invocation.id.as_str() {
+            "external" => {
+                let shape = ints(required(invocation, 0, "shape")?)?;
+                graph.input(&name, &shape)
+            }
+            "variable" => {
+                let label = string(required(invocation, 0, "label")?)?;
+                let (data, shape) = variable(proto, &label)?;
+                graph.constant(&data, &shape)?
+            }
+            "add" | "sub" => {
+                let a = operand(&mut graph, &values, required(invocation, 0, "x")?)?;
+                let b = operand(&mut graph, &values, required(invocation, 1, "y")?)?;
+                match invocation.id.as_str() {
+                    "add" => graph.add(&a, &b),
+                    _ => graph.sub(&a, &b),
+                }
+            }
+            "matmul" => {
+                for (i, flag) in [(2, "transposeA"), (3, "transposeB")] {
+                    if argument(invocation, i, flag).map_or(Ok(false), logical)? {
+                        return Err(invalid("transposed matmul operands"));
+                    }
+                }
+                let a = operand(&mut graph, &values, required(invocation, 0, "A")?)?;
+                let b = operand(&mut graph, &values, required(invocation, 1, "B")?)?;
+                graph.matmul(&a, &b)
+            }
+            "linear" => {
+                let input = operand(&mut graph, &values, required(invocation, 0, "input")?)?;
+                let filter = required(invocation, 1, "filter")?;
+                let weights = operand(&mut graph, &values, filter)?;
+                let bias = match bias(proto, invocation)? {
+                    Some(bias) => graph.constant(&bias, &[bias.len()])?,
+                    // linear layers without bias are given a zero one
+                    None => {
+                        let out = variable_shape(proto, graph_def_variable(graph_def, filter)?)?[0];
+                        graph.constant(&vec![0.0; out], &[out])?
+                    }
+                };
+                graph.affine(&input, &weights, &bias)
+            }
+            "conv" => {
+                let input = operand(&mut graph, &values, required(invocation, 0, "input")?)?;
+                let filter = required(invocation, 1, "filter")?;
+                let kernel = operand(&mut graph, &values, filter)?;
+                let bias = match bias(proto, invocation)? {
+                    Some(bias) => Some(graph.constant(&bias, &[bias.len()])?),
+                    None => None,
+                };
+                if argument(invocation, 7, "groups").map_or(Ok(1), int)? != 1 {
+                    return Err(invalid("grouped convolutions"));
+                }
+                let dilation = argument(invocation, 6, "dilation").map_or(Ok(vec![]), ints)?;
+                if dilation.iter().any(|d| *d != 1) {
+                    return Err(invalid("dilated convolutions"));
+                }
+                let stride = match argument(invocation, 5, "stride").map_or(Ok(vec![]), ints)?[..] {
+                    [] => (1, 1),
+                    [h, w] => (h, w),
+                    _ => return Err(invalid("convolutions other than 2D ones")),
+                };
+                let padding = match argument(invocation, 4, "padding") {
+                    Some(padding) => pairs(padding)?,
+                    None => vec![],
+                };
+                let padding = match padding[..] {
+                    [(top, bottom), (left, right)] if top == bottom && left == right => (top, left),
+                    [] => {
+                        let k_shape =
+                            variable_shape(proto, graph_def_variable(graph_def, filter)?)?;
+                        if k_shape.len() != 4
+                            || k_shape[2] % 2 == 0
+                            || k_shape[3] % 2 == 0
+                            || stride != (1, 1)
+                        {
+                            return Err(invalid(
+                                "automatic padding of even kernels or strided convolutions",
+                            ));
+                        }
+                        (k_shape[2] / 2, k_shape[3] / 2)
+                    }
+                    _ => return Err(invalid("asymmetric padding")),
+                };
+                graph.conv(&input, &kernel, bias.as_ref(), padding, stride)
+            }
+            "relu" => {
+                let x = operand(&mut graph, &values, required(invocation, 0, "x")?)?;
+                graph.relu(&x)
+            }
+            "sigmoid" => {
+                let x = operand(&mut graph, &values, required(invocation, 0, "x")?)?;
+                graph.sigmoid(&x)
+            }
+            // ezkl quantizes the model itself, so copies pass their input through
+            "copy" => operand(&mut graph, &values, required(invocation, 0, "x")?)?,
+            op => return Err(invalid(format!("unsupported fragment {}", op))),
+        };
+        values.insert(name, output);
+    }
+
+    for result in &graph_def.results {
+        let output = values
+            .get(&result.to_string())
+            .ok_or_else(|| invalid(format!("result {} is never computed", result)))?;
+        graph.output(output);
+    }
+    Ok(graph.to_onnx())
+}
+
+/// The argument of `invocation` named `name`, or else its positional argument at `idx`.
+fn argument<'a>(invocation: &'a Invocation, idx: usize, name: &str) -> Option<&'a RValue> {
+    let named = |a: &&Argument| a.id.as_ref().map_or(false, |id| id.to_string() == name);
+    match invocation.arguments.iter().find(named) {
+        Some(a) => Some(&a.rvalue),
+        None => invocation
+            .arguments
+            .get(idx)
+            .filter(|a| a.id.is_none())
+            .map(|a| &a.rvalue),
+    }
+}
+
+fn required<'a>(
+    invocation: &'a Invocation,
+    idx: usize,
+    name: &str,
+) -> Result<&'a RValue, GraphError> {
+    argument(invocation, idx, name)
+        .ok_or_else(|| invalid(format!("{} without {}", invocation.id, name)))
+}
+
+/// The value named by `rvalue`, or a scalar constant for numeric literals.
+fn operand(
+    graph: &mut GraphBuilder,
+    values: &HashMap<String, GraphTensor>,
+    rvalue: &RValue,
+) -> Result<GraphTensor, GraphError> {
+    match rvalue {
+        RValue::Identifier(name) => values
+            .get(&name.to_string())
+            .cloned()
+            .ok_or_else(|| invalid(format!("{} is used before it is computed", name))),
+        RValue::Literal(Literal::Numeric(n)) => graph.constant(&[number(n)?], &[]),
+        _ => Err(invalid("unsupported operand")),
+    }
+}
+
+fn number(n: &str) -> Result<f32, GraphError> {
+    n.parse()
+        .map_err(|_| invalid(format!("{} is not a number", n)))
+}
+
+fn int(rvalue: &RValue) -> Result<usize, GraphError> {
+    match rvalue {
+        RValue::Literal(Literal::Numeric(n)) => n
+            .parse()
+            .map_err(|_| invalid(format!("{} is not a positive integer", n))),
+        _ => Err(invalid("expected an integer")),
+    }
+}
+
+fn ints(rvalue: &RValue) -> Result<Vec<usize>, GraphError> {
+    match rvalue {
+        RValue::Array(items) => items.iter().map(int).collect(),
+        RValue::Literal(Literal::Array(items)) => items
+            .iter()
+            .map(|l| int(&RValue::Literal(l.clone())))
+            .collect(),
+        _ => Err(invalid("expected an array of integers")),
+    }
+}
+
+/// The `(before, after)` pairs of a padding argument.
+fn pairs(rvalue: &RValue) -> Result<Vec<(usize, usize)>, GraphError> {
+    let items = match rvalue {
+        RValue::Array(items) => items,
+        _ => return Err(invalid("expected an array of padding pairs")),
+    };
+    items
+        .iter()
+        .map(|item| match item {
+            RValue::Tuple(pair) if pair.len() == 2 => Ok((int(&pair[0])?, int(&pair[1])?)),
+            _ => Err(invalid("expected a padding pair")),
+        })
+        .collect()
+}
+
+fn logical(rvalue: &RValue) -> Result<bool, GraphError> {
+    match rvalue {
+        RValue::Literal(Literal::Logical(b)) => Ok(*b),
+        _ => Err(invalid("expected a logical")),
+    }
+}
+
+fn string(rvalue: &RValue) -> Result<String, GraphError> {
+    match rvalue {
+        RValue::Literal(Literal::String(s)) => Ok(s.to_string()),
+        _ => Err(invalid("expected a string")),
+    }
+}
+
+/// The values (as f32, in row major order) and shape of the tensor stored under `label` in the archive.
+fn variable(proto: &ProtoModel, label: &str) -> Result<(Vec<f32>, Vec<usize>), GraphError> {
+    let tensor = proto
+        .tensors
+        .get(label)
+        .ok_or_else(|| invalid(format!("missing tensor {}", label)))?;
+    let values = tensor
+        .cast_to::<f32>()
+        .map_err(|e| invalid(e.to_string()))?
+        .as_slice::<f32>()
+        .map_err(|e| invalid(e.to_string()))?
+        .to_vec();
+    Ok((values, tensor.shape().to_vec()))
+}
+
+fn variable_shape(proto: &ProtoModel, label: String) -> Result<Vec<usize>, GraphError> {
+    Ok(variable(proto, &label)?.1)
+}
+
+/// The label of the `variable` fragment assigned to the identifier `rvalue`.
+fn graph_def_variable(
+    graph_def: &tract_nnef::ast::GraphDef,
+    rvalue: &RValue,
+) -> Result<String, GraphError> {
+    let name = match rvalue {
+        RValue::Identifier(name) => name.to_string(),
+        _ => return Err(invalid("expected an identifier")),
+    };
+    graph_def
+        .body
+        .iter()
+        .find_map(|a| match (&a.left, &a.right) {
+            (LValue::Identifier(id), RValue::Invocation(inv))
+                if id.to_string() == name && inv.id == "variable" =>
+            {
+                argument(inv, 0, "label")
+            }
+            _ => None,
+        })
+        .ok_or_else(|| invalid(format!("{} is not a variable", name)))
+        .and_then(string)
+}
+
+/// The flattened bias of a `linear` or `conv` fragment (stored as `[1, channels]` in NNEF), if it isn't zero.
+fn bias(proto: &ProtoModel, invocation: &Invocation) -> Result<Option<Vec<f32>>, GraphError> {
+    match argument(invocation, 2, "bias") {
+        None => Ok(None),
+        Some(RValue::Literal(Literal::Numeric(n))) if number(n)? == 0.0 => Ok(None),
+        Some(rvalue) => {
+            let label = graph_def_variable(&proto.doc.graph_def, rvalue)?;
+            Ok(Some(variable(proto, &label)?.0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_archives() {
+        assert!(is_nnef(&[0x1f, 0x8b, 0x08, 0x00]));
+        let mut tar = vec![0; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert!(is_nnef(&tar));
+        assert!(!is_nnef(b"\x08\x07\x12\x04onnx"));
+        assert!(ModelSource::from("network.nnef.tgz").is_nnef());
+        assert!(!ModelSource::from("network.onnx").is_nnef());
+    }
+}
@@ -1,20 +1,24 @@
 use super::utilities::{node_output_shapes, scale_to_multiplier, vector_to_quantized};
+use crate::circuit::decomposed::DecomposedConfig;
 use crate::circuit::lookup::Config as LookupConfig;
 use crate::circuit::lookup::Op as LookupOp;
 use crate::circuit::polynomial::Config as PolyConfig;
+use crate::circuit::polynomial::DotConfig;
 use crate::circuit::polynomial::Op as PolyOp;
-use crate::graph::GraphError;
+use crate::graph::{CustomConfig, CustomOps, GraphError};
 use crate::tensor::ops::{add, const_mult, div, mult};
-use crate::tensor::Tensor;
 use crate::tensor::TensorType;
+use crate::tensor::{broadcast_shape, resolve_shape, Tensor};
 use anyhow::Result;
 use halo2_proofs::arithmetic::FieldExt;
 use itertools::Itertools;
 use log::{info, trace, warn};
-use std::collections::{btree_map::Entry, BTreeMap};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{btree_map::Entry, BTreeMap, HashSet};
 use std::error::Error;
 use std::fmt;
 use std::ops::Deref;
+use std::sync::Arc;
 use tabled::Tabled;
 use tract_onnx;
 use tract_onnx::prelude::{DatumType, InferenceFact, Node as OnnxNode, OutletId};
@@ -22,6 +26,7 @@ use tract_onnx::tract_hir::{
     infer::Factoid,
     internal::InferenceOp,
     ops::activations::LeakyRelu,
+    ops::array::PermuteAxes,
     ops::cnn::{Conv, PoolSpec, SumPool}, //MaxPool,},
     ops::expandable::Expansion,
     ops::nn::DataFormat,
@@ -36,7 +41,7 @@ use tract_onnx::tract_hir::{
 // Eventually, though, we probably want to keep them and treat them directly (layouting and configuring
 // at each type of node)
 /// Enum of the different kinds of operations `ezkl` can support.
-#[derive(Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Ord, PartialOrd, Serialize, Deserialize)]
 pub enum OpKind {
     /// A nonlinearity
     Lookup(LookupOp),
@@ -46,6 +51,8 @@ pub enum OpKind {
     Const,
     /// Input node
     Input,
+    /// An op implemented by a registered [CustomOp](super::CustomOp), keyed by name
+    Custom(String),
     /// Unable to parse the node type
     Unknown(String),
     #[allow(missing_docs)]
@@ -94,6 +101,7 @@ impl OpKind {
             "GlobalAvgPool" => OpKind::Poly(PolyOp::GlobalSumPool),
             "Reshape" => OpKind::Poly(PolyOp::Reshape(Vec::new())),
             "Flatten" => OpKind::Poly(PolyOp::Flatten(Vec::new())),
+            "PermuteAxes" => OpKind::Poly(PolyOp::Permute(Vec::new())),
             "BatchNorm" => OpKind::Poly(PolyOp::BatchNorm),
             "Pad" => OpKind::Poly(PolyOp::Identity),
             c => {
@@ -121,6 +129,11 @@ impl OpKind {
     pub fn is_const(&self) -> bool {
         matches!(self, OpKind::Const)
     }
+
+    /// Identify custom OpKind
+    pub fn is_custom(&self) -> bool {
+        matches!(self, OpKind::Custom(_))
+    }
 }
 
 impl fmt::Display for OpKind {
@@ -130,6 +143,7 @@ impl fmt::Display for OpKind {
             OpKind::Input => write!(f, "input"),
             OpKind::Lookup(s) => write!(f, "{}", s),
             OpKind::Poly(s) => write!(f, "{}", s),
+            OpKind::Custom(c) => write!(f, "custom {}", c),
             OpKind::Unknown(c) => write!(f, "? {}", c),
             OpKind::None => write!(f, "n/a",),
         }
@@ -140,16 +154,33 @@ impl fmt::Display for OpKind {
 #[allow(missing_docs)]
 #[derive(Clone, Default, Debug)]
 pub enum NodeConfig<F: FieldExt + TensorType> {
-    Lookup(LookupConfig<F>, Vec<usize>),
-    Poly(PolyConfig<F>, Vec<usize>),
+    Lookup(LookupConfig<F>, Vec<OutletId>),
+    Decomposed(DecomposedConfig<F>, Vec<OutletId>),
+    Poly(PolyConfig<F>, Vec<OutletId>),
+    Dot(DotConfig<F>, PolyOp, Vec<OutletId>),
+    Custom(Arc<dyn CustomConfig<F>>, Vec<OutletId>),
     Const,
     Input,
     #[default]
     NotConfigured,
 }
 
+impl<F: FieldExt + TensorType> NodeConfig<F> {
+    /// The outlets (node and output slot) whose results are fed into the node.
+    pub fn inputs(&self) -> &[OutletId] {
+        match self {
+            NodeConfig::Lookup(_, idx)
+            | NodeConfig::Decomposed(_, idx)
+            | NodeConfig::Poly(_, idx)
+            | NodeConfig::Dot(_, _, idx)
+            | NodeConfig::Custom(_, idx) => idx,
+            NodeConfig::Const | NodeConfig::Input | NodeConfig::NotConfigured => &[],
+        }
+    }
+}
+
 /// Representation of an execution graph divided into execution 'buckets'.
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct NodeGraph(pub BTreeMap<Option<usize>, BTreeMap<usize, Node>>);
 
 impl NodeGraph {
@@ -170,24 +201,28 @@ impl NodeGraph {
         }
     }
 
-    /// Flattens the inner [BTreeMap] into a [Vec] of [Node]s.
+    /// Flattens the inner [BTreeMap] into a [Vec] of [Node]s, in execution order (see [NodeGraph::execution_order]).
     pub fn flatten(&self) -> Vec<Node> {
-        let a = self
+        let nodes: BTreeMap<usize, &Node> = self
             .0
-            .clone()
-            .into_values()
-            .map(|d| d.into_values().collect())
-            .collect::<Vec<Vec<Node>>>();
-        let mut c: Vec<Node> = a
-            .iter()
-            .flatten()
-            .collect::<Vec<&Node>>()
-            .iter()
-            .map(|e| (*e).clone())
+            .values()
+            .flat_map(|nodes| nodes.values())
+            .map(|n| (n.idx, n))
             .collect();
+        self.execution_order()
+            .into_iter()
+            .map(|idx| nodes[&idx].clone())
+            .collect()
+    }
 
-        c.sort_by_key(|v| v.idx);
-        c
+    /// The indices of the nodes of the graph in the order they are executed in, see [execution_order].
+    pub fn execution_order(&self) -> Vec<usize> {
+        execution_order(self.0.values().flat_map(|nodes| nodes.values()))
+    }
+
+    /// The node at `idx`, if the graph holds it.
+    pub fn get(&self, idx: usize) -> Option<&Node> {
+        self.0.values().find_map(|nodes| nodes.get(&idx))
     }
 
     /// Retrieves a node, as specified by idx, from the Graph of bucketed nodes.
@@ -200,6 +235,82 @@ impl NodeGraph {
             .collect::<Vec<Node>>()[0];
         c.clone()
     }
+
+    /// Renders the nodes of the graph as a json array of [NodeSummary], ordered by index.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        let nodes = self.flatten();
+        serde_json::to_string(&nodes.iter().map(NodeSummary::from).collect::<Vec<_>>())
+    }
+
+    /// Renders the graph in the Graphviz DOT language, with the nodes of each execution bucket grouped in a cluster.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph model {\n    node [shape=box];\n");
+        for (bucket, nodes) in self.0.iter() {
+            let indent = match bucket {
+                Some(b) => {
+                    dot.push_str(&format!(
+                        "    subgraph cluster_{} {{\n        label=\"bucket {}\";\n",
+                        b, b
+                    ));
+                    "        "
+                }
+                None => "    ",
+            };
+            for node in nodes.values() {
+                dot.push_str(&format!(
+                    "{}n{} [label=\"{}\"];\n",
+                    indent,
+                    node.idx,
+                    node.label().replace('"', "\\\"").replace('\n', "\\n")
+                ));
+            }
+            if bucket.is_some() {
+                dot.push_str("    }\n");
+            }
+        }
+        for (from, to) in self.edges() {
+            dot.push_str(&format!("    n{} -> n{};\n", from, to));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Renders the graph as a Mermaid flowchart, with the nodes of each execution bucket grouped in a subgraph.
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("flowchart TD\n");
+        for (bucket, nodes) in self.0.iter() {
+            let indent = match bucket {
+                Some(b) => {
+                    mermaid.push_str(&format!("    subgraph bucket_{} [bucket {}]\n", b, b));
+                    "        "
+                }
+                None => "    ",
+            };
+            for node in nodes.values() {
+                mermaid.push_str(&format!(
+                    "{}n{}[\"{}\"]\n",
+                    indent,
+                    node.idx,
+                    node.label().replace('"', "#quot;").replace('\n', "<br/>")
+                ));
+            }
+            if bucket.is_some() {
+                mermaid.push_str("    end\n");
+            }
+        }
+        for (from, to) in self.edges() {
+            mermaid.push_str(&format!("    n{} --> n{}\n", from, to));
+        }
+        mermaid
+    }
+
+    /// The (input, consumer) pairs of node indices, in the order of consumers.
+    fn edges(&self) -> Vec<(usize, usize)> {
+        self.flatten()
+            .iter()
+            .flat_map(|node| node.inputs.iter().map(move |i| (i.node, node.idx)))
+            .collect()
+    }
 }
 
 fn display_option<T: fmt::Debug>(o: &Option<T>) -> String {
@@ -249,10 +360,13 @@ fn display_tensorf32(o: &Option<Tensor<f32>>) -> String {
 /// * `const_value` - The constants potentially associated with this self.
 /// * `idx` - The node's unique identifier.
 /// * `bucket` - The execution bucket this node has been assigned to.
-#[derive(Clone, Debug, Default, Tabled)]
+#[derive(Clone, Debug, Default, Tabled, Serialize, Deserialize)]
 pub struct Node {
     /// [OpKind] enum, i.e what operation this node represents.
     pub opkind: OpKind,
+    #[tabled(skip)]
+    /// The name of the node in the model file.
+    pub name: String,
     /// The inferred maximum value that can appear in the output tensor given previous quantization choices.
     pub output_max: f32,
     /// The denominator in the fixed point representation for the node's input. Tensors of differing scales should not be combined.
@@ -268,6 +382,8 @@ pub struct Node {
     // Usually there is a simple in and out shape of the node as an operator.  For example, an Affine node has three input_shapes (one for the input, weight, and bias),
     // but in_dim is [in], out_dim is [out]
     #[tabled(display_with = "display_inputs")]
+    #[serde(serialize_with = "serialize_outlets")]
+    #[serde(deserialize_with = "deserialize_outlets")]
     /// The indices of the node's inputs.
     pub inputs: Vec<OutletId>,
     #[tabled(display_with = "display_vector")]
@@ -276,6 +392,10 @@ pub struct Node {
     #[tabled(display_with = "display_vector")]
     /// Dimensions of output.
     pub out_dims: Vec<usize>,
+    #[tabled(skip)]
+    #[serde(default)]
+    /// Dimensions of the outputs past the first (at slot 0, see `out_dims`), in order of slot, for ops with several outputs.
+    pub slot_dims: Vec<Vec<usize>>,
     /// The node's unique identifier.
     pub idx: usize,
     #[tabled(display_with = "display_option")]
@@ -283,21 +403,132 @@ pub struct Node {
     pub bucket: Option<usize>,
 }
 
+/// The indices of `nodes` in an order they can be executed in: by index, except that each node is preceded by its inputs. Nodes
+/// are converted in index order, so this is their index order unless rescaling nodes were inserted between them (see
+/// [Node::rescale]), which are indexed past the nodes of the model.
+pub fn execution_order<'a>(nodes: impl IntoIterator<Item = &'a Node>) -> Vec<usize> {
+    let nodes: BTreeMap<usize, &Node> = nodes.into_iter().map(|n| (n.idx, n)).collect();
+    let mut order = vec![];
+    let mut visited = HashSet::new();
+    for idx in nodes.keys() {
+        let mut stack = vec![(*idx, false)];
+        while let Some((idx, expanded)) = stack.pop() {
+            if expanded {
+                order.push(idx);
+                continue;
+            }
+            let node = match nodes.get(&idx) {
+                Some(node) if visited.insert(idx) => node,
+                _ => continue,
+            };
+            stack.push((idx, true));
+            stack.extend(node.inputs.iter().rev().map(|i| (i.node, false)));
+        }
+    }
+    order
+}
+
+/// Serializes tract outlets as (node, slot) pairs.
+pub(super) fn serialize_outlets<S: Serializer>(
+    outlets: &[OutletId],
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    outlets
+        .iter()
+        .map(|o| (o.node, o.slot))
+        .collect::<Vec<_>>()
+        .serialize(s)
+}
+
+/// Deserializes tract outlets from (node, slot) pairs.
+pub(super) fn deserialize_outlets<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<Vec<OutletId>, D::Error> {
+    let outlets = Vec::<(usize, usize)>::deserialize(d)?;
+    Ok(outlets
+        .into_iter()
+        .map(|(node, slot)| OutletId::new(node, slot))
+        .collect())
+}
+
+/// The parameters of a [Node] which tools consume, as printed by `table --format json`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NodeSummary {
+    /// The node's unique identifier.
+    pub idx: usize,
+    /// The operation the node represents.
+    pub opkind: String,
+    /// The indices of the node's inputs.
+    pub inputs: Vec<usize>,
+    /// Dimensions of input.
+    pub in_dims: Vec<Vec<usize>>,
+    /// Dimensions of output.
+    pub out_dims: Vec<usize>,
+    /// The denominator in the fixed point representation for the node's input.
+    pub in_scale: i32,
+    /// The denominator in the fixed point representation for the node's output.
+    pub out_scale: i32,
+    /// The inferred maximum value that can appear in the output tensor.
+    pub output_max: f32,
+    /// The execution bucket this node has been assigned to.
+    pub bucket: Option<usize>,
+}
+
+impl From<&Node> for NodeSummary {
+    fn from(node: &Node) -> Self {
+        NodeSummary {
+            idx: node.idx,
+            opkind: node.opkind.to_string(),
+            inputs: node.inputs.iter().map(|i| i.node).collect(),
+            in_dims: node.in_dims.clone(),
+            out_dims: node.out_dims.clone(),
+            in_scale: node.in_scale,
+            out_scale: node.out_scale,
+            output_max: node.output_max,
+            bucket: node.bucket,
+        }
+    }
+}
+
 impl Node {
+    /// The number of outputs of the node.
+    pub fn num_outputs(&self) -> usize {
+        1 + self.slot_dims.len()
+    }
+
+    /// The dimensions of the output of the node at `slot`.
+    pub fn outlet_dims(&self, slot: usize) -> Option<&[usize]> {
+        match slot {
+            0 => Some(&self.out_dims),
+            s => self.slot_dims.get(s - 1).map(|d| d.as_slice()),
+        }
+    }
+
+    /// A (multiline) description of the node for rendering graphs: its index, op, output shape and scales.
+    fn label(&self) -> String {
+        format!(
+            "{}: {}\n{:?}\nscale {} -> {}",
+            self.idx, self.opkind, self.out_dims, self.in_scale, self.out_scale
+        )
+    }
+
     /// Converts a tract [OnnxNode] into an ezkl [Node].
     /// # Arguments:
     /// * `node` - [OnnxNode]
     /// * `other_nodes` - [BTreeMap] of other previously initialized [Node]s in the computational graph.
     /// * `scale` - The denominator in the fixed point representation. Tensors of differing scales should not be combined.
     /// * `idx` - The node's unique identifier.
+    /// * `custom_ops` - The ops implementing ONNX ops ezkl doesn't support, consulted before built-in ops.
     pub fn new(
         mut node: OnnxNode<InferenceFact, Box<dyn InferenceOp>>,
         other_nodes: &mut BTreeMap<usize, Node>,
         scale: i32,
         idx: usize,
+        custom_ops: &CustomOps,
     ) -> Result<Self, Box<dyn Error>> {
         trace!("Create {:?}", node);
         trace!("Create op {:?}", node.op);
+        let node_name = node.name.clone();
         let output_shapes = match node_output_shapes(&node) {
             Ok(s) => Some(s),
             _ => None,
@@ -306,12 +537,23 @@ impl Node {
         let mut inputs = vec![];
         for i in node.inputs.iter_mut() {
             match other_nodes.get(&i.node) {
-                Some(n) => inputs.push(n.clone()),
+                Some(n) => {
+                    // the input as fed through its outlet, with the dims of the output at its slot
+                    let mut n = n.clone();
+                    if let Some(dims) = n.outlet_dims(i.slot) {
+                        n.out_dims = dims.to_vec();
+                    }
+                    inputs.push(n)
+                }
                 None => return Err(Box::new(GraphError::MissingNode(i.node))),
             }
         }
 
-        let mut opkind = OpKind::new(node.op().name().as_ref()); // parses the op name
+        let name = node.op().name();
+        let mut opkind = match custom_ops.contains(&name) {
+            true => OpKind::Custom(name.to_string()),
+            false => OpKind::new(name.as_ref()), // parses the op name
+        };
 
         let mn = match opkind {
             OpKind::Lookup(ref s) => {
@@ -495,6 +737,10 @@ impl Node {
                             ..Default::default()
                         }
                     }
+                    // compositions are only formed by fusing chains of lookup nodes, once they are converted
+                    LookupOp::Composed { .. } => {
+                        return Err(Box::new(GraphError::OpMismatch(idx, opkind.clone())));
+                    }
                 }
             }
             OpKind::Poly(ref s) => {
@@ -792,12 +1038,13 @@ impl Node {
                                 return Err(Box::new(GraphError::RescalingError(opkind)));
                             };
 
+                        let out_dims = Self::broadcast_dims(idx, &opkind, &inputs)?;
                         Node {
                             idx,
                             opkind,
                             inputs: node.inputs.clone(),
                             in_dims: inputs.iter().map(|inp| inp.out_dims.clone()).collect(),
-                            out_dims: inputs[0].out_dims.clone(),
+                            out_dims,
                             in_scale: inputs.iter().map(|input| input.out_scale).max().unwrap(),
                             out_scale: inputs.iter().map(|input| input.out_scale).max().unwrap(),
                             output_max,
@@ -839,12 +1086,13 @@ impl Node {
                                 return Err(Box::new(GraphError::RescalingError(opkind)));
                             };
 
+                        let out_dims = Self::broadcast_dims(idx, &opkind, &inputs)?;
                         Node {
                             idx,
                             opkind,
                             inputs: node.inputs.clone(),
                             in_dims: inputs.iter().map(|inp| inp.out_dims.clone()).collect(),
-                            out_dims: inputs[0].out_dims.clone(),
+                            out_dims,
                             in_scale: inputs.iter().map(|input| input.out_scale).max().unwrap(),
                             out_scale: inputs.iter().map(|input| input.out_scale).max().unwrap(),
                             output_max,
@@ -854,12 +1102,13 @@ impl Node {
                     PolyOp::Mult => {
                         let input_node = &inputs[0];
 
+                        let out_dims = Self::broadcast_dims(idx, &opkind, &inputs)?;
                         Node {
                             idx,
                             opkind,
                             inputs: node.inputs.clone(),
                             in_dims: inputs.iter().map(|inp| inp.out_dims.clone()).collect(),
-                            out_dims: inputs[0].out_dims.clone(),
+                            out_dims,
                             in_scale: input_node.out_scale,
                             out_scale: inputs.iter().map(|input| input.out_scale).sum::<i32>(),
                             output_max: f32::powf(
@@ -935,6 +1184,38 @@ impl Node {
                             ..Default::default()
                         }
                     }
+                    PolyOp::Permute(_) => {
+                        let input_node = &inputs[0];
+                        let op = Box::new(node.op());
+                        let permute: &PermuteAxes = match op.downcast_ref::<Box<dyn Expansion>>() {
+                            Some(b) => match (*b).as_any().downcast_ref() {
+                                Some(b) => b,
+                                None => {
+                                    return Err(Box::new(GraphError::OpMismatch(idx, opkind)));
+                                }
+                            },
+                            None => {
+                                return Err(Box::new(GraphError::OpMismatch(idx, opkind)));
+                            }
+                        };
+                        let axes = Self::permuted_axes(
+                            permute.axes.as_ref().map(|a| a.to_vec()),
+                            input_node.out_dims.len(),
+                        )
+                        .ok_or_else(|| GraphError::InvalidDims(idx, opkind.clone()))?;
+
+                        Node {
+                            idx,
+                            opkind: OpKind::Poly(PolyOp::Permute(axes.clone())),
+                            inputs: node.inputs.clone(),
+                            in_dims: vec![input_node.out_dims.clone()],
+                            out_dims: axes.iter().map(|a| input_node.out_dims[*a]).collect(),
+                            in_scale: input_node.out_scale,
+                            out_scale: input_node.out_scale,
+                            output_max: input_node.output_max,
+                            ..Default::default()
+                        }
+                    }
                     PolyOp::Reshape(_) => {
                         let input_node = &inputs[0];
                         let shape_const_node = &inputs[1];
@@ -946,37 +1227,14 @@ impl Node {
                                 )));
                             }
                         };
-                        let shapes = &shape_const[0..];
-                        let new_dims: Result<Vec<usize>, Box<dyn Error>> =
-                            if shapes.iter().all(|x| x > &0) {
-                                let mut res = vec![];
-                                for x in shapes.iter() {
-                                    if x <= &0 {
-                                        return Err(Box::new(GraphError::InvalidDims(idx, opkind)));
-                                    }
-                                    res.push(*x as usize);
-                                }
-                                Ok(res)
-                            } else {
-                                let num_entries: usize = input_node.out_dims.iter().product();
-                                let explicit_prod: i32 =
-                                    shapes.iter().filter(|x| *x > &0).product();
-                                if explicit_prod <= 0 {
-                                    return Err(Box::new(GraphError::InvalidDims(idx, opkind)));
-                                }
-                                let inferred = num_entries / (explicit_prod as usize);
-                                let mut new_dims: Vec<usize> = Vec::new();
-                                for i in shapes {
-                                    match i {
-                                        -1 => new_dims.push(inferred),
-                                        0 => continue,
-                                        x => new_dims.push(*x as usize),
-                                    }
-                                }
-                                Ok(new_dims)
-                            };
-
-                        let new_dims = new_dims?;
+                        // a dimension of 0 copies that of the input in onnx, which is the batch dimension the graph drops
+                        let shape = shape_const
+                            .iter()
+                            .filter(|x| **x != 0)
+                            .map(|x| *x as i64)
+                            .collect::<Vec<_>>();
+                        let new_dims = resolve_shape(&input_node.out_dims, &shape)
+                            .map_err(|_| GraphError::InvalidDims(idx, opkind.clone()))?;
 
                         Node {
                             idx,
@@ -1088,6 +1346,22 @@ impl Node {
                     ..Default::default()
                 }
             }
+            OpKind::Custom(ref name) => {
+                let in_dims = inputs.iter().map(|n| n.out_dims.clone()).collect_vec();
+                let in_scales = inputs.iter().map(|n| n.out_scale).collect_vec();
+                let q = custom_ops.quantize(name, &in_dims, &in_scales, scale)?;
+                Node {
+                    idx,
+                    opkind,
+                    inputs: node.inputs.clone(),
+                    in_dims,
+                    out_dims: q.out_dims,
+                    in_scale: in_scales.first().copied().unwrap_or(scale),
+                    out_scale: q.out_scale,
+                    output_max: q.output_max,
+                    ..Default::default()
+                }
+            }
             OpKind::Unknown(_) => {
                 warn!("{:?} is unknown", opkind);
                 Node::default()
@@ -1096,7 +1370,56 @@ impl Node {
                 return Err(Box::new(GraphError::UnsupportedOp));
             }
         };
-        Ok(mn)
+        Ok(Node {
+            name: node_name,
+            ..mn
+        })
+    }
+
+    /// The axes an activation of rank `rank` is permuted along, given the `perm` of an ONNX Transpose (which reverses the
+    /// axes when unset). Permutations spanning the batch dim activations are stripped of must leave it in place, and are
+    /// otherwise not supported.
+    fn permuted_axes(perm: Option<Vec<usize>>, rank: usize) -> Option<Vec<usize>> {
+        let axes = match perm {
+            None => (0..rank).rev().collect(),
+            Some(perm) if perm.len() == rank => perm,
+            Some(perm) if perm.len() == rank + 1 && perm[0] == 0 => perm[1..]
+                .iter()
+                .map(|a| a.checked_sub(1))
+                .collect::<Option<_>>()?,
+            Some(_) => return None,
+        };
+        let mut sorted = axes.clone();
+        sorted.sort_unstable();
+        (sorted == (0..rank).collect::<Vec<_>>()).then_some(axes)
+    }
+
+    /// The dims of the output of an elementwise op over `inputs`, which are broadcast against each other (see
+    /// [broadcast_shape]). Constants hold the batch dim activations are stripped of, such that a `[1, C, 1, 1]` bias added to
+    /// a `[C, H, W]` activation broadcasts to `[C, H, W]` rather than `[1, C, H, W]`: the leading unit dims past the rank of
+    /// the activations are dropped.
+    fn broadcast_dims(
+        idx: usize,
+        opkind: &OpKind,
+        inputs: &[Node],
+    ) -> Result<Vec<usize>, Box<dyn Error>> {
+        let dims = inputs
+            .iter()
+            .try_fold(vec![], |dims, input| {
+                broadcast_shape(&dims, &input.out_dims)
+            })
+            .map_err(|_| GraphError::InvalidDims(idx, opkind.clone()))?;
+        let rank = inputs
+            .iter()
+            .filter(|input| !input.opkind.is_const())
+            .map(|input| input.out_dims.len())
+            .max()
+            .unwrap_or(dims.len());
+        let leading = dims[..dims.len() - rank]
+            .iter()
+            .take_while(|d| **d == 1)
+            .count();
+        Ok(dims[leading..].to_vec())
     }
 
     /// Ensures all inputs to a node have the same fixed point denominator.
@@ -1151,6 +1474,77 @@ impl Node {
         Ok(())
     }
 
+    /// Re-targets a lookup node such that it outputs at a (lower) fixed point `scale`. The rescaling is folded
+    /// into the node's lookup table.
+    pub fn rescale_output(&mut self, scale: i32) {
+        let scale_diff = self.out_scale - scale;
+        let mult = scale_to_multiplier(scale_diff) as usize;
+        let op = match &self.opkind {
+            OpKind::Lookup(LookupOp::Div { scale: s }) => LookupOp::Div { scale: s * mult },
+            OpKind::Lookup(LookupOp::ReLU { scale: s }) => LookupOp::ReLU { scale: s * mult },
+            OpKind::Lookup(LookupOp::LeakyReLU { scale: s, slope }) => LookupOp::LeakyReLU {
+                scale: s * mult,
+                slope: *slope,
+            },
+            OpKind::Lookup(LookupOp::PReLU { scale: s, slopes }) => LookupOp::PReLU {
+                scale: s * mult,
+                slopes: slopes.clone(),
+            },
+            OpKind::Lookup(LookupOp::Sigmoid { scales }) => LookupOp::Sigmoid {
+                scales: (scales.0, scale_to_multiplier(scale) as usize),
+            },
+            _ => return,
+        };
+        info!(
+            "------ rescaled lookup node {:?}: {:?} -> {:?}",
+            self.idx, self.out_scale, scale
+        );
+        self.opkind = OpKind::Lookup(op);
+        self.output_max /= mult as f32;
+        self.out_scale = scale;
+    }
+
+    /// A lookup node rescaling the output of `producer` at `outlet` to the fixed point `scale`: dividing it by the power of two
+    /// between their scales, or multiplying it when `scale` is higher. Inserted between nodes running at different scales.
+    pub fn rescale(producer: &Node, outlet: OutletId, scale: i32, idx: usize) -> Self {
+        let scale_diff = producer.out_scale - scale;
+        let mult = scale_to_multiplier(scale_diff.abs());
+        let (op, output_max) = match scale_diff > 0 {
+            true => (
+                LookupOp::Div {
+                    scale: mult as usize,
+                },
+                producer.output_max / mult,
+            ),
+            false => (
+                LookupOp::Mult {
+                    scale: mult as usize,
+                },
+                producer.output_max * mult,
+            ),
+        };
+        info!(
+            "------ rescaled output {:?} of node {:?}: {:?} -> {:?}",
+            outlet.slot, producer.idx, producer.out_scale, scale
+        );
+        let dims = producer
+            .outlet_dims(outlet.slot)
+            .unwrap_or(&producer.out_dims)
+            .to_vec();
+        Node {
+            idx,
+            opkind: OpKind::Lookup(op),
+            name: format!("{}.rescaled", producer.name),
+            inputs: vec![outlet],
+            in_dims: vec![dims.clone()],
+            out_dims: dims,
+            in_scale: producer.out_scale,
+            out_scale: scale,
+            output_max,
+            ..Default::default()
+        }
+    }
+
     /// Re-quantizes a constant value node to a new scale.
     fn scale_up_const_node(node: &mut Node, scale: i32) -> Result<&mut Node, Box<dyn Error>> {
         if !node.opkind.is_const() {
@@ -1175,3 +1569,95 @@ impl Node {
         Ok(node)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph() -> NodeGraph {
+        let mut graph = NodeGraph::new();
+        graph.insert(
+            None,
+            0,
+            Node {
+                opkind: OpKind::Input,
+                out_dims: vec![3],
+                idx: 0,
+                ..Default::default()
+            },
+        );
+        graph.insert(
+            Some(0),
+            1,
+            Node {
+                opkind: OpKind::Lookup(LookupOp::ReLU { scale: 1 }),
+                inputs: vec![OutletId::new(0, 0)],
+                out_dims: vec![3],
+                idx: 1,
+                bucket: Some(0),
+                ..Default::default()
+            },
+        );
+        graph
+    }
+
+    #[test]
+    fn renders_dot() {
+        let dot = graph().to_dot();
+        assert!(dot.starts_with("digraph model {"));
+        assert!(dot.contains("subgraph cluster_0 {"));
+        assert!(dot.contains("n0 [label=\"0: input\\n[3]\\nscale 0 -> 0\"];"));
+        assert!(dot.contains("n0 -> n1;"));
+    }
+
+    #[test]
+    fn renders_json() {
+        let json: serde_json::Value = serde_json::from_str(&graph().to_json().unwrap()).unwrap();
+        assert_eq!(json[1]["opkind"], graph().filter(1).opkind.to_string());
+        assert_eq!(json[1]["inputs"], serde_json::json!([0]));
+        assert_eq!(json[1]["bucket"], 0);
+        assert_eq!(json[0]["bucket"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn renders_mermaid() {
+        let mermaid = graph().to_mermaid();
+        assert!(mermaid.starts_with("flowchart TD"));
+        assert!(mermaid.contains("subgraph bucket_0 [bucket 0]"));
+        assert!(mermaid.contains("n0[\"0: input<br/>[3]<br/>scale 0 -> 0\"]"));
+        assert!(mermaid.contains("n0 --> n1"));
+    }
+
+    #[test]
+    fn constants_broadcast_without_their_batch_dim() {
+        let node = |opkind, out_dims| Node {
+            opkind,
+            out_dims,
+            ..Default::default()
+        };
+        let add = OpKind::Poly(PolyOp::Add);
+        let activation = node(OpKind::Input, vec![3, 4, 4]);
+        let bias = node(OpKind::Const, vec![1, 3, 1, 1]);
+        let dims = Node::broadcast_dims(2, &add, &[activation.clone(), bias]).unwrap();
+        assert_eq!(dims, vec![3, 4, 4]);
+        let scalar = node(OpKind::Const, vec![1]);
+        let dims = Node::broadcast_dims(2, &add, &[activation.clone(), scalar]).unwrap();
+        assert_eq!(dims, vec![3, 4, 4]);
+        let other = node(OpKind::Input, vec![2, 4, 4]);
+        assert!(Node::broadcast_dims(2, &add, &[activation, other]).is_err());
+    }
+
+    #[test]
+    fn transposes_keep_the_batch_dim_in_place() {
+        // nchw -> nhwc over a [c, h, w] activation
+        assert_eq!(
+            Node::permuted_axes(Some(vec![0, 2, 3, 1]), 3),
+            Some(vec![1, 2, 0])
+        );
+        assert_eq!(Node::permuted_axes(Some(vec![1, 0]), 2), Some(vec![1, 0]));
+        assert_eq!(Node::permuted_axes(None, 3), Some(vec![2, 1, 0]));
+        // moving the batch dim isn't supported
+        assert_eq!(Node::permuted_axes(Some(vec![1, 0, 2, 3]), 3), None);
+        assert_eq!(Node::permuted_axes(Some(vec![0, 1, 1]), 3), None);
+    }
+}
@@ -0,0 +1,277 @@
+use super::{GraphError, ModelSource};
+use prost::Message;
+use std::sync::Arc;
+use tract_onnx::pb::{
+    attribute_proto::AttributeType, tensor_proto::DataType, tensor_shape_proto, type_proto,
+    AttributeProto, GraphProto, ModelProto, NodeProto, OperatorSetIdProto, TensorProto,
+    TensorShapeProto, TypeProto, ValueInfoProto,
+};
+
+/// The ONNX opset the ops of a [GraphBuilder] are emitted for.
+const OPSET: i64 = 13;
+
+/// A tensor of a graph under construction, as returned by the ops of a [GraphBuilder].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GraphTensor(String);
+
+impl GraphTensor {
+    /// The name of the tensor in the emitted ONNX graph, e.g. to cut the model at it (see [ModelBuilder::input_nodes]).
+    ///
+    /// [ModelBuilder::input_nodes]: super::ModelBuilder::input_nodes
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Builds the computational graph of a model directly in Rust, for models which don't export cleanly to ONNX. The graph is
+/// emitted as an ONNX model in memory, such that it is quantized, bucketed, and proven exactly as an exported model would be:
+/// pass it (as a [ModelSource]) wherever a model is expected.
+///
+/// ```no_run
+/// use ezkl::graph::{GraphBuilder, ModelBuilder};
+///
+/// let mut graph = GraphBuilder::new();
+/// let x = graph.input("x", &[1, 3]);
+/// let w = graph.constant(&[1.0, 0.0, 0.0, 0.0, -1.0, 0.0], &[2, 3]).unwrap();
+/// let b = graph.constant(&[0.0, 0.5], &[2]).unwrap();
+/// let y = graph.affine(&x, &w, &b);
+/// let y = graph.relu(&y);
+/// graph.output(&y);
+/// let model = ModelBuilder::new(&graph).build().unwrap();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct GraphBuilder {
+    nodes: Vec<NodeProto>,
+    initializers: Vec<TensorProto>,
+    inputs: Vec<ValueInfoProto>,
+    outputs: Vec<ValueInfoProto>,
+}
+
+impl GraphBuilder {
+    /// Starts an empty graph.
+    pub fn new() -> Self {
+        GraphBuilder::default()
+    }
+
+    /// A name for the next tensor of the graph, unique across the graph.
+    fn fresh(&self, prefix: &str) -> String {
+        format!(
+            "{}_{}",
+            prefix,
+            self.nodes.len() + self.initializers.len() + self.inputs.len()
+        )
+    }
+
+    /// Adds an input to the graph, of the given `shape` (including the batch dimension, as exported models do). Inputs are fed
+    /// to the model in the order they are added.
+    pub fn input(&mut self, name: &str, shape: &[usize]) -> GraphTensor {
+        let dims = shape
+            .iter()
+            .map(|d| tensor_shape_proto::Dimension {
+                value: Some(tensor_shape_proto::dimension::Value::DimValue(*d as i64)),
+                ..Default::default()
+            })
+            .collect();
+        self.inputs.push(ValueInfoProto {
+            name: name.to_string(),
+            r#type: Some(TypeProto {
+                value: Some(type_proto::Value::TensorType(type_proto::Tensor {
+                    elem_type: DataType::Float as i32,
+                    shape: Some(TensorShapeProto { dim: dims }),
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        GraphTensor(name.to_string())
+    }
+
+    /// Adds a constant (e.g. weights) of the given `shape` to the graph, holding `values` in row major order.
+    pub fn constant(&mut self, values: &[f32], shape: &[usize]) -> Result<GraphTensor, GraphError> {
+        if values.len() != shape.iter().product::<usize>() {
+            return Err(GraphError::ConstantShape(values.len(), shape.to_vec()));
+        }
+        let name = self.fresh("const");
+        self.initializers.push(TensorProto {
+            name: name.clone(),
+            dims: shape.iter().map(|d| *d as i64).collect(),
+            data_type: DataType::Float as i32,
+            float_data: values.to_vec(),
+            ..Default::default()
+        });
+        Ok(GraphTensor(name))
+    }
+
+    /// Adds a node running the ONNX op `op_type` over `inputs`, returning its output.
+    fn op(
+        &mut self,
+        op_type: &str,
+        inputs: &[&GraphTensor],
+        attribute: Vec<AttributeProto>,
+    ) -> GraphTensor {
+        let name = self.fresh(&op_type.to_lowercase());
+        self.nodes.push(NodeProto {
+            name: name.clone(),
+            op_type: op_type.to_string(),
+            input: inputs.iter().map(|i| i.0.clone()).collect(),
+            output: vec![name.clone()],
+            attribute,
+            ..Default::default()
+        });
+        GraphTensor(name)
+    }
+
+    /// Elementwise sum of `a` and `b`.
+    pub fn add(&mut self, a: &GraphTensor, b: &GraphTensor) -> GraphTensor {
+        self.op("Add", &[a, b], vec![])
+    }
+
+    /// Elementwise difference of `a` and `b`.
+    pub fn sub(&mut self, a: &GraphTensor, b: &GraphTensor) -> GraphTensor {
+        self.op("Sub", &[a, b], vec![])
+    }
+
+    /// Matrix product of `a` and `b`.
+    pub fn matmul(&mut self, a: &GraphTensor, b: &GraphTensor) -> GraphTensor {
+        self.op("MatMul", &[a, b], vec![])
+    }
+
+    /// A fully connected layer: `input` multiplied by the transpose of `weights` (of shape `[out, in]`, as in pytorch), plus
+    /// `bias`.
+    pub fn affine(
+        &mut self,
+        input: &GraphTensor,
+        weights: &GraphTensor,
+        bias: &GraphTensor,
+    ) -> GraphTensor {
+        let trans_b = AttributeProto {
+            name: "transB".to_string(),
+            r#type: AttributeType::Int as i32,
+            i: 1,
+            ..Default::default()
+        };
+        self.op("Gemm", &[input, weights, bias], vec![trans_b])
+    }
+
+    /// A 2D convolution of `input` (NCHW) with `kernel` (OIHW) and an optional `bias`, padding each side of the input by
+    /// `padding` and moving the kernel by `stride`.
+    pub fn conv(
+        &mut self,
+        input: &GraphTensor,
+        kernel: &GraphTensor,
+        bias: Option<&GraphTensor>,
+        padding: (usize, usize),
+        stride: (usize, usize),
+    ) -> GraphTensor {
+        let ints = |name: &str, ints: Vec<usize>| AttributeProto {
+            name: name.to_string(),
+            r#type: AttributeType::Ints as i32,
+            ints: ints.into_iter().map(|i| i as i64).collect(),
+            ..Default::default()
+        };
+        let attributes = vec![
+            ints("pads", vec![padding.0, padding.1, padding.0, padding.1]),
+            ints("strides", vec![stride.0, stride.1]),
+        ];
+        let mut inputs = vec![input, kernel];
+        inputs.extend(bias);
+        self.op("Conv", &inputs, attributes)
+    }
+
+    /// Elementwise rectified linear unit of `x`.
+    pub fn relu(&mut self, x: &GraphTensor) -> GraphTensor {
+        self.op("Relu", &[x], vec![])
+    }
+
+    /// Elementwise sigmoid of `x`.
+    pub fn sigmoid(&mut self, x: &GraphTensor) -> GraphTensor {
+        self.op("Sigmoid", &[x], vec![])
+    }
+
+    /// The sum of all elements of `x`.
+    pub fn sum(&mut self, x: &GraphTensor) -> GraphTensor {
+        let keepdims = AttributeProto {
+            name: "keepdims".to_string(),
+            r#type: AttributeType::Int as i32,
+            i: 0,
+            ..Default::default()
+        };
+        self.op("ReduceSum", &[x], vec![keepdims])
+    }
+
+    /// Marks `t` as an output of the model. Outputs are returned in the order they are marked.
+    pub fn output(&mut self, t: &GraphTensor) {
+        self.outputs.push(ValueInfoProto {
+            name: t.0.clone(),
+            ..Default::default()
+        });
+    }
+
+    /// The graph, encoded as the contents of an .onnx file.
+    pub fn to_onnx(&self) -> Vec<u8> {
+        let model = ModelProto {
+            ir_version: 8,
+            opset_import: vec![OperatorSetIdProto {
+                domain: String::new(),
+                version: OPSET,
+            }],
+            producer_name: "ezkl".to_string(),
+            graph: Some(GraphProto {
+                name: "ezkl".to_string(),
+                node: self.nodes.clone(),
+                initializer: self.initializers.clone(),
+                input: self.inputs.clone(),
+                output: self.outputs.clone(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        model.encode_to_vec()
+    }
+}
+
+impl From<&GraphBuilder> for ModelSource {
+    fn from(graph: &GraphBuilder) -> Self {
+        ModelSource::Bytes(Arc::new(graph.to_onnx()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{ModelBuilder, OpKind};
+    use crate::tensor::Tensor;
+
+    #[test]
+    fn builds_models_without_onnx_files() {
+        let mut graph = GraphBuilder::new();
+        let x = graph.input("x", &[1, 3]);
+        let w = graph
+            .constant(&[1.0, 0.0, 0.0, 0.0, -1.0, 0.0], &[2, 3])
+            .unwrap();
+        let b = graph.constant(&[0.0, 0.0], &[2]).unwrap();
+        let y = graph.affine(&x, &w, &b);
+        let y = graph.relu(&y);
+        graph.output(&y);
+        assert!(graph.constant(&[1.0], &[2]).is_err());
+
+        let model = ModelBuilder::new(&graph).build().unwrap();
+        let ops = model
+            .nodes
+            .flatten()
+            .iter()
+            .map(|n| n.opkind.to_string())
+            .collect::<Vec<_>>();
+        assert!(model
+            .nodes
+            .flatten()
+            .iter()
+            .all(|n| !matches!(n.opkind, OpKind::Unknown(_))));
+        assert_eq!(ops.len(), 5, "{:?}", ops);
+
+        // inputs of 1, 2, 3 at the default scale of 7
+        let input = Tensor::new(Some(&[128, 256, 384]), &[3]).unwrap();
+        let outputs = model.forward(&[input]).unwrap();
+        assert_eq!(outputs[0], Tensor::new(Some(&[128, 0]), &[2]).unwrap());
+    }
+}
@@ -0,0 +1,498 @@
+use super::{GraphBuilder, GraphError, GraphTensor, ModelSource};
+use std::collections::HashMap;
+
+/// The file identifier of TFLite flatbuffers, following the offset of the root table.
+const IDENTIFIER: &[u8; 4] = b"TFL3";
+
+// the builtin operators of the TFLite schema which are supported
+const ADD: i32 = 0;
+const CONV_2D: i32 = 3;
+const DEQUANTIZE: i32 = 6;
+const FULLY_CONNECTED: i32 = 9;
+const LOGISTIC: i32 = 14;
+const RELU: i32 = 19;
+const SUB: i32 = 41;
+const QUANTIZE: i32 = 114;
+
+// the tensor types of the TFLite schema which weights can be stored as
+const FLOAT32: u8 = 0;
+const INT32: u8 = 2;
+const UINT8: u8 = 3;
+const INT16: u8 = 7;
+const INT8: u8 = 9;
+
+fn invalid(msg: impl Into<String>) -> GraphError {
+    GraphError::InvalidTflite(msg.into())
+}
+
+/// A table of a flatbuffer, read field by field through its vtable.
+#[derive(Clone, Copy)]
+struct Table<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+fn read<const N: usize>(buf: &[u8], pos: usize) -> Result<[u8; N], GraphError> {
+    buf.get(pos..pos + N)
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| invalid(format!("read out of bounds at {}", pos)))
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> Result<usize, GraphError> {
+    Ok(u32::from_le_bytes(read(buf, pos)?) as usize)
+}
+
+impl<'a> Table<'a> {
+    fn root(buf: &'a [u8]) -> Result<Self, GraphError> {
+        Ok(Table {
+            buf,
+            pos: read_u32(buf, 0)?,
+        })
+    }
+
+    /// The position of the value of `field`, if it is set.
+    fn field(&self, field: usize) -> Result<Option<usize>, GraphError> {
+        let offset = i32::from_le_bytes(read(self.buf, self.pos)?) as i64;
+        let vtable = usize::try_from(self.pos as i64 - offset)
+            .map_err(|_| invalid("vtable out of bounds"))?;
+        let vtable_len = u16::from_le_bytes(read(self.buf, vtable)?) as usize;
+        let entry = 4 + 2 * field;
+        if entry + 2 > vtable_len {
+            return Ok(None);
+        }
+        match u16::from_le_bytes(read(self.buf, vtable + entry)?) as usize {
+            0 => Ok(None),
+            offset => Ok(Some(self.pos + offset)),
+        }
+    }
+
+    fn u8(&self, field: usize, default: u8) -> Result<u8, GraphError> {
+        Ok(match self.field(field)? {
+            Some(pos) => read::<1>(self.buf, pos)?[0],
+            None => default,
+        })
+    }
+
+    fn i32(&self, field: usize, default: i32) -> Result<i32, GraphError> {
+        Ok(match self.field(field)? {
+            Some(pos) => i32::from_le_bytes(read(self.buf, pos)?),
+            None => default,
+        })
+    }
+
+    fn u32(&self, field: usize, default: u32) -> Result<u32, GraphError> {
+        Ok(match self.field(field)? {
+            Some(pos) => u32::from_le_bytes(read(self.buf, pos)?),
+            None => default,
+        })
+    }
+
+    /// The position of the target of the offset stored at `pos`.
+    fn deref(&self, pos: usize) -> Result<usize, GraphError> {
+        Ok(pos + read_u32(self.buf, pos)?)
+    }
+
+    fn table(&self, field: usize) -> Result<Option<Table<'a>>, GraphError> {
+        match self.field(field)? {
+            Some(pos) => Ok(Some(Table {
+                buf: self.buf,
+                pos: self.deref(pos)?,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// The position of the first element and the length of the vector at `field`.
+    fn vector(&self, field: usize) -> Result<(usize, usize), GraphError> {
+        match self.field(field)? {
+            Some(pos) => {
+                let vector = self.deref(pos)?;
+                Ok((vector + 4, read_u32(self.buf, vector)?))
+            }
+            None => Ok((0, 0)),
+        }
+    }
+
+    fn tables(&self, field: usize) -> Result<Vec<Table<'a>>, GraphError> {
+        let (start, len) = self.vector(field)?;
+        (0..len)
+            .map(|i| {
+                Ok(Table {
+                    buf: self.buf,
+                    pos: self.deref(start + 4 * i)?,
+                })
+            })
+            .collect()
+    }
+
+    fn bytes(&self, field: usize) -> Result<&'a [u8], GraphError> {
+        let (start, len) = self.vector(field)?;
+        self.buf
+            .get(start..start + len)
+            .ok_or_else(|| invalid("vector out of bounds"))
+    }
+
+    /// The vector of `N` byte scalars at `field`, decoded with `f`.
+    fn scalars<const N: usize, T>(
+        &self,
+        field: usize,
+        f: fn([u8; N]) -> T,
+    ) -> Result<Vec<T>, GraphError> {
+        let (start, len) = self.vector(field)?;
+        (0..len)
+            .map(|i| Ok(f(read(self.buf, start + N * i)?)))
+            .collect()
+    }
+
+    fn string(&self, field: usize) -> Result<String, GraphError> {
+        Ok(String::from_utf8_lossy(self.bytes(field)?).into_owned())
+    }
+}
+
+/// A tensor of a TFLite subgraph.
+struct TensorInfo<'a> {
+    name: String,
+    shape: Vec<usize>,
+    ty: u8,
+    data: &'a [u8],
+    scale: Vec<f32>,
+    zero_point: Vec<i64>,
+    axis: usize,
+}
+
+impl<'a> TensorInfo<'a> {
+    fn read(tensor: &Table<'a>, buffers: &[&'a [u8]]) -> Result<Self, GraphError> {
+        let shape = tensor
+            .scalars(0, i32::from_le_bytes)?
+            .into_iter()
+            .map(|d| usize::try_from(d).map_err(|_| invalid("dynamic dimension")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let data = buffers
+            .get(tensor.u32(2, 0)? as usize)
+            .copied()
+            .unwrap_or_default();
+        let (scale, zero_point, axis) = match tensor.table(4)? {
+            Some(q) => (
+                q.scalars(2, f32::from_le_bytes)?,
+                q.scalars(3, i64::from_le_bytes)?,
+                q.i32(6, 0)? as usize,
+            ),
+            None => (vec![], vec![], 0),
+        };
+        Ok(TensorInfo {
+            name: tensor.string(3)?,
+            shape,
+            ty: tensor.u8(1, FLOAT32)?,
+            data,
+            scale,
+            zero_point,
+            axis,
+        })
+    }
+
+    /// The values of a constant tensor, dequantized to floats.
+    fn values(&self) -> Result<Vec<f32>, GraphError> {
+        let raw: Vec<f32> = match self.ty {
+            FLOAT32 => {
+                return Ok(self
+                    .data
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+                    .collect())
+            }
+            INT32 => self
+                .data
+                .chunks_exact(4)
+                .map(|b| i32::from_le_bytes(b.try_into().unwrap()) as f32)
+                .collect(),
+            INT16 => self
+                .data
+                .chunks_exact(2)
+                .map(|b| i16::from_le_bytes(b.try_into().unwrap()) as f32)
+                .collect(),
+            UINT8 => self.data.iter().map(|b| *b as f32).collect(),
+            INT8 => self.data.iter().map(|b| *b as i8 as f32).collect(),
+            ty => {
+                return Err(invalid(format!(
+                    "unsupported weights of type {} in {}",
+                    ty, self.name
+                )))
+            }
+        };
+        if self.scale.is_empty() {
+            return Ok(raw);
+        }
+        // quantized weights are dequantized per tensor, or per channel along `axis`
+        let stride: usize = self.shape.iter().skip(self.axis + 1).product();
+        let channels = self.shape.get(self.axis).copied().unwrap_or(1);
+        Ok(raw
+            .iter()
+            .enumerate()
+            .map(|(i, q)| {
+                let c = match self.scale.len() {
+                    1 => 0,
+                    _ => (i / stride.max(1)) % channels,
+                };
+                let zero_point = self.zero_point.get(c).copied().unwrap_or_default() as f32;
+                self.scale[c.min(self.scale.len() - 1)] * (q - zero_point)
+            })
+            .collect())
+    }
+}
+
+/// Moves the last axis of a rank 4 shape to the second position, i.e from TFLite's NHWC (or OHWI) layout to ONNX's NCHW (or
+/// OIHW), leaving other shapes as is.
+fn to_channels_first(shape: &[usize]) -> Vec<usize> {
+    match shape {
+        [n, h, w, c] => vec![*n, *c, *h, *w],
+        _ => shape.to_vec(),
+    }
+}
+
+/// Permutes the values of a rank 4 tensor of the given (channels last) `shape` as [to_channels_first] does its shape.
+fn values_channels_first(values: Vec<f32>, shape: &[usize]) -> Vec<f32> {
+    match shape {
+        [n, h, w, c] => {
+            let mut out = Vec::with_capacity(values.len());
+            for i in 0..*n {
+                for j in 0..*c {
+                    for y in 0..*h {
+                        for x in 0..*w {
+                            out.push(values[((i * h + y) * w + x) * c + j]);
+                        }
+                    }
+                }
+            }
+            out
+        }
+        _ => values,
+    }
+}
+
+/// The symmetric padding of a `SAME` padded convolution over `size` values with a kernel of `kernel` values and `stride`.
+fn same_padding(size: usize, kernel: usize, stride: usize) -> Result<usize, GraphError> {
+    let out = (size + stride - 1) / stride;
+    let total = ((out - 1) * stride + kernel).saturating_sub(size);
+    match total % 2 {
+        0 => Ok(total / 2),
+        _ => Err(invalid("asymmetric SAME padding")),
+    }
+}
+
+/// Whether `bytes` are those of a TFLite flatbuffer.
+pub fn is_tflite(bytes: &[u8]) -> bool {
+    bytes.get(4..8) == Some(IDENTIFIER)
+}
+
+impl ModelSource {
+    /// Whether the model is a TFLite flatbuffer rather than an .onnx file, as told by its extension or contents.
+    pub fn is_tflite(&self) -> bool {
+        match self {
+            ModelSource::Path(path) => path.extension().map_or(false, |e| e == "tflite"),
+            ModelSource::Bytes(bytes) => is_tflite(bytes),
+        }
+    }
+}
+
+/// Converts the first subgraph of the TFLite flatbuffer `bytes` into an .onnx model, such that it is quantized and laid out as
+/// exported ONNX models are. Quantized weights are dequantized (ezkl quantizes them again at its own scale), and `QUANTIZE` and
+/// `DEQUANTIZE` ops are dropped. Activations are converted to channels first (NCHW), the layout inputs are then fed in.
+pub fn to_onnx(bytes: &[u8]) -> Result<Vec<u8>, GraphError> {
+    if !is_tflite(bytes) {
+        return Err(invalid("missing TFL3 identifier"));
+    }
+    let model = Table::root(bytes)?;
+    let opcodes = model
+        .tables(1)?
+        .iter()
+        .map(|code| {
+            // builtin codes past 127 are only stored in the wider field, older files only set the deprecated one
+            Ok((code.u8(0, 0)? as i8 as i32).max(code.i32(3, 0)?))
+        })
+        .collect::<Result<Vec<_>, GraphError>>()?;
+    let buffers = model
+        .tables(4)?
+        .iter()
+        .map(|b| b.bytes(0))
+        .collect::<Result<Vec<_>, _>>()?;
+    let subgraph = *model
+        .tables(2)?
+        .first()
+        .ok_or_else(|| invalid("no subgraph"))?;
+    let tensors = subgraph
+        .tables(0)?
+        .iter()
+        .map(|t| TensorInfo::read(t, &buffers))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut graph = GraphBuilder::new();
+    let mut values: HashMap<i32, GraphTensor> = HashMap::new();
+    for idx in subgraph.scalars(1, i32::from_le_bytes)? {
+        let t = tensor(&tensors, idx)?;
+        let input = graph.input(&t.name, &to_channels_first(&t.shape));
+        values.insert(idx, input);
+    }
+
+    for op in subgraph.tables(3)? {
+        let code = *opcodes
+            .get(op.u32(0, 0)? as usize)
+            .ok_or_else(|| invalid("opcode out of bounds"))?;
+        let inputs = op.scalars(1, i32::from_le_bytes)?;
+        let outputs = op.scalars(2, i32::from_le_bytes)?;
+        let options = op.table(4)?;
+        let operand = |graph: &mut GraphBuilder, i: usize| -> Result<GraphTensor, GraphError> {
+            let idx = *inputs.get(i).ok_or_else(|| invalid("missing operand"))?;
+            if let Some(value) = values.get(&idx) {
+                return Ok(value.clone());
+            }
+            let t = tensor(&tensors, idx)?;
+            if t.data.is_empty() {
+                return Err(invalid(format!(
+                    "tensor {} is used before it is computed",
+                    t.name
+                )));
+            }
+            graph.constant(
+                &values_channels_first(t.values()?, &t.shape),
+                &to_channels_first(&t.shape),
+            )
+        };
+        let (output, activation) = match code {
+            ADD | SUB => {
+                let a = operand(&mut graph, 0)?;
+                let b = operand(&mut graph, 1)?;
+                let output = match code {
+                    ADD => graph.add(&a, &b),
+                    _ => graph.sub(&a, &b),
+                };
+                (output, option(options, 0)?)
+            }
+            FULLY_CONNECTED => {
+                let input = operand(&mut graph, 0)?;
+                let weights = operand(&mut graph, 1)?;
+                let bias = match inputs.get(2) {
+                    Some(idx) if *idx >= 0 => operand(&mut graph, 2)?,
+                    // fully connected layers without bias are given a zero one
+                    _ => {
+                        let out = tensor(&tensors, inputs[1])?.shape[0];
+                        graph.constant(&vec![0.0; out], &[out])?
+                    }
+                };
+                (graph.affine(&input, &weights, &bias), option(options, 0)?)
+            }
+            CONV_2D => {
+                let input = operand(&mut graph, 0)?;
+                let kernel = operand(&mut graph, 1)?;
+                let bias = match inputs.get(2) {
+                    Some(idx) if *idx >= 0 => Some(operand(&mut graph, 2)?),
+                    _ => None,
+                };
+                let options = options.ok_or_else(|| invalid("conv without options"))?;
+                if options.i32(4, 1)? != 1 || options.i32(5, 1)? != 1 {
+                    return Err(invalid("dilated convolutions"));
+                }
+                let stride = (
+                    options.i32(2, 1)?.max(1) as usize,
+                    options.i32(1, 1)?.max(1) as usize,
+                );
+                let (in_shape, k_shape) = (
+                    &tensor(&tensors, inputs[0])?.shape,
+                    &tensor(&tensors, inputs[1])?.shape,
+                );
+                let padding = match options.u8(0, 0)? {
+                    // SAME
+                    0 => (
+                        same_padding(in_shape[1], k_shape[1], stride.0)?,
+                        same_padding(in_shape[2], k_shape[2], stride.1)?,
+                    ),
+                    _ => (0, 0),
+                };
+                let output = graph.conv(&input, &kernel, bias.as_ref(), padding, stride);
+                (output, options.u8(3, 0)?)
+            }
+            RELU => {
+                let input = operand(&mut graph, 0)?;
+                (graph.relu(&input), 0)
+            }
+            LOGISTIC => {
+                let input = operand(&mut graph, 0)?;
+                (graph.sigmoid(&input), 0)
+            }
+            // ezkl quantizes the model itself, so quantization ops pass their input through
+            QUANTIZE | DEQUANTIZE => (operand(&mut graph, 0)?, 0),
+            code => return Err(invalid(format!("unsupported builtin op {}", code))),
+        };
+        let output = match activation {
+            0 => output,
+            1 => graph.relu(&output),
+            act => return Err(invalid(format!("unsupported fused activation {}", act))),
+        };
+        let idx = *outputs
+            .first()
+            .ok_or_else(|| invalid("op without output"))?;
+        values.insert(idx, output);
+    }
+
+    for idx in subgraph.scalars(2, i32::from_le_bytes)? {
+        let output = values
+            .get(&idx)
+            .ok_or_else(|| invalid(format!("output {} is never computed", idx)))?;
+        graph.output(output);
+    }
+    Ok(graph.to_onnx())
+}
+
+fn tensor<'a, 'b>(
+    tensors: &'b [TensorInfo<'a>],
+    idx: i32,
+) -> Result<&'b TensorInfo<'a>, GraphError> {
+    usize::try_from(idx)
+        .ok()
+        .and_then(|i| tensors.get(i))
+        .ok_or_else(|| invalid(format!("tensor {} out of bounds", idx)))
+}
+
+/// The fused activation of an op, stored in the `field` of its options.
+fn option(options: Option<Table<'_>>, field: usize) -> Result<u8, GraphError> {
+    match options {
+        Some(options) => options.u8(field, 0),
+        None => Ok(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_layouts_and_weights() {
+        assert!(is_tflite(b"\x1c\0\0\0TFL3"));
+        assert!(!is_tflite(b"\x08\x07\x12\x04onnx"));
+        assert!(to_onnx(b"not a model").is_err());
+
+        // a 1x2x2x2 NHWC tensor, channel c at position (y, x) holding 10 * c + 2 * y + x
+        let shape = [1, 2, 2, 2];
+        let nhwc = vec![0.0, 10.0, 1.0, 11.0, 2.0, 12.0, 3.0, 13.0];
+        assert_eq!(to_channels_first(&shape), vec![1, 2, 2, 2]);
+        assert_eq!(
+            values_channels_first(nhwc, &shape),
+            vec![0.0, 1.0, 2.0, 3.0, 10.0, 11.0, 12.0, 13.0]
+        );
+
+        // int8 weights quantized per output channel
+        let weights = TensorInfo {
+            name: "weights".to_string(),
+            shape: vec![2, 2],
+            ty: INT8,
+            data: &[2, 4, 0xfe, 1],
+            scale: vec![0.5, 2.0],
+            zero_point: vec![0, 1],
+            axis: 0,
+        };
+        assert_eq!(weights.values().unwrap(), vec![1.0, 2.0, -6.0, 0.0]);
+
+        assert_eq!(same_padding(28, 3, 1).unwrap(), 1);
+        assert_eq!(same_padding(28, 5, 1).unwrap(), 2);
+        assert!(same_padding(28, 2, 1).is_err());
+    }
+}
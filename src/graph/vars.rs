@@ -1,33 +1,71 @@
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::str::FromStr;
 
-use crate::commands::Cli;
+use crate::commands::CircuitSettings;
 use crate::tensor::TensorType;
 use crate::tensor::{ValTensor, VarTensor};
 use halo2_proofs::{arithmetic::FieldExt, plonk::ConstraintSystem};
 use itertools::Itertools;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use super::GraphError;
 
 /// Label Enum to track whether model input, model parameters, and model output are public or private
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Visibility {
     /// Mark an item as private to the prover (not in the proof submitted for verification)
     Private,
     /// Mark an item as public (sent in the proof submitted for verification)
     Public,
+    /// Mark an item as hashed: only its Poseidon hash is public, and the item itself is passed to the verifier separately
+    Hashed,
+    /// Mark an item as committed to: only the Poseidon hash of a key and the item is public. Unlike [Visibility::Hashed]
+    /// this reveals nothing about the item to those who don't hold the key, even when the item is guessable.
+    Committed,
 }
 impl Visibility {
+    #[allow(missing_docs)]
+    pub fn is_private(&self) -> bool {
+        matches!(&self, Visibility::Private)
+    }
     #[allow(missing_docs)]
     pub fn is_public(&self) -> bool {
         matches!(&self, Visibility::Public)
     }
+    #[allow(missing_docs)]
+    pub fn is_hashed(&self) -> bool {
+        matches!(&self, Visibility::Hashed)
+    }
+    #[allow(missing_docs)]
+    pub fn is_committed(&self) -> bool {
+        matches!(&self, Visibility::Committed)
+    }
 }
 impl std::fmt::Display for Visibility {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Visibility::Private => write!(f, "private"),
             Visibility::Public => write!(f, "public"),
+            Visibility::Hashed => write!(f, "hashed"),
+            Visibility::Committed => write!(f, "committed"),
+        }
+    }
+}
+
+impl FromStr for Visibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s.to_lowercase().as_str() {
+            "private" => Ok(Visibility::Private),
+            "public" => Ok(Visibility::Public),
+            "hashed" => Ok(Visibility::Hashed),
+            "committed" => Ok(Visibility::Committed),
+            _ => Err(format!(
+                "invalid visibility `{}`, expected one of private, public, hashed or committed",
+                s
+            )),
         }
     }
 }
@@ -41,6 +79,12 @@ pub struct VarVisibility {
     pub params: Visibility,
     /// Output of the model or computational graph
     pub output: Visibility,
+    /// Overrides of the visibility of individual inputs, keyed by input index
+    #[serde(default)]
+    pub input_overrides: BTreeMap<usize, Visibility>,
+    /// Overrides of the visibility of individual outputs, keyed by output index
+    #[serde(default)]
+    pub output_overrides: BTreeMap<usize, Visibility>,
 }
 impl std::fmt::Display for VarVisibility {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -48,15 +92,65 @@ impl std::fmt::Display for VarVisibility {
             f,
             "(inputs: {}, params: {}, outputs: {})",
             self.input, self.params, self.output
-        )
+        )?;
+        for (idx, visibility) in &self.input_overrides {
+            write!(f, " (input {}: {})", idx, visibility)?;
+        }
+        for (idx, visibility) in &self.output_overrides {
+            write!(f, " (output {}: {})", idx, visibility)?;
+        }
+        Ok(())
     }
 }
 
 impl VarVisibility {
-    /// Read from cli args whether the model input, model parameters, and model output are Public or Private to the prover.
+    /// Inputs, params and outputs of the given visibility, without overrides for individual inputs and outputs.
+    pub fn new(input: Visibility, params: Visibility, output: Visibility) -> Self {
+        VarVisibility {
+            input,
+            params,
+            output,
+            input_overrides: BTreeMap::new(),
+            output_overrides: BTreeMap::new(),
+        }
+    }
+
+    /// The visibility of the input `idx` of the model.
+    pub fn input_at(&self, idx: usize) -> &Visibility {
+        self.input_overrides.get(&idx).unwrap_or(&self.input)
+    }
+
+    /// The visibility of the output `idx` of the model.
+    pub fn output_at(&self, idx: usize) -> &Visibility {
+        self.output_overrides.get(&idx).unwrap_or(&self.output)
+    }
+
+    /// The visibilities of the `num_inputs` inputs of the model, in order.
+    pub fn inputs(&self, num_inputs: usize) -> Vec<Visibility> {
+        (0..num_inputs).map(|i| self.input_at(i).clone()).collect()
+    }
+
+    /// The visibilities of the `num_outputs` outputs of the model, in order.
+    pub fn outputs(&self, num_outputs: usize) -> Vec<Visibility> {
+        (0..num_outputs)
+            .map(|i| self.output_at(i).clone())
+            .collect()
+    }
+
+    /// Whether any of the `num_inputs` inputs or `num_outputs` outputs is hashed (or committed to) in-circuit.
+    pub fn any_hashed(&self, num_inputs: usize, num_outputs: usize) -> bool {
+        let hashed = |v: &Visibility| v.is_hashed() || v.is_committed();
+        self.inputs(num_inputs).iter().any(hashed) || self.outputs(num_outputs).iter().any(hashed)
+    }
+
+    /// Read from circuit settings whether the model input, model parameters, and model output are Public or Private to the prover.
     /// Place in [VarVisibility] struct.
-    pub fn from_args(args: Cli) -> Result<Self, Box<dyn Error>> {
-        let input_vis = if args.public_inputs {
+    pub fn from_settings(args: &CircuitSettings) -> Result<Self, Box<dyn Error>> {
+        let input_vis = if args.committed_inputs {
+            Visibility::Committed
+        } else if args.hashed_inputs {
+            Visibility::Hashed
+        } else if args.public_inputs {
             Visibility::Public
         } else {
             Visibility::Private
@@ -66,18 +160,40 @@ impl VarVisibility {
         } else {
             Visibility::Private
         };
-        let output_vis = if args.public_outputs {
+        let output_vis = if args.hashed_outputs {
+            Visibility::Hashed
+        } else if args.public_outputs {
             Visibility::Public
         } else {
             Visibility::Private
         };
-        if !output_vis.is_public() & !params_vis.is_public() & !input_vis.is_public() {
+        if args.commit_params && params_vis.is_public() {
+            return Err(Box::new(GraphError::CommitPublicParams));
+        }
+        let public_overrides = args
+            .input_visibility
+            .iter()
+            .chain(args.output_visibility.iter())
+            .any(|(_, v)| !v.is_private());
+        if !output_vis.is_public()
+            & !public_overrides
+            & !output_vis.is_hashed()
+            & !params_vis.is_public()
+            & !input_vis.is_public()
+            & !input_vis.is_hashed()
+            & !input_vis.is_committed()
+            & !args.attested_inputs
+            & args.merkle_depth.is_none()
+            & !args.commit_params
+        {
             return Err(Box::new(GraphError::Visibility));
         }
         Ok(Self {
             input: input_vis,
             params: params_vis,
             output: output_vis,
+            input_overrides: args.input_visibility.iter().cloned().collect(),
+            output_overrides: args.output_visibility.iter().cloned().collect(),
         })
     }
 }
@@ -136,4 +252,41 @@ impl<F: FieldExt + TensorType> ModelVars<F> {
             instances,
         }
     }
+
+    /// The columns of the vars from column `offset` on, see [VarTensor::skip_cols].
+    pub fn skip_cols(&self, offset: usize) -> Self {
+        ModelVars {
+            advices: self.advices.iter().map(|a| a.skip_cols(offset)).collect(),
+            fixed: self.fixed.iter().map(|f| f.skip_cols(offset)).collect(),
+            instances: self.instances.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_visibility_per_tensor() {
+        let settings = CircuitSettings {
+            public_outputs: false,
+            input_visibility: vec![(0, Visibility::Public)],
+            output_visibility: vec![(1, Visibility::Hashed)],
+            ..CircuitSettings::default()
+        };
+        let visibility = VarVisibility::from_settings(&settings).unwrap();
+        assert_eq!(
+            visibility.inputs(2),
+            vec![Visibility::Public, Visibility::Private]
+        );
+        assert_eq!(
+            visibility.outputs(2),
+            vec![Visibility::Private, Visibility::Hashed]
+        );
+        assert!(visibility.any_hashed(2, 2));
+        assert!(!visibility.any_hashed(2, 1));
+        assert_eq!("Committed".parse::<Visibility>(), Ok(Visibility::Committed));
+        assert!("secret".parse::<Visibility>().is_err());
+    }
 }
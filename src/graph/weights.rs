@@ -0,0 +1,384 @@
+use super::{GraphError, ModelSource};
+use itertools::Itertools;
+use prost::Message;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path};
+use tract_onnx::pb::{
+    attribute_proto::AttributeType,
+    tensor_proto::{DataLocation, DataType},
+    ModelProto, TensorProto,
+};
+
+fn invalid(msg: impl Into<String>) -> GraphError {
+    GraphError::InvalidWeights(msg.into())
+}
+
+/// The header entry of a tensor of a safetensors file.
+#[derive(Deserialize)]
+struct SafeTensorInfo {
+    dtype: String,
+    shape: Vec<usize>,
+    data_offsets: (usize, usize),
+}
+
+/// A tensor read from a safetensors file, converted to f32 (as ezkl quantizes all weights from floats).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Weight {
+    /// The shape of the tensor
+    pub shape: Vec<usize>,
+    /// The values of the tensor, in row major order
+    pub values: Vec<f32>,
+}
+
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = ((bits >> 15) as u32) << 31;
+    let exp = ((bits >> 10) & 0x1f) as u32;
+    let frac = (bits & 0x3ff) as u32;
+    let bits = match (exp, frac) {
+        (0, 0) => sign,
+        // subnormals are normalized at the wider exponent
+        (0, _) => {
+            let shift = frac.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | (((frac << shift) & 0x3ff) << 13)
+        }
+        (0x1f, _) => sign | 0x7f80_0000 | (frac << 13),
+        _ => sign | ((exp + 112) << 23) | (frac << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// Parses the tensors of the safetensors file `bytes`, keyed by their name.
+pub fn parse_safetensors(bytes: &[u8]) -> Result<BTreeMap<String, Weight>, GraphError> {
+    let header_len = bytes
+        .get(..8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()) as usize)
+        .ok_or_else(|| invalid("missing safetensors header"))?;
+    let header = bytes
+        .get(8..8usize.saturating_add(header_len))
+        .ok_or_else(|| invalid("truncated safetensors header"))?;
+    let data = &bytes[8 + header_len..];
+    let mut entries: HashMap<String, serde_json::Value> =
+        serde_json::from_slice(header).map_err(|e| invalid(e.to_string()))?;
+    entries.remove("__metadata__");
+
+    let mut weights = BTreeMap::new();
+    for (name, entry) in entries {
+        let info: SafeTensorInfo =
+            serde_json::from_value(entry).map_err(|e| invalid(format!("{}: {}", name, e)))?;
+        let (start, end) = info.data_offsets;
+        let raw = data
+            .get(start..end)
+            .filter(|_| start <= end)
+            .ok_or_else(|| invalid(format!("{}: data out of bounds", name)))?;
+        let values = decode(&info.dtype, raw).map_err(|e| invalid(format!("{}: {}", name, e)))?;
+        if values.len() != info.shape.iter().product::<usize>() {
+            return Err(invalid(format!(
+                "{}: {} values don't fill shape {:?}",
+                name,
+                values.len(),
+                info.shape
+            )));
+        }
+        weights.insert(
+            name,
+            Weight {
+                shape: info.shape,
+                values,
+            },
+        );
+    }
+    Ok(weights)
+}
+
+/// Decodes the little endian values of a safetensors `dtype` into floats.
+fn decode(dtype: &str, raw: &[u8]) -> Result<Vec<f32>, String> {
+    macro_rules! values {
+        ($n:expr, $f:expr) => {{
+            if raw.len() % $n != 0 {
+                return Err(format!("{} bytes don't hold {} values", raw.len(), dtype));
+            }
+            raw.chunks_exact($n)
+                .map(|c| $f(c.try_into().unwrap()))
+                .collect()
+        }};
+    }
+    Ok(match dtype {
+        "F64" => values!(8, |b| f64::from_le_bytes(b) as f32),
+        "F32" => values!(4, f32::from_le_bytes),
+        "F16" => values!(2, |b| f16_to_f32(u16::from_le_bytes(b))),
+        "BF16" => values!(2, |b| f32::from_bits((u16::from_le_bytes(b) as u32) << 16)),
+        "I64" => values!(8, |b| i64::from_le_bytes(b) as f32),
+        "I32" => values!(4, |b| i32::from_le_bytes(b) as f32),
+        "I16" => values!(2, |b| i16::from_le_bytes(b) as f32),
+        "I8" => values!(1, |b: [u8; 1]| b[0] as i8 as f32),
+        "U8" => values!(1, |b: [u8; 1]| b[0] as f32),
+        dtype => return Err(format!("unsupported dtype {}", dtype)),
+    })
+}
+
+/// Reads the values stored outside of the model (in the file named by the `location` of its external data, relative to
+/// `dir`) into the raw data of `tensor`.
+fn inline_external_data(tensor: &mut TensorProto, dir: &Path) -> Result<(), GraphError> {
+    let entry = |key: &str| {
+        tensor
+            .external_data
+            .iter()
+            .find(|e| e.key == key)
+            .map(|e| e.value.clone())
+    };
+    let location = entry("location")
+        .ok_or_else(|| invalid(format!("{}: external data without location", tensor.name)))?;
+    let number = |key: &str| -> Result<Option<u64>, GraphError> {
+        entry(key)
+            .map(|v| {
+                v.parse()
+                    .map_err(|_| invalid(format!("{}: invalid {} {}", tensor.name, key, v)))
+            })
+            .transpose()
+    };
+    let (offset, length) = (number("offset")?, number("length")?);
+
+    // the location must stay inside the directory of the model, such that a model can't read any other file of the host
+    if !Path::new(&location)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(invalid(format!(
+            "{}: external data location {} is outside of the model directory",
+            tensor.name, location
+        )));
+    }
+    let path = dir.join(&location);
+    let mut file = File::open(&path).map_err(|e| invalid(format!("{:?}: {}", path, e)))?;
+    let size = file
+        .metadata()
+        .map_err(|e| invalid(format!("{:?}: {}", path, e)))?
+        .len();
+    let offset = offset.unwrap_or(0);
+    // the length is checked against the file before allocating for it
+    let length = match length {
+        Some(length) if offset.checked_add(length).map_or(true, |end| end > size) => {
+            return Err(invalid(format!(
+                "{}: {} bytes at offset {} exceed the {} bytes of {:?}",
+                tensor.name, length, offset, size, path
+            )))
+        }
+        Some(length) => length,
+        None => size.saturating_sub(offset),
+    };
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| invalid(format!("{:?}: {}", path, e)))?;
+    let mut raw = vec![0; length as usize];
+    file.read_exact(&mut raw)
+        .map_err(|e| invalid(format!("{:?}: {}", path, e)))?;
+
+    tensor.raw_data = raw;
+    tensor.external_data.clear();
+    tensor.data_location = DataLocation::Default as i32;
+    Ok(())
+}
+
+/// Replaces the values of the float `tensor` with those of `weight`, which must be of the same shape.
+fn rebind(tensor: &mut TensorProto, weight: &Weight) -> Result<(), GraphError> {
+    let dims = weight.shape.iter().map(|d| *d as i64).collect::<Vec<_>>();
+    if tensor.dims != dims {
+        return Err(invalid(format!(
+            "{} has shape {:?} in the model but {:?} in the weights",
+            tensor.name, tensor.dims, weight.shape
+        )));
+    }
+    if tensor.data_type != DataType::Float as i32 {
+        return Err(invalid(format!(
+            "{} isn't a float tensor and can't be rebound",
+            tensor.name
+        )));
+    }
+    *tensor = TensorProto {
+        name: tensor.name.clone(),
+        dims,
+        data_type: DataType::Float as i32,
+        float_data: weight.values.clone(),
+        ..Default::default()
+    };
+    Ok(())
+}
+
+/// The contents of the .onnx model of `source`, with the weights it stores in external data files read into the model, and the
+/// initializers (and `Constant` nodes) named in the safetensors file at `weights` (if any) bound to the values of that file.
+/// Returns `None` when the model is loaded as is, such that models without external data aren't re-encoded.
+pub fn resolve(
+    source: &ModelSource,
+    weights: Option<&Path>,
+) -> Result<Option<Vec<u8>>, GraphError> {
+    let bytes = source.read().map_err(|_| GraphError::ModelLoad)?;
+    let mut model = ModelProto::decode(bytes.as_ref()).map_err(|_| GraphError::ModelLoad)?;
+    let graph = match model.graph.as_mut() {
+        Some(graph) => graph,
+        None => return Ok(None),
+    };
+
+    let external = graph
+        .initializer
+        .iter()
+        .any(|t| t.data_location == DataLocation::External as i32);
+    if external {
+        let dir = match source {
+            ModelSource::Path(path) => path.parent().unwrap_or_else(|| Path::new(".")),
+            ModelSource::Bytes(_) => {
+                return Err(invalid(
+                    "models with external data must be loaded from a path",
+                ))
+            }
+        };
+        for tensor in graph
+            .initializer
+            .iter_mut()
+            .filter(|t| t.data_location == DataLocation::External as i32)
+        {
+            inline_external_data(tensor, dir)?;
+        }
+    }
+
+    let path = match weights {
+        Some(path) => path,
+        None if external => return Ok(Some(model.encode_to_vec())),
+        None => return Ok(None),
+    };
+    let data = std::fs::read(path).map_err(|e| invalid(format!("{:?}: {}", path, e)))?;
+    let mut weights = parse_safetensors(&data)?;
+    for tensor in graph.initializer.iter_mut() {
+        if let Some(weight) = weights.remove(&tensor.name) {
+            rebind(tensor, &weight)?;
+        }
+    }
+    for node in graph.node.iter_mut().filter(|n| n.op_type == "Constant") {
+        let weight = match node.output.first().and_then(|o| weights.remove(o)) {
+            Some(weight) => weight,
+            None => continue,
+        };
+        let value = node
+            .attribute
+            .iter_mut()
+            .find(|a| a.name == "value" && a.r#type == AttributeType::Tensor as i32)
+            .and_then(|a| a.t.as_mut())
+            .ok_or_else(|| invalid(format!("{} isn't a tensor constant", node.output[0])))?;
+        value.name = node.output[0].clone();
+        rebind(value, &weight)?;
+    }
+    if !weights.is_empty() {
+        return Err(invalid(format!(
+            "no initializer named {}",
+            weights.keys().join(", ")
+        )));
+    }
+    Ok(Some(model.encode_to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tract_onnx::pb::StringStringEntryProto;
+
+    fn safetensors(header: &str, data: &[u8]) -> Vec<u8> {
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend(header.as_bytes());
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn parses_safetensors() {
+        let mut data = vec![];
+        for v in [1.0f32, -2.0, 0.5, 4.0] {
+            data.extend(v.to_le_bytes());
+        }
+        // 1.0 and -2.0 as half precision floats
+        data.extend([0x00, 0x3c, 0x00, 0xc0]);
+        let bytes = safetensors(
+            r#"{"__metadata__":{"format":"pt"},"w":{"dtype":"F32","shape":[2,2],"data_offsets":[0,16]},"b":{"dtype":"F16","shape":[2],"data_offsets":[16,20]}}"#,
+            &data,
+        );
+        let weights = parse_safetensors(&bytes).unwrap();
+        assert_eq!(
+            weights["w"],
+            Weight {
+                shape: vec![2, 2],
+                values: vec![1.0, -2.0, 0.5, 4.0]
+            }
+        );
+        assert_eq!(weights["b"].values, vec![1.0, -2.0]);
+
+        let bad = safetensors(
+            r#"{"w":{"dtype":"F32","shape":[3],"data_offsets":[0,8]}}"#,
+            &data[..8],
+        );
+        assert!(parse_safetensors(&bad).is_err());
+        assert!(parse_safetensors(b"short").is_err());
+    }
+
+    #[test]
+    fn rebinds_float_tensors_of_the_same_shape() {
+        let mut tensor = TensorProto {
+            name: "w".to_string(),
+            dims: vec![2],
+            data_type: DataType::Float as i32,
+            raw_data: vec![0; 8],
+            ..Default::default()
+        };
+        let weight = Weight {
+            shape: vec![2],
+            values: vec![3.0, 4.0],
+        };
+        rebind(&mut tensor, &weight).unwrap();
+        assert_eq!(tensor.float_data, vec![3.0, 4.0]);
+        assert!(tensor.raw_data.is_empty());
+        assert_eq!(tensor.name, "w");
+
+        let wrong_shape = Weight {
+            shape: vec![1, 2],
+            values: vec![3.0, 4.0],
+        };
+        assert!(rebind(&mut tensor, &wrong_shape).is_err());
+    }
+
+    #[test]
+    fn external_data_stays_in_the_model_directory() {
+        let dir = std::env::temp_dir().join(format!("ezkl-external-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("weights.bin"), [1u8, 2, 3, 4]).unwrap();
+        let tensor = |location: &str, offset: &str, length: &str| TensorProto {
+            name: "w".to_string(),
+            external_data: [
+                ("location", location),
+                ("offset", offset),
+                ("length", length),
+            ]
+            .iter()
+            .map(|(key, value)| StringStringEntryProto {
+                key: key.to_string(),
+                value: value.to_string(),
+            })
+            .collect(),
+            data_location: DataLocation::External as i32,
+            ..Default::default()
+        };
+
+        let mut inside = tensor("weights.bin", "1", "2");
+        inline_external_data(&mut inside, &dir).unwrap();
+        assert_eq!(inside.raw_data, vec![2, 3]);
+
+        for (location, offset, length) in [
+            ("../weights.bin", "0", "4"),
+            ("/etc/passwd", "0", "4"),
+            ("weights.bin", "2", "4"),
+            ("weights.bin", "0", "18446744073709551615"),
+        ] {
+            let mut outside = tensor(location, offset, length);
+            assert!(inline_external_data(&mut outside, &dir).is_err());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -35,19 +35,44 @@
 //! A library for turning computational graphs, such as neural networks, into ZK-circuits.
 //!
 
+/// Proving and verifying models from library code, returning artifacts rather than saving them to files.
+pub mod api;
 /// Methods for configuring tensor operations and assigning values to them in a Halo2 circuit.
 pub mod circuit;
 /// CLI commands.
 pub mod commands;
+/// The error type of the public api, wrapping the errors of each module.
+pub mod error;
 /// Command execution
 pub mod execute;
+/// A C ABI over buffers, for embedding ezkl in other languages (see `include/ezkl.h`).
+#[cfg(feature = "ffi")]
+pub mod ffi;
 /// Utilities for converting from Halo2 Field types to integers (and vice-versa).
 pub mod fieldutils;
 /// Methods for loading onnx format models and automatically laying them out in
 /// a Halo2 circuit.
 #[cfg(feature = "onnx")]
 pub mod graph;
+/// Tracking of the peak memory of the phases of commands.
+pub mod memory;
 /// Tools for proofs and verification used by cli
 pub mod pfsys;
+/// The types and functions needed to prove and verify models from library code.
+pub mod prelude;
+/// Progress bars for long running commands.
+pub mod progress;
+/// Python bindings (the `pyezkl` module), built with maturin.
+#[cfg(feature = "python-bindings")]
+pub mod python;
+/// A prover daemon holding the params and keys of a model in memory, serving proofs over HTTP.
+#[cfg(not(target_arch = "wasm32"))]
+pub mod serve;
+/// Async, cancellable proofs on a tokio runtime, for servers proving on behalf of clients.
+#[cfg(all(feature = "async", not(target_arch = "wasm32")))]
+pub mod tasks;
 /// An implementation of multi-dimensional tensors.
 pub mod tensor;
+/// Javascript bindings for verifying (and proving small models) in the browser, built with wasm-pack.
+#[cfg(feature = "wasm")]
+pub mod wasm;
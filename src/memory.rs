@@ -0,0 +1,125 @@
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::cmp::max;
+use std::sync::{Mutex, MutexGuard};
+
+/// The peak memory of a phase of a command (e.g keygen), as reported with `--json`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct PhaseMemory {
+    /// The name of the phase
+    pub phase: String,
+    /// The peak resident set size of the process over the phase, in bytes
+    pub peak_rss_bytes: u64,
+}
+
+/// A phase being tracked: the peak of its parent up to its start, and the peak of the phases nested within it.
+struct Frame {
+    before: u64,
+    nested: u64,
+}
+
+/// The phases being tracked, innermost last.
+static FRAMES: Mutex<Vec<Frame>> = Mutex::new(Vec::new());
+/// The phases tracked since the last [take_phases], in the order they completed.
+static PHASES: Mutex<Vec<PhaseMemory>> = Mutex::new(Vec::new());
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Reads a field of `/proc/self/status`, in bytes.
+fn status_bytes(field: &str) -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| {
+            let kb = line.strip_prefix(field)?.strip_prefix(':')?;
+            kb.trim().strip_suffix("kB")?.trim().parse::<u64>().ok()
+        })
+        .map(|kb| kb * 1024)
+}
+
+/// The peak resident set size of the process since the start of the innermost tracked phase, in bytes. Memory is only
+/// tracked on Linux, elsewhere this is `None`.
+pub fn peak_rss() -> Option<u64> {
+    status_bytes("VmHWM")
+}
+
+/// The current resident set size of the process, in bytes, on Linux.
+pub fn current_rss() -> Option<u64> {
+    status_bytes("VmRSS")
+}
+
+/// Formats a number of bytes in MiB (or GiB, past 1GiB).
+pub fn human(bytes: u64) -> String {
+    match bytes >= 1 << 30 {
+        true => format!("{:.2}GiB", bytes as f64 / (1u64 << 30) as f64),
+        false => format!("{:.1}MiB", bytes as f64 / (1u64 << 20) as f64),
+    }
+}
+
+/// Runs `f` as the phase named `phase`, returning its result along with the peak resident set size of the process while it
+/// ran (if memory is tracked on this platform), which is also recorded for [take_phases]. The kernel's peak is reset as the
+/// phase starts, and the peak of each phase accounts for the phases nested within it.
+pub fn track<T>(phase: &str, f: impl FnOnce() -> T) -> (T, Option<u64>) {
+    let before = match peak_rss() {
+        Some(before) => before,
+        None => return (f(), None),
+    };
+    lock(&FRAMES).push(Frame { before, nested: 0 });
+    // resetting the peak needs Linux 4.0, on earlier kernels peaks are those of the process so far
+    let _ = std::fs::write("/proc/self/clear_refs", "5");
+
+    let res = f();
+
+    let mut frames = lock(&FRAMES);
+    let frame = frames.pop().unwrap_or(Frame { before, nested: 0 });
+    let peak = max(peak_rss().unwrap_or(0), frame.nested);
+    if let Some(parent) = frames.last_mut() {
+        parent.nested = max(parent.nested, max(frame.before, peak));
+    }
+    drop(frames);
+    lock(&PHASES).push(PhaseMemory {
+        phase: phase.to_string(),
+        peak_rss_bytes: peak,
+    });
+    (res, Some(peak))
+}
+
+/// Takes the phases tracked so far, in the order they completed.
+pub fn take_phases() -> Vec<PhaseMemory> {
+    std::mem::take(&mut *lock(&PHASES))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_phases_peak_within_their_parents() {
+        let ((_, inner), outer) = track("outer", || {
+            track("inner", || {
+                // touch some memory, such that the phases have a peak to report on Linux
+                let buffer = vec![1u8; 16 << 20];
+                buffer.iter().map(|b| *b as u64).sum::<u64>()
+            })
+        });
+        let phases = take_phases();
+        match (inner, outer) {
+            (Some(inner), Some(outer)) => {
+                assert!(inner >= 16 << 20);
+                assert!(outer >= inner);
+                // other tests may track phases of their own concurrently
+                let names = phases
+                    .iter()
+                    .map(|p| p.phase.as_str())
+                    .filter(|p| ["inner", "outer"].contains(p))
+                    .collect::<Vec<_>>();
+                assert_eq!(names, vec!["inner", "outer"]);
+            }
+            // memory isn't tracked on this platform
+            _ => assert!(phases.iter().all(|p| p.phase != "inner")),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use super::PfsysError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::io::{self, Read, Write};
+
+/// Magic bytes prefixing the artifacts written by ezkl (keys, compiled models, and proofs and settings saved as files).
+pub const MAGIC: &[u8; 4] = b"EZKL";
+/// The version of ezkl, recorded in the header of the artifacts it writes.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The header prefixing a serialized artifact: the ezkl magic string, the version of ezkl which produced the artifact, and a hash
+/// of the circuit settings it was produced for (which for artifacts of a circuit, e.g proofs, also covers the model, see
+/// [super::circuit_fingerprint]). Artifacts produced by another version of ezkl are accepted as long as they decode and were
+/// produced for the same settings, and are otherwise reported as incompatible rather than failing to verify.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ArtifactHeader {
+    /// The magic string, `EZKL`.
+    pub magic: String,
+    /// The (semver) version of ezkl which produced the artifact.
+    pub version: String,
+    /// The hex encoded hash of the settings the artifact was produced for.
+    pub settings: String,
+}
+
+/// An artifact serialized as json, with its header as the leading `header` field.
+#[derive(Serialize)]
+struct Headed<'a, T> {
+    header: &'a ArtifactHeader,
+    #[serde(flatten)]
+    artifact: &'a T,
+}
+
+impl ArtifactHeader {
+    /// The header of artifacts produced by this version of ezkl for settings with the hex encoded hash `settings`.
+    pub fn new(settings: String) -> Self {
+        ArtifactHeader {
+            magic: String::from_utf8_lossy(MAGIC).to_string(),
+            version: VERSION.to_string(),
+            settings,
+        }
+    }
+
+    /// Whether the artifact was produced by this version of ezkl.
+    pub fn is_current(&self) -> bool {
+        self.version == VERSION
+    }
+
+    /// The error reporting the `artifact` (e.g "proof") with this header as produced by an incompatible version of ezkl.
+    pub fn incompatible(&self, artifact: &str) -> PfsysError {
+        PfsysError::IncompatibleArtifact(artifact.to_string(), self.version.clone())
+    }
+
+    /// Checks the `artifact` with this header is not from another version of ezkl with settings other than those hashing to
+    /// `expected`. Artifacts produced by this version for other settings pass, such that they are reported in the terms of the
+    /// artifact (e.g stale keys, or mismatched proof hashes).
+    pub fn check(&self, artifact: &str, expected: &str) -> Result<(), PfsysError> {
+        match self.settings != expected && !self.is_current() {
+            true => Err(self.incompatible(artifact)),
+            false => Ok(()),
+        }
+    }
+
+    /// Writes the header in binary: the magic bytes, then the version and hash, each prefixed by its length.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_str(writer, &self.version)?;
+        write_str(writer, &self.settings)
+    }
+
+    /// Reads a header written by [ArtifactHeader::write] from the start of `bytes`, returning it alongside the bytes which follow
+    /// it. Returns `None` for bytes which don't start with the magic bytes, e.g written by a version of ezkl predating headers.
+    pub fn read<'a>(
+        bytes: &'a [u8],
+        artifact: &str,
+    ) -> Result<Option<(Self, &'a [u8])>, PfsysError> {
+        let mut reader = match bytes.strip_prefix(MAGIC) {
+            Some(rest) => rest,
+            None => return Ok(None),
+        };
+        let invalid = |_| PfsysError::InvalidHeader(artifact.to_string());
+        let version = read_str(&mut reader).map_err(invalid)?;
+        let settings = read_str(&mut reader).map_err(invalid)?;
+        let header = ArtifactHeader {
+            magic: String::from_utf8_lossy(MAGIC).to_string(),
+            version,
+            settings,
+        };
+        Ok(Some((header, reader)))
+    }
+}
+
+/// Writes `s` prefixed by its length, as a single byte.
+pub(crate) fn write_str<W: Write>(writer: &mut W, s: &str) -> io::Result<()> {
+    let len = u8::try_from(s.len()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    writer.write_all(&[len])?;
+    writer.write_all(s.as_bytes())
+}
+
+/// Reads a string written by [write_str].
+pub(crate) fn read_str<R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut len = [0u8; 1];
+    reader.read_exact(&mut len)?;
+    let mut bytes = vec![0u8; len[0] as usize];
+    reader.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Decodes an artifact with `f`, reporting decoding failures of artifacts produced by other versions of ezkl as such.
+fn decode<T>(
+    header: Option<ArtifactHeader>,
+    artifact: &str,
+    f: impl FnOnce() -> Result<T, Box<dyn Error>>,
+) -> Result<(Option<ArtifactHeader>, T), Box<dyn Error>> {
+    match (f(), header) {
+        (Ok(decoded), header) => Ok((header, decoded)),
+        (Err(_), Some(header)) if !header.is_current() => {
+            Err(Box::new(header.incompatible(artifact)))
+        }
+        (Err(e), _) => Err(e),
+    }
+}
+
+/// Encodes `artifact` with bincode, prefixed by the binary `header`.
+pub fn to_bincode<T: Serialize>(
+    artifact: &T,
+    header: &ArtifactHeader,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut bytes = vec![];
+    header.write(&mut bytes)?;
+    bincode::serialize_into(&mut bytes, artifact)?;
+    Ok(bytes)
+}
+
+/// Decodes the `artifact` (e.g "compiled model") encoded by [to_bincode] in `bytes`, alongside its header. Artifacts without a
+/// header are decoded as they are.
+pub fn from_bincode<T: DeserializeOwned>(
+    bytes: &[u8],
+    artifact: &str,
+) -> Result<(Option<ArtifactHeader>, T), Box<dyn Error>> {
+    let (header, body) = match ArtifactHeader::read(bytes, artifact)? {
+        Some((header, body)) => (Some(header), body),
+        None => (None, bytes),
+    };
+    decode(header, artifact, || {
+        bincode::deserialize(body).map_err(Box::<dyn Error>::from)
+    })
+}
+
+/// Encodes `artifact` (which must serialize as a json object) as json, with `header` as its leading `header` field.
+pub fn to_json<T: Serialize>(
+    artifact: &T,
+    header: &ArtifactHeader,
+    pretty: bool,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let headed = Headed { header, artifact };
+    Ok(match pretty {
+        true => serde_json::to_vec_pretty(&headed)?,
+        false => serde_json::to_vec(&headed)?,
+    })
+}
+
+/// Decodes the `artifact` (e.g "proof") encoded by [to_json] in `bytes`, alongside its header. Artifacts without a `header`
+/// field are decoded as they are.
+pub fn from_json<T: DeserializeOwned>(
+    bytes: &[u8],
+    artifact: &str,
+) -> Result<(Option<ArtifactHeader>, T), Box<dyn Error>> {
+    let mut value: serde_json::Value = serde_json::from_slice(bytes)?;
+    let header = match value.as_object_mut().and_then(|o| o.remove("header")) {
+        Some(header) => {
+            let header: ArtifactHeader = serde_json::from_value(header)
+                .map_err(|_| PfsysError::InvalidHeader(artifact.to_string()))?;
+            if header.magic.as_bytes() != MAGIC {
+                return Err(Box::new(PfsysError::InvalidHeader(artifact.to_string())));
+            }
+            Some(header)
+        }
+        None => None,
+    };
+    decode(header, artifact, || {
+        serde_json::from_value(value).map_err(Box::<dyn Error>::from)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Artifact {
+        values: Vec<i32>,
+    }
+
+    #[test]
+    fn artifacts_are_prefixed_by_their_header() {
+        let artifact = Artifact {
+            values: vec![1, -2, 3],
+        };
+        let header = ArtifactHeader::new("00ff".to_string());
+
+        let bin = to_bincode(&artifact, &header).unwrap();
+        assert!(bin.starts_with(MAGIC));
+        let (found, decoded) = from_bincode::<Artifact>(&bin, "artifact").unwrap();
+        assert_eq!((found, decoded), (Some(header.clone()), artifact));
+
+        let json = to_json(&Artifact { values: vec![4] }, &header, false).unwrap();
+        assert!(String::from_utf8_lossy(&json).starts_with("{\"header\":{\"magic\":\"EZKL\""));
+        let (found, decoded) = from_json::<Artifact>(&json, "artifact").unwrap();
+        assert_eq!(found, Some(header.clone()));
+        assert_eq!(decoded.values, vec![4]);
+
+        // artifacts predating headers are still readable
+        let (found, _) = from_json::<Artifact>(b"{\"values\":[1]}", "artifact").unwrap();
+        assert_eq!(found, None);
+        let legacy = bincode::serialize(&Artifact { values: vec![1] }).unwrap();
+        assert!(from_bincode::<Artifact>(&legacy, "artifact").is_ok());
+    }
+
+    #[test]
+    fn artifacts_of_other_versions_are_reported() {
+        let header = ArtifactHeader {
+            version: "0.0.1".to_string(),
+            ..ArtifactHeader::new("00ff".to_string())
+        };
+        // artifacts of other versions produced for the same settings are accepted
+        assert!(header.check("proof", "00ff").is_ok());
+        match header.check("proof", "0100") {
+            Err(PfsysError::IncompatibleArtifact(artifact, version)) => {
+                assert_eq!((artifact.as_str(), version.as_str()), ("proof", "0.0.1"))
+            }
+            _ => panic!("expected the proof to be reported as incompatible"),
+        }
+        assert!(ArtifactHeader::new("00ff".to_string())
+            .check("proof", "0100")
+            .is_ok());
+
+        // artifacts of other versions which don't decode are reported as incompatible too
+        let json =
+            serde_json::to_vec(&serde_json::json!({"header": header, "values": "x"})).unwrap();
+        let err = from_json::<Artifact>(&json, "artifact").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PfsysError>(),
+            Some(PfsysError::IncompatibleArtifact(..))
+        ));
+    }
+}
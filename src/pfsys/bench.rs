@@ -0,0 +1,306 @@
+use halo2_proofs::arithmetic::FieldExt;
+use halo2_proofs::circuit::Value;
+use halo2_proofs::plonk::{
+    Advice, Any, Assigned, Assignment, Challenge, Circuit, Column, ConstraintSystem, Error, Fixed,
+    FloorPlanner, Instance, Selector,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::cmp::{max, min};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// The time taken and memory used by a single phase of proof generation.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PhaseReport {
+    /// The name of the phase.
+    pub phase: String,
+    /// Wall clock time taken by the phase, in seconds.
+    pub seconds: f64,
+    /// Peak resident memory of the process during the phase, in bytes. Only measured on Linux.
+    pub peak_memory_bytes: Option<u64>,
+}
+
+/// Statistics of the circuit of a model.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CircuitStats {
+    /// The log2 number of rows of the circuit.
+    pub logrows: u32,
+    /// The number of rows assigned to, including those of lookup tables.
+    pub rows_used: usize,
+    /// The number of advice columns.
+    pub advice_columns: usize,
+    /// The number of fixed columns.
+    pub fixed_columns: usize,
+    /// The number of instance columns.
+    pub instance_columns: usize,
+    /// The number of selectors.
+    pub selectors: usize,
+    /// The number of lookup arguments.
+    pub lookups: usize,
+    /// The rows used by the regions of each execution bucket (`None` for regions belonging to no bucket, e.g hashing).
+    pub bucket_rows: Vec<BucketRows>,
+    /// The number of rows of each lookup table column.
+    pub lookup_table_rows: Vec<usize>,
+    /// The smallest log2 number of rows the circuit fits in, accounting for the rows reserved by halo2 (e.g for blinding).
+    pub min_logrows: u32,
+}
+
+/// The rows used by the regions of an execution bucket.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BucketRows {
+    /// The execution bucket, or `None` for regions belonging to no bucket.
+    pub bucket: Option<usize>,
+    /// The number of rows spanned by the bucket's regions, summed.
+    pub rows: usize,
+}
+
+/// A benchmark of proof generation for a model, as output by `ezkl bench`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BenchReport {
+    /// Timings of each phase, in the order they were run.
+    pub phases: Vec<PhaseReport>,
+    /// Statistics of the model's circuit.
+    pub circuit: CircuitStats,
+}
+
+impl BenchReport {
+    /// Runs `f` as the phase named `phase`, recording its time and peak memory.
+    pub fn measure<T>(&mut self, phase: &str, f: impl FnOnce() -> T) -> T {
+        reset_peak_memory();
+        let now = Instant::now();
+        let res = f();
+        let report = PhaseReport {
+            phase: phase.to_string(),
+            seconds: now.elapsed().as_secs_f64(),
+            peak_memory_bytes: peak_memory(),
+        };
+        info!("{} took {:.3}s", phase, report.seconds);
+        self.phases.push(report);
+        res
+    }
+}
+
+/// Resets the peak resident memory of the process (Linux only), such that the peak of the following phase can be read.
+fn reset_peak_memory() {
+    let _ = std::fs::write("/proc/self/clear_refs", "5");
+}
+
+/// Reads the peak resident memory of the process in bytes (Linux only).
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kb = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmHWM:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// An [Assignment] which only records the highest row assigned to, and the rows spanned by each region, used to measure
+/// the rows used by a circuit.
+#[derive(Debug, Default)]
+struct RowCounter {
+    rows: usize,
+    /// The region being assigned.
+    region: Option<RegionRows>,
+    /// The name and number of rows spanned of each region assigned, excluding lookup tables.
+    regions: Vec<(String, usize)>,
+    /// The rows of each lookup table column, by index.
+    table_rows: BTreeMap<usize, usize>,
+}
+
+/// The rows assigned to by a region.
+#[derive(Debug)]
+struct RegionRows {
+    name: String,
+    first: usize,
+    last: usize,
+    /// Whether the region assigns advice cells or enables selectors, which lookup tables don't.
+    constrained: bool,
+    /// The rows assigned to each fixed column, by index.
+    fixed_rows: BTreeMap<usize, usize>,
+}
+
+impl RowCounter {
+    fn touch(&mut self, row: usize) {
+        self.rows = max(self.rows, row + 1);
+        if let Some(region) = self.region.as_mut() {
+            region.first = min(region.first, row);
+            region.last = max(region.last, row);
+        }
+    }
+
+    fn constrain(&mut self, row: usize) {
+        if let Some(region) = self.region.as_mut() {
+            region.constrained = true;
+        }
+        self.touch(row);
+    }
+
+    /// Sums the rows spanned by regions of each execution bucket, as named by [crate::graph::diagnostics::NodeLayouter].
+    fn bucket_rows(&self) -> Vec<BucketRows> {
+        let mut buckets = BTreeMap::<Option<usize>, usize>::new();
+        for (name, rows) in self.regions.iter() {
+            let bucket = name.find("bucket ").and_then(|i| {
+                let digits: String = name[i + "bucket ".len()..]
+                    .chars()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect();
+                digits.parse().ok()
+            });
+            *buckets.entry(bucket).or_default() += rows;
+        }
+        buckets
+            .into_iter()
+            .map(|(bucket, rows)| BucketRows { bucket, rows })
+            .collect()
+    }
+}
+
+impl<F: FieldExt> Assignment<F> for RowCounter {
+    fn enter_region<NR, N>(&mut self, name: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+        self.region = Some(RegionRows {
+            name: name().into(),
+            first: usize::MAX,
+            last: 0,
+            constrained: false,
+            fixed_rows: BTreeMap::new(),
+        });
+    }
+
+    fn exit_region(&mut self) {
+        if let Some(region) = self.region.take() {
+            if region.constrained || region.fixed_rows.is_empty() {
+                // regions which assign nothing span no rows
+                let rows = (region.last + 1).saturating_sub(region.first);
+                self.regions.push((region.name, rows));
+            } else {
+                // lookup tables are laid out in regions of their own, which only assign fixed cells
+                self.table_rows.extend(region.fixed_rows);
+            }
+        }
+    }
+
+    fn enable_selector<A, AR>(&mut self, _: A, _: &Selector, row: usize) -> Result<(), Error>
+    where
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.constrain(row);
+        Ok(())
+    }
+
+    fn query_instance(&self, _: Column<Instance>, _: usize) -> Result<Value<F>, Error> {
+        Ok(Value::unknown())
+    }
+
+    fn assign_advice<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        _: Column<Advice>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        self.constrain(row);
+        Ok(())
+    }
+
+    fn assign_fixed<V, VR, A, AR>(
+        &mut self,
+        _: A,
+        column: Column<Fixed>,
+        row: usize,
+        _: V,
+    ) -> Result<(), Error>
+    where
+        V: FnOnce() -> Value<VR>,
+        VR: Into<Assigned<F>>,
+        A: FnOnce() -> AR,
+        AR: Into<String>,
+    {
+        // fixed cells assigned outside of any region can only belong to lookup tables
+        let fixed_rows = match self.region.as_mut() {
+            Some(region) => &mut region.fixed_rows,
+            None => &mut self.table_rows,
+        };
+        let rows = fixed_rows.entry(column.index()).or_default();
+        *rows = max(*rows, row + 1);
+        self.touch(row);
+        Ok(())
+    }
+
+    fn copy(
+        &mut self,
+        _: Column<Any>,
+        left: usize,
+        _: Column<Any>,
+        right: usize,
+    ) -> Result<(), Error> {
+        self.touch(max(left, right));
+        Ok(())
+    }
+
+    fn fill_from_row(
+        &mut self,
+        _: Column<Fixed>,
+        row: usize,
+        _: Value<Assigned<F>>,
+    ) -> Result<(), Error> {
+        self.touch(row);
+        Ok(())
+    }
+
+    fn get_challenge(&self, _: Challenge) -> Value<F> {
+        Value::unknown()
+    }
+
+    fn push_namespace<NR, N>(&mut self, _: N)
+    where
+        NR: Into<String>,
+        N: FnOnce() -> NR,
+    {
+    }
+
+    fn pop_namespace(&mut self, _: Option<String>) {}
+}
+
+/// Computes [CircuitStats] for `circuit` with `2^logrows` rows, laying it out (as is done during keygen) with an assignment
+/// that discards all values.
+pub fn circuit_stats<F: FieldExt, C: Circuit<F>>(
+    circuit: &C,
+    logrows: u32,
+) -> Result<CircuitStats, Error> {
+    let mut cs = ConstraintSystem::<F>::default();
+    let config = C::configure(&mut cs);
+    let mut counter = RowCounter::default();
+    C::FloorPlanner::synthesize(&mut counter, circuit, config, vec![])?;
+    let min_logrows = (counter.rows + cs.minimum_rows())
+        .next_power_of_two()
+        .trailing_zeros();
+    Ok(CircuitStats {
+        logrows,
+        rows_used: counter.rows,
+        advice_columns: cs.num_advice_columns(),
+        fixed_columns: cs.num_fixed_columns(),
+        instance_columns: cs.num_instance_columns(),
+        selectors: cs.num_selectors(),
+        lookups: cs.lookups().len(),
+        bucket_rows: counter.bucket_rows(),
+        lookup_table_rows: counter.table_rows.values().cloned().collect(),
+        min_logrows,
+    })
+}
@@ -1,7 +1,9 @@
-use crate::commands::Cli;
+use crate::commands::MultiOpenScheme;
 use crate::fieldutils::i32_to_felt;
+use crate::graph::RunArgs;
+use crate::pfsys::evm::split::VerifierCode;
 use crate::pfsys::prepare_circuit_and_public_input;
-use crate::pfsys::ModelInput;
+use crate::pfsys::{hex_bytes, ModelInput, PoseidonTranscript, Proof};
 use ethereum_types::Address;
 use foundry_evm::executor::{fork::MultiFork, Backend, ExecutorBuilder};
 use halo2_proofs::plonk::VerifyingKey;
@@ -26,17 +28,19 @@ use halo2_proofs::{
 use halo2_wrong_ecc::{
     integer::rns::Rns,
     maingate::{
-        MainGate, MainGateConfig, MainGateInstructions, RangeChip, RangeConfig, RangeInstructions,
-        RegionCtx,
+        AssignedValue, MainGate, MainGateConfig, MainGateInstructions, RangeChip, RangeConfig,
+        RangeInstructions, RegionCtx,
     },
     EccConfig,
 };
 use halo2curves::bn256::{Bn256, Fq, Fr, G1Affine};
+use halo2curves::group::ff::PrimeField;
 use itertools::Itertools;
 use log::trace;
 use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
 use snark_verifier::{
-    loader::evm::{self, encode_calldata, EvmLoader},
+    loader::evm::{encode_calldata, EvmLoader},
     system::halo2::transcript::evm::EvmTranscript,
 };
 use snark_verifier::{
@@ -44,17 +48,20 @@ use snark_verifier::{
     system::halo2::{compile, Config},
 };
 use snark_verifier::{
-    loader::{self},
+    loader::{self, ScalarLoader},
     pcs::{
         kzg::{Gwc19, KzgAccumulator, KzgAs, KzgSuccinctVerifyingKey, LimbsEncoding},
         AccumulationScheme, AccumulationSchemeProver,
     },
-    system,
     util::arithmetic::{fe_to_limbs, FieldExt},
     verifier::{self, plonk::PlonkProtocol, SnarkVerifier},
 };
+use std::collections::HashMap;
 use std::error::Error;
-use std::io::Cursor;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{Cursor, Read, Write};
+use std::path::PathBuf;
 use std::{iter, rc::Rc};
 use thiserror::Error;
 
@@ -65,19 +72,11 @@ type As = KzgAs<Bn256, Gwc19>;
 type PlonkSuccinctVerifier = verifier::plonk::PlonkSuccinctVerifier<As, LimbsEncoding<LIMBS, BITS>>;
 type PlonkVerifier = verifier::plonk::PlonkVerifier<As, LimbsEncoding<LIMBS, BITS>>;
 
-const T: usize = 5;
-const RATE: usize = 4;
-const R_F: usize = 8;
-const R_P: usize = 60;
-
 type Svk = KzgSuccinctVerifyingKey<G1Affine>;
 type BaseFieldEccChip =
     snark_verifier::loader::halo2::halo2_wrong_ecc::BaseFieldEccChip<G1Affine, LIMBS, BITS>;
 /// The loader type used in the transcript definition
 type Halo2Loader<'a> = loader::halo2::Halo2Loader<'a, G1Affine, BaseFieldEccChip>;
-/// Application snark transcript
-pub type PoseidonTranscript<L, S> =
-    system::halo2::transcript::halo2::PoseidonTranscript<G1Affine, L, S, T, RATE, R_F, R_P>;
 
 #[derive(Error, Debug)]
 /// Errors related to proof aggregation
@@ -97,6 +96,35 @@ pub enum AggregationError {
     /// proof creation errors
     #[error("Failed to create proof")]
     ProofCreate,
+    /// The requested multi-open scheme cannot be aggregated
+    #[error("aggregation does not support the {0} multi-open scheme")]
+    UnsupportedMultiOpen(MultiOpenScheme),
+    /// A model, proof, and verification key are required for each application snark
+    #[error("expected a model, proof, and verification key per proof, got {0} models, {1} proofs, and {2} verification keys")]
+    SnarkArtifacts(usize, usize, usize),
+    /// Linked instances of two application snarks differ
+    #[error("instance {1} of proof {0} does not match instance {3} of proof {2}")]
+    UnlinkedInstances(usize, usize, usize, usize),
+    /// A proof and verification key are required for each chunk of a chunked model
+    #[error(
+        "expected a single model, and a proof and verification key for each of its {0} chunks"
+    )]
+    ChunkArtifacts(usize),
+    /// The outputs of a model of a chain can't be linked to the inputs of the next model
+    #[error("the outputs of model {0} can't be linked to the inputs of model {1}: chaining requires at least two models, each with as many inputs as the previous has outputs, and public or hashed inputs and outputs")]
+    UnchainedModels(usize, usize),
+    /// The calls of a data attestation don't match the inputs of the first model
+    #[error("data attestation requires public inputs, and a call for each of the {0} elements of the inputs of the first model")]
+    InvalidDataCalls(usize),
+    /// An instance of an aggregated proof could not be decoded
+    #[error("invalid aggregated proof instance {0}")]
+    InvalidInstance(String),
+    /// The Yul of a verifier isn't laid out as the splitter expects
+    #[error("can't split the verifier, unexpected yul: {0}")]
+    UnexpectedVerifierYul(String),
+    /// A verifier can't be split into contracts within the size limit
+    #[error("can't split the verifier into contracts of at most {0} bytes")]
+    UnsplittableVerifier(usize),
 }
 
 /// An application snark with proof and instance variables ready for aggregation (raw field element)
@@ -118,6 +146,28 @@ impl Snark {
     }
 }
 
+impl Snark {
+    /// Create an application snark from a [Proof] of the circuit with verifying key `vk`, where `params` are the params the proof was created with.
+    /// The proof must have been created using the GWC multi-open scheme and a poseidon transcript.
+    pub fn from_proof(
+        params: &ParamsKZG<Bn256>,
+        vk: &VerifyingKey<G1Affine>,
+        proof: Proof,
+    ) -> Self {
+        let instances: Vec<Vec<Fr>> = proof
+            .public_inputs
+            .iter()
+            .map(|i| i.iter().map(|e| i32_to_felt::<Fr>(*e)).collect())
+            .collect();
+        let protocol = compile(
+            params,
+            vk,
+            Config::kzg().with_num_instance(instances.iter().map(Vec::len).collect()),
+        );
+        Snark::new(protocol, instances, proof.proof)
+    }
+}
+
 impl From<Snark> for SnarkWitness {
     fn from(snark: Snark) -> Self {
         Self {
@@ -158,13 +208,46 @@ impl SnarkWitness {
     }
 }
 
-/// Aggregate one or more application snarks of the same shape into a KzgAccumulator
+/// Identifies an instance column of an application snark, as (snark index, instance column index).
+pub type InstanceColumn = (usize, usize);
+
+/// Links the instance columns of application snarks which hold the same values, given an identifier of the values held by
+/// each instance column of each snark (e.g the index of the model node whose activations the column holds).
+/// Each column is linked to the first column holding the same values.
+pub fn link_instances<T: Hash + Eq + Copy>(
+    tags: &[Vec<T>],
+) -> Vec<(InstanceColumn, InstanceColumn)> {
+    let mut first: HashMap<T, InstanceColumn> = HashMap::new();
+    let mut links = vec![];
+    for (snark, snark_tags) in tags.iter().enumerate() {
+        for (column, tag) in snark_tags.iter().enumerate() {
+            match first.get(tag) {
+                Some(linked) => links.push((*linked, (snark, column))),
+                None => {
+                    first.insert(*tag, (snark, column));
+                }
+            }
+        }
+    }
+    links
+}
+
+/// Aggregate one or more application snarks of the same shape into a KzgAccumulator, constraining the instance columns in `links` to be equal.
+/// Also returns the (flattened) instances of the snarks, as assigned in the aggregation circuit.
+#[allow(clippy::type_complexity)]
 pub fn aggregate<'a>(
     svk: &Svk,
     loader: &Rc<Halo2Loader<'a>>,
     snarks: &[SnarkWitness],
+    links: &[(InstanceColumn, InstanceColumn)],
     as_proof: Value<&'_ [u8]>,
-) -> Result<KzgAccumulator<G1Affine, Rc<Halo2Loader<'a>>>, plonk::Error> {
+) -> Result<
+    (
+        KzgAccumulator<G1Affine, Rc<Halo2Loader<'a>>>,
+        Vec<AssignedValue<Fr>>,
+    ),
+    plonk::Error,
+> {
     let assign_instances = |instances: &[Vec<Value<Fr>>]| {
         instances
             .iter()
@@ -177,11 +260,29 @@ pub fn aggregate<'a>(
             .collect_vec()
     };
 
+    let instances = snarks
+        .iter()
+        .map(|snark| assign_instances(&snark.instances))
+        .collect_vec();
+    for ((a, i), (b, j)) in links.iter() {
+        for (lhs, rhs) in instances[*a][*i].iter().zip(instances[*b][*j].iter()) {
+            loader
+                .assert_eq("linked instances", lhs, rhs)
+                .map_err(|_| plonk::Error::Synthesis)?;
+        }
+    }
+
+    let assigned_instances = instances
+        .iter()
+        .flatten()
+        .flatten()
+        .map(|instance| instance.assigned())
+        .collect_vec();
+
     let mut accumulators = vec![];
 
-    for snark in snarks.iter() {
+    for (snark, instances) in snarks.iter().zip(instances) {
         let protocol = snark.protocol.loaded(loader);
-        let instances = assign_instances(&snark.instances);
         let mut transcript = PoseidonTranscript::<Rc<Halo2Loader>, _>::new(loader, snark.proof());
         let proof = PlonkSuccinctVerifier::read_proof(svk, &protocol, &instances, &mut transcript)
             .map_err(|_| plonk::Error::Synthesis)?;
@@ -192,9 +293,10 @@ pub fn aggregate<'a>(
     let accumulator = {
         let mut transcript = PoseidonTranscript::<Rc<Halo2Loader>, _>::new(loader, as_proof);
         let proof = As::read_proof(&Default::default(), &accumulators, &mut transcript).unwrap();
-        As::verify(&Default::default(), &accumulators, &proof).map_err(|_| plonk::Error::Synthesis)
+        As::verify(&Default::default(), &accumulators, &proof)
+            .map_err(|_| plonk::Error::Synthesis)?
     };
-    accumulator
+    Ok((accumulator, assigned_instances))
 }
 
 /// The Halo2 Config for the aggregation circuit
@@ -244,6 +346,7 @@ impl AggregationConfig {
 pub struct AggregationCircuit {
     svk: Svk,
     snarks: Vec<SnarkWitness>,
+    links: Vec<(InstanceColumn, InstanceColumn)>,
     instances: Vec<Fr>,
     as_proof: Value<Vec<u8>>,
 }
@@ -253,10 +356,28 @@ impl AggregationCircuit {
     pub fn new(
         params: &ParamsKZG<Bn256>,
         snarks: impl IntoIterator<Item = Snark>,
+    ) -> Result<Self, AggregationError> {
+        Self::new_linked(params, snarks, vec![])
+    }
+
+    /// Create a new Aggregation Circuit, which additionally constrains the pairs of instance columns in `links` (see [link_instances]) to be equal.
+    /// For instance this links the activations passed between the proofs of each chunk of a chunked model.
+    pub fn new_linked(
+        params: &ParamsKZG<Bn256>,
+        snarks: impl IntoIterator<Item = Snark>,
+        links: Vec<(InstanceColumn, InstanceColumn)>,
     ) -> Result<Self, AggregationError> {
         let svk = params.get_g()[0].into();
         let snarks = snarks.into_iter().collect_vec();
 
+        for ((a, i), (b, j)) in links.iter() {
+            let lhs = snarks.get(*a).and_then(|s| s.instances.get(*i));
+            let rhs = snarks.get(*b).and_then(|s| s.instances.get(*j));
+            if lhs.is_none() || lhs != rhs {
+                return Err(AggregationError::UnlinkedInstances(*a, *i, *b, *j));
+            }
+        }
+
         let mut accumulators = vec![];
 
         for snark in snarks.iter() {
@@ -286,13 +407,22 @@ impl AggregationCircuit {
 
         trace!("KzgAccumulator");
         let KzgAccumulator { lhs, rhs } = accumulator;
+        // the instances of the aggregated snarks follow on from the limbs of the accumulator
         let instances = [lhs.x, lhs.y, rhs.x, rhs.y]
             .map(fe_to_limbs::<_, _, LIMBS, BITS>)
-            .concat();
+            .into_iter()
+            .flatten()
+            .chain(
+                snarks
+                    .iter()
+                    .flat_map(|s| s.instances.iter().flatten().copied()),
+            )
+            .collect_vec();
 
         Ok(Self {
             svk,
             snarks: snarks.into_iter().map_into().collect(),
+            links,
             instances,
             as_proof: Value::known(as_proof),
         })
@@ -303,9 +433,15 @@ impl AggregationCircuit {
         (0..4 * LIMBS).map(|idx| (0, idx)).collect()
     }
 
-    /// Number of instance variables for the aggregation circuit, used in generating verifier.
-    pub fn num_instance() -> Vec<usize> {
-        vec![4 * LIMBS]
+    /// Number of instance variables for the aggregation circuit, used in generating verifier: the limbs of the accumulator
+    /// followed by the instances of each aggregated snark.
+    pub fn num_instance(&self) -> Vec<usize> {
+        let num_snark_instances: usize = self
+            .snarks
+            .iter()
+            .flat_map(|s| s.instances.iter().map(Vec::len))
+            .sum();
+        vec![4 * LIMBS + num_snark_instances]
     }
 
     /// Instance variables for the aggregation circuit, fed to verifier.
@@ -330,6 +466,7 @@ impl Circuit<Fr> for AggregationCircuit {
                 .iter()
                 .map(SnarkWitness::without_witnesses)
                 .collect(),
+            links: self.links.clone(),
             instances: Vec::new(),
             as_proof: Value::unknown(),
         }
@@ -353,52 +490,130 @@ impl Circuit<Fr> for AggregationCircuit {
 
         range_chip.load_table(&mut layouter)?;
 
-        let (lhs, rhs) = layouter.assign_region(
+        let (lhs, rhs, instances) = layouter.assign_region(
             || "",
             |region| {
                 let ctx = RegionCtx::new(region, 0);
 
                 let ecc_chip = config.ecc_chip();
                 let loader = Halo2Loader::new(ecc_chip, ctx);
-                let KzgAccumulator { lhs, rhs } =
-                    aggregate(&self.svk, &loader, &self.snarks, self.as_proof())?;
+                let (KzgAccumulator { lhs, rhs }, instances) = aggregate(
+                    &self.svk,
+                    &loader,
+                    &self.snarks,
+                    &self.links,
+                    self.as_proof(),
+                )?;
 
                 let lhs = lhs.assigned().clone();
                 let rhs = rhs.assigned().clone();
 
-                Ok((lhs, rhs))
+                Ok((lhs, rhs, instances))
             },
         )?;
 
-        for (limb, row) in iter::empty()
+        for (value, row) in iter::empty()
             .chain(lhs.x().limbs())
             .chain(lhs.y().limbs())
             .chain(rhs.x().limbs())
             .chain(rhs.y().limbs())
+            .map(|limb| limb.into())
+            .chain(instances)
             .zip(0..)
         {
-            main_gate.expose_public(layouter.namespace(|| ""), limb.into(), row)?;
+            main_gate.expose_public(layouter.namespace(|| ""), value, row)?;
         }
 
         Ok(())
     }
 }
 
+/// An aggregated proof and the instances of the aggregation circuit (the limbs of the KZG accumulator followed by the instances of
+/// the aggregated proofs), suitably for serialization/deserialization.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AggregatedProof {
+    /// The instances of the aggregation circuit, each a 0x prefixed hex encoding of a (big-endian) field element.
+    pub instances: Vec<Vec<String>>,
+    /// The aggregated proof, as a vector of bytes.
+    #[serde(with = "hex_bytes")]
+    pub proof: Vec<u8>,
+}
+
+impl AggregatedProof {
+    /// Create an [AggregatedProof] from the `instances` of the aggregation circuit and the `proof`.
+    pub fn new(instances: &[Vec<Fr>], proof: Vec<u8>) -> Self {
+        let instances = instances
+            .iter()
+            .map(|i| {
+                i.iter()
+                    .map(|f| {
+                        let mut repr = f.to_repr().as_ref().to_vec();
+                        repr.reverse();
+                        format!("0x{}", hex::encode(repr))
+                    })
+                    .collect()
+            })
+            .collect();
+        AggregatedProof { instances, proof }
+    }
+
+    /// Decodes the instances of the aggregation circuit to field elements.
+    pub fn instances(&self) -> Result<Vec<Vec<Fr>>, AggregationError> {
+        self.instances
+            .iter()
+            .map(|i| {
+                i.iter()
+                    .map(|s| {
+                        let mut bytes = hex::decode(s.trim_start_matches("0x"))
+                            .map_err(|_| AggregationError::InvalidInstance(s.clone()))?;
+                        bytes.reverse();
+                        let mut repr = <Fr as PrimeField>::Repr::default();
+                        if bytes.len() != repr.as_ref().len() {
+                            return Err(AggregationError::InvalidInstance(s.clone()));
+                        }
+                        repr.as_mut().copy_from_slice(&bytes);
+                        Option::<Fr>::from(Fr::from_repr(repr))
+                            .ok_or_else(|| AggregationError::InvalidInstance(s.clone()))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Saves the AggregatedProof to a specified `path` as json.
+    pub fn save(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        let serialized = serde_json::to_string(&self).map_err(Box::<dyn Error>::from)?;
+
+        let mut file = File::create(path).map_err(Box::<dyn Error>::from)?;
+        file.write_all(serialized.as_bytes())
+            .map_err(Box::<dyn Error>::from)
+    }
+
+    /// Loads an AggregatedProof saved as json from the provided path.
+    pub fn load(path: &PathBuf) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::open(path).map_err(Box::<dyn Error>::from)?;
+        let mut data = String::new();
+        file.read_to_string(&mut data)
+            .map_err(Box::<dyn Error>::from)?;
+        serde_json::from_str(&data).map_err(Box::<dyn Error>::from)
+    }
+}
+
 /// Create proof and instance variables for the application snark
 pub fn gen_application_snark(
     params: &ParamsKZG<Bn256>,
     data: &ModelInput,
-    args: &Cli,
+    args: &RunArgs,
 ) -> Result<Snark, Box<dyn Error>> {
     let (circuit, public_inputs) = prepare_circuit_and_public_input::<Fr>(data, args)?;
 
     let pk = gen_pk(params, &circuit)?;
-    let number_instance = public_inputs[0].len();
+    let number_instance = public_inputs.iter().map(|i| i.len()).collect_vec();
     trace!("number_instance {:?}", number_instance);
     let protocol = compile(
         params,
         pk.get_vk(),
-        Config::kzg().with_num_instance(vec![number_instance]),
+        Config::kzg().with_num_instance(number_instance),
     );
     let pi_inner: Vec<Vec<Fr>> = public_inputs
         .iter()
@@ -415,13 +630,24 @@ pub fn gen_application_snark(
     Ok(Snark::new(protocol, pi_inner, proof))
 }
 
-/// Create aggregation EVM verifier bytecode
+/// Create aggregation EVM verifier bytecode, split into several contracts if it exceeds the contract size limit
 pub fn gen_aggregation_evm_verifier(
     params: &ParamsKZG<Bn256>,
     vk: &VerifyingKey<G1Affine>,
     num_instance: Vec<usize>,
     accumulator_indices: Vec<(usize, usize)>,
-) -> Result<Vec<u8>, AggregationError> {
+) -> Result<VerifierCode, AggregationError> {
+    let yul_code = gen_aggregation_evm_verifier_yul(params, vk, num_instance, accumulator_indices)?;
+    VerifierCode::new(&yul_code)
+}
+
+/// Create the Yul source of the aggregation EVM verifier, as compiled by [gen_aggregation_evm_verifier]
+pub fn gen_aggregation_evm_verifier_yul(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    num_instance: Vec<usize>,
+    accumulator_indices: Vec<(usize, usize)>,
+) -> Result<String, AggregationError> {
     let protocol = compile(
         params,
         vk,
@@ -441,32 +667,67 @@ pub fn gen_aggregation_evm_verifier(
     PlonkVerifier::verify(&vk, &protocol, &instances, &proof)
         .map_err(|_| AggregationError::ProofVerify)?;
 
-    Ok(evm::compile_yul(&loader.yul_code()))
+    Ok(loader.yul_code())
 }
 
-/// Verify by executing bytecode with instance variables and proof as input
+/// The gas used by an EVM verifier, as measured by [evm_verify].
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct GasReport {
+    /// Gas used to deploy the verifier, summed over its contracts if it is split.
+    pub deployment: u64,
+    /// Gas used by a transaction verifying a proof, the sum of `base`, `calldata`, and `execution`.
+    pub verification: u64,
+    /// The base cost of a transaction.
+    pub base: u64,
+    /// The cost of the calldata (the instances followed by the proof): 16 gas per non-zero byte and 4 per zero byte.
+    pub calldata: u64,
+    /// The cost of executing the verifier.
+    pub execution: u64,
+}
+
+/// The base cost of a transaction.
+const TX_BASE_GAS: u64 = 21000;
+
+/// Verify by executing bytecode with instance variables and proof as input, returning whether the proof is accepted and
+/// the gas used to deploy the verifier and to verify the proof
 pub fn evm_verify(
-    deployment_code: Vec<u8>,
+    verifier: &VerifierCode,
     instances: Vec<Vec<Fr>>,
     proof: Vec<u8>,
-) -> Result<bool, Box<dyn Error>> {
+) -> Result<(bool, GasReport), Box<dyn Error>> {
     let calldata = encode_calldata(&instances, &proof);
+    let calldata_gas = calldata
+        .iter()
+        .map(|byte| if *byte == 0 { 4 } else { 16 })
+        .sum::<u64>();
     let mut evm = ExecutorBuilder::default()
         .with_gas_limit(u64::MAX.into())
         .build(Backend::new(MultiFork::new().0, None));
 
     let caller = Address::from_low_u64_be(0xfe);
-    let verifier = evm
-        .deploy(caller, deployment_code.into(), 0.into(), None)
-        .map_err(Box::new)?
-        .address;
+    let mut deployment_gas = 0;
+    let address = verifier.deploy(|code| {
+        let deployment = evm
+            .deploy(caller, code.into(), 0.into(), None)
+            .map_err(Box::new)?;
+        deployment_gas += deployment.gas_used;
+        Ok::<_, Box<dyn Error>>(deployment.address)
+    })?;
     let result = evm
-        .call_raw(caller, verifier, calldata.into(), 0.into())
+        .call_raw(caller, address, calldata.into(), 0.into())
         .map_err(|_| Box::new(AggregationError::EVMRawExecution))?;
 
-    dbg!(result.gas_used);
+    // the gas used by the call includes the intrinsic cost of the transaction
+    let report = GasReport {
+        deployment: deployment_gas,
+        verification: result.gas_used,
+        base: TX_BASE_GAS,
+        calldata: calldata_gas,
+        execution: result.gas_used.saturating_sub(TX_BASE_GAS + calldata_gas),
+    };
+    trace!("verifier gas {:?}", report);
 
-    Ok(!result.reverted)
+    Ok((!result.reverted, report))
 }
 
 /// Generate a structured reference string for testing. Not secure, do not use in production.
@@ -532,3 +793,37 @@ pub fn gen_kzg_proof<
     }
     Ok(proof)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2curves::group::ff::Field;
+
+    #[test]
+    fn instances_are_linked_to_their_first_occurrence() {
+        // chunk 0: model input 0 -> node 3, chunk 1: node 3 -> node 5, chunk 2: nodes 3 and 5 -> node 7
+        let tags = vec![vec![0, 3], vec![3, 5], vec![3, 5, 7]];
+        assert_eq!(
+            link_instances(&tags),
+            vec![((0, 1), (1, 0)), ((0, 1), (2, 0)), ((1, 1), (2, 1))]
+        );
+        assert!(link_instances(&[vec![0, 1], vec![2, 3]]).is_empty());
+    }
+
+    #[test]
+    fn aggregated_proof_roundtrip() {
+        let instances = vec![vec![Fr::zero(), Fr::one(), -Fr::one()]];
+        let proof = AggregatedProof::new(&instances, vec![0, 1, 2, 255]);
+        assert_eq!(proof.instances[0][1], format!("0x{}01", "00".repeat(31)));
+        let json = serde_json::to_string(&proof).unwrap();
+        let decoded: AggregatedProof = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.instances().unwrap(), instances);
+        assert_eq!(decoded.proof, proof.proof);
+
+        let invalid = AggregatedProof {
+            instances: vec![vec!["0x01".to_string()]],
+            proof: vec![],
+        };
+        assert!(invalid.instances().is_err());
+    }
+}
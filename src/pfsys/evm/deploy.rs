@@ -0,0 +1,89 @@
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{Address, Bytes, TransactionRequest};
+use log::{info, warn};
+use std::error::Error;
+use std::future::Future;
+use thiserror::Error;
+
+/// Errors raised when interacting with an Ethereum node
+#[derive(Error, Debug)]
+pub enum DeployError {
+    /// The contract creation transaction was dropped from the mempool, or deployed no contract
+    #[error("the deployment transaction was dropped or deployed no contract")]
+    NoDeployment,
+    /// There is no contract at the address of the verifier
+    #[error("no contract is deployed at {0:?}")]
+    NoContract(Address),
+}
+
+/// Runs a future to completion on a fresh runtime, as the ethers providers are async.
+fn block_on<T>(
+    future: impl Future<Output = Result<T, Box<dyn Error>>>,
+) -> Result<T, Box<dyn Error>> {
+    tokio::runtime::Runtime::new()?.block_on(future)
+}
+
+/// Deploys `deployment_code` (e.g of the verifier generated by `aggregate`) to the chain of the node at `rpc_url`,
+/// from the account of the (hex encoded) `private_key`, and returns the address of the deployed contract.
+pub fn deploy_verifier(
+    rpc_url: &str,
+    private_key: &str,
+    deployment_code: Vec<u8>,
+) -> Result<Address, Box<dyn Error>> {
+    block_on(async {
+        let provider = Provider::<Http>::try_from(rpc_url)?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet = private_key
+            .trim()
+            .trim_start_matches("0x")
+            .parse::<LocalWallet>()?
+            .with_chain_id(chain_id);
+        info!(
+            "deploying verifier from {:?} on chain {}",
+            wallet.address(),
+            chain_id
+        );
+        let client = SignerMiddleware::new(provider, wallet);
+        // a transaction without a recipient creates a contract from its data
+        let tx = TransactionRequest::new().data(Bytes::from(deployment_code));
+        let receipt = client
+            .send_transaction(tx, None)
+            .await?
+            .await?
+            .ok_or(DeployError::NoDeployment)?;
+        info!(
+            "deployment used {:?} gas in transaction {:?}",
+            receipt.gas_used, receipt.transaction_hash
+        );
+        Ok(receipt.contract_address.ok_or(DeployError::NoDeployment)?)
+    })
+}
+
+/// Calls the verifier deployed at `address` on the chain of the node at `rpc_url` with `calldata` (the instances followed by the proof,
+/// see [snark_verifier::loader::evm::encode_calldata]), returning whether the proof is accepted, that is whether the call doesn't revert.
+pub fn verify_onchain(
+    rpc_url: &str,
+    address: Address,
+    calldata: Vec<u8>,
+) -> Result<bool, Box<dyn Error>> {
+    block_on(async {
+        let provider = Provider::<Http>::try_from(rpc_url)?;
+        // calls to addresses without code succeed, so we check the verifier is deployed first
+        if provider.get_code(address, None).await?.as_ref().is_empty() {
+            return Err(Box::new(DeployError::NoContract(address)) as Box<dyn Error>);
+        }
+        let tx = TransactionRequest::new()
+            .to(address)
+            .data(Bytes::from(calldata))
+            .into();
+        match provider.call(&tx, None).await {
+            Ok(_) => Ok(true),
+            Err(e) => {
+                warn!("verifier call reverted: {}", e);
+                Ok(false)
+            }
+        }
+    })
+}
@@ -1,2 +1,10 @@
 /// Aggregate proof generation for EVM
 pub mod aggregation;
+/// Deployment of EVM verifiers, and verification of proofs against deployed verifiers
+pub mod deploy;
+/// Scaffolding of projects around the generated EVM verifiers
+pub mod scaffold;
+/// Solidity wrappers of the generated EVM verifiers
+pub mod solidity;
+/// Splitting of EVM verifiers exceeding the contract size limit into several contracts
+pub mod split;
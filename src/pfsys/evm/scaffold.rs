@@ -0,0 +1,212 @@
+use crate::pfsys::evm::solidity::CalldataFixture;
+use crate::pfsys::evm::split::VerifierCode;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// The Foundry config of the project, allowing tests and scripts to read the verifier and the sample proof.
+const FOUNDRY_TOML: &str = r#"[profile.default]
+src = "src"
+out = "out"
+libs = ["lib"]
+fs_permissions = [{ access = "read", path = "./data" }]
+"#;
+
+/// Deploys the verifier generated from Yul, with `{{chunks}}` standing for the number of chunks of a split verifier, which are
+/// deployed before the dispatcher constructed with their addresses.
+const DEPLOY_YUL_VERIFIER: &str = r#"function deployYulVerifier() internal returns (address yulVerifier) {
+        bytes memory code = vm.parseBytes(vm.readFile("data/verifier.code"));
+        for (uint256 i = 0; i < {{chunks}}; i++) {
+            bytes memory chunk =
+                vm.parseBytes(vm.readFile(string(abi.encodePacked("data/chunk", vm.toString(i), ".code"))));
+            address chunkVerifier;
+            assembly {
+                chunkVerifier := create(0, add(chunk, 0x20), mload(chunk))
+            }
+            require(chunkVerifier != address(0), "failed to deploy a verifier chunk");
+            code = abi.encodePacked(code, uint256(uint160(chunkVerifier)));
+        }
+        assembly {
+            yulVerifier := create(0, add(code, 0x20), mload(code))
+        }
+        require(yulVerifier != address(0), "failed to deploy the yul verifier");
+    }"#;
+
+/// The test of the project, with `{{pragma}}` standing for the solc version requirement and `{{deploy}}` for [DEPLOY_YUL_VERIFIER].
+const TEST_TEMPLATE: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity {{pragma}};
+
+import "forge-std/Test.sol";
+import "../src/Verifier.sol";
+
+contract VerifierTest is Test {
+    Verifier verifier;
+
+    {{deploy}}
+
+    function setUp() public {
+        verifier = new Verifier(deployYulVerifier());
+    }
+
+    function testVerifiesSampleProof() public {
+        string memory fixture = vm.readFile("data/fixture.json");
+        bytes memory proof = vm.parseJsonBytes(fixture, ".proof");
+        uint256[] memory instances = vm.parseJsonUintArray(fixture, ".instances");
+        assertTrue(verifier.verifyProof(proof, instances));
+    }
+
+    function testRejectsTamperedProof() public {
+        string memory fixture = vm.readFile("data/fixture.json");
+        bytes memory proof = vm.parseJsonBytes(fixture, ".proof");
+        uint256[] memory instances = vm.parseJsonUintArray(fixture, ".instances");
+        proof[proof.length - 1] ^= 0x01;
+        vm.expectRevert(Verifier.ProofRejected.selector);
+        verifier.verifyProof(proof, instances);
+    }
+}
+"#;
+
+/// The deployment script of the project, with `{{pragma}}` standing for the solc version requirement and `{{deploy}}` for
+/// [DEPLOY_YUL_VERIFIER].
+const SCRIPT_TEMPLATE: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity {{pragma}};
+
+import "forge-std/Script.sol";
+import "../src/Verifier.sol";
+
+contract DeployVerifier is Script {
+    {{deploy}}
+
+    function run() external returns (Verifier verifier) {
+        vm.startBroadcast();
+        verifier = new Verifier(deployYulVerifier());
+        vm.stopBroadcast();
+        console.log("verifier deployed at", address(verifier));
+    }
+}
+"#;
+
+/// Instructions for running the project.
+const README: &str = r#"# ezkl verifier
+
+A Foundry project holding the Solidity verifier of aggregated ezkl proofs (`src/Verifier.sol`), which forwards proofs to the
+verifier generated from Yul, whose deployment code is in `data/verifier.code`. Verifiers exceeding the contract size limit are split
+into chunks (`data/chunk<i>.code`), which are deployed first and called in order by the contract deployed from `data/verifier.code`.
+`data/fixture.json` holds a sample proof.
+
+```bash
+forge install foundry-rs/forge-std --no-commit
+forge test
+forge script script/DeployVerifier.s.sol --rpc-url <RPC_URL> --private-key <PRIVATE_KEY> --broadcast
+```
+"#;
+
+/// Writes a Foundry project to `dir`, holding the Solidity verifier `verifier` (see [crate::pfsys::evm::solidity::gen_solidity_verifier]),
+/// the deployment code `yul_verifier` of the verifier generated from Yul it forwards proofs to, a test verifying the sample proof of
+/// `fixture`, and a script deploying both verifiers. The test and script require solc `pragma`.
+pub fn scaffold_foundry(
+    dir: &Path,
+    verifier: &str,
+    yul_verifier: &VerifierCode,
+    fixture: &CalldataFixture,
+    pragma: &str,
+) -> Result<(), Box<dyn Error>> {
+    for subdir in ["src", "test", "script", "data"] {
+        fs::create_dir_all(dir.join(subdir))?;
+    }
+    fs::write(dir.join("foundry.toml"), FOUNDRY_TOML)?;
+    fs::write(dir.join("README.md"), README)?;
+    fs::write(dir.join(".gitignore"), "out/\ncache/\nlib/\nbroadcast/\n")?;
+    fs::write(dir.join("src/Verifier.sol"), verifier)?;
+    // the contract the verifier is called at comes last: the dispatcher of a split verifier, the verifier itself otherwise
+    let (deployment_code, chunks) = match &yul_verifier.dispatcher {
+        Some(dispatcher) => (dispatcher, yul_verifier.chunks.as_slice()),
+        None => (&yul_verifier.chunks[0], &[][..]),
+    };
+    fs::write(
+        dir.join("data/verifier.code"),
+        format!("0x{}", hex::encode(deployment_code)),
+    )?;
+    for (idx, chunk) in chunks.iter().enumerate() {
+        fs::write(
+            dir.join(format!("data/chunk{}.code", idx)),
+            format!("0x{}", hex::encode(chunk)),
+        )?;
+    }
+    let deploy = DEPLOY_YUL_VERIFIER.replace("{{chunks}}", &chunks.len().to_string());
+    fs::write(
+        dir.join("data/fixture.json"),
+        serde_json::to_string_pretty(fixture)?,
+    )?;
+    fs::write(
+        dir.join("test/Verifier.t.sol"),
+        TEST_TEMPLATE
+            .replace("{{pragma}}", pragma)
+            .replace("{{deploy}}", &deploy),
+    )?;
+    fs::write(
+        dir.join("script/DeployVerifier.s.sol"),
+        SCRIPT_TEMPLATE
+            .replace("{{pragma}}", pragma)
+            .replace("{{deploy}}", &deploy),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2curves::bn256::Fr;
+
+    #[test]
+    fn project_is_written() {
+        let dir = std::env::temp_dir().join("ezkl_scaffold_test");
+        let fixture = CalldataFixture::new(&[vec![Fr::from(1)]], &[1, 2, 3]);
+        scaffold_foundry(
+            &dir,
+            "contract Verifier {}",
+            &vec![0x60, 0x00].into(),
+            &fixture,
+            "^0.8.4",
+        )
+        .unwrap();
+        for file in [
+            "foundry.toml",
+            "src/Verifier.sol",
+            "test/Verifier.t.sol",
+            "script/DeployVerifier.s.sol",
+            "data/fixture.json",
+        ] {
+            assert!(dir.join(file).exists(), "{} is missing", file);
+        }
+        assert_eq!(
+            fs::read_to_string(dir.join("data/verifier.code")).unwrap(),
+            "0x6000"
+        );
+        let test = fs::read_to_string(dir.join("test/Verifier.t.sol")).unwrap();
+        assert!(test.contains("pragma solidity ^0.8.4;"));
+        assert!(test.contains("i < 0;"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn split_verifier_is_written() {
+        let dir = std::env::temp_dir().join("ezkl_scaffold_split_test");
+        let fixture = CalldataFixture::new(&[vec![Fr::from(1)]], &[1, 2, 3]);
+        let verifier = VerifierCode {
+            chunks: vec![vec![0x60, 0x01], vec![0x60, 0x02]],
+            dispatcher: Some(vec![0x60, 0x00]),
+        };
+        scaffold_foundry(&dir, "contract Verifier {}", &verifier, &fixture, "^0.8.4").unwrap();
+        for (file, code) in [
+            ("data/verifier.code", "0x6000"),
+            ("data/chunk0.code", "0x6001"),
+            ("data/chunk1.code", "0x6002"),
+        ] {
+            assert_eq!(fs::read_to_string(dir.join(file)).unwrap(), code);
+        }
+        let script = fs::read_to_string(dir.join("script/DeployVerifier.s.sol")).unwrap();
+        assert!(script.contains("i < 2;"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,593 @@
+use crate::graph::{Model, Port};
+use ethers::abi::{encode, Token};
+use ethers::types::{Address, U256};
+use ethers::utils::{id, to_checksum};
+use halo2curves::bn256::Fr;
+use halo2curves::group::ff::PrimeField;
+use serde::{Deserialize, Serialize};
+use snark_verifier::loader::evm::encode_calldata;
+use std::error::Error;
+
+/// The Solidity verifier, with `{{pragma}}` and `{{num_instances}}` standing for the solc version requirement and the number
+/// of public instances.
+const VERIFIER_TEMPLATE: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity {{pragma}};
+
+/// @title Verifier of aggregated ezkl proofs
+interface IVerifier {
+    /// @notice Verifies `proof` against its public `instances`, reverting if the proof is rejected
+    /// @param proof The aggregated proof
+    /// @param instances The public instances of the aggregated proof, as elements of the bn254 scalar field
+    /// @return Whether the proof is accepted
+    function verifyProof(bytes calldata proof, uint256[] calldata instances) external view returns (bool);
+}
+
+/// @title Checks the public instances of aggregated ezkl proofs, and forwards them to the verifier generated by ezkl
+/// @notice The generated verifier (compiled from Yul) takes the instances, as 32 byte big endian words, followed by the proof
+/// as calldata, and reverts if the proof is rejected
+contract Verifier is IVerifier {
+    /// @notice The number of public instances of an aggregated proof
+    uint256 public constant NUM_INSTANCES = {{num_instances}};
+
+    /// @notice The order of the bn254 scalar field
+    uint256 public constant SCALAR_FIELD_MODULUS =
+        21888242871839275222246405745257275088548364400416034343698204186575808495617;
+
+    /// @notice The address of the verifier generated by ezkl
+    address public immutable yulVerifier;
+
+    /// @notice The proof was given the wrong number of instances
+    error InvalidInstanceCount(uint256 expected, uint256 actual);
+
+    /// @notice The instance at `index` is not an element of the scalar field
+    error InstanceOutOfField(uint256 index);
+
+    /// @notice The generated verifier rejected the proof
+    error ProofRejected();
+
+    /// @param _yulVerifier The address of the verifier generated by ezkl, as deployed from its deployment code
+    constructor(address _yulVerifier) {
+        yulVerifier = _yulVerifier;
+    }
+
+    /// @inheritdoc IVerifier
+    function verifyProof(bytes calldata proof, uint256[] calldata instances) external view returns (bool) {
+        if (instances.length != NUM_INSTANCES) {
+            revert InvalidInstanceCount(NUM_INSTANCES, instances.length);
+        }
+        for (uint256 i = 0; i < instances.length; i++) {
+            if (instances[i] >= SCALAR_FIELD_MODULUS) {
+                revert InstanceOutOfField(i);
+            }
+        }
+        // packed encoding pads each instance to 32 bytes, without a length prefix
+        (bool accepted, ) = yulVerifier.staticcall(abi.encodePacked(instances, proof));
+        if (!accepted) {
+            revert ProofRejected();
+        }
+        return true;
+    }
+}
+"#;
+
+/// The default solc version requirement of generated Solidity verifiers. Custom errors require solc 0.8.4 or later.
+pub const DEFAULT_PRAGMA: &str = "^0.8.4";
+
+/// Generates a Solidity verifier for aggregated proofs with `num_instances` public instances, requiring solc `pragma`
+/// (e.g `^0.8.4`). The contract checks the instances and forwards them, along with the proof, to the verifier generated
+/// from Yul by [crate::pfsys::evm::aggregation::gen_aggregation_evm_verifier], whose address it is deployed with.
+pub fn gen_solidity_verifier(num_instances: usize, pragma: &str) -> String {
+    VERIFIER_TEMPLATE
+        .replace("{{pragma}}", pragma)
+        .replace("{{num_instances}}", &num_instances.to_string())
+}
+
+/// The attestation contract, with `{{pragma}}`, `{{verifier_import}}`, `{{accumulator_limbs}}`, `{{num_models}}`, and `{{ranges}}`
+/// standing for the solc version requirement, the path of the Solidity verifier, the number of instances holding the accumulator,
+/// the number of aggregated models, and the body of `_ranges`.
+const ATTESTATION_TEMPLATE: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity {{pragma}};
+
+import "{{verifier_import}}";
+
+/// @title Registry of inferences attested by aggregated ezkl proofs
+/// @notice Records the params commitment, inputs, and outputs (as held by the public instances of each aggregated proof) of the
+/// inferences proven by aggregated proofs accepted by the verifier. Hashed inputs and outputs are recorded as their digest limbs.
+contract Attestation {
+    /// @notice What a range of the instances of an aggregated proof holds
+    enum Kind {
+        Inputs,
+        Outputs,
+        ParamsCommitment
+    }
+
+    /// @notice The number of instances holding the accumulator, which precede the instances of the aggregated proofs
+    uint256 public constant ACCUMULATOR_LIMBS = {{accumulator_limbs}};
+
+    /// @notice The number of models whose proofs are aggregated
+    uint256 public constant NUM_MODELS = {{num_models}};
+
+    /// @notice The verifier of aggregated proofs
+    IVerifier public immutable verifier;
+
+    /// @notice The block number each inference was attested at, keyed by the hash of the instances of the aggregated proofs
+    mapping(bytes32 => uint256) public attestedAt;
+
+    /// @notice The instances of the aggregated proofs of each attested inference
+    mapping(bytes32 => uint256[]) internal records;
+
+    /// @notice The inference of `model` was attested under `id`
+    event InferenceAttested(
+        bytes32 indexed id,
+        uint256 indexed model,
+        uint256[] paramsCommitment,
+        uint256[] inputs,
+        uint256[] outputs
+    );
+
+    /// @notice No inference was attested under `id`
+    error NotAttested(bytes32 id);
+
+    /// @notice There is no model `model`
+    error UnknownModel(uint256 model);
+
+    /// @param _verifier The address of the Solidity verifier of aggregated proofs
+    constructor(IVerifier _verifier) {
+        verifier = _verifier;
+    }
+
+    /// @notice Verifies `proof`, reverting if it is rejected, and records the inference it proves
+    /// @param proof The aggregated proof
+    /// @param instances The public instances of the aggregated proof
+    /// @return id The key the inference is recorded under, the hash of the instances of the aggregated proofs
+    function attest(bytes calldata proof, uint256[] calldata instances) external returns (bytes32 id) {
+        verifier.verifyProof(proof, instances);
+        uint256[] memory attested = new uint256[](instances.length - ACCUMULATOR_LIMBS);
+        for (uint256 i = 0; i < attested.length; i++) {
+            attested[i] = instances[ACCUMULATOR_LIMBS + i];
+        }
+        id = keccak256(abi.encodePacked(attested));
+        records[id] = attested;
+        attestedAt[id] = block.number;
+        for (uint256 model = 0; model < NUM_MODELS; model++) {
+            emit InferenceAttested(
+                id,
+                model,
+                _slice(attested, model, Kind.ParamsCommitment),
+                _slice(attested, model, Kind.Inputs),
+                _slice(attested, model, Kind.Outputs)
+            );
+        }
+    }
+
+    /// @notice The inputs of `model` attested under `id`
+    function inputsOf(bytes32 id, uint256 model) external view returns (uint256[] memory) {
+        return _slice(_record(id), model, Kind.Inputs);
+    }
+
+    /// @notice The outputs of `model` attested under `id`
+    function outputsOf(bytes32 id, uint256 model) external view returns (uint256[] memory) {
+        return _slice(_record(id), model, Kind.Outputs);
+    }
+
+    /// @notice The params commitment of `model` attested under `id`, empty if its params aren't committed to
+    function paramsCommitmentOf(bytes32 id, uint256 model) external view returns (uint256[] memory) {
+        return _slice(_record(id), model, Kind.ParamsCommitment);
+    }
+
+    function _record(bytes32 id) internal view returns (uint256[] memory) {
+        if (attestedAt[id] == 0) {
+            revert NotAttested(id);
+        }
+        return records[id];
+    }
+
+    /// @notice Concatenates the ranges of `attested` holding `kind` of `model`
+    function _slice(uint256[] memory attested, uint256 model, Kind kind) internal pure returns (uint256[] memory values) {
+        uint256[2][] memory ranges = _ranges(model, kind);
+        uint256 length = 0;
+        for (uint256 i = 0; i < ranges.length; i++) {
+            length += ranges[i][1] - ranges[i][0];
+        }
+        values = new uint256[](length);
+        uint256 next = 0;
+        for (uint256 i = 0; i < ranges.length; i++) {
+            for (uint256 j = ranges[i][0]; j < ranges[i][1]; j++) {
+                values[next++] = attested[j];
+            }
+        }
+    }
+
+    /// @notice The [start, end) ranges of the instances of the aggregated proofs holding `kind` of `model`
+    function _ranges(uint256 model, Kind kind) internal pure returns (uint256[2][] memory ranges) {
+        if (model >= NUM_MODELS) {
+            revert UnknownModel(model);
+        }
+{{ranges}}
+        return new uint256[2][](0);
+    }
+}
+"#;
+
+/// The data attestation contract, with `{{pragma}}`, `{{verifier_import}}`, and `{{calls}}` standing for the solc version
+/// requirement, the path of the Solidity verifier, and the statements registering each call in the constructor.
+const DATA_ATTESTATION_TEMPLATE: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity {{pragma}};
+
+import "{{verifier_import}}";
+
+/// @title Verifies aggregated ezkl proofs whose inputs are on-chain data
+/// @notice The inputs of the first aggregated model are the results of calls to contracts (e.g the price history of an oracle),
+/// which are made at verification time and checked to match the public instances of the proof
+contract DataAttestation {
+    /// @notice A call whose result, an int256 with `decimals` decimals, is quantized to `scale` and held by instance `index`
+    struct Call {
+        address target;
+        bytes data;
+        uint256 decimals;
+        uint256 scale;
+        uint256 index;
+    }
+
+    /// @notice The order of the bn254 scalar field
+    uint256 public constant SCALAR_FIELD_MODULUS =
+        21888242871839275222246405745257275088548364400416034343698204186575808495617;
+
+    /// @notice The verifier of aggregated proofs
+    IVerifier public immutable verifier;
+
+    /// @notice The calls whose results are the inputs of the model, in order
+    Call[] public calls;
+
+    /// @notice The call at `call` reverted or returned less than a word
+    error CallFailed(uint256 call);
+
+    /// @notice The result of the call at `call` doesn't match the input it is held by
+    error InputMismatch(uint256 call, uint256 expected, uint256 actual);
+
+    /// @param _verifier The address of the Solidity verifier of aggregated proofs
+    constructor(IVerifier _verifier) {
+        verifier = _verifier;
+{{calls}}
+    }
+
+    /// @notice Quantizes `value`, with `decimals` decimals, to the fixed point representation with denominator 2^`scale` used by the
+    /// circuit, as an element of the scalar field. Rounds half away from zero, as inputs are quantized off-chain.
+    function quantize(int256 value, uint256 decimals, uint256 scale) public pure returns (uint256) {
+        uint256 magnitude = uint256(value < 0 ? -value : value);
+        uint256 denominator = 10 ** decimals;
+        uint256 quantized = (magnitude * (2 ** scale) * 2 + denominator) / (2 * denominator);
+        if (value < 0 && quantized != 0) {
+            return SCALAR_FIELD_MODULUS - quantized;
+        }
+        return quantized;
+    }
+
+    /// @notice Checks the inputs held by `instances` match the results of the calls, and verifies `proof`
+    /// @param proof The aggregated proof
+    /// @param instances The public instances of the aggregated proof
+    /// @return Whether the proof is accepted
+    function verifyWithDataAttestation(bytes calldata proof, uint256[] calldata instances) external view returns (bool) {
+        for (uint256 i = 0; i < calls.length; i++) {
+            Call storage call = calls[i];
+            (bool success, bytes memory result) = call.target.staticcall(call.data);
+            if (!success || result.length < 32) {
+                revert CallFailed(i);
+            }
+            uint256 expected = quantize(abi.decode(result, (int256)), call.decimals, call.scale);
+            if (instances[call.index] != expected) {
+                revert InputMismatch(i, expected, instances[call.index]);
+            }
+        }
+        return verifier.verifyProof(proof, instances);
+    }
+}
+"#;
+
+/// A call made by the data attestation contract, whose result is an element of the inputs of a model.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DataCall {
+    /// The address of the contract called
+    pub address: String,
+    /// The (hex encoded) calldata of the call, e.g the selector of a view function followed by its arguments
+    pub calldata: String,
+    /// The number of decimals of the value returned by the call (as an int256), e.g 8 for a price with 8 decimals
+    pub decimals: u8,
+}
+
+/// Returns the index (among the instances of the aggregated proof, see [attested_ranges]) and fixed point scale of each element
+/// of the inputs of the first aggregated model, ordered sample by sample and input by input.
+pub fn data_attestation_inputs(
+    model: &Model,
+    accumulator_limbs: usize,
+    ranges: &[AttestedRange],
+) -> Vec<(usize, i32)> {
+    let indices = ranges
+        .iter()
+        .filter(|r| r.model == 0 && r.kind == Attested::Inputs)
+        .flat_map(|r| r.start..r.end)
+        .map(|i| accumulator_limbs + i);
+    let scales = model
+        .input_shapes()
+        .iter()
+        .zip(model.get_input_scales())
+        .flat_map(|(shape, scale)| vec![scale; shape.iter().product()])
+        .collect::<Vec<_>>()
+        .repeat(model.batch_size);
+    indices.zip(scales).collect()
+}
+
+/// Generates a contract verifying aggregated proofs (with the Solidity verifier, imported from `verifier_import`) whose inputs are
+/// the results of `calls`, made at verification time, requiring solc `pragma`. Each call is paired with the index and scale of the
+/// input it is held by (see [data_attestation_inputs]).
+pub fn gen_data_attestation_contract(
+    verifier_import: &str,
+    calls: &[(DataCall, (usize, i32))],
+    pragma: &str,
+) -> Result<String, Box<dyn Error>> {
+    let mut body = String::new();
+    for (call, (index, scale)) in calls {
+        let address = to_checksum(&call.address.parse::<Address>()?, None);
+        let calldata = hex::decode(call.calldata.trim_start_matches("0x"))?;
+        body += &format!(
+            "        calls.push(Call({}, hex\"{}\", {}, {}, {}));\n",
+            address,
+            hex::encode(calldata),
+            call.decimals,
+            scale,
+            index
+        );
+    }
+    Ok(DATA_ATTESTATION_TEMPLATE
+        .replace("{{pragma}}", pragma)
+        .replace("{{verifier_import}}", verifier_import)
+        .replace("{{calls}}", body.trim_end_matches('\n')))
+}
+
+/// What a range of the instances of an aggregated proof holds, as recorded by the attestation contract.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Attested {
+    /// The (public or hashed) inputs of a model
+    Inputs,
+    /// The (public or hashed) outputs of a model
+    Outputs,
+    /// The commitment to the params of a model
+    ParamsCommitment,
+}
+
+/// A `[start, end)` range of the instances of the aggregated proofs (following the accumulator) holding `kind` of the model `model`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttestedRange {
+    /// The index of the model, in the order its proof was aggregated in
+    pub model: usize,
+    /// What the range holds
+    pub kind: Attested,
+    /// The first instance of the range
+    pub start: usize,
+    /// One past the last instance of the range
+    pub end: usize,
+}
+
+/// Computes the ranges of the instances of the aggregated proofs of `models` (in order) recorded by the attestation contract,
+/// merging adjacent ranges of the same kind. The Merkle root and public key of the signer of attested inputs are left out.
+pub fn attested_ranges(models: &[Model]) -> Vec<AttestedRange> {
+    let mut ranges: Vec<AttestedRange> = vec![];
+    let mut offset = 0;
+    for (model, om) in models.iter().enumerate() {
+        let ports = om.instance_ports();
+        for (column, shape) in om.instance_shapes().iter().enumerate() {
+            let len: usize = shape.iter().product();
+            let kind = match ports.get(column) {
+                Some((_, Port::Input(_))) => Some(Attested::Inputs),
+                Some((_, Port::Output(_))) => Some(Attested::Outputs),
+                // the params commitment is the first instance after those of the samples
+                None if om.commit_params && column == ports.len() => {
+                    Some(Attested::ParamsCommitment)
+                }
+                None => None,
+            };
+            if let Some(kind) = kind {
+                match ranges.last_mut() {
+                    Some(last)
+                        if last.model == model && last.kind == kind && last.end == offset =>
+                    {
+                        last.end += len
+                    }
+                    _ => ranges.push(AttestedRange {
+                        model,
+                        kind,
+                        start: offset,
+                        end: offset + len,
+                    }),
+                }
+            }
+            offset += len;
+        }
+    }
+    ranges
+}
+
+/// Generates a contract recording the inferences proven by aggregated proofs accepted by the Solidity verifier
+/// (see [gen_solidity_verifier], which it imports from `verifier_import`), requiring solc `pragma`. The instances of the aggregated
+/// proofs of `num_models` models follow on from `accumulator_limbs` instances, and are split into `ranges` (see [attested_ranges]).
+pub fn gen_attestation_contract(
+    verifier_import: &str,
+    accumulator_limbs: usize,
+    num_models: usize,
+    ranges: &[AttestedRange],
+    pragma: &str,
+) -> String {
+    let mut body = String::new();
+    for model in 0..num_models {
+        for kind in [
+            Attested::Inputs,
+            Attested::Outputs,
+            Attested::ParamsCommitment,
+        ] {
+            let of_kind = ranges
+                .iter()
+                .filter(|r| r.model == model && r.kind == kind)
+                .collect::<Vec<_>>();
+            if of_kind.is_empty() {
+                continue;
+            }
+            body += &format!(
+                "        if (model == {} && kind == Kind.{:?}) {{
+            ranges = new uint256[2][]({});
+",
+                model,
+                kind,
+                of_kind.len()
+            );
+            for (i, range) in of_kind.iter().enumerate() {
+                body += &format!(
+                    "            ranges[{}] = [uint256({}), uint256({})];
+",
+                    i, range.start, range.end
+                );
+            }
+            body += "            return ranges;
+        }
+";
+        }
+    }
+    ATTESTATION_TEMPLATE
+        .replace("{{pragma}}", pragma)
+        .replace("{{verifier_import}}", verifier_import)
+        .replace("{{accumulator_limbs}}", &accumulator_limbs.to_string())
+        .replace("{{num_models}}", &num_models.to_string())
+        .replace("{{ranges}}", body.trim_end_matches('\n'))
+}
+
+/// The signature of the function verifying proofs in the Solidity verifier.
+const VERIFY_PROOF_SIGNATURE: &str = "verifyProof(bytes,uint256[])";
+
+/// Encodes the calldata of a call to `verifyProof` of the Solidity verifier (see [gen_solidity_verifier]): the function selector
+/// followed by the ABI encoding of `proof` and the (flattened) `instances`.
+pub fn encode_verify_proof_calldata(instances: &[Vec<Fr>], proof: &[u8]) -> Vec<u8> {
+    let instances = instances
+        .iter()
+        .flatten()
+        .map(|i| Token::Uint(U256::from_little_endian(i.to_repr().as_ref())))
+        .collect();
+    let args = encode(&[Token::Bytes(proof.to_vec()), Token::Array(instances)]);
+    [id(VERIFY_PROOF_SIGNATURE).to_vec(), args].concat()
+}
+
+/// The calldata verifying an aggregated proof, as saved by `encode-evm-calldata` for use in contract tests
+/// (e.g with Foundry's `vm.parseJson` or Hardhat). All values are hex encoded.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CalldataFixture {
+    /// The proof
+    pub proof: String,
+    /// The public instances of the proof, as 32 byte big endian words
+    pub instances: Vec<String>,
+    /// The calldata of a call to `verifyProof` of the Solidity verifier
+    pub calldata: String,
+    /// The calldata of a call to the verifier generated from Yul: the instances followed by the proof
+    pub yul_calldata: String,
+}
+
+impl CalldataFixture {
+    /// Encodes the calldata verifying `proof` against `instances`.
+    pub fn new(instances: &[Vec<Fr>], proof: &[u8]) -> Self {
+        let hex = |bytes: &[u8]| format!("0x{}", hex::encode(bytes));
+        CalldataFixture {
+            proof: hex(proof),
+            instances: instances
+                .iter()
+                .flatten()
+                .map(|i| {
+                    let mut repr = i.to_repr().as_ref().to_vec();
+                    repr.reverse();
+                    hex(&repr)
+                })
+                .collect(),
+            calldata: hex(&encode_verify_proof_calldata(instances, proof)),
+            yul_calldata: hex(&encode_calldata(instances, proof)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verifier_is_filled_in() {
+        let verifier = gen_solidity_verifier(16, "0.8.17");
+        assert!(verifier.contains("pragma solidity 0.8.17;"));
+        assert!(verifier.contains("uint256 public constant NUM_INSTANCES = 16;"));
+        assert!(verifier.contains(
+            "function verifyProof(bytes calldata proof, uint256[] calldata instances) external view returns (bool)"
+        ));
+        assert!(!verifier.contains("{{"));
+    }
+
+    #[test]
+    fn attestation_ranges_are_filled_in() {
+        let ranges = vec![
+            AttestedRange {
+                model: 0,
+                kind: Attested::Inputs,
+                start: 0,
+                end: 16,
+            },
+            AttestedRange {
+                model: 1,
+                kind: Attested::Outputs,
+                start: 16,
+                end: 20,
+            },
+        ];
+        let contract = gen_attestation_contract("./Verifier.sol", 16, 2, &ranges, DEFAULT_PRAGMA);
+        assert!(contract.contains("pragma solidity ^0.8.4;"));
+        assert!(contract.contains("uint256 public constant NUM_MODELS = 2;"));
+        assert!(contract.contains("if (model == 1 && kind == Kind.Outputs) {"));
+        assert!(contract.contains("ranges[0] = [uint256(16), uint256(20)];"));
+        assert!(!contract.contains("Kind.ParamsCommitment) {"));
+        assert!(!contract.contains("{{"));
+    }
+
+    #[test]
+    fn data_calls_are_registered() {
+        let call = DataCall {
+            address: "0x5fbdb2315678afecb367f032d93f642f64180aa3".to_string(),
+            calldata: "0xfeaf968c".to_string(),
+            decimals: 8,
+        };
+        let contract =
+            gen_data_attestation_contract("./Verifier.sol", &[(call, (16, 7))], DEFAULT_PRAGMA)
+                .unwrap();
+        assert!(contract.contains(
+            "calls.push(Call(0x5FbDB2315678afecb367f032d93F642f64180aa3, hex\"feaf968c\", 8, 7, 16));"
+        ));
+        assert!(!contract.contains("{{"));
+
+        let invalid = DataCall {
+            address: "0x5fbd".to_string(),
+            calldata: "0x".to_string(),
+            decimals: 0,
+        };
+        assert!(gen_data_attestation_contract("./Verifier.sol", &[(invalid, (0, 0))], "").is_err());
+    }
+
+    #[test]
+    fn verify_proof_calldata_is_abi_encoded() {
+        let instances = vec![vec![Fr::from(1), Fr::from(2)]];
+        let proof = vec![0xab; 40];
+        let calldata = encode_verify_proof_calldata(&instances, &proof);
+        let word = |i: usize| &calldata[4 + 32 * i..4 + 32 * (i + 1)];
+        // the offsets of the proof and of the instances, then the length and (padded) bytes of the proof, then the instances
+        assert_eq!(U256::from_big_endian(word(0)), U256::from(64));
+        assert_eq!(U256::from_big_endian(word(1)), U256::from(64 + 32 + 64));
+        assert_eq!(U256::from_big_endian(word(2)), U256::from(40));
+        assert_eq!(U256::from_big_endian(word(5)), U256::from(2));
+        assert_eq!(U256::from_big_endian(word(7)), U256::from(2));
+        assert_eq!(calldata.len(), 4 + 32 * 8);
+
+        let fixture = CalldataFixture::new(&instances, &proof);
+        assert_eq!(fixture.instances[1], format!("0x{}02", "00".repeat(31)));
+        assert_eq!(fixture.yul_calldata.len(), 2 + 2 * (64 + 40));
+    }
+}
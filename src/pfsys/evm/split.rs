@@ -0,0 +1,503 @@
+use crate::pfsys::evm::aggregation::AggregationError;
+use snark_verifier::loader::evm::compile_yul;
+
+/// The maximum size of the code of a contract (EIP-170).
+pub const MAX_CODE_SIZE: usize = 24576;
+
+/// The deployment code of an EVM verifier. Verifiers whose code exceeds [MAX_CODE_SIZE] are split into chunks, each deployed as its
+/// own contract and run in order by a dispatcher contract, which is called as the verifier is. Each chunk runs a contiguous part of
+/// the verifier and returns its memory, which the dispatcher passes on to the next chunk following the calldata of the proof.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifierCode {
+    /// The deployment code of each chunk, in the order they are run. An unsplit verifier has a single chunk.
+    pub chunks: Vec<Vec<u8>>,
+    /// The deployment code of the dispatcher, without the addresses of the chunks it is constructed with. `None` if the
+    /// verifier isn't split.
+    pub dispatcher: Option<Vec<u8>>,
+}
+
+impl From<Vec<u8>> for VerifierCode {
+    fn from(deployment_code: Vec<u8>) -> Self {
+        VerifierCode {
+            chunks: vec![deployment_code],
+            dispatcher: None,
+        }
+    }
+}
+
+impl VerifierCode {
+    /// Compiles the Yul verifier `yul_code` (see [crate::pfsys::evm::aggregation::gen_aggregation_evm_verifier_yul]), splitting it
+    /// if its deployment code exceeds [MAX_CODE_SIZE].
+    pub fn new(yul_code: &str) -> Result<Self, AggregationError> {
+        Self::with_limit(yul_code, MAX_CODE_SIZE)
+    }
+
+    /// Compiles `yul_code` as [VerifierCode::new] does, splitting it into as few chunks as keep each within `limit` bytes.
+    pub fn with_limit(yul_code: &str, limit: usize) -> Result<Self, AggregationError> {
+        let deployment_code = compile_yul(yul_code);
+        if deployment_code.len() <= limit {
+            return Ok(deployment_code.into());
+        }
+        let runtime = VerifierRuntime::parse(yul_code)?;
+        let mut num_chunks = (deployment_code.len() + limit - 1) / limit;
+        loop {
+            if num_chunks > runtime.body.len() {
+                return Err(AggregationError::UnsplittableVerifier(limit));
+            }
+            let chunks = runtime
+                .split(num_chunks)
+                .iter()
+                .map(|yul| compile_yul(yul))
+                .collect::<Vec<_>>();
+            if chunks.iter().all(|chunk| chunk.len() <= limit) {
+                return Ok(VerifierCode {
+                    chunks,
+                    dispatcher: Some(compile_yul(&dispatcher_yul(num_chunks))),
+                });
+            }
+            num_chunks += 1;
+        }
+    }
+
+    /// Whether the verifier is split into several contracts.
+    pub fn is_split(&self) -> bool {
+        self.dispatcher.is_some()
+    }
+
+    /// The size in bytes of the largest deployment code of the contracts of the verifier.
+    pub fn max_code_size(&self) -> usize {
+        self.chunks
+            .iter()
+            .chain(&self.dispatcher)
+            .map(|code| code.len())
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Deploys the contracts of the verifier with `deploy`, which deploys deployment code and returns the address of the contract
+    /// it deployed, and returns the address the verifier is called at: that of the dispatcher if the verifier is split.
+    pub fn deploy<A: AsRef<[u8]>, E>(
+        &self,
+        mut deploy: impl FnMut(Vec<u8>) -> Result<A, E>,
+    ) -> Result<A, E> {
+        let dispatcher = match &self.dispatcher {
+            Some(dispatcher) => dispatcher,
+            None => return deploy(self.chunks[0].clone()),
+        };
+        let mut deployment_code = dispatcher.clone();
+        for chunk in &self.chunks {
+            let address = deploy(chunk.clone())?;
+            // the dispatcher is constructed with the addresses of the chunks, as 32 byte words following its code
+            let address = address.as_ref();
+            deployment_code.extend(std::iter::repeat(0).take(32 - address.len()));
+            deployment_code.extend_from_slice(address);
+        }
+        deploy(deployment_code)
+    }
+
+    /// Hex encodes the deployment code of each contract on its own line, the chunks followed by the dispatcher, as saved by
+    /// `aggregate`. An unsplit verifier is a single line.
+    pub fn to_hex(&self) -> String {
+        self.chunks
+            .iter()
+            .chain(&self.dispatcher)
+            .map(|code| format!("0x{}", hex::encode(code)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Decodes deployment code encoded by [VerifierCode::to_hex].
+    pub fn from_hex(encoded: &str) -> Result<Self, hex::FromHexError> {
+        let mut codes = encoded
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| hex::decode(line.trim_start_matches("0x")))
+            .collect::<Result<Vec<_>, _>>()?;
+        let dispatcher = if codes.len() > 1 { codes.pop() } else { None };
+        Ok(VerifierCode {
+            chunks: codes,
+            dispatcher,
+        })
+    }
+}
+
+/// The runtime code of a Yul verifier generated by snark-verifier, as a sequence of top level statements.
+#[derive(Debug)]
+struct VerifierRuntime<'a> {
+    /// The declarations of the constants and functions the verifier uses, which each chunk repeats.
+    prelude: Vec<&'a str>,
+    /// The statements of the verifier, split between the chunks.
+    body: Vec<&'a str>,
+    /// The final check of the verifier, which each chunk runs before returning.
+    epilogue: Vec<&'a str>,
+}
+
+impl<'a> VerifierRuntime<'a> {
+    fn parse(yul_code: &'a str) -> Result<Self, AggregationError> {
+        let unexpected = |what: &str| AggregationError::UnexpectedVerifierYul(what.to_string());
+        let runtime = yul_code
+            .find("object \"Runtime\"")
+            .ok_or_else(|| unexpected("no runtime object"))?;
+        let code = runtime
+            + yul_code[runtime..]
+                .find("code")
+                .ok_or_else(|| unexpected("no runtime code"))?;
+        let open = code
+            + yul_code[code..]
+                .find('{')
+                .ok_or_else(|| unexpected("no runtime code"))?;
+        let close = open
+            + matching_brace(&yul_code[open..]).ok_or_else(|| unexpected("unbalanced braces"))?;
+        let statements = statements(&yul_code[open + 1..close]);
+
+        let prelude_len = statements
+            .iter()
+            .take_while(|s| s.starts_with("let ") || s.starts_with("function "))
+            .count();
+        let epilogue_start = statements
+            .iter()
+            .rposition(|s| s.starts_with("if not(success)"))
+            .filter(|idx| *idx >= prelude_len)
+            .ok_or_else(|| unexpected("no final success check"))?;
+        let body = statements[prelude_len..epilogue_start].to_vec();
+        // variables declared at the top level may be used by later statements, which could end up in another chunk
+        if let Some(s) = body.iter().find(|s| s.starts_with("let ")) {
+            return Err(unexpected(s));
+        }
+        Ok(VerifierRuntime {
+            prelude: statements[..prelude_len].to_vec(),
+            body,
+            epilogue: statements[epilogue_start..].to_vec(),
+        })
+    }
+
+    /// The Yul of `num_chunks` contracts running contiguous parts of the body of similar length.
+    fn split(&self, num_chunks: usize) -> Vec<String> {
+        let total: usize = self.body.iter().map(|s| s.len()).sum();
+        let mut chunks = vec![vec![]; num_chunks];
+        let mut acc = 0;
+        for (idx, statement) in self.body.iter().enumerate() {
+            // statements are assigned in order, leaving at least one for each of the remaining chunks
+            let chunk = (acc * num_chunks / total.max(1))
+                .max(num_chunks.saturating_sub(self.body.len() - idx))
+                .min(num_chunks - 1);
+            chunks[chunk].push(*statement);
+            acc += statement.len();
+        }
+        // chunks other than the last return their memory rather than nothing
+        let intermediate_epilogue = self
+            .epilogue
+            .iter()
+            .copied()
+            .filter(|s| !s.starts_with("return("))
+            .chain(["return(0, msize())"])
+            .collect::<Vec<_>>();
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(idx, body)| {
+                let epilogue = if idx + 1 == num_chunks {
+                    &self.epilogue
+                } else {
+                    &intermediate_epilogue
+                };
+                chunk_yul(idx, &self.prelude, body, epilogue)
+            })
+            .collect()
+    }
+}
+
+/// The offset of the brace closing the one `code` starts with.
+fn matching_brace(code: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, c) in code.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits a block of Yul into its top level statements, which snark-verifier ends with a newline.
+fn statements(block: &str) -> Vec<&str> {
+    let mut statements = vec![];
+    let (mut depth, mut start) = (0i32, 0);
+    for (idx, c) in block.char_indices() {
+        match c {
+            '{' | '(' => depth += 1,
+            '}' | ')' => depth -= 1,
+            '\n' if depth == 0 => {
+                let statement = block[start..idx].trim();
+                if !statement.is_empty() {
+                    statements.push(statement);
+                }
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    let statement = block[start..].trim();
+    if !statement.is_empty() {
+        statements.push(statement);
+    }
+    statements
+}
+
+/// The Yul of the `idx`-th chunk of a split verifier, which restores the memory of the previous chunk, passed after the calldata of
+/// the proof and followed by the length of that calldata, before running `body`.
+fn chunk_yul(idx: usize, prelude: &[&str], body: &[&str], epilogue: &[&str]) -> String {
+    format!(
+        r#"object "plonk_verifier_{idx}" {{
+    code {{
+        let size := datasize("Runtime")
+        datacopy(0, dataoffset("Runtime"), size)
+        return(0, size)
+    }}
+    object "Runtime" {{
+        code {{
+            {}
+            {{
+                let len := calldataload(sub(calldatasize(), 0x20))
+                calldatacopy(0, len, sub(sub(calldatasize(), 0x20), len))
+            }}
+            {}
+            {}
+        }}
+    }}
+}}"#,
+        prelude.join("\n"),
+        body.join("\n"),
+        epilogue.join("\n"),
+    )
+}
+
+/// The Yul of the dispatcher of a verifier split into `num_chunks` chunks. It is constructed with the addresses of the chunks, and
+/// calls each with the calldata of the proof, the memory returned by the previous chunk, and the length of the calldata.
+fn dispatcher_yul(num_chunks: usize) -> String {
+    format!(
+        r#"object "plonk_verifier_dispatcher" {{
+    code {{
+        let words := mul({num_chunks}, 0x20)
+        codecopy(0, sub(codesize(), words), words)
+        for {{ let i := 0 }} lt(i, {num_chunks}) {{ i := add(i, 1) }} {{
+            sstore(i, mload(mul(i, 0x20)))
+        }}
+        let size := datasize("Runtime")
+        datacopy(0, dataoffset("Runtime"), size)
+        return(0, size)
+    }}
+    object "Runtime" {{
+        code {{
+            let len := calldatasize()
+            calldatacopy(0, 0, len)
+            let size := len
+            for {{ let i := 0 }} lt(i, {num_chunks}) {{ i := add(i, 1) }} {{
+                mstore(size, len)
+                if iszero(staticcall(gas(), sload(i), 0, add(size, 0x20), 0, 0)) {{ revert(0, 0) }}
+                returndatacopy(len, 0, returndatasize())
+                size := add(len, returndatasize())
+            }}
+            return(0, 0)
+        }}
+    }}
+}}"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pfsys::evm::aggregation::{
+        evm_verify, gen_aggregation_evm_verifier_yul, gen_kzg_proof, gen_pk, gen_srs,
+    };
+    use halo2_proofs::circuit::{Layouter, SimpleFloorPlanner, Value};
+    use halo2_proofs::plonk::{
+        Advice, Circuit, Column, ConstraintSystem, Error, Instance, Selector,
+    };
+    use halo2_proofs::poly::Rotation;
+    use halo2curves::bn256::{Fr, G1Affine};
+    use snark_verifier::system::halo2::transcript::evm::EvmTranscript;
+
+    /// Yul laid out as snark-verifier lays out its verifiers.
+    const YUL: &str = r#"
+        object "plonk_verifier" {
+            code {
+                let size := datasize("Runtime")
+                datacopy(0, dataoffset("Runtime"), size)
+                return(0, size)
+            }
+            object "Runtime" {
+                code {
+                    let success:bool := true
+                    let f_p := 0x30644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd47
+                    function validate_ec_point(x, y) -> valid:bool {
+                        {
+                            let x_lt_p:bool := lt(x, f_p)
+                            valid := x_lt_p
+                        }
+                    }
+                    mstore(0x20, mod(calldataload(0x0), f_p))
+                    {
+                        let x := calldataload(0x20)
+                        mstore(0x40, x)
+                    }
+                    success := and(validate_ec_point(mload(0x20), mload(0x40)), success)
+                    mstore(0x60, add(mload(0x20), mload(0x40)))
+
+                    if not(success) { revert(0, 0) }
+                    return(0, 0)
+                }
+            }
+        }"#;
+
+    #[test]
+    fn runtime_is_split_between_chunks() {
+        let runtime = VerifierRuntime::parse(YUL).unwrap();
+        assert_eq!(runtime.prelude.len(), 3);
+        assert!(runtime.prelude[2].starts_with("function validate_ec_point"));
+        assert_eq!(runtime.body.len(), 4);
+        assert_eq!(
+            runtime.epilogue,
+            vec!["if not(success) { revert(0, 0) }", "return(0, 0)"]
+        );
+
+        let chunks = runtime.split(2);
+        assert_eq!(chunks.len(), 2);
+        for chunk in &chunks {
+            assert!(chunk.contains("function validate_ec_point"));
+            assert!(chunk.contains("calldatacopy(0, len"));
+            assert!(chunk.contains("if not(success) { revert(0, 0) }"));
+        }
+        // each statement of the body is run by exactly one chunk, in order
+        for statement in &runtime.body {
+            assert_eq!(chunks.iter().filter(|c| c.contains(statement)).count(), 1);
+        }
+        assert!(chunks[0].contains("mstore(0x20"));
+        assert!(chunks[1].contains("mstore(0x60"));
+        assert!(chunks[0].contains("return(0, msize())"));
+        assert!(chunks[1].contains("return(0, 0)") && !chunks[1].contains("msize()"));
+        // as many chunks as statements run a statement each
+        for (chunk, statement) in runtime.split(4).iter().zip(&runtime.body) {
+            assert!(chunk.contains(statement));
+        }
+    }
+
+    #[test]
+    fn top_level_variables_are_not_split() {
+        let yul = YUL.replace("mstore(0x60", "let y := 1\n                    mstore(0x60");
+        assert!(matches!(
+            VerifierRuntime::parse(&yul),
+            Err(AggregationError::UnexpectedVerifierYul(_))
+        ));
+    }
+
+    #[test]
+    fn hex_roundtrip() {
+        let unsplit = VerifierCode::from(vec![0x60, 0x00]);
+        assert_eq!(unsplit.to_hex(), "0x6000");
+        assert_eq!(VerifierCode::from_hex("0x6000\n").unwrap(), unsplit);
+
+        let split = VerifierCode {
+            chunks: vec![vec![0x60, 0x01], vec![0x60, 0x02]],
+            dispatcher: Some(vec![0x60, 0x00]),
+        };
+        assert_eq!(split.to_hex(), "0x6001\n0x6002\n0x6000");
+        assert_eq!(VerifierCode::from_hex(&split.to_hex()).unwrap(), split);
+    }
+
+    #[derive(Clone, Default)]
+    struct SquareCircuit {
+        x: Value<Fr>,
+    }
+
+    impl Circuit<Fr> for SquareCircuit {
+        type Config = (Column<Advice>, Column<Instance>, Selector);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            Self::default()
+        }
+
+        fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+            let advice = meta.advice_column();
+            let instance = meta.instance_column();
+            let selector = meta.selector();
+            meta.enable_equality(advice);
+            meta.enable_equality(instance);
+            meta.create_gate("square", |meta| {
+                let s = meta.query_selector(selector);
+                let x = meta.query_advice(advice, Rotation::cur());
+                let y = meta.query_advice(advice, Rotation::next());
+                vec![s * (x.clone() * x - y)]
+            });
+            (advice, instance, selector)
+        }
+
+        fn synthesize(
+            &self,
+            (advice, instance, selector): Self::Config,
+            mut layouter: impl Layouter<Fr>,
+        ) -> Result<(), Error> {
+            let y = layouter.assign_region(
+                || "square",
+                |mut region| {
+                    selector.enable(&mut region, 0)?;
+                    region.assign_advice(|| "x", advice, 0, || self.x)?;
+                    region.assign_advice(|| "y", advice, 1, || self.x * self.x)
+                },
+            )?;
+            layouter.constrain_instance(y.cell(), instance, 0)
+        }
+    }
+
+    #[test]
+    fn split_verifier_accepts_proofs() {
+        let params = gen_srs(5);
+        let circuit = SquareCircuit {
+            x: Value::known(Fr::from(3)),
+        };
+        let instances = vec![vec![Fr::from(9)]];
+        let pk = gen_pk(&params, &circuit).unwrap();
+        let proof = gen_kzg_proof::<
+            _,
+            _,
+            EvmTranscript<G1Affine, _, _, _>,
+            EvmTranscript<G1Affine, _, _, _>,
+        >(&params, &pk, circuit, instances.clone())
+        .unwrap();
+        let yul = gen_aggregation_evm_verifier_yul(&params, pk.get_vk(), vec![1], vec![]).unwrap();
+
+        let unsplit = VerifierCode::new(&yul).unwrap();
+        assert!(!unsplit.is_split());
+        assert!(unsplit.max_code_size() <= MAX_CODE_SIZE);
+        assert!(
+            evm_verify(&unsplit, instances.clone(), proof.clone())
+                .unwrap()
+                .0
+        );
+
+        // a limit of a third of the verifier forces it to be split into at least three chunks
+        let limit = unsplit.max_code_size() / 3;
+        let split = VerifierCode::with_limit(&yul, limit).unwrap();
+        assert!(split.is_split() && split.chunks.len() >= 3);
+        assert!(split.max_code_size() <= limit);
+        assert!(
+            evm_verify(&split, instances.clone(), proof.clone())
+                .unwrap()
+                .0
+        );
+
+        let mut tampered = proof;
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(!evm_verify(&split, instances, tampered).unwrap().0);
+    }
+}
@@ -0,0 +1,256 @@
+use super::ModelInput;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Errors loading and preprocessing images.
+#[derive(Debug, Error)]
+pub enum ImageError {
+    /// The image couldn't be read or decoded
+    #[error("failed to load image {0}: {1}")]
+    Load(String, image::ImageError),
+    /// The preprocessing spec couldn't be read or parsed
+    #[error("invalid preprocessing spec {0}: {1}")]
+    InvalidSpec(String, String),
+    /// The mean or std of the normalization doesn't have a value per channel
+    #[error("{0} has {1} values but images have {2} channels")]
+    ChannelMismatch(&'static str, usize, usize),
+    /// A std of the normalization is zero
+    #[error("std must be non-zero")]
+    ZeroStd,
+    /// The spec lists no images
+    #[error("no images listed in {0}")]
+    NoImages(String),
+}
+
+/// The order of the dimensions of image tensors.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageLayout {
+    /// Channels first, as expected by pytorch models
+    #[default]
+    Nchw,
+    /// Channels last, as expected by tensorflow models
+    Nhwc,
+}
+
+/// How images are turned into input tensors: resized, scaled to `[0, 1]`, normalized per channel as `(x - mean) / std`, and
+/// laid out as `layout`.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preprocessing {
+    /// The `[height, width]` images are resized to, if any.
+    pub resize: Option<[u32; 2]>,
+    /// Whether images are converted to a single grayscale channel, rather than RGB.
+    pub grayscale: bool,
+    /// The factor pixel values (in `[0, 255]`) are multiplied by before normalization.
+    pub scale: f32,
+    /// The mean subtracted from each channel, empty to skip normalization.
+    pub mean: Vec<f32>,
+    /// The standard deviation each channel is divided by, empty to skip normalization.
+    pub std: Vec<f32>,
+    /// The order of the dimensions of the resulting tensor.
+    pub layout: ImageLayout,
+}
+
+impl Default for Preprocessing {
+    fn default() -> Self {
+        Preprocessing {
+            resize: None,
+            grayscale: false,
+            scale: 1.0 / 255.0,
+            mean: vec![],
+            std: vec![],
+            layout: ImageLayout::Nchw,
+        }
+    }
+}
+
+/// A preprocessing spec along with the images (one per model input) it applies to.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ImageInput {
+    /// The images, relative to the spec file.
+    pub images: Vec<PathBuf>,
+    /// The preprocessing applied to every image.
+    #[serde(flatten)]
+    pub preprocessing: Preprocessing,
+}
+
+/// Whether `path` is an image file which can be loaded by [load_image].
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map_or(false, |e| {
+            matches!(e.to_lowercase().as_str(), "png" | "jpg" | "jpeg")
+        })
+}
+
+/// Applies `spec` to `img`, returning the shape (with a leading batch dimension of 1) and values of the resulting tensor.
+pub fn preprocess(
+    img: DynamicImage,
+    spec: &Preprocessing,
+) -> Result<(Vec<usize>, Vec<f32>), ImageError> {
+    let img = match spec.resize {
+        Some([height, width]) => img.resize_exact(width, height, FilterType::Triangle),
+        None => img,
+    };
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let (channels, pixels) = match spec.grayscale {
+        true => (1, img.to_luma8().into_raw()),
+        false => (3, img.to_rgb8().into_raw()),
+    };
+    let normalize = !spec.mean.is_empty() || !spec.std.is_empty();
+    if normalize {
+        for (name, values) in [("mean", &spec.mean), ("std", &spec.std)] {
+            if values.len() != channels {
+                return Err(ImageError::ChannelMismatch(name, values.len(), channels));
+            }
+        }
+        if spec.std.contains(&0.0) {
+            return Err(ImageError::ZeroStd);
+        }
+    }
+
+    // pixels are decoded channels last
+    let value = |y: usize, x: usize, c: usize| {
+        let v = pixels[(y * width + x) * channels + c] as f32 * spec.scale;
+        match normalize {
+            true => (v - spec.mean[c]) / spec.std[c],
+            false => v,
+        }
+    };
+    let (shape, values) = match spec.layout {
+        ImageLayout::Nchw => (
+            vec![1, channels, height, width],
+            (0..channels)
+                .flat_map(|c| (0..height).flat_map(move |y| (0..width).map(move |x| (y, x, c))))
+                .map(|(y, x, c)| value(y, x, c))
+                .collect(),
+        ),
+        ImageLayout::Nhwc => (
+            vec![1, height, width, channels],
+            (0..height)
+                .flat_map(|y| (0..width).flat_map(move |x| (0..channels).map(move |c| (y, x, c))))
+                .map(|(y, x, c)| value(y, x, c))
+                .collect(),
+        ),
+    };
+    Ok((shape, values))
+}
+
+/// Loads the image at `path` and applies `spec` to it.
+pub fn load_image(path: &Path, spec: &Preprocessing) -> Result<(Vec<usize>, Vec<f32>), ImageError> {
+    let img = image::open(path).map_err(|e| ImageError::Load(path.display().to_string(), e))?;
+    preprocess(img, spec)
+}
+
+/// Loads the inputs of a model from a single image, preprocessed with the default [Preprocessing]. Outputs are left empty.
+pub fn load_inputs(path: &Path) -> Result<ModelInput, ImageError> {
+    load_spec_inputs(
+        &ImageInput {
+            images: vec![path.to_path_buf()],
+            preprocessing: Preprocessing::default(),
+        },
+        Path::new(""),
+    )
+}
+
+/// Parses an [ImageInput] spec, returning `None` if `json` isn't one (i.e doesn't list `images`).
+pub fn parse_spec(json: &str, name: &str) -> Result<Option<ImageInput>, ImageError> {
+    let value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| ImageError::InvalidSpec(name.to_string(), e.to_string()))?;
+    if value.get("images").is_none() {
+        return Ok(None);
+    }
+    serde_json::from_value(value)
+        .map(Some)
+        .map_err(|e| ImageError::InvalidSpec(name.to_string(), e.to_string()))
+}
+
+/// Loads the inputs of a model from the images of `spec`, resolved relative to `dir`, one per input. Outputs are left empty.
+pub fn load_spec_inputs(spec: &ImageInput, dir: &Path) -> Result<ModelInput, ImageError> {
+    if spec.images.is_empty() {
+        return Err(ImageError::NoImages(dir.display().to_string()));
+    }
+    let (input_shapes, input_data) = spec
+        .images
+        .iter()
+        .map(|image| load_image(&dir.join(image), &spec.preprocessing))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .unzip();
+    Ok(ModelInput {
+        input_data,
+        input_shapes,
+        input_names: vec![],
+        output_data: vec![],
+        input_key: None,
+        input_signature: None,
+        input_merkle_paths: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// A 2x1 image with a red and a blue pixel.
+    fn red_blue() -> DynamicImage {
+        let mut img = RgbImage::new(2, 1);
+        img.put_pixel(0, 0, Rgb([255, 0, 0]));
+        img.put_pixel(1, 0, Rgb([0, 0, 255]));
+        DynamicImage::ImageRgb8(img)
+    }
+
+    #[test]
+    fn lays_out_and_normalizes_channels() {
+        let (shape, values) = preprocess(red_blue(), &Preprocessing::default()).unwrap();
+        assert_eq!(shape, vec![1, 3, 1, 2]);
+        assert_eq!(values, vec![1.0, 0.0, 0.0, 0.0, 0.0, 1.0]);
+
+        let spec = Preprocessing {
+            mean: vec![0.5, 0.5, 0.5],
+            std: vec![0.5, 0.5, 0.5],
+            layout: ImageLayout::Nhwc,
+            ..Preprocessing::default()
+        };
+        let (shape, values) = preprocess(red_blue(), &spec).unwrap();
+        assert_eq!(shape, vec![1, 1, 2, 3]);
+        assert_eq!(values, vec![1.0, -1.0, -1.0, -1.0, -1.0, 1.0]);
+
+        let spec = Preprocessing {
+            resize: Some([2, 4]),
+            grayscale: true,
+            ..Preprocessing::default()
+        };
+        assert_eq!(preprocess(red_blue(), &spec).unwrap().0, vec![1, 1, 2, 4]);
+    }
+
+    #[test]
+    fn parses_specs() {
+        let spec = parse_spec(
+            r#"{"images": ["cat.png"], "resize": [224, 224], "mean": [0.485, 0.456, 0.406], "std": [0.229, 0.224, 0.225]}"#,
+            "spec.json",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(spec.images, vec![PathBuf::from("cat.png")]);
+        assert_eq!(spec.preprocessing.resize, Some([224, 224]));
+        assert_eq!(spec.preprocessing.layout, ImageLayout::Nchw);
+
+        let spec = Preprocessing {
+            mean: vec![0.5],
+            ..Preprocessing::default()
+        };
+        assert!(matches!(
+            preprocess(red_blue(), &spec),
+            Err(ImageError::ChannelMismatch("mean", 1, 3))
+        ));
+        assert!(parse_spec(r#"{"input_data": []}"#, "data.json")
+            .unwrap()
+            .is_none());
+    }
+}
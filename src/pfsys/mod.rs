@@ -1,72 +1,675 @@
+/// Headers prefixing serialized artifacts with the version of ezkl which produced them
+pub mod artifact;
+/// Per-phase timing and memory measurements, and circuit statistics, for benchmarking proof generation
+pub mod bench;
 /// Aggregation circuit
 #[cfg(feature = "evm")]
 pub mod evm;
+/// Loading model inputs from PNG and JPEG images, with a preprocessing spec
+pub mod image;
+/// Loading model inputs from numpy .npy and .npz files
+pub mod npy;
+/// Loading, converting, and verifying structured reference strings for KZG
+pub mod srs;
 
-use crate::commands::{data_path, Cli};
-use crate::fieldutils::i32_to_felt;
-use crate::graph::{utilities::vector_to_quantized, Model, ModelCircuit};
+use crate::circuit::eddsa::{verify, Signature};
+use crate::circuit::merkle::MerklePath;
+use crate::circuit::poseidon::{
+    digest_to_limbs, hash_to_limbs, limbs_to_digest, poseidon_hash, DIGEST_LIMBS, KEY_LIMBS,
+};
+use crate::commands::{data_path, ProofFormat};
+use crate::fieldutils::{felt_to_i128, i32_to_felt};
+use crate::graph::input::load_csv;
+use crate::graph::{
+    utilities::vector_to_quantized, GraphError, Model, ModelCircuit, RunArgs, Visibility,
+};
 use crate::tensor::{Tensor, TensorType};
+use artifact::ArtifactHeader;
 use halo2_proofs::arithmetic::FieldExt;
 use halo2_proofs::plonk::{
     create_proof, keygen_pk, keygen_vk, verify_proof, Circuit, ProvingKey, VerifyingKey,
 };
 use halo2_proofs::poly::commitment::{CommitmentScheme, Params, Prover, Verifier};
+use halo2_proofs::poly::kzg::commitment::{KZGCommitmentScheme, ParamsKZG};
+use halo2_proofs::poly::kzg::strategy::{AccumulatorStrategy, SingleStrategy};
 use halo2_proofs::poly::VerificationStrategy;
-use halo2_proofs::transcript::{
-    Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
-};
+use halo2_proofs::transcript::{EncodedChallenge, TranscriptReadBuffer, TranscriptWriterBuffer};
+use halo2curves::bn256::{Bn256, Fr, G1Affine};
 use halo2curves::group::ff::PrimeField;
 use halo2curves::serde::SerdeObject;
 use halo2curves::CurveAffine;
-use log::{info, trace};
+use itertools::Itertools;
+use log::{info, trace, warn};
 use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
+use snark_verifier::system;
+use srs::mmap_file;
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs::File;
-use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::io::{self, BufWriter, Cursor, Read, Write};
 use std::marker::PhantomData;
 use std::ops::Deref;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
+use thiserror::Error;
+
+/// The version of the on-disk format of proving and verifying keys, bump this whenever said format changes.
+pub const KEY_FORMAT_VERSION: u32 = 3;
+
+/// Errors related to the proof system artifacts
+#[derive(Debug, Error)]
+pub enum PfsysError {
+    /// A key file is missing the ezkl header
+    #[error("not an ezkl key file")]
+    InvalidKeyHeader,
+    /// A key file was written using a different version of the key format
+    #[error(
+        "key was written with format version {0} but version {} is expected, re-run setup",
+        KEY_FORMAT_VERSION
+    )]
+    KeyVersion(u32),
+    /// A key file was generated for a different circuit (different model or circuit parameters)
+    #[error("key was generated for a different circuit, re-run setup")]
+    StaleKey,
+    /// Hex calldata could not be decoded into a proof
+    #[error("invalid proof calldata")]
+    InvalidCalldata,
+    /// Input data does not hold a batch of the configured size
+    #[error("input data does not hold a batch of {0} samples")]
+    InvalidBatch(usize),
+    /// An input of the data doesn't have the shape of the corresponding input of the model
+    #[error("input {0} has shape {1:?} but the model expects {2:?}")]
+    InputShape(usize, Vec<usize>, Vec<usize>),
+    /// The data lacks an input of the model, when inputs are given by name
+    #[error("no data for input {0} of the model")]
+    MissingInput(String),
+    /// The data has an input which the model doesn't have, when inputs are given by name
+    #[error("the model has no input named {0}, expected one of {1:?}")]
+    UnknownInput(String, Vec<String>),
+    /// A proof was generated with a different model, circuit settings, key, or SRS than it is being verified with
+    #[error(
+        "proof was generated with a different {0}: expected hash {1} but the proof records {2}"
+    )]
+    ArtifactMismatch(String, String, String),
+}
+
+/// A stable (blake3) hash identifying a circuit, see [circuit_fingerprint].
+pub type Fingerprint = [u8; 32];
+
+/// Stable (blake3) hashes of the artifacts a proof was generated with. These are recorded in proofs such that verifying
+/// with a mismatched model, key, or SRS is reported as such, rather than as a failed verification.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ArtifactHashes {
+    /// Hash of the model and circuit settings, see [circuit_fingerprint].
+    pub circuit: String,
+    /// Hash of the verifying key.
+    pub vk: String,
+    /// Hash of the structured reference string (params).
+    pub srs: String,
+}
+
+impl ArtifactHashes {
+    /// Hashes the artifacts of a circuit with fingerprint `circuit`, verifying key `vk`, and parameters `params`.
+    pub fn new<Scheme: CommitmentScheme>(
+        circuit: Fingerprint,
+        vk: &VerifyingKey<Scheme::Curve>,
+        params: &Scheme::ParamsVerifier,
+    ) -> Result<Self, io::Error>
+    where
+        Scheme::Curve: SerdeObject + CurveAffine,
+        Scheme::Scalar: PrimeField + SerdeObject,
+    {
+        let mut vk_hasher = blake3::Hasher::new();
+        vk.write(&mut vk_hasher, halo2_proofs::SerdeFormat::Processed)?;
+        let mut srs_hasher = blake3::Hasher::new();
+        params.write(&mut srs_hasher)?;
+        Ok(ArtifactHashes {
+            circuit: hex::encode(circuit),
+            vk: vk_hasher.finalize().to_hex().to_string(),
+            srs: srs_hasher.finalize().to_hex().to_string(),
+        })
+    }
+
+    /// Checks these hashes, as recorded in a proof, match those of the artifacts the proof is being verified with (`expected`).
+    pub fn check(&self, expected: &ArtifactHashes) -> Result<(), PfsysError> {
+        for (artifact, found, expected) in [
+            (
+                "model or circuit settings",
+                &self.circuit,
+                &expected.circuit,
+            ),
+            ("verifying key", &self.vk, &expected.vk),
+            ("SRS", &self.srs, &expected.srs),
+        ] {
+            if found != expected {
+                return Err(PfsysError::ArtifactMismatch(
+                    artifact.to_string(),
+                    expected.clone(),
+                    found.clone(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+const T: usize = 5;
+const RATE: usize = 4;
+const R_F: usize = 8;
+const R_P: usize = 60;
+
+/// An algebraic (Poseidon) transcript over bn256, cheap to verify within a circuit and as such suited to proofs which are
+/// to be aggregated.
+pub type PoseidonTranscript<L, S> =
+    system::halo2::transcript::halo2::PoseidonTranscript<G1Affine, L, S, T, RATE, R_F, R_P>;
 
 /// The input tensor data and shape, and output data for the computational graph (model) as floats.
 /// For example, the input might be the image data for a neural network, and the output class scores.
+///
+/// In json, `input_data` is either a list of flattened inputs (with their shapes in `input_shapes`), or a map from the names of
+/// the inputs of the model to nested arrays, whose shapes are inferred.
 #[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "ModelInputJson")]
 pub struct ModelInput {
     /// Inputs to the model / computational graph.
     pub input_data: Vec<Vec<f32>>,
     /// The shape of said inputs.
     pub input_shapes: Vec<Vec<usize>>,
+    /// The names of said inputs, when given by name, matched against the names of the inputs of the model by
+    /// [ModelInput::order_inputs]. Empty when inputs are given in the order of the model's inputs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub input_names: Vec<String>,
     /// The expected output of the model (can be empty vectors if outputs are not being constrained).
     pub output_data: Vec<Vec<f32>>,
+    /// The key inputs are committed to with when running with `--committed-inputs`: 8 random values in `[0, 65536)`,
+    /// shared by the data owner with whoever verifies the commitment. Shared by all samples of a batch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_key: Option<Vec<i32>>,
+    /// The signature over the inputs checked when running with `--attested-inputs`, as produced by the `sign` command.
+    /// Covers all samples of a batch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_signature: Option<InputSignature>,
+    /// The path from the inputs of each sample to the root of the dataset they are drawn from, checked when running with
+    /// `--merkle-depth`, as produced by the `merkle-path` command.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub input_merkle_paths: Vec<InputMerklePath>,
+}
+
+/// The path from the inputs of a sample to the root of a Merkle tree (see [MerklePath]), with each sibling stored as its limbs.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InputMerklePath {
+    /// The index of the leaf in the tree.
+    pub index: usize,
+    /// The siblings of the nodes on the path, from the leaf upwards.
+    pub siblings: Vec<Vec<i32>>,
+}
+
+impl InputMerklePath {
+    /// Converts `path` into its limbs.
+    pub fn new<F: FieldExt>(path: &MerklePath<F>) -> Self {
+        InputMerklePath {
+            index: path.index,
+            siblings: path.siblings.iter().map(|s| digest_to_limbs(*s)).collect(),
+        }
+    }
+
+    /// Recomposes the path from its limbs.
+    pub fn to_path<F: FieldExt>(&self) -> MerklePath<F> {
+        MerklePath {
+            index: self.index,
+            siblings: self.siblings.iter().map(|s| limbs_to_digest(s)).collect(),
+        }
+    }
+}
+
+/// An EdDSA signature over the inputs of a [ModelInput] (see [crate::circuit::eddsa::sign]). Each field element is stored as its
+/// limbs (see [digest_to_limbs]), the form the public key takes in the public inputs of proofs.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InputSignature {
+    /// The `x` then `y` coordinates of the signer's public key.
+    pub public_key: Vec<i32>,
+    /// The `x` then `y` coordinates of the commitment to the nonce of the signature.
+    pub r: Vec<i32>,
+    /// The response of the signature.
+    pub s: Vec<i32>,
+}
+
+impl InputSignature {
+    /// Converts `signature` into its limbs.
+    pub fn new<F: FieldExt>(signature: &Signature<F>) -> Self {
+        let point = |p: &(F, F)| [digest_to_limbs(p.0), digest_to_limbs(p.1)].concat();
+        InputSignature {
+            public_key: point(&signature.public_key),
+            r: point(&signature.r),
+            s: digest_to_limbs(signature.s),
+        }
+    }
+
+    /// Recomposes the signature from its limbs.
+    pub fn to_signature<F: FieldExt>(&self) -> Result<Signature<F>, GraphError> {
+        let point = |limbs: &[i32]| match limbs.len() == 2 * DIGEST_LIMBS {
+            true => Ok((
+                limbs_to_digest(&limbs[..DIGEST_LIMBS]),
+                limbs_to_digest(&limbs[DIGEST_LIMBS..]),
+            )),
+            false => Err(GraphError::InvalidInputSignature),
+        };
+        if self.s.len() != DIGEST_LIMBS {
+            return Err(GraphError::InvalidInputSignature);
+        }
+        Ok(Signature {
+            public_key: point(&self.public_key)?,
+            r: point(&self.r)?,
+            s: limbs_to_digest(&self.s),
+        })
+    }
+}
+
+/// The inputs of a json data file, either flattened or keyed by name, see [ModelInput].
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum InputData {
+    Flat(Vec<Vec<f32>>),
+    Named(BTreeMap<String, serde_json::Value>),
+}
+
+/// The json representation of a [ModelInput], where inputs can be given by name.
+#[derive(Deserialize)]
+struct ModelInputJson {
+    input_data: InputData,
+    #[serde(default)]
+    input_shapes: Option<Vec<Vec<usize>>>,
+    #[serde(default)]
+    input_names: Vec<String>,
+    output_data: Vec<Vec<f32>>,
+    #[serde(default)]
+    input_key: Option<Vec<i32>>,
+    #[serde(default)]
+    input_signature: Option<InputSignature>,
+    #[serde(default)]
+    input_merkle_paths: Vec<InputMerklePath>,
+}
+
+/// Flattens a (rectangular) nested array of numbers, returning its shape and values.
+fn flatten_nested(value: &serde_json::Value) -> Result<(Vec<usize>, Vec<f32>), String> {
+    match value {
+        serde_json::Value::Number(n) => Ok((vec![], vec![n.as_f64().unwrap_or_default() as f32])),
+        serde_json::Value::Bool(b) => Ok((vec![], vec![*b as u8 as f32])),
+        serde_json::Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(flatten_nested)
+                .collect::<Result<Vec<_>, _>>()?;
+            let inner = items.first().map(|(s, _)| s.clone()).unwrap_or_default();
+            if items.iter().any(|(s, _)| *s != inner) {
+                return Err("nested arrays must all have the same shape".to_string());
+            }
+            let shape = [vec![items.len()], inner].concat();
+            Ok((shape, items.into_iter().flat_map(|(_, v)| v).collect()))
+        }
+        v => Err(format!("expected a number or an array, found {}", v)),
+    }
+}
+
+impl TryFrom<ModelInputJson> for ModelInput {
+    type Error = String;
+
+    fn try_from(json: ModelInputJson) -> Result<Self, Self::Error> {
+        let (input_data, input_shapes, input_names) = match json.input_data {
+            InputData::Flat(data) => {
+                let shapes = json.input_shapes.ok_or("missing field `input_shapes`")?;
+                (data, shapes, json.input_names)
+            }
+            InputData::Named(named) => {
+                let mut data = vec![];
+                let mut shapes = vec![];
+                for (name, value) in &named {
+                    let (shape, values) =
+                        flatten_nested(value).map_err(|e| format!("input {}: {}", name, e))?;
+                    shapes.push(shape);
+                    data.push(values);
+                }
+                // shapes are inferred, but must match those given if any
+                if let Some(given) = json.input_shapes {
+                    if given != shapes {
+                        return Err(format!(
+                            "input_shapes {:?} don't match the shapes of the named inputs {:?}",
+                            given, shapes
+                        ));
+                    }
+                }
+                (data, shapes, named.into_keys().collect())
+            }
+        };
+        Ok(ModelInput {
+            input_data,
+            input_shapes,
+            input_names,
+            output_data: json.output_data,
+            input_key: json.input_key,
+            input_signature: json.input_signature,
+            input_merkle_paths: json.input_merkle_paths,
+        })
+    }
+}
+
+impl ModelInput {
+    /// Orders inputs given by name as the inputs of the model, whose names are `names`. Inputs given in order (without names) are
+    /// returned as is. Data for the `unused` inputs of the model (see [Model::unused_inputs]) may be left out, and is dropped when
+    /// given: by name, or in order alongside the data of all other inputs.
+    pub fn order_inputs(
+        &self,
+        names: &[String],
+        unused: &[(usize, String)],
+    ) -> Result<ModelInput, PfsysError> {
+        if self.input_names.is_empty() {
+            if unused.is_empty() || self.input_data.len() != names.len() + unused.len() {
+                return Ok(self.clone());
+            }
+            let used = |i: &usize| !unused.iter().any(|(u, _)| u == i);
+            let mut ordered = self.clone();
+            ordered.input_data = (0..self.input_data.len())
+                .filter(used)
+                .map(|i| self.input_data[i].clone())
+                .collect();
+            ordered.input_shapes = (0..self.input_shapes.len())
+                .filter(used)
+                .map(|i| self.input_shapes[i].clone())
+                .collect();
+            return Ok(ordered);
+        }
+        let is_unused = |name: &String| unused.iter().any(|(_, u)| u == name);
+        if let Some(unknown) = self
+            .input_names
+            .iter()
+            .find(|n| !names.contains(n) && !is_unused(n))
+        {
+            return Err(PfsysError::UnknownInput(unknown.clone(), names.to_vec()));
+        }
+        let mut ordered = ModelInput {
+            input_data: vec![],
+            input_shapes: vec![],
+            input_names: names.to_vec(),
+            ..self.clone()
+        };
+        for name in names {
+            let idx = self
+                .input_names
+                .iter()
+                .position(|n| n == name)
+                .ok_or_else(|| PfsysError::MissingInput(name.clone()))?;
+            ordered.input_data.push(self.input_data[idx].clone());
+            ordered.input_shapes.push(self.input_shapes[idx].clone());
+        }
+        Ok(ordered)
+    }
+
+    /// Splits a batch of `batch_size` samples into a [ModelInput] per sample. Each entry of `input_shapes` should have a leading
+    /// batch dimension of `batch_size`, with the data of each sample stored consecutively in `input_data` and `output_data`.
+    /// A batch of one sample is returned as is.
+    pub fn split_batch(&self, batch_size: usize) -> Result<Vec<ModelInput>, PfsysError> {
+        if batch_size == 1 {
+            return Ok(vec![self.clone()]);
+        }
+        if batch_size == 0
+            || self
+                .input_shapes
+                .iter()
+                .any(|s| s.first() != Some(&batch_size))
+        {
+            return Err(PfsysError::InvalidBatch(batch_size));
+        }
+        let split = |data: &Vec<Vec<f32>>| {
+            data.iter()
+                .map(|d| {
+                    // outputs can be left empty when they are not being constrained
+                    if d.is_empty() {
+                        return Ok(vec![vec![]; batch_size]);
+                    }
+                    if d.len() % batch_size != 0 {
+                        return Err(PfsysError::InvalidBatch(batch_size));
+                    }
+                    Ok(d.chunks(d.len() / batch_size)
+                        .map(|c| c.to_vec())
+                        .collect_vec())
+                })
+                .collect::<Result<Vec<Vec<Vec<f32>>>, PfsysError>>()
+        };
+        let inputs = split(&self.input_data)?;
+        let outputs = split(&self.output_data)?;
+        Ok((0..batch_size)
+            .map(|sample| ModelInput {
+                input_data: inputs.iter().map(|i| i[sample].clone()).collect(),
+                input_shapes: self.input_shapes.iter().map(|s| s[1..].to_vec()).collect(),
+                input_names: self.input_names.clone(),
+                output_data: outputs.iter().map(|o| o[sample].clone()).collect(),
+                input_key: self.input_key.clone(),
+                input_signature: self.input_signature.clone(),
+                input_merkle_paths: self
+                    .input_merkle_paths
+                    .get(sample)
+                    .cloned()
+                    .into_iter()
+                    .collect(),
+            })
+            .collect())
+    }
+
+    /// Saves the ModelInput to a specified `path` as json.
+    pub fn save(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        let serialized = serde_json::to_string(&self).map_err(Box::<dyn Error>::from)?;
+
+        let mut file = std::fs::File::create(path).map_err(Box::<dyn Error>::from)?;
+        file.write_all(serialized.as_bytes())
+            .map_err(Box::<dyn Error>::from)
+    }
+}
+
+/// The quantized value of a single node in a computational graph.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NodeWitness {
+    /// The operation of the node, as displayed by the `table` command.
+    pub op: String,
+    /// The fixed point scale of the values.
+    pub scale: i32,
+    /// The shape of the values.
+    pub dims: Vec<usize>,
+    /// The (flattened) quantized values.
+    pub values: Vec<i32>,
+}
+
+/// The quantized values of every node in a computational graph, as computed during a forward pass, keyed by node index.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ModelWitness(pub BTreeMap<usize, NodeWitness>);
+
+impl ModelWitness {
+    /// Collects the quantized values computed by [Model::forward_all] into a [ModelWitness].
+    pub fn new(model: &Model, results: &BTreeMap<usize, Tensor<i32>>) -> Self {
+        ModelWitness(
+            results
+                .iter()
+                .map(|(idx, t)| {
+                    let node = model.nodes.filter(*idx);
+                    let witness = NodeWitness {
+                        op: node.opkind.to_string(),
+                        scale: node.out_scale,
+                        dims: t.dims().to_vec(),
+                        values: t.clone().into_iter().collect(),
+                    };
+                    (*idx, witness)
+                })
+                .collect(),
+        )
+    }
+
+    /// Saves the ModelWitness to a specified `path` as json.
+    pub fn save(&self, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+        let serialized = serde_json::to_string(&self).map_err(Box::<dyn Error>::from)?;
+
+        let mut file = std::fs::File::create(path).map_err(Box::<dyn Error>::from)?;
+        file.write_all(serialized.as_bytes())
+            .map_err(Box::<dyn Error>::from)
+    }
 }
 
 /// Defines the proof generated by a model / circuit suitably for serialization/deserialization.  
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Proof {
     /// Public inputs to the model.
     pub public_inputs: Vec<Vec<i32>>,
     /// The generated proof, as a vector of bytes.
+    #[serde(with = "hex_bytes")]
     pub proof: Vec<u8>,
+    /// Hashes of the artifacts the proof was generated with, absent for proofs saved as hex calldata.
+    #[serde(default)]
+    pub hashes: Option<ArtifactHashes>,
+    /// The header of the file the proof was loaded from, absent for proofs created by this process, loaded from hex calldata,
+    /// or saved by versions of ezkl predating headers.
+    #[serde(skip)]
+    pub header: Option<ArtifactHeader>,
 }
 
 impl Proof {
-    /// Saves the Proof to a specified `proof_path`.
-    pub fn save(&self, proof_path: &PathBuf) -> Result<(), Box<dyn Error>> {
-        let serialized = serde_json::to_string(&self).map_err(Box::<dyn Error>::from)?;
+    /// Checks the proof was generated with the artifacts whose hashes are `expected`. Proofs which don't record
+    /// hashes (e.g loaded from hex calldata) pass the check.
+    pub fn check_hashes(&self, expected: &ArtifactHashes) -> Result<(), PfsysError> {
+        if let Some(header) = &self.header {
+            header.check("proof", &expected.circuit)?;
+        }
+        match &self.hashes {
+            Some(hashes) => hashes.check(expected),
+            None => Ok(()),
+        }
+    }
+
+    /// The header the proof is saved with, recording the circuit it was generated for.
+    fn artifact_header(&self) -> ArtifactHeader {
+        let circuit = self.hashes.as_ref().map(|h| h.circuit.clone());
+        ArtifactHeader::new(circuit.unwrap_or_default())
+    }
+
+    /// Saves the Proof to a specified `proof_path` in the given [ProofFormat], prefixed by an [ArtifactHeader] unless saved as
+    /// [ProofFormat::Hex], in which case public inputs are encoded as elements of `F`.
+    pub fn save<F: FieldExt>(
+        &self,
+        proof_path: &PathBuf,
+        format: ProofFormat,
+    ) -> Result<(), Box<dyn Error>> {
+        let serialized = match format {
+            ProofFormat::Json => artifact::to_json(self, &self.artifact_header(), false)?,
+            ProofFormat::Bincode => artifact::to_bincode(self, &self.artifact_header())?,
+            ProofFormat::Hex => format!("0x{}", hex::encode(self.to_calldata::<F>())).into_bytes(),
+        };
 
         let mut file = std::fs::File::create(proof_path).map_err(Box::<dyn Error>::from)?;
-        file.write_all(serialized.as_bytes())
-            .map_err(Box::<dyn Error>::from)
+        file.write_all(&serialized).map_err(Box::<dyn Error>::from)
     }
 
-    /// Load a json serialized proof from the provided path.
-    pub fn load(proof_path: &PathBuf) -> Result<Self, Box<dyn Error>> {
+    /// Load a proof serialized in the given [ProofFormat] from the provided path.
+    /// As hex calldata is a flat encoding, the lengths of each set of public inputs (`num_instances`) are
+    /// required to load a proof saved as [ProofFormat::Hex]. They are ignored for other formats.
+    pub fn load<F: FieldExt>(
+        proof_path: &PathBuf,
+        format: ProofFormat,
+        num_instances: &[usize],
+    ) -> Result<Self, Box<dyn Error>> {
         let mut file = File::open(proof_path).map_err(Box::<dyn Error>::from)?;
-        let mut data = String::new();
-        file.read_to_string(&mut data)
+        let mut data = vec![];
+        file.read_to_end(&mut data)
             .map_err(Box::<dyn Error>::from)?;
-        serde_json::from_str(&data).map_err(Box::<dyn Error>::from)
+        let (header, proof) = match format {
+            ProofFormat::Json => artifact::from_json::<Proof>(&data, "proof")?,
+            ProofFormat::Bincode => artifact::from_bincode::<Proof>(&data, "proof")?,
+            ProofFormat::Hex => {
+                let data = String::from_utf8(data).map_err(Box::<dyn Error>::from)?;
+                let calldata = hex::decode(data.trim().trim_start_matches("0x"))
+                    .map_err(Box::<dyn Error>::from)?;
+                return Self::from_calldata::<F>(&calldata, num_instances);
+            }
+        };
+        Ok(Proof { header, ..proof })
+    }
+
+    /// Encodes the proof as calldata: each public input as a 32 byte big-endian element of `F`, followed by the proof bytes.
+    pub fn to_calldata<F: FieldExt>(&self) -> Vec<u8> {
+        let mut calldata = vec![];
+        for i in self.public_inputs.iter().flatten() {
+            let mut repr = i32_to_felt::<F>(*i).to_repr().as_ref().to_vec();
+            repr.reverse();
+            calldata.extend(repr);
+        }
+        calldata.extend(self.proof.iter());
+        calldata
+    }
+
+    /// Decodes a proof from `calldata` (as produced by [Proof::to_calldata]), where `num_instances` holds the number
+    /// of public inputs in each set of public inputs.
+    pub fn from_calldata<F: FieldExt>(
+        calldata: &[u8],
+        num_instances: &[usize],
+    ) -> Result<Self, Box<dyn Error>> {
+        let felt_len = F::Repr::default().as_ref().len();
+        let total = num_instances.iter().sum::<usize>() * felt_len;
+        if calldata.len() < total {
+            return Err(Box::new(PfsysError::InvalidCalldata));
+        }
+        let mut felts = calldata[..total].chunks(felt_len).map(|c| {
+            let mut repr = F::Repr::default();
+            repr.as_mut().copy_from_slice(c);
+            repr.as_mut().reverse();
+            Option::<F>::from(F::from_repr(repr))
+                .map(|f| felt_to_i128(f) as i32)
+                .ok_or(PfsysError::InvalidCalldata)
+        });
+        let mut public_inputs = vec![];
+        for n in num_instances {
+            public_inputs.push(
+                (0..*n)
+                    .map(|_| felts.next().unwrap())
+                    .collect::<Result<Vec<i32>, _>>()?,
+            );
+        }
+        Ok(Proof {
+            public_inputs,
+            proof: calldata[total..].to_vec(),
+            hashes: None,
+            header: None,
+        })
+    }
+}
+
+/// Serializes bytes as a 0x prefixed hex string for human readable formats (e.g json) and as raw bytes otherwise (e.g bincode).
+pub(crate) mod hex_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum HexOrBytes {
+        Hex(String),
+        Bytes(Vec<u8>),
+    }
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&format!("0x{}", hex::encode(bytes)))
+        } else {
+            serializer.serialize_bytes(bytes)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        if deserializer.is_human_readable() {
+            // older proofs store the proof bytes as an array of integers
+            match HexOrBytes::deserialize(deserializer)? {
+                HexOrBytes::Hex(s) => {
+                    hex::decode(s.trim_start_matches("0x")).map_err(D::Error::custom)
+                }
+                HexOrBytes::Bytes(b) => Ok(b),
+            }
+        } else {
+            Vec::<u8>::deserialize(deserializer)
+        }
     }
 }
 
@@ -75,28 +678,112 @@ type CircuitInputs<F> = (ModelCircuit<F>, Vec<Tensor<i32>>);
 /// Initialize the model circuit and quantize the provided float inputs from the provided `ModelInput`.
 pub fn prepare_circuit_and_public_input<F: FieldExt>(
     data: &ModelInput,
-    args: &Cli,
+    args: &RunArgs,
 ) -> Result<CircuitInputs<F>, Box<dyn Error>> {
-    let model = Model::from_ezkl_conf(args.clone())?;
+    let model = Model::shared_from_run_args(args)?;
+    let data = &data.order_inputs(&model.input_names(), &model.unused_inputs)?;
+    if args.settings.chunk.is_some() {
+        return prepare_chunk_circuit_and_public_input(data, args, &model);
+    }
+    let in_scales = model.get_input_scales();
     let out_scales = model.get_output_scales();
-    let circuit = prepare_circuit(data, args)?;
 
     // quantize the supplied data using the provided scale.
     // the ordering here is important, we want the inputs to come before the outputs
-    // as they are configured in that order as Column<Instances>
+    // as they are configured in that order as Column<Instances>, sample after sample
+    let mut inputs = vec![];
     let mut public_inputs = vec![];
-    if model.visibility.input.is_public() {
-        for v in data.input_data.iter() {
-            let t = vector_to_quantized(v, &Vec::from([v.len()]), 0.0, model.scale)?;
-            public_inputs.push(t);
+    // hashed inputs and outputs are replaced by the limbs of their digest
+    let digest = |t: &Tensor<i32>| Tensor::from(hash_to_limbs::<F>(t.iter().copied()).into_iter());
+    let output_visibility = model.visibility.outputs(model.num_outputs());
+    let committed = (0..model.num_inputs()).any(|i| model.visibility.input_at(i).is_committed());
+    let input_key = match committed {
+        true => Some(input_key(data)?),
+        false => None,
+    };
+    for sample in data.split_batch(args.settings.batch_size)? {
+        check_input_shapes(&sample, &model)?;
+        let sample_inputs = prepare_circuit::<F>(&sample, &in_scales)?.inputs;
+        for (idx, (v, input)) in sample.input_data.iter().zip(&sample_inputs).enumerate() {
+            match model.visibility.input_at(idx) {
+                Visibility::Public => {
+                    let t = vector_to_quantized(v, &Vec::from([v.len()]), 0.0, in_scales[idx])?;
+                    public_inputs.push(t);
+                }
+                Visibility::Hashed => public_inputs.push(digest(input)),
+                // committed inputs are hashed with the key ahead of them
+                Visibility::Committed => {
+                    let key = input_key
+                        .as_ref()
+                        .ok_or(GraphError::MissingInputKey(KEY_LIMBS))?;
+                    let limbs = hash_to_limbs::<F>(key.iter().chain(input.iter()).copied());
+                    public_inputs.push(Tensor::from(limbs.into_iter()));
+                }
+                Visibility::Private => {}
+            }
         }
-    }
-    if model.visibility.output.is_public() {
-        for (idx, v) in data.output_data.iter().enumerate() {
-            let t = vector_to_quantized(v, &Vec::from([v.len()]), 0.0, out_scales[idx])?;
-            public_inputs.push(t);
+        // hashed outputs (and outputs missing from the data) are those computed by the model, as are those claims are about
+        let computed = match output_visibility.iter().any(|v| v.is_hashed())
+            || sample.output_data.len() < output_visibility.len()
+            || !model.claims.is_empty()
+        {
+            true => model.forward(&sample_inputs)?,
+            false => vec![],
+        };
+        model.check_claims(&computed)?;
+        for (idx, visibility) in output_visibility.iter().enumerate() {
+            match visibility {
+                Visibility::Public => match sample.output_data.get(idx) {
+                    Some(v) => {
+                        let t =
+                            vector_to_quantized(v, &Vec::from([v.len()]), 0.0, out_scales[idx])?;
+                        public_inputs.push(t);
+                    }
+                    // data loaded from numpy arrays doesn't hold outputs, which are then those computed by the model
+                    None => {
+                        warn!(
+                            "the data holds no output {}, using that computed by the model",
+                            idx
+                        );
+                        public_inputs.push(computed[idx].clone());
+                    }
+                },
+                // the digest is of the outputs as computed by the circuit, which the verifier needs to be passed
+                Visibility::Hashed => {
+                    info!("hashed output {}: {:?}", idx, computed[idx]);
+                    public_inputs.push(digest(&computed[idx]));
+                }
+                _ => {}
+            }
         }
+        inputs.extend(sample_inputs);
+    }
+    if model.commit_params {
+        public_inputs.push(Tensor::from(model.params_commitment::<F>()?.into_iter()));
     }
+    let input_merkle_paths = match model.merkle_depth {
+        Some(depth) => {
+            let (paths, root) = inclusion(data, &model, depth)?;
+            public_inputs.push(root);
+            paths
+        }
+        None => vec![],
+    };
+    let input_signature = match model.attested_inputs {
+        true => {
+            let (signature, public_key) = attestation(data, &model)?;
+            public_inputs.extend(public_key);
+            Some(signature)
+        }
+        false => None,
+    };
+    let circuit = ModelCircuit::<F> {
+        inputs,
+        input_key,
+        input_signature,
+        input_merkle_paths,
+        _marker: PhantomData,
+    };
     info!(
         "public inputs lengths: {:?}",
         public_inputs
@@ -109,30 +796,261 @@ pub fn prepare_circuit_and_public_input<F: FieldExt>(
     Ok((circuit, public_inputs))
 }
 
-/// Initialize the model circuit
+/// Checks the inputs of a sample of `data` have the shapes of the inputs of `model`, ignoring leading dimensions of size 1.
+fn check_input_shapes(data: &ModelInput, model: &Model) -> Result<(), PfsysError> {
+    let squeeze = |shape: &[usize]| shape.iter().copied().skip_while(|d| *d == 1).collect_vec();
+    for (idx, (shape, expected)) in data
+        .input_shapes
+        .iter()
+        .zip(model.input_shapes())
+        .enumerate()
+    {
+        if squeeze(shape) != squeeze(&expected) {
+            return Err(PfsysError::InputShape(idx, shape.clone(), expected));
+        }
+    }
+    Ok(())
+}
+
+/// Initialize the circuit of a chunk of a model (see [Model::chunk]). The activations flowing into and out of the chunk are computed
+/// by running a forward pass over the full model on the inputs in `data`, and are both public inputs to the chunk's circuit.
+fn prepare_chunk_circuit_and_public_input<F: FieldExt>(
+    data: &ModelInput,
+    args: &RunArgs,
+    chunk: &Model,
+) -> Result<CircuitInputs<F>, Box<dyn Error>> {
+    let mut full = args.clone();
+    full.settings.chunk = None;
+    // the model already loaded is that of the chunk
+    full.prepared = None;
+    let model = Model::from_run_args(&full)?;
+    let results = data
+        .split_batch(args.settings.batch_size)?
+        .iter()
+        .map(|sample| {
+            let inputs = prepare_circuit::<F>(sample, &model.get_input_scales())?.inputs;
+            model.forward_all(&inputs)
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()?;
+
+    let activation = |sample: usize, n: usize| {
+        results[sample]
+            .get(&n)
+            .cloned()
+            .ok_or(GraphError::MissingNode(n))
+    };
+    let chunk_inputs = (0..args.settings.batch_size)
+        .flat_map(|sample| chunk.inputs.iter().map(move |o| (sample, o.node)))
+        .map(|(sample, n)| activation(sample, n))
+        .collect::<Result<Vec<Tensor<i32>>, GraphError>>()?;
+    let mut public_inputs = chunk
+        .instance_nodes()
+        .into_iter()
+        .map(|(sample, n)| {
+            let mut t = activation(sample, n)?;
+            t.flatten();
+            Ok(t)
+        })
+        .collect::<Result<Vec<Tensor<i32>>, GraphError>>()?;
+    if chunk.commit_params {
+        public_inputs.push(Tensor::from(chunk.params_commitment::<F>()?.into_iter()));
+    }
+    // the inputs of the first chunk are those of the model
+    let input_merkle_paths = match chunk.merkle_depth {
+        Some(depth) => {
+            let (paths, root) = inclusion(data, &model, depth)?;
+            public_inputs.push(root);
+            paths
+        }
+        None => vec![],
+    };
+    let input_signature = match chunk.attested_inputs {
+        true => {
+            let (signature, public_key) = attestation(data, &model)?;
+            public_inputs.extend(public_key);
+            Some(signature)
+        }
+        false => None,
+    };
+    trace!("{:?}", public_inputs);
+
+    Ok((
+        ModelCircuit::<F> {
+            inputs: chunk_inputs,
+            input_key: None,
+            input_signature,
+            input_merkle_paths,
+            _marker: PhantomData,
+        },
+        public_inputs,
+    ))
+}
+
+/// Initialize the model circuit, quantizing each input using the corresponding fixed point scale in `in_scales`.
 pub fn prepare_circuit<F: FieldExt>(
     data: &ModelInput,
-    args: &Cli,
+    in_scales: &[i32],
 ) -> Result<ModelCircuit<F>, Box<dyn Error>> {
-    // quantize the supplied data using the provided scale.
+    // quantize the supplied data using the provided scales.
     let mut inputs: Vec<Tensor<i32>> = vec![];
-    for (input, shape) in data.input_data.iter().zip(data.input_shapes.clone()) {
-        let t = vector_to_quantized(input, &shape, 0.0, args.scale)?;
+    for ((input, shape), scale) in data
+        .input_data
+        .iter()
+        .zip(data.input_shapes.clone())
+        .zip(in_scales)
+    {
+        let t = vector_to_quantized(input, &shape, 0.0, *scale)?;
         inputs.push(t);
     }
+    let input_key = match &data.input_key {
+        Some(_) => Some(input_key(data)?),
+        None => None,
+    };
+
+    Ok(ModelCircuit::<F> {
+        inputs,
+        input_key,
+        input_signature: None,
+        input_merkle_paths: vec![],
+        _marker: PhantomData,
+    })
+}
+
+/// The leaves of the inputs of each sample in `data` when proving their inclusion in a Merkle tree: the Poseidon hash of the
+/// inputs of the sample, as quantized for `model`.
+pub fn input_leaves<F: FieldExt>(
+    data: &ModelInput,
+    model: &Model,
+) -> Result<Vec<F>, Box<dyn Error>> {
+    data.split_batch(model.batch_size)?
+        .iter()
+        .map(|sample| {
+            let inputs = prepare_circuit::<F>(sample, &model.get_input_scales())?.inputs;
+            let message = inputs
+                .iter()
+                .flat_map(|input| input.iter().map(|x| i32_to_felt::<F>(*x)))
+                .collect::<Vec<F>>();
+            Ok(poseidon_hash(&message))
+        })
+        .collect()
+}
+
+/// Checks the Merkle paths in `data` lead the inputs of every sample to a common root, returning the paths alongside the limbs of the
+/// root as public inputs.
+fn inclusion<F: FieldExt>(
+    data: &ModelInput,
+    model: &Model,
+    depth: usize,
+) -> Result<(Vec<MerklePath<F>>, Tensor<i32>), Box<dyn Error>> {
+    let leaves = input_leaves::<F>(data, model)?;
+    let paths = data
+        .input_merkle_paths
+        .iter()
+        .map(|p| p.to_path::<F>())
+        .collect::<Vec<MerklePath<F>>>();
+    if paths.len() != leaves.len() || paths.iter().any(|p| p.siblings.len() != depth) {
+        return Err(Box::new(GraphError::InvalidMerklePath(depth)));
+    }
+    let roots = paths
+        .iter()
+        .zip(leaves)
+        .map(|(path, leaf)| path.root(leaf))
+        .collect::<Vec<F>>();
+    if roots.iter().any(|r| *r != roots[0]) {
+        return Err(Box::new(GraphError::InvalidMerklePath(depth)));
+    }
+    // there is at least one sample
+    Ok((paths, Tensor::from(digest_to_limbs(roots[0]).into_iter())))
+}
+
+/// The message signed when attesting the inputs in `data`: the Poseidon hash of the inputs of every sample in turn, as quantized for `model`.
+pub fn input_message<F: FieldExt>(data: &ModelInput, model: &Model) -> Result<F, Box<dyn Error>> {
+    let mut message = vec![];
+    for sample in data.split_batch(model.batch_size)? {
+        for input in prepare_circuit::<F>(&sample, &model.get_input_scales())?.inputs {
+            message.extend(input.iter().map(|x| i32_to_felt::<F>(*x)));
+        }
+    }
+    Ok(poseidon_hash(&message))
+}
+
+/// Checks the signature over the inputs in `data`, returning it alongside the limbs of the signer's public key as public inputs.
+fn attestation<F: FieldExt>(
+    data: &ModelInput,
+    model: &Model,
+) -> Result<(Signature<F>, Vec<Tensor<i32>>), Box<dyn Error>> {
+    let signature = data
+        .input_signature
+        .as_ref()
+        .ok_or(GraphError::InvalidInputSignature)?
+        .to_signature::<F>()?;
+    if !verify(&signature, input_message(data, model)?) {
+        return Err(Box::new(GraphError::InvalidInputSignature));
+    }
+    let (x, y) = signature.public_key;
+    let public_key = vec![
+        Tensor::from(digest_to_limbs(x).into_iter()),
+        Tensor::from(digest_to_limbs(y).into_iter()),
+    ];
+    Ok((signature, public_key))
+}
+
+/// Returns the key in `data` the inputs are committed to with, checking it holds [KEY_LIMBS] values.
+fn input_key(data: &ModelInput) -> Result<Tensor<i32>, GraphError> {
+    match &data.input_key {
+        Some(key) if key.len() == KEY_LIMBS => Ok(Tensor::from(key.iter().copied())),
+        _ => Err(GraphError::MissingInputKey(KEY_LIMBS)),
+    }
+}
+
+/// Initialize the model circuit with zero-valued inputs of the right shape, for generating keys when no data is at hand.
+pub fn prepare_dummy_circuit<F: FieldExt>(
+    model: &Model,
+) -> Result<ModelCircuit<F>, Box<dyn Error>> {
+    let inputs = model
+        .input_shapes()
+        .repeat(model.batch_size)
+        .iter()
+        .map(|shape| Tensor::new(None, shape))
+        .collect::<Result<Vec<Tensor<i32>>, _>>()?;
+    let committed = (0..model.num_inputs()).any(|i| model.visibility.input_at(i).is_committed());
+    let input_key = match committed {
+        true => Some(Tensor::new(None, &[KEY_LIMBS])?),
+        false => None,
+    };
 
     Ok(ModelCircuit::<F> {
         inputs,
+        input_key,
+        // the signature and merkle paths are witnesses, unknown when generating keys
+        input_signature: None,
+        input_merkle_paths: vec![],
         _marker: PhantomData,
     })
 }
 
 /// Deserializes the required inputs to a model at path `datapath` to a [ModelInput] struct.
 pub fn prepare_data(datapath: String) -> Result<ModelInput, Box<dyn Error>> {
-    let mut file = File::open(data_path(datapath)).map_err(Box::<dyn Error>::from)?;
+    let path = data_path(datapath);
+    // numpy arrays hold the inputs only, with their shapes
+    if path.is_dir() || path.extension().map_or(false, |e| e == "npy" || e == "npz") {
+        return npy::load_inputs(&path).map_err(Box::<dyn Error>::from);
+    }
+    if path.extension().map_or(false, |e| e == "csv") {
+        return load_csv(&path).map_err(Box::<dyn Error>::from);
+    }
+    if image::is_image(&path) {
+        return image::load_inputs(&path).map_err(Box::<dyn Error>::from);
+    }
+    let mut file = File::open(&path).map_err(Box::<dyn Error>::from)?;
     let mut data = String::new();
     file.read_to_string(&mut data)
         .map_err(Box::<dyn Error>::from)?;
+    // a preprocessing spec listing the images to load, relative to the spec
+    if let Some(spec) = image::parse_spec(&data, &path.display().to_string())? {
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        return image::load_spec_inputs(&spec, dir).map_err(Box::<dyn Error>::from);
+    }
     serde_json::from_str(&data).map_err(Box::<dyn Error>::from)
 }
 
@@ -158,12 +1076,14 @@ where
     Ok(pk)
 }
 
-/// a wrapper around halo2's create_proof
+/// a wrapper around halo2's create_proof, writing the proof to a transcript of type `TW`
 pub fn create_proof_model<
     'params,
     Scheme: CommitmentScheme,
     F: FieldExt + TensorType,
     P: Prover<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    TW: TranscriptWriterBuffer<Vec<u8>, Scheme::Curve, E>,
 >(
     circuit: &ModelCircuit<F>,
     public_inputs: &[Tensor<i32>],
@@ -174,7 +1094,7 @@ where
     ModelCircuit<F>: Circuit<Scheme::Scalar>,
 {
     let now = Instant::now();
-    let mut transcript = Blake2bWrite::<_, Scheme::Curve, Challenge255<_>>::init(vec![]);
+    let mut transcript = TW::init(vec![]);
     let mut rng = OsRng;
     let pi_inner: Vec<Vec<Scheme::Scalar>> = public_inputs
         .iter()
@@ -193,7 +1113,7 @@ where
 
     let dims = circuit.inputs.iter().map(|i| i.dims().to_vec()).collect();
 
-    create_proof::<Scheme, P, _, _, _, _>(
+    create_proof::<Scheme, P, _, _, TW, _>(
         params,
         pk,
         &[circuit.clone()],
@@ -210,18 +1130,22 @@ where
             .map(|i| i.clone().into_iter().collect())
             .collect(),
         proof,
+        hashes: None,
+        header: None,
     };
 
     Ok((checkable_pf, dims))
 }
 
-/// A wrapper around halo2's verify_proof
+/// A wrapper around halo2's verify_proof, reading the proof from a transcript of type `TR`
 pub fn verify_proof_model<
     'params,
     F: FieldExt,
     V: Verifier<'params, Scheme>,
     Scheme: CommitmentScheme,
     Strategy: VerificationStrategy<'params, Scheme, V>,
+    E: EncodedChallenge<Scheme::Curve>,
+    TR: TranscriptReadBuffer<Cursor<Vec<u8>>, Scheme::Curve, E>,
 >(
     proof: Proof,
     params: &'params Scheme::ParamsVerifier,
@@ -248,14 +1172,200 @@ where
     trace!("instances {:?}", instances);
 
     let now = Instant::now();
-    let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof.proof[..]);
+    let mut transcript = TR::init(Cursor::new(proof.proof));
     info!("verify took {}", now.elapsed().as_secs());
-    verify_proof::<Scheme, V, _, _, _>(params, vk, strategy, instances, &mut transcript)
+    verify_proof::<Scheme, V, _, TR, _>(params, vk, strategy, instances, &mut transcript)
 }
 
-/// Loads a [VerifyingKey] at `path`.
+/// Verifies KZG `proofs` of the same circuit (sharing the verifying key `vk`) as a batch, reading each from a transcript of type
+/// `TR`. Rather than checking a pairing per proof, the MSMs of the proofs are scaled by random challenges and accumulated, such
+/// that a single pairing check is made for the whole batch. Returns whether each proof was accepted: when the batch is rejected,
+/// the proofs are verified one by one to find out which of them are invalid.
+pub fn verify_batch<'params, V, E, TR>(
+    proofs: &[Proof],
+    params: &'params ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+) -> Vec<bool>
+where
+    V: Verifier<'params, KZGCommitmentScheme<Bn256>>,
+    E: EncodedChallenge<G1Affine>,
+    TR: TranscriptReadBuffer<Cursor<Vec<u8>>, G1Affine, E>,
+    AccumulatorStrategy<'params, Bn256>: VerificationStrategy<
+        'params,
+        KZGCommitmentScheme<Bn256>,
+        V,
+        Output = AccumulatorStrategy<'params, Bn256>,
+    >,
+    SingleStrategy<'params, Bn256>: VerificationStrategy<'params, KZGCommitmentScheme<Bn256>, V>,
+{
+    let now = Instant::now();
+    let batch = proofs
+        .iter()
+        .try_fold(AccumulatorStrategy::new(params), |strategy, proof| {
+            verify_proof_model::<Fr, V, _, _, _, TR>(proof.clone(), params, vk, strategy)
+        });
+    let accepted = match batch {
+        Ok(strategy) => <AccumulatorStrategy<'params, Bn256> as VerificationStrategy<
+            'params,
+            KZGCommitmentScheme<Bn256>,
+            V,
+        >>::finalize(strategy),
+        Err(_) => false,
+    };
+    info!(
+        "batch verification of {} proofs took {}ms",
+        proofs.len(),
+        now.elapsed().as_millis()
+    );
+    if accepted {
+        return vec![true; proofs.len()];
+    }
+    warn!("the batch was rejected, verifying its proofs one by one");
+    proofs
+        .iter()
+        .map(|proof| {
+            verify_proof_model::<Fr, V, _, _, _, TR>(
+                proof.clone(),
+                params,
+                vk,
+                SingleStrategy::new(params),
+            )
+            .is_ok()
+        })
+        .collect()
+}
+
+/// Computes a fingerprint of the circuit defined by the model and the circuit settings in `args`.
+/// This is stored alongside generated keys such that keys generated for a different circuit can be detected.
+pub fn circuit_fingerprint(args: &RunArgs) -> Result<Fingerprint, Box<dyn Error>> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&args.model.read().map_err(Box::<dyn Error>::from)?);
+    if let Some(weights) = &args.weights {
+        hasher.update(&std::fs::read(weights)?);
+    }
+    // settings are hashed through their json encoding, which unlike std's Hash is stable across platforms and compiler versions
+    let settings = &args.settings;
+    let encoded = serde_json::to_vec(&(
+        settings.tolerance,
+        settings.scale,
+        settings.bits,
+        settings.logrows,
+        // serde only encodes tuples of up to 16 elements, so the visibility settings are grouped
+        (
+            settings.public_inputs,
+            settings.public_outputs,
+            settings.public_params,
+            settings.hashed_inputs,
+            settings.hashed_outputs,
+            settings.committed_inputs,
+            settings.attested_inputs,
+            settings.merkle_depth,
+            settings.commit_params,
+            &settings.input_visibility,
+            &settings.output_visibility,
+            settings.range_check_inputs,
+            &settings.claims,
+            &settings.tolerance_norms,
+        ),
+        settings.max_rotations,
+        &settings.layer_scales,
+        &settings.cut_points,
+        settings.chunk,
+        settings.batch_size,
+        settings.optimize_graph,
+        &settings.input_shapes,
+        &settings.input_nodes,
+        &settings.output_nodes,
+        settings.dot_chunk,
+        settings.sparse_params,
+    ))
+    .map_err(Box::<dyn Error>::from)?;
+    hasher.update(&encoded);
+    Ok(*hasher.finalize().as_bytes())
+}
+
+/// Writes the header of a key file: magic bytes, the key format version, the version of ezkl, and the `fingerprint` of the
+/// circuit.
+fn write_key_header<W: Write>(writer: &mut W, fingerprint: Fingerprint) -> Result<(), io::Error> {
+    writer.write_all(artifact::MAGIC)?;
+    writer.write_all(&KEY_FORMAT_VERSION.to_le_bytes())?;
+    artifact::write_str(writer, artifact::VERSION)?;
+    writer.write_all(&fingerprint)
+}
+
+/// Reads the header of a key file, checking it was generated with the current key format for the circuit with `fingerprint`.
+fn read_key_header<R: Read>(
+    reader: &mut R,
+    fingerprint: Fingerprint,
+) -> Result<(), Box<dyn Error>> {
+    let mut magic = [0u8; 4];
+    reader
+        .read_exact(&mut magic)
+        .map_err(|_| PfsysError::InvalidKeyHeader)?;
+    if &magic != artifact::MAGIC {
+        return Err(Box::new(PfsysError::InvalidKeyHeader));
+    }
+    let mut version = [0u8; 4];
+    reader
+        .read_exact(&mut version)
+        .map_err(|_| PfsysError::InvalidKeyHeader)?;
+    let version = u32::from_le_bytes(version);
+    if version != KEY_FORMAT_VERSION {
+        return Err(Box::new(PfsysError::KeyVersion(version)));
+    }
+    let ezkl_version = artifact::read_str(reader).map_err(|_| PfsysError::InvalidKeyHeader)?;
+    let mut key_fingerprint = [0u8; 32];
+    reader
+        .read_exact(&mut key_fingerprint)
+        .map_err(|_| PfsysError::InvalidKeyHeader)?;
+    if key_fingerprint != fingerprint {
+        let header = ArtifactHeader {
+            version: ezkl_version,
+            ..ArtifactHeader::new(hex::encode(key_fingerprint))
+        };
+        header.check("key", &hex::encode(fingerprint))?;
+        return Err(Box::new(PfsysError::StaleKey));
+    }
+    Ok(())
+}
+
+/// Loads a [ProvingKey] at `path`, checking it was generated for the circuit with `fingerprint`.
+pub fn load_pk<Scheme: CommitmentScheme, F: FieldExt + TensorType>(
+    path: PathBuf,
+    fingerprint: Fingerprint,
+) -> Result<ProvingKey<Scheme::Curve>, Box<dyn Error>>
+where
+    ModelCircuit<F>: Circuit<Scheme::Scalar>,
+    Scheme::Curve: SerdeObject + CurveAffine,
+    Scheme::Scalar: PrimeField + SerdeObject,
+{
+    info!("loading proving key from {:?}", path);
+    let mmap = mmap_file(&path).map_err(Box::<dyn Error>::from)?;
+    read_pk::<Scheme, F>(&mmap[..], fingerprint)
+}
+
+/// Reads a [ProvingKey] saved by [save_pk] from `bytes`, checking it was generated for the circuit with `fingerprint`.
+pub fn read_pk<Scheme: CommitmentScheme, F: FieldExt + TensorType>(
+    mut bytes: &[u8],
+    fingerprint: Fingerprint,
+) -> Result<ProvingKey<Scheme::Curve>, Box<dyn Error>>
+where
+    ModelCircuit<F>: Circuit<Scheme::Scalar>,
+    Scheme::Curve: SerdeObject + CurveAffine,
+    Scheme::Scalar: PrimeField + SerdeObject,
+{
+    read_key_header(&mut bytes, fingerprint)?;
+    ProvingKey::<Scheme::Curve>::read::<_, ModelCircuit<F>>(
+        &mut bytes,
+        halo2_proofs::SerdeFormat::Processed,
+    )
+    .map_err(Box::<dyn Error>::from)
+}
+
+/// Loads a [VerifyingKey] at `path`, checking it was generated for the circuit with `fingerprint`.
 pub fn load_vk<Scheme: CommitmentScheme, F: FieldExt + TensorType>(
     path: PathBuf,
+    fingerprint: Fingerprint,
 ) -> Result<VerifyingKey<Scheme::Curve>, Box<dyn Error>>
 where
     ModelCircuit<F>: Circuit<Scheme::Scalar>,
@@ -263,10 +1373,23 @@ where
     Scheme::Scalar: PrimeField + SerdeObject,
 {
     info!("loading verification key from {:?}", path);
-    let f = File::open(path).map_err(Box::<dyn Error>::from)?;
-    let mut reader = BufReader::new(f);
+    let mmap = mmap_file(&path).map_err(Box::<dyn Error>::from)?;
+    read_vk::<Scheme, F>(&mmap[..], fingerprint)
+}
+
+/// Reads a [VerifyingKey] saved by [save_vk] from `bytes`, checking it was generated for the circuit with `fingerprint`.
+pub fn read_vk<Scheme: CommitmentScheme, F: FieldExt + TensorType>(
+    mut bytes: &[u8],
+    fingerprint: Fingerprint,
+) -> Result<VerifyingKey<Scheme::Curve>, Box<dyn Error>>
+where
+    ModelCircuit<F>: Circuit<Scheme::Scalar>,
+    Scheme::Curve: SerdeObject + CurveAffine,
+    Scheme::Scalar: PrimeField + SerdeObject,
+{
+    read_key_header(&mut bytes, fingerprint)?;
     VerifyingKey::<Scheme::Curve>::read::<_, ModelCircuit<F>>(
-        &mut reader,
+        &mut bytes,
         halo2_proofs::SerdeFormat::Processed,
     )
     .map_err(Box::<dyn Error>::from)
@@ -277,15 +1400,47 @@ pub fn load_params<Scheme: CommitmentScheme>(
     path: PathBuf,
 ) -> Result<Scheme::ParamsVerifier, Box<dyn Error>> {
     info!("loading params from {:?}", path);
-    let f = File::open(path).map_err(Box::<dyn Error>::from)?;
-    let mut reader = BufReader::new(f);
-    Params::<'_, Scheme::Curve>::read(&mut reader).map_err(Box::<dyn Error>::from)
+    let mmap = mmap_file(&path).map_err(Box::<dyn Error>::from)?;
+    Params::<'_, Scheme::Curve>::read(&mut &mmap[..]).map_err(Box::<dyn Error>::from)
 }
 
-/// Saves a [VerifyingKey] to `path`.
+/// Saves a [ProvingKey] to `path`, tagged with the `fingerprint` of the circuit it was generated for.
+pub fn save_pk<Scheme: CommitmentScheme>(
+    path: &PathBuf,
+    pk: &ProvingKey<Scheme::Curve>,
+    fingerprint: Fingerprint,
+) -> Result<(), io::Error>
+where
+    Scheme::Curve: SerdeObject + CurveAffine,
+    Scheme::Scalar: PrimeField + SerdeObject,
+{
+    info!("saving proving key 💾");
+    let f = File::create(path)?;
+    let mut writer = BufWriter::new(f);
+    write_pk::<Scheme, _>(&mut writer, pk, fingerprint)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes a [ProvingKey] to `writer` as [save_pk] does, e.g. to hold it in memory rather than in a file.
+pub fn write_pk<Scheme: CommitmentScheme, W: Write>(
+    writer: &mut W,
+    pk: &ProvingKey<Scheme::Curve>,
+    fingerprint: Fingerprint,
+) -> Result<(), io::Error>
+where
+    Scheme::Curve: SerdeObject + CurveAffine,
+    Scheme::Scalar: PrimeField + SerdeObject,
+{
+    write_key_header(writer, fingerprint)?;
+    pk.write(writer, halo2_proofs::SerdeFormat::Processed)
+}
+
+/// Saves a [VerifyingKey] to `path`, tagged with the `fingerprint` of the circuit it was generated for.
 pub fn save_vk<Scheme: CommitmentScheme>(
     path: &PathBuf,
     vk: &VerifyingKey<Scheme::Curve>,
+    fingerprint: Fingerprint,
 ) -> Result<(), io::Error>
 where
     Scheme::Curve: SerdeObject + CurveAffine,
@@ -294,11 +1449,25 @@ where
     info!("saving verification key 💾");
     let f = File::create(path)?;
     let mut writer = BufWriter::new(f);
-    vk.write(&mut writer, halo2_proofs::SerdeFormat::Processed)?;
+    write_vk::<Scheme, _>(&mut writer, vk, fingerprint)?;
     writer.flush()?;
     Ok(())
 }
 
+/// Writes a [VerifyingKey] to `writer` as [save_vk] does, e.g. to hold it in memory rather than in a file.
+pub fn write_vk<Scheme: CommitmentScheme, W: Write>(
+    writer: &mut W,
+    vk: &VerifyingKey<Scheme::Curve>,
+    fingerprint: Fingerprint,
+) -> Result<(), io::Error>
+where
+    Scheme::Curve: SerdeObject + CurveAffine,
+    Scheme::Scalar: PrimeField + SerdeObject,
+{
+    write_key_header(writer, fingerprint)?;
+    vk.write(writer, halo2_proofs::SerdeFormat::Processed)
+}
+
 /// Saves [CommitmentScheme] parameters to `path`.
 pub fn save_params<Scheme: CommitmentScheme>(
     path: &PathBuf,
@@ -311,3 +1480,200 @@ pub fn save_params<Scheme: CommitmentScheme>(
     writer.flush()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2curves::bn256::Fr;
+
+    #[test]
+    fn proof_calldata_roundtrip() {
+        let proof = Proof {
+            public_inputs: vec![vec![1, -2, 3], vec![-65537]],
+            proof: vec![0, 1, 2, 255],
+            hashes: None,
+            header: None,
+        };
+        let calldata = proof.to_calldata::<Fr>();
+        assert_eq!(calldata.len(), 4 * 32 + 4);
+        let decoded = Proof::from_calldata::<Fr>(&calldata, &[3, 1]).unwrap();
+        assert_eq!(decoded.public_inputs, proof.public_inputs);
+        assert_eq!(decoded.proof, proof.proof);
+        assert!(Proof::from_calldata::<Fr>(&calldata, &[3, 2]).is_err());
+    }
+
+    #[test]
+    fn batch_is_split_per_sample() {
+        let data = ModelInput {
+            input_data: vec![vec![1.0, 2.0, 3.0, 4.0]],
+            input_shapes: vec![vec![2, 2]],
+            input_names: vec![],
+            output_data: vec![vec![5.0, 6.0]],
+            input_key: Some(vec![7; KEY_LIMBS]),
+            input_signature: None,
+            input_merkle_paths: vec![],
+        };
+        let samples = data.split_batch(2).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1].input_data, vec![vec![3.0, 4.0]]);
+        assert_eq!(samples[1].input_shapes, vec![vec![2]]);
+        assert_eq!(samples[1].output_data, vec![vec![6.0]]);
+        assert_eq!(samples[1].input_key, data.input_key);
+        assert_eq!(
+            data.split_batch(1).unwrap()[0].input_shapes,
+            vec![vec![2, 2]]
+        );
+        assert!(data.split_batch(3).is_err());
+    }
+
+    #[test]
+    fn inputs_are_given_by_name() {
+        let data: ModelInput = serde_json::from_str(
+            r#"{"input_data": {"b": [[1, 2], [3, 4]], "a": 5.0}, "output_data": []}"#,
+        )
+        .unwrap();
+        let names = vec!["b".to_string(), "a".to_string()];
+        let ordered = data.order_inputs(&names, &[]).unwrap();
+        assert_eq!(
+            ordered.input_data,
+            vec![vec![1.0, 2.0, 3.0, 4.0], vec![5.0]]
+        );
+        assert_eq!(ordered.input_shapes, vec![vec![2, 2], vec![]]);
+        assert!(matches!(
+            data.order_inputs(&["b".to_string()], &[]),
+            Err(PfsysError::UnknownInput(..))
+        ));
+        // inputs the model doesn't use are dropped
+        let unused = vec![(1, "a".to_string())];
+        assert_eq!(
+            data.order_inputs(&["b".to_string()], &unused)
+                .unwrap()
+                .input_data,
+            vec![vec![1.0, 2.0, 3.0, 4.0]]
+        );
+        assert!(matches!(
+            data.order_inputs(&[names.clone(), vec!["c".to_string()]].concat(), &[]),
+            Err(PfsysError::MissingInput(..))
+        ));
+        assert!(serde_json::from_str::<ModelInput>(
+            r#"{"input_data": {"a": [[1, 2], [3]]}, "output_data": []}"#
+        )
+        .is_err());
+
+        // the flat format is still supported, and requires shapes
+        let data: ModelInput = serde_json::from_str(
+            r#"{"input_data": [[1.0, 2.0]], "input_shapes": [[2]], "output_data": [[]]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            data.order_inputs(&names, &[]).unwrap().input_data,
+            vec![vec![1.0, 2.0]]
+        );
+        assert!(serde_json::from_str::<ModelInput>(
+            r#"{"input_data": [[1.0]], "output_data": []}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn proof_serde_roundtrip() {
+        let proof = Proof {
+            public_inputs: vec![vec![1, -2, 3]],
+            proof: vec![0, 1, 2, 255],
+            hashes: Some(ArtifactHashes {
+                circuit: "00".into(),
+                vk: "01".into(),
+                srs: "02".into(),
+            }),
+            header: None,
+        };
+        let json = serde_json::to_string(&proof).unwrap();
+        assert!(json.contains("\"0x000102ff\""));
+        let decoded: Proof = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.proof, proof.proof);
+        // proofs saved with byte arrays are still readable
+        let legacy: Proof =
+            serde_json::from_str("{\"public_inputs\":[[1]],\"proof\":[0,1,2,255]}").unwrap();
+        assert_eq!(legacy.proof, proof.proof);
+
+        let bin = bincode::serialize(&proof).unwrap();
+        let decoded: Proof = bincode::deserialize(&bin).unwrap();
+        assert_eq!(decoded.public_inputs, proof.public_inputs);
+        assert_eq!(decoded.proof, proof.proof);
+        assert_eq!(decoded.hashes, proof.hashes);
+    }
+
+    #[test]
+    fn proofs_are_saved_with_a_header() {
+        let hashes = ArtifactHashes {
+            circuit: "00".into(),
+            vk: "01".into(),
+            srs: "02".into(),
+        };
+        let proof = Proof {
+            public_inputs: vec![vec![1, -2, 3]],
+            proof: vec![0, 1, 2, 255],
+            hashes: Some(hashes.clone()),
+            header: None,
+        };
+        let path = std::env::temp_dir().join(format!("ezkl_header_{}.pf", std::process::id()));
+        for format in [ProofFormat::Json, ProofFormat::Bincode] {
+            proof.save::<Fr>(&path, format).unwrap();
+            let loaded = Proof::load::<Fr>(&path, format, &[]).unwrap();
+            assert_eq!(loaded.header, Some(ArtifactHeader::new("00".into())));
+            assert_eq!(loaded.proof, proof.proof);
+            assert!(loaded.check_hashes(&hashes).is_ok());
+        }
+
+        // proofs of another version for another circuit are reported as incompatible, rather than as mismatched hashes
+        let old = Proof {
+            header: Some(ArtifactHeader {
+                version: "0.0.1".into(),
+                ..ArtifactHeader::new("00".into())
+            }),
+            ..proof
+        };
+        let other_circuit = ArtifactHashes {
+            circuit: "03".into(),
+            ..hashes
+        };
+        assert!(matches!(
+            old.check_hashes(&other_circuit),
+            Err(PfsysError::IncompatibleArtifact(..))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn mismatched_artifacts_are_reported() {
+        let hashes = ArtifactHashes {
+            circuit: "00".into(),
+            vk: "01".into(),
+            srs: "02".into(),
+        };
+        let proof = Proof {
+            public_inputs: vec![],
+            proof: vec![],
+            hashes: Some(hashes.clone()),
+            header: None,
+        };
+        assert!(proof.check_hashes(&hashes).is_ok());
+        let other_srs = ArtifactHashes {
+            srs: "03".into(),
+            ..hashes.clone()
+        };
+        match proof.check_hashes(&other_srs) {
+            Err(PfsysError::ArtifactMismatch(artifact, expected, found)) => {
+                assert_eq!(artifact, "SRS");
+                assert_eq!((expected.as_str(), found.as_str()), ("03", "02"));
+            }
+            _ => panic!("expected the srs mismatch to be reported"),
+        }
+        // proofs which don't record hashes can't be checked
+        let legacy = Proof {
+            hashes: None,
+            ..proof
+        };
+        assert!(legacy.check_hashes(&other_srs).is_ok());
+    }
+}
@@ -0,0 +1,226 @@
+use super::ModelInput;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use thiserror::Error;
+
+/// Errors reading .npy and .npz files.
+#[derive(Debug, Error)]
+pub enum NpyError {
+    /// The file couldn't be read
+    #[error("failed to read {0}: {1}")]
+    Io(String, std::io::Error),
+    /// The file doesn't start with the .npy magic string, or its header can't be parsed
+    #[error("invalid .npy header: {0}")]
+    InvalidHeader(String),
+    /// The array is of a dtype which can't be fed to a model
+    #[error("unsupported dtype {0}, expected a (little endian) bool, integer, or float array")]
+    UnsupportedDtype(String),
+    /// The array is stored in Fortran (column major) order
+    #[error("arrays stored in fortran order are not supported")]
+    FortranOrder,
+    /// The data of the array doesn't match the size of its shape
+    #[error("array of shape {0:?} holds {1} bytes")]
+    InvalidLength(Vec<usize>, usize),
+    /// The .npz archive couldn't be read
+    #[error("invalid .npz archive: {0}")]
+    InvalidArchive(String),
+    /// A directory or archive holds no arrays
+    #[error("no arrays found in {0}")]
+    NoArrays(String),
+}
+
+/// Reads a .npy array, returning its shape and its values converted to floats.
+pub fn read_npy(mut reader: impl Read) -> Result<(Vec<usize>, Vec<f32>), NpyError> {
+    let io = |e| NpyError::Io(".npy".to_string(), e);
+    let mut preamble = [0u8; 8];
+    reader.read_exact(&mut preamble).map_err(io)?;
+    if &preamble[..6] != b"\x93NUMPY" {
+        return Err(NpyError::InvalidHeader("missing magic string".to_string()));
+    }
+    // version 1 headers have their length encoded on 2 bytes, later versions on 4
+    let header_len = match preamble[6] {
+        1 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len).map_err(io)?;
+            u16::from_le_bytes(len) as usize
+        }
+        _ => {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len).map_err(io)?;
+            u32::from_le_bytes(len) as usize
+        }
+    };
+    let mut header = vec![0u8; header_len];
+    reader.read_exact(&mut header).map_err(io)?;
+    let header = String::from_utf8_lossy(&header);
+
+    let descr = header_value(&header, "descr")?
+        .trim_matches(|c| c == '\'' || c == '"')
+        .to_string();
+    if header_value(&header, "fortran_order")?.starts_with("True") {
+        return Err(NpyError::FortranOrder);
+    }
+    let shape = header_value(&header, "shape")?;
+    let shape = shape[..shape.find(')').unwrap_or(shape.len())]
+        .trim_start_matches('(')
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|d| {
+            d.parse::<usize>()
+                .map_err(|_| NpyError::InvalidHeader(format!("invalid shape {}", shape)))
+        })
+        .collect::<Result<Vec<usize>, _>>()?;
+
+    let mut bytes = vec![];
+    reader.read_to_end(&mut bytes).map_err(io)?;
+    let values = decode(&descr, &bytes)?;
+    if values.len() != shape.iter().product::<usize>() {
+        return Err(NpyError::InvalidLength(shape, bytes.len()));
+    }
+    Ok((shape, values))
+}
+
+/// Returns the (unparsed) value of `key` in the python dict literal of a .npy header.
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, NpyError> {
+    let start = header
+        .find(&format!("'{}':", key))
+        .ok_or_else(|| NpyError::InvalidHeader(format!("missing {}", key)))?;
+    Ok(header[start + key.len() + 3..].trim_start())
+}
+
+/// Decodes little endian (or single byte) values of the numpy dtype `descr` into floats.
+fn decode(descr: &str, bytes: &[u8]) -> Result<Vec<f32>, NpyError> {
+    macro_rules! decode_as {
+        ($t:ty) => {
+            bytes
+                .chunks_exact(std::mem::size_of::<$t>())
+                .map(|c| <$t>::from_le_bytes(c.try_into().unwrap()) as f32)
+                .collect()
+        };
+    }
+    let values = match descr {
+        "<f4" => decode_as!(f32),
+        "<f8" => decode_as!(f64),
+        "|i1" => decode_as!(i8),
+        "<i2" => decode_as!(i16),
+        "<i4" => decode_as!(i32),
+        "<i8" => decode_as!(i64),
+        "|u1" | "|b1" => decode_as!(u8),
+        "<u2" => decode_as!(u16),
+        "<u4" => decode_as!(u32),
+        "<u8" => decode_as!(u64),
+        _ => return Err(NpyError::UnsupportedDtype(descr.to_string())),
+    };
+    Ok(values)
+}
+
+/// Reads the arrays of a .npz archive, in the order they were saved in.
+pub fn read_npz(path: &Path) -> Result<Vec<(Vec<usize>, Vec<f32>)>, NpyError> {
+    let name = path.display().to_string();
+    let file = File::open(path).map_err(|e| NpyError::Io(name.clone(), e))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| NpyError::InvalidArchive(e.to_string()))?;
+    (0..archive.len())
+        .map(|i| {
+            let entry = archive
+                .by_index(i)
+                .map_err(|e| NpyError::InvalidArchive(e.to_string()))?;
+            read_npy(entry)
+        })
+        .collect()
+}
+
+/// Loads the inputs of a model from a .npy file (for models with a single input), a .npz archive holding an array per input,
+/// or a directory of .npy files, one per input in the order of their file names. Outputs are left empty.
+pub fn load_inputs(path: &Path) -> Result<ModelInput, NpyError> {
+    let name = path.display().to_string();
+    let arrays = if path.is_dir() {
+        let mut files = std::fs::read_dir(path)
+            .map_err(|e| NpyError::Io(name.clone(), e))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().map_or(false, |e| e == "npy"))
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+            .iter()
+            .map(|p| {
+                let file = File::open(p).map_err(|e| NpyError::Io(p.display().to_string(), e))?;
+                read_npy(file)
+            })
+            .collect::<Result<Vec<_>, _>>()?
+    } else if path.extension().map_or(false, |e| e == "npz") {
+        read_npz(path)?
+    } else {
+        let file = File::open(path).map_err(|e| NpyError::Io(name.clone(), e))?;
+        vec![read_npy(file)?]
+    };
+    if arrays.is_empty() {
+        return Err(NpyError::NoArrays(name));
+    }
+    let (input_shapes, input_data) = arrays.into_iter().unzip();
+    Ok(ModelInput {
+        input_data,
+        input_shapes,
+        input_names: vec![],
+        output_data: vec![],
+        input_key: None,
+        input_signature: None,
+        input_merkle_paths: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encodes `bytes` as a version 1 .npy file with the given header fields.
+    fn npy(descr: &str, shape: &str, bytes: &[u8]) -> Vec<u8> {
+        let header = format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}\n",
+            descr, shape
+        );
+        let mut file = b"\x93NUMPY\x01\x00".to_vec();
+        file.extend((header.len() as u16).to_le_bytes());
+        file.extend(header.as_bytes());
+        file.extend(bytes);
+        file
+    }
+
+    #[test]
+    fn reads_floats_and_integers() {
+        let floats = [1.5f32, -2.0, 3.25, 0.0, 8.0, -1.0]
+            .iter()
+            .flat_map(|f| f.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let (shape, values) = read_npy(npy("<f4", "(2, 3)", &floats).as_slice()).unwrap();
+        assert_eq!(shape, vec![2, 3]);
+        assert_eq!(values, vec![1.5, -2.0, 3.25, 0.0, 8.0, -1.0]);
+
+        let ints = [7i64, -3]
+            .iter()
+            .flat_map(|i| i.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let (shape, values) = read_npy(npy("<i8", "(2,)", &ints).as_slice()).unwrap();
+        assert_eq!(shape, vec![2]);
+        assert_eq!(values, vec![7.0, -3.0]);
+    }
+
+    #[test]
+    fn rejects_invalid_arrays() {
+        let bytes = [0u8; 8];
+        assert!(matches!(
+            read_npy(npy(">f4", "(2,)", &bytes).as_slice()),
+            Err(NpyError::UnsupportedDtype(_))
+        ));
+        assert!(matches!(
+            read_npy(npy("<f4", "(3,)", &bytes).as_slice()),
+            Err(NpyError::InvalidLength(_, 8))
+        ));
+        assert!(matches!(
+            read_npy(&b"not a npy file"[..]),
+            Err(NpyError::InvalidHeader(_))
+        ));
+    }
+}
@@ -0,0 +1,361 @@
+use crate::pfsys::load_params;
+use halo2_proofs::arithmetic::{best_multiexp, g_to_lagrange};
+use halo2_proofs::poly::kzg::commitment::{KZGCommitmentScheme, ParamsKZG};
+use halo2_proofs::SerdeFormat;
+use halo2curves::bn256::{Bn256, Fr, G1Affine, G2Affine, G1};
+use halo2curves::group::ff::Field;
+use halo2curves::group::prime::PrimeCurveAffine;
+use halo2curves::pairing::Engine;
+use halo2curves::serde::SerdeObject;
+use log::info;
+#[cfg(not(target_arch = "wasm32"))]
+use memmap2::Mmap;
+use rand::rngs::OsRng;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// The smallest ceremony file published by the Hermez (Polygon) perpetual powers of tau ceremony.
+const MIN_HERMEZ_POWER: u32 = 8;
+/// The largest ceremony file published by the Hermez (Polygon) perpetual powers of tau ceremony.
+const MAX_HERMEZ_POWER: u32 = 28;
+
+/// The size of an uncompressed G1 point in its raw encoding.
+const G1_RAW_BYTES: u64 = 64;
+
+/// Errors related to structured reference strings
+#[derive(Debug, Error)]
+pub enum SrsError {
+    /// The file is not a well formed .ptau file
+    #[error("invalid .ptau file: {0}")]
+    InvalidPtau(String),
+    /// The SRS holds fewer powers of tau than required
+    #[error("SRS only supports circuits of up to 2^{0} rows but 2^{1} are required")]
+    TooSmall(u32, u32),
+    /// The SRS isn't made up of consecutive powers of a single secret
+    #[error("SRS failed verification")]
+    Verification,
+}
+
+/// Memory maps the file at `path`, so that large files (SRS, proving keys) are paged in from disk as they are read rather
+/// than being copied into buffers.
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(unsafe_code)]
+pub fn mmap_file(path: &PathBuf) -> io::Result<Mmap> {
+    let f = File::open(path)?;
+    // SAFETY: the map is only ever read from, and the files we map are not expected to be modified while ezkl is running
+    unsafe { Mmap::map(&f) }
+}
+
+/// Reads the file at `path` into memory, as files can't be memory mapped on wasm.
+#[cfg(target_arch = "wasm32")]
+pub fn mmap_file(path: &PathBuf) -> io::Result<Vec<u8>> {
+    std::fs::read(path)
+}
+
+/// Streams the raw encoding of a sequence of points, so that params can be deserialized from points without first
+/// holding a serialized copy of all of them in memory.
+struct RawPoints<I> {
+    points: I,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<I> RawPoints<I> {
+    fn new(points: I) -> Self {
+        Self {
+            points,
+            buf: vec![],
+            pos: 0,
+        }
+    }
+}
+
+impl<P: SerdeObject, I: Iterator<Item = P>> Read for RawPoints<I> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf.len() {
+            match self.points.next() {
+                Some(p) => {
+                    self.buf.clear();
+                    p.write_raw(&mut self.buf)?;
+                    self.pos = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Reads a section header (section type and size in bytes) of a .ptau file.
+fn read_section_header<R: Read>(reader: &mut R) -> io::Result<(u32, u64)> {
+    let mut ty = [0u8; 4];
+    reader.read_exact(&mut ty)?;
+    let mut size = [0u8; 8];
+    reader.read_exact(&mut size)?;
+    Ok((u32::from_le_bytes(ty), u64::from_le_bytes(size)))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Reads a snarkjs / Hermez ceremony `.ptau` file at `path` and converts it to [ParamsKZG] supporting circuits of `2^k` rows.
+///
+/// A .ptau file is made up of a header (the `ptau` magic bytes, a version, and the number of sections) followed by sections, each
+/// prefixed by its type and size. Section 1 holds the field element size, the base field modulus, and the power of the ceremony.
+/// Section 2 holds the powers of tau in G1 and section 3 the powers of tau in G2, with points stored as uncompressed coordinates
+/// in little-endian Montgomery form (which matches the raw encoding of points in halo2curves).
+pub fn read_ptau(path: &PathBuf, k: u32) -> Result<ParamsKZG<Bn256>, Box<dyn Error>> {
+    info!("loading powers of tau from {:?}", path);
+    let mmap = mmap_file(path).map_err(Box::<dyn Error>::from)?;
+    let mut reader = Cursor::new(&mmap[..]);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != b"ptau" {
+        return Err(Box::new(SrsError::InvalidPtau(
+            "missing magic bytes".into(),
+        )));
+    }
+    let _version = read_u32(&mut reader)?;
+    let num_sections = read_u32(&mut reader)?;
+
+    // record the offset of each section so they can be read in order
+    let mut sections = std::collections::BTreeMap::new();
+    for _ in 0..num_sections {
+        let (ty, size) = read_section_header(&mut reader)?;
+        let offset = reader.stream_position()?;
+        sections.insert(ty, offset);
+        reader.seek(SeekFrom::Current(size as i64))?;
+    }
+    let seek_section = |reader: &mut Cursor<&[u8]>, ty: u32| -> Result<(), Box<dyn Error>> {
+        let offset = sections
+            .get(&ty)
+            .ok_or_else(|| SrsError::InvalidPtau(format!("missing section {}", ty)))?;
+        reader.seek(SeekFrom::Start(*offset))?;
+        Ok(())
+    };
+
+    // header
+    seek_section(&mut reader, 1)?;
+    let n8 = read_u32(&mut reader)?;
+    if n8 != 32 {
+        return Err(Box::new(SrsError::InvalidPtau(format!(
+            "expected 32 byte field elements (bn254) but found {} bytes",
+            n8
+        ))));
+    }
+    reader.seek(SeekFrom::Current(n8 as i64))?;
+    let power = read_u32(&mut reader)?;
+    if power < k {
+        return Err(Box::new(SrsError::TooSmall(power, k)));
+    }
+    let n = 1usize << k;
+
+    // powers of tau in G1, we only need the first n
+    seek_section(&mut reader, 2)?;
+    let g = (0..n)
+        .map(|_| G1Affine::read_raw(&mut reader))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| SrsError::InvalidPtau(e.to_string()))?;
+
+    // powers of tau in G2, we only need the generator and tau * generator
+    seek_section(&mut reader, 3)?;
+    let g2 = G2Affine::read_raw(&mut reader).map_err(|e| SrsError::InvalidPtau(e.to_string()))?;
+    let s_g2 = G2Affine::read_raw(&mut reader).map_err(|e| SrsError::InvalidPtau(e.to_string()))?;
+
+    params_from_parts(k, g, g2, s_g2).map_err(Box::<dyn Error>::from)
+}
+
+/// Assembles [ParamsKZG] from the powers of tau in G1 (`g`), the G2 generator (`g2`), and tau times the G2 generator (`s_g2`).
+fn params_from_parts(
+    k: u32,
+    g: Vec<G1Affine>,
+    g2: G2Affine,
+    s_g2: G2Affine,
+) -> io::Result<ParamsKZG<Bn256>> {
+    let g_lagrange: Vec<G1Affine> = g_to_lagrange(g.iter().map(|p| G1::from(*p)).collect(), k);
+    // ParamsKZG doesn't expose a constructor, so we stream its raw serialization into its deserializer
+    let mut reader = Cursor::new(k.to_le_bytes())
+        .chain(RawPoints::new(g.into_iter().chain(g_lagrange)))
+        .chain(RawPoints::new([g2, s_g2].into_iter()));
+    ParamsKZG::<Bn256>::read_custom(&mut reader, SerdeFormat::RawBytes)
+}
+
+/// Checks that `params` is made up of consecutive powers of a single secret `s`, i.e that `g[i + 1] = s * g[i]` for every `i`,
+/// where `s` is the secret committed to in `s_g2`. Rather than checking every pair we check a random linear combination of them
+/// using a single pairing. Note that this does not (and can not) check the secret is unknown, which depends on the ceremony.
+pub fn verify_srs(params: &ParamsKZG<Bn256>) -> Result<(), SrsError> {
+    let g = params.get_g();
+    if g[0] != G1Affine::generator() || params.g2() != G2Affine::generator() {
+        return Err(SrsError::Verification);
+    }
+    let coeffs = (0..g.len() - 1)
+        .map(|_| Fr::random(OsRng))
+        .collect::<Vec<_>>();
+    let lhs: G1Affine = best_multiexp(&coeffs, &g[1..]).into();
+    let rhs: G1Affine = best_multiexp(&coeffs, &g[..g.len() - 1]).into();
+    if Bn256::pairing(&lhs, &params.g2()) != Bn256::pairing(&rhs, &params.s_g2()) {
+        return Err(SrsError::Verification);
+    }
+    Ok(())
+}
+
+/// Downloads the Hermez perpetual powers of tau ceremony file supporting circuits of `2^k` rows to `path`.
+pub fn download_ptau(k: u32, path: &PathBuf) -> Result<(), Box<dyn Error>> {
+    if k > MAX_HERMEZ_POWER {
+        return Err(Box::new(SrsError::TooSmall(MAX_HERMEZ_POWER, k)));
+    }
+    let url = format!(
+        "https://hermez.s3-eu-west-1.amazonaws.com/powersOfTau28_hez_final_{:02}.ptau",
+        k.max(MIN_HERMEZ_POWER)
+    );
+    info!("downloading powers of tau from {} to {:?}", url, path);
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut resp = reqwest::blocking::get(url)?.error_for_status()?;
+        let mut file = File::create(path).map_err(Box::<dyn Error>::from)?;
+        resp.copy_to(&mut file)?;
+        Ok(())
+    }
+    // there are no blocking requests in the browser, params are instead passed in by the caller
+    #[cfg(target_arch = "wasm32")]
+    Err(Box::new(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "downloading params is unsupported on wasm",
+    )))
+}
+
+/// Loads [ParamsKZG] saved by ezkl at `path`, trimmed to support circuits of `2^k` rows. When the saved params are larger than
+/// required only the first `2^k` powers of tau are read from disk (rather than loading the full params and then downsizing them),
+/// and the lagrange basis is recomputed for the smaller domain.
+pub fn load_trimmed_params(path: &PathBuf, k: u32) -> Result<ParamsKZG<Bn256>, Box<dyn Error>> {
+    let mmap = mmap_file(path).map_err(Box::<dyn Error>::from)?;
+    let mut reader = Cursor::new(&mmap[..]);
+    let file_k = read_u32(&mut reader)?;
+    if file_k < k {
+        return Err(Box::new(SrsError::TooSmall(file_k, k)));
+    }
+    if file_k == k {
+        return load_params::<KZGCommitmentScheme<Bn256>>(path.clone());
+    }
+    info!(
+        "loading params from {:?}, trimmed from 2^{} to 2^{} rows",
+        path, file_k, k
+    );
+    // params are saved as k, then the 2^k powers of tau in G1, then the lagrange basis (also 2^k points in G1), then the two G2 points
+    let g = (0..1usize << k)
+        .map(|_| G1Affine::read_raw(&mut reader))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Box::<dyn Error>::from)?;
+    reader.seek(SeekFrom::Start(4 + 2 * (1u64 << file_k) * G1_RAW_BYTES))?;
+    let g2 = G2Affine::read_raw(&mut reader).map_err(Box::<dyn Error>::from)?;
+    let s_g2 = G2Affine::read_raw(&mut reader).map_err(Box::<dyn Error>::from)?;
+    params_from_parts(k, g, g2, s_g2).map_err(Box::<dyn Error>::from)
+}
+
+/// Loads a structured reference string supporting circuits of `2^k` rows from `path`, which can either be a `.ptau` ceremony file
+/// or params previously saved by ezkl. If no file exists at `path` the appropriate Hermez ceremony file is downloaded to `path`.
+/// The SRS is verified (see [verify_srs]) before being returned.
+pub fn load_srs(path: &PathBuf, k: u32) -> Result<ParamsKZG<Bn256>, Box<dyn Error>> {
+    let is_ptau = !path.exists() || path.extension().map_or(false, |e| e == "ptau");
+    if !path.exists() {
+        download_ptau(k, path)?;
+    }
+    let params = if is_ptau {
+        read_ptau(path, k)?
+    } else {
+        load_trimmed_params(path, k)?
+    };
+    info!("verifying SRS");
+    verify_srs(&params)?;
+    Ok(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::poly::commitment::{Params, ParamsProver};
+    use std::io::Write;
+
+    /// Writes `params` as a minimal .ptau file, holding only the sections we read.
+    fn write_ptau(params: &ParamsKZG<Bn256>, path: &PathBuf) {
+        let mut header = vec![];
+        header.extend(32u32.to_le_bytes());
+        header.extend([0u8; 32]);
+        header.extend(params.k().to_le_bytes());
+        header.extend(params.k().to_le_bytes());
+        let mut tau_g1 = vec![];
+        for p in params.get_g() {
+            p.write_raw(&mut tau_g1).unwrap();
+        }
+        let mut tau_g2 = vec![];
+        params.g2().write_raw(&mut tau_g2).unwrap();
+        params.s_g2().write_raw(&mut tau_g2).unwrap();
+
+        let mut f = File::create(path).unwrap();
+        f.write_all(b"ptau").unwrap();
+        f.write_all(&1u32.to_le_bytes()).unwrap();
+        f.write_all(&3u32.to_le_bytes()).unwrap();
+        for (ty, section) in [(1u32, header), (2, tau_g1), (3, tau_g2)] {
+            f.write_all(&ty.to_le_bytes()).unwrap();
+            f.write_all(&(section.len() as u64).to_le_bytes()).unwrap();
+            f.write_all(&section).unwrap();
+        }
+    }
+
+    #[test]
+    fn ptau_roundtrip() {
+        let params = ParamsKZG::<Bn256>::new(4);
+        let path = std::env::temp_dir().join("ezkl_ptau_roundtrip.ptau");
+        write_ptau(&params, &path);
+
+        let loaded = read_ptau(&path, 4).unwrap();
+        // compare serializations, which also checks the lagrange basis is correctly recovered
+        let (mut expected, mut actual) = (vec![], vec![]);
+        params.write(&mut expected).unwrap();
+        loaded.write(&mut actual).unwrap();
+        assert_eq!(actual, expected);
+        verify_srs(&loaded).unwrap();
+
+        // a smaller srs can be read from a larger ceremony
+        let loaded = read_ptau(&path, 3).unwrap();
+        assert_eq!(loaded.get_g(), params.get_g()[..8].to_vec());
+        verify_srs(&loaded).unwrap();
+
+        assert!(read_ptau(&path, 5).is_err());
+    }
+
+    #[test]
+    fn trimmed_params_match_downsized_params() {
+        let mut params = ParamsKZG::<Bn256>::new(5);
+        let path = std::env::temp_dir().join("ezkl_trimmed_params.params");
+        let mut f = File::create(&path).unwrap();
+        params.write(&mut f).unwrap();
+        drop(f);
+
+        let trimmed = load_trimmed_params(&path, 3).unwrap();
+        params.downsize(3);
+        let (mut expected, mut actual) = (vec![], vec![]);
+        params.write(&mut expected).unwrap();
+        trimmed.write(&mut actual).unwrap();
+        assert_eq!(actual, expected);
+        assert!(load_trimmed_params(&path, 6).is_err());
+    }
+
+    #[test]
+    fn srs_verification_fails_for_mismatched_powers() {
+        let params = ParamsKZG::<Bn256>::new(3);
+        let other = ParamsKZG::<Bn256>::new(3);
+        let mixed = params_from_parts(3, params.get_g(), params.g2(), other.s_g2()).unwrap();
+        assert!(verify_srs(&mixed).is_err());
+    }
+}
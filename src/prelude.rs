@@ -0,0 +1,24 @@
+//! Re-exports the types and functions needed to prove and verify models from library code, e.g:
+//!
+//! ```no_run
+//! use ezkl::prelude::*;
+//!
+//! let settings = CircuitSettings::default();
+//! let data = prepare_data("input.json".to_string()).unwrap();
+//! let params = gen_srs(settings.logrows);
+//! let keys = setup("network.onnx", &settings, &params).unwrap();
+//! let proof = prove("network.onnx", &settings, &data, &params, &keys).unwrap();
+//! assert!(verify(&proof, &params, keys.vk()));
+//! ```
+
+pub use crate::api::{
+    circuit, gen_srs, load_model, prove, public_outputs, setup, verify, Keys, PreparedCircuit,
+};
+pub use crate::commands::{CircuitSettings, ProofFormat};
+pub use crate::error::EzklError;
+pub use crate::graph::{
+    CustomConfig, CustomOp, CustomOps, CustomQuantization, GraphBuilder, GraphTensor, Model,
+    ModelBuilder, ModelCircuit, Pass, RunArgs, VarVisibility, Visibility,
+};
+pub use crate::pfsys::srs::load_srs;
+pub use crate::pfsys::{prepare_data, ModelInput, Proof};
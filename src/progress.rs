@@ -0,0 +1,151 @@
+use crate::memory;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use log::info;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{field, info_span};
+
+thread_local! {
+    /// Bars are drawn together, such that phases nested within others (e.g the model layout within keygen) are drawn below them.
+    static BARS: MultiProgress = MultiProgress::new();
+}
+
+/// Creates a progress bar for a phase of `len` steps, drawn on stderr, or a hidden bar when progress isn't `enabled`
+/// (see `--progress`), such that callers can report progress unconditionally.
+pub fn bar(enabled: bool, phase: &str, len: u64) -> ProgressBar {
+    if !enabled {
+        return ProgressBar::hidden();
+    }
+    let bar = BARS.with(|bars| bars.add(ProgressBar::new(len)));
+    bar.set_style(
+        ProgressStyle::with_template("{prefix:>20} [{elapsed_precise}] {bar:40} {pos}/{len} {msg}")
+            .expect("the template is valid"),
+    );
+    bar.set_prefix(phase.to_string());
+    bar
+}
+
+/// Creates a spinner for a phase whose progress can't be measured (e.g the prover's commitments), drawn on stderr, or a
+/// hidden spinner when progress isn't `enabled`.
+pub fn spinner(enabled: bool, phase: &str) -> ProgressBar {
+    if !enabled {
+        return ProgressBar::hidden();
+    }
+    let spinner = BARS.with(|bars| bars.add(ProgressBar::new_spinner()));
+    spinner.set_style(
+        ProgressStyle::with_template("{prefix:>20} [{elapsed_precise}] {spinner} {msg}")
+            .expect("the template is valid"),
+    );
+    spinner.set_prefix(phase.to_string());
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner
+}
+
+/// Runs `f` as the phase named `phase`, within a tracing span of the same name, showing a spinner until it completes
+/// when progress is `enabled`. The peak memory of the phase is tracked (see [memory::track]), and recorded as the
+/// `peak_rss` field of the span.
+pub fn with_spinner<T>(enabled: bool, phase: &str, f: impl FnOnce() -> T) -> T {
+    let spinner = spinner(enabled, phase);
+    let span = info_span!("phase", phase, peak_rss = field::Empty);
+    let (res, peak) = span.in_scope(|| memory::track(phase, f));
+    match peak {
+        Some(peak) => {
+            span.record("peak_rss", peak);
+            info!("{} peaked at {} of memory", phase, memory::human(peak));
+            spinner.finish_with_message(format!("done, peak memory {}", memory::human(peak)));
+        }
+        None => spinner.finish_with_message("done"),
+    }
+    res
+}
+
+/// The phases of a proof, as reported to the listener of a [Task].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Waiting for a thread to prove on.
+    Queued,
+    /// Loading the model and running it over its inputs.
+    Witness,
+    /// Laying out the circuit, `done` of its `total` nodes in.
+    Layout {
+        /// The number of nodes laid out.
+        done: u64,
+        /// The number of nodes of the circuit, over all samples.
+        total: u64,
+    },
+    /// Committing to the witness and computing the proof, which can't be interrupted.
+    Proving,
+    /// The proof is complete.
+    Done,
+}
+
+/// A handle on a long running proof, shared by the proof (which reports its progress and checks whether it should stop) and
+/// its owner (which listens for its progress and cancels it). Cancellation is cooperative: it is checked between phases and
+/// as each node is laid out.
+#[derive(Clone, Default)]
+pub struct Task {
+    cancelled: Arc<AtomicBool>,
+    listener: Option<Arc<dyn Fn(Phase) + Send + Sync>>,
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Task")
+            .field("cancelled", &self.is_cancelled())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Task {
+    /// Creates a task whose progress is reported to `listener`.
+    pub fn new(listener: impl Fn(Phase) + Send + Sync + 'static) -> Self {
+        Task {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            listener: Some(Arc::new(listener)),
+        }
+    }
+
+    /// Asks the task to stop at its next check.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the task was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Reports the task entered `phase`.
+    pub fn report(&self, phase: Phase) {
+        if let Some(listener) = &self.listener {
+            listener(phase);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn tasks_report_and_cancel() {
+        let phases = Arc::new(Mutex::new(vec![]));
+        let task = Task::new({
+            let phases = phases.clone();
+            move |phase| phases.lock().unwrap().push(phase)
+        });
+        let shared = task.clone();
+        shared.report(Phase::Witness);
+        shared.report(Phase::Layout { done: 1, total: 2 });
+        assert!(!shared.is_cancelled());
+        task.cancel();
+        assert!(shared.is_cancelled());
+        assert_eq!(
+            *phases.lock().unwrap(),
+            vec![Phase::Witness, Phase::Layout { done: 1, total: 2 }]
+        );
+    }
+}
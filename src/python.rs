@@ -0,0 +1,215 @@
+// the pyo3 macros expand to unsafe ffi glue
+#![allow(unsafe_code)]
+
+use crate::api;
+use crate::commands::{CircuitSettings, ProofFormat, TranscriptType};
+use crate::pfsys::{load_params, prepare_data, save_params, ModelInput, Proof};
+use clap::ValueEnum;
+use halo2_proofs::poly::kzg::commitment::{KZGCommitmentScheme, ParamsKZG};
+use halo2curves::bn256::{Bn256, Fr};
+use numpy::PyReadonlyArrayDyn;
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::fmt::Display;
+use std::path::PathBuf;
+
+/// Raises errors of the library as python `RuntimeError`s.
+fn py_err(e: impl Display) -> PyErr {
+    PyRuntimeError::new_err(e.to_string())
+}
+
+fn load_settings(settings_path: &PathBuf) -> PyResult<CircuitSettings> {
+    CircuitSettings::load(settings_path).map_err(py_err)
+}
+
+fn load_kzg_params(params_path: &PathBuf) -> PyResult<ParamsKZG<Bn256>> {
+    load_params::<KZGCommitmentScheme<Bn256>>(params_path.clone()).map_err(py_err)
+}
+
+fn parse_transcript(transcript: &str) -> PyResult<TranscriptType> {
+    TranscriptType::from_str(transcript, true).map_err(PyValueError::new_err)
+}
+
+/// Converts a numpy array of floats to the shape and (row major) values of a model input.
+fn array_input(array: &PyAny) -> PyResult<(Vec<usize>, Vec<f32>)> {
+    if let Ok(array) = array.extract::<PyReadonlyArrayDyn<f32>>() {
+        let array = array.as_array();
+        return Ok((array.shape().to_vec(), array.iter().copied().collect()));
+    }
+    let array = array.extract::<PyReadonlyArrayDyn<f64>>()?;
+    let array = array.as_array();
+    Ok((
+        array.shape().to_vec(),
+        array.iter().map(|v| *v as f32).collect(),
+    ))
+}
+
+/// Loads the inputs of a model from `data`: the path to a data file (as accepted by the CLI), a list of numpy arrays (one per
+/// input, in order), or a dict of numpy arrays keyed by the names of the inputs.
+fn model_input(data: &PyAny) -> PyResult<ModelInput> {
+    if let Ok(path) = data.extract::<PathBuf>() {
+        return prepare_data(path.to_string_lossy().to_string()).map_err(py_err);
+    }
+    let (input_names, arrays): (Vec<String>, Vec<&PyAny>) = match data.downcast::<PyDict>() {
+        Ok(dict) => dict
+            .iter()
+            .map(|(name, array)| Ok((name.extract::<String>()?, array)))
+            .collect::<PyResult<Vec<_>>>()?
+            .into_iter()
+            .unzip(),
+        Err(_) => (vec![], data.extract()?),
+    };
+    let (input_shapes, input_data) = arrays
+        .into_iter()
+        .map(array_input)
+        .collect::<PyResult<Vec<_>>>()?
+        .into_iter()
+        .unzip();
+    Ok(ModelInput {
+        input_data,
+        input_shapes,
+        input_names,
+        output_data: vec![],
+        input_key: None,
+        input_signature: None,
+        input_merkle_paths: vec![],
+    })
+}
+
+/// Saves circuit settings to `settings_path`, as the `gen-settings` command does. Unset settings take their CLI defaults.
+#[pyfunction]
+#[pyo3(signature = (
+    settings_path,
+    scale = 7,
+    bits = 16,
+    logrows = 17,
+    public_inputs = false,
+    public_outputs = true,
+    public_params = false,
+    tolerance = 0,
+    batch_size = 1,
+))]
+#[allow(clippy::too_many_arguments)]
+fn gen_settings(
+    settings_path: PathBuf,
+    scale: i32,
+    bits: usize,
+    logrows: u32,
+    public_inputs: bool,
+    public_outputs: bool,
+    public_params: bool,
+    tolerance: usize,
+    batch_size: usize,
+) -> PyResult<()> {
+    let settings = CircuitSettings {
+        scale,
+        bits,
+        logrows,
+        public_inputs,
+        public_outputs,
+        public_params,
+        tolerance,
+        batch_size,
+        ..CircuitSettings::default()
+    };
+    settings.save(&settings_path).map_err(py_err)
+}
+
+/// Saves unsafe (locally generated) KZG params for circuits of `2^logrows` rows to `params_path`, for testing only.
+#[pyfunction]
+fn gen_srs(params_path: PathBuf, logrows: u32) -> PyResult<()> {
+    save_params::<KZGCommitmentScheme<Bn256>>(&params_path, &api::gen_srs(logrows)).map_err(py_err)
+}
+
+/// Generates the keys of the circuit of the .onnx model at `model`, saving them to `pk_path` and `vk_path`.
+#[pyfunction]
+fn setup(
+    model: PathBuf,
+    settings_path: PathBuf,
+    params_path: PathBuf,
+    pk_path: PathBuf,
+    vk_path: PathBuf,
+) -> PyResult<()> {
+    let settings = load_settings(&settings_path)?;
+    let params = load_kzg_params(&params_path)?;
+    let keys = api::setup(&model, &settings, &params).map_err(py_err)?;
+    api::save_keys(&keys, &pk_path, &vk_path).map_err(py_err)
+}
+
+/// Proves the .onnx model at `model` over the inputs in `data` (see [model_input]), saving the proof as json to `proof_path`.
+/// Proofs to be verified on the EVM are created with the `keccak` transcript.
+#[pyfunction]
+#[pyo3(signature = (data, model, settings_path, params_path, pk_path, proof_path, transcript = "blake2b"))]
+fn prove(
+    data: &PyAny,
+    model: PathBuf,
+    settings_path: PathBuf,
+    params_path: PathBuf,
+    pk_path: PathBuf,
+    proof_path: PathBuf,
+    transcript: &str,
+) -> PyResult<()> {
+    let transcript = parse_transcript(transcript)?;
+    let data = model_input(data)?;
+    let settings = load_settings(&settings_path)?;
+    let params = load_kzg_params(&params_path)?;
+    let keys = api::load_keys(&model, &settings, &pk_path).map_err(py_err)?;
+    let proof = api::prove_with_transcript(&model, &settings, &data, &params, &keys, transcript)
+        .map_err(py_err)?;
+    proof
+        .save::<Fr>(&proof_path, ProofFormat::Json)
+        .map_err(py_err)
+}
+
+/// Verifies the proof at `proof_path` against the verifying key at `vk_path`, returning whether it is accepted.
+#[pyfunction]
+#[pyo3(signature = (proof_path, model, settings_path, params_path, vk_path, transcript = "blake2b"))]
+fn verify(
+    proof_path: PathBuf,
+    model: PathBuf,
+    settings_path: PathBuf,
+    params_path: PathBuf,
+    vk_path: PathBuf,
+    transcript: &str,
+) -> PyResult<bool> {
+    let transcript = parse_transcript(transcript)?;
+    let settings = load_settings(&settings_path)?;
+    let params = load_kzg_params(&params_path)?;
+    let vk = api::load_verifying_key(&model, &settings, &vk_path).map_err(py_err)?;
+    let proof = Proof::load::<Fr>(&proof_path, ProofFormat::Json, &[]).map_err(py_err)?;
+    Ok(api::verify_with_transcript(
+        &proof, &params, &vk, transcript,
+    ))
+}
+
+/// Saves the (hex encoded) bytecode of an EVM verifier for proofs of the .onnx model at `model`, created with the `keccak`
+/// transcript, to `deployment_code_path`, as `aggregate` saves it (one line per contract if the verifier is split).
+#[cfg(feature = "evm")]
+#[pyfunction]
+fn create_evm_verifier(
+    model: PathBuf,
+    settings_path: PathBuf,
+    params_path: PathBuf,
+    vk_path: PathBuf,
+    deployment_code_path: PathBuf,
+) -> PyResult<()> {
+    let settings = load_settings(&settings_path)?;
+    let params = load_kzg_params(&params_path)?;
+    let vk = api::load_verifying_key(&model, &settings, &vk_path).map_err(py_err)?;
+    let code = api::create_evm_verifier(&model, &settings, &params, &vk).map_err(py_err)?;
+    std::fs::write(deployment_code_path, code.to_hex()).map_err(py_err)
+}
+
+/// The `pyezkl` python module.
+#[pymodule]
+fn pyezkl(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(gen_settings, m)?)?;
+    m.add_function(wrap_pyfunction!(gen_srs, m)?)?;
+    m.add_function(wrap_pyfunction!(setup, m)?)?;
+    m.add_function(wrap_pyfunction!(prove, m)?)?;
+    m.add_function(wrap_pyfunction!(verify, m)?)?;
+    #[cfg(feature = "evm")]
+    m.add_function(wrap_pyfunction!(create_evm_verifier, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use crate::api::{self, PreparedCircuit};
+use crate::commands::{CircuitSettings, TranscriptType};
+use crate::error::EzklError;
+use crate::pfsys::{load_params, ModelInput, Proof};
+use crate::progress::Task;
+use halo2_proofs::poly::kzg::commitment::KZGCommitmentScheme;
+use halo2curves::bn256::Bn256;
+use log::{error, info};
+use serde::Serialize;
+use std::error::Error;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::Instant;
+use tiny_http::{Header, Method, Response, Server};
+
+/// A prover holding the model, params and keys of a model in memory (see [PreparedCircuit]), such that they are loaded once
+/// rather than for every proof.
+#[derive(Debug)]
+pub struct Prover {
+    circuit: PreparedCircuit,
+}
+
+/// The response of the `/verify` endpoint.
+#[derive(Debug, Serialize)]
+struct Verified {
+    verified: bool,
+}
+
+/// The response of endpoints on failure.
+#[derive(Debug, Serialize)]
+struct Failure {
+    error: String,
+}
+
+impl Prover {
+    /// Loads the params at `params_path` and the proving key at `pk_path` (as saved by `setup`) of the circuit of the model at
+    /// `model`, creating proofs with `transcript`.
+    pub fn load(
+        model: PathBuf,
+        settings: CircuitSettings,
+        pk_path: &PathBuf,
+        params_path: &PathBuf,
+        transcript: TranscriptType,
+    ) -> Result<Self, EzklError> {
+        let params = load_params::<KZGCommitmentScheme<Bn256>>(params_path.clone())?;
+        let keys = api::load_keys(&model, &settings, pk_path)?;
+        let circuit = PreparedCircuit::new(model, &settings, params, keys, transcript)?;
+        Ok(Prover { circuit })
+    }
+
+    /// Proves the model over the inputs in `data`.
+    pub fn prove(&self, data: &ModelInput) -> Result<Proof, EzklError> {
+        self.circuit.prove(data)
+    }
+
+    /// Proves the model over the inputs in `data`, reporting progress to `task` and stopping when it is cancelled.
+    pub fn prove_task(&self, data: &ModelInput, task: &Task) -> Result<Proof, EzklError> {
+        self.circuit.prove_task(data, task)
+    }
+
+    /// Verifies a `proof` of the model, returning whether it is accepted.
+    pub fn verify(&self, proof: &Proof) -> Result<bool, EzklError> {
+        self.circuit.verify(proof)
+    }
+
+    /// Handles a request to `url` with the json `body`, returning the status code and json body of the response.
+    fn handle(&self, method: &Method, url: &str, body: &str) -> (u16, String) {
+        let res = match (method, url) {
+            (Method::Post, "/prove") => serde_json::from_str(body)
+                .map_err(|e| (400, Box::<dyn Error>::from(e)))
+                .and_then(|data| self.prove(&data).map_err(|e| (500, e.into())))
+                .and_then(|proof| serde_json::to_string(&proof).map_err(|e| (500, e.into()))),
+            (Method::Post, "/verify") => serde_json::from_str(body)
+                .map_err(|e| (400, Box::<dyn Error>::from(e)))
+                .and_then(|proof| self.verify(&proof).map_err(|e| (400, e.into())))
+                .and_then(|verified| {
+                    serde_json::to_string(&Verified { verified }).map_err(|e| (500, e.into()))
+                }),
+            (Method::Get, "/health") => Ok("{}".to_string()),
+            (_, "/prove" | "/verify" | "/health") => Err((405, "method not allowed".into())),
+            _ => Err((404, format!("no endpoint at {}", url).into())),
+        };
+        match res {
+            Ok(body) => (200, body),
+            Err((status, e)) => {
+                let failure = Failure {
+                    error: e.to_string(),
+                };
+                (status, serde_json::to_string(&failure).unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Reads the body of a request from `reader`, rejecting bodies of more than `max_body_size` bytes with a 413, whether declared by
+/// their `content_length` or not, such that a client can't exhaust the memory of the server.
+fn read_body(
+    reader: impl Read,
+    content_length: Option<usize>,
+    max_body_size: u64,
+) -> Result<String, (u16, String)> {
+    let too_large = || (413, format!("request body exceeds {} bytes", max_body_size));
+    if content_length.map_or(false, |len| len as u64 > max_body_size) {
+        return Err(too_large());
+    }
+    let mut body = String::new();
+    // bodies without a (truthful) content length are read up to one byte past the limit, to tell whether they exceed it
+    reader
+        .take(max_body_size + 1)
+        .read_to_string(&mut body)
+        .map_err(|e| (400, e.to_string()))?;
+    if body.len() as u64 > max_body_size {
+        return Err(too_large());
+    }
+    Ok(body)
+}
+
+/// Serves proofs (`POST /prove`, with the json inputs of a data file as body) and verifications (`POST /verify`, with a json
+/// proof as body) of the model held by `prover` on `addr`. Requests are handled one at a time, as proving is itself parallel, and
+/// bodies of more than `max_body_size` bytes are rejected.
+pub fn serve(prover: &Prover, addr: &str, max_body_size: u64) -> Result<(), Box<dyn Error>> {
+    let server = Server::http(addr).map_err(|e| e.to_string())?;
+    let json = Header::from_bytes("Content-Type", "application/json")
+        .map_err(|_| "invalid content type header")?;
+    info!("serving proofs on http://{}", addr);
+    for mut request in server.incoming_requests() {
+        let now = Instant::now();
+        let content_length = request.body_length();
+        let (status, response) = match read_body(request.as_reader(), content_length, max_body_size)
+        {
+            Ok(body) => prover.handle(request.method(), request.url(), &body),
+            Err((status, e)) => {
+                let failure = Failure { error: e };
+                (status, serde_json::to_string(&failure).unwrap_or_default())
+            }
+        };
+        info!(
+            "{} {} -> {} in {}ms",
+            request.method(),
+            request.url(),
+            status,
+            now.elapsed().as_millis()
+        );
+        let response = Response::from_string(response)
+            .with_status_code(status)
+            .with_header(json.clone());
+        if let Err(e) = request.respond(response) {
+            error!("failed to respond: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn bodies_over_the_limit_are_rejected() {
+        let body = "{\"input_data\": []}";
+        let len = body.len();
+        assert_eq!(
+            read_body(Cursor::new(body), Some(len), len as u64).unwrap(),
+            body
+        );
+        // declared too large, rejected before reading
+        assert_eq!(
+            read_body(Cursor::new(body), Some(len), len as u64 - 1)
+                .unwrap_err()
+                .0,
+            413
+        );
+        // undeclared (e.g chunked) or understated, rejected once the limit is read past
+        assert_eq!(
+            read_body(Cursor::new(body), None, len as u64 - 1)
+                .unwrap_err()
+                .0,
+            413
+        );
+        assert_eq!(
+            read_body(Cursor::new(body), Some(1), len as u64 - 1)
+                .unwrap_err()
+                .0,
+            413
+        );
+    }
+}
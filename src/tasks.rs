@@ -0,0 +1,113 @@
+use crate::error::EzklError;
+use crate::graph::GraphError;
+use crate::pfsys::{ModelInput, Proof};
+use crate::progress::{Phase, Task};
+use crate::serve::Prover;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use thiserror::Error;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Errors of a [ProofTask].
+#[derive(Debug, Error)]
+pub enum TaskError {
+    /// The proof was cancelled before it completed
+    #[error("the proof was cancelled")]
+    Cancelled,
+    /// The proof failed
+    #[error("failed to prove: {0}")]
+    Failed(String),
+    /// The thread the proof ran on panicked
+    #[error("the prover panicked")]
+    Panicked,
+}
+
+impl From<EzklError> for TaskError {
+    fn from(e: EzklError) -> Self {
+        match e {
+            EzklError::Graph(GraphError::Cancelled) => TaskError::Cancelled,
+            e => TaskError::Failed(e.to_string()),
+        }
+    }
+}
+
+/// A proof running on the blocking thread pool of a tokio runtime, resolving to the proof once it completes. Dropping the task
+/// (e.g. when the client waiting on it disconnects) cancels the proof, such that the memory it holds is freed as soon as it
+/// stops.
+#[derive(Debug)]
+pub struct ProofTask {
+    task: Task,
+    progress: watch::Receiver<Phase>,
+    handle: JoinHandle<Result<Proof, TaskError>>,
+}
+
+impl ProofTask {
+    /// The phase the proof is in, which changes as it progresses (see [watch::Receiver::changed]).
+    pub fn progress(&self) -> watch::Receiver<Phase> {
+        self.progress.clone()
+    }
+
+    /// Cancels the proof, which then resolves to [TaskError::Cancelled]. Proofs are cancelled as each node of the model is laid
+    /// out, but not once the prover commits to the witness: a proof cancelled then completes before stopping.
+    pub fn cancel(&self) {
+        self.task.cancel();
+    }
+}
+
+impl Future for ProofTask {
+    type Output = Result<Proof, TaskError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut self.handle).poll(cx).map(|res| match res {
+            Ok(res) => res,
+            Err(e) if e.is_panic() => Err(TaskError::Panicked),
+            Err(_) => Err(TaskError::Cancelled),
+        })
+    }
+}
+
+impl Drop for ProofTask {
+    fn drop(&mut self) {
+        self.task.cancel();
+    }
+}
+
+/// Spawns a proof of the model held by `prover` over the inputs in `data` on the blocking thread pool of the current tokio
+/// runtime, such that async servers can prove without stalling their executor. Panics when called outside of a runtime.
+///
+/// ```no_run
+/// # async fn run(prover: std::sync::Arc<ezkl::serve::Prover>, data: ezkl::pfsys::ModelInput) {
+/// let task = ezkl::tasks::spawn_prove(prover, data);
+/// let mut progress = task.progress();
+/// tokio::spawn(async move {
+///     while progress.changed().await.is_ok() {
+///         println!("{:?}", *progress.borrow());
+///     }
+/// });
+/// let proof = task.await;
+/// # }
+/// ```
+pub fn spawn_prove(prover: Arc<Prover>, data: ModelInput) -> ProofTask {
+    let (sender, progress) = watch::channel(Phase::Queued);
+    let task = Task::new(move |phase| {
+        // progress is dropped once nobody listens for it
+        let _ = sender.send(phase);
+    });
+    let handle = tokio::task::spawn_blocking({
+        let task = task.clone();
+        move || {
+            if task.is_cancelled() {
+                return Err(TaskError::Cancelled);
+            }
+            Ok(prover.prove_task(&data, &task)?)
+        }
+    });
+    ProofTask {
+        task,
+        progress,
+        handle,
+    }
+}
@@ -0,0 +1,75 @@
+use super::{Tensor, TensorError, TensorType};
+use ndarray::{Array, ArrayViewD, ArrayViewMutD, Dimension, IxDyn};
+
+impl<T: TensorType> Tensor<T> {
+    /// A view of the tensor as an ndarray array, sharing its values.
+    /// ```
+    /// use ezkl::tensor::Tensor;
+    /// let a = Tensor::<i32>::new(Some(&[1, 2, 3, 4, 5, 6]), &[2, 3]).unwrap();
+    /// assert_eq!(a.view()[[1, 2]], 6);
+    /// ```
+    pub fn view(&self) -> ArrayViewD<'_, T> {
+        // the values of a tensor always fill its dimensions
+        ArrayViewD::from_shape(IxDyn(&self.dims), &self.inner).unwrap()
+    }
+
+    /// A mutable view of the tensor as an ndarray array, sharing its values.
+    pub fn view_mut(&mut self) -> ArrayViewMutD<'_, T> {
+        ArrayViewMutD::from_shape(IxDyn(&self.dims), &mut self.inner).unwrap()
+    }
+}
+
+/// Converts arrays of any dimensionality into tensors, moving their values (in logical order).
+impl<T: TensorType, D: Dimension> From<Array<T, D>> for Tensor<T> {
+    fn from(array: Array<T, D>) -> Self {
+        let dims = array.shape().to_vec();
+        Tensor {
+            inner: array.into_iter().collect(),
+            dims,
+        }
+    }
+}
+
+/// Converts tensors into arrays, moving their values. Converting into arrays of a fixed dimensionality (e.g. `Array2`) fails if
+/// the tensor is of a different rank, conversions into `ArrayD` never do.
+impl<T: TensorType, D: Dimension> TryFrom<Tensor<T>> for Array<T, D> {
+    type Error = TensorError;
+
+    fn try_from(tensor: Tensor<T>) -> Result<Self, TensorError> {
+        Array::from_shape_vec(IxDyn(&tensor.dims), tensor.inner)
+            .and_then(|array| array.into_dimensionality::<D>())
+            .map_err(|e| TensorError::DimMismatch(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::{array, Array2, Array3, ArrayD};
+
+    #[test]
+    fn converts_to_and_from_arrays() {
+        let a = Tensor::<i32>::new(Some(&[1, 2, 3, 4, 5, 6]), &[2, 3]).unwrap();
+        let array = Array2::try_from(a.clone()).unwrap();
+        assert_eq!(array, array![[1, 2, 3], [4, 5, 6]]);
+        assert!(Array3::<i32>::try_from(a.clone()).is_err());
+        assert_eq!(ArrayD::try_from(a.clone()).unwrap().shape(), &[2, 3]);
+        assert_eq!(Tensor::from(array.clone()), a);
+
+        // arrays which aren't laid out in standard order keep their logical order
+        let transposed = Tensor::from(array.reversed_axes());
+        assert_eq!(
+            transposed,
+            Tensor::new(Some(&[1, 4, 2, 5, 3, 6]), &[3, 2]).unwrap()
+        );
+    }
+
+    #[test]
+    fn views_share_values() {
+        let mut a = Tensor::<i32>::new(Some(&[1, 2, 3, 4, 5, 6]), &[2, 3]).unwrap();
+        assert_eq!(a.view().sum(), 21);
+        a.view_mut()[[0, 1]] = 7;
+        assert_eq!(a[1], 7);
+        assert_eq!(a.view().t().shape(), &[3, 2]);
+    }
+}
@@ -1,12 +1,18 @@
+/// Conversions between tensors and ndarray arrays.
+#[cfg(feature = "ndarray")]
+pub mod array;
 /// Implementations of common operations on tensors.
 pub mod ops;
 /// A wrapper around a tensor of circuit variables / advices.
 pub mod val;
 /// A wrapper around a tensor of Halo2 Value types.
 pub mod var;
+/// Strided views over the values of tensors.
+pub mod view;
 
 pub use val::*;
 pub use var::*;
+pub use view::*;
 
 use crate::fieldutils::{felt_to_i32, i32_to_felt};
 
@@ -16,7 +22,9 @@ use halo2_proofs::{
     plonk::{Advice, Assigned, Column, ConstraintSystem, Expression, Fixed, VirtualCells},
     poly::Rotation,
 };
-use itertools::Itertools;
+use rayon::prelude::*;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::cmp::max;
 use std::error::Error;
 use std::fmt::Debug;
@@ -40,7 +48,7 @@ pub enum TensorError {
 }
 
 /// The (inner) type of tensor elements.
-pub trait TensorType: Clone + Debug + 'static {
+pub trait TensorType: Clone + Debug + Send + Sync + 'static {
     /// Returns the zero value.
     fn zero() -> Option<Self> {
         None
@@ -89,6 +97,7 @@ impl TensorType for f32 {
 }
 
 tensor_type!(i32, Int32, 0);
+tensor_type!(i128, Int128, 0);
 tensor_type!(usize, USize, 0);
 tensor_type!((), Empty, ());
 
@@ -188,15 +197,140 @@ impl TensorType for halo2curves::bn256::Fr {
     }
 }
 
+/// The row major strides of a tensor of shape `dims`, i.e the distance between consecutive elements along each of its axes.
+///
+/// ```
+/// use ezkl::tensor::strides;
+/// assert_eq!(strides(&[2, 3, 1, 4, 5]), vec![60, 20, 20, 5, 1]);
+/// assert_eq!(strides(&[]), Vec::<usize>::new());
+/// ```
+pub fn strides(dims: &[usize]) -> Vec<usize> {
+    let mut strides = vec![1; dims.len()];
+    for i in (0..dims.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * dims[i + 1];
+    }
+    strides
+}
+
+/// The coordinates of the element at `index` (in row major order) of a tensor of shape `dims`.
+fn unravel(dims: &[usize], index: usize) -> Vec<usize> {
+    let mut coord = vec![0; dims.len()];
+    let mut rest = index;
+    for i in (0..dims.len()).rev() {
+        coord[i] = rest % dims[i];
+        rest /= dims[i];
+    }
+    coord
+}
+
+/// Iterates over the coordinates of the elements of a tensor of shape `dims` in row major order, for tensors of any rank
+/// (including rank 0, whose single element has no coordinates).
+///
+/// ```
+/// use ezkl::tensor::coordinates;
+/// let coords = coordinates(&[1, 2, 1, 1, 2]).collect::<Vec<_>>();
+/// assert_eq!(coords[3], vec![0, 1, 0, 0, 1]);
+/// assert_eq!(coordinates(&[]).collect::<Vec<_>>(), vec![Vec::<usize>::new()]);
+/// ```
+pub fn coordinates(dims: &[usize]) -> impl Iterator<Item = Vec<usize>> + '_ {
+    (0..dims.iter().product::<usize>()).map(move |i| unravel(dims, i))
+}
+
+/// Resolves the shape a tensor of shape `dims` is reshaped to, given as `shape` where at most one dimension may be `-1`, which
+/// is then inferred from the number of elements of the tensor. Tensors of any rank can be reshaped to shapes of any rank.
+///
+/// ```
+/// use ezkl::tensor::resolve_shape;
+/// assert_eq!(resolve_shape(&[2, 3, 4, 5, 6], &[6, -1, 2, 5]).unwrap(), vec![6, 12, 2, 5]);
+/// assert!(resolve_shape(&[2, 3], &[4, -1]).is_err());
+/// ```
+pub fn resolve_shape(dims: &[usize], shape: &[i64]) -> Result<Vec<usize>, TensorError> {
+    let len = dims.iter().product::<usize>();
+    if shape.iter().any(|d| *d == 0 || *d < -1) || shape.iter().filter(|d| **d == -1).count() > 1 {
+        return Err(TensorError::DimError);
+    }
+    let explicit = shape
+        .iter()
+        .filter(|d| **d > 0)
+        .map(|d| *d as usize)
+        .product::<usize>();
+    let inferred = match shape.contains(&-1) && explicit > 0 && len % explicit == 0 {
+        true => len / explicit,
+        false => 0,
+    };
+    let resolved = shape
+        .iter()
+        .map(|d| match d {
+            -1 => inferred,
+            d => *d as usize,
+        })
+        .collect::<Vec<_>>();
+    if resolved.iter().product::<usize>() != len {
+        return Err(TensorError::DimError);
+    }
+    Ok(resolved)
+}
+
+/// The shape tensors of shapes `a` and `b` broadcast to under numpy's rules: shapes are aligned on their trailing dimensions,
+/// missing leading dimensions are taken to be 1, and each pair of dimensions must either match or have one of them be 1.
+///
+/// ```
+/// use ezkl::tensor::broadcast_shape;
+/// assert_eq!(broadcast_shape(&[1, 3, 1, 1], &[2, 3, 4, 4]).unwrap(), vec![2, 3, 4, 4]);
+/// assert_eq!(broadcast_shape(&[3, 1, 1], &[2, 3, 4, 4]).unwrap(), vec![2, 3, 4, 4]);
+/// assert_eq!(broadcast_shape(&[5, 1], &[1, 4]).unwrap(), vec![5, 4]);
+/// assert!(broadcast_shape(&[2, 3], &[3, 2]).is_err());
+/// ```
+pub fn broadcast_shape(a: &[usize], b: &[usize]) -> Result<Vec<usize>, TensorError> {
+    let rank = a.len().max(b.len());
+    let dim = |dims: &[usize], i: usize| match i + dims.len() >= rank {
+        true => dims[i + dims.len() - rank],
+        false => 1,
+    };
+    (0..rank)
+        .map(|i| match (dim(a, i), dim(b, i)) {
+            (x, y) if x == y || y == 1 => Ok(x),
+            (1, y) => Ok(y),
+            _ => Err(TensorError::DimMismatch("broadcast".to_string())),
+        })
+        .collect()
+}
+
 /// A generic multi-dimensional array representation of a Tensor.
 /// The `inner` attribute contains a vector of values whereas `dims` corresponds to the dimensionality of the array
 /// and as such determines how we index, query for values, or slice a Tensor.
 #[derive(Clone, Debug, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawTensor<T>"))]
 pub struct Tensor<T: TensorType> {
+    #[cfg_attr(feature = "serde", serde(rename = "values"))]
     inner: Vec<T>,
     dims: Vec<usize>,
 }
 
+/// A deserialized [Tensor], whose values are yet to be checked against its dimensions.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawTensor<T> {
+    values: Vec<T>,
+    dims: Vec<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl<T: TensorType> TryFrom<RawTensor<T>> for Tensor<T> {
+    type Error = TensorError;
+
+    fn try_from(raw: RawTensor<T>) -> Result<Self, TensorError> {
+        if raw.values.len() != raw.dims.iter().product::<usize>() {
+            return Err(TensorError::DimError);
+        }
+        Ok(Tensor {
+            inner: raw.values,
+            dims: raw.dims,
+        })
+    }
+}
+
 impl<T: TensorType> IntoIterator for Tensor<T> {
     type Item = T;
     type IntoIter = ::std::vec::IntoIter<T>;
@@ -388,11 +522,23 @@ impl<T: Clone + TensorType> Tensor<T> {
         for i in 0..(self.dims.len() - indices.len()) {
             full_indices.push(0..self.dims()[indices.len() + i])
         }
-        for e in full_indices.iter().cloned().multi_cartesian_product() {
+        if full_indices
+            .iter()
+            .zip(self.dims.iter())
+            .any(|(r, d)| r.start > r.end || r.end > *d)
+        {
+            return Err(TensorError::DimError);
+        }
+        let mut dims: Vec<usize> = full_indices.iter().map(|e| e.end - e.start).collect();
+        for coord in coordinates(&dims) {
+            let e = coord
+                .iter()
+                .zip(full_indices.iter())
+                .map(|(c, r)| c + r.start)
+                .collect::<Vec<_>>();
             let index = self.get_index(&e);
             res.push(self[index].clone())
         }
-        let mut dims: Vec<usize> = full_indices.iter().map(|e| e.end - e.start).collect();
         for i in (0..indices.len()).rev() {
             if (dims[i] == 1) && (dims.len() > 1) {
                 dims.remove(i);
@@ -425,17 +571,151 @@ impl<T: Clone + TensorType> Tensor<T> {
         index
     }
 
+    /// Get the rows / columns indices from an array index, the inverse of [Tensor::get_index].
+    ///
+    /// ```
+    /// use ezkl::tensor::Tensor;
+    /// let a = Tensor::<f32>::new(None, &[2, 3, 1, 4, 2, 2]).unwrap();
+    ///
+    /// assert_eq!(a.unravel_index(a.get_index(&[1, 2, 0, 3, 1, 0])), vec![1, 2, 0, 3, 1, 0]);
+    /// ```
+    pub fn unravel_index(&self, index: usize) -> Vec<usize> {
+        assert!(index < self.len());
+        unravel(&self.dims, index)
+    }
+
+    /// The elements of the tensor within `range` along `axis`. Unlike [Tensor::get_slice], the rank of the tensor is kept, even
+    /// where the range spans a single element.
+    ///
+    /// ```
+    /// use ezkl::tensor::Tensor;
+    /// let a = Tensor::<i32>::new(Some(&[1, 2, 3, 4, 5, 6]), &[2, 3]).unwrap();
+    /// let expected = Tensor::<i32>::new(Some(&[2, 5]), &[2, 1]).unwrap();
+    /// assert_eq!(a.slice(1, 1..2).unwrap(), expected);
+    /// ```
+    pub fn slice(&self, axis: usize, range: Range<usize>) -> Result<Tensor<T>, TensorError> {
+        Ok(self.strided().slice(axis, range)?.to_tensor())
+    }
+
+    /// Splits the tensor along `axis` into consecutive tensors of `sizes` elements along the axis, the inverse of
+    /// [Tensor::concat].
+    ///
+    /// ```
+    /// use ezkl::tensor::Tensor;
+    /// let a = Tensor::<i32>::new(Some(&[1, 2, 3, 4, 5, 6]), &[2, 3]).unwrap();
+    /// let parts = a.split(1, &[1, 2]).unwrap();
+    /// assert_eq!(parts[0], Tensor::<i32>::new(Some(&[1, 4]), &[2, 1]).unwrap());
+    /// assert_eq!(parts[1], Tensor::<i32>::new(Some(&[2, 3, 5, 6]), &[2, 2]).unwrap());
+    /// assert_eq!(Tensor::concat(&parts, 1).unwrap(), a);
+    /// ```
+    pub fn split(&self, axis: usize, sizes: &[usize]) -> Result<Vec<Tensor<T>>, TensorError> {
+        if axis >= self.dims.len() || sizes.iter().sum::<usize>() != self.dims[axis] {
+            return Err(TensorError::DimError);
+        }
+        let mut start = 0;
+        sizes
+            .iter()
+            .map(|size| {
+                start += size;
+                self.slice(axis, start - size..start)
+            })
+            .collect()
+    }
+
+    /// Concatenates `tensors` along `axis`. The tensors must be of the same rank and match along every other axis.
+    ///
+    /// ```
+    /// use ezkl::tensor::Tensor;
+    /// let a = Tensor::<i32>::new(Some(&[1, 2, 3, 4]), &[2, 2]).unwrap();
+    /// let b = Tensor::<i32>::new(Some(&[5, 6]), &[1, 2]).unwrap();
+    /// let expected = Tensor::<i32>::new(Some(&[1, 2, 3, 4, 5, 6]), &[3, 2]).unwrap();
+    /// assert_eq!(Tensor::concat(&[a, b], 0).unwrap(), expected);
+    /// ```
+    pub fn concat(tensors: &[Tensor<T>], axis: usize) -> Result<Tensor<T>, TensorError> {
+        let first = tensors.first().ok_or(TensorError::DimError)?;
+        let rank = first.dims.len();
+        let matches = |t: &Tensor<T>| {
+            t.dims.len() == rank && (0..rank).all(|i| i == axis || t.dims[i] == first.dims[i])
+        };
+        if axis >= rank || !tensors.iter().all(matches) {
+            return Err(TensorError::DimMismatch("concat".to_string()));
+        }
+        let mut dims = first.dims.clone();
+        dims[axis] = tensors.iter().map(|t| t.dims[axis]).sum();
+        let values = coordinates(&dims)
+            .map(|mut coord| {
+                let mut i = 0;
+                while coord[axis] >= tensors[i].dims[axis] {
+                    coord[axis] -= tensors[i].dims[axis];
+                    i += 1;
+                }
+                tensors[i][tensors[i].get_index(&coord)].clone()
+            })
+            .collect::<Vec<_>>();
+        Tensor::new(Some(&values), &dims)
+    }
+
+    /// Pads the tensor with `value`, with `padding[i].0` elements before and `padding[i].1` after those of the tensor along
+    /// each axis `i`.
+    ///
+    /// ```
+    /// use ezkl::tensor::Tensor;
+    /// let a = Tensor::<i32>::new(Some(&[1, 2]), &[1, 2]).unwrap();
+    /// let expected = Tensor::<i32>::new(Some(&[0, 1, 2, 0, 0, 0, 0, 0]), &[2, 4]).unwrap();
+    /// assert_eq!(a.pad(&[(0, 1), (1, 1)], 0).unwrap(), expected);
+    /// ```
+    pub fn pad(&self, padding: &[(usize, usize)], value: T) -> Result<Tensor<T>, TensorError> {
+        if padding.len() != self.dims.len() {
+            return Err(TensorError::DimMismatch("pad".to_string()));
+        }
+        let dims = self
+            .dims
+            .iter()
+            .zip(padding)
+            .map(|(d, (before, after))| before + d + after)
+            .collect::<Vec<_>>();
+        let mut output = Tensor::new(Some(&vec![value; dims.iter().product()]), &dims)?;
+        for (i, coord) in coordinates(&self.dims).enumerate() {
+            let padded = coord
+                .iter()
+                .zip(padding)
+                .map(|(c, (before, _))| c + before)
+                .collect::<Vec<_>>();
+            output.set(&padded, self[i].clone());
+        }
+        Ok(output)
+    }
+
+    /// Broadcasts the tensor to the shape `dims` (see [broadcast_shape]), repeating its elements along the dimensions it has
+    /// of size 1, or lacks.
+    ///
+    /// ```
+    /// use ezkl::tensor::Tensor;
+    /// let bias = Tensor::<i32>::new(Some(&[1, 2]), &[1, 2, 1]).unwrap();
+    /// let expected = Tensor::<i32>::new(Some(&[1, 1, 1, 2, 2, 2, 1, 1, 1, 2, 2, 2]), &[2, 2, 3]).unwrap();
+    /// assert_eq!(bias.expand(&[2, 2, 3]).unwrap(), expected);
+    /// assert!(bias.expand(&[2, 3, 3]).is_err());
+    /// ```
+    pub fn expand(&self, dims: &[usize]) -> Result<Tensor<T>, TensorError> {
+        if self.dims == dims {
+            return Ok(self.clone());
+        }
+        Ok(self.strided().expand(dims)?.to_tensor())
+    }
+
     /// Returns the tensor's dimensions.
     pub fn dims(&self) -> &[usize] {
         &self.dims
     }
 
-    ///Reshape the tensor
+    ///Reshape the tensor, to a shape of any rank holding as many elements
     /// ```
     /// use ezkl::tensor::Tensor;
     /// let mut a = Tensor::<f32>::new(None, &[3, 3, 3]).unwrap();
     /// a.reshape(&[9, 3]);
     /// assert_eq!(a.dims(), &[9, 3]);
+    /// a.reshape(&[1, 3, 1, 3, 3, 1]);
+    /// assert_eq!(a.dims(), &[1, 3, 1, 3, 3, 1]);
     /// ```
     pub fn reshape(&mut self, new_dims: &[usize]) {
         assert!(self.len() == new_dims.iter().product::<usize>());
@@ -466,6 +746,20 @@ impl<T: Clone + TensorType> Tensor<T> {
         t
     }
 
+    /// Maps a function to tensors in parallel, over the threads of rayon's global pool
+    /// ```
+    /// use ezkl::tensor::Tensor;
+    /// let mut a = Tensor::<i32>::new(Some(&[1, 4]), &[2]).unwrap();
+    /// let mut c = a.par_map(|x| i32::pow(x,2));
+    /// assert_eq!(c, Tensor::from([1, 16].into_iter()))
+    /// ```
+    pub fn par_map<F: Fn(T) -> G + Send + Sync, G: TensorType>(&self, f: F) -> Tensor<G> {
+        let inner: Vec<G> = self.inner.par_iter().map(|e| f(e.clone())).collect();
+        let mut t = Tensor::from(inner.into_iter());
+        t.reshape(self.dims());
+        t
+    }
+
     /// Maps a function to tensors and enumerates
     /// ```
     /// use ezkl::tensor::{Tensor, TensorError};
@@ -499,13 +793,9 @@ impl<T: Clone + TensorType> Tensor<T> {
         &self,
         mut f: F,
     ) -> Result<Tensor<G>, TensorError> {
-        let mut indices = Vec::new();
-        for i in self.dims.clone() {
-            indices.push(0..i);
-        }
         let mut res = Vec::new();
-        for coord in indices.iter().cloned().multi_cartesian_product() {
-            res.push(f(&coord, self.get(&coord)));
+        for (coord, e) in coordinates(&self.dims).zip(self.inner.iter()) {
+            res.push(f(&coord, e.clone()));
         }
 
         Tensor::new(Some(&res), self.dims())
@@ -570,4 +860,61 @@ mod tests {
         let b = Tensor::<i32>::new(Some(&[1, 4]), &[2]).unwrap();
         assert_eq!(a.get_slice(&[0..2, 0..1]).unwrap(), b);
     }
+
+    #[test]
+    fn tensor_index_math_of_high_rank() {
+        let dims = [2, 3, 1, 4, 2, 3];
+        let a = Tensor::<i32>::new(Some(&(0..144).collect::<Vec<_>>()), &dims).unwrap();
+        for (i, coord) in coordinates(&dims).enumerate() {
+            assert_eq!(a.get_index(&coord), i);
+            assert_eq!(a.unravel_index(i), coord);
+            assert_eq!(a.get(&coord), i as i32);
+        }
+        assert_eq!(strides(&dims), vec![72, 24, 24, 6, 3, 1]);
+        assert_eq!(a.get(&[1, 2, 0, 3, 1, 2]), 72 + 2 * 24 + 3 * 6 + 3 + 2);
+
+        // slices of 6D tensors drop the leading axes indexed down to a single element
+        let slice = a.get_slice(&[1..2, 0..3, 0..1, 2..4]).unwrap();
+        assert_eq!(slice.dims(), &[3, 2, 2, 3]);
+        assert_eq!(slice.get(&[2, 1, 0, 1]), a.get(&[1, 2, 0, 3, 0, 1]));
+        assert!(a.get_slice(&[0..3]).is_err());
+
+        let mapped = a.mc_enum_map(|coord, x| x - coord[5] as i32).unwrap();
+        assert_eq!(mapped.dims(), &dims);
+        assert_eq!(
+            mapped.get(&[1, 2, 0, 3, 1, 2]),
+            a.get(&[1, 2, 0, 3, 1, 2]) - 2
+        );
+
+        // rank 0 tensors hold a single element
+        let scalar = Tensor::<i32>::new(Some(&[7]), &[]).unwrap();
+        assert_eq!(scalar.get(&[]), 7);
+        assert_eq!(scalar.get_slice(&[]).unwrap(), scalar);
+    }
+
+    #[test]
+    fn tensors_are_reshaped_across_ranks() {
+        let mut a =
+            Tensor::<i32>::new(Some(&(0..720).collect::<Vec<_>>()), &[2, 3, 4, 5, 6]).unwrap();
+        let shape = resolve_shape(a.dims(), &[1, 6, -1, 2, 3, 5]).unwrap();
+        assert_eq!(shape, vec![1, 6, 4, 2, 3, 5]);
+        a.reshape(&shape);
+        assert_eq!(a.get(&[0, 5, 3, 1, 2, 4]), 719);
+        assert_eq!(resolve_shape(a.dims(), &[-1]).unwrap(), vec![720]);
+        // at most one dimension is inferred, and it must divide the tensor evenly
+        assert!(resolve_shape(a.dims(), &[-1, -1]).is_err());
+        assert!(resolve_shape(a.dims(), &[7, -1]).is_err());
+        assert!(resolve_shape(a.dims(), &[2, 3]).is_err());
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    #[test]
+    fn tensor_serde() {
+        let a = Tensor::<i32>::new(Some(&[1, 2, 3, 4, 5, 6]), &[2, 3]).unwrap();
+        let json = serde_json::to_string(&a).unwrap();
+        assert_eq!(json, r#"{"values":[1,2,3,4,5,6],"dims":[2,3]}"#);
+        assert_eq!(serde_json::from_str::<Tensor<i32>>(&json).unwrap(), a);
+        // values which don't fill the dimensions are rejected
+        assert!(serde_json::from_str::<Tensor<i32>>(r#"{"values":[1,2],"dims":[2,3]}"#).is_err());
+    }
 }
@@ -1,8 +1,68 @@
 use super::TensorError;
-use crate::tensor::{Tensor, TensorType};
+use crate::tensor::{broadcast_shape, coordinates, Tensor, TensorType};
 use itertools::Itertools;
+use rayon::prelude::*;
 pub use std::ops::{Add, Div, Mul, Sub};
 
+/// Computes each element of a tensor of shape `dims` from its coordinates, in parallel over the threads of rayon's global pool.
+fn par_from_coords<T: TensorType, F: Fn(&[usize]) -> Result<T, TensorError> + Send + Sync>(
+    dims: &[usize],
+    f: F,
+) -> Result<Tensor<T>, TensorError> {
+    let coords = coordinates(dims).collect_vec();
+    let values = coords
+        .par_iter()
+        .map(|coord| f(coord))
+        .collect::<Result<Vec<T>, TensorError>>()?;
+    Tensor::new(Some(&values), dims)
+}
+
+/// The number of rows of the output each task of [par_matmul_t] computes, sharing the block of columns in cache.
+const ROW_BLOCK: usize = 8;
+/// The number of columns of the output [par_matmul_t] computes at a time, whose (transposed) values stay in cache across the
+/// rows of a task.
+const COL_BLOCK: usize = 64;
+
+/// Multiplies the `[m, n]` matrix `a` by the `[n, p]` matrix `b`, given transposed as `b_t` such that each dot product runs
+/// over contiguous memory. The output is computed in blocks of rows, in parallel over the threads of rayon's global pool, each
+/// block sweeping over blocks of columns of `b` which stay in cache across its rows. Returns the products in row major order.
+fn par_matmul_t<T: TensorType + Mul<Output = T> + Add<Output = T>>(
+    a: &[T],
+    b_t: &[T],
+    (m, n, p): (usize, usize, usize),
+) -> Vec<T> {
+    let zero = T::zero().unwrap();
+    let mut output = vec![zero.clone(); m * p];
+    if output.is_empty() {
+        return output;
+    }
+    output
+        .par_chunks_mut(ROW_BLOCK * p)
+        .enumerate()
+        .for_each(|(block, rows)| {
+            for cols in (0..p).step_by(COL_BLOCK) {
+                for (r, row) in rows.chunks_mut(p).enumerate() {
+                    let a_i = &a[(block * ROW_BLOCK + r) * n..][..n];
+                    for j in cols..std::cmp::min(cols + COL_BLOCK, p) {
+                        let b_j = &b_t[j * n..][..n];
+                        row[j] = a_i
+                            .iter()
+                            .zip(b_j)
+                            .fold(zero.clone(), |acc, (x, y)| acc + x.clone() * y.clone());
+                    }
+                }
+            }
+        });
+    output
+}
+
+/// Transposes the `[n, p]` matrix `b` into a `[p, n]` one.
+fn transpose<T: TensorType>(b: &[T], n: usize, p: usize) -> Vec<T> {
+    (0..p)
+        .flat_map(|j| (0..n).map(move |k| b[k * p + j].clone()))
+        .collect()
+}
+
 /// Matrix multiplies two 2D tensors (and adds an offset).
 /// # Arguments
 ///
@@ -44,21 +104,14 @@ pub fn affine<T: TensorType + Mul<Output = T> + Add<Output = T>>(
         input.reshape(&[input.dims()[0], 1])
     }
 
-    let input_dims = input.dims();
-    let kernel_dims = kernel.dims();
-
-    // calculate value of output
-    let mut output: Tensor<T> = Tensor::new(None, &[kernel_dims[0], input_dims[1]]).unwrap();
-
-    for i in 0..kernel_dims[0] {
-        for j in 0..input_dims[1] {
-            let prod = dot(&vec![
-                &kernel.get_slice(&[i..i + 1])?,
-                &input.get_slice(&[0..input_dims[0], j..j + 1])?,
-            ])?;
-            output.set(&[i, j], prod[0].clone() + bias[i].clone());
+    let (m, n, p) = (kernel.dims()[0], input.dims()[0], input.dims()[1]);
+    let mut products = par_matmul_t(&kernel, &transpose(&input, n, p), (m, n, p));
+    for (i, row) in products.chunks_mut(p.max(1)).enumerate() {
+        for value in row {
+            *value = value.clone() + bias[i].clone();
         }
     }
+    let mut output = Tensor::new(Some(&products), &[m, p])?;
     // does matrix to vector multiplication
     if output.dims()[1] == 1 {
         output.flatten();
@@ -133,37 +186,30 @@ pub fn scale_and_shift<T: TensorType + Mul<Output = T> + Add<Output = T>>(
 pub fn matmul<T: TensorType + Mul<Output = T> + Add<Output = T>>(
     inputs: &Vec<Tensor<T>>,
 ) -> Result<Tensor<T>, TensorError> {
-    let (a, b) = (inputs[0].clone(), inputs[1].clone());
+    let (a, b) = (&inputs[0], &inputs[1]);
+    let rank = a.dims().len();
     if (inputs.len() != 2)
-        || (a.dims()[a.dims().len() - 1] != b.dims()[a.dims().len() - 2])
-        || (a.dims()[0..a.dims().len() - 2] != b.dims()[0..a.dims().len() - 2])
+        || rank < 2
+        || (b.dims().len() != rank)
+        || (a.dims()[rank - 1] != b.dims()[rank - 2])
+        || (a.dims()[0..rank - 2] != b.dims()[0..rank - 2])
     {
         return Err(TensorError::DimMismatch("matmul".to_string()));
     }
 
-    let mut dims = Vec::from(&a.dims()[0..a.dims().len() - 2]);
-    dims.push(a.dims()[a.dims().len() - 2]);
-    dims.push(b.dims()[a.dims().len() - 1]);
-    // calculate value of output
-    let mut output: Tensor<T> = Tensor::new(None, &dims).unwrap();
-
-    let indices = dims.iter().map(|d| 0..*d).collect::<Vec<_>>();
-
-    for coord in indices.iter().cloned().multi_cartesian_product() {
-        let row = coord[0..coord.len() - 1]
-            .iter()
-            .map(|&d| d..(d + 1))
-            .collect::<Vec<_>>();
-        let mut col = coord[0..coord.len()]
-            .iter()
-            .map(|&d| d..(d + 1))
-            .collect::<Vec<_>>();
-        col[coord.len() - 2] = 0..b.dims()[coord.len() - 2];
-        let prod = dot(&vec![&a.get_slice(&row[0..])?, &b.get_slice(&col[0..])?])?;
-        output.set(&coord, prod[0].clone());
+    let (m, n, p) = (a.dims()[rank - 2], a.dims()[rank - 1], b.dims()[rank - 1]);
+    let mut dims = Vec::from(&a.dims()[0..rank - 2]);
+    dims.push(m);
+    dims.push(p);
+    // each matrix of the leading dimensions is multiplied in turn
+    let batches = a.dims()[0..rank - 2].iter().product::<usize>();
+    let mut values = Vec::with_capacity(batches * m * p);
+    for batch in 0..batches {
+        let a_batch = &a[batch * m * n..][..m * n];
+        let b_batch = &b[batch * n * p..][..n * p];
+        values.extend(par_matmul_t(a_batch, &transpose(b_batch, n, p), (m, n, p)));
     }
-
-    Ok(output)
+    Tensor::new(Some(&values), &dims)
 }
 
 /// Adds multiple tensors.
@@ -191,11 +237,7 @@ pub fn add<T: TensorType + Add<Output = T>>(t: &Vec<Tensor<T>>) -> Result<Tensor
     if t.len() == 2 && t[1].dims().len() == 1 && t[1].dims()[0] == 1 {
         return const_add(&t[0], t[1][0].clone());
     }
-    for e in t.iter() {
-        if t[0].dims() != e.dims() {
-            return Err(TensorError::DimMismatch("add".to_string()));
-        }
-    }
+    let t = broadcast(t, "add")?;
     // calculate value of output
     let mut output: Tensor<T> = t[0].clone();
 
@@ -208,6 +250,16 @@ pub fn add<T: TensorType + Add<Output = T>>(t: &Vec<Tensor<T>>) -> Result<Tensor
     Ok(output)
 }
 
+/// Broadcasts the tensors `t` to their common shape (see [broadcast_shape]), such that the elementwise ops accept e.g a
+/// `[1, C, 1, 1]` bias alongside an `[N, C, H, W]` activation, as ONNX does.
+fn broadcast<T: TensorType>(t: &[Tensor<T>], op: &str) -> Result<Vec<Tensor<T>>, TensorError> {
+    let dims = t
+        .iter()
+        .try_fold(vec![], |dims, e| broadcast_shape(&dims, e.dims()));
+    let dims = dims.map_err(|_| TensorError::DimMismatch(op.to_string()))?;
+    t.iter().map(|e| e.expand(&dims)).collect()
+}
+
 /// Elementwise adds a tensor with a const element.
 /// # Arguments
 ///
@@ -267,11 +319,7 @@ pub fn sub<T: TensorType + Sub<Output = T>>(t: &Vec<Tensor<T>>) -> Result<Tensor
         return const_sub(&t[0], t[1][0].clone());
     }
 
-    for e in t.iter() {
-        if t[0].dims() != e.dims() {
-            return Err(TensorError::DimMismatch("sub".to_string()));
-        }
-    }
+    let t = broadcast(t, "sub")?;
     // calculate value of output
     let mut output: Tensor<T> = t[0].clone();
 
@@ -343,11 +391,7 @@ pub fn mult<T: TensorType + Mul<Output = T>>(t: &Vec<Tensor<T>>) -> Result<Tenso
         return const_mult(&t[0], t[1][0].clone());
     }
 
-    for e in t.iter() {
-        if t[0].dims() != e.dims() {
-            return Err(TensorError::DimMismatch("mult".to_string()));
-        }
-    }
+    let t = broadcast(t, "mult")?;
     // calculate value of output
     let mut output: Tensor<T> = t[0].clone();
 
@@ -582,34 +626,38 @@ pub fn convolution<T: TensorType + Mul<Output = T> + Add<Output = T>>(
     let vert_slides = (image_height + 2 * padding.0 - kernel_height) / stride.0 + 1;
     let horz_slides = (image_width + 2 * padding.1 - kernel_width) / stride.1 + 1;
 
-    // calculate value of output
-    let mut output: Tensor<T> =
-        Tensor::new(None, &[output_channels, vert_slides, horz_slides]).unwrap();
-
-    for i in 0..output_channels {
-        for j in 0..vert_slides {
-            let rs = j * stride.0;
-            for k in 0..horz_slides {
-                let cs = k * stride.1;
-                let mut res = dot(&vec![
-                    &kernel.get_slice(&[i..i + 1])?.clone(),
-                    &padded_image.get_slice(&[
-                        0..input_channels,
-                        rs..(rs + kernel_height),
-                        cs..(cs + kernel_width),
-                    ])?,
-                ])?;
-
-                if has_bias {
-                    // increment result by the bias
-                    res[0] = res[0].clone() + inputs[2][i].clone();
+    // the patch of the image under the kernel at each output position is laid out contiguously (im2col), in the order of the
+    // values of the kernel, such that the convolution is a matrix product of the kernel and the patches
+    let (padded_height, padded_width) = (padded_image.dims()[1], padded_image.dims()[2]);
+    let patch_len = input_channels * kernel_height * kernel_width;
+    let positions = vert_slides * horz_slides;
+    let mut patches = vec![T::zero().unwrap(); positions * patch_len];
+    if !patches.is_empty() {
+        patches
+            .par_chunks_mut(patch_len)
+            .enumerate()
+            .for_each(|(position, patch)| {
+                let rs = (position / horz_slides) * stride.0;
+                let cs = (position % horz_slides) * stride.1;
+                let coords = (0..input_channels)
+                    .cartesian_product(0..kernel_height)
+                    .cartesian_product(0..kernel_width);
+                for (value, ((c, ki), kj)) in patch.iter_mut().zip(coords) {
+                    *value = padded_image[(c * padded_height + rs + ki) * padded_width + cs + kj]
+                        .clone();
                 }
-
-                output.set(&[i, j, k], res[0].clone());
+            });
+    }
+    let mut values = par_matmul_t(&kernel, &patches, (output_channels, patch_len, positions));
+    if has_bias {
+        // increment each output channel by its bias
+        for (i, channel) in values.chunks_mut(positions.max(1)).enumerate() {
+            for value in channel {
+                *value = value.clone() + inputs[2][i].clone();
             }
         }
     }
-    Ok(output)
+    Tensor::new(Some(&values), &[output_channels, vert_slides, horz_slides])
 }
 
 /// Applies 2D sum pooling over a 3D tensor of shape C x H x W.
@@ -860,15 +908,11 @@ pub mod activations {
     /// ```
     pub fn sigmoid(a: &Tensor<i32>, scale_input: usize, scale_output: usize) -> Tensor<i32> {
         // calculate value of output
-        let mut output: Tensor<i32> = a.clone();
-
-        for (i, a_i) in a.iter().enumerate() {
-            let kix = (*a_i as f32) / (scale_input as f32);
+        a.par_map(|a_i| {
+            let kix = (a_i as f32) / (scale_input as f32);
             let fout = (scale_output as f32) / (1.0 + (-kix).exp());
-            let rounded = fout.round();
-            output[i] = rounded as i32;
-        }
-        output
+            fout.round() as i32
+        })
     }
 
     /// Elementwise applies leaky relu to a tensor of integers.
@@ -891,18 +935,15 @@ pub mod activations {
     /// ```
     pub fn leakyrelu(a: &Tensor<i32>, scale: usize, slope: f32) -> Tensor<i32> {
         // calculate value of output
-        let mut output: Tensor<i32> = a.clone();
-
-        for (i, a_i) in a.iter().enumerate() {
-            output[i] = if a_i < &0 {
-                let d_inv_x = (slope) * (*a_i as f32) / (scale as f32);
+        a.par_map(|a_i| {
+            if a_i < 0 {
+                let d_inv_x = (slope) * (a_i as f32) / (scale as f32);
                 d_inv_x.round() as i32
             } else {
-                let d_inv_x = (*a_i as f32) / (scale as f32);
+                let d_inv_x = (a_i as f32) / (scale as f32);
                 d_inv_x.round() as i32
-            };
-        }
-        output
+            }
+        })
     }
 
     /// Elementwise applies prelu to a tensor of integers.
@@ -930,20 +971,18 @@ pub mod activations {
             // assert number of slopes is equal to number of channels
             assert_eq!(slopes.len(), a.dims()[0])
         }
-        // calculate value of output
-        let mut output: Tensor<i32> = a.clone();
-
-        for (i, a_i) in a.iter().enumerate() {
-            output[i] = if a_i < &0 {
-                let slope_i: f32 = slopes[i / (a.dims()[1..].iter().product::<usize>())];
-                let d_inv_x = (slope_i) * (*a_i as f32) / (scale as f32);
+        // calculate value of output, each channel having its own slope
+        par_from_coords(a.dims(), |coord| {
+            let a_i = a.get(coord);
+            Ok(if a_i < 0 {
+                let d_inv_x = (slopes[coord[0]]) * (a_i as f32) / (scale as f32);
                 d_inv_x.round() as i32
             } else {
-                let d_inv_x = (*a_i as f32) / (scale as f32);
+                let d_inv_x = (a_i as f32) / (scale as f32);
                 d_inv_x.round() as i32
-            };
-        }
-        output
+            })
+        })
+        .unwrap()
     }
 
     /// Elementwise divides a tensor with a const integer element.
@@ -966,13 +1005,83 @@ pub mod activations {
     /// ```
     pub fn const_div(a: &Tensor<i32>, scale: i32) -> Tensor<i32> {
         // calculate value of output
-        // calculate value of output
-        let mut output: Tensor<i32> = a.clone();
+        a.par_map(|a_i| {
+            let d_inv_x = (a_i as f32) / (scale as f32);
+            d_inv_x.round() as i32
+        })
+    }
+}
 
-        for (i, a_i) in a.iter().enumerate() {
-            let d_inv_x = (*a_i as f32) / (scale as f32);
-            output[i] = d_inv_x.round() as i32;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocked_matmul_matches_naive_products() {
+        // spans several row and column blocks, neither of which divides the output evenly
+        let (m, n, p) = (ROW_BLOCK * 2 + 3, 5, COL_BLOCK + 7);
+        let a = (0..m * n).map(|i| (i % 7) as i32 - 3).collect_vec();
+        let b = (0..n * p).map(|i| (i % 5) as i32 - 2).collect_vec();
+        let products = par_matmul_t(&a, &transpose(&b, n, p), (m, n, p));
+        for (i, j) in (0..m).cartesian_product(0..p) {
+            let expected: i32 = (0..n).map(|k| a[i * n + k] * b[k * p + j]).sum();
+            assert_eq!(products[i * p + j], expected);
+        }
+    }
+
+    #[test]
+    fn strided_convolution_matches_naive_sums() {
+        let image = Tensor::<i32>::new(
+            Some(&(0..2 * 5 * 4).map(|i| (i % 9) - 4).collect_vec()),
+            &[2, 5, 4],
+        )
+        .unwrap();
+        let kernel = Tensor::<i32>::new(
+            Some(&(0..3 * 2 * 2 * 3).map(|i| (i % 4) - 1).collect_vec()),
+            &[3, 2, 2, 3],
+        )
+        .unwrap();
+        let bias = Tensor::<i32>::new(Some(&[1, -2, 3]), &[3]).unwrap();
+        let output =
+            convolution(&vec![image.clone(), kernel.clone(), bias], (1, 0), (2, 1)).unwrap();
+        assert_eq!(output.dims(), &[3, 3, 2]);
+
+        let padded = pad(image, (1, 0)).unwrap();
+        for ((o, r), c) in (0..3).cartesian_product(0..3).cartesian_product(0..2) {
+            let mut expected = [1, -2, 3][o];
+            for ((ic, ki), kj) in (0..2).cartesian_product(0..2).cartesian_product(0..3) {
+                expected += kernel.get(&[o, ic, ki, kj]) * padded.get(&[ic, r * 2 + ki, c + kj]);
+            }
+            assert_eq!(output.get(&[o, r, c]), expected);
+        }
+    }
+
+    #[test]
+    fn elementwise_ops_broadcast() {
+        let (n, c, h, w) = (2, 3, 2, 2);
+        let x = Tensor::<i32>::new(
+            Some(&(0..n * c * h * w).map(|i| i as i32).collect_vec()),
+            &[n, c, h, w],
+        )
+        .unwrap();
+        let bias = Tensor::<i32>::new(Some(&[10, 20, 30]), &[1, c, 1, 1]).unwrap();
+        for (res, f) in [
+            (add(&vec![x.clone(), bias.clone()]).unwrap(), 1),
+            (add(&vec![bias.clone(), x.clone()]).unwrap(), 1),
+            (sub(&vec![x.clone(), bias.clone()]).unwrap(), -1),
+        ] {
+            assert_eq!(res.dims(), &[n, c, h, w]);
+            for (i, coord) in coordinates(&[n, c, h, w]).enumerate() {
+                assert_eq!(res[i], x[i] + f * bias[coord[1]]);
+            }
+        }
+        // lower rank operands are aligned on their trailing dims
+        let scale = Tensor::<i32>::new(Some(&[2, 3]), &[w]).unwrap();
+        let res = mult(&vec![x.clone(), scale]).unwrap();
+        for (i, coord) in coordinates(&[n, c, h, w]).enumerate() {
+            assert_eq!(res[i], x[i] * [2, 3][coord[3]]);
         }
-        output
+        let other = Tensor::<i32>::new(None, &[n, 2, h, w]).unwrap();
+        assert!(add(&vec![x, other]).is_err());
     }
 }
@@ -72,6 +72,26 @@ impl<F: FieldExt + TensorType> ValTensor<F> {
         ValTensor::Instance { inner: col, dims }
     }
 
+    /// Fetches the known field element values of the [ValTensor]. Values which are unknown (e.g during key generation)
+    /// are skipped, so the returned (flat) [Tensor] is empty if no values have been witnessed.
+    pub fn get_felt_evals(&self) -> Result<Tensor<F>, Box<dyn Error>> {
+        let mut felt_evals: Vec<F> = vec![];
+        match self {
+            ValTensor::Value { inner: v, .. } => {
+                let _ = v.map(|vaf| vaf.map(|f| felt_evals.push(f)));
+            }
+            ValTensor::AssignedValue { inner: v, .. } => {
+                let _ = v.map(|vaf| vaf.map(|f| felt_evals.push(f.evaluate())));
+            }
+            ValTensor::PrevAssigned { inner: v, .. } => {
+                let _ = v.map(|acaf| acaf.value().map(|f| felt_evals.push(*f)));
+            }
+            ValTensor::Instance { .. } => return Err(Box::new(TensorError::WrongMethod)),
+        };
+        let len = felt_evals.len();
+        Ok(Tensor::new(Some(&felt_evals), &[len])?)
+    }
+
     /// Calls `get_slice` on the inner tensor.
     pub fn get_slice(&self, indices: &[Range<usize>]) -> Result<ValTensor<F>, Box<dyn Error>> {
         let slice = match self {
@@ -101,6 +121,89 @@ impl<F: FieldExt + TensorType> ValTensor<F> {
         Ok(slice)
     }
 
+    /// The elements within `range` along `axis` (see [Tensor::slice]). Assigned cells are kept, such that laying out the slice
+    /// copies them along with their constraints.
+    pub fn slice(&self, axis: usize, range: Range<usize>) -> Result<ValTensor<F>, Box<dyn Error>> {
+        Ok(match self {
+            ValTensor::Value { inner: v, .. } => v.slice(axis, range)?.into(),
+            ValTensor::AssignedValue { inner: v, .. } => v.slice(axis, range)?.into(),
+            ValTensor::PrevAssigned { inner: v, .. } => v.slice(axis, range)?.into(),
+            ValTensor::Instance { .. } => return Err(Box::new(TensorError::WrongMethod)),
+        })
+    }
+
+    /// Splits the [ValTensor] along `axis` into consecutive tensors of `sizes` elements along the axis (see [Tensor::split]).
+    pub fn split(&self, axis: usize, sizes: &[usize]) -> Result<Vec<ValTensor<F>>, Box<dyn Error>> {
+        Ok(match self {
+            ValTensor::Value { inner: v, .. } => v
+                .split(axis, sizes)?
+                .into_iter()
+                .map(|t| t.into())
+                .collect(),
+            ValTensor::AssignedValue { inner: v, .. } => v
+                .split(axis, sizes)?
+                .into_iter()
+                .map(|t| t.into())
+                .collect(),
+            ValTensor::PrevAssigned { inner: v, .. } => v
+                .split(axis, sizes)?
+                .into_iter()
+                .map(|t| t.into())
+                .collect(),
+            ValTensor::Instance { .. } => return Err(Box::new(TensorError::WrongMethod)),
+        })
+    }
+
+    /// Concatenates `tensors` along `axis` (see [Tensor::concat]). The tensors must be of the same kind, tensors mixing
+    /// assigned cells with values (e.g an activation and a constant) are laid out with [VarTensor::assign_concat] instead.
+    pub fn concat(tensors: &[ValTensor<F>], axis: usize) -> Result<ValTensor<F>, Box<dyn Error>> {
+        fn inners<F: FieldExt + TensorType, T: TensorType>(
+            tensors: &[ValTensor<F>],
+            f: impl Fn(&ValTensor<F>) -> Option<&Tensor<T>>,
+        ) -> Result<Vec<Tensor<T>>, TensorError> {
+            tensors
+                .iter()
+                .map(|t| f(t).cloned().ok_or(TensorError::WrongMethod))
+                .collect()
+        }
+        Ok(match tensors.first() {
+            Some(ValTensor::Value { .. }) => {
+                let inner = inners(tensors, |t| match t {
+                    ValTensor::Value { inner, .. } => Some(inner),
+                    _ => None,
+                })?;
+                Tensor::concat(&inner, axis)?.into()
+            }
+            Some(ValTensor::AssignedValue { .. }) => {
+                let inner = inners(tensors, |t| match t {
+                    ValTensor::AssignedValue { inner, .. } => Some(inner),
+                    _ => None,
+                })?;
+                Tensor::concat(&inner, axis)?.into()
+            }
+            Some(ValTensor::PrevAssigned { .. }) => {
+                let inner = inners(tensors, |t| match t {
+                    ValTensor::PrevAssigned { inner, .. } => Some(inner),
+                    _ => None,
+                })?;
+                Tensor::concat(&inner, axis)?.into()
+            }
+            _ => return Err(Box::new(TensorError::WrongMethod)),
+        })
+    }
+
+    /// Pads the values of the [ValTensor] with zeros (see [Tensor::pad]). Assigned cells can't be padded without laying out
+    /// the zeros, which [VarTensor::assign_padded] does.
+    pub fn pad(&self, padding: &[(usize, usize)]) -> Result<ValTensor<F>, Box<dyn Error>> {
+        Ok(match self {
+            ValTensor::Value { inner: v, .. } => v.pad(padding, Value::known(F::zero()))?.into(),
+            ValTensor::AssignedValue { inner: v, .. } => {
+                v.pad(padding, Value::known(F::zero().into()))?.into()
+            }
+            _ => return Err(Box::new(TensorError::WrongMethod)),
+        })
+    }
+
     /// Sets the [ValTensor]'s shape.
     pub fn reshape(&mut self, new_dims: &[usize]) -> Result<(), Box<dyn Error>> {
         match self {
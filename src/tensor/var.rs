@@ -173,6 +173,70 @@ impl VarTensor {
             VarTensor::Advice { capacity, .. } | VarTensor::Fixed { capacity, .. } => *capacity,
         }
     }
+
+    /// Returns the `col_size` attribute of the `VarTensor`.
+    pub fn col_size(&self) -> usize {
+        match self {
+            VarTensor::Advice { col_size, .. } | VarTensor::Fixed { col_size, .. } => *col_size,
+        }
+    }
+
+    /// The same columns, spanning `col_size` rows each, e.g to spread values evenly over the columns they need.
+    pub fn with_col_size(&self, col_size: usize) -> Self {
+        match self {
+            VarTensor::Advice {
+                inner,
+                capacity,
+                dims,
+                ..
+            } => VarTensor::Advice {
+                inner: inner.clone(),
+                col_size,
+                capacity: *capacity,
+                dims: dims.clone(),
+            },
+            VarTensor::Fixed {
+                inner,
+                capacity,
+                dims,
+                ..
+            } => VarTensor::Fixed {
+                inner: inner.clone(),
+                col_size,
+                capacity: *capacity,
+                dims: dims.clone(),
+            },
+        }
+    }
+
+    /// The columns of the `VarTensor` from column `offset` on, such that values laid out in them don't share columns with
+    /// values laid out in the first `offset` columns.
+    pub fn skip_cols(&self, offset: usize) -> Self {
+        match self {
+            VarTensor::Advice {
+                inner,
+                col_size,
+                capacity,
+                dims,
+            } => VarTensor::Advice {
+                inner: inner[offset..].to_vec(),
+                col_size: *col_size,
+                capacity: capacity.saturating_sub(offset * col_size),
+                dims: dims.clone(),
+            },
+            VarTensor::Fixed {
+                inner,
+                col_size,
+                capacity,
+                dims,
+            } => VarTensor::Fixed {
+                inner: inner[offset..].to_vec(),
+                col_size: *col_size,
+                capacity: capacity.saturating_sub(offset * col_size),
+                dims: dims.clone(),
+            },
+        }
+    }
 }
 
 impl VarTensor {
@@ -278,4 +342,145 @@ impl VarTensor {
             }),
         }
     }
+
+    /// Lays out the concatenation of `values` along `axis` (see [Tensor::concat]) from `offset` on, each tensor following the
+    /// last. The tensors may mix assigned cells, which are copied along with their constraints, with values.
+    pub fn assign_concat<F: FieldExt + TensorType>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        values: &[ValTensor<F>],
+        axis: usize,
+    ) -> Result<Tensor<AssignedCell<F, F>>, halo2_proofs::plonk::Error> {
+        let mut parts = vec![];
+        let mut start = offset;
+        for value in values {
+            parts.push(self.assign(region, start, value)?);
+            start += value.dims().iter().product::<usize>();
+        }
+        Tensor::concat(&parts, axis).map_err(|_| halo2_proofs::plonk::Error::Synthesis)
+    }
+
+    /// Lays out `values` from `offset` on, padded with zeros (see [Tensor::pad]). The padding is a single cell following the
+    /// values, constrained to zero: from the constants of the circuit for advices, which requires a column enabled for
+    /// constants (see `ConstraintSystem::enable_constant`), or as a fixed cell.
+    pub fn assign_padded<F: FieldExt + TensorType>(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        values: &ValTensor<F>,
+        padding: &[(usize, usize)],
+    ) -> Result<Tensor<AssignedCell<F, F>>, halo2_proofs::plonk::Error> {
+        let assigned = self.assign(region, offset, values)?;
+        if padding.iter().all(|(before, after)| before + after == 0) {
+            return Ok(assigned);
+        }
+        let (x, y) = self.cartesian_coord(offset + assigned.len());
+        let zero = match &self {
+            VarTensor::Advice { inner: advices, .. } => {
+                region.assign_advice_from_constant(|| "pad", advices[x], y, F::zero())?
+            }
+            VarTensor::Fixed { inner: fixed, .. } => {
+                region.assign_fixed(|| "pad", fixed[x], y, || Value::known(F::zero()))?
+            }
+        };
+        assigned
+            .pad(padding, zero)
+            .map_err(|_| halo2_proofs::plonk::Error::Synthesis)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use halo2_proofs::{
+        circuit::{Layouter, SimpleFloorPlanner},
+        dev::MockProver,
+        plonk::{Circuit, Error as PlonkError, Instance},
+    };
+    use halo2curves::pasta::Fp as F;
+
+    const K: usize = 5;
+
+    #[derive(Clone)]
+    struct ConcatCircuit {
+        x: ValTensor<F>,
+        c: ValTensor<F>,
+    }
+
+    impl Circuit<F> for ConcatCircuit {
+        type Config = (VarTensor, Column<Instance>);
+        type FloorPlanner = SimpleFloorPlanner;
+
+        fn without_witnesses(&self) -> Self {
+            self.clone()
+        }
+
+        fn configure(cs: &mut ConstraintSystem<F>) -> Self::Config {
+            let var = VarTensor::new_advice(cs, K, 16, vec![16], true, 16);
+            let instance = cs.instance_column();
+            cs.enable_equality(instance);
+            let constants = cs.fixed_column();
+            cs.enable_constant(constants);
+            (var, instance)
+        }
+
+        fn synthesize(
+            &self,
+            (var, instance): Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), PlonkError> {
+            let x =
+                layouter.assign_region(|| "x", |mut region| var.assign(&mut region, 0, &self.x))?;
+            let x = ValTensor::from(x);
+            let outputs = layouter.assign_region(
+                || "concat and pad",
+                |mut region| {
+                    let concat =
+                        var.assign_concat(&mut region, 0, &[x.clone(), self.c.clone()], 1)?;
+                    let padded =
+                        var.assign_padded(&mut region, concat.len(), &x, &[(0, 1), (1, 0)])?;
+                    Ok(concat
+                        .iter()
+                        .chain(padded.iter())
+                        .cloned()
+                        .collect::<Vec<_>>())
+                },
+            )?;
+            for (i, cell) in outputs.iter().enumerate() {
+                layouter.constrain_instance(cell.cell(), instance, i)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn concatenated_and_padded_cells_are_constrained() {
+        let val = |values: &[u64], dims: &[usize]| {
+            let values = values
+                .iter()
+                .map(|v| Value::known(F::from(*v)))
+                .collect::<Vec<_>>();
+            ValTensor::from(Tensor::new(Some(&values), dims).unwrap())
+        };
+        let circuit = ConcatCircuit {
+            x: val(&[1, 2], &[2, 1]),
+            c: val(&[3, 4, 5, 6], &[2, 2]),
+        };
+        // [[1, 3, 4], [2, 5, 6]] followed by [[0, 1], [0, 2], [0, 0]]
+        let public = [1, 3, 4, 2, 5, 6, 0, 1, 0, 2, 0, 0]
+            .iter()
+            .map(|v| F::from(*v))
+            .collect::<Vec<_>>();
+        let prover = MockProver::run(K as u32, &circuit, vec![public.clone()]).unwrap();
+        prover.assert_satisfied();
+
+        // neither the copies nor the padding can be swapped for other values
+        for i in [0, 6] {
+            let mut wrong = public.clone();
+            wrong[i] += F::one();
+            let prover = MockProver::run(K as u32, &circuit, vec![wrong]).unwrap();
+            assert!(prover.verify().is_err());
+        }
+    }
 }
@@ -0,0 +1,184 @@
+use super::{coordinates, strides, Tensor, TensorError, TensorType};
+use std::ops::Range;
+
+/// A strided view over the values of a [Tensor]: its dims, along with the distance between consecutive elements along each
+/// of them and the position of its first element within the values of the tensor. Layout only ops (permuting axes, slicing,
+/// broadcasting) change the strides and offset of the view rather than copying the values, which are only laid out in row
+/// major order, as a [Tensor], once an op needs them to be (see [TensorView::to_tensor]).
+#[derive(Clone, Debug)]
+pub struct TensorView<'a, T: TensorType> {
+    values: &'a [T],
+    dims: Vec<usize>,
+    strides: Vec<usize>,
+    offset: usize,
+}
+
+impl<T: TensorType> Tensor<T> {
+    /// A strided view over the values of the tensor, spanning all of them in row major order.
+    pub fn strided(&self) -> TensorView<'_, T> {
+        TensorView {
+            values: &self.inner,
+            dims: self.dims.clone(),
+            strides: strides(&self.dims),
+            offset: 0,
+        }
+    }
+}
+
+impl<'a, T: TensorType> TensorView<'a, T> {
+    /// The dims of the view.
+    pub fn dims(&self) -> &[usize] {
+        &self.dims
+    }
+
+    /// The distance between consecutive elements of the view along each of its axes, within the values of the tensor.
+    pub fn strides(&self) -> &[usize] {
+        &self.strides
+    }
+
+    /// The number of elements of the view.
+    pub fn len(&self) -> usize {
+        self.dims.iter().product()
+    }
+
+    /// Whether the view holds no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the view spans the values of the tensor in row major order, such that it is laid out as a tensor without
+    /// reordering its values.
+    pub fn is_contiguous(&self) -> bool {
+        self.offset == 0 && self.len() == self.values.len() && self.strides == strides(&self.dims)
+    }
+
+    /// The element of the view at `coord`.
+    pub fn get(&self, coord: &[usize]) -> &'a T {
+        assert_eq!(coord.len(), self.dims.len());
+        let index = coord
+            .iter()
+            .zip(&self.strides)
+            .map(|(c, s)| c * s)
+            .sum::<usize>();
+        &self.values[self.offset + index]
+    }
+
+    /// Iterates over the elements of the view in row major order.
+    pub fn iter(&self) -> impl Iterator<Item = &'a T> + '_ {
+        coordinates(&self.dims).map(move |coord| self.get(&coord))
+    }
+
+    /// Permutes the axes of the view, such that its axis `i` is the axis `axes[i]` of `self`.
+    ///
+    /// ```
+    /// use ezkl::tensor::Tensor;
+    /// let a = Tensor::<i32>::new(Some(&[1, 2, 3, 4, 5, 6]), &[2, 3]).unwrap();
+    /// let t = a.strided().permute(&[1, 0]).unwrap();
+    /// assert_eq!(t.dims(), &[3, 2]);
+    /// assert_eq!(t.to_tensor(), Tensor::<i32>::new(Some(&[1, 4, 2, 5, 3, 6]), &[3, 2]).unwrap());
+    /// ```
+    pub fn permute(&self, axes: &[usize]) -> Result<Self, TensorError> {
+        let mut sorted = axes.to_vec();
+        sorted.sort_unstable();
+        if sorted != (0..self.dims.len()).collect::<Vec<_>>() {
+            return Err(TensorError::DimMismatch("permute".to_string()));
+        }
+        Ok(TensorView {
+            values: self.values,
+            dims: axes.iter().map(|a| self.dims[*a]).collect(),
+            strides: axes.iter().map(|a| self.strides[*a]).collect(),
+            offset: self.offset,
+        })
+    }
+
+    /// Swaps the axes `a` and `b` of the view.
+    pub fn transpose(&self, a: usize, b: usize) -> Result<Self, TensorError> {
+        let mut axes = (0..self.dims.len()).collect::<Vec<_>>();
+        if a >= axes.len() || b >= axes.len() {
+            return Err(TensorError::DimError);
+        }
+        axes.swap(a, b);
+        self.permute(&axes)
+    }
+
+    /// The elements of the view within `range` along `axis`, keeping the rank of the view (as [Tensor::slice] does).
+    pub fn slice(&self, axis: usize, range: Range<usize>) -> Result<Self, TensorError> {
+        if axis >= self.dims.len() || range.start > range.end || range.end > self.dims[axis] {
+            return Err(TensorError::DimError);
+        }
+        let mut dims = self.dims.clone();
+        dims[axis] = range.end - range.start;
+        Ok(TensorView {
+            values: self.values,
+            dims,
+            strides: self.strides.clone(),
+            offset: self.offset + range.start * self.strides[axis],
+        })
+    }
+
+    /// Broadcasts the view to the shape `dims` (see [Tensor::expand]), with a stride of 0 along the dimensions it repeats.
+    ///
+    /// ```
+    /// use ezkl::tensor::Tensor;
+    /// let bias = Tensor::<i32>::new(Some(&[1, 2]), &[2, 1]).unwrap();
+    /// let t = bias.strided().expand(&[3, 2, 2]).unwrap();
+    /// assert_eq!(t.strides(), &[0, 1, 0]);
+    /// assert_eq!(t.to_tensor(), bias.expand(&[3, 2, 2]).unwrap());
+    /// ```
+    pub fn expand(&self, dims: &[usize]) -> Result<Self, TensorError> {
+        if dims.len() < self.dims.len() {
+            return Err(TensorError::DimMismatch("expand".to_string()));
+        }
+        let lead = dims.len() - self.dims.len();
+        let mut strides = vec![0; dims.len()];
+        for (i, (d, s)) in self.dims.iter().zip(&self.strides).enumerate() {
+            match (*d, dims[lead + i]) {
+                (d, e) if d == e => strides[lead + i] = *s,
+                (1, _) => {}
+                _ => return Err(TensorError::DimMismatch("expand".to_string())),
+            }
+        }
+        Ok(TensorView {
+            values: self.values,
+            dims: dims.to_vec(),
+            strides,
+            offset: self.offset,
+        })
+    }
+
+    /// Lays out the elements of the view as a [Tensor], in row major order.
+    pub fn to_tensor(&self) -> Tensor<T> {
+        let values = match self.is_contiguous() {
+            true => self.values.to_vec(),
+            false => self.iter().cloned().collect(),
+        };
+        // the view spans as many values as its dims hold
+        Tensor::new(Some(&values), &self.dims).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn views_compose_without_copies() {
+        let dims = [2, 3, 4, 5];
+        let a = Tensor::<i32>::new(Some(&(0..120).collect::<Vec<_>>()), &dims).unwrap();
+        // nchw -> nhwc, then the second and third rows of the result
+        let view = a.strided().permute(&[0, 2, 3, 1]).unwrap();
+        assert!(!view.is_contiguous());
+        let rows = view.slice(1, 1..3).unwrap();
+        assert_eq!(rows.dims(), &[2, 2, 5, 3]);
+        for coord in coordinates(rows.dims()) {
+            let (n, h, w, c) = (coord[0], coord[1] + 1, coord[2], coord[3]);
+            assert_eq!(*rows.get(&coord), a.get(&[n, c, h, w]));
+        }
+        // permuting back spans the tensor in order again
+        let back = view.permute(&[0, 3, 1, 2]).unwrap();
+        assert!(back.is_contiguous());
+        assert_eq!(back.to_tensor(), a);
+        assert!(view.permute(&[0, 1, 1, 2]).is_err());
+        assert_eq!(a.strided().transpose(0, 3).unwrap().dims(), &[5, 3, 4, 2]);
+    }
+}
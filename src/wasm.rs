@@ -0,0 +1,90 @@
+// the wasm-bindgen macros expand to unsafe ffi glue
+#![allow(unsafe_code)]
+
+use crate::api;
+use crate::commands::{CircuitSettings, TranscriptType};
+use crate::graph::ModelSource;
+use crate::pfsys::{ModelInput, Proof};
+use clap::ValueEnum;
+use halo2_proofs::poly::commitment::Params;
+use halo2_proofs::poly::kzg::commitment::ParamsKZG;
+use halo2curves::bn256::Bn256;
+use std::fmt::Display;
+use wasm_bindgen::prelude::*;
+
+/// Throws errors of the library as javascript `Error`s.
+fn js_err(e: impl Display) -> JsError {
+    JsError::new(&e.to_string())
+}
+
+fn parse_settings(settings: &str) -> Result<CircuitSettings, JsError> {
+    serde_json::from_str(settings).map_err(js_err)
+}
+
+fn parse_transcript(transcript: &str) -> Result<TranscriptType, JsError> {
+    TranscriptType::from_str(transcript, true).map_err(|e| JsError::new(&e))
+}
+
+fn read_params(params: &[u8]) -> Result<ParamsKZG<Bn256>, JsError> {
+    ParamsKZG::<Bn256>::read(&mut &params[..]).map_err(js_err)
+}
+
+/// Verifies `proof` (the proof bytes, without public inputs) over the public inputs `instances` (a json array holding an array of
+/// quantized values per instance column), against the verifying key `vk` saved by `setup`. The model is that of the .onnx file
+/// contents `model`, built with the json circuit `settings`, and `params` are the KZG params saved by ezkl. Returns whether the
+/// proof is accepted.
+#[wasm_bindgen]
+pub fn verify(
+    proof: &[u8],
+    instances: &str,
+    vk: &[u8],
+    model: &[u8],
+    settings: &str,
+    params: &[u8],
+    transcript: &str,
+) -> Result<bool, JsError> {
+    let transcript = parse_transcript(transcript)?;
+    let settings = parse_settings(settings)?;
+    let params = read_params(params)?;
+    let vk = api::read_verifying_key(model.to_vec(), &settings, vk).map_err(js_err)?;
+    let proof = Proof {
+        public_inputs: serde_json::from_str(instances).map_err(js_err)?,
+        proof: proof.to_vec(),
+        hashes: None,
+        header: None,
+    };
+    Ok(api::verify_with_transcript(
+        &proof, &params, &vk, transcript,
+    ))
+}
+
+/// Checks the circuit of the .onnx file contents `model` is satisfied by the json `data` (as accepted by the cli), returning
+/// whether it is. Only practical for small models, as the circuit is laid out in the browser.
+#[wasm_bindgen]
+pub fn mock(data: &str, model: &[u8], settings: &str) -> Result<bool, JsError> {
+    let data: ModelInput = serde_json::from_str(data).map_err(js_err)?;
+    let settings = parse_settings(settings)?;
+    api::mock(model.to_vec(), &settings, &data).map_err(js_err)
+}
+
+/// Proves the .onnx file contents `model` over the json `data` with the proving key `pk` saved by `setup`, returning the proof
+/// as json (as saved by the `prove` command). Only practical for small models, as the proof is created in the browser.
+#[wasm_bindgen]
+pub fn prove(
+    data: &str,
+    model: &[u8],
+    settings: &str,
+    pk: &[u8],
+    params: &[u8],
+    transcript: &str,
+) -> Result<String, JsError> {
+    let transcript = parse_transcript(transcript)?;
+    let data: ModelInput = serde_json::from_str(data).map_err(js_err)?;
+    let settings = parse_settings(settings)?;
+    let params = read_params(params)?;
+    let model = ModelSource::from(model.to_vec());
+    let keys = api::read_keys(model.clone(), &settings, pk).map_err(js_err)?;
+    let proof = api::prove_with_transcript(model, &settings, &data, &params, &keys, transcript)
+        .map_err(js_err)?;
+    serde_json::to_string(&proof).map_err(js_err)
+}
@@ -58,10 +58,30 @@ macro_rules! test_func {
             use crate::mock;
             use crate::mock_public_inputs;
             use crate::mock_public_params;
-            // use crate::ipa_fullprove;
-            // use crate::ipa_prove_and_verify;
+            use crate::accuracy;
+            use crate::forward;
+            use crate::ipa_fullprove;
+            use crate::ipa_prove_and_verify;
             use crate::kzg_fullprove;
             use crate::kzg_prove_and_verify;
+            use crate::kzg_shplonk_prove_and_verify;
+            use crate::kzg_fullprove_transcripts;
+            use crate::kzg_setup_prove_and_verify;
+            use crate::mock_chunks;
+            use crate::mock_batch;
+            use crate::bench;
+            use crate::mock_commit_params;
+            use crate::mock_hashed;
+            use crate::mock_committed;
+            use crate::mock_attested;
+            use crate::mock_merkle;
+            use crate::mock_settings;
+            use crate::mock_config;
+            use crate::table_json;
+            use crate::estimate;
+            use crate::gen_input;
+            use crate::mock_witness;
+            use crate::serve;
             seq!(N in 0..=11 {
             #(#[test_case(TESTS[N])])*
             fn mock_public_outputs_(test: &str) {
@@ -78,15 +98,25 @@ macro_rules! test_func {
                 mock_public_params(test.to_string());
             }
 
-            // #(#[test_case(TESTS[N])])*
-            // fn ipa_fullprove_(test: &str) {
-            //     ipa_fullprove(test.to_string());
-            // }
+            #(#[test_case(TESTS[N])])*
+            fn accuracy_(test: &str) {
+                accuracy(test.to_string());
+            }
+
+            #(#[test_case(TESTS[N])])*
+            fn forward_(test: &str) {
+                forward(test.to_string());
+            }
+
+            #(#[test_case(TESTS[N])])*
+            fn ipa_fullprove_(test: &str) {
+                ipa_fullprove(test.to_string());
+            }
 
-            // #(#[test_case(TESTS[N])])*
-            // fn ipa_prove_and_verify_(test: &str) {
-            //     ipa_prove_and_verify(test.to_string());
-            // }
+            #(#[test_case(TESTS[N])])*
+            fn ipa_prove_and_verify_(test: &str) {
+                ipa_prove_and_verify(test.to_string());
+            }
 
             #(#[test_case(TESTS[N])])*
             fn kzg_fullprove_(test: &str) {
@@ -97,7 +127,105 @@ macro_rules! test_func {
             fn kzg_prove_and_verify_(test: &str) {
                 kzg_prove_and_verify(test.to_string());
             }
+
+            #(#[test_case(TESTS[N])])*
+            fn kzg_shplonk_prove_and_verify_(test: &str) {
+                kzg_shplonk_prove_and_verify(test.to_string());
+            }
+
+            #(#[test_case(TESTS[N])])*
+            fn kzg_fullprove_transcripts_(test: &str) {
+                kzg_fullprove_transcripts(test.to_string());
+            }
+
+            #(#[test_case(TESTS[N])])*
+            fn kzg_setup_prove_and_verify_(test: &str) {
+                kzg_setup_prove_and_verify(test.to_string());
+            }
             });
+
+            #[test_case("2l_relu_small")]
+            #[test_case("2l_relu_sigmoid_small")]
+            fn mock_chunks_(test: &str) {
+                mock_chunks(test.to_string());
+            }
+
+            #[test_case("1l_mlp")]
+            #[test_case("2l_relu_small")]
+            fn mock_batch_(test: &str) {
+                mock_batch(test.to_string());
+            }
+
+            #[test_case("1l_relu")]
+            fn bench_(test: &str) {
+                bench(test.to_string());
+            }
+
+            #[test_case("1l_mlp")]
+            #[test_case("1l_conv")]
+            fn mock_commit_params_(test: &str) {
+                mock_commit_params(test.to_string());
+            }
+
+            #[test_case("1l_mlp")]
+            #[test_case("2l_relu_small")]
+            fn mock_hashed_(test: &str) {
+                mock_hashed(test.to_string());
+            }
+
+            #[test_case("1l_mlp")]
+            #[test_case("2l_relu_small")]
+            fn mock_committed_(test: &str) {
+                mock_committed(test.to_string());
+            }
+
+            #[test_case("1l_mlp")]
+            fn mock_attested_(test: &str) {
+                mock_attested(test.to_string());
+            }
+
+            #[test_case("1l_mlp")]
+            fn mock_merkle_(test: &str) {
+                mock_merkle(test.to_string());
+            }
+
+            #[test_case("1l_mlp")]
+            fn mock_settings_(test: &str) {
+                mock_settings(test.to_string());
+            }
+
+            #[test_case("1l_mlp")]
+            fn mock_config_(test: &str) {
+                mock_config(test.to_string());
+            }
+
+            #[test_case("2l_relu_small")]
+            fn table_json_(test: &str) {
+                table_json(test.to_string());
+            }
+
+            #[test_case("1l_relu")]
+            #[test_case("2l_relu_small")]
+            fn estimate_(test: &str) {
+                estimate(test.to_string());
+            }
+
+            #[test_case("1l_mlp")]
+            #[test_case("2l_relu_small")]
+            fn gen_input_(test: &str) {
+                gen_input(test.to_string());
+            }
+
+            #[test_case("1l_relu")]
+            #[test_case("2l_relu_small")]
+            fn mock_witness_(test: &str) {
+                mock_witness(test.to_string());
+            }
+
+            #[test_case("1l_relu")]
+            fn serve_(test: &str) {
+                serve(test.to_string());
+            }
     }
     };
 }
@@ -110,13 +238,31 @@ macro_rules! test_func_evm {
             use crate::TESTS_EVM;
             use test_case::test_case;
             use crate::kzg_evm_fullprove;
+            use crate::kzg_evm_aggregate;
+            use crate::kzg_evm_aggregate_chunks;
+            use crate::kzg_evm_aggregate_chain;
             seq!(N in 0..=8 {
             // these take a particularly long time to run
             #(#[test_case(TESTS_EVM[N])])*
             fn kzg_evm_fullprove_(test: &str) {
                 kzg_evm_fullprove(test.to_string());
             }
+
+            #(#[test_case(TESTS_EVM[N])])*
+            fn kzg_evm_aggregate_(test: &str) {
+                kzg_evm_aggregate(test.to_string());
+            }
             });
+
+            #[test_case("2l_relu_small")]
+            fn kzg_evm_aggregate_chunks_(test: &str) {
+                kzg_evm_aggregate_chunks(test.to_string());
+            }
+
+            #[test_case("1l_relu")]
+            fn kzg_evm_aggregate_chain_(test: &str) {
+                kzg_evm_aggregate_chain(test.to_string());
+            }
     }
     };
 }
@@ -249,6 +395,57 @@ fn mock_public_params(example_name: String) {
     assert!(status.success());
 }
 
+// compares the quantized model to the original float model
+fn accuracy(example_name: String) {
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "accuracy",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+}
+
+// runs a forward pass and then mock proves using the generated outputs
+fn forward(example_name: String) {
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "forward",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "-O",
+            format!("forward_{}.json", example_name).as_str(),
+            "--witness-path",
+            format!("forward_{}_witness.json", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "mock",
+            "-D",
+            format!("forward_{}.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+}
+
 // prove-serialize-verify, the usual full path
 fn kzg_prove_and_verify(example_name: String) {
     let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
@@ -291,6 +488,216 @@ fn kzg_prove_and_verify(example_name: String) {
     assert!(status.success());
 }
 
+// setup-prove-verify, reusing the proving key generated by setup
+fn kzg_setup_prove_and_verify(example_name: String) {
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "setup",
+            "--pfsys=kzg",
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--pk-path",
+            format!("kzg_setup_{}.pk", example_name).as_str(),
+            "--vk-path",
+            format!("kzg_setup_{}.vk", example_name).as_str(),
+            "--params-path",
+            format!("kzg_setup_{}.params", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "prove",
+            "--pfsys=kzg",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--proof-path",
+            format!("kzg_setup_{}.pf", example_name).as_str(),
+            "--vk-path",
+            format!("kzg_setup_{}.vk", example_name).as_str(),
+            "--params-path",
+            format!("kzg_setup_{}.params", example_name).as_str(),
+            "--pk-path",
+            format!("kzg_setup_{}.pk", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "verify",
+            "--pfsys=kzg",
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--proof-path",
+            format!("kzg_setup_{}.pf", example_name).as_str(),
+            "--vk-path",
+            format!("kzg_setup_{}.vk", example_name).as_str(),
+            "--params-path",
+            format!("kzg_setup_{}.params", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+    // the proof records the hashes of the artifacts it was generated with
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "hash",
+            "--pfsys=kzg",
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--vk-path",
+            format!("kzg_setup_{}.vk", example_name).as_str(),
+            "--params-path",
+            format!("kzg_setup_{}.params", example_name).as_str(),
+            "--proof-path",
+            format!("kzg_setup_{}.pf", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+    // keys generated for a different circuit should be rejected
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "--scale=6",
+            "prove",
+            "--pfsys=kzg",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--proof-path",
+            format!("kzg_setup_{}.pf", example_name).as_str(),
+            "--vk-path",
+            format!("kzg_setup_{}.vk", example_name).as_str(),
+            "--params-path",
+            format!("kzg_setup_{}.params", example_name).as_str(),
+            "--pk-path",
+            format!("kzg_setup_{}.pk", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(!status.success());
+}
+
+// prove-serialize-verify using the SHPLONK multi-open scheme
+fn kzg_shplonk_prove_and_verify(example_name: String) {
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "prove",
+            "--pfsys=kzg",
+            "--multiopen=shplonk",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--proof-path",
+            format!("kzg_shplonk_{}.pf", example_name).as_str(),
+            "--vk-path",
+            format!("kzg_shplonk_{}.vk", example_name).as_str(),
+            "--params-path",
+            format!("kzg_shplonk_{}.params", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "verify",
+            "--pfsys=kzg",
+            "--multiopen=shplonk",
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--proof-path",
+            format!("kzg_shplonk_{}.pf", example_name).as_str(),
+            "--vk-path",
+            format!("kzg_shplonk_{}.vk", example_name).as_str(),
+            "--params-path",
+            format!("kzg_shplonk_{}.params", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+}
+
+// IPA tests
+// prove-serialize-verify, the usual full path
+fn ipa_prove_and_verify(example_name: String) {
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "prove",
+            "--pfsys=ipa",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--proof-path",
+            format!("ipa_{}.pf", example_name).as_str(),
+            "--vk-path",
+            format!("ipa_{}.vk", example_name).as_str(),
+            "--params-path",
+            format!("ipa_{}.params", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "verify",
+            "--pfsys=ipa",
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--proof-path",
+            format!("ipa_{}.pf", example_name).as_str(),
+            "--vk-path",
+            format!("ipa_{}.vk", example_name).as_str(),
+            "--params-path",
+            format!("ipa_{}.params", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+}
+
+// full prove (slower, covers more, but still reuses the pk)
+fn ipa_fullprove(example_name: String) {
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "fullprove",
+            "--pfsys=ipa",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+}
+
 // KZG  tests
 // full prove (slower, covers more, but still reuses the pk)
 fn kzg_fullprove(example_name: String) {
@@ -310,6 +717,27 @@ fn kzg_fullprove(example_name: String) {
     assert!(status.success());
 }
 
+// full prove with each of the non-default transcripts
+fn kzg_fullprove_transcripts(example_name: String) {
+    for transcript in ["keccak", "poseidon"] {
+        let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+            .args([
+                "--bits=16",
+                "-K=17",
+                "fullprove",
+                "--pfsys=kzg",
+                format!("--transcript={}", transcript).as_str(),
+                "-D",
+                format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+                "-M",
+                format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            ])
+            .status()
+            .expect("failed to execute process");
+        assert!(status.success());
+    }
+}
+
 // KZG / EVM tests
 // full prove (slower, covers more, but still reuses the pk)
 fn kzg_evm_fullprove(example_name: String) {
@@ -336,6 +764,818 @@ fn kzg_evm_fullprove(example_name: String) {
     assert!(status.success());
 }
 
+// prove two different models with poseidon transcripts and aggregate the proofs
+fn kzg_evm_aggregate(example_name: String) {
+    let run = |args: &[&str]| {
+        let status = Command::new("cargo")
+            .args([
+                "run",
+                "--release",
+                "--features",
+                "evm",
+                "--bin",
+                "ezkl",
+                "--",
+                "--bits=16",
+            ])
+            .args(args)
+            .status()
+            .expect("failed to execute process");
+        assert!(status.success());
+    };
+    let srs_path = format!("kzg_aggr_{}.srs", example_name);
+    run(&["-K=23", "gen-srs", "--params-path", srs_path.as_str()]);
+
+    let models = [example_name.clone(), "1l_relu".to_string()];
+    for (i, model) in models.iter().enumerate() {
+        run(&[
+            "-K=17",
+            "--srs-path",
+            srs_path.as_str(),
+            "prove",
+            "--pfsys=kzg",
+            "--transcript=poseidon",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", model).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", model).as_str(),
+            "--proof-path",
+            format!("kzg_aggr_{}_{}.pf", example_name, i).as_str(),
+            "--vk-path",
+            format!("kzg_aggr_{}_{}.vk", example_name, i).as_str(),
+            "--params-path",
+            format!("kzg_aggr_{}_{}.params", example_name, i).as_str(),
+        ]);
+    }
+
+    run(&[
+        "-K=17",
+        "--srs-path",
+        srs_path.as_str(),
+        "aggregate",
+        "--gas-report",
+        "--yul-path",
+        format!("kzg_aggr_{}.yul", example_name).as_str(),
+        "--sol-path",
+        format!("kzg_aggr_{}.sol", example_name).as_str(),
+        "--attestation-path",
+        format!("kzg_aggr_{}_attestation.sol", example_name).as_str(),
+        "-M",
+        format!("./examples/onnx/examples/{}/network.onnx", models[0]).as_str(),
+        format!("./examples/onnx/examples/{}/network.onnx", models[1]).as_str(),
+        "--proof-path",
+        format!("kzg_aggr_{}_0.pf", example_name).as_str(),
+        format!("kzg_aggr_{}_1.pf", example_name).as_str(),
+        "--vk-path",
+        format!("kzg_aggr_{}_0.vk", example_name).as_str(),
+        format!("kzg_aggr_{}_1.vk", example_name).as_str(),
+        "--aggregation-proof-path",
+        format!("kzg_aggr_{}.pf", example_name).as_str(),
+        "--deployment-code-path",
+        format!("kzg_aggr_{}.code", example_name).as_str(),
+    ]);
+    // each contract of the verifier (one per line, several if it is split) is within the 24KB contract size limit
+    let code = std::fs::read_to_string(format!("kzg_aggr_{}.code", example_name)).unwrap();
+    for contract in code.lines() {
+        assert!(contract.trim_start_matches("0x").len() / 2 <= 24576);
+    }
+
+    run(&[
+        "encode-evm-calldata",
+        "--aggregation-proof-path",
+        format!("kzg_aggr_{}.pf", example_name).as_str(),
+        "--calldata-path",
+        format!("kzg_aggr_{}.calldata", example_name).as_str(),
+        "--fixture-path",
+        format!("kzg_aggr_{}_fixture.json", example_name).as_str(),
+    ]);
+}
+
+// mock prove each chunk of a model split in two
+fn mock_chunks(example_name: String) {
+    for chunk in 0..2 {
+        let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+            .args([
+                "--bits=16",
+                "-K=17",
+                "--cut-points=1",
+                format!("--chunk={}", chunk).as_str(),
+                "mock",
+                "-D",
+                format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+                "-M",
+                format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            ])
+            .status()
+            .expect("failed to execute process");
+        assert!(status.success());
+    }
+    // the model only has so many execution buckets
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "--cut-points=1",
+            "--chunk=2",
+            "mock",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(!status.success());
+}
+
+// mock prove a batch of two copies of the example input in a single circuit
+fn mock_batch(example_name: String) {
+    let data = std::fs::read_to_string(format!(
+        "./examples/onnx/examples/{}/input.json",
+        example_name
+    ))
+    .unwrap();
+    let mut data: serde_json::Value = serde_json::from_str(&data).unwrap();
+    for shape in data["input_shapes"].as_array_mut().unwrap() {
+        shape.as_array_mut().unwrap().insert(0, 2.into());
+    }
+    for key in ["input_data", "output_data"] {
+        for values in data[key].as_array_mut().unwrap() {
+            let values = values.as_array_mut().unwrap();
+            values.extend(values.clone());
+        }
+    }
+    let batch_path = format!("batch_{}.json", example_name);
+    std::fs::write(&batch_path, data.to_string()).unwrap();
+
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "--batch-size=2",
+            "mock",
+            "-D",
+            batch_path.as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+    // the data doesn't hold a batch of three samples
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "--batch-size=3",
+            "mock",
+            "-D",
+            batch_path.as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(!status.success());
+}
+
+// benchmark proof generation, checking each phase is reported
+fn bench(example_name: String) {
+    let report_path = format!("bench_{}.json", example_name);
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "bench",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--output",
+            report_path.as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+    let report: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(report_path).unwrap()).unwrap();
+    let phases = report["phases"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["phase"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        phases,
+        [
+            "graph load",
+            "quantization",
+            "witness generation",
+            "srs",
+            "keygen",
+            "proving",
+            "verification"
+        ]
+    );
+    assert!(report["circuit"]["rows_used"].as_u64().unwrap() > 0);
+}
+
+// mock prove with the model's params committed to by a public hash
+fn mock_commit_params(example_name: String) {
+    let run = |args: &[&str]| {
+        Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+            .args(["--bits=16", "-K=17", "--commit-params"])
+            .args(args)
+            .args([
+                "mock",
+                "-D",
+                format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+                "-M",
+                format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            ])
+            .status()
+            .expect("failed to execute process")
+    };
+    assert!(run(&[]).success());
+    // public params can't be committed to
+    assert!(!run(&["--public-params"]).success());
+}
+
+// mock prove with hashed inputs and outputs, separately and together
+fn mock_hashed(example_name: String) {
+    for flags in [
+        vec!["--hashed-inputs"],
+        vec!["--hashed-outputs"],
+        vec!["--hashed-inputs", "--hashed-outputs"],
+    ] {
+        let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+            .args(["--bits=16", "-K=17"])
+            .args(flags)
+            .args([
+                "mock",
+                "-D",
+                format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+                "-M",
+                format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            ])
+            .status()
+            .expect("failed to execute process");
+        assert!(status.success());
+    }
+}
+
+// mock prove with inputs committed to with a key, which fails when the data file holds no key
+fn mock_committed(example_name: String) {
+    let data_path = format!("./examples/onnx/examples/{}/input.json", example_name);
+    let mut data: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&data_path).unwrap()).unwrap();
+    data["input_key"] = serde_json::json!([4821, 60213, 117, 39027, 8841, 23318, 51160, 7402]);
+    let keyed_path = format!("keyed_{}.json", example_name);
+    std::fs::write(&keyed_path, data.to_string()).unwrap();
+
+    for (path, succeeds) in [(keyed_path.as_str(), true), (data_path.as_str(), false)] {
+        let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+            .args([
+                "--bits=16",
+                "-K=17",
+                "--committed-inputs",
+                "mock",
+                "-D",
+                path,
+                "-M",
+                format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            ])
+            .status()
+            .expect("failed to execute process");
+        assert_eq!(status.success(), succeeds);
+    }
+}
+
+// sign the inputs of an example and mock prove them as attested, which fails for unsigned inputs
+fn mock_attested(example_name: String) {
+    let data_path = format!("./examples/onnx/examples/{}/input.json", example_name);
+    let model_path = format!("./examples/onnx/examples/{}/network.onnx", example_name);
+    let key_path = format!("{}.key", example_name);
+    let signed_path = format!("signed_{}.json", example_name);
+    let _ = std::fs::remove_file(&key_path);
+
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "sign",
+            "-D",
+            data_path.as_str(),
+            "-M",
+            model_path.as_str(),
+            "--key-path",
+            key_path.as_str(),
+            "-O",
+            signed_path.as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+
+    for (path, succeeds) in [(signed_path.as_str(), true), (data_path.as_str(), false)] {
+        let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+            .args([
+                "--bits=16",
+                "-K=17",
+                "--attested-inputs",
+                "mock",
+                "-D",
+                path,
+                "-M",
+                model_path.as_str(),
+            ])
+            .status()
+            .expect("failed to execute process");
+        assert_eq!(status.success(), succeeds);
+    }
+}
+
+// build a merkle tree over a dataset of two records and prove the second is a leaf of it
+fn mock_merkle(example_name: String) {
+    let data_path = format!("./examples/onnx/examples/{}/input.json", example_name);
+    let model_path = format!("./examples/onnx/examples/{}/network.onnx", example_name);
+    let leaf_path = format!("leaf_{}.json", example_name);
+
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "--merkle-depth=2",
+            "merkle-path",
+            "-D",
+            data_path.as_str(),
+            data_path.as_str(),
+            "-M",
+            model_path.as_str(),
+            "--index",
+            "1",
+            "-O",
+            leaf_path.as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+
+    for (path, succeeds) in [(leaf_path.as_str(), true), (data_path.as_str(), false)] {
+        let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+            .args([
+                "--bits=16",
+                "-K=17",
+                "--merkle-depth=2",
+                "mock",
+                "-D",
+                path,
+                "-M",
+                model_path.as_str(),
+            ])
+            .status()
+            .expect("failed to execute process");
+        assert_eq!(status.success(), succeeds);
+    }
+}
+
+// run mock with the settings of a settings file, alongside flags which agree and disagree with it
+fn mock_settings(example_name: String) {
+    let settings_path = format!("settings_{}.json", example_name);
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "--public-inputs",
+            "gen-settings",
+            "--settings-path",
+            settings_path.as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+
+    for (flags, succeeds) in [
+        (vec![], true),
+        (vec!["--bits=16"], true),
+        (vec!["--bits=14"], false),
+    ] {
+        let mut args = vec!["--settings-path", settings_path.as_str()];
+        args.extend(flags);
+        let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+            .args(args)
+            .args([
+                "mock",
+                "-D",
+                format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+                "-M",
+                format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            ])
+            .status()
+            .expect("failed to execute process");
+        assert_eq!(status.success(), succeeds);
+    }
+}
+
+// run mock with a config file setting too few rows, which is overridden by flags and environment variables
+fn mock_config(example_name: String) {
+    let config_path = format!("config_{}.toml", example_name);
+    std::fs::write(&config_path, "bits = 16\nlogrows = 1\n").unwrap();
+
+    for (flags, env, succeeds) in [
+        (vec![], None, false),
+        (vec!["-K=17"], None, true),
+        (vec![], Some("17"), true),
+    ] {
+        let mut command = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR));
+        if let Some(logrows) = env {
+            command.env("EZKL_LOGROWS", logrows);
+        }
+        let status = command
+            .args(["--config", config_path.as_str()])
+            .args(flags)
+            .args([
+                "mock",
+                "-D",
+                format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+                "-M",
+                format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            ])
+            .status()
+            .expect("failed to execute process");
+        assert_eq!(status.success(), succeeds);
+    }
+}
+
+// print the nodes of a model as json, followed by the outcome of the command
+fn table_json(example_name: String) {
+    let output = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "--json",
+            "table",
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--format",
+            "json",
+        ])
+        .output()
+        .expect("failed to execute process");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    let nodes: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+    assert!(!nodes.as_array().unwrap().is_empty());
+    let report: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+    assert_eq!(report["command"], "table");
+    assert_eq!(report["success"], true);
+}
+
+// estimate the cost of a circuit, which must fit in the rows it is configured with
+fn estimate(example_name: String) {
+    let output = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "estimate",
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+        ])
+        .output()
+        .expect("failed to execute process");
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(stats["min_logrows"].as_u64().unwrap() <= 17);
+    assert!(!stats["bucket_rows"].as_array().unwrap().is_empty());
+    assert!(stats["lookup_table_rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|rows| rows == 1 << 16));
+}
+
+// generate random inputs twice with the same seed, and run mock with them as public inputs and outputs
+fn gen_input(example_name: String) {
+    let model = format!("./examples/onnx/examples/{}/network.onnx", example_name);
+    let data_path = format!("random_input_{}.json", example_name);
+    let mut generated = vec![];
+    for _ in 0..2 {
+        let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+            .args([
+                "--bits=16",
+                "-K=17",
+                "gen-input",
+                "-M",
+                model.as_str(),
+                "-O",
+                data_path.as_str(),
+                "--seed=42",
+            ])
+            .status()
+            .expect("failed to execute process");
+        assert!(status.success());
+        generated.push(std::fs::read_to_string(&data_path).unwrap());
+    }
+    assert_eq!(generated[0], generated[1]);
+
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "--public-inputs",
+            "--public-outputs",
+            "mock",
+            "-D",
+            data_path.as_str(),
+            "-M",
+            model.as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+}
+
+// run mock saving the witness of every node, whose shapes must match their values
+fn mock_witness(example_name: String) {
+    let witness_path = format!("witness_{}.json", example_name);
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "mock",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--witness-path",
+            witness_path.as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+    let witness: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&witness_path).unwrap()).unwrap();
+    let nodes = witness.as_object().unwrap();
+    assert!(nodes.values().any(|n| n["op"] == "input"));
+    for node in nodes.values() {
+        let len: u64 = node["dims"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d.as_u64().unwrap())
+            .product();
+        assert_eq!(node["values"].as_array().unwrap().len() as u64, len);
+    }
+}
+
+// serve proofs of a model over http, proving and verifying an input with the running daemon
+fn serve(example_name: String) {
+    let model = format!("./examples/onnx/examples/{}/network.onnx", example_name);
+    let pk_path = format!("serve_{}.pk", example_name);
+    let params_path = format!("serve_{}.params", example_name);
+    let status = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "setup",
+            "--pfsys=kzg",
+            "-M",
+            model.as_str(),
+            "--pk-path",
+            pk_path.as_str(),
+            "--vk-path",
+            format!("serve_{}.vk", example_name).as_str(),
+            "--params-path",
+            params_path.as_str(),
+        ])
+        .status()
+        .expect("failed to execute process");
+    assert!(status.success());
+
+    let addr = "127.0.0.1:8731";
+    let mut daemon = Command::new(format!("{}/release/ezkl", *CARGO_TARGET_DIR))
+        .args([
+            "--bits=16",
+            "-K=17",
+            "serve",
+            "-M",
+            model.as_str(),
+            "--pk-path",
+            pk_path.as_str(),
+            "--params-path",
+            params_path.as_str(),
+            "--addr",
+            addr,
+        ])
+        .spawn()
+        .expect("failed to execute process");
+
+    let data = std::fs::read_to_string(format!(
+        "./examples/onnx/examples/{}/input.json",
+        example_name
+    ))
+    .unwrap();
+    let client = reqwest::blocking::Client::builder()
+        .timeout(None)
+        .build()
+        .unwrap();
+    // the daemon only listens once the model, params and proving key are loaded
+    let proof = (0..60)
+        .find_map(|_| {
+            match client
+                .post(format!("http://{}/prove", addr))
+                .body(data.clone())
+                .send()
+            {
+                Ok(res) => Some(res),
+                Err(_) => {
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                    None
+                }
+            }
+        })
+        .expect("the daemon didn't start");
+    assert!(proof.status().is_success());
+    let proof = proof.text().unwrap();
+    let verified = client
+        .post(format!("http://{}/verify", addr))
+        .body(proof)
+        .send()
+        .unwrap()
+        .text()
+        .unwrap();
+    let verified: serde_json::Value = serde_json::from_str(&verified).unwrap();
+    let missing = client
+        .post(format!("http://{}/missing", addr))
+        .send()
+        .unwrap();
+    daemon.kill().unwrap();
+    assert_eq!(verified["verified"], true);
+    assert_eq!(missing.status().as_u16(), 404);
+}
+
+// prove each chunk of a model split in two and aggregate the chunk proofs
+fn kzg_evm_aggregate_chunks(example_name: String) {
+    let run = |args: &[&str]| {
+        let status = Command::new("cargo")
+            .args([
+                "run",
+                "--release",
+                "--features",
+                "evm",
+                "--bin",
+                "ezkl",
+                "--",
+                "--bits=16",
+                "--cut-points=1",
+            ])
+            .args(args)
+            .status()
+            .expect("failed to execute process");
+        assert!(status.success());
+    };
+    let srs_path = format!("kzg_chunks_{}.srs", example_name);
+    run(&["-K=23", "gen-srs", "--params-path", srs_path.as_str()]);
+
+    for chunk in 0..2 {
+        run(&[
+            "-K=17",
+            "--srs-path",
+            srs_path.as_str(),
+            format!("--chunk={}", chunk).as_str(),
+            "prove",
+            "--pfsys=kzg",
+            "--transcript=poseidon",
+            "-D",
+            format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+            "-M",
+            format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+            "--proof-path",
+            format!("kzg_chunks_{}_{}.pf", example_name, chunk).as_str(),
+            "--vk-path",
+            format!("kzg_chunks_{}_{}.vk", example_name, chunk).as_str(),
+            "--params-path",
+            format!("kzg_chunks_{}_{}.params", example_name, chunk).as_str(),
+        ]);
+    }
+
+    run(&[
+        "-K=17",
+        "--srs-path",
+        srs_path.as_str(),
+        "aggregate",
+        "-M",
+        format!("./examples/onnx/examples/{}/network.onnx", example_name).as_str(),
+        "--proof-path",
+        format!("kzg_chunks_{}_0.pf", example_name).as_str(),
+        format!("kzg_chunks_{}_1.pf", example_name).as_str(),
+        "--vk-path",
+        format!("kzg_chunks_{}_0.vk", example_name).as_str(),
+        format!("kzg_chunks_{}_1.vk", example_name).as_str(),
+        "--aggregation-proof-path",
+        format!("kzg_chunks_{}.pf", example_name).as_str(),
+        "--deployment-code-path",
+        format!("kzg_chunks_{}.code", example_name).as_str(),
+    ]);
+}
+
+// chain a model with itself, feeding the outputs of the first stage into the second, and aggregate the linked proofs
+fn kzg_evm_aggregate_chain(example_name: String) {
+    let run = |args: &[&str]| {
+        let status = Command::new("cargo")
+            .args([
+                "run",
+                "--release",
+                "--features",
+                "evm",
+                "--bin",
+                "ezkl",
+                "--",
+                "--bits=16",
+                "--hashed-inputs",
+                "--hashed-outputs",
+            ])
+            .args(args)
+            .status()
+            .expect("failed to execute process");
+        assert!(status.success());
+    };
+    let model_path = format!("./examples/onnx/examples/{}/network.onnx", example_name);
+    let srs_path = format!("kzg_chain_{}.srs", example_name);
+    run(&["-K=23", "gen-srs", "--params-path", srs_path.as_str()]);
+
+    let data_paths = [
+        format!("kzg_chain_{}_0.json", example_name),
+        format!("kzg_chain_{}_1.json", example_name),
+    ];
+    let next_path = format!("kzg_chain_{}_next.json", example_name);
+    run(&[
+        "-K=17",
+        "forward",
+        "-D",
+        format!("./examples/onnx/examples/{}/input.json", example_name).as_str(),
+        "-M",
+        model_path.as_str(),
+        "-O",
+        data_paths[0].as_str(),
+        "--next-data",
+        next_path.as_str(),
+    ]);
+    run(&[
+        "-K=17",
+        "forward",
+        "-D",
+        next_path.as_str(),
+        "-M",
+        model_path.as_str(),
+        "-O",
+        data_paths[1].as_str(),
+    ]);
+
+    for (stage, data_path) in data_paths.iter().enumerate() {
+        run(&[
+            "-K=17",
+            "--srs-path",
+            srs_path.as_str(),
+            "prove",
+            "--pfsys=kzg",
+            "--transcript=poseidon",
+            "-D",
+            data_path.as_str(),
+            "-M",
+            model_path.as_str(),
+            "--proof-path",
+            format!("kzg_chain_{}_{}.pf", example_name, stage).as_str(),
+            "--vk-path",
+            format!("kzg_chain_{}_{}.vk", example_name, stage).as_str(),
+            "--params-path",
+            format!("kzg_chain_{}_{}.params", example_name, stage).as_str(),
+        ]);
+    }
+
+    run(&[
+        "-K=17",
+        "--srs-path",
+        srs_path.as_str(),
+        "aggregate",
+        "--chain",
+        "-M",
+        model_path.as_str(),
+        model_path.as_str(),
+        "--proof-path",
+        format!("kzg_chain_{}_0.pf", example_name).as_str(),
+        format!("kzg_chain_{}_1.pf", example_name).as_str(),
+        "--vk-path",
+        format!("kzg_chain_{}_0.vk", example_name).as_str(),
+        format!("kzg_chain_{}_1.vk", example_name).as_str(),
+        "--aggregation-proof-path",
+        format!("kzg_chain_{}.pf", example_name).as_str(),
+        "--deployment-code-path",
+        format!("kzg_chain_{}.code", example_name).as_str(),
+    ]);
+}
+
 fn build_ezkl() {
     let status = Command::new("cargo")
         .args(["build", "--release", "--bin", "ezkl"])